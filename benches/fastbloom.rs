@@ -2,7 +2,7 @@ use std::hash::{Hash, Hasher};
 use std::ops::Range;
 
 use crc32fast::Hasher as CRCHasher;
-use criterion::{black_box, Criterion, criterion_group, criterion_main};
+use criterion::{black_box, Criterion, criterion_group, criterion_main, Throughput};
 use fastmurmur3::murmur3_x64_128;
 use fxhash::{FxHasher64, hash64};
 use getrandom::getrandom;
@@ -159,7 +159,8 @@ fn counting_bloom_add_bench(c: &mut Criterion) {
 
     let hello = "hellohellohellohello".to_string();
 
-    let mut filter = FilterBuilder::new(items_count as u64, 0.001).build_counting_bloom_filter();
+    let mut filter = FilterBuilder::new(items_count as u64, 0.001)
+        .build_counting_bloom_filter::<Vec<usize>>(());
 
     c.bench_function("counting_bloom_add_test", |b| b.iter(|| filter.add(black_box(hello.as_bytes()))));
     c.bench_function("counting_bloom_add_million_test", |b| b.iter(|| for input in inputs.iter() {
@@ -167,5 +168,21 @@ fn counting_bloom_add_bench(c: &mut Criterion) {
     }));
 }
 
-criterion_group!(benches, bloom_add_bench, counting_bloom_add_bench);
+fn bloom_merge_bench(c: &mut Criterion) {
+    // 8 MB filters, merged like per-shard filters into one
+    let mut builder = FilterBuilder::from_size_and_hashes(8 << 23, 7);
+    let mut filter = builder.build_bloom_filter();
+    let mut shard = builder.build_bloom_filter();
+    for i in 0..100_000u64 {
+        shard.add(&i.to_le_bytes());
+    }
+
+    let mut group = c.benchmark_group("bloom_merge_8mb");
+    group.throughput(Throughput::Bytes(8 << 20));
+    group.bench_function("union", |b| b.iter(|| filter.union(black_box(&shard))));
+    group.bench_function("intersect", |b| b.iter(|| filter.intersect(black_box(&shard))));
+    group.finish();
+}
+
+criterion_group!(benches, bloom_add_bench, counting_bloom_add_bench, bloom_merge_bench);
 criterion_main!(benches);
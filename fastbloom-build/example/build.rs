@@ -0,0 +1,6 @@
+fn main() {
+    fastbloom_build::FilterGenerator::new(0.001)
+        .seed(7)
+        .words_file("blocklist.txt").unwrap()
+        .write("blocklist.bin").unwrap();
+}
@@ -0,0 +1,18 @@
+//! A crate embedding a blocklist generated by its build script, see `build.rs`.
+
+use fastbloom_rs::StaticFilterRef;
+
+/// Returns the embedded blocklist.
+pub fn blocklist() -> StaticFilterRef {
+    fastbloom_rs::include_filter!("blocklist.bin")
+}
+
+#[test]
+fn include_filter_test() {
+    let blocklist = blocklist();
+    assert_eq!(blocklist.config().seed, 7);
+    assert!(blocklist.contains(b"evil.example.com"));
+    assert!(blocklist.contains(b"tracker.example.net"));
+    assert!(!blocklist.contains(b"# hosts of the example blocklist"));
+    assert!(!blocklist.contains(b"example.com"));
+}
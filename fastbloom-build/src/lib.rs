@@ -0,0 +1,139 @@
+//! Generates [fastbloom_rs] filters from word lists in build scripts, to be embedded into the
+//! binary with [fastbloom_rs::include_filter].
+//!
+//! In `build.rs`:
+//!
+//! ```no_run
+//! fastbloom_build::FilterGenerator::new(0.001)
+//!     .words_file("blocklist.txt").unwrap()
+//!     .write("blocklist.bin").unwrap();
+//! ```
+//!
+//! and in the crate:
+//!
+//! ```ignore
+//! let blocklist = fastbloom_rs::include_filter!("blocklist.bin");
+//! assert!(blocklist.contains(b"evil.example.com"));
+//! ```
+//!
+//! The filters are sized in whole 64-bit words, so a build script running on a 32-bit host
+//! embeds the same filter as any other. The `example` crate next to this one is such a build.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use fastbloom_rs::{FilterBuilder, Membership, params};
+
+/// Collects words and serializes a Bloom filter of them in the format of
+/// [fastbloom_rs::StaticFilterRef].
+#[derive(Clone)]
+#[derive(Debug)]
+pub struct FilterGenerator {
+    false_positive_probability: f64,
+    seed: u64,
+    words: Vec<Vec<u8>>,
+    sources: Vec<PathBuf>,
+}
+
+impl FilterGenerator {
+    /// Starts a generator for a filter with the given false positive probability.
+    pub fn new(false_positive_probability: f64) -> Self {
+        FilterGenerator { false_positive_probability, seed: 0, words: Vec::new(), sources: Vec::new() }
+    }
+
+    /// set the seed of the hash functions, see [FilterBuilder::seed].
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    /// Adds a single word.
+    pub fn word(mut self, word: impl AsRef<[u8]>) -> Self {
+        self.words.push(word.as_ref().to_vec());
+        self
+    }
+
+    /// Adds every line of the file at `path` as one word. Surrounding whitespace is trimmed,
+    /// empty lines and lines starting with `#` are skipped. The build script is rerun when the
+    /// file changes, see [FilterGenerator::write].
+    pub fn words_file(mut self, path: impl AsRef<Path>) -> io::Result<Self> {
+        let text = fs::read_to_string(path.as_ref())?;
+        self.words.extend(text.lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(|line| line.as_bytes().to_vec()));
+        self.sources.push(path.as_ref().to_path_buf());
+        Ok(self)
+    }
+
+    /// Returns the number of collected words.
+    pub fn len(&self) -> usize {
+        self.words.len()
+    }
+
+    /// Returns [true] if no word was collected.
+    pub fn is_empty(&self) -> bool {
+        self.words.is_empty()
+    }
+
+    /// Builds the filter and returns its serialized form.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let elements = (self.words.len() as u64).max(1);
+        // whole u64 words, which [fastbloom_rs::StaticFilterRef] needs also on 32-bit hosts
+        let size = params::optimal_bits(elements, self.false_positive_probability)
+            .next_multiple_of(64);
+        let mut builder =
+            FilterBuilder::from_size_and_hashes(size, params::optimal_hashes(size, elements));
+        builder.seed(self.seed);
+        let mut bloom = builder.build_bloom_filter();
+        for word in self.words.iter() {
+            bloom.add(word);
+        }
+        bloom.to_static_bytes()
+    }
+
+    /// Writes the serialized filter to `name` in the `OUT_DIR` of the build script, where
+    /// [fastbloom_rs::include_filter] looks for it, and returns the written path.
+    ///
+    /// Declares the inputs of the filter to cargo: the word files, and the build script itself
+    /// if there are none, so the build script is never rerun for unrelated changes. A build
+    /// script which reads further inputs must declare them as well.
+    pub fn write(&self, name: &str) -> io::Result<PathBuf> {
+        let out_dir = std::env::var_os("OUT_DIR")
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "OUT_DIR is not set"))?;
+        let path = Path::new(&out_dir).join(name);
+        self.write_to(&path)?;
+        if self.sources.is_empty() {
+            println!("cargo:rerun-if-changed=build.rs");
+        }
+        for source in self.sources.iter() {
+            println!("cargo:rerun-if-changed={}", source.display());
+        }
+        Ok(path)
+    }
+
+    /// Writes the serialized filter to `path`, without declaring anything to cargo.
+    pub fn write_to(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        fs::write(path, self.to_bytes())
+    }
+}
+
+#[test]
+fn generator_test() {
+    use fastbloom_rs::StaticFilterRef;
+
+    let path = std::env::temp_dir().join(format!("fastbloom-build-{}.txt", std::process::id()));
+    fs::write(&path, "# blocklist\nevil.example.com\n\n  spam.example.com  \n").unwrap();
+    let generator = FilterGenerator::new(0.001).seed(5).words_file(&path).unwrap().word("extra");
+    fs::remove_file(&path).unwrap();
+    assert_eq!(generator.len(), 3);
+
+    let filter = StaticFilterRef::from_static(generator.to_bytes().leak()).unwrap();
+    assert_eq!(filter.config().seed, 5);
+    assert_eq!(filter.config().size % 64, 0);
+    assert!(filter.contains(b"evil.example.com"));
+    assert!(filter.contains(b"spam.example.com"));
+    assert!(filter.contains(b"extra"));
+    assert!(!filter.contains(b"# blocklist"));
+}
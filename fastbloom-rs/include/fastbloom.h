@@ -0,0 +1,138 @@
+#ifndef FASTBLOOM_H
+#define FASTBLOOM_H
+
+/* Generated with cbindgen from src/ffi.rs, do not edit. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/**
+ * Version of the C interface, incremented on incompatible changes of the declarations.
+ */
+#define FASTBLOOM_ABI_VERSION 1
+
+/**
+ * Opaque handle of a [BloomFilter] for the C interface declared in `include/fastbloom.h`
+ * (regenerate it with `cbindgen --config cbindgen.toml --output include/fastbloom.h`). Build the
+ * library with `cargo rustc --release --features ffi --crate-type staticlib` (or `cdylib`).
+ *
+ * The functions never unwind into C, invalid arguments are reported as `NULL` or `false`. A
+ * filter may be queried from several threads at once, but updates need exclusive access.
+ */
+typedef struct FastBloom FastBloom;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ * Returns [FASTBLOOM_ABI_VERSION] of the linked library, to be compared with the version of the
+ * header.
+ */
+uint32_t fastbloom_abi_version(void);
+
+/**
+ * Creates an empty filter for `expected_elements` at `false_positive_probability`. Returns
+ * `NULL` if `expected_elements` is 0, the probability is not in `(0, 1)` or the filter is too
+ * large. Free it with [fastbloom_free].
+ */
+FastBloom *fastbloom_new(uint64_t expected_elements, double false_positive_probability);
+
+/**
+ * Frees a filter, `NULL` is ignored.
+ *
+ * # Safety
+ *
+ * `filter` must be `NULL` or returned by this library and not freed before.
+ */
+void fastbloom_free(FastBloom *filter);
+
+/**
+ * Adds the `len` bytes at `element` to the filter.
+ *
+ * # Safety
+ *
+ * `filter` must be a valid filter not accessed by other threads, `element` must point to `len`
+ * readable bytes.
+ */
+void fastbloom_add(FastBloom *filter, const uint8_t *element, size_t len);
+
+/**
+ * Tests whether the `len` bytes at `element` are present in the filter (subject to the false
+ * positive probability).
+ *
+ * # Safety
+ *
+ * `filter` must be a valid filter not updated by other threads, `element` must point to `len`
+ * readable bytes.
+ */
+bool fastbloom_contains(const FastBloom *filter, const uint8_t *element, size_t len);
+
+/**
+ * Removes all elements from the filter.
+ *
+ * # Safety
+ *
+ * `filter` must be a valid filter not accessed by other threads.
+ */
+void fastbloom_clear(FastBloom *filter);
+
+/**
+ * Adds all elements of `other` to `filter`. Returns `false` and leaves `filter` unchanged if
+ * the filters were not built with the same parameters.
+ *
+ * # Safety
+ *
+ * Both must be valid filters, `filter` must not be accessed by other threads and must not be
+ * `other`.
+ */
+bool fastbloom_union(FastBloom *filter, const FastBloom *other);
+
+/**
+ * Returns the number of hash functions of the filter.
+ *
+ * # Safety
+ *
+ * `filter` must be a valid filter.
+ */
+uint32_t fastbloom_hashes(const FastBloom *filter);
+
+/**
+ * Returns the size of the filter in bits.
+ *
+ * # Safety
+ *
+ * `filter` must be a valid filter.
+ */
+uint64_t fastbloom_size(const FastBloom *filter);
+
+/**
+ * Serializes the filter like [BloomFilter::to_static_bytes] into `out` and returns the length
+ * of the serialized filter. Nothing is written if it exceeds `capacity`, so call it with a
+ * `capacity` of 0 first to learn the length.
+ *
+ * # Safety
+ *
+ * `filter` must be a valid filter not updated by other threads, `out` must point to `capacity`
+ * writable bytes.
+ */
+size_t fastbloom_to_bytes(const FastBloom *filter, uint8_t *out, size_t capacity);
+
+/**
+ * Restores a filter written by [fastbloom_to_bytes] (or [BloomFilter::to_static_bytes]).
+ * Returns `NULL` if the bytes are not a valid filter. Free it with [fastbloom_free].
+ *
+ * # Safety
+ *
+ * `bytes` must point to `len` readable bytes.
+ */
+FastBloom *fastbloom_from_bytes(const uint8_t *bytes, size_t len);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* FASTBLOOM_H */
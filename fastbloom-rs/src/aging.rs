@@ -0,0 +1,274 @@
+use alloc::vec::Vec;
+#[cfg(feature = "std")]
+use std::time::{Duration, Instant};
+
+use smallvec::SmallVec;
+
+use crate::builder::FilterBuilder;
+use crate::index::Indices;
+#[cfg(feature = "std")]
+use crate::Maintain;
+use crate::Membership;
+use crate::vec::BloomBitVec;
+
+/// Bloom filter forgetting old elements: it keeps a ring of generations, each a bit vector of
+/// the configured size, adds to the newest one and queries all of them. [AgingBloomFilter::tick]
+/// starts a new generation in place of the oldest, so an element added before the last
+/// `generations - 1` ticks is forgotten at the next one.
+///
+/// With [AgingBloomFilter::rotate_every] the filter ticks on its own once per interval: queries
+/// skip the generations which expired in the meantime, the next add clears them. An element is
+/// then remembered for `generations - 1` to `generations` intervals. The [FilterBuilder] sizes
+/// one generation, i.e. the elements added in one interval.
+///
+/// # Examples
+///
+/// ```rust
+/// use fastbloom_rs::{FilterBuilder, Membership};
+///
+/// let mut filter = FilterBuilder::new(1000, 0.01).build_aging_bloom_filter(3);
+/// filter.add(b"hello");
+/// filter.tick();
+/// filter.add(b"world");
+/// filter.tick();
+/// assert_eq!(filter.contains(b"hello"), true);
+///
+/// filter.tick();
+/// assert_eq!(filter.contains(b"hello"), false);
+/// assert_eq!(filter.contains(b"world"), true);
+/// ```
+#[derive(Clone)]
+#[derive(Debug)]
+pub struct AgingBloomFilter {
+    config: FilterBuilder,
+    generations: Vec<BloomBitVec>,
+    /// Index of the newest generation, the older ones precede it in the ring.
+    current: usize,
+    /// Interval of the automatic ticks and the time of the last one.
+    #[cfg(feature = "std")]
+    interval: Option<(Duration, Instant)>,
+}
+
+impl AgingBloomFilter {
+    /// Builds an empty filter of `generations` generations configured by `config`.
+    pub fn new(mut config: FilterBuilder, generations: usize) -> Self {
+        assert!(generations > 0, "AgingBloomFilter needs a generation!");
+        config.complete();
+        let words = (config.size / usize::BITS as u64) as usize;
+        AgingBloomFilter {
+            generations: (0..generations).map(|_| BloomBitVec::new(words)).collect(),
+            config,
+            current: 0,
+            #[cfg(feature = "std")]
+            interval: None,
+        }
+    }
+
+    /// Returns the configuration/builder of a generation.
+    pub fn config(&self) -> FilterBuilder {
+        self.config.clone()
+    }
+
+    /// Returns the number of generations.
+    pub fn generations(&self) -> usize {
+        self.generations.len()
+    }
+
+    /// Starts a new generation, forgetting the elements of the oldest one.
+    pub fn tick(&mut self) {
+        self.current = (self.current + 1) % self.generations.len();
+        self.generations[self.current].clear();
+    }
+
+    /// Ticks every `interval` from now on, see [AgingBloomFilter].
+    #[cfg(feature = "std")]
+    pub fn rotate_every(&mut self, interval: Duration) {
+        assert!(!interval.is_zero(), "interval must not be zero!");
+        self.interval = Some((interval, Instant::now()));
+    }
+
+    /// Returns the number of automatic ticks which are due.
+    #[cfg(feature = "std")]
+    fn due(&self) -> u128 {
+        self.due_at(Instant::now())
+    }
+
+    /// Returns the number of automatic ticks which are due at `now`.
+    #[cfg(feature = "std")]
+    fn due_at(&self, now: Instant) -> u128 {
+        self.interval.map_or(0, |(interval, ticked)| {
+            now.saturating_duration_since(ticked).as_nanos() / interval.as_nanos()
+        })
+    }
+
+    /// Returns the number of generations which did not expire, the newest ones.
+    #[inline]
+    fn live(&self) -> usize {
+        #[cfg(feature = "std")]
+        return self.generations.len() - self.due().min(self.generations.len() as u128) as usize;
+        #[cfg(not(feature = "std"))]
+        self.generations.len()
+    }
+
+    /// Performs the automatic ticks which are due.
+    #[cfg(feature = "std")]
+    fn expire(&mut self) {
+        self.expire_at(Instant::now());
+    }
+
+    /// Performs the automatic ticks which are due at `now`.
+    #[cfg(feature = "std")]
+    fn expire_at(&mut self, now: Instant) {
+        let due = self.due_at(now);
+        if let (Some((interval, ticked)), true) = (self.interval, due > 0) {
+            for _ in 0..due.min(self.generations.len() as u128) {
+                self.tick();
+            }
+            let elapsed = Duration::from_nanos((interval.as_nanos() * due) as u64);
+            self.interval = Some((interval, ticked + elapsed));
+        }
+    }
+
+    /// Returns the generations from the oldest to the newest, `None` for the ones which expired.
+    #[cfg(feature = "std")]
+    pub(crate) fn by_age(&self) -> impl Iterator<Item=Option<&BloomBitVec>> + '_ {
+        let (len, live) = (self.generations.len(), self.live());
+        (0..len).rev().map(move |age| {
+            (age < live).then(|| &self.generations[(self.current + len - age) % len])
+        })
+    }
+
+    /// Builds a filter of the generations from the oldest to the newest, configured by `config`.
+    #[cfg(feature = "std")]
+    pub(crate) fn from_generations(config: FilterBuilder, generations: Vec<BloomBitVec>) -> Self {
+        assert!(!generations.is_empty(), "AgingBloomFilter needs a generation!");
+        AgingBloomFilter {
+            current: generations.len() - 1,
+            generations,
+            config,
+            interval: None,
+        }
+    }
+
+    /// Tests the indices against the live generations.
+    fn contains_indices(&self, indices: &[u64]) -> bool {
+        let len = self.generations.len();
+        !indices.is_empty() && (0..self.live()).any(|age| {
+            let generation = &self.generations[(self.current + len - age) % len];
+            indices.iter().all(|&index| generation.get(index as usize))
+        })
+    }
+}
+
+impl Membership for AgingBloomFilter {
+    /// Adds the passed value to the newest generation.
+    fn add(&mut self, element: &[u8]) {
+        #[cfg(feature = "std")]
+        self.expire();
+        let indices = Indices::new(element, &self.config);
+        assert!(!indices.is_empty(), "cannot add to a filter without capacity!");
+        let generation = &mut self.generations[self.current];
+        for index in indices {
+            generation.set(index as usize);
+        }
+    }
+
+    /// Tests whether an element is present in one of the generations (subject to the false
+    /// positive probability of each).
+    fn contains(&self, element: &[u8]) -> bool {
+        let indices: SmallVec<[u64; 16]> = Indices::new(element, &self.config).collect();
+        self.contains_indices(&indices)
+    }
+
+    /// Get the hashes indices of the element in a generation.
+    fn get_hash_indices(&self, element: &[u8]) -> Vec<u64> {
+        Indices::new(element, &self.config).collect()
+    }
+
+    /// Tests whether a hashes indices is present in one of the generations.
+    fn contains_hash_indices(&self, indices: &Vec<u64>) -> bool {
+        self.contains_indices(indices)
+    }
+
+    /// Removes all elements from all generations.
+    fn clear(&mut self) {
+        for generation in &mut self.generations {
+            generation.clear();
+        }
+        #[cfg(feature = "std")]
+        if let Some((interval, _)) = self.interval {
+            self.interval = Some((interval, Instant::now()));
+        }
+    }
+}
+
+/// Performs the automatic ticks of [AgingBloomFilter::rotate_every] which are due, clearing the
+/// expired generations. Without an interval there is nothing to do.
+#[cfg(feature = "std")]
+impl Maintain for AgingBloomFilter {
+    fn maintain(&mut self, now: Instant) {
+        self.expire_at(now);
+    }
+}
+
+#[test]
+fn aging_bloom_filter_test() {
+    let mut filter = FilterBuilder::new(1000, 0.01).build_aging_bloom_filter(3);
+    for i in 0..3000u32 {
+        if i % 1000 == 0 && i > 0 {
+            filter.tick();
+        }
+        filter.add(&i.to_le_bytes());
+    }
+    assert!((0..3000u32).all(|i| filter.contains(&i.to_le_bytes())));
+    let false_positives = (3000..103_000u32).filter(|i| filter.contains(&i.to_le_bytes())).count();
+    assert!(false_positives < 3 * 1300, "{false_positives} false positives");
+
+    filter.tick();
+    assert!((1000..3000u32).all(|i| filter.contains(&i.to_le_bytes())));
+    let forgotten = (0..1000u32).filter(|i| filter.contains(&i.to_le_bytes())).count();
+    assert!(forgotten < 30, "{forgotten} forgotten elements found");
+
+    filter.clear();
+    assert!(!filter.contains(&2999u32.to_le_bytes()));
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn aging_bloom_filter_interval_test() {
+    let mut filter = FilterBuilder::new(1000, 0.01).build_aging_bloom_filter(2);
+    filter.rotate_every(Duration::from_millis(200));
+    filter.add(b"hello");
+    assert!(filter.contains(b"hello"));
+    std::thread::sleep(Duration::from_millis(450));
+    // both generations expired, without an add to clear them
+    assert!(!filter.contains(b"hello"));
+    filter.add(b"world");
+    assert!(filter.contains(b"world") && !filter.contains(b"hello"));
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn aging_bloom_filter_maintain_test() {
+    let mut filter = FilterBuilder::new(1000, 0.01).build_aging_bloom_filter(3);
+    let hour = Duration::from_secs(3600);
+    let start = Instant::now();
+    filter.maintain(start + hour);
+    filter.add(b"hello");
+    // nothing is due without an interval
+    assert!(filter.contains(b"hello"));
+
+    filter.rotate_every(hour);
+    let start = Instant::now();
+    filter.maintain(start);
+    filter.maintain(start + hour);
+    assert!(filter.contains(b"hello"));
+    // the ticks due up to then ran, clearing the generation of hello
+    filter.maintain(start + 3 * hour);
+    assert!(!filter.contains(b"hello"));
+    assert!(filter.generations.iter().all(|generation| generation.count_ones() == 0));
+    // ticking at an earlier time does nothing
+    filter.add(b"world");
+    filter.maintain(start);
+    assert!(filter.contains(b"world"));
+}
@@ -0,0 +1,299 @@
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::bloom::{BloomFilter, CountingBloomFilter};
+use crate::builder::FilterBuilder;
+use crate::index::Indices;
+use crate::vec::{le_word, BloomBitVec, CountingVec, Storage};
+
+/// A Bloom filter which many threads can add to at the same time without a lock: bits are set
+/// with an atomic `fetch_or`, so `add` and `contains` take `&self` and the filter can be shared
+/// in an [Arc](std::sync::Arc). An element whose `add` has returned is seen by `contains` on all
+/// threads which synchronized with the adding thread afterwards.
+///
+/// # Examples
+///
+/// ```rust
+/// use std::sync::Arc;
+/// use std::thread;
+///
+/// use fastbloom_rs::{AtomicBloomFilter, FilterBuilder};
+///
+/// let bloom = Arc::new(AtomicBloomFilter::new(FilterBuilder::new(100_000, 0.01)));
+/// let workers: Vec<_> = (0..4).map(|t| {
+///     let bloom = bloom.clone();
+///     thread::spawn(move || bloom.add(format!("https://example.com/{t}").as_bytes()))
+/// }).collect();
+/// workers.into_iter().for_each(|worker| worker.join().unwrap());
+/// assert!(bloom.contains(b"https://example.com/3"));
+/// ```
+#[derive(Debug)]
+pub struct AtomicBloomFilter {
+    bloom: BloomFilter<Vec<AtomicUsize>>,
+}
+
+impl AtomicBloomFilter {
+    /// Build an empty filter from [FilterBuilder].
+    pub fn new(config: FilterBuilder) -> Self {
+        AtomicBloomFilter { bloom: BloomFilter::new_in(config, ()) }
+    }
+
+    /// Returns the configuration/builder of the filter.
+    pub fn config(&self) -> FilterBuilder {
+        self.bloom.config()
+    }
+
+    /// Adds the passed value to the filter.
+    pub fn add(&self, element: &[u8]) {
+        self.add_checked(element);
+    }
+
+    /// Adds the passed value and returns whether all its bits were set before, i.e. whether the
+    /// filter (probably) contained it already.
+    pub(crate) fn add_checked(&self, element: &[u8]) -> bool {
+        let indices = Indices::new(element, &self.bloom.config);
+        assert!(!indices.is_empty(), "cannot add to a filter without capacity!");
+        self.bloom.stats.record_add();
+        let words = &self.bloom.bit_set.storage;
+        let mut present = true;
+        for index in indices {
+            let (w, b) = (index / usize::BITS as u64, index % usize::BITS as u64);
+            let mask = le_word(1 << b);
+            present &= words[w as usize].fetch_or(mask, Ordering::Relaxed) & mask != 0;
+        }
+        present
+    }
+
+    /// Tests whether an element is present in the filter (subject to the specified false
+    /// positive rate).
+    #[inline]
+    pub fn contains(&self, element: &[u8]) -> bool {
+        self.bloom.contains(element)
+    }
+
+    /// Returns the share of set bits, see [BloomFilter::fill_ratio]. Adds running at the same
+    /// time may or may not be counted.
+    pub fn fill_ratio(&self) -> f64 {
+        self.bloom.fill_ratio()
+    }
+
+    /// Estimates the number of distinct elements added, see [BloomFilter::estimate_len].
+    pub fn estimate_len(&self) -> f64 {
+        self.bloom.estimate_len()
+    }
+
+    /// Returns the underlying Bloom filter, e.g. to serialize it once all threads are done.
+    pub fn into_inner(self) -> BloomFilter {
+        let storage = self.bloom.bit_set.storage.into_iter().map(AtomicUsize::into_inner);
+        BloomFilter::from_bit_vec(self.bloom.config, BloomBitVec::with_storage(storage.collect()))
+    }
+}
+
+/// Copies the bits of a filter, which stays usable by other threads.
+impl From<&AtomicBloomFilter> for BloomFilter {
+    fn from(atomic: &AtomicBloomFilter) -> Self {
+        let words = &atomic.bloom.bit_set.storage;
+        let storage = (0..words.slots()).map(|slot| words.get(slot)).collect();
+        BloomFilter::from_bit_vec(atomic.config(), BloomBitVec::with_storage(storage))
+    }
+}
+
+/// Shares a filter between threads, e.g. one restored from bytes.
+impl From<BloomFilter> for AtomicBloomFilter {
+    fn from(bloom: BloomFilter) -> Self {
+        let storage = bloom.bit_set.storage.into_iter().map(AtomicUsize::new).collect();
+        let bloom = BloomFilter::from_bit_vec(bloom.config, BloomBitVec::with_storage(storage));
+        AtomicBloomFilter { bloom }
+    }
+}
+
+/// A counting Bloom filter which many threads can add to and remove from at the same time
+/// without a lock, e.g. a rate limiter shared by the workers of an async runtime: counters are
+/// updated with atomic `fetch_update` loops on their words, so all operations take `&self`.
+///
+/// Concurrent operations on the same element are not ordered with each other: without
+/// [repeat inserts](FilterBuilder::enable_repeat_insert) two threads adding an element at once
+/// may both count it. A remove decrements each counter only if it is non-zero and undoes its
+/// decrements if one of them was zero, so two threads removing an element added once can't
+/// both take its count and leave the counters of other elements short. Counters saturate
+/// at their maximum like those of a [CountingBloomFilter], but the saturations are not tracked.
+///
+/// # Examples
+///
+/// ```rust
+/// use std::thread;
+///
+/// use fastbloom_rs::{ConcurrentCountingBloomFilter, FilterBuilder};
+///
+/// let limiter: ConcurrentCountingBloomFilter = ConcurrentCountingBloomFilter::new(
+///     FilterBuilder::new(100_000, 0.01));
+/// thread::scope(|scope| {
+///     for _ in 0..4 {
+///         scope.spawn(|| limiter.add(b"client-1"));
+///     }
+/// });
+/// assert_eq!(limiter.estimate_count(b"client-1"), 4);
+/// assert!(limiter.remove(b"client-1"));
+/// assert_eq!(limiter.estimate_count(b"client-1"), 3);
+/// ```
+#[derive(Debug)]
+pub struct ConcurrentCountingBloomFilter<const W: usize = 4> {
+    cbf: CountingBloomFilter<Vec<AtomicUsize>, W>,
+}
+
+impl<const W: usize> ConcurrentCountingBloomFilter<W> {
+    /// Build an empty filter with counters of `W` bits from [FilterBuilder].
+    pub fn new(config: FilterBuilder) -> Self {
+        ConcurrentCountingBloomFilter { cbf: CountingBloomFilter::with_width(config, ()) }
+    }
+
+    /// Returns the configuration/builder of the filter.
+    pub fn config(&self) -> FilterBuilder {
+        self.cbf.config()
+    }
+
+    /// Adds the passed value to the filter, see [CountingBloomFilter::add].
+    pub fn add(&self, element: &[u8]) {
+        let indices = Indices::new(element, &self.cbf.config);
+        assert!(!indices.is_empty(), "cannot add to a filter without capacity!");
+        self.cbf.stats.record_add();
+        let counters = &self.cbf.counting_vec;
+        if !self.cbf.config.enable_repeat_insert && self.cbf.contains(element) {
+            return;
+        }
+        if self.cbf.config.enable_conservative_update {
+            let min_count = counters.get_many(indices.clone()).into_iter().min().unwrap_or(0);
+            for index in indices {
+                if counters.get(index as usize) == min_count {
+                    counters.increment_shared(index as usize);
+                }
+            }
+            return;
+        }
+        for index in indices {
+            counters.increment_shared(index as usize);
+        }
+    }
+
+    /// Decrements the counters of the element if all of them are non-zero and returns whether
+    /// it did, see [CountingBloomFilter::remove_if_present].
+    pub fn remove(&self, element: &[u8]) -> bool {
+        let counters = &self.cbf.counting_vec;
+        let indices = Indices::new(element, &self.cbf.config);
+        for (n, index) in indices.clone().enumerate() {
+            if !counters.decrement_shared(index as usize) {
+                // absent, or another thread removed it first
+                indices.take(n).for_each(|index| { counters.increment_shared(index as usize); });
+                return false;
+            }
+        }
+        true
+    }
+
+    /// Tests whether an element is present in the filter (subject to the specified false
+    /// positive rate).
+    #[inline]
+    pub fn contains(&self, element: &[u8]) -> bool {
+        self.cbf.contains(element)
+    }
+
+    /// Returns the estimated count of the element, see [CountingBloomFilter::estimate_count].
+    pub fn estimate_count(&self, element: &[u8]) -> usize {
+        self.cbf.estimate_count(element)
+    }
+
+    /// Returns the underlying counting Bloom filter, e.g. to snapshot it once all threads are
+    /// done.
+    pub fn into_inner(self) -> CountingBloomFilter<Vec<usize>, W> {
+        let config = self.cbf.config.clone();
+        let storage = self.cbf.counting_vec.storage.into_iter().map(AtomicUsize::into_inner);
+        CountingBloomFilter::from_counting_vec(config, CountingVec::new(storage.collect()))
+    }
+}
+
+/// Shares a counting filter between threads, e.g. one restored from a snapshot.
+impl<const W: usize> From<CountingBloomFilter<Vec<usize>, W>> for ConcurrentCountingBloomFilter<W> {
+    fn from(cbf: CountingBloomFilter<Vec<usize>, W>) -> Self {
+        let storage = cbf.counting_vec.storage.into_iter().map(AtomicUsize::new).collect();
+        let cbf = CountingBloomFilter::from_counting_vec(cbf.config, CountingVec::new(storage));
+        ConcurrentCountingBloomFilter { cbf }
+    }
+}
+
+#[test]
+fn atomic_bloom_filter_test() {
+    use crate::Membership;
+
+    let atomic = AtomicBloomFilter::new(FilterBuilder::new(100_000, 0.01));
+    std::thread::scope(|scope| {
+        for t in 0..8u32 {
+            let atomic = &atomic;
+            scope.spawn(move || {
+                for i in (t..20_000).step_by(8) {
+                    atomic.add(&i.to_le_bytes());
+                }
+            });
+        }
+    });
+    assert!((0..20_000u32).all(|i| atomic.contains(&i.to_le_bytes())));
+
+    let mut expected = FilterBuilder::new(100_000, 0.01).build_bloom_filter();
+    for i in 0..20_000u32 {
+        expected.add(&i.to_le_bytes());
+    }
+    assert_eq!(BloomFilter::from(&atomic).get_u64_array(), expected.get_u64_array());
+    assert_eq!(atomic.into_inner().get_u64_array(), expected.get_u64_array());
+}
+
+#[test]
+fn concurrent_counting_bloom_filter_test() {
+    let mut builder = FilterBuilder::new(10_000, 0.01);
+    builder.enable_repeat_insert(true);
+    let cbf = ConcurrentCountingBloomFilter::<8>::new(builder.clone());
+    std::thread::scope(|scope| {
+        for t in 0..8u32 {
+            let cbf = &cbf;
+            scope.spawn(move || {
+                for i in 0..1000u32 {
+                    cbf.add(&i.to_le_bytes());
+                    if i % 2 == 0 {
+                        cbf.add(&(i + t).to_le_bytes());
+                        cbf.remove(&(i + t).to_le_bytes());
+                    }
+                }
+            });
+        }
+    });
+    assert!((0..1000u32).all(|i| cbf.estimate_count(&i.to_le_bytes()) >= 8));
+
+    let mut expected = builder.build_counting_bloom_filter_with_width::<Vec<usize>, 8>(());
+    for _ in 0..8 {
+        for i in 0..1000u32 {
+            expected.add(&i.to_le_bytes());
+        }
+    }
+    let cbf = ConcurrentCountingBloomFilter::from(cbf.into_inner());
+    assert_eq!(cbf.into_inner().storage(), expected.storage());
+}
+
+#[test]
+fn concurrent_counting_bloom_filter_remove_test() {
+    let cbf: ConcurrentCountingBloomFilter = ConcurrentCountingBloomFilter::new(
+        FilterBuilder::new(10_000, 0.001));
+    (0..100u32).for_each(|i| cbf.add(&i.to_le_bytes()));
+    let removed = std::sync::atomic::AtomicUsize::new(0);
+    std::thread::scope(|scope| {
+        for _ in 0..8 {
+            scope.spawn(|| {
+                for i in 0..100u32 {
+                    if cbf.remove(&i.to_le_bytes()) {
+                        removed.fetch_add(1, Ordering::Relaxed);
+                    }
+                }
+            });
+        }
+    });
+    assert_eq!(removed.into_inner(), 100);
+    assert!(!cbf.remove(&0u32.to_le_bytes()));
+    assert!(cbf.into_inner().storage().iter().all(|word| *word == 0));
+}
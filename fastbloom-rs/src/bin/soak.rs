@@ -0,0 +1,203 @@
+//! Long-running soak test: writer threads add keys to an [AtomicBloomFilter] and record them in
+//! a shadow set, reader threads check that every recorded key is still reported as present, the
+//! filter is rotated once it is full (the previous generation stays queryable) and persisted
+//! generations are reloaded from disk and checked against their shadow set. Any false negative
+//! aborts the run.
+//!
+//! ```text
+//! cargo run --release --features soak --bin fastbloom-soak -- --duration 3600 --threads 8
+//! ```
+
+use std::env;
+use std::fs;
+use std::process;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex, RwLock};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use fastbloom_rs::{AtomicBloomFilter, BloomFilter, FilterBuilder, mix64};
+
+struct Options {
+    duration: Duration,
+    threads: u64,
+    capacity: u64,
+    fpp: f64,
+}
+
+impl Options {
+    fn parse() -> Options {
+        let mut options = Options {
+            duration: Duration::from_secs(3600),
+            threads: 4,
+            capacity: 1_000_000,
+            fpp: 0.01,
+        };
+        let mut args = env::args().skip(1);
+        while let Some(arg) = args.next() {
+            let value = args.next().unwrap_or_else(|| usage(&format!("missing value of {arg}")));
+            match arg.as_str() {
+                "--duration" => options.duration = Duration::from_secs(parse(&arg, &value)),
+                "--threads" => options.threads = parse(&arg, &value),
+                "--capacity" => options.capacity = parse(&arg, &value),
+                "--fpp" => options.fpp = parse(&arg, &value),
+                _ => usage(&format!("unknown argument {arg}")),
+            }
+        }
+        options
+    }
+}
+
+fn parse<T: FromStr>(arg: &str, value: &str) -> T {
+    value.parse().unwrap_or_else(|_| usage(&format!("invalid value of {arg}: {value}")))
+}
+
+fn usage(error: &str) -> ! {
+    eprintln!("{error}");
+    eprintln!("usage: fastbloom-soak [--duration SECS] [--threads N] [--capacity N] [--fpp P]");
+    process::exit(2)
+}
+
+/// A filter and the exact set of keys whose `add` has returned.
+struct Generation {
+    id: u64,
+    filter: AtomicBloomFilter,
+    keys: Mutex<Vec<u64>>,
+}
+
+impl Generation {
+    fn new(id: u64, options: &Options) -> Arc<Generation> {
+        let filter = AtomicBloomFilter::new(FilterBuilder::new(options.capacity, options.fpp));
+        Arc::new(Generation { id, filter, keys: Mutex::new(Vec::new()) })
+    }
+
+    /// Checks up to `samples` recorded keys, picked with `seed`, and returns how many.
+    fn check(&self, seed: u64, samples: usize) -> u64 {
+        let keys = self.keys.lock().unwrap();
+        if keys.is_empty() {
+            return 0;
+        }
+        for i in 0..samples {
+            let key = keys[(mix64(seed ^ i as u64) % keys.len() as u64) as usize];
+            if !self.filter.contains(&key.to_le_bytes()) {
+                fail(&format!("false negative for key {key:#x} in generation {}", self.id));
+            }
+        }
+        samples as u64
+    }
+}
+
+/// The current generation receives the adds, the previous one is still queried.
+struct Generations {
+    current: Arc<Generation>,
+    previous: Option<Arc<Generation>>,
+}
+
+#[derive(Default)]
+struct Counters {
+    adds: AtomicU64,
+    checks: AtomicU64,
+    rotations: AtomicU64,
+    persists: AtomicU64,
+}
+
+fn fail(message: &str) -> ! {
+    eprintln!("soak test failed: {message}");
+    process::exit(1)
+}
+
+fn writer(thread: u64, options: &Options, generations: &RwLock<Generations>, counters: &Counters,
+          stop: &AtomicBool) {
+    let mut n = 0u64;
+    while !stop.load(Ordering::Relaxed) {
+        let generation = generations.read().unwrap().current.clone();
+        let key = mix64(thread << 48 | n);
+        n += 1;
+        generation.filter.add(&key.to_le_bytes());
+        let len = {
+            let mut keys = generation.keys.lock().unwrap();
+            keys.push(key);
+            keys.len() as u64
+        };
+        counters.adds.fetch_add(1, Ordering::Relaxed);
+        if len >= options.capacity {
+            let mut generations = generations.write().unwrap();
+            if generations.current.id == generation.id {
+                let next = Generation::new(generation.id + 1, options);
+                generations.previous = Some(std::mem::replace(&mut generations.current, next));
+                counters.rotations.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+}
+
+fn reader(thread: u64, generations: &RwLock<Generations>, counters: &Counters, stop: &AtomicBool) {
+    let mut round = 0u64;
+    while !stop.load(Ordering::Relaxed) {
+        let (current, previous) = {
+            let generations = generations.read().unwrap();
+            (generations.current.clone(), generations.previous.clone())
+        };
+        let seed = mix64(thread << 48 | round);
+        round += 1;
+        let mut checked = current.check(seed, 64);
+        if let Some(previous) = previous {
+            checked += previous.check(!seed, 64);
+        }
+        counters.checks.fetch_add(checked, Ordering::Relaxed);
+    }
+}
+
+/// Writes the current generation to disk, reloads it and checks all keys recorded before the
+/// copy was taken.
+fn persist(generations: &RwLock<Generations>, counters: &Counters) {
+    let generation = generations.read().unwrap().current.clone();
+    let keys = generation.keys.lock().unwrap().clone();
+    let bytes = BloomFilter::from(&generation.filter).to_static_bytes();
+    let path = env::temp_dir().join(format!("fastbloom-soak-{}.bin", process::id()));
+    fs::write(&path, &bytes).unwrap_or_else(|e| fail(&format!("cannot write {path:?}: {e}")));
+    let read = fs::read(&path).unwrap_or_else(|e| fail(&format!("cannot read {path:?}: {e}")));
+    let _ = fs::remove_file(&path);
+    let restored = BloomFilter::from_static_bytes(&read)
+        .unwrap_or_else(|| fail(&format!("generation {} doesn't reload", generation.id)));
+    if restored.config().size != generation.filter.config().size {
+        fail(&format!("generation {} reloads with another size", generation.id));
+    }
+    for key in keys {
+        if !restored.contains(&key.to_le_bytes()) {
+            fail(&format!("false negative for key {key:#x} after reloading generation {}",
+                          generation.id));
+        }
+    }
+    counters.persists.fetch_add(1, Ordering::Relaxed);
+}
+
+fn main() {
+    let options = Options::parse();
+    let current = Generation::new(0, &options);
+    let generations = RwLock::new(Generations { current, previous: None });
+    let counters = Counters::default();
+    let stop = AtomicBool::new(false);
+    let start = Instant::now();
+
+    thread::scope(|scope| {
+        for thread in 0..options.threads {
+            let (options, generations, counters, stop) = (&options, &generations, &counters, &stop);
+            scope.spawn(move || writer(thread, options, generations, counters, stop));
+            scope.spawn(move || reader(thread, generations, counters, stop));
+        }
+        while start.elapsed() < options.duration {
+            let remaining = options.duration.saturating_sub(start.elapsed());
+            thread::sleep(Duration::from_secs(10).min(remaining));
+            persist(&generations, &counters);
+            println!("[{:>6}s] adds {} checks {} rotations {} persists {}",
+                     start.elapsed().as_secs(), counters.adds.load(Ordering::Relaxed),
+                     counters.checks.load(Ordering::Relaxed),
+                     counters.rotations.load(Ordering::Relaxed),
+                     counters.persists.load(Ordering::Relaxed));
+        }
+        stop.store(true, Ordering::Relaxed);
+    });
+    println!("soak test passed after {}s", start.elapsed().as_secs());
+}
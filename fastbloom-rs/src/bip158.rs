@@ -0,0 +1,204 @@
+use alloc::vec::Vec;
+
+use crate::family::siphash24;
+use crate::golomb::{matches_any, rice_decode, rice_encode};
+
+/// Golomb-Rice parameter of the basic filters of BIP158.
+const BIP158_P: u8 = 19;
+/// Inverse false positive probability of the basic filters of BIP158.
+const BIP158_M: u64 = 784_931;
+
+/// Compact block filter of BIP158 ("Compact Block Filters for Light Clients", the basic filters
+/// of Neutrino and Electrum-style clients), bit for bit: the elements are hashed with
+/// SipHash-2-4 keyed with the first 16 bytes of the block hash, mapped to `[0, n * M)` with
+/// `M = 784931`, sorted, and the differences are written with a Golomb-Rice code of `P = 19`.
+/// A query has a false positive probability of `1 / M`.
+///
+/// The filter stores no configuration, the parameters are fixed by BIP158 and the key is the
+/// block hash of the serialized block header, in its internal byte order (the reverse of the hex
+/// shown by block explorers). [Bip158Filter::to_bytes] writes the filter of the `cfilter`
+/// message, so servers build filters of the scripts of a block with [Bip158Filter::from_keys]
+/// and clients match their scripts against received ones with [Bip158Filter::contains_any].
+/// Needs the `siphash` feature.
+///
+/// # Examples
+///
+/// ```rust
+/// use fastbloom_rs::Bip158Filter;
+///
+/// let block_hash = [7u8; 32];
+/// let filter = Bip158Filter::from_keys(&block_hash, [b"script 1", b"script 2"]);
+/// let received = Bip158Filter::from_bytes(&block_hash, &filter.to_bytes()).unwrap();
+/// assert_eq!(received.contains(b"script 1"), true);
+/// assert_eq!(received.contains_any([&b"other"[..], b"script 2"]), true);
+/// assert_eq!(received.contains(b"other"), false);
+/// ```
+#[derive(Clone)]
+#[derive(Debug)]
+pub struct Bip158Filter {
+    k0: u64,
+    k1: u64,
+    len: u64,
+    data: Vec<u8>,
+}
+
+impl Bip158Filter {
+    /// Builds the filter of the block with the hash `block_hash` of `keys`, e.g. the output
+    /// scripts of the block and the scripts it spends. Duplicate keys are stored once, as BIP158
+    /// builds filters of sets; empty keys are skipped like the empty scripts of BIP158.
+    pub fn from_keys<K: AsRef<[u8]>>(block_hash: &[u8; 32],
+                                     keys: impl IntoIterator<Item=K>) -> Self {
+        let mut keys: Vec<K> = keys.into_iter().filter(|key| !key.as_ref().is_empty()).collect();
+        keys.sort_unstable_by(|a, b| a.as_ref().cmp(b.as_ref()));
+        keys.dedup_by(|a, b| a.as_ref() == b.as_ref());
+        let mut filter = Bip158Filter::with_key(block_hash, keys.len() as u64, Vec::new());
+        let mut values: Vec<u64> = keys.iter().map(|key| filter.value_of(key.as_ref())).collect();
+        values.sort_unstable();
+        filter.data = rice_encode(&values, BIP158_P);
+        filter
+    }
+
+    fn with_key(block_hash: &[u8; 32], len: u64, data: Vec<u8>) -> Self {
+        let k0 = u64::from_le_bytes(block_hash[..8].try_into().unwrap());
+        let k1 = u64::from_le_bytes(block_hash[8..16].try_into().unwrap());
+        Bip158Filter { k0, k1, len, data }
+    }
+
+    /// Returns the number of keys of the filter.
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    /// Returns [true] if the filter has no keys.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the length of the coded stream in bytes, without the number of keys.
+    pub fn byte_len(&self) -> usize {
+        self.data.len()
+    }
+
+    /// Maps a key to `[0, n * M)`.
+    #[inline]
+    fn value_of(&self, element: &[u8]) -> u64 {
+        let hash = siphash24(self.k0, self.k1, element);
+        ((hash as u128 * (self.len * BIP158_M) as u128) >> 64) as u64
+    }
+
+    /// Tests whether an element is one of the keys (subject to the false positive probability).
+    /// Decodes the filter up to the element, use [Bip158Filter::contains_any] for many.
+    pub fn contains(&self, element: &[u8]) -> bool {
+        if self.is_empty() {
+            return false;
+        }
+        let target = self.value_of(element);
+        rice_decode(&self.data, self.len, BIP158_P).find(|value| *value >= target) == Some(target)
+    }
+
+    /// Tests whether any of the elements is one of the keys, decoding the filter once: the
+    /// match of the scripts of a wallet against a block.
+    pub fn contains_any<'a>(&self, elements: impl IntoIterator<Item=&'a [u8]>) -> bool {
+        if self.is_empty() {
+            return false;
+        }
+        let targets = elements.into_iter().map(|e| self.value_of(e)).collect();
+        matches_any(rice_decode(&self.data, self.len, BIP158_P), targets)
+    }
+
+    /// Serializes the filter as BIP158: the number of keys (CompactSize) and the coded stream.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(9 + self.data.len());
+        match self.len {
+            len @ 0..0xfd => out.push(len as u8),
+            len @ 0xfd..=0xffff => {
+                out.push(0xfd);
+                out.extend_from_slice(&(len as u16).to_le_bytes());
+            }
+            len @ 0x10000..=0xffff_ffff => {
+                out.push(0xfe);
+                out.extend_from_slice(&(len as u32).to_le_bytes());
+            }
+            len => {
+                out.push(0xff);
+                out.extend_from_slice(&len.to_le_bytes());
+            }
+        }
+        out.extend_from_slice(&self.data);
+        out
+    }
+
+    /// Restores the filter of the block with the hash `block_hash` from bytes written by
+    /// [Bip158Filter::to_bytes] or received in a `cfilter` message. Returns `None` when the
+    /// number of keys is truncated, not in its shortest encoding or too large.
+    pub fn from_bytes(block_hash: &[u8; 32], bytes: &[u8]) -> Option<Self> {
+        let (&prefix, rest) = bytes.split_first()?;
+        let (width, min) = match prefix {
+            0xfd => (2, 0xfd),
+            0xfe => (4, 0x10000),
+            0xff => (8, 0x1_0000_0000),
+            len => (0, len as u64),
+        };
+        let (len, rest) = if width == 0 {
+            (min, rest)
+        } else {
+            let mut le = [0u8; 8];
+            le[..width].copy_from_slice(rest.get(..width)?);
+            (u64::from_le_bytes(le), &rest[width..])
+        };
+        if len < min || len.checked_mul(BIP158_M).is_none() {
+            return None;
+        }
+        Some(Bip158Filter::with_key(block_hash, len, rest.to_vec()))
+    }
+}
+
+#[test]
+fn bip158_filter_test() {
+    // block 0 of testnet3, whose only key is the script of the genesis coinbase output
+    let mut block_hash: [u8; 32] = [
+        0x00, 0x00, 0x00, 0x00, 0x09, 0x33, 0xea, 0x01, 0xad, 0x0e, 0xe9, 0x84, 0x20, 0x97, 0x79,
+        0xba, 0xae, 0xc3, 0xce, 0xd9, 0x0f, 0xa3, 0xf4, 0x08, 0x71, 0x95, 0x26, 0xf8, 0xd7, 0x7f,
+        0x49, 0x43,
+    ];
+    block_hash.reverse();
+    let mut script = alloc::vec![0x41];
+    script.extend_from_slice(&[
+        0x04, 0x67, 0x8a, 0xfd, 0xb0, 0xfe, 0x55, 0x48, 0x27, 0x19, 0x67, 0xf1, 0xa6, 0x71, 0x30,
+        0xb7, 0x10, 0x5c, 0xd6, 0xa8, 0x28, 0xe0, 0x39, 0x09, 0xa6, 0x79, 0x62, 0xe0, 0xea, 0x1f,
+        0x61, 0xde, 0xb6, 0x49, 0xf6, 0xbc, 0x3f, 0x4c, 0xef, 0x38, 0xc4, 0xf3, 0x55, 0x04, 0xe5,
+        0x1e, 0xc1, 0x12, 0xde, 0x5c, 0x38, 0x4d, 0xf7, 0xba, 0x0b, 0x8d, 0x57, 0x8a, 0x4c, 0x70,
+        0x2b, 0x6b, 0xf1, 0x1d, 0x5f,
+    ]);
+    script.push(0xac);
+    let filter = Bip158Filter::from_keys(&block_hash, [&script[..], &script[..], b""]);
+    assert_eq!(filter.to_bytes(), [0x01, 0x9d, 0xfc, 0xa8]);
+    assert!(filter.contains(&script));
+    let received = Bip158Filter::from_bytes(&block_hash, &[0x01, 0x9d, 0xfc, 0xa8]).unwrap();
+    assert!(received.contains(&script) && received.len() == 1);
+
+    let keys: Vec<[u8; 4]> = (0..10_000u32).map(u32::to_le_bytes).collect();
+    let filter = Bip158Filter::from_keys(&[1; 32], &keys);
+    assert_eq!(filter.len(), 10_000);
+    // about P + 1 + M / 2^P bits per key
+    assert!(filter.byte_len() * 8 < 10_000 * 22, "{} bytes", filter.byte_len());
+    let bytes = filter.to_bytes();
+    assert_eq!(bytes[..3], [0xfd, 0x10, 0x27]);
+    let received = Bip158Filter::from_bytes(&[1; 32], &bytes).unwrap();
+    assert!(keys.iter().step_by(37).all(|key| received.contains(key)));
+    assert!(received.contains_any([&b"other"[..], &keys[5000]]));
+    let others: Vec<[u8; 4]> = (10_000..20_000u32).map(u32::to_le_bytes).collect();
+    assert!(!received.contains_any(others.iter().map(|key| &key[..])));
+    // another block hashes the keys with another key
+    let other_block = Bip158Filter::from_bytes(&[2; 32], &bytes).unwrap();
+    assert!(!keys[..100].iter().all(|key| other_block.contains(key)));
+
+    let empty = Bip158Filter::from_keys(&[1; 32], [b""; 0]);
+    assert_eq!(empty.to_bytes(), [0]);
+    assert!(!empty.contains(b"script") && !empty.contains_any([&b"script"[..]]));
+    assert!(Bip158Filter::from_bytes(&[1; 32], &[]).is_none());
+    assert!(Bip158Filter::from_bytes(&[1; 32], &[0xfd, 0x10]).is_none());
+    // not the shortest encoding of 1
+    assert!(Bip158Filter::from_bytes(&[1; 32], &[0xfd, 0x01, 0x00]).is_none());
+    assert!(Bip158Filter::from_bytes(&[1; 32], &[0xff; 9]).is_none());
+}
@@ -0,0 +1,580 @@
+use alloc::vec::Vec;
+use core::hint::black_box;
+use core::ops::Range;
+
+use crate::{Hashes, Membership};
+use crate::bloom::estimate_elements;
+use crate::builder::FilterBuilder;
+use crate::compat::{CompatibilityKey, FilterLayout};
+use crate::index::hash_pair;
+use crate::vec::{Advice, BloomBitVec, le_word, Storage, StorageMut};
+
+/// Size of the blocks of a [BlockedBloomFilter]. All `k` bits of an element fall inside a single
+/// block, so a query touches one block only; larger blocks spread the bits better (lower false
+/// positive probability) but touch more memory.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum BlockSize {
+    /// 64 bytes, one cache line: best for filters which live in the CPU caches. With 64-bit
+    /// words the block is set and tested as one 512-bit register, using AVX-512 or NEON where
+    /// available.
+    #[default]
+    CacheLine,
+    /// 512 bytes, one disk sector: a good compromise for filters in DRAM.
+    Sector,
+    /// 4 KB, one page: for filters which are paged in from an SSD.
+    Page,
+}
+
+impl BlockSize {
+    /// Returns the size of a block in bytes.
+    pub fn bytes(&self) -> usize {
+        match self {
+            BlockSize::CacheLine => 64,
+            BlockSize::Sector => 512,
+            BlockSize::Page => 4096,
+        }
+    }
+
+    /// Returns the size of a block in bits.
+    #[inline]
+    pub(crate) fn bits(&self) -> u64 {
+        self.bytes() as u64 * 8
+    }
+}
+
+/// Fill of a group of blocks of a [BlockedBloomFilter] or of a shard of a
+/// [ShardedBloomFilter](crate::ShardedBloomFilter), see [BlockedBloomFilter::shard_fill_report]
+/// and [ShardedBloomFilter::shard_fill_report](crate::ShardedBloomFilter::shard_fill_report).
+#[derive(Clone, Debug, PartialEq)]
+pub struct ShardFill {
+    /// The blocks of the shard, `i..i + 1` for the `i`-th shard of a sharded filter.
+    pub blocks: Range<u64>,
+    /// Fraction of set bits.
+    pub fill_ratio: f64,
+    /// Estimated number of elements in the shard.
+    pub estimated_elements: f64,
+}
+
+/// A blocked Bloom filter splits its bit vector into blocks of [BlockSize] and sets all `k` bits
+/// of an element in one block, chosen by the first hash. Lookups need a single memory (or disk)
+/// access at the price of a slightly higher false positive probability than [crate::BloomFilter]
+/// of the same size, the smaller the block the higher.
+///
+/// A [BlockSize::CacheLine] block is one cache line only if the storage is aligned to 64 bytes.
+/// `Vec<usize>` is aligned to a word, and large allocations of the system allocator usually start
+/// 16 bytes past a page boundary, so most blocks straddle two lines. Large filters should be
+/// built in an [AllocStorage](crate::AllocStorage), which aligns its words to 64 bytes:
+///
+/// ```rust
+/// use std::alloc::System;
+///
+/// use fastbloom_rs::{AllocStorage, BlockedBloomFilter, FilterBuilder, Membership};
+///
+/// let builder = FilterBuilder::new(100_000, 0.01);
+/// let mut bloom = BlockedBloomFilter::<AllocStorage<System>>::new_in(builder, System);
+/// bloom.add(b"hello");
+/// assert_eq!(bloom.contains(b"hello"), true);
+/// assert_eq!(bloom.storage().as_slice().as_ptr() as usize % 64, 0);
+/// ```
+///
+/// **Reference**: Putze, F., Sanders, P., & Singler, J. (2007). Cache-, hash- and space-efficient
+/// bloom filters. In International Workshop on Experimental and Efficient Algorithms (pp.
+/// 108-121). Springer.
+///
+/// # Examples
+///
+/// ```rust
+/// use fastbloom_rs::{BlockSize, FilterBuilder, Membership};
+///
+/// let mut builder = FilterBuilder::new(100_000, 0.01);
+/// builder.block_size(BlockSize::Sector);
+/// let mut bloom = builder.build_blocked_bloom_filter();
+/// bloom.add(b"hello");
+/// assert_eq!(bloom.contains(b"hello"), true);
+/// ```
+#[derive(Clone)]
+#[derive(Debug)]
+pub struct BlockedBloomFilter<S = Vec<usize>> {
+    pub(crate) config: FilterBuilder,
+    pub(crate) bit_set: BloomBitVec<S>,
+    pub(crate) blocks: u64,
+}
+
+/// Iterator over the `k` bit indices of an element, all within the same block.
+pub(crate) struct BlockIndices {
+    /// The first bit index of the block.
+    pub(crate) base: u64,
+    mask: u64,
+    hash: u64,
+    delta: u64,
+    k: u32,
+    i: u32,
+}
+
+impl Iterator for BlockIndices {
+    type Item = u64;
+
+    #[inline]
+    fn next(&mut self) -> Option<u64> {
+        if self.i == self.k {
+            return None;
+        }
+        // delta is odd, so the first `mask + 1` offsets are distinct.
+        let offset = self.hash.wrapping_add((self.i as u64).wrapping_mul(self.delta)) & self.mask;
+        self.i += 1;
+        Some(self.base + offset)
+    }
+}
+
+/// Returns the indices of `element` in a blocked filter of `blocks` blocks configured by `config`.
+#[inline]
+pub(crate) fn block_indices(element: &[u8], config: &FilterBuilder, blocks: u64) -> BlockIndices {
+    let block_bits = config.block_size.bits();
+    let (block, hash) = hash_pair(element, config);
+    let block = block % blocks;
+    BlockIndices {
+        base: block * block_bits,
+        mask: block_bits - 1,
+        hash: hash & 0xffff_ffff,
+        delta: (hash >> 32) | 1,
+        k: config.hashes,
+        i: 0,
+    }
+}
+
+impl BlockedBloomFilter {
+    /// Build a blocked Bloom filter form [FilterBuilder], using the block size of the builder.
+    pub fn new(config: FilterBuilder) -> Self {
+        BlockedBloomFilter::new_in(config, ())
+    }
+}
+
+impl<S: Storage> BlockedBloomFilter<S> {
+    /// Build a blocked Bloom filter form [FilterBuilder] on a new storage of type `S` created from
+    /// `init`. The size is rounded up to a whole number of blocks.
+    pub fn new_in(mut config: FilterBuilder, init: S::Init) -> Self {
+        config.complete_for(FilterLayout::Blocked(config.block_size));
+        let block_bits = config.block_size.bits();
+        let blocks = config.size.div_ceil(block_bits).max(1);
+        config.size = blocks * block_bits;
+        let storage = S::new((config.size / usize::BITS as u64) as usize, init);
+        BlockedBloomFilter { config, bit_set: BloomBitVec::with_storage(storage), blocks }
+    }
+
+    /// Returns the configuration/builder of the blocked Bloom filter.
+    pub fn config(&self) -> FilterBuilder {
+        self.config.clone()
+    }
+
+    /// Returns the block size of the blocked Bloom filter.
+    pub fn block_size(&self) -> BlockSize {
+        self.config.block_size
+    }
+
+    /// Returns the underlying storage of the blocked Bloom filter.
+    pub fn storage(&self) -> &S {
+        &self.bit_set.storage
+    }
+
+    /// Touches every page of the storage, see [crate::BloomFilter::prefault_all].
+    pub fn prefault_all(&self) {
+        self.bit_set.storage.prefault();
+    }
+
+    /// Passes the expected access pattern of the storage on to the OS, see [Advice].
+    pub fn advise(&self, advice: Advice) {
+        self.bit_set.storage.advise(advice);
+    }
+
+    /// Returns [true] if nothing has been added to the filter.
+    pub fn is_empty(&self) -> bool {
+        self.bit_set.is_empty()
+    }
+
+    /// Returns the first word of the block of `element` and the probed bits of the block as a
+    /// 512-bit register in the in-memory representation of the words, if the block is a
+    /// [BlockSize::CacheLine] of 64-bit words.
+    #[inline]
+    fn register(&self, element: &[u8]) -> Option<(usize, Register)> {
+        if self.config.block_size != BlockSize::CacheLine || usize::BITS != 64 {
+            return None;
+        }
+        let indices = self.indices(element);
+        let first = (indices.base >> 6) as usize;
+        let mut mask = [0u64; 8];
+        for index in indices {
+            let offset = index & 511;
+            mask[(offset >> 6) as usize] |= 1 << (offset & 63);
+        }
+        Some((first, mask.map(|bits| le_word(bits as usize) as u64)))
+    }
+
+    #[inline]
+    fn indices(&self, element: &[u8]) -> BlockIndices {
+        block_indices(element, &self.config, self.blocks)
+    }
+
+    /// Splits the blocks into `shards` contiguous groups (the last ones one block smaller if
+    /// they don't divide evenly) and reports the fill and estimated cardinality of each. As all
+    /// bits of an element are in one block, the shards partition the elements, and a shard much
+    /// fuller than the others points to keys the hash function doesn't spread evenly, e.g. a
+    /// custom [crate::FilterHasher] which ignores part of the key.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use fastbloom_rs::{FilterBuilder, Membership};
+    ///
+    /// let mut bloom = FilterBuilder::new(10_000, 0.01).build_blocked_bloom_filter();
+    /// for i in 0..10_000u32 {
+    ///     bloom.add(&i.to_le_bytes());
+    /// }
+    /// let report = bloom.shard_fill_report(4);
+    /// let total: f64 = report.iter().map(|shard| shard.estimated_elements).sum();
+    /// assert!((total - 10_000.0).abs() < 500.0);
+    /// ```
+    pub fn shard_fill_report(&self, shards: usize) -> Vec<ShardFill> {
+        assert!(shards > 0, "shards must be positive!");
+        let shards = (shards as u64).min(self.blocks);
+        let words_per_block = self.config.block_size.bits() / usize::BITS as u64;
+        let (base, extra) = (self.blocks / shards, self.blocks % shards);
+        let mut start = 0;
+        (0..shards).map(|shard| {
+            let end = start + base + (shard < extra) as u64;
+            let words = (start * words_per_block) as usize..(end * words_per_block) as usize;
+            let ones: u64 = words.map(|w| self.bit_set.word(w).count_ones() as u64).sum();
+            let bits = (end - start) * self.config.block_size.bits();
+            let fill = ShardFill {
+                blocks: start..end,
+                fill_ratio: ones as f64 / bits as f64,
+                estimated_elements: estimate_elements(ones, bits, self.config.hashes),
+            };
+            start = end;
+            fill
+        }).collect()
+    }
+
+    /// Returns the [CompatibilityKey] of the filter, equal for filters which can be merged.
+    pub fn compatibility_key(&self) -> CompatibilityKey {
+        self.config.compatibility_key(FilterLayout::Blocked(self.config.block_size))
+    }
+
+    fn compatible<T: Storage>(&self, other: &BlockedBloomFilter<T>) -> bool {
+        self.compatibility_key().is_mergeable_with(&other.compatibility_key())
+    }
+}
+
+impl<S: StorageMut> BlockedBloomFilter<S> {
+    /// Performs the union operation on two compatible blocked Bloom filters, see
+    /// [crate::BloomFilter::union].
+    pub fn union<T: Storage>(&mut self, other: &BlockedBloomFilter<T>) -> bool {
+        if self.compatible(other) {
+            self.bit_set.or(&other.bit_set);
+            true
+        } else { false }
+    }
+
+    /// Performs the intersection operation on two compatible blocked Bloom filters, see
+    /// [crate::BloomFilter::intersect].
+    pub fn intersect<T: Storage>(&mut self, other: &BlockedBloomFilter<T>) -> bool {
+        if self.compatible(other) {
+            self.bit_set.and(&other.bit_set);
+            true
+        } else { false }
+    }
+}
+
+impl<S: StorageMut> Membership for BlockedBloomFilter<S> {
+    /// Adds the passed value to the filter.
+    fn add(&mut self, element: &[u8]) {
+        if let Some((first, mask)) = self.register(element) {
+            #[cfg(target_pointer_width = "64")]
+            if let Some(words) = self.bit_set.storage.words_mut() {
+                kernel::add(register_mut(words, first), &mask);
+                return;
+            }
+            for (w, bits) in mask.into_iter().enumerate().filter(|(_, bits)| *bits != 0) {
+                self.bit_set.storage.update(first + w, |word| Some(word | bits as usize));
+            }
+            return;
+        }
+        for index in self.indices(element) {
+            self.bit_set.set(index as usize);
+        }
+    }
+
+    /// Tests whether an element is present in the filter (subject to the specified false
+    /// positive rate).
+    #[inline]
+    fn contains(&self, element: &[u8]) -> bool {
+        if let Some((first, mask)) = self.register(element) {
+            // a single masked compare of the whole block, which doesn't branch on its content:
+            // the block is one cache line, so an exit after its first word saves no memory access.
+            #[cfg(target_pointer_width = "64")]
+            if let Some(words) = self.bit_set.storage.words() {
+                return kernel::contains(register(words, first), &mask);
+            }
+            let block = core::array::from_fn(|w| self.bit_set.storage.get(first + w) as u64);
+            return kernel::contains(&block, &mask);
+        }
+        let mut indices = self.indices(element);
+        if self.config.enable_constant_time {
+            let mut res = 1;
+            for index in indices {
+                res = black_box(res & self.bit_set.get_bit(index as usize));
+            }
+            res == 1
+        } else {
+            indices.all(|index| self.bit_set.get(index as usize))
+        }
+    }
+
+    /// Get the hashes indices of the element in the filter.
+    fn get_hash_indices(&self, element: &[u8]) -> Vec<u64> {
+        self.indices(element).collect()
+    }
+
+    /// Tests whether a hashes indices is present in the filter
+    fn contains_hash_indices(&self, indices: &Vec<u64>) -> bool {
+        indices.iter().all(|index| self.bit_set.get(*index as usize))
+    }
+
+    /// Removes all elements from the filter (i.e. resets all bits to zero).
+    fn clear(&mut self) {
+        self.bit_set.clear();
+    }
+}
+
+/// Adds all elements, like [Membership::add] for each.
+impl<S: StorageMut, T: AsRef<[u8]>> Extend<T> for BlockedBloomFilter<S> {
+    fn extend<I: IntoIterator<Item=T>>(&mut self, iter: I) {
+        for item in iter {
+            self.add(item.as_ref());
+        }
+    }
+}
+
+impl<S: Storage> Hashes for BlockedBloomFilter<S> {
+    ///  Returns the hash function number of the blocked Bloom filter.
+    fn hashes(&self) -> u32 {
+        self.config.hashes
+    }
+}
+
+/// A 512-bit block as 64-bit words, lowest bit first.
+type Register = [u64; 8];
+
+/// Returns the block of the 8 words from `first` on.
+#[cfg(target_pointer_width = "64")]
+#[inline]
+fn register(words: &[usize], first: usize) -> &Register {
+    let block: &[usize; 8] = words[first..first + 8].try_into().unwrap();
+    // SAFETY: usize and u64 have the same layout on 64-bit targets.
+    unsafe { &*(block as *const [usize; 8] as *const Register) }
+}
+
+/// Mutable version of [register].
+#[cfg(target_pointer_width = "64")]
+#[inline]
+fn register_mut(words: &mut [usize], first: usize) -> &mut Register {
+    let block: &mut [usize; 8] = (&mut words[first..first + 8]).try_into().unwrap();
+    // SAFETY: see `register`.
+    unsafe { &mut *(block as *mut [usize; 8] as *mut Register) }
+}
+
+/// Kernels setting or testing all probed bits of a [Register] at once: AVX-512 (detected once
+/// at runtime, which needs `std`) on x86_64, NEON on aarch64 and portable code, which the
+/// compiler vectorizes as far as the target allows, elsewhere.
+mod kernel {
+    use super::Register;
+
+    /// Returns [true] if the CPU supports AVX-512F, detecting it on the first call only.
+    #[cfg(all(target_arch = "x86_64", feature = "std"))]
+    #[inline]
+    fn has_avx512() -> bool {
+        use core::sync::atomic::{AtomicU8, Ordering};
+
+        // 0 until detected, then 1 without and 2 with AVX-512F
+        static DETECTED: AtomicU8 = AtomicU8::new(0);
+        match DETECTED.load(Ordering::Relaxed) {
+            0 => {
+                let detected = std::arch::is_x86_feature_detected!("avx512f");
+                DETECTED.store(1 + detected as u8, Ordering::Relaxed);
+                detected
+            }
+            state => state == 2,
+        }
+    }
+
+    #[inline]
+    pub(super) fn contains(block: &Register, mask: &Register) -> bool {
+        #[cfg(all(target_arch = "x86_64", feature = "std"))]
+        if has_avx512() {
+            // SAFETY: the CPU supports AVX-512F.
+            return unsafe { contains_avx512(block, mask) };
+        }
+        #[cfg(target_arch = "aarch64")]
+        // SAFETY: NEON is part of the aarch64 baseline.
+        return unsafe { contains_neon(block, mask) };
+        #[allow(unreachable_code)]
+        contains_portable(block, mask)
+    }
+
+    /// Sets the bits of `mask` in the block. Only 64-bit words form registers.
+    #[cfg_attr(not(target_pointer_width = "64"), allow(dead_code))]
+    #[inline]
+    pub(super) fn add(block: &mut Register, mask: &Register) {
+        #[cfg(all(target_arch = "x86_64", feature = "std"))]
+        if has_avx512() {
+            // SAFETY: the CPU supports AVX-512F.
+            return unsafe { add_avx512(block, mask) };
+        }
+        #[cfg(target_arch = "aarch64")]
+        // SAFETY: NEON is part of the aarch64 baseline.
+        return unsafe { add_neon(block, mask) };
+        #[allow(unreachable_code)]
+        add_portable(block, mask)
+    }
+
+    #[inline]
+    pub(super) fn contains_portable(block: &Register, mask: &Register) -> bool {
+        block.iter().zip(mask).fold(0, |missing, (word, bits)| missing | (!word & bits)) == 0
+    }
+
+    #[cfg_attr(not(target_pointer_width = "64"), allow(dead_code))]
+    #[inline]
+    pub(super) fn add_portable(block: &mut Register, mask: &Register) {
+        for (word, bits) in block.iter_mut().zip(mask) {
+            *word |= bits;
+        }
+    }
+
+    #[cfg(all(target_arch = "x86_64", feature = "std"))]
+    #[target_feature(enable = "avx512f")]
+    unsafe fn add_avx512(block: &mut Register, mask: &Register) {
+        use core::arch::x86_64::*;
+        let words = _mm512_loadu_epi64(block.as_ptr() as *const i64);
+        let mask = _mm512_loadu_epi64(mask.as_ptr() as *const i64);
+        _mm512_storeu_epi64(block.as_mut_ptr() as *mut i64, _mm512_or_si512(words, mask));
+    }
+
+    #[cfg(target_arch = "aarch64")]
+    unsafe fn add_neon(block: &mut Register, mask: &Register) {
+        use core::arch::aarch64::*;
+        for i in (0..8).step_by(2) {
+            let bits = vld1q_u64(mask.as_ptr().add(i));
+            let word = vld1q_u64(block.as_ptr().add(i));
+            vst1q_u64(block.as_mut_ptr().add(i), vorrq_u64(word, bits));
+        }
+    }
+
+    #[cfg(all(target_arch = "x86_64", feature = "std"))]
+    #[target_feature(enable = "avx512f")]
+    unsafe fn contains_avx512(block: &Register, mask: &Register) -> bool {
+        use core::arch::x86_64::*;
+        let block = _mm512_loadu_epi64(block.as_ptr() as *const i64);
+        let mask = _mm512_loadu_epi64(mask.as_ptr() as *const i64);
+        _mm512_cmpeq_epi64_mask(_mm512_and_si512(block, mask), mask) == 0xff
+    }
+
+    #[cfg(target_arch = "aarch64")]
+    unsafe fn contains_neon(block: &Register, mask: &Register) -> bool {
+        use core::arch::aarch64::*;
+        let mut missing = vdupq_n_u64(0);
+        for i in (0..8).step_by(2) {
+            let bits = vld1q_u64(mask.as_ptr().add(i));
+            let word = vld1q_u64(block.as_ptr().add(i));
+            missing = vorrq_u64(missing, vbicq_u64(bits, word));
+        }
+        vmaxvq_u32(vreinterpretq_u32_u64(missing)) == 0
+    }
+}
+
+#[test]
+fn register_kernel_test() {
+    let mut block = [0u64; 8];
+    let mut mask = [0u64; 8];
+    mask[0] = 0b101;
+    mask[7] = 1 << 63;
+    assert!(!kernel::contains(&block, &mask));
+    block[0] = 0b111;
+    assert!(!kernel::contains(&block, &mask));
+    block[7] = u64::MAX;
+    assert!(kernel::contains(&block, &mask));
+    assert!(kernel::contains_portable(&block, &mask));
+    assert!(kernel::contains(&block, &[0; 8]));
+
+    let mut added = [0u64; 8];
+    kernel::add(&mut added, &mask);
+    assert_eq!(added, mask);
+    kernel::add(&mut added, &[1; 8]);
+    let mut portable = mask;
+    kernel::add_portable(&mut portable, &[1; 8]);
+    assert_eq!(added, portable);
+    assert!(kernel::contains(&added, &mask) && kernel::contains_portable(&added, &[1; 8]));
+}
+
+#[test]
+fn shard_fill_report_test() {
+    let mut bloom = FilterBuilder::new(20_000, 0.01).build_blocked_bloom_filter();
+    let blocks = bloom.config().size / BlockSize::CacheLine.bits();
+    for i in 0..20_000u32 {
+        bloom.add(&i.to_le_bytes());
+    }
+    let report = bloom.shard_fill_report(3);
+    assert_eq!(report.len(), 3);
+    assert_eq!((report[0].blocks.start, report[2].blocks.end), (0, blocks));
+    assert!(report.windows(2).all(|pair| pair[0].blocks.end == pair[1].blocks.start));
+    for shard in report.iter() {
+        assert!((shard.estimated_elements - 20_000.0 / 3.0).abs() < 700.0, "{shard:?}");
+        assert!(shard.fill_ratio > 0.3 && shard.fill_ratio < 0.7, "{shard:?}");
+    }
+    assert_eq!(bloom.shard_fill_report(usize::MAX).len() as u64, blocks);
+}
+
+#[test]
+#[cfg(feature = "std")]
+fn blocked_alloc_storage_test() {
+    use std::alloc::System;
+
+    use crate::vec::AllocStorage;
+
+    let builder = FilterBuilder::new(100_000, 0.01);
+    let mut bloom = BlockedBloomFilter::<AllocStorage<System>>::new_in(builder.clone(), System);
+    assert_eq!(bloom.storage().as_slice().as_ptr() as usize % 64, 0);
+    let mut vec = BlockedBloomFilter::new(builder);
+    for i in 0..10_000u32 {
+        bloom.add(&i.to_le_bytes());
+        vec.add(&i.to_le_bytes());
+    }
+    assert!((0..20_000u32).all(|i| {
+        bloom.contains(&i.to_le_bytes()) == vec.contains(&i.to_le_bytes())
+    }));
+    let clone = bloom.clone();
+    assert_eq!(clone.storage().as_slice().as_ptr() as usize % 64, 0);
+}
+
+#[test]
+fn blocked_test() {
+    for block_size in [BlockSize::CacheLine, BlockSize::Sector, BlockSize::Page] {
+        let mut builder = FilterBuilder::new(100_000, 0.01);
+        builder.block_size(block_size);
+        let mut bloom = builder.build_blocked_bloom_filter();
+        assert_eq!(bloom.config().size % block_size.bits(), 0);
+        for i in 0..100_000 {
+            bloom.add(format!("key-{i}").as_bytes());
+        }
+        for i in 0..100_000 {
+            assert!(bloom.contains(format!("key-{i}").as_bytes()));
+        }
+        let indices = bloom.get_hash_indices(b"hello");
+        let block = indices[0] / block_size.bits();
+        assert!(indices.iter().all(|index| index / block_size.bits() == block));
+
+        let false_positives = (100_000..200_000)
+            .filter(|i| bloom.contains(format!("key-{i}").as_bytes()))
+            .count();
+        // blocking raises the false positive probability above the configured 1%.
+        assert!(false_positives < 3_000, "{block_size:?}: {false_positives}");
+    }
+}
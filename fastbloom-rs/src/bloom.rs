@@ -1,63 +1,121 @@
-use std::clone;
-use std::cmp::min;
-use std::ptr::slice_from_raw_parts;
+use alloc::collections::BTreeMap;
+use alloc::sync::Arc;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::cmp::min;
+use core::hint::black_box;
+use core::ops::{BitAnd, BitOr, Range};
+use core::ptr::slice_from_raw_parts;
 
-use fastmurmur3::murmur3_x64_128;
-use xxhash_rust::xxh3::xxh3_64_with_seed;
+use smallvec::SmallVec;
 
-use crate::{Deletable, Hashes, Membership};
+use crate::{Deletable, Hashes, Membership, MembershipDetail};
 use crate::builder::FilterBuilder;
-use crate::vec::{BloomBitVec, CountingVec, Storage, StorageMut};
+use crate::codec::{put_hashing, put_varint, Reader};
+use crate::compat::{CompatibilityKey, FilterLayout, FormatFeatures, MismatchError};
+use crate::events::{FilterEvents, InsertHook};
+use crate::index::{ElementHash, Indices, is_sampled};
+use crate::params::{expected_fpp, expected_fpp_const, optimal_hashes};
+#[cfg(feature = "simd")]
+use crate::simd::{LANES, LaneProbes};
+use crate::sketch::HyperLogLog;
+use crate::stats::OpCounters;
+#[cfg(feature = "metrics")]
+use crate::stats::FilterMetrics;
+#[cfg(feature = "stats")]
+use crate::stats::OpStats;
+use crate::vec::{
+    Advice, BloomBitVec, combine_words, CountingVec, le_word, SparseStorage, Storage, StorageMut,
+};
+#[cfg(not(feature = "std"))]
+use crate::float::Float;
 
 #[inline]
-fn bit_set(bit_set: &mut BloomBitVec, value: &[u8], m: u64, k: u64) {
-    // let len = m >> 5;
-    // let hash1 = (murmur3_x64_128(value, 0) % m) as u64;
-    // let hash2 = (murmur3_x64_128(value, 32) % m) as u64;
-    let hash1 = xxh3_64_with_seed(value, 0) % m;
-    let hash2 = xxh3_64_with_seed(value, 32) % m;
-
-    let m = m as u64;
-    for i in 1..k {
-        let mo = ((hash1 + i * hash2) % m) as usize;
-        bit_set.set(mo);
-    };
-    bit_set.set(hash1 as usize);
+pub(crate) fn bit_set<S: StorageMut>(bit_set: &mut BloomBitVec<S>, indices: Indices) {
+    assert!(!indices.is_empty(), "cannot add to a filter without capacity!");
+    for index in indices {
+        bit_set.set(index as usize);
+    }
+}
+
+#[inline]
+fn bit_check<S: Storage>(bit_set: &BloomBitVec<S>, indices: Indices) -> bool {
+    if indices.is_empty() { return false; }
+    for index in indices {
+        if !bit_set.get(index as usize) { return false; }
+    }
+    true
 }
 
+/// Same as [bit_check] but probes all `k` positions and folds the probed bits with `&` instead of
+/// returning at the first unset bit.
 #[inline]
-fn bit_check(bit_set: &BloomBitVec, value: &[u8], m: u64, k: u64) -> bool {
-    // let hash1 = (murmur3_x64_128(value, 0) % m) as u64;
-    // let hash2 = (murmur3_x64_128(value, 32) % m) as u64;
-    let hash1 = xxh3_64_with_seed(value, 0) % m;
-    let hash2 = xxh3_64_with_seed(value, 32) % m;
-    let mut res = bit_set.get(hash1 as usize);
-    if !res { return false; }
-    // let m = m as u64;
-    for i in 1..k {
-        let mo = ((hash1 + i * hash2) % m) as usize;
-        res = res && bit_set.get(mo);
-        if !res { return false; }
-    }
-    res
+fn bit_check_constant_time<S: Storage>(bit_set: &BloomBitVec<S>, indices: Indices) -> bool {
+    let mut res = !indices.is_empty() as usize;
+    for index in indices {
+        res = black_box(res & bit_set.get_bit(index as usize));
+    }
+    res == 1
+}
+
+/// Estimates the number of elements in a filter of `m` bits and `k` hashes with `ones` set bits
+/// (Swamidass & Baldi).
+#[inline]
+pub(crate) fn estimate_elements(ones: u64, m: u64, k: u32) -> f64 {
+    if m == 0 || k == 0 {
+        return 0.0;
+    }
+    -(m as f64 / k as f64) * (1.0 - ones as f64 / m as f64).ln()
+}
+
+/// Checks that all `filters` can be merged with the first one and returns the empty filter of
+/// their union, `None` without filters.
+pub(crate) fn union_many_target<T: Storage>(filters: &[&BloomFilter<T>])
+                                            -> Result<Option<BloomFilter>, MismatchError> {
+    let Some(first) = filters.first() else {
+        return Ok(None);
+    };
+    let key = first.compatibility_key();
+    for filter in &filters[1..] {
+        MismatchError::check(key, filter.compatibility_key())?;
+    }
+    let bit_set = BloomBitVec::new(first.bit_set.storage.slots());
+    let res = BloomFilter::from_bit_vec(first.config.clone(), bit_set);
+    res.stats.record_merge();
+    Ok(Some(res))
 }
 
+/// Merges the words of `filters` from `offset` on into `words`.
 #[inline]
-fn get_bit_indices(bit_set: &BloomBitVec, value: &[u8], m: u64, k: u64) -> Vec<u64> {
-    let mut res = Vec::<u64>::with_capacity(k as usize);
-    // let hash1 = (murmur3_x64_128(value, 0) % m) as u64;
-    // let hash2 = (murmur3_x64_128(value, 32) % m) as u64;
-    let hash1 = xxh3_64_with_seed(value, 0) % m;
-    let hash2 = xxh3_64_with_seed(value, 32) % m;
-    res.push(hash1);
-    // let m = m as u64;
-    for i in 1..k {
-        let mo = ((hash1 + i * hash2) % m) as usize;
-        res.push(mo as u64);
+pub(crate) fn union_block<T: Storage>(words: &mut [usize], offset: usize,
+                                      filters: &[&BloomFilter<T>]) {
+    let range = offset..offset + words.len();
+    for filter in filters {
+        match filter.bit_set.storage.words() {
+            Some(theirs) => combine_words(words, &theirs[range.clone()], |m, o| m | o),
+            None => {
+                for (word, slot) in words.iter_mut().zip(range.clone()) {
+                    *word |= filter.bit_set.storage.get(slot);
+                }
+            }
+        }
     }
-    res
 }
 
+/// Words of a block of [BloomFilter::union_many], 32 KiB which stay in the L1 cache while the
+/// words of all filters are merged into them.
+pub(crate) const UNION_BLOCK_WORDS: usize = 32 * 1024 / size_of::<usize>();
+
+/// Batch size from which [BloomFilter::contains_batch] visits the probe positions in sorted order.
+const SORTED_PROBE_THRESHOLD: usize = 1024;
+
+/// Size in bits from which [BloomFilter::contains_many] plans its probes by word, about a
+/// cache of 1 MiB: smaller filters stay cached and are probed directly.
+const PLANNED_PROBE_MIN_BITS: u64 = 8 << 20;
+
+/// Number of items [BloomFilter::add_all] passes to [BloomFilter::add_batch] at once.
+const BULK_GROUP: usize = 64;
+
 /// A Bloom filter is a space-efficient probabilistic data structure, conceived by Burton Howard
 /// Bloom in 1970, that is used to test whether an element is a member of a set. False positive
 /// matches are possible, but false negatives are not.
@@ -67,34 +125,34 @@ fn get_bit_indices(bit_set: &BloomBitVec, value: &[u8], m: u64, k: u64) -> Vec<u
 /// [Full text article](http://crystal.uta.edu/~mcguigan/cse6350/papers/Bloom.pdf)
 #[derive(Clone)]
 #[derive(Debug)]
-pub struct BloomFilter {
-    config: FilterBuilder,
-    bit_set: BloomBitVec,
+pub struct BloomFilter<S = Vec<usize>> {
+    pub(crate) config: FilterBuilder,
+    pub(crate) bit_set: BloomBitVec<S>,
+    pub(crate) stats: OpCounters,
+    pub(crate) hook: InsertHook,
 }
 
-impl Membership for BloomFilter {
+impl<S: StorageMut> Membership for BloomFilter<S> {
     /// Adds the passed value to the filter.
     fn add(&mut self, element: &[u8]) {
-        bit_set(&mut self.bit_set, element, self.config.size,
-                self.config.hashes as u64);
+        self.insert_hashed(element, ElementHash::new(element, &self.config));
     }
 
     /// Tests whether an element is present in the filter (subject to the specified false
     /// positive rate).
     #[inline]
     fn contains(&self, element: &[u8]) -> bool {
-        bit_check(&self.bit_set, element, self.config.size,
-                  self.config.hashes as u64)
+        BloomFilter::contains(self, element)
     }
 
     /// Get the hashes indices of the element in the filter.
     fn get_hash_indices(&self, element: &[u8]) -> Vec<u64> {
-        get_bit_indices(&self.bit_set, element, self.config.size,
-                        self.config.hashes as u64)
+        Indices::new(element, &self.config).collect()
     }
 
     /// Tests whether a hashes indices is present in the filter
     fn contains_hash_indices(&self, indices: &Vec<u64>) -> bool {
+        if indices.is_empty() { return false; }
         for x in indices.iter() {
             let index = *x;
             if !self.bit_set.get(index as usize) { return false; }
@@ -108,13 +166,38 @@ impl Membership for BloomFilter {
     }
 }
 
-impl Hashes for BloomFilter {
+impl<S: Storage> Hashes for BloomFilter<S> {
     ///  Returns the hash function number of the Bloom filter.
     fn hashes(&self) -> u32 {
         self.config.hashes
     }
 }
 
+/// [BloomFilter::unioned], panicking if the filters can't be merged.
+impl<S: Storage, T: Storage> BitOr<&BloomFilter<T>> for &BloomFilter<S> {
+    type Output = BloomFilter;
+
+    fn bitor(self, rhs: &BloomFilter<T>) -> BloomFilter {
+        self.unioned(rhs).unwrap_or_else(|err| panic!("{err}"))
+    }
+}
+
+/// [BloomFilter::intersected], panicking if the filters can't be merged.
+impl<S: Storage, T: Storage> BitAnd<&BloomFilter<T>> for &BloomFilter<S> {
+    type Output = BloomFilter;
+
+    fn bitand(self, rhs: &BloomFilter<T>) -> BloomFilter {
+        self.intersected(rhs).unwrap_or_else(|err| panic!("{err}"))
+    }
+}
+
+/// Adds all elements, see [BloomFilter::add_all].
+impl<S: StorageMut, T: AsRef<[u8]>> Extend<T> for BloomFilter<S> {
+    fn extend<I: IntoIterator<Item=T>>(&mut self, iter: I) {
+        self.add_all(iter);
+    }
+}
+
 impl BloomFilter {
     /// Build a Bloom filter form [FilterBuilder].
     ///
@@ -132,7 +215,164 @@ impl BloomFilter {
             let bit_set = BloomBitVec::new((config.size >> 6) as usize);
         #[cfg(target_pointer_width = "32")]
             let bit_set = BloomBitVec::new((config.size >> 5) as usize);
-        BloomFilter { config, bit_set }
+        BloomFilter::from_bit_vec(config, bit_set)
+    }
+
+    /// Folds the bit vector to the smallest size which divides the current one (OR-ing the
+    /// parts into the first one) and keeps the false positive probability, given the actual
+    /// fill, within the configured one, and returns the new size in bits. Reclaims the memory
+    /// of a filter sized with headroom (see [FilterBuilder::headroom_factor]) once the bulk
+    /// build is done; a power-of-two size can be folded to any smaller power of two.
+    ///
+    /// An index modulo a divisor of the size is the index modulo the size, folded, so the
+    /// folded filter has no false negatives and is the filter of that size built from the same
+    /// elements. Sizes stay whole words, and filters with [canaries](FilterBuilder::canaries)
+    /// or [partitions](FilterBuilder::partitioned) are left as they are.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use fastbloom_rs::{FilterBuilder, Membership};
+    ///
+    /// let mut bloom = FilterBuilder::from_size_and_hashes(1 << 20, 7).build_bloom_filter();
+    /// for i in 0..1000u32 {
+    ///     bloom.add(&i.to_le_bytes());
+    /// }
+    /// assert!(bloom.shrink_to_fit() < 1 << 20);
+    /// assert!((0..1000u32).all(|i| bloom.contains(&i.to_le_bytes())));
+    /// ```
+    pub fn shrink_to_fit(&mut self) -> u64 {
+        let words = self.bit_set.storage.len();
+        if self.config.canaries != 0 || self.config.partitioned || words == 0 {
+            return self.config.size;
+        }
+        let target = self.config.false_positive_probability;
+        let k = self.config.hashes as i32;
+        let fits = |folded: &[usize]| {
+            let ones: u64 = folded.iter().map(|w| w.count_ones() as u64).sum();
+            (ones as f64 / (folded.len() as u64 * usize::BITS as u64) as f64).powi(k) <= target
+        };
+        let smallest = (1..words).filter(|len| words.is_multiple_of(*len))
+            .map(|len| self.folded(len))
+            .find(|folded| fits(folded));
+        if let Some(folded) = smallest {
+            self.config.size = folded.len() as u64 * usize::BITS as u64;
+            self.bit_set = BloomBitVec::with_storage(folded);
+        }
+        self.config.size
+    }
+
+    /// Shrinks the filter to `1 / factor` of its size by OR-ing the equal parts of the bit
+    /// vector onto the first one, keeping the number of hashes. The folded filter is the filter
+    /// of the new size built from the same elements (see [BloomFilter::shrink_to_fit]), with
+    /// the false positive probability of that size. Panics unless `factor` is a power of two
+    /// dividing the size into whole words, or when the filter has
+    /// [canaries](FilterBuilder::canaries) or [partitions](FilterBuilder::partitioned).
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use fastbloom_rs::{FilterBuilder, Membership};
+    ///
+    /// let mut bloom = FilterBuilder::from_size_and_hashes(1 << 20, 7).build_bloom_filter();
+    /// bloom.add(b"hello");
+    /// bloom.fold(4);
+    /// assert_eq!(bloom.config().size, 1 << 18);
+    /// assert_eq!(bloom.contains(b"hello"), true);
+    /// ```
+    pub fn fold(&mut self, factor: usize) {
+        let words = self.bit_set.storage.len();
+        assert!(factor.is_power_of_two(), "factor must be a power of two!");
+        assert!(words.is_multiple_of(factor) && words >= factor,
+                "cannot fold {words} words by {factor}!");
+        assert!(self.config.canaries == 0 && !self.config.partitioned,
+                "cannot fold a filter with canaries or partitions!");
+        let folded = self.folded(words / factor);
+        self.config.size = folded.len() as u64 * usize::BITS as u64;
+        self.bit_set = BloomBitVec::with_storage(folded);
+    }
+
+    /// Like [BloomFilter::union], but also merges filters whose sizes differ by a power of two:
+    /// the larger filter is [folded](BloomFilter::fold) to the smaller size first, so the result
+    /// has the smaller size. Returns the [MismatchError] of filters which differ in anything else
+    /// or can't be folded, leaving this filter unchanged.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use fastbloom_rs::{FilterBuilder, Membership};
+    ///
+    /// let mut small = FilterBuilder::from_size_and_hashes(1 << 16, 7).build_bloom_filter();
+    /// let mut large = FilterBuilder::from_size_and_hashes(1 << 20, 7).build_bloom_filter();
+    /// small.add(b"hello");
+    /// large.add(b"world");
+    /// small.merge(&large).unwrap();
+    /// assert_eq!(small.contains(b"hello") && small.contains(b"world"), true);
+    /// ```
+    pub fn merge<T: Storage>(&mut self, other: &BloomFilter<T>) -> Result<(), MismatchError> {
+        let (left, right) = (self.compatibility_key(), other.compatibility_key());
+        let smaller = left.size.min(right.size).max(1);
+        let factor = left.size.max(right.size) / smaller;
+        let foldable = factor * smaller == left.size.max(right.size) && factor.is_power_of_two()
+            && (factor == 1 || left.canaries == 0 && !left.partitioned);
+        if !foldable || !left.is_mergeable_with(&CompatibilityKey { size: left.size, ..right }) {
+            return Err(MismatchError { left, right });
+        }
+        self.stats.record_merge();
+        if left.size > right.size {
+            self.fold(factor as usize);
+            self.bit_set.or(&other.bit_set);
+        } else if left.size < right.size {
+            let mut other = other.to_dense();
+            other.fold(factor as usize);
+            self.bit_set.or(&other.bit_set);
+        } else {
+            self.bit_set.or(&other.bit_set);
+        }
+        Ok(())
+    }
+
+    /// Returns the bit vector folded to `len` words, which divides the number of words.
+    fn folded(&self, len: usize) -> Vec<usize> {
+        let mut folded = vec![0usize; len];
+        for (i, word) in self.bit_set.storage.iter().enumerate() {
+            folded[i % len] |= word;
+        }
+        folded
+    }
+
+    /// Returns a new filter holding the elements of all `filters`, e.g. of thousands of shards,
+    /// or the [MismatchError] of the first filter which can't be merged with the first one. The
+    /// keys are checked once before any bits are merged, and the result is allocated once: its
+    /// words are merged in blocks which stay in the L1 cache, each block with the words of all
+    /// filters, so there are no intermediate filters as with pairwise unions. With the `rayon`
+    /// feature `BloomFilter::par_union_many` merges the blocks in parallel. Returns `Ok(None)`
+    /// without filters.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use fastbloom_rs::{BloomFilter, FilterBuilder, Membership};
+    ///
+    /// let mut builder = FilterBuilder::new(10_000, 0.01);
+    /// let shards: Vec<BloomFilter> = (0..4u32).map(|i| {
+    ///     let mut shard = builder.build_bloom_filter();
+    ///     shard.add(&i.to_le_bytes());
+    ///     shard
+    /// }).collect();
+    /// let all = BloomFilter::union_many(&shards).unwrap().unwrap();
+    /// assert!((0..4u32).all(|i| all.contains(&i.to_le_bytes())));
+    /// ```
+    pub fn union_many<'a, T: Storage + 'a>(filters: impl IntoIterator<Item=&'a BloomFilter<T>>)
+                                         -> Result<Option<BloomFilter>, MismatchError> {
+        let filters: Vec<&BloomFilter<T>> = filters.into_iter().collect();
+        let Some(mut res) = union_many_target(&filters)? else {
+            return Ok(None);
+        };
+        for (block, words) in res.bit_set.storage.chunks_mut(UNION_BLOCK_WORDS).enumerate() {
+            union_block(words, block * UNION_BLOCK_WORDS, &filters);
+        }
+        Ok(Some(res))
     }
 
     /// Build a Bloom filter form `&[u8]`.
@@ -153,15 +393,13 @@ impl BloomFilter {
         #[cfg(target_pointer_width = "32")]
             let mut bit_vec = BloomBitVec::new((config.size >> 5) as usize);
 
-        let ptr = array.as_ptr() as *const usize;
-        #[cfg(target_pointer_width = "64")]
-            let usize_array = slice_from_raw_parts(ptr, (config.size >> 6) as usize);
-        #[cfg(target_pointer_width = "32")]
-            let usize_array = slice_from_raw_parts(ptr, (config.size >> 5) as usize);
-
-        bit_vec.storage.copy_from_slice(unsafe { &*usize_array });
+        // `array` may be unaligned (e.g. `include_bytes!`), so read the words byte-wise.
+        let chunks = array.chunks_exact(core::mem::size_of::<usize>());
+        for (word, chunk) in bit_vec.storage.iter_mut().zip(chunks) {
+            *word = usize::from_ne_bytes(chunk.try_into().unwrap());
+        }
 
-        BloomFilter { config, bit_set: bit_vec }
+        BloomFilter::from_bit_vec(config, bit_vec)
     }
 
     /// Build a Bloom filter form `&[u16]`.
@@ -190,7 +428,7 @@ impl BloomFilter {
 
         bit_vec.storage.copy_from_slice(unsafe { &*usize_array });
 
-        BloomFilter { config, bit_set: bit_vec }
+        BloomFilter::from_bit_vec(config, bit_vec)
     }
 
 
@@ -220,7 +458,7 @@ impl BloomFilter {
 
         bit_vec.storage.copy_from_slice(unsafe { &*usize_array });
 
-        BloomFilter { config, bit_set: bit_vec }
+        BloomFilter::from_bit_vec(config, bit_vec)
     }
 
     /// Build a Bloom filter form `&[u64]`.
@@ -249,145 +487,257 @@ impl BloomFilter {
 
         bit_vec.storage.copy_from_slice(unsafe { &*usize_array });
 
-        BloomFilter { config, bit_set: bit_vec }
+        BloomFilter::from_bit_vec(config, bit_vec)
     }
 
-    /// Returns the configuration/builder of the Bloom filter.
+}
+
+/// An empty filter without capacity (size, hashes and expected elements are `0`), e.g. for
+/// `#[derive(Default)]` structs whose filter is initialized lazily. It never matches, and
+/// adding to it panics; replace it with a sized filter first.
+///
+/// # Examples
+///
+/// ```rust
+/// use fastbloom_rs::{BloomFilter, Membership};
+///
+/// let bloom = BloomFilter::<Vec<usize>>::default();
+/// assert_eq!(bloom.config().size, 0);
+/// assert_eq!(bloom.contains(b"hello"), false);
+/// ```
+impl<'a> BloomFilter<&'a [u8]> {
+    /// Build a Bloom filter directly on top of `bytes`, e.g. a memory-mapped file written from
+    /// [BloomFilter::get_u8_array], without copying them. Queries read the borrowed bytes (which
+    /// need not be aligned), the filter can't be modified. Panics if the length of `bytes` isn't
+    /// a multiple of the word size.
+    ///
     /// # Examples
     ///
     /// ```rust
-    /// use fastbloom_rs::{BloomFilter, FilterBuilder};
+    /// use fastbloom_rs::{BloomFilter, FilterBuilder, Membership};
     ///
-    /// let bloom = FilterBuilder::new(100_000_000, 0.01).build_bloom_filter();
-    /// let builder = bloom.config();
-    /// ```
+    /// let mut bloom = FilterBuilder::new(1000, 0.01).build_bloom_filter();
+    /// bloom.add(b"hello");
+    /// let bytes = bloom.get_u8_array().to_vec();
     ///
-    pub fn config(&self) -> FilterBuilder {
-        self.config.clone()
+    /// let borrowed = BloomFilter::from_bytes_ref(&bytes, bloom.config().hashes);
+    /// assert_eq!(borrowed.contains(b"hello"), true);
+    /// assert_eq!(borrowed.contains(b"world"), false);
+    /// ```
+    pub fn from_bytes_ref(bytes: &'a [u8], hashes: u32) -> Self {
+        assert_eq!(bytes.len() % core::mem::size_of::<usize>(), 0,
+                   "array length must be a multiple of the word size!");
+        BloomFilter::from_storage(bytes, hashes)
     }
+}
 
-    /// Return the underlying byte vector of the Bloom filter.
-    pub fn get_u8_array(&self) -> &[u8] {
-        let storage = &self.bit_set.storage;
-        let ptr = storage.as_ptr();
-        let u8_ptr = ptr as *const u8;
-        #[cfg(target_pointer_width = "64")]
-            let ptr = slice_from_raw_parts(u8_ptr, storage.len() * 8);
-        #[cfg(target_pointer_width = "32")]
-            let ptr = slice_from_raw_parts(u8_ptr, storage.len() * 4);
-        unsafe { &*ptr }
+/// [BloomFilter] of `WORDS` 64-bit words stored inline, without heap allocation, for many small
+/// short-lived filters such as one per connection or packet batch, for targets without an
+/// allocator, or for `static`s built by the const [ArrayBloomFilter::with_hashes]. It takes
+/// `WORDS * 64` bits on every target and supports all operations of a [BloomFilter].
+///
+/// # Examples
+///
+/// ```rust
+/// use fastbloom_rs::{ArrayBloomFilter, Membership};
+///
+/// let mut bloom = ArrayBloomFilter::<16>::for_elements(64);
+/// bloom.add(b"hello");
+/// assert_eq!(bloom.contains(b"hello"), true);
+/// assert_eq!(bloom.contains(b"world"), false);
+/// assert_eq!(bloom.config().size, 16 * 64);
+///
+/// const EMPTY: ArrayBloomFilter<8> = ArrayBloomFilter::with_hashes(4);
+/// assert_eq!(EMPTY.contains(b"hello"), false);
+/// ```
+pub type ArrayBloomFilter<const WORDS: usize> = BloomFilter<[u64; WORDS]>;
+
+impl<const WORDS: usize> BloomFilter<[u64; WORDS]> {
+    /// Builds an empty [ArrayBloomFilter] with `hashes` hashes, in const contexts too, configured
+    /// like [FilterBuilder::from_size_and_hashes].
+    pub const fn with_hashes(hashes: u32) -> Self {
+        let size = WORDS as u64 * u64::BITS as u64;
+        let elements = core::f64::consts::LN_2 * size as f64 / hashes as f64;
+        let mut n = elements as u64;
+        if (n as f64) < elements {
+            n += 1;
+        }
+        let p = expected_fpp_const(size, n, hashes);
+        BloomFilter {
+            config: FilterBuilder::with_shape(size, hashes, n, p),
+            bit_set: BloomBitVec::zeroed_array(),
+            stats: OpCounters::new(),
+            hook: InsertHook::new(None),
+        }
     }
 
-    /// Return the underlying u16 vector of the Bloom filter.
-    pub fn get_u16_array(&self) -> &[u16] {
-        let storage = &self.bit_set.storage;
-        let ptr = storage.as_ptr() as *const u16;
-        #[cfg(target_pointer_width = "64")]
-            let ptr = slice_from_raw_parts(ptr, storage.len() * 4);
-        #[cfg(target_pointer_width = "32")]
-            let ptr = slice_from_raw_parts(ptr, storage.len() * 2);
-        unsafe { &*ptr }
+    /// Builds an empty [ArrayBloomFilter] with the optimal number of hashes for
+    /// `expected_elements` elements.
+    pub fn for_elements(expected_elements: u64) -> Self {
+        let size = WORDS as u64 * usize::BITS as u64;
+        let mut bloom = BloomFilter::with_hashes(optimal_hashes(size, expected_elements.max(1)));
+        bloom.config.expected_elements = expected_elements;
+        bloom.config.false_positive_probability =
+            expected_fpp(size, expected_elements, bloom.config.hashes);
+        bloom
     }
+}
 
-    /// Return the underlying u32 vector of the Bloom filter.
-    pub fn get_u32_array(&self) -> &[u32] {
-        let storage = &self.bit_set.storage;
-        let ptr = storage.as_ptr() as *const u32;
-        #[cfg(target_pointer_width = "64")]
-            let ptr = slice_from_raw_parts(ptr, storage.len() * 2);
-        #[cfg(target_pointer_width = "32")]
-            let ptr = slice_from_raw_parts(ptr, storage.len());
-        unsafe { &*ptr }
+impl<S: Storage> Default for BloomFilter<S> where S::Init: Default {
+    fn default() -> Self {
+        BloomFilter::new_in(FilterBuilder::zero_capacity(), S::Init::default())
     }
+}
 
-    /// Return the underlying u64 vector of the Bloom filter.
-    pub fn get_u64_array(&self) -> &[u64] {
-        let storage = &self.bit_set.storage;
-        let ptr = storage.as_ptr() as *const u64;
+impl<S: Storage> BloomFilter<S> {
+    /// Build a Bloom filter form [FilterBuilder] on a new storage of type `S` created from
+    /// `init`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use fastbloom_rs::{BloomFilter, FilterBuilder, Membership, SparseStorage};
+    ///
+    /// let builder = FilterBuilder::new(100_000_000, 0.01);
+    /// let mut bloom = BloomFilter::<SparseStorage>::new_in(builder, ());
+    /// bloom.add(b"hello");
+    /// assert_eq!(bloom.contains(b"hello"), true);
+    /// ```
+    pub fn new_in(mut config: FilterBuilder, init: S::Init) -> Self {
+        config.complete();
         #[cfg(target_pointer_width = "64")]
-            let ptr = slice_from_raw_parts(ptr, storage.len());
-        if cfg!(target_pointer_width= "32") {
-            if storage.len() % 2 != 0 {
-                panic!("BloomBitVec with len {} can't export as u64 array!", storage.len())
-            }
-        }
+            let storage = S::new((config.size >> 6) as usize, init);
         #[cfg(target_pointer_width = "32")]
-            let ptr = slice_from_raw_parts(ptr, storage.len() / 2usize);
+            let storage = S::new((config.size >> 5) as usize, init);
+        BloomFilter::from_bit_vec(config, BloomBitVec::with_storage(storage))
+    }
 
-        unsafe { &*ptr }
+    pub(crate) fn from_bit_vec(config: FilterBuilder, bit_set: BloomBitVec<S>) -> Self {
+        BloomFilter { config, bit_set, stats: OpCounters::default(), hook: InsertHook::default() }
     }
 
+    /// Build a Bloom filter on top of an existing storage, e.g. a buffer in an arena or a
+    /// shared-memory segment. The storage content is kept as is.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use fastbloom_rs::{BloomFilter, Membership};
+    ///
+    /// let mut buffer = vec![0usize; 1024];
+    /// let mut bloom = BloomFilter::from_storage(&mut buffer[..], 4);
+    /// bloom.add(b"hello");
+    /// assert_eq!(bloom.contains(b"hello"), true);
+    /// ```
+    pub fn from_storage(storage: S, hashes: u32) -> Self {
+        let mut config =
+            FilterBuilder::from_size_and_hashes(storage.slots() as u64 * usize::BITS as u64, hashes);
+        config.complete();
+        BloomFilter::from_bit_vec(config, BloomBitVec::with_storage(storage))
+    }
 
-    /// Performs the union operation on two compatible bloom filters. This is achieved through a
-    /// bitwise OR operation on their bit vectors. This operations is lossless, i.e. no elements
-    /// are lost and the bloom filter is the same that would have resulted if all elements wer
-    /// directly inserted in just one bloom filter.
-    pub fn union(&mut self, other: &BloomFilter) -> bool {
-        if self.compatible(other) {
-            self.bit_set.or(&other.bit_set);
-            true
-        } else { false }
+    /// Returns the underlying storage of the Bloom filter.
+    pub fn storage(&self) -> &S {
+        &self.bit_set.storage
     }
 
-    /// Performs the intersection operation on two compatible bloom filters. This is achieved
-    /// through a bitwise AND operation on their bit vectors. The operations doesn't introduce
-    /// any false negatives but it does raise the false positive probability. The the false
-    /// positive probability in the resulting Bloom filter is at most the false-positive probability
-    /// in one of the constituent bloom filters
-    pub fn intersect(&mut self, other: &BloomFilter) -> bool {
-        if self.compatible(other) {
-            self.bit_set.and(&other.bit_set);
-            true
-        } else { false }
+    /// Touches every page of the storage, so that a disk-backed filter is in the OS cache before
+    /// traffic shifts to it and the first queries don't pay for page faults.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use fastbloom_rs::{Advice, FilterBuilder};
+    ///
+    /// let bloom = FilterBuilder::new(100_000, 0.01).build_bloom_filter();
+    /// bloom.advise(Advice::WillNeed);
+    /// bloom.prefault_all();
+    /// ```
+    pub fn prefault_all(&self) {
+        self.bit_set.storage.prefault();
     }
 
-    /// Returns [true] if the Bloom filter does not contain any elements
-    pub fn is_empty(&self) -> bool {
-        self.bit_set.is_empty()
+    /// Passes the expected access pattern of the storage on to the OS, see [Advice].
+    pub fn advise(&self, advice: Advice) {
+        self.bit_set.storage.advise(advice);
     }
 
-    pub(crate) fn set_bit_vec(&mut self, bit_vec: BloomBitVec) {
-        assert_eq!(self.config.size, bit_vec.nbits as u64);
-        self.bit_set = bit_vec
+    /// Returns the per-operation counters of this filter.
+    #[cfg(feature = "stats")]
+    pub fn stats(&self) -> OpStats {
+        self.stats.snapshot()
     }
 
-    /// Checks if two Bloom filters are compatible, i.e. have compatible parameters (hash function,
-    /// size, etc.)
-    fn compatible(&self, other: &BloomFilter) -> bool {
-        self.config.is_compatible_to(&other.config)
+    /// Resets the per-operation counters of this filter.
+    #[cfg(feature = "stats")]
+    pub fn reset_stats(&self) {
+        self.stats.reset()
     }
-}
 
-/// A Counting Bloom filter works in a similar manner as a regular Bloom filter; however, it is
-/// able to keep track of insertions and deletions. In a counting Bloom filter, each entry in the
-/// Bloom filter is a small counter associated with a basic Bloom filter bit.
-///
-/// **Reference**: F. Bonomi, M. Mitzenmacher, R. Panigrahy, S. Singh, and G. Varghese, “An Improved
-/// Construction for Counting Bloom Filters,” in 14th Annual European Symposium on
-/// Algorithms, LNCS 4168, 2006
-#[derive(Clone)]
-#[derive(Debug)]
-pub struct CountingBloomFilter<S=Vec<usize>> {
-    config: FilterBuilder,
-    counting_vec: CountingVec<S>,
-}
+    /// Returns the metrics of this filter, counting its set bits.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use fastbloom_rs::{FilterBuilder, Membership};
+    ///
+    /// let mut bloom = FilterBuilder::new(1000, 0.01).build_bloom_filter();
+    /// bloom.add(b"hello");
+    /// bloom.contains(b"hello");
+    /// let metrics = bloom.metrics();
+    /// assert_eq!((metrics.ops.adds, metrics.ops.hits), (1, 1));
+    /// assert_eq!(metrics.estimated_fpp, bloom.current_fpp());
+    /// ```
+    #[cfg(feature = "metrics")]
+    pub fn metrics(&self) -> FilterMetrics {
+        FilterMetrics {
+            ops: self.stats.snapshot(),
+            fill_ratio: self.fill_ratio(),
+            estimated_fpp: self.current_fpp(),
+            saturation_events: 0,
+        }
+    }
 
-impl<S: Storage> CountingBloomFilter<S> {
-    pub fn new(mut config: FilterBuilder, init: S::Init) -> Self {
-        config.complete();
-        let counting_vec = CountingVec::new(S::new((config.size >> 4) as usize, init));
-        CountingBloomFilter { config, counting_vec }
+    /// Registers `events` to be called with [FilterEvents::on_insert] on every
+    /// [add](Membership::add), with the element and its probed indices; `None` removes it.
+    /// Clones of the filter share the registered events.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use std::sync::{Arc, Mutex};
+    ///
+    /// use fastbloom_rs::{FilterBuilder, FilterEvents, Membership};
+    ///
+    /// #[derive(Default)]
+    /// struct AuditLog(Mutex<Vec<(Vec<u8>, Vec<u64>)>>);
+    ///
+    /// impl FilterEvents for AuditLog {
+    ///     fn on_insert(&self, element: &[u8], indices: &[u64]) {
+    ///         self.0.lock().unwrap().push((element.to_vec(), indices.to_vec()));
+    ///     }
+    /// }
+    ///
+    /// let log = Arc::new(AuditLog::default());
+    /// let mut bloom = FilterBuilder::new(10_000, 0.01).build_bloom_filter();
+    /// bloom.set_insert_hook(Some(log.clone()));
+    /// bloom.add(b"hello");
+    /// assert_eq!(log.0.lock().unwrap()[0].1, bloom.get_hash_indices(b"hello"));
+    /// ```
+    pub fn set_insert_hook(&mut self, events: Option<Arc<dyn FilterEvents>>) {
+        self.hook = InsertHook::new(events);
     }
 
-    pub(crate) fn set_counting_vec(&mut self, counting_vec: CountingVec<S>) {
-        assert_eq!(self.config.size, counting_vec.counters() as u64);
-        self.counting_vec = counting_vec
+    /// Returns a copy of this filter using [SparseStorage], which only keeps the non-zero words.
+    pub fn to_sparse(&self) -> BloomFilter<SparseStorage> {
+        let storage = SparseStorage::from_dense(&self.bit_set.storage);
+        BloomFilter::from_bit_vec(self.config.clone(), BloomBitVec::with_storage(storage))
     }
 
-    /// Checks if two Counting Bloom filters are compatible, i.e. have compatible parameters (hash
-    /// function, size, etc.)
-    pub fn compatible(&self, other: &BloomFilter) -> bool {
-        self.config.is_compatible_to(&other.config)
+    /// Returns a copy of this filter using a dense `Vec<usize>` storage.
+    pub fn to_dense(&self) -> BloomFilter {
+        let storage = (0..self.bit_set.storage.slots()).map(|w| self.bit_set.storage.get(w)).collect();
+        BloomFilter::from_bit_vec(self.config.clone(), BloomBitVec::with_storage(storage))
     }
 
     /// Returns the configuration/builder of the Bloom filter.
@@ -404,136 +754,2030 @@ impl<S: Storage> CountingBloomFilter<S> {
         self.config.clone()
     }
 
-    pub fn from_storage(storage: S, hashes: u32, enable_repeat_insert:bool) -> Self {
-        let mut config =
-            FilterBuilder::from_size_and_hashes((storage.slots() * 16) as u64, hashes);
-        config.enable_repeat_insert(enable_repeat_insert);
-        config.complete();
-        let counting_vec = CountingVec::new(storage);
-
-        CountingBloomFilter { config, counting_vec }
+    /// Returns [true] if the Bloom filter does not contain any elements
+    pub fn is_empty(&self) -> bool {
+        self.bit_set.is_empty()
     }
 
-    pub fn storage(&self) -> &S {
-        &self.counting_vec.storage
+    /// Tests whether an element is present in the filter (subject to the specified false
+    /// positive rate), the same as [Membership::contains] but also for read-only storage such as
+    /// a borrowed `&[u8]`.
+    #[inline]
+    pub fn contains(&self, element: &[u8]) -> bool {
+        let indices = Indices::new(element, &self.config);
+        let res = if self.config.enable_constant_time {
+            bit_check_constant_time(&self.bit_set, indices)
+        } else {
+            bit_check(&self.bit_set, indices)
+        };
+        self.stats.record_contains(res);
+        res
     }
 
-    /// Get the estimate count for element in this counting bloom filter.
-    /// See: https://github.com/yankun1992/fastbloom/issues/3
-    pub fn estimate_count(&self, element: &[u8]) -> usize {
-        let m = self.config.size;
-        let hash1 = xxh3_64_with_seed(element, 0) % m;
-        let hash2 = xxh3_64_with_seed(element, 32) % m;
-
-        let mut res = self.counting_vec.get(hash1 as usize);
-        if res == 0 { return 0; }
+    /// Tests whether an element is present like [BloomFilter::contains], but computes all `k`
+    /// words first and prefetches them before probing any, so their cache misses overlap
+    /// instead of stalling one after the other. Pays off for filters much larger than the last
+    /// level cache, where every probe misses; for many elements at once
+    /// [BloomFilter::contains_batch] also overlaps the misses of different elements. The words
+    /// are only prefetched with the `simd` feature and by storage which supports it, see
+    /// [Storage::prefetch].
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use fastbloom_rs::{FilterBuilder, Membership};
+    ///
+    /// let mut bloom = FilterBuilder::new(100_000, 0.01).build_bloom_filter();
+    /// bloom.add(b"hello");
+    /// assert_eq!(bloom.contains_prefetch(b"hello"), true);
+    /// assert_eq!(bloom.contains_prefetch(b"world"), false);
+    /// ```
+    pub fn contains_prefetch(&self, element: &[u8]) -> bool {
+        if self.config.enable_constant_time {
+            return self.contains(element);
+        }
+        let storage = &self.bit_set.storage;
+        let probes: SmallVec<[(usize, usize); 16]> = Indices::new(element, &self.config)
+            .map(|index| {
+                let (w, b) = (index / usize::BITS as u64, index % usize::BITS as u64);
+                (w as usize, le_word(1 << b))
+            })
+            .collect();
+        for &(w, _) in &probes {
+            storage.prefetch(w);
+        }
+        let res = !probes.is_empty() && probes.iter().all(|&(w, mask)| storage.get(w) & mask != 0);
+        self.stats.record_contains(res);
+        res
+    }
+
+    /// Returns the base hashes of an element, which [BloomFilter::contains_hash] and
+    /// [BloomFilter::insert_hash] take instead of the element, so a key probed against many
+    /// filters (e.g. one per partition) is hashed once. The hash is valid for all filters with
+    /// the same hasher, seed, index scheme and key length hint, the sizes and numbers of hashes
+    /// may differ; the other filters panic on it, as they would miss the element when queried
+    /// for it.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use fastbloom_rs::FilterBuilder;
+    ///
+    /// let mut partitions: Vec<_> = (1..=12u64)
+    ///     .map(|i| FilterBuilder::new(1000 * i, 0.01).build_bloom_filter())
+    ///     .collect();
+    /// let hash = partitions[0].hash(b"hello");
+    /// partitions[3].insert_hash(hash);
+    /// assert_eq!(partitions[3].contains(b"hello"), true);
+    /// let hits: Vec<bool> = partitions.iter().map(|p| p.contains_hash(hash)).collect();
+    /// assert_eq!(hits.iter().filter(|hit| **hit).count(), 1);
+    /// ```
+    #[inline]
+    pub fn hash(&self, element: &[u8]) -> ElementHash {
+        ElementHash::new(element, &self.config)
+    }
+
+    /// Tests whether the element with the base hashes `hash` of [BloomFilter::hash] is present,
+    /// like [BloomFilter::contains].
+    #[inline]
+    pub fn contains_hash(&self, hash: ElementHash) -> bool {
+        assert!(hash.is_for(&self.config), "ElementHash of a filter with other hashing!");
+        let indices = Indices::from_hash(hash, &self.config);
+        let res = if self.config.enable_constant_time {
+            bit_check_constant_time(&self.bit_set, indices)
+        } else {
+            bit_check(&self.bit_set, indices)
+        };
+        self.stats.record_contains(res);
+        res
+    }
+
+    /// Tests whether an element is present, probing only the first `k_query` of its `k` positions
+    /// (`k_query` is capped at `k`). The indices of an element are the same sequence for any
+    /// number of hashes, so an element which was added still has all probed bits set: there are
+    /// no false negatives, but the false positive probability rises from `ρ^k` to `ρ^k_query`,
+    /// `ρ = 1 - e^(-k·n/m)` being the share of set bits after `n` additions. E.g. at the optimal
+    /// fill (`ρ = 1/2`) probing 4 of the `k = 7` positions of a 1% filter gives about `2^-4 ≈ 6%`.
+    ///
+    /// Useful for tiered filtering: a cheap first pass which rejects most absent elements,
+    /// followed by [BloomFilter::contains] or an exact check for the survivors.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use fastbloom_rs::{FilterBuilder, Membership};
+    ///
+    /// let mut bloom = FilterBuilder::new(10_000, 0.01).build_bloom_filter();
+    /// bloom.add(b"hello");
+    /// assert!(bloom.contains_with_k(b"hello", 2));
+    /// assert_eq!(bloom.contains_with_k(b"world", u32::MAX), bloom.contains(b"world"));
+    /// ```
+    pub fn contains_with_k(&self, element: &[u8], k_query: u32) -> bool {
+        assert!(k_query > 0, "k_query must be positive!");
+        let indices = Indices::new(element, &self.config).truncate(k_query);
+        let res = if self.config.enable_constant_time {
+            bit_check_constant_time(&self.bit_set, indices)
+        } else {
+            bit_check(&self.bit_set, indices)
+        };
+        self.stats.record_contains(res);
+        res
+    }
+
+    /// Returns [true] if none of the canary bits (see [FilterBuilder::canaries]) is set. Hashing
+    /// never sets them, so a set canary means the memory of the filter was corrupted, e.g. by an
+    /// out-of-range write in a long-lived process.
+    pub fn check_canaries(&self) -> bool {
+        let size = self.config.size;
+        (size - self.config.canaries as u64..size).all(|index| !self.bit_set.get(index as usize))
+    }
+
+    /// Tests whether an element is present in the filter like `contains`, but probes all `k`
+    /// positions and reports how many of them are set.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use fastbloom_rs::{FilterBuilder, Membership, MembershipDetail};
+    ///
+    /// let mut bloom = FilterBuilder::new(100_000, 0.01).build_bloom_filter();
+    /// bloom.add(b"hello");
+    /// let k = bloom.config().hashes;
+    /// assert_eq!(bloom.contains_detailed(b"hello"), MembershipDetail::MaybePresent { probes_set: k });
+    /// assert_eq!(bloom.contains_detailed(b"world").is_present(), false);
+    /// ```
+    pub fn contains_detailed(&self, element: &[u8]) -> MembershipDetail {
+        let probes_set = Indices::new(element, &self.config)
+            .filter(|index| self.bit_set.get(*index as usize))
+            .count() as u32;
+        MembershipDetail::from_probes(probes_set, self.config.hashes)
+    }
+
+    /// Tests whether each of `elements` is present in the filter. Large batches (at least
+    /// 1024 elements) first compute all probe positions and visit them in sorted order, which
+    /// turns random accesses into a near-sequential scan and pays off for cold or disk-backed
+    /// storage. With the `simd` feature smaller batches are hashed a group of elements at a time
+    /// and the words of a group are prefetched before they are probed, so the cache misses of
+    /// the group overlap instead of stalling one after the other.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use fastbloom_rs::{FilterBuilder, Membership};
+    ///
+    /// let mut bloom = FilterBuilder::new(100_000, 0.01).build_bloom_filter();
+    /// bloom.add(b"hello");
+    /// assert_eq!(bloom.contains_batch(&[b"hello", b"world"]), vec![true, false]);
+    /// ```
+    pub fn contains_batch(&self, elements: &[&[u8]]) -> Vec<bool> {
+        let res: Vec<bool> = if elements.len() < SORTED_PROBE_THRESHOLD {
+            self.contains_small_batch(elements)
+        } else {
+            let mut probes = Vec::with_capacity(elements.len() * self.config.hashes as usize);
+            for (i, element) in elements.iter().enumerate() {
+                probes.extend(Indices::new(element, &self.config).map(|index| (index, i)));
+            }
+            probes.sort_unstable();
+            let mut res = vec![self.config.size != 0 && self.config.hashes != 0; elements.len()];
+            for (index, i) in probes {
+                if res[i] && !self.bit_set.get(index as usize) {
+                    res[i] = false;
+                }
+            }
+            res
+        };
+        for hit in &res {
+            self.stats.record_contains(*hit);
+        }
+        res
+    }
+
+    /// Tests up to 64 elements at once like [BloomFilter::contains_batch] and packs the answers
+    /// into a bitmask, bit `i` being set if `elements[i]` is (probably) present. Meant for
+    /// scanning joins which prune a column chunk with the mask, e.g. in `elements.chunks(64)`.
+    /// Filters of more than 1 MiB plan the probes of the batch: the words of all probes are
+    /// sorted and deduplicated, and each word is read once for all elements probing it, which
+    /// cuts the random reads of batches with locality on large filters. Panics for more than 64
+    /// elements.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use fastbloom_rs::{FilterBuilder, Membership};
+    ///
+    /// let mut bloom = FilterBuilder::new(100_000, 0.01).build_bloom_filter();
+    /// bloom.add(b"hello");
+    /// bloom.add(b"other");
+    /// assert_eq!(bloom.contains_many(&[b"hello", b"world", b"other"]), 0b101);
+    /// ```
+    pub fn contains_many(&self, elements: &[&[u8]]) -> u64 {
+        assert!(elements.len() <= 64, "contains_many takes up to 64 elements!");
+        if self.config.size >= PLANNED_PROBE_MIN_BITS {
+            let mask = self.contains_planned(elements);
+            for i in 0..elements.len() {
+                self.stats.record_contains(mask >> i & 1 == 1);
+            }
+            return mask;
+        }
+        let mut mask = 0;
+        for (i, hit) in self.contains_small_batch(elements).into_iter().enumerate() {
+            self.stats.record_contains(hit);
+            mask |= (hit as u64) << i;
+        }
+        mask
+    }
+
+    /// The probe planner of [BloomFilter::contains_many]: sorts the probes of up to 64 elements
+    /// by index, reads each word once and clears the bits of the elements with an unset probe.
+    fn contains_planned(&self, elements: &[&[u8]]) -> u64 {
+        let mut probes: SmallVec<[(u64, u8); 256]> = SmallVec::new();
+        for (i, element) in elements.iter().enumerate() {
+            probes.extend(Indices::new(element, &self.config).map(|index| (index, i as u8)));
+        }
+        if probes.is_empty() {
+            return 0;
+        }
+        probes.sort_unstable();
+        let mut mask = u64::MAX >> (64 - elements.len());
+        let bits = usize::BITS as u64;
+        let mut probes = &probes[..];
+        while let Some(&(index, _)) = probes.first() {
+            let slot = index / bits;
+            let same = probes.iter().take_while(|(index, _)| index / bits == slot).count();
+            let (group, rest) = probes.split_at(same);
+            probes = rest;
+            // no read for words of elements which are absent already
+            if group.iter().all(|&(_, i)| mask >> i & 1 == 0) {
+                continue;
+            }
+            let word = self.bit_set.word(slot as usize);
+            for &(index, i) in group {
+                if word >> (index % bits) & 1 == 0 {
+                    mask &= !(1 << i);
+                }
+            }
+        }
+        mask
+    }
+
+    /// Returns [true] if all `items` are present in the filter (also for no items at all),
+    /// stopping at the first absent one.
+    pub fn contains_all<T: AsRef<[u8]>>(&self, items: impl IntoIterator<Item=T>) -> bool {
+        items.into_iter().all(|item| self.contains(item.as_ref()))
+    }
+
+    /// Returns [true] if any of `items` is present in the filter, stopping at the first present
+    /// one.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use fastbloom_rs::{FilterBuilder, Membership};
+    ///
+    /// let mut bloom = FilterBuilder::new(100_000, 0.01).build_bloom_filter();
+    /// bloom.add(b"hello");
+    /// assert!(bloom.contains_any(["world", "hello"]));
+    /// assert!(!bloom.contains_any(["world", "other"]));
+    /// assert!(!bloom.contains_all(["world", "hello"]));
+    /// ```
+    pub fn contains_any<T: AsRef<[u8]>>(&self, items: impl IntoIterator<Item=T>) -> bool {
+        items.into_iter().any(|item| self.contains(item.as_ref()))
+    }
+
+    #[cfg(not(feature = "simd"))]
+    fn contains_small_batch(&self, elements: &[&[u8]]) -> Vec<bool> {
+        elements.iter()
+            .map(|element| bit_check(&self.bit_set, Indices::new(element, &self.config)))
+            .collect()
+    }
+
+    #[cfg(feature = "simd")]
+    fn contains_small_batch(&self, elements: &[&[u8]]) -> Vec<bool> {
+        let storage = &self.bit_set.storage;
+        let mut lanes = LaneProbes::default();
+        let mut res = Vec::with_capacity(elements.len());
+        for group in elements.chunks(LANES) {
+            lanes.load(group, &self.config);
+            lanes.prefetch(storage);
+            res.extend((0..group.len()).map(|lane| {
+                let probes = lanes.lane(lane);
+                !probes.is_empty() && probes.iter().all(|&(w, mask)| storage.get(w) & mask != 0)
+            }));
+        }
+        res
+    }
+
+    /// Builds a new Bloom filter with the same size and hashes, seeded with `new_seed`, holding
+    /// the `items` this filter (probably) contains. Bits can't be rehashed on their own, so the
+    /// caller supplies the candidate items, e.g. the source keys. Together with
+    /// [FilterBuilder::sanitized] this allows sharing a filter under a seed unknown to third
+    /// parties, which makes dictionary probing harder.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use fastbloom_rs::{FilterBuilder, Membership};
+    ///
+    /// let mut bloom = FilterBuilder::new(100_000, 0.01).build_bloom_filter();
+    /// bloom.add(b"hello");
+    /// let items: Vec<&[u8]> = vec![b"hello", b"world"];
+    /// let rehashed = bloom.rehash_into(items, 0x5eed);
+    /// assert_eq!(rehashed.config().seed, 0x5eed);
+    /// assert_eq!(rehashed.contains(b"hello"), true);
+    /// assert_eq!(rehashed.contains(b"world"), false);
+    /// ```
+    pub fn rehash_into<'a>(&self, items: impl IntoIterator<Item=&'a [u8]>, new_seed: u64) -> BloomFilter {
+        let mut config = self.config.clone();
+        config.seed = new_seed;
+        let mut filter = BloomFilter::new(config);
+        for item in items {
+            if bit_check(&self.bit_set, Indices::new(item, &self.config)) {
+                filter.add(item);
+            }
+        }
+        filter
+    }
+
+    /// Builds a new Bloom filter with the same configuration holding only the `items` this
+    /// filter (probably) contains and which pass `predicate`. Bloom filters can't drop elements,
+    /// so compaction rebuilds from the source keys.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use fastbloom_rs::{FilterBuilder, Membership};
+    ///
+    /// let mut bloom = FilterBuilder::new(100_000, 0.01).build_bloom_filter();
+    /// bloom.add(b"keep");
+    /// bloom.add(b"drop");
+    /// let items: Vec<&[u8]> = vec![b"keep", b"drop"];
+    /// let compacted = bloom.rebuild_retaining(items, |item| item != b"drop");
+    /// assert_eq!(compacted.contains(b"keep"), true);
+    /// assert_eq!(compacted.contains(b"drop"), false);
+    /// ```
+    pub fn rebuild_retaining<'a>(&self, items: impl IntoIterator<Item=&'a [u8]>,
+                                 mut predicate: impl FnMut(&[u8]) -> bool) -> BloomFilter {
+        let mut filter = BloomFilter::new(self.config.clone());
+        for item in items {
+            if bit_check(&self.bit_set, Indices::new(item, &self.config)) && predicate(item) {
+                filter.add(item);
+            }
+        }
+        filter
+    }
+
+    /// Checks if two Bloom filters are compatible, i.e. have compatible parameters (hash function,
+    /// size, etc.)
+    /// Iterates over the `k` bit indices the filter sets for `element` and probes for it, in
+    /// probe order, with the seed, hasher and index scheme of the filter; indices may repeat.
+    /// E.g. to keep the bits in an external store, see [FilterBuilder::hash_indices] for the
+    /// same without a filter.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use fastbloom_rs::{FilterBuilder, Membership};
+    ///
+    /// let mut bloom = FilterBuilder::new(100_000, 0.01).build_bloom_filter();
+    /// bloom.add(b"hello");
+    /// let indices: Vec<u64> = bloom.hash_indices(b"hello").collect();
+    /// assert_eq!(indices.len(), bloom.config().hashes as usize);
+    /// assert!(bloom.iter_set_bits().all(|index| indices.contains(&index)));
+    /// ```
+    pub fn hash_indices(&self, element: &[u8]) -> impl Iterator<Item=u64> {
+        Indices::new(element, &self.config)
+    }
+
+    /// Returns the [CompatibilityKey] of the filter, equal for filters which can be merged.
+    pub fn compatibility_key(&self) -> CompatibilityKey {
+        self.config.compatibility_key(FilterLayout::Standard)
+    }
+
+    /// Returns a new filter holding the elements of both filters, the non-mutating version of
+    /// [BloomFilter::union], or the [MismatchError] of filters which differ in size, hashing or
+    /// seed. `&a | &b` does the same but panics on a mismatch.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use fastbloom_rs::{FilterBuilder, Membership};
+    ///
+    /// let mut builder = FilterBuilder::new(10_000, 0.01);
+    /// let mut a = builder.build_bloom_filter();
+    /// let mut b = builder.build_bloom_filter();
+    /// a.add(b"hello");
+    /// b.add(b"world");
+    /// let c = a.unioned(&b).unwrap();
+    /// assert!(c.contains(b"hello") && c.contains(b"world"));
+    /// assert_eq!((&a | &b).get_u8_array(), c.get_u8_array());
+    /// assert!(!(&a & &b).contains(b"hello"));
+    ///
+    /// builder.seed(1);
+    /// assert!(a.unioned(&builder.build_bloom_filter()).is_err());
+    /// ```
+    pub fn unioned<T: Storage>(&self, other: &BloomFilter<T>)
+                               -> Result<BloomFilter, MismatchError> {
+        MismatchError::check(self.compatibility_key(), other.compatibility_key())?;
+        let mut res = self.to_dense();
+        res.bit_set.or(&other.bit_set);
+        Ok(res)
+    }
+
+    /// Returns a new filter of the bitwise AND of both filters, the non-mutating version of
+    /// [BloomFilter::intersect], or the [MismatchError] of incompatible filters. `&a & &b` does
+    /// the same but panics on a mismatch.
+    pub fn intersected<T: Storage>(&self, other: &BloomFilter<T>)
+                                   -> Result<BloomFilter, MismatchError> {
+        MismatchError::check(self.compatibility_key(), other.compatibility_key())?;
+        let mut res = self.to_dense();
+        res.bit_set.and(&other.bit_set);
+        Ok(res)
+    }
+
+    /// Returns the intersection of [BloomFilter::intersected] together with a bound on its false
+    /// positive probability, or the [MismatchError] of filters which differ in size, hashing or
+    /// seed: the bitwise AND is only the intersection of the sets for filters built alike.
+    ///
+    /// The AND differs from a filter built from the elements in both sets: it also keeps the
+    /// bits both filters set for different elements. Elements of one set only are therefore
+    /// false positives at the probability of the other filter, which is the bound, the larger
+    /// [BloomFilter::current_fpp] of both. Elements of neither set are false positives at the
+    /// much lower `current_fpp` of the result. The result's [BloomFilter::estimate_len]
+    /// overestimates the intersection for the same reason, see
+    /// [BloomFilter::intersection_estimate].
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use fastbloom_rs::{FilterBuilder, Membership};
+    ///
+    /// let mut builder = FilterBuilder::new(10_000, 0.01);
+    /// let (mut a, mut b) = (builder.build_bloom_filter(), builder.build_bloom_filter());
+    /// for i in 0..5000u32 {
+    ///     a.add(&i.to_le_bytes());
+    ///     b.add(&(i + 4000).to_le_bytes());
+    /// }
+    /// let (both, fpp) = a.intersect_with_estimate(&b).unwrap();
+    /// assert!(both.contains(&4500u32.to_le_bytes()));
+    /// assert_eq!(fpp, a.current_fpp().max(b.current_fpp()));
+    /// assert!(both.current_fpp() < fpp);
+    /// ```
+    pub fn intersect_with_estimate<T: Storage>(&self, other: &BloomFilter<T>)
+                                               -> Result<(BloomFilter, f64), MismatchError> {
+        let res = self.intersected(other)?;
+        Ok((res, self.current_fpp().max(other.current_fpp())))
+    }
+
+    /// Tests whether an element was added with [BloomFilter::add_sampled] at the same `rate`.
+    /// Elements outside of the sample are never present, so the answer is relative to the
+    /// sampled inserts only.
+    pub fn contains_sampled(&self, element: &[u8], rate: f64) -> bool {
+        is_sampled(element, &self.config, rate)
+            && bit_check(&self.bit_set, Indices::new(element, &self.config))
+    }
+
+    /// Estimates the number of distinct elements added to the filter from the number of set
+    /// bits `X`, as `-m/k * ln(1 - X/m)`. Returns infinity when all bits are set and `0` for a
+    /// filter without bits.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use fastbloom_rs::{FilterBuilder, Membership};
+    ///
+    /// let mut bloom = FilterBuilder::new(10_000, 0.01).build_bloom_filter();
+    /// for i in 0..1000u32 {
+    ///     bloom.add(&i.to_le_bytes());
+    ///     bloom.add(&i.to_le_bytes());
+    /// }
+    /// assert!((950.0..1050.0).contains(&bloom.estimate_len()));
+    /// ```
+    pub fn estimate_len(&self) -> f64 {
+        let ones = self.bit_set.count_ones();
+        if ones >= self.config.size && self.config.size != 0 {
+            return f64::INFINITY;
+        }
+        estimate_elements(ones, self.config.size, self.config.hashes)
+    }
+
+    /// Returns the share of set bits of the filter, `0` for a filter without bits.
+    pub fn fill_ratio(&self) -> f64 {
+        if self.config.size == 0 {
+            return 0.0;
+        }
+        self.bit_set.count_ones() as f64 / self.config.size as f64
+    }
+
+    /// Returns the number of set bits of the filter.
+    pub fn bits_set(&self) -> u64 {
+        self.bit_set.count_ones()
+    }
+
+    /// Iterates over the indices of the set bits in ascending order, e.g. to check the
+    /// distribution of the hashes or for an export format of your own.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use fastbloom_rs::{FilterBuilder, Membership};
+    ///
+    /// let mut bloom = FilterBuilder::new(100_000, 0.01).build_bloom_filter();
+    /// bloom.add(b"hello");
+    /// let mut indices = bloom.get_hash_indices(b"hello");
+    /// indices.sort_unstable();
+    /// indices.dedup();
+    /// assert_eq!(bloom.iter_set_bits().collect::<Vec<_>>(), indices);
+    /// assert_eq!(bloom.bits_set(), indices.len() as u64);
+    /// ```
+    pub fn iter_set_bits(&self) -> impl Iterator<Item=u64> + '_ {
+        self.bit_set.ones()
+    }
+
+    /// Returns the number of 64-bit words of the filter, see [BloomFilter::bits_in_range].
+    pub fn word_len(&self) -> usize {
+        self.bit_set.u64_words()
+    }
+
+    /// Returns the 64-bit words in the range `words` of the [word_len](Self::word_len) words,
+    /// bit `b` of word `i` standing for bit index `64 * i + b` on all platforms. Together with
+    /// [BloomFilter::apply_range] this syncs a filter in pages instead of the whole bitmap.
+    /// Panics if the range is out of bounds.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use fastbloom_rs::{FilterBuilder, Membership};
+    ///
+    /// let mut builder = FilterBuilder::new(100_000, 0.01);
+    /// let mut primary = builder.build_bloom_filter();
+    /// let mut replica = builder.build_bloom_filter();
+    /// primary.add(b"hello");
+    /// // 64 KB pages
+    /// for start in (0..primary.word_len()).step_by(8192) {
+    ///     let end = (start + 8192).min(primary.word_len());
+    ///     replica.apply_range(start, &primary.bits_in_range(start..end));
+    /// }
+    /// assert_eq!(replica.contains(b"hello"), true);
+    /// ```
+    pub fn bits_in_range(&self, words: Range<usize>) -> Vec<u64> {
+        self.bit_set.words_u64(words)
+    }
+
+    /// Returns the current false positive probability from the [fill ratio](Self::fill_ratio)
+    /// `ρ` and the number of hashes `k` as `ρ^k`, e.g. to alarm when a long-lived filter
+    /// degrades past its configured false positive probability.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use fastbloom_rs::{FilterBuilder, Membership};
+    ///
+    /// let mut bloom = FilterBuilder::new(1000, 0.01).build_bloom_filter();
+    /// assert_eq!(bloom.current_fpp(), 0.0);
+    /// for i in 0..1000u32 {
+    ///     bloom.add(&i.to_le_bytes());
+    /// }
+    /// assert!((0.005..0.015).contains(&bloom.current_fpp()));
+    /// for i in 1000..3000u32 {
+    ///     bloom.add(&i.to_le_bytes());
+    /// }
+    /// assert!(bloom.current_fpp() > 0.1);
+    /// ```
+    pub fn current_fpp(&self) -> f64 {
+        self.fill_ratio().powi(self.config.hashes as i32)
+    }
+
+    /// Estimates how many more elements can be added before the false positive probability
+    /// exceeds the configured one, from the number of set bits. See
+    /// [FilterBuilder::headroom_factor].
+    pub fn remaining_capacity(&self) -> u64 {
+        let elements = estimate_elements(self.bit_set.count_ones(), self.config.size, self.config.hashes);
+        self.config.capacity().saturating_sub(elements.round() as u64)
+    }
+
+    /// Estimates the number of elements of this filter which are not in `other` (`|A \ B|`)
+    /// from the bit counts of `other` and of the union, e.g. to quantify how far behind a
+    /// replica's filter is. Returns infinity when the filters are too full to tell, or the
+    /// [MismatchError] of filters which can't be merged.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use fastbloom_rs::{FilterBuilder, Membership};
+    ///
+    /// let mut builder = FilterBuilder::new(10_000, 0.01);
+    /// let mut primary = builder.build_bloom_filter();
+    /// let mut replica = builder.build_bloom_filter();
+    /// for i in 0..1000u32 {
+    ///     primary.add(&i.to_le_bytes());
+    ///     if i < 900 { replica.add(&i.to_le_bytes()); }
+    /// }
+    /// let behind = primary.difference_estimate(&replica).unwrap();
+    /// assert!((80.0..120.0).contains(&behind));
+    /// ```
+    pub fn difference_estimate<T: Storage>(&self, other: &BloomFilter<T>)
+                                           -> Result<f64, MismatchError> {
+        Ok(match self.cardinalities(other)? {
+            Some((_, b, union)) => (union - b).max(0.0),
+            None => f64::INFINITY,
+        })
+    }
+
+    /// Estimates the number of elements in both filters (`|A ∩ B|`) as `|A| + |B| - |A ∪ B|`
+    /// from the bit counts of both filters and of their union, e.g. to compare set summaries
+    /// shipped between services without exchanging the keys. Returns infinity when the filters
+    /// are too full to tell, or the [MismatchError] of filters which can't be merged.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use fastbloom_rs::{FilterBuilder, Membership};
+    ///
+    /// let mut builder = FilterBuilder::new(10_000, 0.01);
+    /// let (mut a, mut b) = (builder.build_bloom_filter(), builder.build_bloom_filter());
+    /// for i in 0..1000u32 {
+    ///     a.add(&i.to_le_bytes());
+    ///     b.add(&(i + 500).to_le_bytes());
+    /// }
+    /// assert!((450.0..550.0).contains(&a.intersection_estimate(&b).unwrap()));
+    /// assert!((0.3..0.37).contains(&a.jaccard(&b).unwrap()));
+    /// ```
+    pub fn intersection_estimate<T: Storage>(&self, other: &BloomFilter<T>)
+                                             -> Result<f64, MismatchError> {
+        Ok(match self.cardinalities(other)? {
+            Some((a, b, union)) => (a + b - union).max(0.0),
+            None => f64::INFINITY,
+        })
+    }
+
+    /// Estimates the Jaccard similarity `|A ∩ B| / |A ∪ B|` of the sets of both filters, see
+    /// [BloomFilter::intersection_estimate]. Returns 1 when both are empty and NaN when the
+    /// filters are too full to tell.
+    pub fn jaccard<T: Storage>(&self, other: &BloomFilter<T>) -> Result<f64, MismatchError> {
+        Ok(match self.cardinalities(other)? {
+            Some((_, _, 0.0)) => 1.0,
+            Some((a, b, union)) => ((a + b - union) / union).clamp(0.0, 1.0),
+            None => f64::NAN,
+        })
+    }
+
+    /// Estimates `(|A|, |B|, |A ∪ B|)` of mergeable filters, `None` if the union is full.
+    fn cardinalities<T: Storage>(&self, other: &BloomFilter<T>)
+                                 -> Result<Option<(f64, f64, f64)>, MismatchError> {
+        MismatchError::check(self.compatibility_key(), other.compatibility_key())?;
+        let (m, k) = (self.config.size, self.config.hashes);
+        let union_ones: u64 = (0..self.bit_set.storage.slots())
+            .map(|slot| (self.bit_set.storage.get(slot) | other.bit_set.storage.get(slot)).count_ones() as u64)
+            .sum();
+        if m != 0 && union_ones >= m {
+            return Ok(None);
+        }
+        Ok(Some((estimate_elements(self.bit_set.count_ones(), m, k),
+                 estimate_elements(other.bit_set.count_ones(), m, k),
+                 estimate_elements(union_ones, m, k))))
+    }
+}
+
+impl<S: Storage + AsRef<[usize]>> BloomFilter<S> {
+    /// Return the underlying byte vector of the Bloom filter.
+    pub fn get_u8_array(&self) -> &[u8] {
+        let storage = self.bit_set.storage.as_ref();
+        let ptr = storage.as_ptr();
+        let u8_ptr = ptr as *const u8;
+        #[cfg(target_pointer_width = "64")]
+            let ptr = slice_from_raw_parts(u8_ptr, storage.len() * 8);
+        #[cfg(target_pointer_width = "32")]
+            let ptr = slice_from_raw_parts(u8_ptr, storage.len() * 4);
+        unsafe { &*ptr }
+    }
+
+    /// Return the underlying u16 vector of the Bloom filter.
+    pub fn get_u16_array(&self) -> &[u16] {
+        let storage = self.bit_set.storage.as_ref();
+        let ptr = storage.as_ptr() as *const u16;
+        #[cfg(target_pointer_width = "64")]
+            let ptr = slice_from_raw_parts(ptr, storage.len() * 4);
+        #[cfg(target_pointer_width = "32")]
+            let ptr = slice_from_raw_parts(ptr, storage.len() * 2);
+        unsafe { &*ptr }
+    }
+
+    /// Return the underlying u32 vector of the Bloom filter.
+    pub fn get_u32_array(&self) -> &[u32] {
+        let storage = self.bit_set.storage.as_ref();
+        let ptr = storage.as_ptr() as *const u32;
+        #[cfg(target_pointer_width = "64")]
+            let ptr = slice_from_raw_parts(ptr, storage.len() * 2);
+        #[cfg(target_pointer_width = "32")]
+            let ptr = slice_from_raw_parts(ptr, storage.len());
+        unsafe { &*ptr }
+    }
+
+    /// Return the underlying u64 vector of the Bloom filter.
+    pub fn get_u64_array(&self) -> &[u64] {
+        let storage = self.bit_set.storage.as_ref();
+        let ptr = storage.as_ptr() as *const u64;
+        #[cfg(target_pointer_width = "64")]
+            let ptr = slice_from_raw_parts(ptr, storage.len());
+        if cfg!(target_pointer_width= "32") {
+            if storage.len() % 2 != 0 {
+                panic!("BloomBitVec with len {} can't export as u64 array!", storage.len())
+            }
+        }
+        #[cfg(target_pointer_width = "32")]
+            let ptr = slice_from_raw_parts(ptr, storage.len() / 2usize);
+
+        unsafe { &*ptr }
+    }
+}
+
+impl<S: StorageMut> BloomFilter<S> {
+    /// Adds the element with the base hashes `hash` of [BloomFilter::hash], like
+    /// [Membership::add]. The [insert hook](BloomFilter::set_insert_hook) gets an empty element.
+    /// Panics if the hash is of a filter with other hashing, see [BloomFilter::hash].
+    pub fn insert_hash(&mut self, hash: ElementHash) {
+        assert!(hash.is_for(&self.config), "ElementHash of a filter with other hashing!");
+        self.insert_hashed(&[], hash);
+    }
+
+    /// Adds the element like [Membership::add] and counts it in `hll`, from the same hashes.
+    /// The sketch then estimates the number of distinct elements added, see [HyperLogLog].
+    pub fn add_and_sketch(&mut self, element: &[u8], hll: &mut HyperLogLog) {
+        let hash = ElementHash::new(element, &self.config);
+        hll.add_hash(hash);
+        self.insert_hashed(element, hash);
+    }
+
+    /// Adds the element with the base hashes `hash`, reporting `element` to the insert hook.
+    fn insert_hashed(&mut self, element: &[u8], hash: ElementHash) {
+        self.stats.record_add();
+        let indices = Indices::from_hash(hash, &self.config);
+        match self.hook.get() {
+            None => bit_set(&mut self.bit_set, indices),
+            Some(events) => {
+                let indices: SmallVec<[u64; 16]> = indices.collect();
+                assert!(!indices.is_empty(), "cannot add to a filter without capacity!");
+                for &index in &indices {
+                    self.bit_set.set(index as usize);
+                }
+                events.on_insert(element, &indices);
+            }
+        }
+    }
+
+    /// Adds all `items`, taken in groups of 64 and added with [BloomFilter::add_batch], so the
+    /// caller needs no loop and the prefetching of the `simd` feature applies to any iterator.
+    /// The filter also implements [Extend] with the same effect.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use fastbloom_rs::FilterBuilder;
+    ///
+    /// let mut bloom = FilterBuilder::new(100_000, 0.01).build_bloom_filter();
+    /// bloom.add_all(["hello", "world"]);
+    /// bloom.extend((0..100u32).map(u32::to_le_bytes));
+    /// assert!(bloom.contains_all(["hello", "world"]));
+    /// assert!(bloom.contains_all((0..100u32).map(u32::to_le_bytes)));
+    /// ```
+    pub fn add_all<T: AsRef<[u8]>>(&mut self, items: impl IntoIterator<Item=T>) {
+        let mut items = items.into_iter();
+        loop {
+            let group: SmallVec<[T; BULK_GROUP]> = items.by_ref().take(BULK_GROUP).collect();
+            if group.is_empty() {
+                return;
+            }
+            let elements: SmallVec<[&[u8]; BULK_GROUP]> = group.iter().map(T::as_ref).collect();
+            self.add_batch(&elements);
+        }
+    }
+
+    /// Adds each of `elements` to the filter, the insert equivalent of
+    /// [BloomFilter::contains_batch]: with the `simd` feature the elements are hashed a group at a
+    /// time and the words of a group are prefetched before their bits are set.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use fastbloom_rs::FilterBuilder;
+    ///
+    /// let mut bloom = FilterBuilder::new(100_000, 0.01).build_bloom_filter();
+    /// bloom.add_batch(&[b"hello", b"world"]);
+    /// assert_eq!(bloom.contains_batch(&[b"hello", b"world", b"other"]), vec![true, true, false]);
+    /// ```
+    pub fn add_batch(&mut self, elements: &[&[u8]]) {
+        #[cfg(feature = "simd")]
+        if self.hook.get().is_none() {
+            let mut lanes = LaneProbes::default();
+            for group in elements.chunks(LANES) {
+                lanes.load(group, &self.config);
+                lanes.prefetch(&self.bit_set.storage);
+                for lane in 0..group.len() {
+                    let probes = lanes.lane(lane);
+                    assert!(!probes.is_empty(), "cannot add to a filter without capacity!");
+                    self.stats.record_add();
+                    for &(w, mask) in probes {
+                        self.bit_set.storage.update(w, |word| Some(word | mask));
+                    }
+                }
+            }
+            return;
+        }
+        for element in elements {
+            Membership::add(self, element);
+        }
+    }
+
+    /// Adds the passed value and returns whether the filter (probably) contained it already, i.e.
+    /// whether all its bits were set before. Hashes the element once, for dedup pipelines which
+    /// would otherwise call [BloomFilter::contains] and then [Membership::add].
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use fastbloom_rs::FilterBuilder;
+    ///
+    /// let mut bloom = FilterBuilder::new(100_000, 0.01).build_bloom_filter();
+    /// assert_eq!(bloom.check_and_set(b"hello"), false);
+    /// assert_eq!(bloom.check_and_set(b"hello"), true);
+    /// assert!(bloom.contains(b"hello"));
+    /// ```
+    pub fn check_and_set(&mut self, element: &[u8]) -> bool {
+        let indices = Indices::new(element, &self.config);
+        assert!(!indices.is_empty(), "cannot add to a filter without capacity!");
+        self.stats.record_add();
+        let mut present = true;
+        for index in indices.clone() {
+            present &= self.bit_set.get(index as usize);
+            self.bit_set.set(index as usize);
+        }
+        if let Some(events) = self.hook.get() {
+            let indices: SmallVec<[u64; 16]> = indices.collect();
+            events.on_insert(element, &indices);
+        }
+        present
+    }
+
+    /// Overwrites the 64-bit words from `offset` on with `words`, as returned by
+    /// [BloomFilter::bits_in_range] of a filter with the same configuration. Panics if the
+    /// words don't fit into the filter.
+    pub fn apply_range(&mut self, offset: usize, words: &[u64]) {
+        self.bit_set.set_words_u64(offset, words);
+    }
+
+    /// Performs the union operation on two compatible bloom filters. This is achieved through a
+    /// bitwise OR operation on their bit vectors. This operations is lossless, i.e. no elements
+    /// are lost and the bloom filter is the same that would have resulted if all elements wer
+    /// directly inserted in just one bloom filter.
+    pub fn union<T: Storage>(&mut self, other: &BloomFilter<T>) -> bool {
+        if self.compatibility_key().is_mergeable_with(&other.compatibility_key()) {
+            self.stats.record_merge();
+            self.bit_set.or(&other.bit_set);
+            true
+        } else { false }
+    }
+
+    /// Like [BloomFilter::union], but also merges filters which only differ in the number of
+    /// hashes `k`, e.g. from producers configured for different false positive probabilities.
+    /// The indices of an element are the same sequence for any `k`, so the first `min(k)` of them
+    /// are set for the elements of both filters: the result keeps the smaller `k` and has no false
+    /// negatives. Keeping the larger `k` would probe indices the other filter never set.
+    ///
+    /// The false positive probability of the result is higher than with a plain union, as fewer
+    /// indices are probed against the bits set with the larger `k`. It grows with the gap
+    /// between the two values of `k`, so rebuild the filter once the producers agree on one.
+    ///
+    /// [Partitioned](FilterBuilder::partitioned) filters with different `k` are not merged, as
+    /// `k` sets the size of their partitions and so where the indices land.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use fastbloom_rs::{FilterBuilder, Membership};
+    ///
+    /// let mut bloom = FilterBuilder::from_size_and_hashes(8192, 4).build_bloom_filter();
+    /// let mut other = FilterBuilder::from_size_and_hashes(8192, 7).build_bloom_filter();
+    /// bloom.add(b"hello");
+    /// other.add(b"world");
+    /// assert!(!bloom.union(&other));
+    /// assert!(bloom.union_lenient(&other));
+    /// assert_eq!(bloom.config().hashes, 4);
+    /// assert!(bloom.contains(b"hello") && bloom.contains(b"world"));
+    /// ```
+    pub fn union_lenient<T: Storage>(&mut self, other: &BloomFilter<T>) -> bool {
+        if self.config.partitioned && self.config.hashes != other.config.hashes {
+            return false;
+        }
+        let mut key = other.config.compatibility_key(FilterLayout::Standard);
+        key.hashes = self.config.hashes;
+        if self.config.compatibility_key(FilterLayout::Standard) == key {
+            self.stats.record_merge();
+            self.config.hashes = self.config.hashes.min(other.config.hashes);
+            self.bit_set.or(&other.bit_set);
+            true
+        } else { false }
+    }
+
+    /// Performs the intersection operation on two compatible bloom filters. This is achieved
+    /// through a bitwise AND operation on their bit vectors. The operations doesn't introduce
+    /// any false negatives but it does raise the false positive probability. The the false
+    /// positive probability in the resulting Bloom filter is at most the false-positive probability
+    /// in one of the constituent bloom filters
+    pub fn intersect<T: Storage>(&mut self, other: &BloomFilter<T>) -> bool {
+        if self.compatibility_key().is_mergeable_with(&other.compatibility_key()) {
+            self.stats.record_merge();
+            self.bit_set.and(&other.bit_set);
+            true
+        } else { false }
+    }
+
+    /// Adds the passed value only if it falls into a deterministic, hash-based sample of a
+    /// `rate` (between `0.0` and `1.0`) of all elements, and returns whether it did. Useful for
+    /// memory-bounded "have we ever logged this?" suppression: query with
+    /// [BloomFilter::contains_sampled] and the same rate.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use fastbloom_rs::FilterBuilder;
+    ///
+    /// let mut bloom = FilterBuilder::new(10_000, 0.01).build_bloom_filter();
+    /// let sampled = bloom.add_sampled(b"hello", 0.1);
+    /// assert_eq!(bloom.contains_sampled(b"hello", 0.1), sampled);
+    /// ```
+    pub fn add_sampled(&mut self, element: &[u8], rate: f64) -> bool {
+        if !is_sampled(element, &self.config, rate) {
+            return false;
+        }
+        self.stats.record_add();
+        bit_set(&mut self.bit_set, Indices::new(element, &self.config));
+        true
+    }
+
+    /// Retouches the filter (retouched Bloom filter): for every known false positive still
+    /// reported present, clears its lowest probed position. This removes the false positives at
+    /// the cost of false negatives for the members sharing a cleared bit, about `n * k / m`
+    /// members per cleared bit. Returns the number of cleared bits. Prefer
+    /// [BloomFilter::retouch_with_members] when the members are at hand.
+    ///
+    /// **Reference**: Donnet, B., Baynat, B., & Friedman, T. (2006). Retouched bloom filters:
+    /// allowing networked applications to trade off selected false positives against false
+    /// negatives. In Proceedings of the 2006 ACM CoNEXT conference.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use fastbloom_rs::{FilterBuilder, Membership};
+    ///
+    /// let mut bloom = FilterBuilder::new(100, 0.5).build_bloom_filter();
+    /// for i in 0..100u32 {
+    ///     bloom.add(&i.to_le_bytes());
+    /// }
+    /// let false_positives: Vec<[u8; 4]> = (100..1000u32).map(|i| i.to_le_bytes())
+    ///     .filter(|key| bloom.contains(key)).collect();
+    /// bloom.retouch(false_positives.iter().map(|key| &key[..]));
+    /// assert!(false_positives.iter().all(|key| !bloom.contains(key)));
+    /// ```
+    pub fn retouch<'a>(&mut self, false_positives: impl IntoIterator<Item=&'a [u8]>) -> usize {
+        let mut cleared = 0;
+        for element in false_positives {
+            let indices = Indices::new(element, &self.config);
+            if bit_check(&self.bit_set, indices.clone()) {
+                self.bit_set.unset(indices.min().unwrap() as usize);
+                cleared += 1;
+            }
+        }
+        cleared
+    }
+
+    /// Same as [BloomFilter::retouch], but clears the probed position of each false positive
+    /// shared by the fewest of the `members` (the elements added to the filter), which keeps
+    /// the number of introduced false negatives minimal. Returns the number of cleared bits.
+    pub fn retouch_with_members<'a, 'b>(&mut self,
+                                        false_positives: impl IntoIterator<Item=&'a [u8]>,
+                                        members: impl IntoIterator<Item=&'b [u8]>) -> usize {
+        let false_positives: Vec<Indices> = false_positives.into_iter()
+            .map(|element| Indices::new(element, &self.config))
+            .filter(|indices| bit_check(&self.bit_set, indices.clone()))
+            .collect();
+        // number of members mapping to each candidate position
+        let mut shared: BTreeMap<u64, usize> =
+            false_positives.iter().flat_map(|indices| indices.clone()).map(|i| (i, 0)).collect();
+        for element in members {
+            for index in Indices::new(element, &self.config) {
+                if let Some(count) = shared.get_mut(&index) {
+                    *count += 1;
+                }
+            }
+        }
+
+        let mut cleared = 0;
+        for indices in false_positives {
+            if bit_check(&self.bit_set, indices.clone()) {
+                let index = indices.min_by_key(|index| shared[index]).unwrap();
+                self.bit_set.unset(index as usize);
+                cleared += 1;
+            }
+        }
+        cleared
+    }
+
+    pub(crate) fn set_bit_vec(&mut self, bit_vec: BloomBitVec<S>) {
+        assert_eq!(self.config.size, bit_vec.nbits as u64);
+        self.bit_set = bit_vec
+    }
+}
+
+/// A Counting Bloom filter works in a similar manner as a regular Bloom filter; however, it is
+/// able to keep track of insertions and deletions. In a counting Bloom filter, each entry in the
+/// Bloom filter is a small counter associated with a basic Bloom filter bit.
+///
+/// **Reference**: F. Bonomi, M. Mitzenmacher, R. Panigrahy, S. Singh, and G. Varghese, “An Improved
+/// Construction for Counting Bloom Filters,” in 14th Annual European Symposium on
+/// Algorithms, LNCS 4168, 2006
+///
+/// Counters are `W` bits wide, 4 by default. Wider counters (8 or 16 bits) take more memory but
+/// saturate later under hot keys, 2-bit counters halve the memory for sets with few duplicates;
+/// see [FilterBuilder::build_counting_bloom_filter_with_width].
+#[derive(Clone)]
+#[derive(Debug)]
+pub struct CountingBloomFilter<S=Vec<usize>, const W: usize = 4> {
+    pub(crate) config: FilterBuilder,
+    pub(crate) counting_vec: CountingVec<S, W>,
+    pub(crate) stats: OpCounters,
+    /// Number of increments of saturated counters.
+    overflows: u64,
+    /// Increments beyond the maximum by counter, see [CountingBloomFilter::enable_overflow_map].
+    overflow_map: Option<BTreeMap<usize, usize>>,
+    /// Number of adds by key, see [CountingBloomFilter::with_key_log].
+    key_log: Option<BTreeMap<Vec<u8>, usize>>,
+    /// Events of saturating increments, see [CountingBloomFilter::set_events].
+    hook: InsertHook,
+}
+
+/// An empty filter without capacity, see the [Default] of [BloomFilter].
+impl<S: Storage, const W: usize> Default for CountingBloomFilter<S, W> where S::Init: Default {
+    fn default() -> Self {
+        CountingBloomFilter::with_width(FilterBuilder::zero_capacity(), S::Init::default())
+    }
+}
+
+impl<S: Storage> CountingBloomFilter<S> {
+    pub fn new(config: FilterBuilder, init: S::Init) -> Self {
+        CountingBloomFilter::with_width(config, init)
+    }
+
+    pub fn from_storage(storage: S, hashes: u32, enable_repeat_insert:bool) -> Self {
+        let mut config =
+            FilterBuilder::from_size_and_hashes((storage.slots() * 16) as u64, hashes);
+        config.enable_repeat_insert(enable_repeat_insert);
+        config.complete();
+        let counting_vec = CountingVec::new(storage);
+
+        CountingBloomFilter {
+            config,
+            counting_vec,
+            stats: OpCounters::default(),
+            overflows: 0,
+            overflow_map: None,
+            key_log: None,
+            hook: InsertHook::default(),
+        }
+    }
+}
+
+impl<S: Storage, const W: usize> CountingBloomFilter<S, W> {
+    /// Build a filter with counters of `W` bits, see
+    /// [FilterBuilder::build_counting_bloom_filter_with_width].
+    pub(crate) fn with_width(mut config: FilterBuilder, init: S::Init) -> Self {
+        config.complete_for(FilterLayout::Counting(W as u32));
+        let slots = config.size / CountingVec::<S, W>::PER_SLOT as u64;
+        let counting_vec = CountingVec::new(S::new(slots as usize, init));
+        CountingBloomFilter::from_counting_vec(config, counting_vec)
+    }
+
+    /// Build a filter with full 32- or 64-bit counters (`W`), two or one per storage word, for
+    /// frequency workloads whose counts would saturate narrow counters: counts stay exact up to
+    /// `2^W - 1` without an [overflow map](CountingBloomFilter::enable_overflow_map), at 8 or
+    /// 16 times the memory of 4-bit counters. Panics at compile time for other widths.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use fastbloom_rs::{CountingBloomFilter, FilterBuilder};
+    ///
+    /// let mut builder = FilterBuilder::new(1000, 0.01);
+    /// builder.enable_repeat_insert(true);
+    /// let mut cbf: CountingBloomFilter<Vec<usize>, 32> =
+    ///     CountingBloomFilter::with_wide_counters(builder, ());
+    /// for _ in 0..100_000 {
+    ///     cbf.add(b"abuser");
+    /// }
+    /// assert_eq!(cbf.estimate_count(b"abuser"), 100_000);
+    /// assert_eq!(cbf.saturated_counters(), 0);
+    /// ```
+    pub fn with_wide_counters(config: FilterBuilder, init: S::Init) -> Self {
+        const { assert!(W == 32 || W == 64, "wide counters have 32 or 64 bits!") };
+        CountingBloomFilter::with_width(config, init)
+    }
+
+    pub(crate) fn from_counting_vec(config: FilterBuilder, counting_vec: CountingVec<S, W>)
+                                    -> Self {
+        assert_eq!(config.size, counting_vec.counters() as u64);
+        CountingBloomFilter {
+            config,
+            counting_vec,
+            stats: OpCounters::default(),
+            overflows: 0,
+            overflow_map: None,
+            key_log: None,
+            hook: InsertHook::default(),
+        }
+    }
+
+    pub(crate) fn set_counting_vec(&mut self, counting_vec: CountingVec<S, W>) {
+        assert_eq!(self.config.size, counting_vec.counters() as u64);
+        self.counting_vec = counting_vec
+    }
+
+    /// Checks if two Counting Bloom filters are compatible, i.e. have compatible parameters (hash
+    /// function, size, etc.)
+    pub fn compatible<T>(&self, other: &BloomFilter<T>) -> bool {
+        self.config.is_compatible_to(&other.config)
+    }
+
+    /// Returns the [CompatibilityKey] of the filter, equal for filters which can be merged.
+    pub fn compatibility_key(&self) -> CompatibilityKey {
+        self.config.compatibility_key(FilterLayout::Counting(W as u32))
+    }
+
+    /// Returns the configuration/builder of the Bloom filter.
+    /// # Examples
+    ///
+    /// ```rust
+    /// use fastbloom_rs::{BloomFilter, FilterBuilder};
+    ///
+    /// let bloom = FilterBuilder::new(100_000_000, 0.01).build_bloom_filter();
+    /// let builder = bloom.config();
+    /// ```
+    ///
+    pub fn config(&self) -> FilterBuilder {
+        self.config.clone()
+    }
+
+    pub fn storage(&self) -> &S {
+        &self.counting_vec.storage
+    }
+
+    /// Touches every page of the storage, see [BloomFilter::prefault_all].
+    pub fn prefault_all(&self) {
+        self.counting_vec.storage.prefault();
+    }
+
+    /// Passes the expected access pattern of the storage on to the OS, see [Advice].
+    pub fn advise(&self, advice: Advice) {
+        self.counting_vec.storage.advise(advice);
+    }
+
+    /// Returns the per-operation counters of this filter.
+    #[cfg(feature = "stats")]
+    pub fn stats(&self) -> OpStats {
+        self.stats.snapshot()
+    }
+
+    /// Resets the per-operation counters of this filter.
+    #[cfg(feature = "stats")]
+    pub fn reset_stats(&self) {
+        self.stats.reset()
+    }
+
+    /// Returns the metrics of this filter, counting its non-zero counters; the saturation
+    /// events are the [overflows](CountingBloomFilter::overflows).
+    #[cfg(feature = "metrics")]
+    pub fn metrics(&self) -> FilterMetrics {
+        let size = self.config.size;
+        let fill_ratio = if size == 0 {
+            0.0
+        } else {
+            self.counting_vec.iter().filter(|count| *count != 0).count() as f64 / size as f64
+        };
+        FilterMetrics {
+            ops: self.stats.snapshot(),
+            fill_ratio,
+            estimated_fpp: fill_ratio.powi(self.config.hashes as i32),
+            saturation_events: self.overflows,
+        }
+    }
+
+    /// Registers `events` to be called with [FilterEvents::on_counter_saturate] on every
+    /// increment of a counter at its maximum, e.g. to alert before the counts of hot keys drift;
+    /// `None` removes it. Clones of the filter share the registered events.
+    pub fn set_events(&mut self, events: Option<Arc<dyn FilterEvents>>) {
+        self.hook = InsertHook::new(events);
+    }
 
-        for i in 1..self.config.hashes as u64 {
-            let mo = ((hash1 + i * hash2) % m) as usize;
-            let count = self.counting_vec.get(mo);
+    /// Get the estimate count for element in this counting bloom filter.
+    /// See: https://github.com/yankun1992/fastbloom/issues/3
+    ///
+    /// This is the minimum of the counters at the positions of the element, like a count-min
+    /// sketch, so the filter doubles as a frequency estimator when it is built with
+    /// [FilterBuilder::enable_repeat_insert]. The estimate never undercounts (collisions only add
+    /// to counters), [FilterBuilder::enable_conservative_update] reduces the overcount, and it
+    /// saturates at the largest counter value (`15` with the default 4-bit counters).
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use fastbloom_rs::FilterBuilder;
+    ///
+    /// let mut builder = FilterBuilder::new(10_000, 0.01);
+    /// builder.enable_repeat_insert(true);
+    /// let mut cbf = builder.build_counting_bloom_filter::<Vec<usize>>(());
+    /// for _ in 0..3 {
+    ///     cbf.add(b"hello");
+    /// }
+    /// assert_eq!(cbf.estimate_count(b"hello"), 3);
+    /// assert_eq!(cbf.estimate_count(b"world"), 0);
+    /// ```
+    pub fn estimate_count(&self, element: &[u8]) -> usize {
+        let mut res = usize::MAX;
+        for index in Indices::new(element, &self.config) {
+            let count = self.count(index as usize);
             if count == 0 { return 0; } else { res = min(count, res) }
         }
+        if res == usize::MAX { 0 } else { res }
+    }
+
+    /// Returns the counter at `index` plus its increments in the overflow map.
+    #[inline]
+    fn count(&self, index: usize) -> usize {
+        let count = self.counting_vec.get(index);
+        match &self.overflow_map {
+            Some(map) if count == CountingVec::<S, W>::MAX => {
+                count + map.get(&index).copied().unwrap_or(0)
+            }
+            _ => count,
+        }
+    }
+
+    /// Returns the number of increments of a counter at its maximum (`15` with the default
+    /// 4-bit counters) since the filter was built or cleared. Without the
+    /// [overflow map](CountingBloomFilter::enable_overflow_map) these increments are lost: the
+    /// counts of hot keys are too low and removing them leaves their counters too high, or
+    /// drops the counters of other keys sharing them. A growing number means the counters are
+    /// too narrow for the workload, see [FilterBuilder::build_counting_bloom_filter_with_width].
+    pub fn overflows(&self) -> u64 {
+        self.overflows
+    }
+
+    /// Returns the number of counters at their maximum, which no longer count exactly.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use fastbloom_rs::FilterBuilder;
+    ///
+    /// let mut builder = FilterBuilder::new(1000, 0.01);
+    /// builder.enable_repeat_insert(true);
+    /// let mut cbf = builder.build_counting_bloom_filter::<Vec<usize>>(());
+    /// for _ in 0..20 {
+    ///     cbf.add(b"hello");
+    /// }
+    /// assert_eq!(cbf.saturated_counters(), cbf.counters_of(b"hello").len());
+    /// assert_eq!(cbf.overflows(), 5 * cbf.counters_of(b"hello").len() as u64);
+    /// ```
+    pub fn saturated_counters(&self) -> usize {
+        self.counting_vec.iter().filter(|count| *count == CountingVec::<S, W>::MAX).count()
+    }
+
+    /// Keeps the increments of saturated counters in a secondary map from then on, so
+    /// [CountingBloomFilter::estimate_count] and removes stay exact for hot keys, at the cost of
+    /// a map entry per saturated counter. The map is not part of serialized filters or
+    /// snapshots, which keep the saturated counters.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use fastbloom_rs::FilterBuilder;
+    ///
+    /// let mut builder = FilterBuilder::new(1000, 0.01);
+    /// builder.enable_repeat_insert(true);
+    /// let mut cbf = builder.build_counting_bloom_filter::<Vec<usize>>(());
+    /// cbf.enable_overflow_map();
+    /// for _ in 0..20 {
+    ///     cbf.add(b"hello");
+    /// }
+    /// assert_eq!(cbf.estimate_count(b"hello"), 20);
+    /// ```
+    pub fn enable_overflow_map(&mut self) {
+        self.overflow_map.get_or_insert_with(BTreeMap::new);
+    }
+
+    /// Keeps a log of the added keys and their number of adds from now on, so
+    /// [CountingBloomFilter::resize] can rebuild the filter: the counters don't tell which
+    /// elements they count, a larger filter can only be filled from the keys. Costs the memory
+    /// of all distinct keys. Removes take keys out of the log, unions and merges keep it only if
+    /// the other filter keeps one too. The log is not part of serialized filters or snapshots.
+    /// Panics unless the filter is empty.
+    pub fn with_key_log(mut self) -> Self {
+        assert!(self.counting_vec.iter().all(|count| count == 0),
+                "the key log must start with an empty filter!");
+        self.key_log = Some(BTreeMap::new());
+        self
+    }
+
+    /// Returns the number of distinct keys in the log of [CountingBloomFilter::with_key_log],
+    /// or `None` without a log.
+    pub fn logged_keys(&self) -> Option<usize> {
+        self.key_log.as_ref().map(BTreeMap::len)
+    }
+
+    /// Returns the `k` counters of an element in probe order, e.g. for frequency analysis over
+    /// many keys.
+    pub fn counters_of(&self, element: &[u8]) -> SmallVec<[usize; 16]> {
+        self.counting_vec.get_many(Indices::new(element, &self.config))
+    }
+
+    /// Iterates over all counters of the filter in index order.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use fastbloom_rs::FilterBuilder;
+    ///
+    /// let mut cbf = FilterBuilder::new(1000, 0.01).build_counting_bloom_filter::<Vec<usize>>(());
+    /// cbf.add(b"hello");
+    /// let total: usize = cbf.counters_iter().sum();
+    /// assert_eq!(total, cbf.counters_of(b"hello").len());
+    /// ```
+    pub fn counters_iter(&self) -> impl Iterator<Item=usize> + '_ {
+        self.counting_vec.iter()
+    }
+
+    /// Iterates over the counters greater than `threshold` as `(index, value)` in index order,
+    /// e.g. to spot the counters of hot keys before they saturate at `2^W - 1`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use fastbloom_rs::FilterBuilder;
+    ///
+    /// let mut builder = FilterBuilder::new(1000, 0.01);
+    /// builder.enable_repeat_insert(true);
+    /// let mut cbf = builder.build_counting_bloom_filter::<Vec<usize>>(());
+    /// cbf.add(b"world");
+    /// for _ in 0..10 {
+    ///     cbf.add(b"hello");
+    /// }
+    /// let hot: Vec<(usize, usize)> = cbf.counters_above(5).collect();
+    /// assert_eq!(hot.len(), cbf.counters_of(b"hello").len());
+    /// assert_eq!(cbf.max_counter(), 10);
+    /// ```
+    pub fn counters_above(&self, threshold: usize) -> impl Iterator<Item=(usize, usize)> + '_ {
+        self.counting_vec.iter().enumerate().filter(move |(_, count)| *count > threshold)
+    }
+
+    /// Returns the largest counter, `0` for an empty filter. A counter of `2^W - 1` is
+    /// saturated, see [CountingBloomFilter::saturated_counters].
+    pub fn max_counter(&self) -> usize {
+        self.counting_vec.iter().max().unwrap_or(0)
+    }
+
+    /// Returns a [BloomFilter] with the same configuration and a bit set wherever a counter is
+    /// non-zero, answering queries like this filter at a `W`th of the memory. E.g. to ship a
+    /// read-only snapshot after a build phase with many removals.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use fastbloom_rs::{Deletable, FilterBuilder};
+    ///
+    /// let mut builder = FilterBuilder::new(100_000, 0.01);
+    /// let mut cbf = builder.build_counting_bloom_filter::<Vec<usize>>(());
+    /// cbf.add(b"hello");
+    /// cbf.add(b"world");
+    /// cbf.remove(b"world");
+    /// let bloom = cbf.to_bloom_filter();
+    /// assert_eq!(bloom.contains(b"hello"), true);
+    /// assert_eq!(bloom.contains(b"world"), false);
+    /// ```
+    pub fn to_bloom_filter(&self) -> BloomFilter {
+        let mut bloom = BloomFilter::new(self.config.clone());
+        for (index, counter) in self.counting_vec.iter().enumerate() {
+            if counter != 0 {
+                bloom.bit_set.set(index);
+            }
+        }
+        bloom
+    }
+
+    /// Tests whether an element is present in the filter, but probes all `k` counters and
+    /// reports how many of them are non-zero.
+    pub fn contains_detailed(&self, element: &[u8]) -> MembershipDetail {
+        let probes_set = Indices::new(element, &self.config)
+            .filter(|index| self.counting_vec.get(*index as usize) > 0)
+            .count() as u32;
+        MembershipDetail::from_probes(probes_set, self.config.hashes)
+    }
+
+    /// Get the underlying counter at index.
+    pub fn counter_at(&self, index: u64) -> usize {
+        self.counting_vec.get(index as usize)
+    }
+
+    /// Estimates how similar the frequency distributions of two compatible filters are, as the
+    /// weighted Jaccard similarity of their counters (sum of the counter-wise minimums over the
+    /// sum of the maximums): `1.0` for identical counters, `0.0` for disjoint ones. Lets a
+    /// pipeline verify that two aggregation paths produced approximately the same multiset.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use fastbloom_rs::FilterBuilder;
+    ///
+    /// let mut builder = FilterBuilder::new(10_000, 0.01);
+    /// let mut a = builder.build_counting_bloom_filter::<Vec<usize>>(());
+    /// let mut b = builder.build_counting_bloom_filter::<Vec<usize>>(());
+    /// for i in 0..100u32 {
+    ///     a.add(&i.to_le_bytes());
+    ///     b.add(&i.to_le_bytes());
+    /// }
+    /// assert_eq!(a.multiset_similarity(&b), 1.0);
+    /// b.add(&0u32.to_le_bytes());
+    /// assert!(a.multiset_similarity(&b) > 0.99);
+    /// ```
+    pub fn multiset_similarity<T: Storage>(&self, other: &CountingBloomFilter<T, W>) -> f64 {
+        assert!(self.compatibility_key().is_mergeable_with(&other.compatibility_key()),
+                "filters must be compatible!");
+        let (mut min, mut max) = (0u64, 0u64);
+        let (a, b) = (&self.counting_vec.storage, &other.counting_vec.storage);
+        for slot in 0..a.slots() {
+            let (mut a, mut b) = (a.get(slot), b.get(slot));
+            while a | b != 0 {
+                let mask = CountingVec::<S, W>::MAX;
+                let (x, y) = ((a & mask) as u64, (b & mask) as u64);
+                min += x.min(y);
+                max += x.max(y);
+                // a 64-bit counter fills the word, shifting it out would overflow
+                a = a.checked_shr(W as u32).unwrap_or(0);
+                b = b.checked_shr(W as u32).unwrap_or(0);
+            }
+        }
+        if max == 0 { 1.0 } else { min as f64 / max as f64 }
+    }
+}
+
+const SNAPSHOT_MAGIC: &[u8; 4] = b"FBCS";
+
+impl<S: Storage> CountingBloomFilter<S> {
+    /// Returns a compressed copy of this filter suitable for archival: runs of all-zero counter
+    /// words are stored as a length and the parameters needed to query the filter are kept in a
+    /// small header. Restore it with [CountingBloomFilter::from_snapshot_compressed].
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use fastbloom_rs::{CountingBloomFilter, FilterBuilder};
+    ///
+    /// let mut cbf = FilterBuilder::new(100_000, 0.01).build_counting_bloom_filter::<Vec<usize>>(());
+    /// cbf.add(b"hello");
+    /// let snapshot = cbf.snapshot_compressed();
+    /// assert!(snapshot.len() < 1024);
+    /// let restored = CountingBloomFilter::from_snapshot_compressed(&snapshot).unwrap();
+    /// assert_eq!(restored.estimate_count(b"hello"), 1);
+    /// ```
+    pub fn snapshot_compressed(&self) -> Vec<u8> {
+        let storage = &self.counting_vec.storage;
+        let mut out = Vec::new();
+        out.extend_from_slice(SNAPSHOT_MAGIC);
+        let flags = self.config.enable_repeat_insert as u8
+            | (self.config.enable_conservative_update as u8) << 1;
+        put_hashing(&mut out, &self.config, flags, FormatFeatures::COUNTING_4BIT);
+        out.extend_from_slice(&(storage.slots() as u64).to_le_bytes());
+
+        // pairs of (zero words, non-zero words) followed by the non-zero words
+        let mut slot = 0;
+        while slot < storage.slots() {
+            let zeros = (slot..storage.slots()).take_while(|w| storage.get(*w) == 0).count();
+            slot += zeros;
+            let words = (slot..storage.slots()).take_while(|w| storage.get(*w) != 0).count();
+            put_varint(&mut out, zeros as u64);
+            put_varint(&mut out, words as u64);
+            for w in slot..slot + words {
+                out.extend_from_slice(&(storage.get(w) as u64).to_le_bytes());
+            }
+            slot += words;
+        }
+        out
+    }
+}
+
+impl CountingBloomFilter {
+    /// Restores a filter from [CountingBloomFilter::snapshot_compressed] to its full width.
+    /// Returns `None` when `bytes` is not a valid snapshot.
+    pub fn from_snapshot_compressed(bytes: &[u8]) -> Option<Self> {
+        let mut reader = Reader::new(bytes);
+        if reader.take(4)? != SNAPSHOT_MAGIC {
+            return None;
+        }
+        let hashing = reader.hashing()?;
+        // snapshots written before the features were recorded have none.
+        if !hashing.features.is_empty() && hashing.features != FormatFeatures::COUNTING_4BIT {
+            return None;
+        }
+        let slots = usize::try_from(reader.u64()?).ok()?;
+
+        // the runs are checked against the size before allocating it, and a zero run of any
+        // length takes a few bytes, so a forged size fails on the allocation, not with an abort.
+        let runs = reader.clone();
+        let mut filled = 0usize;
+        while !reader.is_empty() {
+            let zeros = usize::try_from(reader.varint()?).ok()?;
+            let words = usize::try_from(reader.varint()?).ok()?;
+            if zeros.checked_add(words)? > slots - filled {
+                return None;
+            }
+            reader.take(words.checked_mul(8)?)?;
+            filled += zeros + words;
+        }
+        if filled != slots || slots == 0 {
+            return None;
+        }
+        let mut storage = Vec::new();
+        storage.try_reserve_exact(slots).ok()?;
+        let mut reader = runs;
+        while !reader.is_empty() {
+            let zeros = reader.varint()? as usize;
+            let words = reader.varint()? as usize;
+            storage.resize(storage.len() + zeros, 0);
+            for _ in 0..words {
+                storage.push(usize::try_from(reader.u64()?).ok()?);
+            }
+        }
+
+        let flags = hashing.flags;
+        let mut cbf = CountingBloomFilter::from_storage(storage, hashing.hashes, flags & 1 != 0);
+        cbf.config.enable_conservative_update = flags & 2 != 0;
+        hashing.apply(&mut cbf.config)?;
+        Some(cbf)
+    }
+}
+
+const COMPRESSED_MAGIC: &[u8; 4] = b"FBCB";
+/// Flag of [BloomFilter::to_compressed_bytes] for the raw words instead of the bit gaps.
+const COMPRESSED_RAW: u8 = 1;
+
+impl<S: Storage> BloomFilter<S> {
+    /// Returns a compact copy of this filter for storage or transfer: the positions of the set
+    /// bits are stored as varint gaps, about one byte per set bit while the filter is less than
+    /// about 10% full, so an 8 MB filter at 2% takes about 1.3 MB. Fuller filters keep their
+    /// words as is, the copy is never much larger than [BloomFilter::to_static_bytes]. Restore
+    /// it with [BloomFilter::from_compressed_bytes].
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use fastbloom_rs::{BloomFilter, FilterBuilder, Membership};
+    ///
+    /// let mut bloom = FilterBuilder::new(100_000, 0.01).build_bloom_filter();
+    /// bloom.add(b"hello");
+    /// let bytes = bloom.to_compressed_bytes();
+    /// assert!(bytes.len() < 64);
+    /// let restored = BloomFilter::from_compressed_bytes(&bytes).unwrap();
+    /// assert_eq!(restored.contains(b"hello"), true);
+    /// ```
+    pub fn to_compressed_bytes(&self) -> Vec<u8> {
+        let slots = self.bit_set.storage.slots();
+        let raw_len = slots * size_of::<usize>();
+        // varint gaps between the set bits, preceded by their number
+        let (mut gaps, mut count) = (Vec::new(), 0u64);
+        let mut next = 0u64;
+        for bit in self.bit_set.ones() {
+            put_varint(&mut gaps, bit - next);
+            next = bit + 1;
+            count += 1;
+            if gaps.len() > raw_len {
+                break;
+            }
+        }
 
-        res
+        let mut out = Vec::with_capacity(32 + gaps.len().min(raw_len));
+        out.extend_from_slice(COMPRESSED_MAGIC);
+        let raw = gaps.len() > raw_len;
+        put_hashing(&mut out, &self.config, raw as u8 * COMPRESSED_RAW, FormatFeatures::default());
+        out.extend_from_slice(&self.config.size.to_le_bytes());
+        if raw {
+            for slot in 0..slots {
+                out.extend_from_slice(&self.bit_set.word(slot).to_le_bytes());
+            }
+        } else {
+            put_varint(&mut out, count);
+            out.extend_from_slice(&gaps);
+        }
+        out
     }
+}
 
-    /// Get the underlying counter at index.
-    pub fn counter_at(&self, index: u64) -> usize {
-        self.counting_vec.get(index as usize)
+impl BloomFilter {
+    /// Restores a filter from [BloomFilter::to_compressed_bytes]. Returns `None` when `bytes`
+    /// is not such a filter.
+    pub fn from_compressed_bytes(bytes: &[u8]) -> Option<Self> {
+        let mut reader = Reader::new(bytes);
+        if reader.take(4)? != COMPRESSED_MAGIC {
+            return None;
+        }
+        let hashing = reader.hashing()?;
+        if !hashing.features.is_empty() || hashing.flags & !COMPRESSED_RAW != 0 {
+            return None;
+        }
+        let size = reader.u64()?;
+        if size == 0 || size % 64 != 0 {
+            return None;
+        }
+        let slots = usize::try_from(size / usize::BITS as u64).ok()?;
+
+        let storage = if hashing.flags & COMPRESSED_RAW != 0 {
+            let words = reader.take(slots.checked_mul(size_of::<usize>())?)?;
+            if !reader.is_empty() {
+                return None;
+            }
+            words.chunks_exact(size_of::<usize>())
+                .map(|word| le_word(usize::from_le_bytes(word.try_into().unwrap())))
+                .collect()
+        } else {
+            // the gaps are checked against the size before allocating it, and an empty filter
+            // of any size takes a few bytes, so a forged size fails on the allocation, not with
+            // an abort.
+            let count = reader.varint()?;
+            let gaps = reader.clone();
+            let mut next = 0u64;
+            for _ in 0..count {
+                let bit = next.checked_add(reader.varint()?)?;
+                if bit >= size {
+                    return None;
+                }
+                next = bit + 1;
+            }
+            if !reader.is_empty() {
+                return None;
+            }
+            let mut storage = Vec::new();
+            storage.try_reserve_exact(slots).ok()?;
+            storage.resize(slots, 0usize);
+            let (mut reader, mut next) = (gaps, 0u64);
+            for _ in 0..count {
+                let bit = next + reader.varint()?;
+                let slot = (bit / usize::BITS as u64) as usize;
+                storage[slot] |= 1 << (bit % usize::BITS as u64);
+                next = bit + 1;
+            }
+            storage.into_iter().map(le_word).collect()
+        };
+
+        let mut config = FilterBuilder::from_size_and_hashes(size, hashing.hashes);
+        hashing.apply(&mut config)?;
+        Some(BloomFilter::from_bit_vec(config, BloomBitVec::with_storage(storage)))
     }
 }
 
-impl<S: StorageMut> CountingBloomFilter<S> {
+impl<S: StorageMut, const W: usize> CountingBloomFilter<S, W> {
     pub fn add(&mut self, element: &[u8]) {
-        let m = self.config.size;
-        // let hash1 = (murmur3_x64_128(element, 0) % m) as u64;
-        // let hash2 = (murmur3_x64_128(element, 32) % m) as u64;
-        let hash1 = xxh3_64_with_seed(element, 0) % m;
-        let hash2 = xxh3_64_with_seed(element, 32) % m;
+        self.stats.record_add();
+        let indices = Indices::new(element, &self.config);
+        assert!(!indices.is_empty(), "cannot add to a filter without capacity!");
 
-        let mut res = self.counting_vec.get(hash1 as usize) > 0;
-        // let m = self.config.size;
-        for i in 1..self.config.hashes as u64 {
-            let mo = ((hash1 + i * hash2) % m) as usize;
-            res = res && (self.counting_vec.get(mo) > 0);
+        // contains and not enable repeat insert
+        if !self.config.enable_repeat_insert && self.contains_indices(indices.clone()) {
+            return;
+        }
+        if let Some(log) = &mut self.key_log {
+            // the key is copied on its first add only
+            match log.get_mut(element) {
+                Some(adds) => *adds += 1,
+                None => {
+                    log.insert(element.to_vec(), 1);
+                }
+            }
         }
 
-        // contains and not enable repeat insert
-        if res && !self.config.enable_repeat_insert {
+        // conservative update: only increment the minimal counters
+        if self.config.enable_conservative_update {
+            let min_count = self.counting_vec.get_many(indices.clone())
+                .into_iter()
+                .min()
+                .unwrap_or(0);
+            for index in indices {
+                if self.counting_vec.get(index as usize) == min_count {
+                    self.increment(index as usize);
+                }
+            }
             return;
         }
 
         // insert
-        for i in 1..self.config.hashes as u64 {
-            let mo = ((hash1 + i * hash2) % m) as usize;
-            self.counting_vec.increment(mo);
-        };
-        self.counting_vec.increment(hash1 as usize);
+        for index in indices {
+            self.increment(index as usize);
+        }
+    }
+
+    /// Increments a counter, or its entry in the overflow map once it is saturated.
+    #[inline]
+    fn increment(&mut self, index: usize) {
+        if !self.counting_vec.increment(index) {
+            self.overflows += 1;
+            if let Some(events) = self.hook.get() {
+                events.on_counter_saturate(index as u64);
+            }
+            if let Some(map) = &mut self.overflow_map {
+                *map.entry(index).or_default() += 1;
+            }
+        }
+    }
+
+    /// Decrements the entry of a counter in the overflow map, or the counter itself.
+    #[inline]
+    fn decrement(&mut self, index: usize) {
+        if let Some(map) = &mut self.overflow_map {
+            if let Some(extra) = map.get_mut(&index) {
+                *extra -= 1;
+                if *extra == 0 {
+                    map.remove(&index);
+                }
+                return;
+            }
+        }
+        self.counting_vec.decrement(index);
     }
+
     pub fn clear(&mut self) {
-        self.counting_vec.clear()
+        self.counting_vec.clear();
+        self.overflows = 0;
+        if let Some(map) = &mut self.overflow_map {
+            map.clear();
+        }
+        if let Some(log) = &mut self.key_log {
+            log.clear();
+        }
     }
     pub fn remove(&mut self, element: &[u8]) {
-        let m = self.config.size;
-        // let hash1 = (murmur3_x64_128(element, 0) % m) as u64;
-        // let hash2 = (murmur3_x64_128(element, 32) % m) as u64;
-        let hash1 = xxh3_64_with_seed(element, 0) % m;
-        let hash2 = xxh3_64_with_seed(element, 32) % m;
+        self.remove_if_present(element);
+    }
+
+    /// Decrements the counters of the element only if all of them are non-zero, and returns
+    /// whether it did. Decrementing the counters of an element which was never added would
+    /// cause false negatives for the elements sharing them; this still happens for the false
+    /// positives, which can't be told apart.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use fastbloom_rs::FilterBuilder;
+    ///
+    /// let mut cbf = FilterBuilder::new(1000, 0.01).build_counting_bloom_filter::<Vec<usize>>(());
+    /// cbf.add(b"hello");
+    /// assert_eq!(cbf.remove_if_present(b"world"), false);
+    /// assert_eq!(cbf.remove_if_present(b"hello"), true);
+    /// assert_eq!(cbf.contains(b"hello"), false);
+    /// ```
+    pub fn remove_if_present(&mut self, element: &[u8]) -> bool {
+        let indices = Indices::new(element, &self.config);
+        if !self.contains_indices(indices.clone()) {
+            return false;
+        }
+        for index in indices {
+            self.decrement(index as usize);
+        }
+        if let Some(log) = &mut self.key_log {
+            unlog(log, element, 1);
+        }
+        true
+    }
+
+    /// Rebuilds the filter for `new_capacity` elements at the configured false positive
+    /// probability, with the same hashing, from the keys of the log of
+    /// [CountingBloomFilter::with_key_log], and returns whether it did. Without the log the keys
+    /// are unknown: the filter is left unchanged and `false` returned.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use fastbloom_rs::FilterBuilder;
+    ///
+    /// let mut cbf = FilterBuilder::new(100, 0.01)
+    ///     .build_counting_bloom_filter::<Vec<usize>>(())
+    ///     .with_key_log();
+    /// for i in 0..1000u32 {
+    ///     cbf.add(&i.to_le_bytes());
+    /// }
+    /// assert!(cbf.resize(1000));
+    /// assert_eq!(cbf.config().expected_elements, 1000);
+    /// assert!((0..1000u32).all(|i| cbf.contains(&i.to_le_bytes())));
+    /// ```
+    pub fn resize(&mut self, new_capacity: u64) -> bool where S::Init: Default {
+        assert!(new_capacity > 0, "new_capacity must larger than 0!");
+        let Some(log) = self.key_log.take() else {
+            return false;
+        };
+        let mut config = self.config.clone();
+        config.expected_elements = new_capacity;
+        config.size = 0;
+        config.hashes = 0;
+        config.done = false;
+        let mut resized = CountingBloomFilter::with_width(config, S::Init::default());
+        if self.overflow_map.is_some() {
+            resized.enable_overflow_map();
+        }
+        for (key, adds) in log.iter() {
+            for _ in 0..*adds {
+                resized.add(key);
+            }
+        }
+        resized.key_log = Some(log);
+        resized.stats = core::mem::take(&mut self.stats);
+        *self = resized;
+        true
+    }
 
-        let mut res = self.counting_vec.get(hash1 as usize) > 0;
-        // let m = self.config.size;
-        for i in 1..self.config.hashes as u64 {
-            let mo = ((hash1 + i * hash2) % m) as usize;
-            res = res && (self.counting_vec.get(mo) > 0);
+    /// Adds the counters of a compatible filter, so the filter counts the elements of both
+    /// (counters saturate at their maximum, see [CountingBloomFilter::overflows]). Returns
+    /// `false` and leaves the filter unchanged if `other` is not compatible.
+    pub fn union<T: Storage>(&mut self, other: &CountingBloomFilter<T, W>) -> bool {
+        if self.compatibility_key().is_mergeable_with(&other.compatibility_key()) {
+            self.stats.record_merge();
+            let max = CountingVec::<S, W>::MAX;
+            let sums = self.counting_vec.iter().zip(other.counting_vec.iter()).map(|(a, b)| a + b);
+            let excess: Vec<(usize, usize)> = sums.enumerate()
+                .filter(|(_, sum)| *sum > max)
+                .map(|(index, sum)| (index, sum - max))
+                .collect();
+            self.overflows += other.overflows + excess.iter().map(|(_, n)| *n as u64).sum::<u64>();
+            if let Some(map) = &mut self.overflow_map {
+                let theirs = other.overflow_map.iter().flatten().map(|(i, n)| (*i, *n));
+                for (index, extra) in excess.into_iter().chain(theirs) {
+                    *map.entry(index).or_default() += extra;
+                }
+            }
+            self.counting_vec.add(&other.counting_vec);
+            match (&mut self.key_log, &other.key_log) {
+                (Some(log), Some(theirs)) => {
+                    for (key, adds) in theirs {
+                        *log.entry(key.clone()).or_default() += adds;
+                    }
+                }
+                (log, _) => *log = None,
+            }
+            true
+        } else {
+            false
         }
+    }
+
+    /// Adds the counters of `other` like [CountingBloomFilter::union], e.g. to aggregate the
+    /// filters of several workers, but returns the [MismatchError] of an incompatible filter.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use fastbloom_rs::FilterBuilder;
+    ///
+    /// let mut builder = FilterBuilder::new(100_000, 0.01);
+    /// let mut total = builder.build_counting_bloom_filter::<Vec<usize>>(());
+    /// let mut worker = builder.build_counting_bloom_filter::<Vec<usize>>(());
+    /// total.add(b"hello");
+    /// worker.add(b"hello");
+    /// worker.add(b"world");
+    /// total.merge_add(&worker).unwrap();
+    /// assert_eq!(total.estimate_count(b"hello"), 2);
+    ///
+    /// total.merge_sub(&worker).unwrap();
+    /// assert_eq!(total.estimate_count(b"hello"), 1);
+    /// assert_eq!(total.contains(b"world"), false);
+    /// ```
+    pub fn merge_add<T: Storage>(&mut self, other: &CountingBloomFilter<T, W>)
+                                 -> Result<(), MismatchError> {
+        MismatchError::check(self.compatibility_key(), other.compatibility_key())?;
+        self.union(other);
+        Ok(())
+    }
 
-        // contains
-        if res {
-            for i in 1..self.config.hashes as u64 {
-                let mo = ((hash1 + i * hash2) % m) as usize;
-                self.counting_vec.decrement(mo);
-            };
-            self.counting_vec.decrement(hash1 as usize);
+    /// Subtracts the counters of `other` slot by slot, e.g. to take the counts of an expired
+    /// window out of an aggregate built with [CountingBloomFilter::merge_add]. Counters stop at
+    /// zero, and counters saturated in this filter stay saturated as their count is unknown,
+    /// unless it has an [overflow map](CountingBloomFilter::enable_overflow_map): then the
+    /// counts of `other`, including the ones in its overflow map, are taken from the overflow
+    /// map first and then from the counter. Returns the [MismatchError] of an incompatible
+    /// filter, leaving this filter unchanged.
+    pub fn merge_sub<T: Storage>(&mut self, other: &CountingBloomFilter<T, W>)
+                                 -> Result<(), MismatchError> {
+        MismatchError::check(self.compatibility_key(), other.compatibility_key())?;
+        self.stats.record_merge();
+        match (&mut self.key_log, &other.key_log) {
+            (Some(log), Some(theirs)) => {
+                for (key, adds) in theirs {
+                    unlog(log, key, *adds);
+                }
+            }
+            (log, _) => *log = None,
+        }
+        if self.overflow_map.is_none() {
+            self.counting_vec.sub(&other.counting_vec);
+            return Ok(());
+        }
+        let max = CountingVec::<S, W>::MAX;
+        let theirs: Vec<usize> = other.counting_vec.iter().enumerate()
+            .filter(|(_, count)| *count != 0)
+            .map(|(index, _)| index)
+            .collect();
+        for index in theirs {
+            let left = self.count(index).saturating_sub(other.count(index));
+            self.counting_vec.set(index, left.min(max));
+            let map = self.overflow_map.as_mut().unwrap();
+            if left > max {
+                map.insert(index, left - max);
+            } else {
+                map.remove(&index);
+            }
         }
+        Ok(())
+    }
+
+    /// Removes everything accounted for in `checkpoint`, an earlier copy of this filter, so the
+    /// filter holds the adds since the checkpoint: a sliding aggregation window (the current
+    /// counts minus those of an hour ago) without removing the elements one by one. Subtracts
+    /// like [CountingBloomFilter::merge_sub]: counters stop at zero and saturated counters stay
+    /// saturated. Elements removed after the checkpoint are taken out twice, which can remove
+    /// later adds of elements sharing their counters, so use it on filters which only grow.
+    /// Returns the [MismatchError] of an incompatible filter, leaving this filter unchanged.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use fastbloom_rs::{CountingBloomFilter, FilterBuilder, Membership};
+    ///
+    /// let mut cbf: CountingBloomFilter = FilterBuilder::new(100_000, 0.01)
+    ///     .build_counting_bloom_filter(());
+    /// cbf.add(b"last hour");
+    /// let checkpoint = cbf.clone();
+    /// cbf.add(b"this hour");
+    /// cbf.add(b"last hour");
+    ///
+    /// cbf.subtract_snapshot(&checkpoint).unwrap();
+    /// assert_eq!(cbf.estimate_count(b"this hour"), 1);
+    /// assert_eq!(cbf.estimate_count(b"last hour"), 1);
+    /// ```
+    pub fn subtract_snapshot<T: Storage>(&mut self, checkpoint: &CountingBloomFilter<T, W>)
+                                         -> Result<(), MismatchError> {
+        self.merge_sub(checkpoint)
     }
 }
-impl<S: Storage> CountingBloomFilter<S> {
-    #[inline]
-    fn contains(&self, element: &[u8]) -> bool {
-        let m = self.config.size;
-        // let hash1 = (murmur3_x64_128(element, 0) % m) as u64;
-        // let hash2 = (murmur3_x64_128(element, 32) % m) as u64;
-        let hash1 = xxh3_64_with_seed(element, 0) % m;
-        let hash2 = xxh3_64_with_seed(element, 32) % m;
-
-        let mut res = self.counting_vec.get(hash1 as usize) > 0;
-        if !res { return false; }
-        // let m = self.config.size;
-        for i in 1..self.config.hashes as u64 {
-            let mo = ((hash1 + i * hash2) % m) as usize;
-            res = res && (self.counting_vec.get(mo) > 0);
-            if !res { return false; }
+/// Takes `adds` adds of `key` out of a key log.
+fn unlog(log: &mut BTreeMap<Vec<u8>, usize>, key: &[u8], adds: usize) {
+    if let Some(logged) = log.get_mut(key) {
+        *logged = logged.saturating_sub(adds);
+        if *logged == 0 {
+            log.remove(key);
         }
+    }
+}
+
+impl<S: Storage, const W: usize> CountingBloomFilter<S, W> {
+    /// Tests whether an element is present in the filter (subject to the specified false
+    /// positive rate).
+    #[inline]
+    pub fn contains(&self, element: &[u8]) -> bool {
+        let indices = Indices::new(element, &self.config);
+        let res = if self.config.enable_constant_time {
+            // non-zero counter to 1 without branching
+            let nonzero = |c: usize| (c | c.wrapping_neg()) >> (usize::BITS - 1);
+            let mut res = !indices.is_empty() as usize;
+            for index in indices {
+                res = black_box(res & nonzero(self.counting_vec.get(index as usize)));
+            }
+            res == 1
+        } else {
+            self.contains_indices(indices)
+        };
+        self.stats.record_contains(res);
         res
     }
 
-    fn get_hash_indices(&self, element: &[u8]) -> Vec<u64> {
-        let m = self.config.size;
-        let mut res = Vec::<u64>::with_capacity(self.config.size as usize);
-        // let hash1 = (murmur3_x64_128(element, 0) % m) as u64;
-        // let hash2 = (murmur3_x64_128(element, 32) % m) as u64;
-        let hash1 = xxh3_64_with_seed(element, 0) % m;
-        let hash2 = xxh3_64_with_seed(element, 32) % m;
-        res.push(hash1);
-        // let m = self.config.size;
-        for i in 1..self.config.hashes as u64 {
-            let mo = ((hash1 + i * hash2) % m) as usize;
-            res.push(mo as u64);
+    #[inline]
+    fn contains_indices(&self, indices: Indices) -> bool {
+        if indices.is_empty() { return false; }
+        for index in indices {
+            if self.counting_vec.get(index as usize) == 0 { return false; }
         }
-        res
+        true
     }
 
-    fn contains_hash_indices(&self, indices: &Vec<u64>) -> bool {
+    /// Returns the counter indices of an element.
+    pub fn get_hash_indices(&self, element: &[u8]) -> Vec<u64> {
+        Indices::new(element, &self.config).collect()
+    }
+
+    /// Tests whether all counters at `indices` are non-zero.
+    pub fn contains_hash_indices(&self, indices: &[u64]) -> bool {
+        if indices.is_empty() { return false; }
         for x in indices.iter() {
             let index = *x;
             if self.counting_vec.get(index as usize) == 0 { return false; }
@@ -543,31 +2787,51 @@ impl<S: Storage> CountingBloomFilter<S> {
 
 }
 
-impl<S: Storage> Hashes for CountingBloomFilter<S> {
-    fn hashes(&self) -> u32 {
-        self.config.hashes
+/// Adds all elements, like [CountingBloomFilter::add] for each.
+impl<S: StorageMut, T: AsRef<[u8]>, const W: usize> Extend<T> for CountingBloomFilter<S, W> {
+    fn extend<I: IntoIterator<Item=T>>(&mut self, iter: I) {
+        for item in iter {
+            self.add(item.as_ref());
+        }
     }
 }
 
-/// A Partitioned Bloom Filter is a variation of a classic Bloom Filter.
-///
-/// This filter works by partitioning the M-sized bit array into k slices of size `m = M/k` bits,
-/// `k = nb of hash functions` in the filter. Each hash function produces an index over `m` for its
-/// respective slice. Thus, each element is described by exactly `k` bits, meaning the distribution
-/// of false positives is uniform across all elements.
-///
-/// Be careful, as a Partitioned Bloom Filter have much higher collison risks that a classic
-/// Bloom Filter on small sets of data.
-///
-/// **Reference**: Chang, F., Feng, W. C., & Li, K. (2004, March). Approximate caches for packet
-/// classification. In INFOCOM 2004. Twenty-third AnnualJoint Conference of the IEEE Computer and
-/// Communications Societies (Vol. 4, pp. 2196-2207). IEEE.
-/// [Full text article](http://citeseerx.ist.psu.edu/viewdoc/download?doi=10.1.1.153.6902&rep=rep1&type=pdf)
-#[derive(Clone)]
-#[derive(Debug)]
-pub(crate) struct PartitionedBloomFilter {}
+impl<S: StorageMut, const W: usize> Membership for CountingBloomFilter<S, W> {
+    /// Adds the passed value to the filter, see [CountingBloomFilter::add].
+    fn add(&mut self, element: &[u8]) {
+        CountingBloomFilter::add(self, element);
+    }
+
+    #[inline]
+    fn contains(&self, element: &[u8]) -> bool {
+        CountingBloomFilter::contains(self, element)
+    }
+
+    fn get_hash_indices(&self, element: &[u8]) -> Vec<u64> {
+        CountingBloomFilter::get_hash_indices(self, element)
+    }
+
+    fn contains_hash_indices(&self, indices: &Vec<u64>) -> bool {
+        CountingBloomFilter::contains_hash_indices(self, indices)
+    }
+
+    fn clear(&mut self) {
+        CountingBloomFilter::clear(self);
+    }
+}
 
-impl PartitionedBloomFilter {}
+impl<S: StorageMut, const W: usize> Deletable for CountingBloomFilter<S, W> {
+    /// Removes the passed value from the filter, see [CountingBloomFilter::remove_if_present].
+    fn remove(&mut self, element: &[u8]) {
+        CountingBloomFilter::remove(self, element);
+    }
+}
+
+impl<S: Storage, const W: usize> Hashes for CountingBloomFilter<S, W> {
+    fn hashes(&self) -> u32 {
+        self.config.hashes
+    }
+}
 
 /// A Scalable Bloom Filter is a variant of Bloom Filters that can adapt dynamically to the number
 /// of elements stored, while assuring a maximum false positive probability.
@@ -622,26 +2886,26 @@ fn bloom_test() {
 
     #[cfg(target_pointer_width = "64")]{
         let mut bloom2 = BloomFilter::from_u64_array(bloom.get_u64_array(), bloom.hashes());
-        assert_eq!(bloom2.compatible(&bloom), true);
+        assert!(bloom2.compatibility_key().is_mergeable_with(&bloom.compatibility_key()));
         assert_eq!(bloom2.contains(b"hello"), true);
         assert_eq!(bloom2.contains(b"world"), false);
     }
 
     let mut bloom3 =
         BloomFilter::from_u32_array(bloom.get_u32_array(), bloom.config.hashes);
-    assert_eq!(bloom3.compatible(&bloom), true);
+    assert!(bloom3.compatibility_key().is_mergeable_with(&bloom.compatibility_key()));
     assert_eq!(bloom3.contains(b"hello"), true);
     assert_eq!(bloom3.contains(b"world"), false);
 
     let u8_array = bloom.get_u8_array();
     let mut bloom4 = BloomFilter::from_u8_array(u8_array, bloom.config.hashes);
     println!("{:?}", &bloom4.bit_set.storage[0..300]);
-    assert_eq!(bloom4.compatible(&bloom), true);
+    assert!(bloom4.compatibility_key().is_mergeable_with(&bloom.compatibility_key()));
     assert_eq!(bloom4.contains(b"hello"), true);
     assert_eq!(bloom4.contains(b"world"), false);
 
     let bloom5 = BloomFilter::from_u16_array(bloom.get_u16_array(), bloom.hashes());
-    assert_eq!(bloom5.compatible(&bloom), true);
+    assert!(bloom5.compatibility_key().is_mergeable_with(&bloom.compatibility_key()));
     assert_eq!(bloom5.contains(b"hello"), true);
     assert_eq!(bloom5.contains(b"world"), false);
 
@@ -654,41 +2918,410 @@ fn bloom_test() {
     bloom3.add(b"hello world");
     bloom3.add(b"hello yankun");
 
-    assert_eq!(bloom3.union(&bloom4), true);
-    assert_eq!(bloom3.contains(b"hello"), true);
-    assert_eq!(bloom3.contains(b"hello world"), true);
-    assert_eq!(bloom3.contains(b"hello yankun"), true);
-}
+    assert_eq!(bloom3.union(&bloom4), true);
+    assert_eq!(bloom3.contains(b"hello"), true);
+    assert_eq!(bloom3.contains(b"hello world"), true);
+    assert_eq!(bloom3.contains(b"hello yankun"), true);
+}
+
+#[test]
+fn bloom_constant_time_test() {
+    let mut builder = FilterBuilder::new(10_000, 0.01);
+    let mut bloom = builder.build_bloom_filter();
+    builder.enable_constant_time(true);
+    let mut constant = builder.build_bloom_filter();
+    let mut counting = builder.build_counting_bloom_filter::<Vec<usize>>(());
+    for i in 0..5_000u64 {
+        bloom.add(&i.to_le_bytes());
+        constant.add(&i.to_le_bytes());
+        counting.add(&i.to_le_bytes());
+    }
+    for i in 0..20_000u64 {
+        let element = i.to_le_bytes();
+        assert_eq!(constant.contains(&element), bloom.contains(&element));
+        assert_eq!(counting.contains(&element), bloom.contains(&element));
+    }
+}
+
+#[test]
+fn bloom_seed_test() {
+    let mut builder = FilterBuilder::new(10_000, 0.01);
+    let mut bloom = builder.build_bloom_filter();
+    builder.seed(42);
+    let mut seeded = builder.build_bloom_filter();
+    bloom.add(b"hello");
+    seeded.add(b"hello");
+    assert_ne!(bloom.get_hash_indices(b"hello"), seeded.get_hash_indices(b"hello"));
+    assert_eq!(seeded.contains(b"hello"), true);
+    assert_eq!(bloom.union(&seeded), false);
+
+    let rehashed = bloom.rehash_into(vec![&b"hello"[..]], 42);
+    assert_eq!(rehashed.get_u64_array(), seeded.get_u64_array());
+}
+
+#[test]
+fn bloom_contains_batch_test() {
+    let mut bloom = FilterBuilder::new(10_000, 0.01).build_bloom_filter();
+    for i in 0..5_000u64 {
+        bloom.add(&i.to_le_bytes());
+    }
+    let elements: Vec<[u8; 8]> = (0..10_000u64).map(|i| (i * 3).to_le_bytes()).collect();
+    let elements: Vec<&[u8]> = elements.iter().map(|e| &e[..]).collect();
+    let expected: Vec<bool> = elements.iter().map(|e| bloom.contains(e)).collect();
+    // sorted and unsorted probing
+    assert_eq!(bloom.contains_batch(&elements), expected);
+    assert_eq!(bloom.contains_batch(&elements[..100]), expected[..100]);
+}
+
+#[test]
+fn bloom_difference_estimate_test() {
+    let mut builder = FilterBuilder::new(100_000, 0.01);
+    let mut a = builder.build_bloom_filter();
+    let mut b = builder.build_bloom_filter();
+    for i in 0..50_000u64 {
+        a.add(&i.to_le_bytes());
+        if i < 40_000 { b.add(&i.to_le_bytes()); }
+    }
+    let diff = a.difference_estimate(&b).unwrap();
+    assert!((9_000.0..11_000.0).contains(&diff), "{diff}");
+    assert!(b.difference_estimate(&a).unwrap() < 500.0);
+    assert_eq!(a.difference_estimate(&a), Ok(0.0));
+    let other = FilterBuilder::new(1000, 0.01).build_bloom_filter();
+    assert!(a.difference_estimate(&other).is_err());
+}
+
+#[test]
+fn bloom_sampled_test() {
+    let mut bloom = FilterBuilder::new(100_000, 0.01).build_bloom_filter();
+    let sampled = (0..100_000u32).filter(|i| bloom.add_sampled(&i.to_le_bytes(), 0.1)).count();
+    assert!((9_000..11_000).contains(&sampled), "{sampled}");
+    for i in 0..100_000u32 {
+        let key = i.to_le_bytes();
+        // deterministic: sampled elements are present, all others absent
+        assert_eq!(bloom.contains_sampled(&key, 0.1), bloom.add_sampled(&key, 0.1));
+    }
+    assert!(bloom.add_sampled(b"hello", 1.0));
+    assert!(!bloom.add_sampled(b"world", 0.0));
+}
+
+#[test]
+fn bloom_retouch_test() {
+    let mut builder = FilterBuilder::new(1_000, 0.1);
+    let mut bloom = builder.build_bloom_filter();
+    let members: Vec<[u8; 4]> = (0..1_000u32).map(|i| i.to_le_bytes()).collect();
+    for key in members.iter() {
+        bloom.add(key);
+    }
+    let false_positives: Vec<[u8; 4]> = (1_000..3_000u32).map(|i| i.to_le_bytes())
+        .filter(|key| bloom.contains(key)).collect();
+    assert!(!false_positives.is_empty());
+
+    let mut random = bloom.clone();
+    let cleared = random.retouch(false_positives.iter().map(|key| &key[..]));
+    assert!(cleared > 0 && cleared <= false_positives.len());
+    assert!(false_positives.iter().all(|key| !random.contains(key)));
+    let random_false_negatives = members.iter().filter(|key| !random.contains(*key)).count();
+
+    bloom.retouch_with_members(false_positives.iter().map(|key| &key[..]),
+                               members.iter().map(|key| &key[..]));
+    assert!(false_positives.iter().all(|key| !bloom.contains(key)));
+    let false_negatives = members.iter().filter(|key| !bloom.contains(*key)).count();
+    assert!(false_negatives <= random_false_negatives, "{false_negatives} {random_false_negatives}");
+}
+
+#[test]
+fn bloom_hash_indices_test() {
+    let mut builder =
+        FilterBuilder::new(10_000, 0.01);
+    let mut bloom = builder.build_bloom_filter();
+    println!("{:?}", bloom.config);
+    bloom.add(b"hello");
+    assert_eq!(bloom.contains(b"hello"), true);
+    assert_eq!(bloom.contains(b"world"), false);
+
+    let indices = bloom.get_hash_indices(b"hello");
+    println!("{:?}", indices);
+    assert_eq!(bloom.contains_hash_indices(&indices), true);
+    assert_eq!(bloom.contains_hash_indices(&bloom.get_hash_indices(b"world")), false);
+}
+
+#[test]
+fn bloom_element_hash_test() {
+    use crate::index::IndexScheme;
+
+    let mut small = FilterBuilder::new(1000, 0.01).build_bloom_filter();
+    let mut builder = FilterBuilder::new(100_000, 0.001);
+    builder.index_scheme(IndexScheme::EnhancedDoubleHashing);
+    let mut large = builder.build_bloom_filter();
+    let mut partitioned = FilterBuilder::new(1000, 0.01);
+    partitioned.partitioned(true);
+    let mut partitioned = partitioned.build_bloom_filter();
+    for i in 0..500u32 {
+        let hash = small.hash(&i.to_le_bytes());
+        small.insert_hash(hash);
+        partitioned.insert_hash(hash);
+        large.add(&i.to_le_bytes());
+    }
+    for i in 0..2000u32 {
+        let hash = small.hash(&i.to_le_bytes());
+        assert_eq!(small.contains_hash(hash), small.contains(&i.to_le_bytes()));
+        assert_eq!(partitioned.contains_hash(hash), partitioned.contains(&i.to_le_bytes()));
+        let hash = large.hash(&i.to_le_bytes());
+        assert_eq!(large.contains_hash(hash), large.contains(&i.to_le_bytes()));
+    }
+    assert!((0..500u32).all(|i| small.contains(&i.to_le_bytes())));
+
+    // any mutable storage takes hashes
+    let mut array = ArrayBloomFilter::<16>::with_hashes(4);
+    array.insert_hash(array.hash(b"hello"));
+    assert!(array.contains(b"hello"));
+}
+
+#[test]
+#[should_panic(expected = "other hashing")]
+fn bloom_element_hash_seed_test() {
+    let mut seeded = FilterBuilder::new(1000, 0.01);
+    seeded.seed(7);
+    let mut seeded = seeded.build_bloom_filter();
+    let hash = FilterBuilder::new(1000, 0.01).build_bloom_filter().hash(b"hello");
+    seeded.insert_hash(hash);
+}
+
+#[test]
+fn bloom_contains_prefetch_test() {
+    let mut bloom = FilterBuilder::new(10_000, 0.01).build_bloom_filter();
+    let mut partitioned = FilterBuilder::new(10_000, 0.01);
+    partitioned.partitioned(true);
+    let mut partitioned = partitioned.build_bloom_filter();
+    for i in 0..10_000u32 {
+        bloom.add(&i.to_le_bytes());
+        partitioned.add(&i.to_le_bytes());
+    }
+    for i in 0..100_000u32 {
+        assert_eq!(bloom.contains_prefetch(&i.to_le_bytes()), bloom.contains(&i.to_le_bytes()));
+        assert_eq!(partitioned.contains_prefetch(&i.to_le_bytes()),
+                   partitioned.contains(&i.to_le_bytes()));
+    }
+    assert!(!FilterBuilder::new(0, 0.01).build_bloom_filter().contains_prefetch(b"hello"));
+}
+
+
+#[test]
+fn counting_bloom_test() {
+    let mut builder =
+        FilterBuilder::new(10_000, 0.01);
+    let mut bloom = builder.build_counting_bloom_filter::<Vec<usize>>(());
+
+    bloom.add(b"hello");
+
+    assert_eq!(bloom.contains(b"hello"), true);
+
+    bloom.remove(b"hello");
+    assert_eq!(bloom.contains(b"hello"), false);
+
+    // removing an element never added leaves the counters of the others alone
+    bloom.add(b"world");
+    let counters = bloom.storage().clone();
+    assert_eq!(bloom.remove_if_present(b"hello"), false);
+    assert_eq!(bloom.storage(), &counters);
+    assert_eq!(bloom.remove_if_present(b"world"), true);
+    assert_eq!(bloom.contains(b"world"), false);
+}
+
+#[test]
+fn counting_bloom_merge_test() {
+    let mut builder = FilterBuilder::new(10_000, 0.01);
+    let mut total = builder.build_counting_bloom_filter::<Vec<usize>>(());
+    let mut worker = builder.build_counting_bloom_filter::<Vec<usize>>(());
+    for i in 0..1000u32 {
+        total.add(&i.to_le_bytes());
+        worker.add(&(i / 2).to_le_bytes());
+    }
+    for _ in 0..20 {
+        total.add(b"hot");
+    }
+    worker.add(b"hot");
+    let before: Vec<usize> = total.counters_iter().collect();
+    total.merge_add(&worker).unwrap();
+    assert_eq!(total.estimate_count(&7u32.to_le_bytes()), 3);
+    total.merge_sub(&worker).unwrap();
+    let after: Vec<usize> = total.counters_iter().collect();
+    // only the counters saturated by "hot" differ
+    let differ = before.iter().zip(&after).filter(|(a, b)| a != b).count();
+    assert!(after.iter().zip(&before).all(|(a, b)| a >= b));
+    assert!(differ <= total.config().hashes as usize);
+    assert_eq!(total.estimate_count(b"hot"), 15);
+
+    let mut empty = builder.build_counting_bloom_filter::<Vec<usize>>(());
+    empty.merge_sub(&worker).unwrap();
+    assert!(empty.counters_iter().all(|counter| counter == 0));
+
+    let other = FilterBuilder::new(1000, 0.01).build_counting_bloom_filter::<Vec<usize>>(());
+    assert!(total.merge_add(&other).is_err());
+    assert!(total.merge_sub(&other).is_err());
+}
+
+#[test]
+fn counting_bloom_subtract_snapshot_test() {
+    let mut builder = FilterBuilder::new(10_000, 0.01);
+    let mut window = builder.build_counting_bloom_filter::<Vec<usize>>(());
+    (0..1000u32).for_each(|i| window.add(&i.to_le_bytes()));
+    (0..20).for_each(|_| window.add(b"hot"));
+    let checkpoint = window.clone();
+    (1000..2000u32).for_each(|i| window.add(&i.to_le_bytes()));
+    window.add(b"hot");
+
+    window.subtract_snapshot(&checkpoint).unwrap();
+    assert!((1000..2000u32).all(|i| window.contains(&i.to_le_bytes())));
+    let left = (0..1000u32).filter(|i| window.contains(&i.to_le_bytes())).count();
+    assert!(left < 50, "{left} elements of the checkpoint left");
+    // saturated counters keep their unknown count
+    assert_eq!(window.estimate_count(b"hot"), 15);
+
+    let other = FilterBuilder::new(1000, 0.01).build_counting_bloom_filter::<Vec<usize>>(());
+    assert!(window.subtract_snapshot(&other).is_err());
+}
+
+#[test]
+fn counting_bloom_to_bloom_filter_test() {
+    let mut builder = FilterBuilder::new(10_000, 0.01);
+    builder.canaries(8);
+    let mut cbf = builder.build_counting_bloom_filter_with_width::<Vec<usize>, 8>(());
+    let mut bloom = builder.build_bloom_filter();
+    for i in 0..2000u32 {
+        cbf.add(&i.to_le_bytes());
+        if i % 2 == 0 {
+            bloom.add(&i.to_le_bytes());
+        }
+    }
+    for i in (1..2000u32).step_by(2) {
+        cbf.remove(&i.to_le_bytes());
+    }
+    let converted = cbf.to_bloom_filter();
+    assert_eq!(converted.get_u8_array(), bloom.get_u8_array());
+    assert!(converted.check_canaries());
+}
+
+#[test]
+fn counting_bloom_overflow_test() {
+    let mut builder = FilterBuilder::new(10_000, 0.01);
+    builder.enable_repeat_insert(true);
+    let mut lossy = builder.build_counting_bloom_filter::<Vec<usize>>(());
+    let mut exact = lossy.clone();
+    exact.enable_overflow_map();
+    for _ in 0..40 {
+        lossy.add(b"hot");
+        exact.add(b"hot");
+    }
+    lossy.add(b"cold");
+    exact.add(b"cold");
+    let k = lossy.counters_of(b"hot").len();
+    assert_eq!((lossy.saturated_counters(), lossy.overflows()), (k, 25 * k as u64));
+    assert_eq!((exact.saturated_counters(), exact.overflows()), (k, 25 * k as u64));
+    assert_eq!((lossy.estimate_count(b"hot"), exact.estimate_count(b"hot")), (15, 40));
+    assert_eq!(lossy.max_counter(), 15);
+    let hot: Vec<(usize, usize)> = lossy.counters_above(1).collect();
+    let mut indices = lossy.get_hash_indices(b"hot");
+    indices.sort_unstable();
+    assert_eq!(hot, indices.iter().map(|&i| (i as usize, 15)).collect::<Vec<_>>());
+
+    for _ in 0..20 {
+        lossy.remove(b"hot");
+        exact.remove(b"hot");
+    }
+    // the lost increments drop the counters of the hot key too early
+    assert!(!lossy.contains(b"hot"));
+    assert_eq!(exact.estimate_count(b"hot"), 20);
+    for _ in 0..20 {
+        exact.remove(b"hot");
+    }
+    assert_eq!(exact.storage(), lossy.storage());
+    assert!(exact.contains(b"cold") && !exact.contains(b"hot"));
+
+    // counts beyond the maximum survive a union
+    let mut other = builder.build_counting_bloom_filter::<Vec<usize>>(());
+    for _ in 0..10 {
+        other.add(b"warm");
+        exact.add(b"warm");
+    }
+    exact.union(&other);
+    assert_eq!(exact.estimate_count(b"warm"), 20);
+    assert_eq!(exact.overflows(), 30 * k as u64);
 
-#[test]
-fn bloom_hash_indices_test() {
-    let mut builder =
-        FilterBuilder::new(10_000, 0.01);
-    let mut bloom = builder.build_bloom_filter();
-    println!("{:?}", bloom.config);
-    bloom.add(b"hello");
-    assert_eq!(bloom.contains(b"hello"), true);
-    assert_eq!(bloom.contains(b"world"), false);
+    // and are subtracted from the overflow map first, with the ones of the other overflow map
+    exact.merge_sub(&other).unwrap();
+    assert_eq!(exact.estimate_count(b"warm"), 10);
+    let mut hot = builder.build_counting_bloom_filter::<Vec<usize>>(());
+    hot.enable_overflow_map();
+    for _ in 0..40 {
+        exact.add(b"hot");
+        hot.add(b"hot");
+    }
+    for _ in 0..10 {
+        exact.add(b"hot");
+    }
+    exact.merge_sub(&hot).unwrap();
+    assert_eq!(exact.estimate_count(b"hot"), 10);
+    assert!(exact.contains(b"cold"));
 
-    let indices = bloom.get_hash_indices(b"hello");
-    println!("{:?}", indices);
-    assert_eq!(bloom.contains_hash_indices(&indices), true);
-    assert_eq!(bloom.contains_hash_indices(&bloom.get_hash_indices(b"world")), false);
+    exact.clear();
+    assert_eq!((exact.overflows(), exact.saturated_counters()), (0, 0));
+    assert_eq!((exact.max_counter(), exact.counters_above(0).count()), (0, 0));
 }
 
-
 #[test]
-fn counting_bloom_test() {
-    let mut builder =
-        FilterBuilder::new(10_000, 0.01);
-    let mut bloom = builder.build_counting_bloom_filter::<Vec<usize>>(());
+fn counting_bloom_resize_test() {
+    let mut builder = FilterBuilder::new(100, 0.01);
+    builder.enable_repeat_insert(true);
+    let mut cbf = builder.build_counting_bloom_filter::<Vec<usize>>(()).with_key_log();
+    for i in 0..2000u32 {
+        cbf.add(&i.to_le_bytes());
+    }
+    cbf.add(b"twice");
+    cbf.add(b"twice");
+    assert!(cbf.remove_if_present(&0u32.to_le_bytes()));
+    assert_eq!(cbf.logged_keys(), Some(2000));
 
-    bloom.add(b"hello");
+    assert!(cbf.resize(2000));
+    assert_eq!(cbf.config().expected_elements, 2000);
+    assert_eq!(cbf.logged_keys(), Some(2000));
+    assert!((1..2000u32).all(|i| cbf.contains(&i.to_le_bytes())));
+    assert_eq!(cbf.estimate_count(b"twice"), 2);
+    let false_positives = (2000..22_000u32).filter(|i| cbf.contains(&i.to_le_bytes())).count();
+    assert!(false_positives < 400, "{false_positives} false positives");
 
-    assert_eq!(bloom.contains(b"hello"), true);
+    // the other filter didn't log its keys, which are lost to a resize
+    let mut other = builder.build_counting_bloom_filter::<Vec<usize>>(());
+    let mut logged = builder.build_counting_bloom_filter::<Vec<usize>>(()).with_key_log();
+    logged.add(b"hello");
+    let mut both = builder.build_counting_bloom_filter::<Vec<usize>>(()).with_key_log();
+    both.add(b"hello");
+    both.add(b"again");
+    assert!(both.union(&logged));
+    assert_eq!(both.logged_keys(), Some(2));
+    both.merge_sub(&logged).unwrap();
+    assert_eq!(both.logged_keys(), Some(2));
+    assert!(both.resize(1000));
+    assert_eq!((both.estimate_count(b"hello"), both.estimate_count(b"again")), (1, 1));
+    other.add(b"world");
+    assert!(logged.union(&other));
+    assert_eq!(logged.logged_keys(), None);
+    assert!(!logged.resize(1000));
+    assert_eq!(logged.config().expected_elements, 100);
 
-    bloom.remove(b"hello");
-    assert_eq!(bloom.contains(b"hello"), false);
+    logged.clear();
+    let mut logged = logged.with_key_log();
+    logged.add(b"hello");
+    logged.clear();
+    assert_eq!(logged.logged_keys(), Some(0));
+}
+
+#[test]
+#[should_panic(expected = "must start with an empty filter")]
+fn counting_bloom_key_log_test() {
+    let mut cbf = FilterBuilder::new(100, 0.01).build_counting_bloom_filter::<Vec<usize>>(());
+    cbf.add(b"hello");
+    let _ = cbf.with_key_log();
 }
 
 #[test]
@@ -734,6 +3367,30 @@ fn counting_bloom_from_test() {
 
 }
 
+#[test]
+fn counting_bloom_union_test() {
+    let builder = FilterBuilder::new(10_000, 0.01);
+    let mut cbf = builder.clone().build_counting_bloom_filter::<Vec<usize>>(());
+    let mut other = builder.clone().build_counting_bloom_filter::<Vec<usize>>(());
+    cbf.add(b"hello");
+    other.add(b"hello");
+    other.add(b"world");
+    for _ in 0..20 {
+        other.add(b"often");
+    }
+    assert!(cbf.union(&other));
+    assert_eq!(cbf.estimate_count(b"hello"), 2);
+    assert_eq!(cbf.estimate_count(b"world"), 1);
+    assert_eq!(cbf.estimate_count(b"often"), 15);
+    assert!(cbf.contains(b"world"));
+
+    let mut seeded = builder.clone();
+    seeded.seed(1);
+    let seeded = seeded.build_counting_bloom_filter::<Vec<usize>>(());
+    assert!(!cbf.union(&seeded));
+    assert_eq!(cbf.estimate_count(b"hello"), 2);
+}
+
 #[test]
 fn counting_bloom_hash_indices_test() {
     let mut builder =
@@ -752,6 +3409,141 @@ fn counting_bloom_hash_indices_test() {
     assert_eq!(bloom.contains_hash_indices(&bloom.get_hash_indices(b"hello")), false);
 }
 
+#[test]
+fn counting_bloom_contains_detailed_test() {
+    let mut builder = FilterBuilder::new(10_000, 0.01);
+    let mut cbf = builder.build_counting_bloom_filter::<Vec<usize>>(());
+    let k = builder.hashes;
+    cbf.add(b"hello");
+    assert_eq!(cbf.contains_detailed(b"hello"), MembershipDetail::MaybePresent { probes_set: k });
+    let detail = cbf.contains_detailed(b"world");
+    assert_eq!(detail.is_present(), false);
+    assert!(detail.probes_set() < k);
+    cbf.remove(b"hello");
+    assert_eq!(cbf.contains_detailed(b"hello"), MembershipDetail::Absent { probes_set: 0 });
+}
+
+#[test]
+fn counting_multiset_similarity_test() {
+    let mut builder = FilterBuilder::new(10_000, 0.01);
+    let mut a = builder.build_counting_bloom_filter::<Vec<usize>>(());
+    let mut b = builder.build_counting_bloom_filter::<Vec<usize>>(());
+    assert_eq!(a.multiset_similarity(&b), 1.0);
+    for i in 0..1_000u32 {
+        a.add(&i.to_le_bytes());
+        b.add(&(i + 500).to_le_bytes());
+    }
+    // half of the elements are shared: 500 / 1500
+    let similarity = a.multiset_similarity(&b);
+    assert!((similarity - 1.0 / 3.0).abs() < 0.02, "{similarity}");
+    assert_eq!(a.multiset_similarity(&b), b.multiset_similarity(&a));
+}
+
+#[test]
+fn bloom_compressed_bytes_test() {
+    let mut builder = FilterBuilder::new(100_000, 0.01);
+    builder.seed(7);
+    let mut bloom = builder.build_bloom_filter();
+    for i in 0..2000u32 {
+        bloom.add(&i.to_le_bytes());
+    }
+    let raw_len = bloom.to_static_bytes().len();
+    let bytes = bloom.to_compressed_bytes();
+    assert!(bytes.len() < raw_len / 4, "{} of {raw_len} bytes", bytes.len());
+    let restored = BloomFilter::from_compressed_bytes(&bytes).unwrap();
+    assert_eq!(restored.get_u8_array(), bloom.get_u8_array());
+    assert_eq!((restored.config.seed, restored.hashes()), (7, bloom.hashes()));
+    assert!(BloomFilter::from_compressed_bytes(&bytes[..bytes.len() - 1]).is_none());
+    assert!(BloomFilter::from_compressed_bytes(b"FBCB").is_none());
+    // a forged size of an empty filter is rejected by the allocation
+    let size = bloom.config.size.to_le_bytes();
+    let at = bytes.windows(8).position(|window| window == size).unwrap();
+    let mut forged = bytes[..at].to_vec();
+    forged.extend_from_slice(&(1u64 << 50).to_le_bytes());
+    forged.push(0);
+    assert!(BloomFilter::from_compressed_bytes(&forged).is_none());
+
+    // dense filters keep their words
+    for i in 2000..200_000u32 {
+        bloom.add(&i.to_le_bytes());
+    }
+    let bytes = bloom.to_compressed_bytes();
+    assert!(bytes.len() <= raw_len + 1);
+    let restored = BloomFilter::from_compressed_bytes(&bytes).unwrap();
+    assert_eq!(restored.get_u8_array(), bloom.get_u8_array());
+
+    let empty = FilterBuilder::new(1000, 0.01).build_bloom_filter();
+    let restored = BloomFilter::from_compressed_bytes(&empty.to_compressed_bytes()).unwrap();
+    assert_eq!(restored.get_u8_array(), empty.get_u8_array());
+}
+
+#[test]
+fn counting_bloom_snapshot_test() {
+    let mut builder = FilterBuilder::new(100_000, 0.01);
+    builder.seed(7);
+    builder.enable_repeat_insert(false);
+    builder.expected_key_len(8);
+    let mut cbf = builder.build_counting_bloom_filter::<Vec<usize>>(());
+    for i in 0..100u64 {
+        cbf.add(&i.to_le_bytes());
+    }
+    let snapshot = cbf.snapshot_compressed();
+    assert!(snapshot.len() < cbf.storage().len() * 8 / 10);
+
+    let restored = CountingBloomFilter::from_snapshot_compressed(&snapshot).unwrap();
+    assert_eq!(restored.storage(), cbf.storage());
+    assert_eq!(restored.config.seed, 7);
+    assert_eq!(restored.config.enable_repeat_insert, false);
+    assert_eq!(restored.config.expected_key_len, 8);
+    for i in 0..100u64 {
+        assert_eq!(restored.estimate_count(&i.to_le_bytes()), cbf.estimate_count(&i.to_le_bytes()));
+    }
+
+    assert!(CountingBloomFilter::from_snapshot_compressed(&snapshot[..snapshot.len() - 1]).is_none());
+    assert!(CountingBloomFilter::from_snapshot_compressed(b"FBCS").is_none());
+
+    // a zero run of 2^58 words in a few bytes
+    let mut runs = Vec::new();
+    put_varint(&mut runs, cbf.storage().len() as u64);
+    put_varint(&mut runs, 0);
+    let empty = builder.build_counting_bloom_filter::<Vec<usize>>(()).snapshot_compressed();
+    assert!(empty.ends_with(&runs));
+    let mut forged = empty[..empty.len() - 8 - runs.len()].to_vec();
+    forged.extend_from_slice(&(1u64 << 58).to_le_bytes());
+    put_varint(&mut forged, 1 << 58);
+    put_varint(&mut forged, 0);
+    assert!(CountingBloomFilter::from_snapshot_compressed(&forged).is_none());
+}
+
+#[test]
+fn default_filter_test() {
+    #[derive(Default)]
+    struct Cache {
+        seen: BloomFilter,
+        counts: CountingBloomFilter,
+    }
+
+    let mut cache = Cache::default();
+    let other: BloomFilter = BloomFilter::default();
+    assert!(cache.seen.is_empty());
+    assert!(!cache.seen.contains(b"hello"));
+    assert!(!cache.seen.contains_detailed(b"hello").is_present());
+    assert!(!cache.seen.contains_hash_indices(&cache.seen.get_hash_indices(b"hello")));
+    assert_eq!(cache.seen.contains_batch(&[&b"hello"[..]; 2000]), vec![false; 2000]);
+    assert_eq!(cache.seen.remaining_capacity(), 0);
+    assert_eq!((cache.seen.estimate_len(), cache.seen.fill_ratio()), (0.0, 0.0));
+    assert!(cache.seen.union(&other));
+    assert!(!cache.counts.contains(b"hello"));
+    assert_eq!(cache.counts.estimate_count(b"hello"), 0);
+    cache.counts.remove(b"hello");
+}
+
+#[test]
+#[should_panic(expected = "without capacity")]
+fn default_filter_add_test() {
+    BloomFilter::<Vec<usize>>::default().add(b"hello");
+}
+
 #[test]
 fn counting_bloom_estimate_count() {
     let mut builder =
@@ -773,3 +3565,627 @@ fn counting_bloom_estimate_count() {
         assert!(bloom.counter_at(index) <= 2);
     }
 }
+
+#[test]
+fn counting_bloom_conservative_update_test() {
+    let mut builder = FilterBuilder::new(10_000, 0.01);
+    let mut plain = builder.build_counting_bloom_filter::<Vec<usize>>(());
+    builder.enable_conservative_update(true);
+    let mut conservative = builder.build_counting_bloom_filter::<Vec<usize>>(());
+
+    for i in 0..2000u64 {
+        plain.add(&i.to_le_bytes());
+        conservative.add(&i.to_le_bytes());
+    }
+    for _ in 0..3 {
+        conservative.add(b"hello");
+    }
+    assert_eq!(conservative.estimate_count(b"hello"), 3);
+
+    let sum = |cbf: &CountingBloomFilter| -> usize {
+        (0..cbf.config.size).map(|i| cbf.counter_at(i)).sum()
+    };
+    assert!(sum(&conservative) < sum(&plain));
+    for i in 0..2000u64 {
+        assert!(conservative.estimate_count(&i.to_le_bytes()) >= 1);
+        assert!(conservative.estimate_count(&i.to_le_bytes())
+            <= plain.estimate_count(&i.to_le_bytes()));
+    }
+}
+
+#[cfg(feature = "stats")]
+#[test]
+fn bloom_stats_test() {
+    let mut bloom = FilterBuilder::new(10_000, 0.01).build_bloom_filter();
+    bloom.add(b"hello");
+    assert_eq!(bloom.contains(b"hello"), true);
+    assert_eq!(bloom.contains(b"world"), false);
+    let other = bloom.clone();
+    bloom.union(&other);
+    assert_eq!(bloom.stats(), OpStats { adds: 1, contains: 2, hits: 1, misses: 1, merges: 1 });
+    bloom.reset_stats();
+    assert_eq!(bloom.stats(), OpStats::default());
+}
+
+#[cfg(feature = "metrics")]
+#[test]
+fn counting_bloom_metrics_test() {
+    let mut builder = FilterBuilder::new(1000, 0.01);
+    builder.enable_repeat_insert(true);
+    let mut cbf = builder.build_counting_bloom_filter::<Vec<usize>>(());
+    for _ in 0..20 {
+        cbf.add(b"hello");
+    }
+    assert!(cbf.contains(b"hello"));
+    let k = cbf.counters_of(b"hello").len();
+    let metrics = cbf.metrics();
+    assert_eq!((metrics.ops.adds, metrics.ops.hits), (20, 1));
+    assert_eq!(metrics.fill_ratio, k as f64 / cbf.config.size as f64);
+    assert_eq!(metrics.estimated_fpp, metrics.fill_ratio.powi(cbf.config.hashes as i32));
+    assert_eq!(metrics.saturation_events, 5 * k as u64);
+}
+
+#[test]
+fn counting_bloom_saturate_events_test() {
+    use std::sync::Mutex;
+
+    #[derive(Default)]
+    struct Saturations(Mutex<Vec<u64>>);
+
+    impl FilterEvents for Saturations {
+        fn on_counter_saturate(&self, index: u64) {
+            self.0.lock().unwrap().push(index);
+        }
+    }
+
+    let mut builder = FilterBuilder::new(1000, 0.01);
+    builder.enable_repeat_insert(true);
+    let mut cbf = builder.build_counting_bloom_filter::<Vec<usize>>(());
+    let saturations = Arc::new(Saturations::default());
+    cbf.set_events(Some(saturations.clone()));
+    for _ in 0..16 {
+        cbf.add(b"hello");
+    }
+    let mut saturated = saturations.0.lock().unwrap().clone();
+    saturated.sort_unstable();
+    let mut indices = cbf.get_hash_indices(b"hello");
+    indices.sort_unstable();
+    assert_eq!(saturated, indices);
+    cbf.set_events(None);
+    cbf.add(b"hello");
+    assert_eq!(saturations.0.lock().unwrap().len(), indices.len());
+}
+
+#[test]
+fn bloom_from_bytes_ref_test() {
+    let mut bloom = FilterBuilder::new(10_000, 0.01).build_bloom_filter();
+    for i in 0..1000u32 {
+        bloom.add(&i.to_le_bytes());
+    }
+    // an unaligned copy, as in a memory-mapped file after a header
+    let mut buffer = vec![0u8; 1];
+    buffer.extend_from_slice(bloom.get_u8_array());
+    let borrowed = BloomFilter::from_bytes_ref(&buffer[1..], bloom.hashes());
+    assert_eq!(borrowed.config().size, bloom.config().size);
+    for i in 0..2000u32 {
+        assert_eq!(borrowed.contains(&i.to_le_bytes()), bloom.contains(&i.to_le_bytes()));
+    }
+}
+
+#[test]
+fn bloom_canaries_test() {
+    let mut builder = FilterBuilder::new(1000, 0.1);
+    builder.canaries(64);
+    let mut bloom = builder.build_bloom_filter();
+    let size = bloom.config().size;
+    for i in 0..100_000u32 {
+        bloom.add(&i.to_le_bytes());
+    }
+    // the filter is saturated, yet the last 64 bits are clear.
+    assert!((0..size - 64).all(|index| bloom.bit_set.get(index as usize)));
+    assert!(bloom.check_canaries());
+
+    let restored = BloomFilter::from_static_bytes(&bloom.to_static_bytes()).unwrap();
+    assert_eq!(restored.config().canaries, 64);
+    assert!(restored.compatibility_key().is_mergeable_with(&bloom.compatibility_key()));
+
+    bloom.bit_set.set(size as usize - 1);
+    assert!(!bloom.check_canaries());
+}
+
+#[test]
+fn bloom_partitioned_test() {
+    let mut builder = FilterBuilder::new(10_000, 0.01);
+    builder.partitioned(true);
+    let mut bloom = builder.build_bloom_filter();
+    let (size, hashes) = (bloom.config().size, bloom.config().hashes as u64);
+    for i in 0..10_000u32 {
+        let indices = bloom.get_hash_indices(&i.to_le_bytes());
+        assert!(indices.iter().enumerate().all(|(j, index)| index / (size / hashes) == j as u64));
+        bloom.add(&i.to_le_bytes());
+    }
+    assert!((0..10_000u32).all(|i| bloom.contains(&i.to_le_bytes())));
+    let false_positives = (10_000..110_000u32).filter(|i| bloom.contains(&i.to_le_bytes())).count();
+    assert!(false_positives < 1200, "{false_positives} false positives");
+    assert_eq!(bloom.shrink_to_fit(), size);
+
+    let restored = BloomFilter::from_static_bytes(&bloom.to_static_bytes()).unwrap();
+    assert!(restored.config().partitioned);
+    assert!(restored.compatibility_key().is_mergeable_with(&bloom.compatibility_key()));
+    assert!((0..10_000u32).all(|i| restored.contains(&i.to_le_bytes())));
+
+    let plain = FilterBuilder::new(10_000, 0.01).build_bloom_filter();
+    let err = bloom.unioned(&plain).unwrap_err();
+    assert!(err.to_string().contains("partitioned true != false"), "{err}");
+
+    let mut fewer = FilterBuilder::from_size_and_hashes(size, hashes as u32 - 1);
+    fewer.partitioned(true);
+    assert!(!bloom.union_lenient(&fewer.build_bloom_filter()));
+    assert_eq!(bloom.config().hashes as u64, hashes);
+
+    let manifest = bloom.build_manifest(10_000);
+    assert!(manifest.partitioned);
+    let restored = BloomFilter::from_u8_array_verified(bloom.get_u8_array(), &manifest).unwrap();
+    assert!((0..10_000u32).all(|i| restored.contains(&i.to_le_bytes())));
+}
+
+#[test]
+fn counting_bloom_width_test() {
+    let mut builder = FilterBuilder::new(10_000, 0.01);
+    let mut narrow = builder.build_counting_bloom_filter::<Vec<usize>>(());
+    let mut wide: CountingBloomFilter<Vec<usize>, 16> =
+        builder.build_counting_bloom_filter_with_width(());
+    assert_eq!(wide.storage().len(), narrow.storage().len() * 4);
+    assert_ne!(wide.compatibility_key(), narrow.compatibility_key());
+    for _ in 0..1000 {
+        narrow.add(b"hot");
+        wide.add(b"hot");
+    }
+    assert_eq!(narrow.estimate_count(b"hot"), 15);
+    assert_eq!(wide.estimate_count(b"hot"), 1000);
+    wide.remove(b"hot");
+    assert_eq!(wide.estimate_count(b"hot"), 999);
+    assert_eq!(wide.counters_iter().sum::<usize>(), 999 * wide.hashes() as usize);
+}
+
+#[cfg(target_pointer_width = "64")]
+#[test]
+fn counting_bloom_wide_counters_test() {
+    let mut builder = FilterBuilder::new(10_000, 0.01);
+    let narrow = builder.build_counting_bloom_filter::<Vec<usize>>(());
+    let mut widest: CountingBloomFilter<Vec<usize>, 64> =
+        CountingBloomFilter::with_wide_counters(builder.clone(), ());
+    assert_eq!(widest.storage().len(), narrow.storage().len() * 16);
+    for _ in 0..100_000 {
+        widest.add(b"hot");
+    }
+    assert_eq!(widest.estimate_count(b"hot"), 100_000);
+    assert_eq!(widest.max_counter(), 100_000);
+    let other = widest.clone();
+    assert!(widest.union(&other));
+    assert_eq!(widest.estimate_count(b"hot"), 200_000);
+    assert_eq!(widest.multiset_similarity(&other), 0.5);
+}
+
+#[test]
+fn bloom_union_lenient_test() {
+    let mut bloom = FilterBuilder::from_size_and_hashes(65536, 3).build_bloom_filter();
+    let mut other = FilterBuilder::from_size_and_hashes(65536, 9).build_bloom_filter();
+    for i in 0..1000u32 {
+        bloom.add(&i.to_le_bytes());
+        other.add(&(i + 1000).to_le_bytes());
+    }
+    let mut merged = other.clone();
+    assert!(merged.union_lenient(&bloom));
+    assert_eq!(merged.config().hashes, 3);
+    assert!((0..2000u32).all(|i| merged.contains(&i.to_le_bytes())));
+
+    let mut seeded = FilterBuilder::from_size_and_hashes(65536, 3);
+    seeded.seed(1);
+    assert!(!bloom.union_lenient(&seeded.build_bloom_filter()));
+    let smaller = FilterBuilder::from_size_and_hashes(32768, 3).build_bloom_filter();
+    assert!(!bloom.union_lenient(&smaller));
+}
+
+#[test]
+fn bloom_insert_hook_test() {
+    use std::sync::Mutex;
+
+    struct Mirror(Mutex<BloomFilter>);
+
+    impl FilterEvents for Mirror {
+        fn on_insert(&self, _element: &[u8], indices: &[u64]) {
+            let mut mirror = self.0.lock().unwrap();
+            for &index in indices {
+                mirror.bit_set.set(index as usize);
+            }
+        }
+    }
+
+    let mut bloom = FilterBuilder::new(10_000, 0.01).build_bloom_filter();
+    let mirror = Arc::new(Mirror(Mutex::new(BloomFilter::new(bloom.config()))));
+    bloom.set_insert_hook(Some(mirror.clone()));
+    for i in 0..100u32 {
+        bloom.add(&i.to_le_bytes());
+    }
+    assert_eq!(mirror.0.lock().unwrap().get_u8_array(), bloom.get_u8_array());
+
+    bloom.set_insert_hook(None);
+    bloom.add(b"hello");
+    assert_ne!(mirror.0.lock().unwrap().get_u8_array(), bloom.get_u8_array());
+}
+
+#[test]
+fn bloom_shrink_to_fit_test() {
+    // 14977 words = 17 * 881
+    let mut bloom = FilterBuilder::new(100_000, 0.01).build_bloom_filter();
+    for i in 0..5_000u32 {
+        bloom.add(&i.to_le_bytes());
+    }
+    let size = bloom.shrink_to_fit();
+    assert_eq!(size, 881 * 64);
+    assert_eq!(bloom.config().size, size);
+    assert_eq!(bloom.get_u8_array().len() as u64 * 8, size);
+
+    // the folded filter is the filter of that size built from the same elements
+    let mut rebuilt = FilterBuilder::from_size_and_hashes(size, bloom.config().hashes)
+        .build_bloom_filter();
+    for i in 0..5_000u32 {
+        rebuilt.add(&i.to_le_bytes());
+    }
+    assert_eq!(bloom.get_u8_array(), rebuilt.get_u8_array());
+
+    let false_positives = (5_000..105_000u32).filter(|i| bloom.contains(&i.to_le_bytes())).count();
+    assert!(false_positives < 1_500, "{false_positives}");
+    assert_eq!(bloom.shrink_to_fit(), size);
+}
+
+#[test]
+fn bloom_fold_test() {
+    let mut builder = FilterBuilder::from_size_and_hashes(1 << 20, 7);
+    builder.seed(3);
+    let mut bloom = builder.build_bloom_filter();
+    for i in 0..10_000u32 {
+        bloom.add(&i.to_le_bytes());
+    }
+    bloom.fold(8);
+    assert_eq!(bloom.config().size, 1 << 17);
+    assert_eq!((bloom.hashes(), bloom.config().seed), (7, 3));
+
+    let mut rebuilt = FilterBuilder::from_size_and_hashes(1 << 17, 7);
+    rebuilt.seed(3);
+    let mut rebuilt = rebuilt.build_bloom_filter();
+    for i in 0..10_000u32 {
+        rebuilt.add(&i.to_le_bytes());
+    }
+    assert_eq!(bloom.get_u8_array(), rebuilt.get_u8_array());
+    bloom.add(b"hello");
+    assert!(bloom.contains(b"hello"));
+}
+
+#[test]
+#[should_panic(expected = "power of two")]
+fn bloom_fold_factor_test() {
+    FilterBuilder::from_size_and_hashes(1 << 20, 7).build_bloom_filter().fold(3);
+}
+
+#[test]
+fn bloom_merge_test() {
+    let config = |size: u64| {
+        let mut builder = FilterBuilder::from_size_and_hashes(size, 5);
+        builder.seed(9);
+        builder
+    };
+    let mut small = config(1 << 14).build_bloom_filter();
+    let mut large = config(1 << 17).build_bloom_filter();
+    for i in 0..500u32 {
+        small.add(&i.to_le_bytes());
+        large.add(&(i + 500).to_le_bytes());
+    }
+    let mut merged = small.clone();
+    merged.merge(&large).unwrap();
+    large.merge(&small).unwrap();
+    assert_eq!(large.config().size, 1 << 14);
+    assert_eq!(merged.get_u8_array(), large.get_u8_array());
+    assert!((0..1000u32).all(|i| merged.contains(&i.to_le_bytes())));
+
+    let mut same = config(1 << 14).build_bloom_filter();
+    same.merge(&small).unwrap();
+    assert_eq!(same.get_u8_array(), small.get_u8_array());
+
+    let odd = config(3 << 14).build_bloom_filter();
+    let err = small.merge(&odd).unwrap_err();
+    assert_eq!((err.left.size, err.right.size), (1 << 14, 3 << 14));
+    let mut seeded = config(1 << 15);
+    seeded.seed(1);
+    assert!(small.merge(&seeded.build_bloom_filter()).is_err());
+    assert_eq!(small.config().size, 1 << 14);
+}
+
+#[test]
+fn bloom_union_many_test() {
+    let mut builder = FilterBuilder::new(1_000_000, 0.01);
+    let shards: Vec<BloomFilter> = (0..40u32).map(|shard| {
+        let mut bloom = builder.build_bloom_filter();
+        (shard * 100..shard * 100 + 100).for_each(|i| bloom.add(&i.to_le_bytes()));
+        bloom
+    }).collect();
+    // more words than a block
+    assert!(shards[0].bit_set.storage.len() > UNION_BLOCK_WORDS);
+    let all = BloomFilter::union_many(&shards).unwrap().unwrap();
+    let pairwise = shards[1..].iter().fold(shards[0].clone(), |mut all, shard| {
+        assert!(all.union(shard));
+        all
+    });
+    assert_eq!(all.get_u8_array(), pairwise.get_u8_array());
+    assert!(all.config().is_compatible_to(&shards[0].config()));
+    assert_eq!(BloomFilter::union_many([&shards[3]]).unwrap().unwrap().get_u8_array(),
+               shards[3].get_u8_array());
+
+    // filters over other storage, merged one word at a time
+    let sparse: Vec<BloomFilter<SparseStorage>> = shards.iter()
+        .map(|shard| BloomFilter::from_bit_vec(shard.config(), BloomBitVec::with_storage(
+            SparseStorage::from_dense(&shard.bit_set.storage))))
+        .collect();
+    let merged = BloomFilter::union_many(&sparse).unwrap().unwrap();
+    assert_eq!(merged.get_u8_array(), all.get_u8_array());
+
+    builder.seed(1);
+    let other = builder.build_bloom_filter();
+    let error = BloomFilter::union_many(shards.iter().chain([&other])).unwrap_err();
+    assert_eq!(error.left, shards[0].compatibility_key());
+    assert_eq!(error.right, other.compatibility_key());
+}
+
+#[test]
+fn bloom_union_many_empty_test() {
+    assert!(BloomFilter::union_many(&Vec::<BloomFilter>::new()).unwrap().is_none());
+}
+
+#[test]
+fn bloom_batch_test() {
+    let mut bloom = FilterBuilder::new(100_000, 0.01).build_bloom_filter();
+    let keys: Vec<[u8; 4]> = (0..1000u32).map(u32::to_le_bytes).collect();
+    let elements: Vec<&[u8]> = keys.iter().map(|key| &key[..]).collect();
+    bloom.add_batch(&elements[..500]);
+
+    let mut single = FilterBuilder::new(100_000, 0.01).build_bloom_filter();
+    for element in &elements[..500] {
+        single.add(element);
+    }
+    assert_eq!(bloom.get_u8_array(), single.get_u8_array());
+
+    // odd length, so the last group is partial
+    let res = bloom.contains_batch(&elements[..997]);
+    let expected: Vec<bool> = elements[..997].iter().map(|e| single.contains(e)).collect();
+    assert_eq!(res, expected);
+    assert!(res[..500].iter().all(|hit| *hit));
+    assert_eq!(BloomFilter::<Vec<usize>>::default().contains_batch(&elements[..3]), vec![false; 3]);
+
+    for chunk in elements.chunks(64) {
+        let mask = bloom.contains_many(chunk);
+        let hits = bloom.contains_batch(chunk);
+        assert!(hits.iter().enumerate().all(|(i, hit)| (mask >> i & 1 == 1) == *hit));
+    }
+    assert_eq!(bloom.contains_many(&[]), 0);
+}
+
+#[test]
+fn bloom_contains_many_planned_test() {
+    let mut bloom = FilterBuilder::new(1_000_000, 0.01).build_bloom_filter();
+    assert!(bloom.config().size >= PLANNED_PROBE_MIN_BITS);
+    (0..500_000u32).for_each(|i| bloom.add(&i.to_le_bytes()));
+    let keys: Vec<[u8; 4]> = (490_000..510_000u32).map(u32::to_le_bytes).collect();
+    let elements: Vec<&[u8]> = keys.iter().map(|key| &key[..]).collect();
+    // full and partial chunks, half of them present
+    for chunk in elements.chunks(64).chain(elements.chunks(37)) {
+        let mask = bloom.contains_many(chunk);
+        assert!(chunk.iter().enumerate().all(|(i, e)| (mask >> i & 1 == 1) == bloom.contains(e)));
+    }
+    assert_eq!(bloom.contains_many(&elements[..64]), u64::MAX);
+    assert_eq!(bloom.contains_many(&[]), 0);
+}
+
+#[test]
+fn array_bloom_filter_test() {
+    let mut bloom = ArrayBloomFilter::<32>::for_elements(100);
+    assert_eq!(size_of_val(bloom.storage()), 32 * 8);
+    assert_eq!(bloom.config().size, 32 * 64);
+    for i in 0..100u32 {
+        bloom.add(&i.to_le_bytes());
+    }
+    assert!((0..100u32).all(|i| bloom.contains(&i.to_le_bytes())));
+    let false_positives = (100..10_100u32).filter(|i| bloom.contains(&i.to_le_bytes())).count();
+    assert!(false_positives < 100, "{false_positives} false positives");
+
+    let mut heap = FilterBuilder::from_size_and_hashes(bloom.config().size, bloom.hashes())
+        .build_bloom_filter();
+    heap.add(b"hello");
+    assert!(bloom.union(&heap) && bloom.contains(b"hello"));
+    let copy = bloom.clone();
+    bloom.clear();
+    assert!(!bloom.contains(b"hello") && copy.contains(b"hello"));
+
+    static EMPTY: ArrayBloomFilter<4> = ArrayBloomFilter::with_hashes(5);
+    let config = FilterBuilder::from_size_and_hashes(4 * 64, 5);
+    assert_eq!(EMPTY.config().expected_elements, config.expected_elements);
+    let p = config.false_positive_probability;
+    assert!((EMPTY.config().false_positive_probability - p).abs() <= p * 1e-12);
+    let mut bloom = EMPTY.clone();
+    bloom.add(b"hello");
+    assert!(bloom.contains(b"hello") && !EMPTY.contains(b"hello"));
+}
+
+#[test]
+fn bloom_check_and_set_test() {
+    let mut bloom = FilterBuilder::new(10_000, 0.01).build_bloom_filter();
+    let mut single = FilterBuilder::new(10_000, 0.01).build_bloom_filter();
+    for i in 0..10_000u32 {
+        let key = (i % 5000).to_le_bytes();
+        assert_eq!(bloom.check_and_set(&key), single.contains(&key));
+        single.add(&key);
+    }
+    assert_eq!(bloom.get_u8_array(), single.get_u8_array());
+}
+
+#[test]
+fn bloom_range_sync_test() {
+    let mut builder = FilterBuilder::new(10_000, 0.01);
+    let mut primary = builder.build_bloom_filter();
+    let mut replica = builder.build_bloom_filter();
+    (0..1000u32).for_each(|i| primary.add(&i.to_le_bytes()));
+    replica.add(b"stale");
+    assert_eq!(primary.word_len(), primary.config().size as usize / 64);
+    for start in (0..primary.word_len()).step_by(100) {
+        let end = (start + 100).min(primary.word_len());
+        let page = primary.bits_in_range(start..end);
+        assert_eq!(page.len(), end - start);
+        replica.apply_range(start, &page);
+    }
+    assert_eq!(replica.get_u8_array(), primary.get_u8_array());
+    assert!(primary.bits_in_range(0..0).is_empty());
+}
+
+#[test]
+fn bloom_bulk_test() {
+    let mut bloom = FilterBuilder::new(10_000, 0.01).build_bloom_filter();
+    let keys: Vec<String> = (0..1000).map(|i| format!("key-{i}")).collect();
+    bloom.add_all(&keys);
+    let mut single = FilterBuilder::new(10_000, 0.01).build_bloom_filter();
+    single.extend(keys.iter().map(String::as_bytes));
+    assert_eq!(bloom.get_u8_array(), single.get_u8_array());
+
+    assert!(bloom.contains_all(&keys));
+    assert!(bloom.contains_all(Vec::<&[u8]>::new()));
+    assert!(!bloom.contains_any(Vec::<&[u8]>::new()));
+    assert!(!bloom.contains_all(["key-1", "absent"]));
+    assert!(bloom.contains_any(["absent", "key-1"]));
+
+    let mut cbf = FilterBuilder::new(10_000, 0.01).build_counting_bloom_filter::<Vec<usize>>(());
+    cbf.extend(&keys);
+    assert!(keys.iter().all(|key| cbf.estimate_count(key.as_bytes()) > 0));
+}
+
+#[test]
+fn bloom_contains_with_k_test() {
+    let mut bloom = FilterBuilder::new(10_000, 0.01).build_bloom_filter();
+    for i in 0..10_000u32 {
+        bloom.add(&i.to_le_bytes());
+    }
+    let k = bloom.config().hashes;
+    assert_eq!(k, 7);
+    assert!((0..10_000u32).all(|i| bloom.contains_with_k(&i.to_le_bytes(), 3)));
+
+    let false_positives = |k_query| (10_000..110_000u32)
+        .filter(|i| bloom.contains_with_k(&i.to_le_bytes(), k_query))
+        .count();
+    let (full, reduced) = (false_positives(k), false_positives(3));
+    assert_eq!(full, (10_000..110_000u32).filter(|i| bloom.contains(&i.to_le_bytes())).count());
+    // ρ ≈ 1/2: about 1% and 12.5%
+    assert!(full < 1_500, "{full}");
+    assert!((10_000..15_000).contains(&reduced), "{reduced}");
+}
+
+#[test]
+fn bloom_set_operators_test() {
+    let mut builder = FilterBuilder::new(10_000, 0.01);
+    let mut a = builder.build_bloom_filter();
+    let mut b = builder.build_bloom_filter();
+    for i in 0..100u32 {
+        a.add(&i.to_le_bytes());
+        b.add(&(i + 50).to_le_bytes());
+    }
+    let union = &a | &b;
+    assert!((0..150u32).all(|i| union.contains(&i.to_le_bytes())));
+    let mut expected = a.clone();
+    expected.union(&b);
+    assert_eq!(union.get_u8_array(), expected.get_u8_array());
+
+    let intersection = a.intersected(&b).unwrap();
+    assert!((50..100u32).all(|i| intersection.contains(&i.to_le_bytes())));
+    assert_eq!((&a & &b).get_u8_array(), intersection.get_u8_array());
+
+    builder.seed(7);
+    let err = a.unioned(&builder.build_bloom_filter()).unwrap_err();
+    assert_eq!(err.to_string(), "filters can't be merged: seed 0 != 7");
+    let smaller = FilterBuilder::from_size_and_hashes(4096, a.config().hashes).build_bloom_filter();
+    assert!(a.intersected(&smaller).unwrap_err().to_string().contains("size"));
+}
+
+#[test]
+fn bloom_intersect_with_estimate_test() {
+    let mut builder = FilterBuilder::new(10_000, 0.01);
+    let (mut a, mut b) = (builder.build_bloom_filter(), builder.build_bloom_filter());
+    for i in 0..8000u32 {
+        a.add(&i.to_le_bytes());
+        b.add(&(i + 4000).to_le_bytes());
+    }
+    let (both, fpp) = a.intersect_with_estimate(&b).unwrap();
+    assert!((4000..8000u32).all(|i| both.contains(&i.to_le_bytes())));
+    // elements of one set only are false positives at about the bound
+    let only_a = (0..4000u32).filter(|i| both.contains(&i.to_le_bytes())).count();
+    assert!((only_a as f64) < 2.0 * fpp * 4000.0, "{only_a} false positives, bound {fpp}");
+    assert!((only_a as f64) > 0.5 * fpp * 4000.0, "{only_a} false positives, bound {fpp}");
+    let neither = (100_000..200_000u32).filter(|i| both.contains(&i.to_le_bytes())).count();
+    assert!((neither as f64) < 0.5 * fpp * 100_000.0, "{neither} false positives, bound {fpp}");
+
+    let other = FilterBuilder::new(20_000, 0.01).build_bloom_filter();
+    assert!(a.intersect_with_estimate(&other).is_err());
+}
+
+#[test]
+fn bloom_estimate_len_test() {
+    let mut bloom = FilterBuilder::new(100_000, 0.01).build_bloom_filter();
+    assert_eq!(bloom.estimate_len(), 0.0);
+    for i in 0..50_000u32 {
+        bloom.add(&i.to_le_bytes());
+    }
+    let len = bloom.estimate_len();
+    assert!((49_000.0..51_000.0).contains(&len), "{len}");
+
+    let mut full = FilterBuilder::from_size_and_hashes(64, 2).build_bloom_filter();
+    (0..1000u32).for_each(|i| full.add(&i.to_le_bytes()));
+    assert_eq!(full.estimate_len(), f64::INFINITY);
+}
+
+#[test]
+fn bloom_current_fpp_test() {
+    let mut bloom = FilterBuilder::from_size_and_hashes(1024, 3).build_bloom_filter();
+    assert_eq!(bloom.fill_ratio(), 0.0);
+    bloom.add(b"hello");
+    assert_eq!(bloom.fill_ratio(), bloom.bit_set.count_ones() as f64 / 1024.0);
+    assert_eq!(bloom.current_fpp(), bloom.fill_ratio().powi(3));
+    (0..10_000u32).for_each(|i| bloom.add(&i.to_le_bytes()));
+    assert_eq!(bloom.fill_ratio(), 1.0);
+    assert_eq!(bloom.current_fpp(), 1.0);
+}
+
+#[test]
+fn bloom_similarity_test() {
+    let mut builder = FilterBuilder::new(100_000, 0.01);
+    let (mut a, mut b) = (builder.build_bloom_filter(), builder.build_bloom_filter());
+    assert_eq!(a.jaccard(&b), Ok(1.0));
+    for i in 0..10_000u32 {
+        a.add(&i.to_le_bytes());
+        b.add(&(i + 5000).to_le_bytes());
+    }
+    let intersection = a.intersection_estimate(&b).unwrap();
+    assert!((4800.0..5200.0).contains(&intersection), "{intersection}");
+    let jaccard = a.jaccard(&b).unwrap();
+    assert!((0.32..0.35).contains(&jaccard), "{jaccard}");
+    assert!((a.jaccard(&a).unwrap() - 1.0).abs() < 1e-9);
+
+    let mut full = FilterBuilder::from_size_and_hashes(64, 2).build_bloom_filter();
+    (0..1000u32).for_each(|i| full.add(&i.to_le_bytes()));
+    let small = FilterBuilder::from_size_and_hashes(64, 2).build_bloom_filter();
+    assert_eq!(full.intersection_estimate(&small), Ok(f64::INFINITY));
+    assert!(full.jaccard(&small).unwrap().is_nan());
+    let other = FilterBuilder::from_size_and_hashes(128, 2).build_bloom_filter();
+    assert!(full.intersection_estimate(&other).is_err());
+    assert!(small.jaccard(&other).is_err());
+
+    let empty = FilterBuilder::from_size_and_hashes(0, 2).build_bloom_filter();
+    assert_eq!(empty.jaccard(&empty), Ok(1.0));
+    assert_eq!(empty.intersection_estimate(&empty), Ok(0.0));
+    assert_eq!(empty.difference_estimate(&empty), Ok(0.0));
+}
@@ -0,0 +1,143 @@
+use alloc::vec::Vec;
+
+use crate::bloom::BloomFilter;
+use crate::builder::FilterBuilder;
+use crate::family::BLOOMFILTER_SIPHASH;
+use crate::index::IndexScheme;
+use crate::vec::{BloomBitVec, le_word, Storage};
+
+impl BloomFilter {
+    /// Restores a filter of the `bloomfilter` crate from the parts it exports: `Bloom::bitmap`,
+    /// `Bloom::number_of_bits`, `Bloom::number_of_hash_functions` and `Bloom::sip_keys`, the
+    /// arguments of its `Bloom::from_existing`. The filter hashes like the `Bloom` (see
+    /// [FilterBuilder::bloomfilter_sip_keys]), so elements added on either side are found on the
+    /// other. Keys are the bytes the `Hash` implementation of an item writes, add and query them
+    /// with [Membership::add_hashable](crate::Membership::add_hashable) and
+    /// [Membership::contains_hashable](crate::Membership::contains_hashable); `usize` lengths
+    /// are written as 64 bits, as the `Bloom` does on 64-bit hosts.
+    ///
+    /// Returns `None` when there are no bits or hashes or the bitmap doesn't hold
+    /// `bitmap_bits` bits. Needs the `siphash` feature.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use fastbloom_rs::{BloomFilter, FilterBuilder, Membership};
+    ///
+    /// let sip_keys = [(1, 2), (3, 4)];
+    /// let mut builder = FilterBuilder::from_size_and_hashes(8 * 1024, 7);
+    /// builder.bloomfilter_sip_keys(sip_keys);
+    /// let mut bloom = builder.build_bloom_filter();
+    /// bloom.add_hashable("hello");
+    ///
+    /// // the parts of `Bloom::from_existing`
+    /// let bitmap = bloom.to_bloomfilter_bitmap();
+    /// let restored = BloomFilter::from_bloomfilter(&bitmap, 8 * 1024, 7, sip_keys).unwrap();
+    /// assert!(restored.contains_hashable("hello"));
+    /// ```
+    pub fn from_bloomfilter(bitmap: &[u8], bitmap_bits: u64, k_num: u32,
+                            sip_keys: [(u64, u64); 2]) -> Option<Self> {
+        if bitmap_bits == 0 || k_num == 0 || bitmap_bits.div_ceil(8) != bitmap.len() as u64 {
+            return None;
+        }
+        let mut config = FilterBuilder::from_size_and_hashes(bitmap_bits, k_num);
+        config.bloomfilter_sip_keys(sip_keys);
+        // bit `i` is the most significant bit of byte `i / 8`, the bytes of the words hold
+        // their least significant bit first
+        let mut le: Vec<u8> = bitmap.iter().map(|byte| byte.reverse_bits()).collect();
+        le.resize(le.len().next_multiple_of(size_of::<usize>()), 0);
+        let storage = le.chunks_exact(size_of::<usize>())
+            .map(|word| le_word(usize::from_le_bytes(word.try_into().unwrap())))
+            .collect();
+        Some(BloomFilter::from_bit_vec(config, BloomBitVec::with_storage(storage)))
+    }
+}
+
+impl<S: Storage> BloomFilter<S> {
+    /// Returns the bits in the layout of `Bloom::bitmap` of the `bloomfilter` crate, to be read
+    /// with `Bloom::from_existing` together with the size and hashes of the filter and the keys
+    /// given to [FilterBuilder::bloomfilter_sip_keys], see [BloomFilter::from_bloomfilter].
+    /// Panics unless the filter uses the hashing of the `bloomfilter` crate, without canaries or
+    /// partitions.
+    pub fn to_bloomfilter_bitmap(&self) -> Vec<u8> {
+        let config = &self.config;
+        assert!(config.index_scheme == IndexScheme::BloomfilterCrate
+                    && config.hasher.id == BLOOMFILTER_SIPHASH && config.canaries == 0
+                    && !config.partitioned,
+                "filter must use the hashing of FilterBuilder::bloomfilter_sip_keys!");
+        let bytes = usize::try_from(config.size.div_ceil(8)).unwrap();
+        (0..self.bit_set.storage.slots())
+            .flat_map(|slot| self.bit_set.word(slot).to_le_bytes())
+            .take(bytes)
+            .map(|byte| byte.reverse_bits())
+            .collect()
+    }
+}
+
+#[test]
+fn bloomfilter_indices_test() {
+    use core::hash::{Hash, Hasher};
+    use std::collections::hash_map::DefaultHasher;
+
+    use crate::hasher::KeyBytes;
+    use crate::index::Indices;
+
+    // `Bloom::set` of the bloomfilter crate; std's hasher is SipHash-1-3 with zero keys
+    fn expected<T: Hash + ?Sized>(item: &T, bits: u64, k: u32) -> Vec<u64> {
+        let mut sip = DefaultHasher::new();
+        item.hash(&mut sip);
+        let hashes = [sip.finish(), sip.finish()];
+        (0..k as u64).map(|i| {
+            let hash = if i < 2 {
+                hashes[i as usize]
+            } else {
+                hashes[0].wrapping_add(i.wrapping_mul(hashes[1])) % 0xffffffffffffffc5
+            };
+            hash % bits
+        }).collect()
+    }
+
+    let mut config = FilterBuilder::from_size_and_hashes(1000, 7);
+    config.bloomfilter_sip_keys([(0, 0), (0, 0)]);
+    let indices = |key: KeyBytes| Indices::new(&key.0, &config).collect::<Vec<u64>>();
+    assert_eq!(indices(KeyBytes::of("hello")), expected("hello", 1000, 7));
+    assert_eq!(indices(KeyBytes::of(&42u64)), expected(&42u64, 1000, 7));
+    assert_eq!(indices(KeyBytes::of(&b"bytes"[..])), expected(&b"bytes"[..], 1000, 7));
+}
+
+#[test]
+fn bloomfilter_bitmap_test() {
+    use crate::Membership;
+
+    // 1000 bits are not whole words, as in the filters `Bloom::new_for_fp_rate` sizes
+    let sip_keys = [(0x0123, 0x4567), (0x89ab, 0xcdef)];
+    let mut bloom = BloomFilter::from_bloomfilter(&[0; 125], 1000, 5, sip_keys).unwrap();
+    for i in 0..100u64 {
+        bloom.add_hashable(&i);
+    }
+    let bitmap = bloom.to_bloomfilter_bitmap();
+    assert_eq!(bitmap.len(), 125);
+    // bit `i` is the bit `7 - i % 8` of byte `i / 8`
+    for index in bloom.get_hash_indices(&crate::hasher::KeyBytes::of(&0u64).0) {
+        assert_ne!(bitmap[index as usize / 8] & (0x80 >> (index % 8)), 0);
+    }
+    assert_eq!(bitmap.iter().map(|byte| byte.count_ones() as u64).sum::<u64>(),
+               bloom.get_u8_array().iter().map(|byte| byte.count_ones() as u64).sum::<u64>());
+
+    let restored = BloomFilter::from_bloomfilter(&bitmap, 1000, 5, sip_keys).unwrap();
+    assert_eq!(restored.to_bloomfilter_bitmap(), bitmap);
+    assert!((0..100u64).all(|i| restored.contains_hashable(&i)));
+    assert!(restored.config().is_compatible_to(&bloom.config()));
+    let other_keys = BloomFilter::from_bloomfilter(&bitmap, 1000, 5, [(1, 2), (3, 4)]).unwrap();
+    assert!(!other_keys.config().is_compatible_to(&bloom.config()));
+
+    assert!(BloomFilter::from_bloomfilter(&bitmap[1..], 1000, 5, sip_keys).is_none());
+    assert!(BloomFilter::from_bloomfilter(&bitmap, 1000, 0, sip_keys).is_none());
+    assert!(BloomFilter::from_bloomfilter(&[], 0, 5, sip_keys).is_none());
+}
+
+#[test]
+#[should_panic(expected = "hashing of FilterBuilder::bloomfilter_sip_keys")]
+fn bloomfilter_bitmap_hashing_test() {
+    FilterBuilder::new(1000, 0.01).build_bloom_filter().to_bloomfilter_bitmap();
+}
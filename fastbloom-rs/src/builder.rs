@@ -1,6 +1,42 @@
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::fmt::{Display, Formatter};
+use core::marker::PhantomData;
+#[cfg(feature = "std")]
+use std::time::Duration;
+
+use crate::aging::AgingBloomFilter;
+use crate::blocked::{BlockedBloomFilter, BlockSize};
 use crate::bloom::{BloomFilter, CountingBloomFilter};
+use crate::cascade::FilterCascade;
+use crate::compat::{CompatibilityKey, FilterLayout};
+use crate::cuckoo::CuckooFilter;
+use crate::dleft::DLeftCountingFilter;
+use crate::dlbf::DlBloomFilter;
+use crate::double::DoubleBufferedBloomFilter;
+use crate::family::HashFamily;
+use crate::fingerprint::FingerprintTable;
+use crate::fuse::{BinaryFuseFilter, BloomierFilter, FuseFingerprint};
+use crate::golomb::GolombCodedSet;
+use crate::hasher::HasherRef;
+use crate::index::{default_hash_seed, hash_seeds_digest, is_default_hash_seeds, IndexScheme,
+                   Indices};
+use crate::params::{expected_fpp, max_elements, optimal_bits, optimal_hashes};
+use crate::quarantine::QuarantineFilter;
+use crate::quotient::{CountingQuotientFilter, QuotientFilter};
+#[cfg(feature = "async")]
+use crate::remote::{AsyncBloomFilter, AsyncStorage};
+use crate::ribbon::RibbonFilter;
+use crate::sbbf::SplitBlockBloomFilter;
+use crate::sharded::ShardedBloomFilter;
+#[cfg(feature = "std")]
+use crate::sliding::SlidingBloomFilter;
+use crate::sketch::HyperLogLog;
+use crate::tiered::TieredBloomFilter;
 use crate::Membership;
 use crate::vec::Storage;
+#[cfg(not(feature = "std"))]
+use crate::float::Float;
 
 /// Builder for Bloom Filters.
 #[derive(Clone)]
@@ -8,34 +44,71 @@ use crate::vec::Storage;
 pub struct FilterBuilder {
     pub expected_elements: u64,
     pub false_positive_probability: f64,
+    /// The filter is sized for `expected_elements * headroom_factor` elements.
+    pub headroom_factor: f64,
     pub size: u64,
     pub hashes: u32,
+    /// Seed of the hash functions.
+    pub seed: u64,
+    /// How the bit indices are derived from the hash of an element.
+    pub index_scheme: IndexScheme,
+    /// Salts of the indices of [IndexScheme::Extended], see [FilterBuilder::hash_seeds].
+    pub(crate) hash_seeds: Option<Arc<[u64]>>,
+    /// Hash function, see [FilterBuilder::hasher].
+    pub(crate) hasher: HasherRef,
+    /// Hint of the typical key length in bytes, `0` if unknown.
+    pub expected_key_len: usize,
+    /// Usage for BlockedBloomFilter.
+    pub block_size: BlockSize,
     /// Usage for CountingBloomFilter.
     pub enable_repeat_insert: bool,
+    /// Usage for CountingBloomFilter.
+    pub enable_conservative_update: bool,
+    /// Probe all hash positions on `contains` without short-circuiting.
+    pub enable_constant_time: bool,
+    /// Number of canary bits at the end of the filter, see [FilterBuilder::canaries].
+    pub canaries: u32,
+    /// One slice of the bits per hash function, see [FilterBuilder::partitioned].
+    pub partitioned: bool,
     pub(crate) done: bool,
 }
 
-#[cfg(target_pointer_width = "32")]
+// sizes are whole words, u64 words with the `canonical-u64` feature.
+#[cfg(all(target_pointer_width = "32", not(feature = "canonical-u64")))]
 pub(crate) const SUFFIX: usize = 0b0001_1111;
-#[cfg(target_pointer_width = "64")]
+#[cfg(any(target_pointer_width = "64", feature = "canonical-u64"))]
 pub(crate) const SUFFIX: usize = 0b0011_1111;
-#[cfg(target_pointer_width = "32")]
+#[cfg(all(target_pointer_width = "32", not(feature = "canonical-u64")))]
 pub(crate) const MASK: u64 = 0b11111111_11111111_11111111_11111111_11111111_11111111_11111111_11100000;
-#[cfg(target_pointer_width = "64")]
+#[cfg(any(target_pointer_width = "64", feature = "canonical-u64"))]
 pub(crate) const MASK: u64 = 0b11111111_11111111_11111111_11111111_11111111_11111111_11111111_11000000;
 
 /// Calculates the optimal size `m` of the bloom filter in bits given `n` (expected
-/// number of elements in bloom filter) and `p` (tolerable false positive rate).
+/// number of elements in bloom filter) and `p` (tolerable false positive rate), in whole words.
+/// Returns `None` if `m` doesn't fit into a u64.
 #[inline]
-fn optimal_m(n: u64, p: f64) -> u64 {
-    let fact = -(n as f64) * p.ln();
-    let div = 2f64.ln().powi(2);
-    let m: f64 = fact / div;
-    let mut m = m.ceil() as u64;
+fn optimal_m(n: u64, p: f64) -> Option<u64> {
+    let m = optimal_bits(n, p);
+    if p.is_nan() || m == u64::MAX {
+        return None;
+    }
     if (m & SUFFIX as u64) != 0 {
-        m = (m & MASK) + SUFFIX as u64 + 1;
-    };
-    m
+        (m & MASK).checked_add(SUFFIX as u64 + 1)
+    } else { Some(m) }
+}
+
+/// Returns the largest size in bits of a filter in `layout` whose storage is addressable on this
+/// platform, i.e. takes at most `isize::MAX` bytes.
+fn max_size(layout: FilterLayout) -> u64 {
+    let bytes = isize::MAX as u64;
+    match layout {
+        FilterLayout::Standard => bytes.saturating_mul(8) & MASK,
+        FilterLayout::Counting(bits) => (bytes.saturating_mul(8) / bits as u64) & MASK,
+        FilterLayout::Blocked(block_size) => {
+            let bits = bytes.saturating_mul(8);
+            bits - bits % block_size.bits()
+        }
+    }
 }
 
 /// Calculates the optimal `hashes` (number of hash function) given `n` (expected number of
@@ -55,14 +128,91 @@ fn optimal_n(k: u32, m: u64) -> u64 {
 }
 
 
-/// Calculates the best-case (uniform hash function) false positive probability.
-/// `k` number of hashes.
-/// `m` The size of the bloom filter in bits.
-/// `n` number of elements inserted in the filter.
-#[inline]
-fn optimal_p(k: u32, m: u64, n: u64) -> f64 {
-    let nk = -(k as f64);
-    (1.0 - (nk * n as f64 / m as f64).exp()).powi(k as i32)
+/// Returns the size in bits of a memory budget of `bytes`, in whole words.
+fn budget_size(bytes: u64) -> u64 {
+    let size = bytes.saturating_mul(8) & MASK;
+    assert!(size > 0, "the memory budget must hold at least one word!");
+    size
+}
+
+/// Error of a configuration which needs more memory than this platform can address, see
+/// [FilterBuilder::checked_size].
+#[derive(Clone, Debug, PartialEq)]
+pub struct SizeError {
+    /// Size in bits the configuration asks for, `None` if it doesn't even fit into a u64.
+    pub requested_size: Option<u64>,
+    /// Largest supported size in bits.
+    pub max_size: u64,
+    /// Largest supported expected number of elements at `false_positive_probability` (and the
+    /// configured headroom factor).
+    pub max_expected_elements: u64,
+    pub false_positive_probability: f64,
+}
+
+impl Display for SizeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self.requested_size {
+            Some(size) => write!(f, "filter of {size} bits")?,
+            None => write!(f, "filter of more than {} bits", u64::MAX)?,
+        }
+        write!(f, " exceeds the maximum of {} bits, at most {} elements are supported at a false \
+            positive probability of {}", self.max_size, self.max_expected_elements,
+            self.false_positive_probability)
+    }
+}
+
+impl core::error::Error for SizeError {}
+
+/// Error of a configuration no filter can be built from, see
+/// [FilterBuilder::try_build_bloom_filter].
+#[derive(Clone, Debug, PartialEq)]
+pub enum BuildError {
+    /// `expected_elements` is zero.
+    ZeroExpectedElements,
+    /// `false_positive_probability` is not in `(0, 1)`.
+    InvalidFalsePositiveProbability(f64),
+    /// `headroom_factor` is less than `1.0`.
+    InvalidHeadroomFactor(f64),
+    /// The filter is sized explicitly (see [FilterBuilder::from_size_and_hashes]) with zero
+    /// hashes.
+    ZeroHashes,
+    /// The canaries (see [FilterBuilder::canaries]) take all bits of the filter.
+    TooManyCanaries,
+    /// The filter is partitioned into more partitions than it has bits.
+    EmptyPartitions,
+    /// The filter needs more memory than this platform can address.
+    TooLarge(SizeError),
+}
+
+impl Display for BuildError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            BuildError::ZeroExpectedElements => write!(f, "expected elements must not be zero!"),
+            BuildError::InvalidFalsePositiveProbability(p) =>
+                write!(f, "false positive probability {p} is not in (0, 1)!"),
+            BuildError::InvalidHeadroomFactor(factor) =>
+                write!(f, "headroom factor {factor} is less than 1.0!"),
+            BuildError::ZeroHashes => write!(f, "hashes must not be zero!"),
+            BuildError::TooManyCanaries => write!(f, "canaries must be less than the size!"),
+            BuildError::EmptyPartitions => write!(f, "partitions must have at least one bit!"),
+            BuildError::TooLarge(err) => Display::fmt(err, f),
+        }
+    }
+}
+
+impl core::error::Error for BuildError {
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        match self {
+            BuildError::TooLarge(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<SizeError> for BuildError {
+    fn from(err: SizeError) -> Self {
+        BuildError::TooLarge(err)
+    }
 }
 
 impl FilterBuilder {
@@ -82,9 +232,20 @@ impl FilterBuilder {
         FilterBuilder {
             expected_elements,
             false_positive_probability,
+            headroom_factor: 1.0,
             size: 0,
             hashes: 0,
+            seed: 0,
+            index_scheme: IndexScheme::DoubleHashing,
+            hash_seeds: None,
+            hasher: HasherRef::default(),
+            expected_key_len: 0,
+            block_size: BlockSize::CacheLine,
             enable_repeat_insert: true,
+            enable_conservative_update: false,
+            enable_constant_time: false,
+            canaries: 0,
+            partitioned: false,
             done: false,
         }
     }
@@ -94,17 +255,190 @@ impl FilterBuilder {
     /// probability will be inferred from this.
     pub fn from_size_and_hashes(size: u64, hashes: u32) -> Self {
         let n = optimal_n(hashes, size);
-        let p = optimal_p(hashes, size, n);
+        FilterBuilder::with_shape(size, hashes, n, expected_fpp(size, n, hashes))
+    }
+
+    /// Const constructor of a completed builder of `size` bits and `hashes` hashes for
+    /// `expected_elements` elements at the false positive probability `p`.
+    pub(crate) const fn with_shape(size: u64, hashes: u32, expected_elements: u64, p: f64)
+                                   -> Self {
         FilterBuilder {
-            expected_elements: n,
+            expected_elements,
             false_positive_probability: p,
+            headroom_factor: 1.0,
             size,
             hashes,
+            seed: 0,
+            index_scheme: IndexScheme::DoubleHashing,
+            hash_seeds: None,
+            hasher: HasherRef::builtin(),
+            expected_key_len: 0,
+            block_size: BlockSize::CacheLine,
             enable_repeat_insert: true,
+            enable_conservative_update: false,
+            enable_constant_time: false,
+            canaries: 0,
+            partitioned: false,
             done: true,
         }
     }
 
+    /// Constructs a Bloom Filter Builder for a filter taking `bytes` (rounded down to whole words)
+    /// at the tolerable false positive probability: the expected number of elements is the
+    /// largest the size holds at that probability, with the optimal number of hashes. The budget
+    /// is the bit vector of a [BloomFilter]; a counting filter with `W`-bit counters takes `W`
+    /// times as much.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use fastbloom_rs::FilterBuilder;
+    ///
+    /// let builder = FilterBuilder::from_size_and_fpp(64 << 20, 0.01);
+    /// assert_eq!(builder.size, 64 << 23);
+    /// assert_eq!(builder.hashes, 7);
+    /// assert!(builder.expected_elements > 56_000_000);
+    /// ```
+    pub fn from_size_and_fpp(bytes: u64, false_positive_probability: f64) -> Self {
+        let p = false_positive_probability;
+        assert!(p > 0.0 && p < 1.0, "False positive probability must be in (0, 1)!");
+        let size = budget_size(bytes);
+        let n = max_elements(size, p).max(1);
+        let mut config = FilterBuilder::from_size_and_hashes(size, optimal_hashes(size, n));
+        config.expected_elements = n;
+        config.false_positive_probability = p;
+        config
+    }
+
+    /// Constructs a Bloom Filter Builder for a filter taking `bytes` (rounded down to whole words)
+    /// which will hold `expected_elements`, with the optimal number of hashes. The false positive
+    /// probability is the one the size reaches with that many elements, see
+    /// [FilterBuilder::from_size_and_fpp].
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use fastbloom_rs::FilterBuilder;
+    ///
+    /// let builder = FilterBuilder::from_size_and_items(64 << 20, 10_000_000);
+    /// assert_eq!(builder.hashes, 37);
+    /// assert!(builder.false_positive_probability < 1e-10);
+    /// ```
+    pub fn from_size_and_items(bytes: u64, expected_elements: u64) -> Self {
+        assert!(expected_elements > 0, "expected elements must not be zero!");
+        let size = budget_size(bytes);
+        let hashes = optimal_hashes(size, expected_elements);
+        let mut config = FilterBuilder::from_size_and_hashes(size, hashes);
+        config.expected_elements = expected_elements;
+        config.false_positive_probability = expected_fpp(size, expected_elements, hashes);
+        config
+    }
+
+    /// Constructs a Bloom Filter Builder for the number of distinct elements in `sample`, e.g.
+    /// a replay of yesterday's traffic or the first part of a stream, estimated with a
+    /// HyperLogLog (about 1% error) in constant memory, and the tolerable false positive
+    /// probability. Use it if the number of elements is not known in advance; add
+    /// [FilterBuilder::headroom_factor] if the stream continues past the sample.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use fastbloom_rs::FilterBuilder;
+    ///
+    /// let sample = (0..30_000u32).map(|i| (i % 10_000).to_le_bytes());
+    /// let builder = FilterBuilder::from_stream_sample(sample, 0.01);
+    /// assert!(builder.expected_elements.abs_diff(10_000) < 300);
+    /// ```
+    pub fn from_stream_sample<T: AsRef<[u8]>>(sample: impl IntoIterator<Item=T>,
+                                              false_positive_probability: f64) -> Self {
+        let mut hll = HyperLogLog::new();
+        for element in sample {
+            hll.add(element.as_ref());
+        }
+        FilterBuilder::new((hll.estimate().round() as u64).max(1), false_positive_probability)
+    }
+
+    /// Configuration of a filter without capacity: size, hashes and expected elements are `0`,
+    /// and as nothing ever matches, so is the false positive probability.
+    pub(crate) fn zero_capacity() -> Self {
+        let mut config = FilterBuilder::from_size_and_hashes(0, 0);
+        config.false_positive_probability = 0.0;
+        config
+    }
+
+    /// Starts a [TypedBuilder], which rejects invalid configurations at compile time.
+    pub fn typed() -> TypedBuilder<state::Unsized> {
+        TypedBuilder { config: FilterBuilder::new(1, 0.5), state: PhantomData }
+    }
+
+    /// Preset for URL (or any web crawl) deduplication of `expected_elements` URLs: a false
+    /// positive silently drops a URL, so the false positive probability is `0.001`, and as URLs
+    /// are long keys they are hashed once with [IndexScheme::EnhancedDoubleHashing]. Build it with
+    /// [FilterBuilder::build_bloom_filter], the plain layout.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use fastbloom_rs::{FilterBuilder, Membership};
+    ///
+    /// let mut bloom = FilterBuilder::preset_url_dedup(1_000_000).build_bloom_filter();
+    /// bloom.add(b"https://example.com/");
+    /// assert_eq!(bloom.contains(b"https://example.com/"), true);
+    /// ```
+    pub fn preset_url_dedup(expected_elements: u64) -> Self {
+        let mut builder = FilterBuilder::new(expected_elements, 0.001);
+        builder.index_scheme(IndexScheme::EnhancedDoubleHashing);
+        builder
+    }
+
+    /// Preset for cache admission (TinyLFU style doorkeeper) over `expected_elements` distinct
+    /// keys: admission only needs approximate frequencies, so the false positive probability is
+    /// `0.01` and conservative update is enabled to keep the frequency estimates tight. Cache keys
+    /// are mostly short ids, so the hashing specializes on keys of up to 16 bytes, see
+    /// [FilterBuilder::expected_key_len]. Build it with
+    /// [FilterBuilder::build_counting_bloom_filter] and query
+    /// [CountingBloomFilter::estimate_count].
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use fastbloom_rs::FilterBuilder;
+    ///
+    /// let mut cbf = FilterBuilder::preset_cache_admission(100_000)
+    ///     .build_counting_bloom_filter::<Vec<usize>>(());
+    /// cbf.add(b"key");
+    /// cbf.add(b"key");
+    /// assert_eq!(cbf.estimate_count(b"key"), 2);
+    /// ```
+    pub fn preset_cache_admission(expected_elements: u64) -> Self {
+        let mut builder = FilterBuilder::new(expected_elements, 0.01);
+        builder.enable_conservative_update(true);
+        builder.expected_key_len(16);
+        builder
+    }
+
+    /// Preset for skipping point lookups of absent keys in a database (LSM tree, SST file)
+    /// holding `expected_elements` keys: a false positive only costs one extra read, so it uses
+    /// about 10 bits per key (false positive probability `0.01`). Each lookup should cost one
+    /// cache miss, so the layout is a [BlockedBloomFilter] with [BlockSize::CacheLine] blocks, as
+    /// in RocksDB; build it with [FilterBuilder::build_blocked_bloom_filter].
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use fastbloom_rs::{FilterBuilder, Membership};
+    ///
+    /// let mut blocked = FilterBuilder::preset_db_point_lookup(1_000_000)
+    ///     .build_blocked_bloom_filter();
+    /// blocked.add(b"user:42");
+    /// assert_eq!(blocked.contains(b"user:42"), true);
+    /// ```
+    pub fn preset_db_point_lookup(expected_elements: u64) -> Self {
+        let mut builder = FilterBuilder::new(expected_elements, 0.01);
+        builder.block_size(BlockSize::CacheLine);
+        builder
+    }
+
     /// set the expected size of the filter.
     fn expected_elements(&mut self, expected_elements: u64) {
         assert!(expected_elements > 0, "expected_elements must larger than 0!");
@@ -118,6 +452,375 @@ impl FilterBuilder {
         self.false_positive_probability = false_positive_probability;
     }
 
+    /// set a factor (at least `1.0`) by which the filter is overallocated for planned growth: it
+    /// is sized for `expected_elements * factor` elements, so the false positive probability
+    /// stays below `false_positive_probability` until it holds that many elements, see
+    /// [FilterBuilder::fpp_trajectory]. Has no effect once a filter was built.
+    ///
+    /// # Example:
+    /// ```rust
+    /// use fastbloom_rs::FilterBuilder;
+    ///
+    /// let mut builder = FilterBuilder::new(100_000, 0.01);
+    /// builder.headroom_factor(2.0);
+    /// assert_eq!(builder.capacity(), 200_000);
+    /// assert!(builder.fpp_at(100_000) < 0.001);
+    /// let bloom = builder.build_bloom_filter();
+    /// assert_eq!(bloom.remaining_capacity(), 200_000);
+    /// ```
+    pub fn headroom_factor(&mut self, factor: f64) {
+        assert!(factor >= 1.0, "headroom_factor must be at least 1.0!");
+        self.headroom_factor = factor;
+    }
+
+    /// Returns the number of elements the filter is sized for, `expected_elements` times the
+    /// [FilterBuilder::headroom_factor].
+    pub fn capacity(&self) -> u64 {
+        (self.expected_elements as f64 * self.headroom_factor).ceil() as u64
+    }
+
+    /// Returns the bit indices of `element` in a [BloomFilter] built from this configuration,
+    /// the same as [BloomFilter::hash_indices] without building the filter.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use fastbloom_rs::FilterBuilder;
+    ///
+    /// let mut builder = FilterBuilder::new(100_000, 0.01);
+    /// builder.seed(42);
+    /// let bloom = builder.build_bloom_filter();
+    /// assert!(builder.hash_indices(b"hello").eq(bloom.hash_indices(b"hello")));
+    /// ```
+    pub fn hash_indices(&self, element: &[u8]) -> impl Iterator<Item=u64> {
+        let mut config = self.clone();
+        config.complete();
+        Indices::new(element, &config)
+    }
+
+    /// Returns the false positive probability of the filter once it holds `elements` elements.
+    pub fn fpp_at(&self, elements: u64) -> f64 {
+        let mut config = self.clone();
+        config.complete();
+        expected_fpp(config.size, elements, config.hashes)
+    }
+
+    /// Returns the false positive probability after every `capacity / steps` elements, up to
+    /// the [FilterBuilder::capacity].
+    pub fn fpp_trajectory(&self, steps: u64) -> Vec<(u64, f64)> {
+        let capacity = self.capacity();
+        (1..=steps).map(|step| {
+            let elements = capacity * step / steps;
+            (elements, self.fpp_at(elements))
+        }).collect()
+    }
+
+    /// set the seed of the hash functions. All `k` indices of an element are derived from hashes
+    /// salted with it (see [IndexScheme]), so filters of distinct seeds set distinct bits for
+    /// the same keys. The seed is part of the serialized filters and of the [CompatibilityKey]:
+    /// filters are only compatible, and can only be merged, when they share the same seed.
+    ///
+    /// The seed is no secret: it is written into every serialized header, and the built-in hash
+    /// functions are not keyed, so it doesn't keep an attacker from crafting colliding keys. For
+    /// keys chosen by an attacker use `FilterBuilder::secret_key` of the `siphash` feature.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use fastbloom_rs::{FilterBuilder, Membership};
+    ///
+    /// let mut builder = FilterBuilder::new(100_000, 0.01);
+    /// builder.seed(0x7e4a47);
+    /// let mut tenant_a = builder.build_bloom_filter();
+    /// builder.seed(0x7e4a48);
+    /// let mut tenant_b = builder.build_bloom_filter();
+    /// tenant_a.add(b"hello");
+    /// tenant_b.add(b"hello");
+    /// assert_ne!(tenant_a.get_hash_indices(b"hello"), tenant_b.get_hash_indices(b"hello"));
+    /// assert_eq!(tenant_a.union(&tenant_b), false);
+    /// ```
+    pub fn seed(&mut self, seed: u64) {
+        self.seed = seed;
+    }
+
+    /// set how the bit indices are derived from the hash of an element, see [IndexScheme].
+    /// Filters are only compatible when they share the same scheme.
+    ///
+    /// # Example:
+    /// ```rust
+    /// use fastbloom_rs::{FilterBuilder, IndexScheme, Membership};
+    ///
+    /// let mut builder = FilterBuilder::new(100_000, 1e-8);
+    /// builder.index_scheme(IndexScheme::Extended);
+    /// let mut bloom = builder.build_bloom_filter();
+    /// bloom.add(b"hello");
+    /// assert_eq!(bloom.contains(b"hello"), true);
+    /// ```
+    pub fn index_scheme(&mut self, scheme: IndexScheme) {
+        self.index_scheme = scheme;
+    }
+
+    /// set the salts the `k` indices are derived from, one per hash function, to reproduce the
+    /// indices outside of this crate, e.g. in a SQL function or another language. Selects
+    /// [IndexScheme::Extended] and sets the number of hashes to the number of seeds: for a key
+    /// hashed to the halves `h1` (low) and `h2` (high) of xxh3-128 with [FilterBuilder::seed],
+    /// the `i`-th index is `(mix64(h1 + seeds[i]) ^ h2) % m` in wrapping 64-bit arithmetic, where
+    /// `mix64` is the splitmix64 finalizer (`z ^= z >> 30; z *= 0xbf58476d1ce4e5b9; z ^= z >> 27;
+    /// z *= 0x94d049bb133111eb; z ^= z >> 31`) and `m` is the size minus the canaries.
+    /// [Partitioned](FilterBuilder::partitioned) filters take `m` as the size of a partition and
+    /// add `i * m`. The default salts are `i * 0x9e3779b97f4a7c15`, see
+    /// [FilterBuilder::hash_seeds_in_use]. Filters are only compatible when they share the seeds.
+    /// Panics if `seeds` is empty.
+    ///
+    /// # Example:
+    /// ```rust
+    /// use fastbloom_rs::{FilterBuilder, Membership};
+    ///
+    /// let mut builder = FilterBuilder::new(100_000, 0.01);
+    /// builder.hash_seeds(&[11, 22, 33, 44, 55]);
+    /// let mut bloom = builder.build_bloom_filter();
+    /// bloom.add(b"hello");
+    /// assert_eq!(bloom.config().hashes, 5);
+    /// assert_eq!(bloom.config().hash_seeds_in_use(), Some(vec![11, 22, 33, 44, 55]));
+    /// assert_eq!(bloom.contains(b"hello"), true);
+    /// ```
+    pub fn hash_seeds(&mut self, seeds: &[u64]) {
+        assert!(!seeds.is_empty(), "hash_seeds needs at least one seed!");
+        self.index_scheme = IndexScheme::Extended;
+        self.hashes = seeds.len() as u32;
+        self.hash_seeds = Some(seeds.into());
+    }
+
+    /// Returns the salts of the indices of the filter, given or chosen by default, see
+    /// [FilterBuilder::hash_seeds]. `None` for the index schemes other than
+    /// [IndexScheme::Extended], which don't derive the indices from salts.
+    ///
+    /// # Example:
+    /// ```rust
+    /// use fastbloom_rs::{FilterBuilder, IndexScheme};
+    ///
+    /// let mut builder = FilterBuilder::from_size_and_hashes(1024, 3);
+    /// assert_eq!(builder.hash_seeds_in_use(), None);
+    /// builder.index_scheme(IndexScheme::Extended);
+    /// assert_eq!(builder.hash_seeds_in_use(),
+    ///            Some(vec![0, 0x9e3779b97f4a7c15, 0x3c6ef372fe94f82a]));
+    /// ```
+    pub fn hash_seeds_in_use(&self) -> Option<Vec<u64>> {
+        if self.index_scheme != IndexScheme::Extended {
+            return None;
+        }
+        let seeds = self.custom_hash_seeds().unwrap_or_default();
+        Some((0..self.hashes as usize)
+            .map(|i| seeds.get(i).copied().unwrap_or_else(|| default_hash_seed(i as u64)))
+            .collect())
+    }
+
+    /// The seeds given to [FilterBuilder::hash_seeds] which are in use, at most one per hash.
+    #[inline]
+    pub(crate) fn custom_hash_seeds(&self) -> Option<&[u64]> {
+        match &self.hash_seeds {
+            Some(seeds) if self.index_scheme == IndexScheme::Extended => {
+                Some(&seeds[..seeds.len().min(self.hashes as usize)])
+            }
+            _ => None,
+        }
+    }
+
+    /// set the hash function to the one registered with [crate::register_hasher] under `id`;
+    /// [crate::DEFAULT_HASHER] selects the built-in one. Filters are only compatible when they
+    /// share the same hasher.
+    pub fn hasher(&mut self, id: u32) {
+        self.hasher = HasherRef::resolve(id)
+            .unwrap_or_else(|| panic!("no hasher registered with id {id}!"));
+    }
+
+    /// set the hash function to one shipped with the crate, see [HashFamily]. Filters are only
+    /// compatible when they share the same hash family.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use fastbloom_rs::{FilterBuilder, HashFamily, Membership};
+    ///
+    /// let mut builder = FilterBuilder::new(100_000, 0.01);
+    /// builder.hash_family(HashFamily::Xxh3);
+    /// let mut bloom = builder.build_bloom_filter();
+    /// bloom.add(b"hello");
+    /// assert_eq!(bloom.contains(b"hello"), true);
+    /// assert_eq!(builder.hasher_id(), HashFamily::Xxh3.id());
+    /// ```
+    pub fn hash_family(&mut self, family: HashFamily) {
+        self.hasher = family.hasher_ref();
+    }
+
+    /// Returns the id of the hash function, see [FilterBuilder::hasher].
+    pub fn hasher_id(&self) -> u32 {
+        self.hasher.id
+    }
+
+    /// set the hash function to SipHash-1-3 keyed with a 128-bit secret `key`, for filters of
+    /// keys chosen by an attacker (e.g. a spam filter fed with user supplied strings): without
+    /// the key, crafting keys which collide in the filter or are false positives for everybody
+    /// is as hard as breaking SipHash. Unlike [HashFamily::SipHash13], whose key is the 64-bit
+    /// seed written into every serialized header, the key is never stored.
+    ///
+    /// Only filters built with the same key can be merged: the [CompatibilityKey] of a keyed
+    /// filter carries a check value derived from the key, so unions and intersections with
+    /// unkeyed filters or filters of another key fail with a [MismatchError] (or return
+    /// [false]). Serialized keyed filters record the hasher id but not the key, so loaders
+    /// reject them instead of answering with the wrong hash; rebuild them from the keys, or
+    /// keep their bits with [BloomFilter::get_u8_array] and the key out of band. Needs the
+    /// `siphash` feature.
+    ///
+    /// [MismatchError]: crate::MismatchError
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use fastbloom_rs::{FilterBuilder, Membership};
+    ///
+    /// let mut builder = FilterBuilder::new(100_000, 0.01);
+    /// builder.secret_key(*b"0123456789abcdef");
+    /// let mut bloom = builder.build_bloom_filter();
+    /// bloom.add(b"hello");
+    /// assert_eq!(bloom.contains(b"hello"), true);
+    /// assert!(builder.is_keyed());
+    ///
+    /// let unkeyed = FilterBuilder::new(100_000, 0.01).build_bloom_filter();
+    /// assert!(bloom.unioned(&unkeyed).is_err());
+    /// ```
+    #[cfg(feature = "siphash")]
+    pub fn secret_key(&mut self, key: [u8; 16]) {
+        self.hasher = crate::family::keyed_hasher_ref(key);
+    }
+
+    /// set the hashing to the one of the `bloomfilter` crate with the keys `sip_keys` (its
+    /// `Bloom::sip_keys`): two SipHash-1-3 hashes and [IndexScheme::BloomfilterCrate], so a
+    /// filter built from this builder with the size and hashes of a `Bloom` answers like it, see
+    /// [BloomFilter::from_bloomfilter]. The keys are secret like the one of
+    /// [FilterBuilder::secret_key] and not stored either. Needs the `siphash` feature.
+    #[cfg(feature = "siphash")]
+    pub fn bloomfilter_sip_keys(&mut self, sip_keys: [(u64, u64); 2]) {
+        self.hasher = crate::family::bloomfilter_hasher_ref(sip_keys);
+        self.index_scheme = IndexScheme::BloomfilterCrate;
+    }
+
+    /// Returns [true] if the hash function is keyed with a secret key, see
+    /// [FilterBuilder::secret_key].
+    pub fn is_keyed(&self) -> bool {
+        self.hasher.key_check != 0
+    }
+
+    /// set a hint of the typical key length in bytes. Any non-zero hint switches to a hashing
+    /// which dispatches on the key length (up to 8, 16, 64 bytes and longer), notably faster when
+    /// most keys are short ids. The default `0` keeps the hashing of prior releases. Filters are
+    /// only compatible when both or none of them were given a hint.
+    ///
+    /// # Example:
+    /// ```rust
+    /// use fastbloom_rs::{FilterBuilder, Membership};
+    ///
+    /// let mut builder = FilterBuilder::new(100_000, 0.01);
+    /// builder.expected_key_len(8);
+    /// let mut bloom = builder.build_bloom_filter();
+    /// bloom.add(&42u64.to_le_bytes());
+    /// assert_eq!(bloom.contains(&42u64.to_le_bytes()), true);
+    /// ```
+    pub fn expected_key_len(&mut self, len: usize) {
+        self.expected_key_len = len;
+    }
+
+    /// Whether the hashing specialized on the key length is used.
+    #[inline]
+    pub(crate) fn key_len_hashing(&self) -> bool {
+        self.expected_key_len != 0
+    }
+
+    /// set the number of canary bits: the last `canaries` bits of the filter are excluded from
+    /// the index range, so hashing never sets them and a set canary means the memory of the
+    /// filter was corrupted or written out of range. Check them periodically with
+    /// [BloomFilter::check_canaries](crate::BloomFilter::check_canaries). Filters are only
+    /// compatible when they have the same number of canaries.
+    ///
+    /// # Example:
+    /// ```rust
+    /// use fastbloom_rs::{FilterBuilder, Membership};
+    ///
+    /// let mut builder = FilterBuilder::new(100_000, 0.01);
+    /// builder.canaries(16);
+    /// let mut bloom = builder.build_bloom_filter();
+    /// bloom.add(b"hello");
+    /// assert_eq!(bloom.check_canaries(), true);
+    /// ```
+    pub fn canaries(&mut self, canaries: u32) {
+        self.canaries = canaries;
+    }
+
+    /// set whether the bits are partitioned into one slice of `size / hashes` bits per hash
+    /// function, the `i`-th index of an element lying in the `i`-th slice. Every element then has
+    /// exactly `hashes` distinct positions, even for adversarial keys whose indices would collide,
+    /// and each index is computed modulo the slice. The false positive probability is about the
+    /// same as without partitions. The bits behind the last whole slice stay unused, and
+    /// [BloomFilter::shrink_to_fit](crate::BloomFilter::shrink_to_fit) leaves partitioned filters
+    /// as they are. Filters are only compatible when both or none of them are partitioned.
+    ///
+    /// **Reference**: Chang, F., Feng, W. C., & Li, K. (2004, March). Approximate caches for packet
+    /// classification. In INFOCOM 2004. Twenty-third AnnualJoint Conference of the IEEE Computer
+    /// and Communications Societies (Vol. 4, pp. 2196-2207). IEEE.
+    ///
+    /// # Example:
+    /// ```rust
+    /// use fastbloom_rs::{FilterBuilder, Membership};
+    ///
+    /// let mut builder = FilterBuilder::new(100_000, 0.01);
+    /// builder.partitioned(true);
+    /// let mut bloom = builder.build_bloom_filter();
+    /// bloom.add(b"hello");
+    /// let slice = bloom.config().size / bloom.config().hashes as u64;
+    /// let indices = bloom.get_hash_indices(b"hello");
+    /// assert!(indices.iter().enumerate().all(|(i, index)| index / slice == i as u64));
+    /// ```
+    pub fn partitioned(&mut self, partitioned: bool) {
+        self.partitioned = partitioned;
+    }
+
+    /// Use for BlockedBloomFilter. set the size of the blocks, see [BlockSize]. Larger blocks
+    /// lower the false positive probability but touch more memory per query.
+    pub fn block_size(&mut self, block_size: BlockSize) {
+        self.block_size = block_size;
+    }
+
+    /// Returns a copy of this configuration which only keeps what is needed to lay out a filter
+    /// (size and hashes): the seed is reset to `0` and the expected elements and false positive
+    /// probability are re-derived from the size. Use it when sharing a filter externally; the
+    /// recipient needs the seed out of band to query the filter.
+    ///
+    /// # Example:
+    /// ```rust
+    /// use fastbloom_rs::FilterBuilder;
+    ///
+    /// let mut builder = FilterBuilder::new(100_000, 0.01);
+    /// builder.seed(0x5eed);
+    /// let bloom = builder.build_bloom_filter();
+    /// let shared = bloom.config().sanitized();
+    /// assert_eq!(shared.seed, 0);
+    /// assert_eq!(shared.size, bloom.config().size);
+    /// ```
+    pub fn sanitized(&self) -> FilterBuilder {
+        let mut config = FilterBuilder::from_size_and_hashes(self.size, self.hashes);
+        config.index_scheme = self.index_scheme;
+        config.hash_seeds = self.hash_seeds.clone();
+        config.hasher = self.hasher.clone();
+        config.expected_key_len = self.expected_key_len;
+        config.block_size = self.block_size;
+        config.enable_repeat_insert = self.enable_repeat_insert;
+        config.enable_conservative_update = self.enable_conservative_update;
+        config.enable_constant_time = self.enable_constant_time;
+        config.canaries = self.canaries;
+        config.partitioned = self.partitioned;
+        config
+    }
+
     /// Use for CountingBloomFilter.
     ///
     /// # Example:
@@ -149,6 +852,47 @@ impl FilterBuilder {
         self.enable_repeat_insert = enable;
     }
 
+    /// Use for CountingBloomFilter. When enabled, `add` only increments the counters which are
+    /// equal to the current minimum of the element's counters (conservative update). This
+    /// reduces the overestimation of [CountingBloomFilter::estimate_count] at no memory cost,
+    /// but `remove` may then introduce false negatives, so it is meant for frequency estimation
+    /// workloads without deletions.
+    ///
+    /// # Example:
+    /// ```rust
+    /// use fastbloom_rs::FilterBuilder;
+    ///
+    /// let mut builder = FilterBuilder::new(100_000, 0.01);
+    /// builder.enable_conservative_update(true);
+    /// let mut cbf = builder.build_counting_bloom_filter::<Vec<usize>>(());
+    /// cbf.add(b"hello");
+    /// cbf.add(b"hello");
+    /// assert_eq!(cbf.estimate_count(b"hello"), 2);
+    /// ```
+    pub fn enable_conservative_update(&mut self, enable: bool) {
+        self.enable_conservative_update = enable;
+    }
+
+    /// When enabled, `contains` always probes all `k` positions and combines the probed bits
+    /// without branching, so the time taken does not leak how many positions matched. This is
+    /// meant for side-channel-sensitive filtering; note that the memory access pattern still
+    /// depends on the element.
+    ///
+    /// # Example:
+    /// ```rust
+    /// use fastbloom_rs::{FilterBuilder, Membership};
+    ///
+    /// let mut builder = FilterBuilder::new(100_000, 0.01);
+    /// builder.enable_constant_time(true);
+    /// let mut bloom = builder.build_bloom_filter();
+    /// bloom.add(b"hello");
+    /// assert_eq!(bloom.contains(b"hello"), true);
+    /// assert_eq!(bloom.contains(b"world"), false);
+    /// ```
+    pub fn enable_constant_time(&mut self, enable: bool) {
+        self.enable_constant_time = enable;
+    }
+
     /// set  the size of the bloom filter in bits.
     fn size(&mut self, size: u64) {
         assert_eq!(size & SUFFIX as u64, 0);
@@ -159,14 +903,143 @@ impl FilterBuilder {
     /// Checks if all necessary parameters were set and tries to infer optimal parameters (e.g.
     /// size and hashes from given expected_elements (`n`) and falsePositiveProbability (`p`)).
     /// This is done automatically.
+    ///
+    /// Panics if the filter is too large for this platform, see [FilterBuilder::checked_size].
     pub(crate) fn complete(&mut self) {
+        self.complete_for(FilterLayout::Standard);
+    }
+
+    /// Like [FilterBuilder::complete], checking the size against the limit of `layout`.
+    pub(crate) fn complete_for(&mut self, layout: FilterLayout) {
+        if let Err(err) = self.try_complete(layout) {
+            panic!("{err}");
+        }
+    }
+
+    /// Like [FilterBuilder::complete_for], returning the error instead of panicking.
+    fn try_complete(&mut self, layout: FilterLayout) -> Result<(), BuildError> {
+        self.complete_size(layout)?;
+        self.check_shape()
+    }
+
+    /// Infers the size and hashes if they are not set and checks the size against the limit of
+    /// `layout`.
+    fn complete_size(&mut self, layout: FilterLayout) -> Result<(), SizeError> {
         if !self.done {
             if self.size == 0 {
-                self.size = optimal_m(self.expected_elements, self.false_positive_probability);
-                self.hashes = optimal_k(self.expected_elements, self.size);
+                let capacity = self.capacity();
+                let size = optimal_m(capacity, self.false_positive_probability)
+                    .ok_or_else(|| self.size_error(None, layout))?;
+                self.size = size;
+                self.hashes = match &self.hash_seeds {
+                    Some(seeds) => seeds.len() as u32,
+                    None => optimal_k(capacity, self.size),
+                };
             }
             self.done = true;
         }
+        if self.size > max_size(layout) {
+            return Err(self.size_error(Some(self.size), layout));
+        }
+        Ok(())
+    }
+
+    /// Checks the parameters which [FilterBuilder::complete] builds a degenerate filter from.
+    fn check_parameters(&self) -> Result<(), BuildError> {
+        if self.size == 0 {
+            let p = self.false_positive_probability;
+            if self.expected_elements == 0 {
+                return Err(BuildError::ZeroExpectedElements);
+            }
+            if !(p > 0.0 && p < 1.0) {
+                return Err(BuildError::InvalidFalsePositiveProbability(p));
+            }
+            if self.headroom_factor.is_nan() || self.headroom_factor < 1.0 {
+                return Err(BuildError::InvalidHeadroomFactor(self.headroom_factor));
+            }
+        } else if self.hashes == 0 {
+            return Err(BuildError::ZeroHashes);
+        }
+        Ok(())
+    }
+
+    /// Checks the canaries and partitions against the size, if it is known.
+    fn check_shape(&self) -> Result<(), BuildError> {
+        if self.size != 0 && self.canaries as u64 >= self.size {
+            return Err(BuildError::TooManyCanaries);
+        }
+        if self.partitioned && self.size != 0
+            && self.size - (self.canaries as u64) < self.hashes as u64 {
+            return Err(BuildError::EmptyPartitions);
+        }
+        Ok(())
+    }
+
+    fn size_error(&self, requested_size: Option<u64>, layout: FilterLayout) -> SizeError {
+        let max_size = max_size(layout);
+        let p = self.false_positive_probability;
+        // leave room for float rounding and the rounding up of optimal_m.
+        let bits = max_size as f64 * (1.0 - 1e-12) - (SUFFIX + 1) as f64;
+        let elements = bits * 2f64.ln().powi(2) / -p.ln() / self.headroom_factor;
+        SizeError {
+            requested_size,
+            max_size,
+            max_expected_elements: elements.floor() as u64,
+            false_positive_probability: p,
+        }
+    }
+
+    /// Returns the size in bits of a [BloomFilter] built from this configuration, or a
+    /// [SizeError] with the largest supported number of elements if its bit vector would not be
+    /// addressable on this platform.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use fastbloom_rs::FilterBuilder;
+    ///
+    /// let err = FilterBuilder::new(u64::MAX, 0.01).checked_size().unwrap_err();
+    /// let builder = FilterBuilder::new(err.max_expected_elements, 0.01);
+    /// assert!(builder.checked_size().unwrap() <= err.max_size);
+    /// ```
+    pub fn checked_size(&self) -> Result<u64, SizeError> {
+        let mut config = self.clone();
+        config.complete_size(FilterLayout::Standard)?;
+        Ok(config.size)
+    }
+
+    /// Like [FilterBuilder::build_bloom_filter], but returns a [BuildError] instead of panicking
+    /// on an invalid configuration: zero expected elements, a false positive probability outside
+    /// of `(0, 1)`, a filter too large for this platform ([BuildError::TooLarge] with the
+    /// [SizeError]) and so on. Lets applications report configurations of their users as errors.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use fastbloom_rs::{BuildError, FilterBuilder, Membership};
+    ///
+    /// let mut bloom = FilterBuilder::new(10_000, 0.01).try_build_bloom_filter().unwrap();
+    /// bloom.add(b"hello");
+    /// assert_eq!(bloom.contains(b"hello"), true);
+    ///
+    /// let err = FilterBuilder::new(10_000, 1.5).try_build_bloom_filter().unwrap_err();
+    /// assert_eq!(err, BuildError::InvalidFalsePositiveProbability(1.5));
+    /// let err = FilterBuilder::new(u64::MAX, 1e-9).try_build_bloom_filter().unwrap_err();
+    /// assert!(matches!(err, BuildError::TooLarge(_)));
+    /// ```
+    pub fn try_build_bloom_filter(&mut self) -> Result<BloomFilter, BuildError> {
+        self.check_parameters()?;
+        self.try_complete(FilterLayout::Standard)?;
+        Ok(BloomFilter::new(self.clone()))
+    }
+
+    /// Like [FilterBuilder::build_counting_bloom_filter], returning a [BuildError] instead of
+    /// panicking on an invalid configuration, see [FilterBuilder::try_build_bloom_filter].
+    pub fn try_build_counting_bloom_filter<S: Storage>(&mut self, init: S::Init)
+                                                       -> Result<CountingBloomFilter<S>, BuildError> {
+        self.check_parameters()?;
+        self.try_complete(FilterLayout::Counting(4))?;
+        Ok(CountingBloomFilter::new(self.clone(), init))
     }
 
     /// Constructs a Bloom filter using the specified parameters and computing missing parameters
@@ -176,31 +1049,585 @@ impl FilterBuilder {
         BloomFilter::new(self.clone())
     }
 
+    /// Constructs a Bloom filter on a storage of type `S` using the specified parameters and
+    /// computing missing parameters if possible (e.g. the optimal Bloom filter bit size).
+    pub fn build_bloom_filter_in<S: Storage>(&mut self, init: S::Init) -> BloomFilter<S> {
+        self.complete();
+        BloomFilter::new_in(self.clone(), init)
+    }
+
+    /// Constructs a blocked Bloom filter using the specified parameters and computing missing
+    /// parameters if possible (e.g. the optimal Bloom filter bit size).
+    pub fn build_blocked_bloom_filter(&mut self) -> BlockedBloomFilter {
+        self.complete();
+        BlockedBloomFilter::new(self.clone())
+    }
+
+    /// Constructs a Parquet [SplitBlockBloomFilter] for [FilterBuilder::capacity] distinct
+    /// values.
+    pub fn build_split_block_bloom_filter(&mut self) -> SplitBlockBloomFilter {
+        SplitBlockBloomFilter::new(self.clone())
+    }
+
+    /// Constructs a [ShardedBloomFilter] of `shards` shards, each sized for its share of the
+    /// elements of this builder.
+    pub fn build_sharded_bloom_filter(&mut self, shards: usize) -> ShardedBloomFilter {
+        ShardedBloomFilter::new(self.clone(), shards)
+    }
+
+    /// Constructs a [DLeftCountingFilter] for [FilterBuilder::capacity] elements.
+    pub fn build_d_left_counting_filter(&mut self) -> DLeftCountingFilter {
+        DLeftCountingFilter::new(self.clone())
+    }
+
+    /// Constructs a [DlBloomFilter] with its bits split into `regions` regions.
+    pub fn build_dl_bloom_filter(&mut self, regions: u64) -> DlBloomFilter {
+        DlBloomFilter::new(self.clone(), regions)
+    }
+
+    /// Constructs an [AgingBloomFilter] of `generations` generations, each sized by this builder.
+    pub fn build_aging_bloom_filter(&mut self, generations: usize) -> AgingBloomFilter {
+        AgingBloomFilter::new(self.clone(), generations)
+    }
+
+    /// Constructs a [SlidingBloomFilter] of the items inserted within `window`, in `slices`
+    /// slices each sized by this builder.
+    #[cfg(feature = "std")]
+    pub fn build_sliding_bloom_filter(&mut self, window: Duration, slices: usize)
+                                      -> SlidingBloomFilter {
+        SlidingBloomFilter::new(window, slices, self.clone())
+    }
+
+    /// Constructs an [AsyncBloomFilter] sized by this builder over the words of `storage`.
+    #[cfg(feature = "async")]
+    pub fn build_async_bloom_filter<S: AsyncStorage>(&mut self, storage: S)
+                                                     -> AsyncBloomFilter<S> {
+        AsyncBloomFilter::new(self.clone(), storage)
+    }
+
+    /// Constructs a [DoubleBufferedBloomFilter] of two filters, each sized by this builder.
+    pub fn build_double_buffered_bloom_filter(&mut self) -> DoubleBufferedBloomFilter {
+        DoubleBufferedBloomFilter::new(self.clone())
+    }
+
+    /// Constructs a [QuarantineFilter] with a filter sized by this builder and an exact set of
+    /// up to `capacity` recently added keys in front of it.
+    pub fn build_quarantine_filter(&mut self, capacity: usize) -> QuarantineFilter {
+        QuarantineFilter::new(self.clone(), capacity)
+    }
+
+    /// Constructs a [TieredBloomFilter] with a back filter sized by this builder and a front
+    /// filter for `front_elements` hot elements.
+    pub fn build_tiered_bloom_filter(&mut self, front_elements: u64) -> TieredBloomFilter {
+        TieredBloomFilter::new(self.clone(), front_elements)
+    }
+
+    /// Constructs a [FingerprintTable] for the expected number of elements, using the hashing
+    /// configured in this builder.
+    pub fn build_fingerprint_table(&mut self) -> FingerprintTable {
+        FingerprintTable::new(self.clone())
+    }
+
+    /// Constructs a [BinaryFuseFilter] of a fixed set of keys, using the hashing configured in
+    /// this builder.
+    pub fn build_binary_fuse_filter<F: FuseFingerprint, K: AsRef<[u8]>>(
+        &mut self, keys: impl IntoIterator<Item=K>) -> BinaryFuseFilter<F> {
+        BinaryFuseFilter::from_keys(self.clone(), keys)
+    }
+
+    /// Constructs a [BloomierFilter] of a fixed map from keys to small values, using the hashing
+    /// configured in this builder.
+    pub fn build_bloomier_filter<V: FuseFingerprint, F: FuseFingerprint, K: AsRef<[u8]>>(
+        &mut self, entries: impl IntoIterator<Item=(K, V)>) -> BloomierFilter<V, F> {
+        BloomierFilter::from_entries(self.clone(), entries)
+    }
+
+    /// Constructs a [RibbonFilter] of a fixed set of keys at the false positive probability and
+    /// with the hashing configured in this builder.
+    pub fn build_ribbon_filter<K: AsRef<[u8]>>(
+        &mut self, keys: impl IntoIterator<Item=K>) -> RibbonFilter {
+        RibbonFilter::from_keys(self.clone(), keys)
+    }
+
+    /// Constructs a [GolombCodedSet] of a fixed set of keys at the false positive probability
+    /// and with the hashing configured in this builder.
+    pub fn build_golomb_coded_set<K: AsRef<[u8]>>(
+        &mut self, keys: impl IntoIterator<Item=K>) -> GolombCodedSet {
+        GolombCodedSet::from_keys(self.clone(), keys)
+    }
+
+    /// Constructs a [FilterCascade] of the `included` keys, without false positives for the
+    /// `excluded` keys, at the false positive probability and with the hashing configured in this
+    /// builder.
+    pub fn build_filter_cascade<K: AsRef<[u8]>, E: AsRef<[u8]>>(
+        &mut self, included: impl IntoIterator<Item=K>,
+        excluded: impl IntoIterator<Item=E>) -> FilterCascade {
+        FilterCascade::from_keys(self.clone(), included, excluded)
+    }
+
+    /// Constructs a [QuotientFilter] with 16-bit slots for the expected number of elements,
+    /// using the hashing configured in this builder.
+    pub fn build_quotient_filter(&mut self) -> QuotientFilter {
+        QuotientFilter::new(self.clone())
+    }
+
+    /// Constructs a [QuotientFilter] with slots of `W` bits (8 or 16), see
+    /// [FilterBuilder::build_quotient_filter].
+    pub fn build_quotient_filter_with_width<const W: usize>(&mut self) -> QuotientFilter<W> {
+        QuotientFilter::new(self.clone())
+    }
+
+    /// Constructs a [CountingQuotientFilter] with 16-bit slots for the expected number of
+    /// distinct elements, using the hashing configured in this builder.
+    pub fn build_counting_quotient_filter(&mut self) -> CountingQuotientFilter {
+        CountingQuotientFilter::new(self.clone())
+    }
+
+    /// Constructs a [CountingQuotientFilter] with slots of `W` bits (8 or 16), see
+    /// [FilterBuilder::build_counting_quotient_filter].
+    pub fn build_counting_quotient_filter_with_width<const W: usize>(
+        &mut self) -> CountingQuotientFilter<W> {
+        CountingQuotientFilter::new(self.clone())
+    }
+
+    /// Constructs a [CuckooFilter] for the expected number of elements at the false positive
+    /// probability, using the hashing configured in this builder.
+    pub fn build_cuckoo_filter(&mut self) -> CuckooFilter {
+        CuckooFilter::new(self.clone())
+    }
+
     /// Constructs a Counting Bloom filter using the specified parameters and computing missing parameters
     /// if possible (e.g. the optimal Bloom filter bit size).
     pub fn build_counting_bloom_filter<S: Storage>(&mut self, init: S::Init) -> CountingBloomFilter<S> {
-        self.complete();
+        self.complete_for(FilterLayout::Counting(4));
         CountingBloomFilter::new(self.clone(), init)
     }
 
+    /// Constructs a Counting Bloom filter with counters of `W` bits (2, 4, 8, 16, 32 or 64)
+    /// instead of 4, e.g. 8 or 16 bits for workloads with hot keys which would saturate 4-bit
+    /// counters, see also [CountingBloomFilter::with_wide_counters].
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use fastbloom_rs::{CountingBloomFilter, FilterBuilder};
+    ///
+    /// let mut builder = FilterBuilder::new(1000, 0.01);
+    /// let mut cbf: CountingBloomFilter<Vec<usize>, 8> =
+    ///     builder.build_counting_bloom_filter_with_width(());
+    /// for _ in 0..100 {
+    ///     cbf.add(b"hot");
+    /// }
+    /// assert_eq!(cbf.estimate_count(b"hot"), 100);
+    /// ```
+    pub fn build_counting_bloom_filter_with_width<S: Storage, const W: usize>(
+        &mut self,
+        init: S::Init,
+    ) -> CountingBloomFilter<S, W> {
+        self.complete_for(FilterLayout::Counting(W as u32));
+        CountingBloomFilter::with_width(self.clone(), init)
+    }
+
+    /// Returns the memory in bytes taken by the storage of a filter in `layout` built from this
+    /// configuration: a bit per position for [FilterLayout::Standard] and
+    /// [FilterLayout::Blocked], the counter width for [FilterLayout::Counting].
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use fastbloom_rs::{FilterBuilder, FilterLayout};
+    ///
+    /// let builder = FilterBuilder::new(100_000, 0.01);
+    /// let bits = builder.memory_usage(FilterLayout::Standard);
+    /// assert_eq!(builder.memory_usage(FilterLayout::Counting(4)), bits * 4);
+    /// assert_eq!(builder.memory_usage(FilterLayout::Counting(16)), bits * 16);
+    /// ```
+    pub fn memory_usage(&self, layout: FilterLayout) -> u64 {
+        let mut config = self.clone();
+        config.complete_for(layout);
+        match layout {
+            FilterLayout::Standard | FilterLayout::Blocked(_) => config.size / 8,
+            FilterLayout::Counting(bits) => config.size * bits as u64 / 8,
+        }
+    }
+
     /// Checks whether a configuration is compatible to another configuration based on the size of
     /// the Bloom filter and its hash functions.
     pub(crate) fn is_compatible_to(&self, other: &FilterBuilder) -> bool {
-        self.size == other.size && self.hashes == other.hashes
+        self.compatibility_key(FilterLayout::Standard)
+            == other.compatibility_key(FilterLayout::Standard)
+    }
+
+    /// Returns the [CompatibilityKey] of a filter in `layout` built from this configuration.
+    pub(crate) fn compatibility_key(&self, layout: FilterLayout) -> CompatibilityKey {
+        CompatibilityKey {
+            size: self.size,
+            hashes: self.hashes,
+            seed: self.seed,
+            hasher: self.hasher.id,
+            key_check: self.hasher.key_check,
+            key_len_hashing: self.key_len_hashing(),
+            index_scheme: self.index_scheme,
+            // the seeds in use, the given ones padded with the defaults to one per hash
+            hash_seeds: match self.custom_hash_seeds().and(self.hash_seeds_in_use()) {
+                Some(seeds) if !is_default_hash_seeds(&seeds) => hash_seeds_digest(&seeds),
+                _ => 0,
+            },
+            canaries: self.canaries,
+            partitioned: self.partitioned,
+            layout,
+        }
+    }
+}
+
+/// States of a [TypedBuilder].
+pub mod state {
+    mod sealed {
+        pub trait Sealed {}
+    }
+
+    /// No size was given yet.
+    #[derive(Clone, Copy, Debug)]
+    pub struct Unsized;
+    /// Sized from the expected elements and false positive probability.
+    #[derive(Clone, Copy, Debug)]
+    pub struct Estimated;
+    /// Sized from an exact number of bits and hashes.
+    #[derive(Clone, Copy, Debug)]
+    pub struct Exact;
+    /// Blocked layout, see [crate::BlockedBloomFilter].
+    #[derive(Clone, Copy, Debug)]
+    pub struct Blocked;
+    /// Counting layout, see [crate::CountingBloomFilter].
+    #[derive(Clone, Copy, Debug)]
+    pub struct Counting;
+
+    /// States in which the hashing can be configured.
+    pub trait Configured: sealed::Sealed {}
+    /// States from which a plain [crate::BloomFilter] can be built.
+    pub trait Plain: Configured {}
+
+    impl sealed::Sealed for Estimated {}
+    impl sealed::Sealed for Exact {}
+    impl sealed::Sealed for Blocked {}
+    impl sealed::Sealed for Counting {}
+    impl Configured for Estimated {}
+    impl Configured for Exact {}
+    impl Configured for Blocked {}
+    impl Configured for Counting {}
+    impl Plain for Estimated {}
+    impl Plain for Exact {}
+}
+
+/// [FilterBuilder] which tracks in its type what was configured, so invalid configurations fail
+/// to compile instead of panicking or being silently adjusted: a filter can only be built once
+/// it is sized, the blocked layout needs a size it may round up (not an exact one), and the
+/// counting options only exist for counting filters.
+///
+/// # Examples
+///
+/// ```rust
+/// use fastbloom_rs::{BlockSize, FilterBuilder, Membership};
+///
+/// let mut bloom = FilterBuilder::typed()
+///     .expected_elements(100_000, 0.01)
+///     .seed(7)
+///     .blocked(BlockSize::Sector)
+///     .build_blocked_bloom_filter();
+/// bloom.add(b"hello");
+/// assert_eq!(bloom.contains(b"hello"), true);
+///
+/// let mut cbf = FilterBuilder::typed()
+///     .size_and_hashes(1 << 16, 4)
+///     .counting()
+///     .enable_repeat_insert(false)
+///     .build_counting_bloom_filter::<Vec<usize>>(());
+/// cbf.add(b"hello");
+/// ```
+///
+/// An exactly sized filter can't use the blocked layout:
+///
+/// ```compile_fail
+/// use fastbloom_rs::{BlockSize, FilterBuilder};
+///
+/// let bloom = FilterBuilder::typed()
+///     .size_and_hashes(1 << 16, 4)
+///     .blocked(BlockSize::CacheLine)
+///     .build_blocked_bloom_filter();
+/// ```
+#[derive(Clone)]
+#[derive(Debug)]
+pub struct TypedBuilder<State> {
+    config: FilterBuilder,
+    state: PhantomData<State>,
+}
+
+impl<State> TypedBuilder<State> {
+    fn into_state<Next>(self) -> TypedBuilder<Next> {
+        TypedBuilder { config: self.config, state: PhantomData }
+    }
+}
+
+impl TypedBuilder<state::Unsized> {
+    /// Sizes the filter for `expected_elements` with the tolerable false positive probability,
+    /// see [FilterBuilder::new].
+    pub fn expected_elements(mut self, expected_elements: u64, false_positive_probability: f64)
+                             -> TypedBuilder<state::Estimated> {
+        FilterBuilder::expected_elements(&mut self.config, expected_elements);
+        FilterBuilder::false_positive_probability(&mut self.config, false_positive_probability);
+        self.config.complete();
+        self.into_state()
+    }
+
+    /// Sizes the filter to exactly `size` bits and `hashes` hash functions, see
+    /// [FilterBuilder::from_size_and_hashes].
+    pub fn size_and_hashes(self, size: u64, hashes: u32) -> TypedBuilder<state::Exact> {
+        assert!(hashes > 0, "hashes must larger than 0!");
+        let mut config = FilterBuilder::from_size_and_hashes(size, hashes);
+        config.size(size);
+        TypedBuilder { config, state: PhantomData }
+    }
+}
+
+impl<State: state::Configured> TypedBuilder<State> {
+    /// see [FilterBuilder::seed].
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.config.seed(seed);
+        self
+    }
+
+    /// see [FilterBuilder::index_scheme].
+    pub fn index_scheme(mut self, scheme: IndexScheme) -> Self {
+        self.config.index_scheme(scheme);
+        self
+    }
+
+    /// see [FilterBuilder::hash_seeds].
+    pub fn hash_seeds(mut self, seeds: &[u64]) -> Self {
+        self.config.hash_seeds(seeds);
+        self
+    }
+
+    /// see [FilterBuilder::expected_key_len].
+    pub fn expected_key_len(mut self, len: usize) -> Self {
+        self.config.expected_key_len(len);
+        self
+    }
+
+    /// see [FilterBuilder::enable_constant_time].
+    pub fn enable_constant_time(mut self, enable: bool) -> Self {
+        self.config.enable_constant_time(enable);
+        self
+    }
+
+    /// see [FilterBuilder::partitioned].
+    pub fn partitioned(mut self, partitioned: bool) -> Self {
+        self.config.partitioned(partitioned);
+        self
+    }
+
+    /// Returns the configuration built so far.
+    pub fn config(&self) -> &FilterBuilder {
+        &self.config
+    }
+
+    /// Unwraps the untyped [FilterBuilder].
+    pub fn into_builder(self) -> FilterBuilder {
+        self.config
+    }
+}
+
+impl<State: state::Plain> TypedBuilder<State> {
+    /// Switches to the counting layout.
+    pub fn counting(self) -> TypedBuilder<state::Counting> {
+        self.into_state()
+    }
+
+    /// see [FilterBuilder::build_bloom_filter].
+    pub fn build_bloom_filter(self) -> BloomFilter {
+        BloomFilter::new(self.config)
+    }
+
+    /// see [FilterBuilder::build_bloom_filter_in].
+    pub fn build_bloom_filter_in<S: Storage>(self, init: S::Init) -> BloomFilter<S> {
+        BloomFilter::new_in(self.config, init)
+    }
+}
+
+impl TypedBuilder<state::Estimated> {
+    /// Switches to the blocked layout with blocks of `block_size`, see [FilterBuilder::block_size].
+    pub fn blocked(mut self, block_size: BlockSize) -> TypedBuilder<state::Blocked> {
+        self.config.block_size(block_size);
+        self.into_state()
+    }
+}
+
+impl TypedBuilder<state::Blocked> {
+    /// see [FilterBuilder::build_blocked_bloom_filter].
+    pub fn build_blocked_bloom_filter(self) -> BlockedBloomFilter {
+        BlockedBloomFilter::new(self.config)
+    }
+}
+
+impl TypedBuilder<state::Counting> {
+    /// see [FilterBuilder::enable_repeat_insert].
+    pub fn enable_repeat_insert(mut self, enable: bool) -> Self {
+        self.config.enable_repeat_insert(enable);
+        self
+    }
+
+    /// see [FilterBuilder::enable_conservative_update].
+    pub fn enable_conservative_update(mut self, enable: bool) -> Self {
+        self.config.enable_conservative_update(enable);
+        self
+    }
+
+    /// see [FilterBuilder::build_counting_bloom_filter].
+    pub fn build_counting_bloom_filter<S: Storage>(self, init: S::Init) -> CountingBloomFilter<S> {
+        CountingBloomFilter::new(self.config, init)
+    }
+
+    /// see [FilterBuilder::build_counting_bloom_filter_with_width].
+    pub fn build_counting_bloom_filter_with_width<S: Storage, const W: usize>(
+        self,
+        init: S::Init,
+    ) -> CountingBloomFilter<S, W> {
+        CountingBloomFilter::with_width(self.config, init)
     }
 }
 
 #[test]
 fn optimal_test() {
-    let m = optimal_m(100_000_000, 0.01);
+    let m = optimal_m(100_000_000, 0.01).unwrap();
     let k = optimal_k(100_000_000, m);
     let n = optimal_n(k, m);
-    let p = optimal_p(k, m, n);
+    let p = expected_fpp(m, n, k);
     println!("{m} {k} {n} {p}");
     assert_eq!(m, 958505856);
     assert_eq!(k, 7)
 }
 
+#[test]
+fn checked_size_test() {
+    assert_eq!(optimal_m(u64::MAX, 1e-9), None);
+    let err = FilterBuilder::new(u64::MAX, 0.01).checked_size().unwrap_err();
+    assert_eq!(err.requested_size, None);
+    assert!(err.to_string().contains("elements are supported"));
+
+    let mut builder = FilterBuilder::new(err.max_expected_elements, 0.01);
+    assert!(builder.checked_size().unwrap() <= err.max_size);
+    builder.headroom_factor(2.0);
+    let err = builder.checked_size().unwrap_err();
+    assert!(err.max_expected_elements <= builder.expected_elements / 2);
+
+    let builder = FilterBuilder::from_size_and_hashes(MASK, 3);
+    assert_eq!(builder.checked_size().is_ok(), max_size(FilterLayout::Standard) == MASK);
+}
+
+#[test]
+fn try_build_test() {
+    let err = |mut builder: FilterBuilder| builder.try_build_bloom_filter().unwrap_err();
+    assert_eq!(err(FilterBuilder::new(0, 0.01)), BuildError::ZeroExpectedElements);
+    for p in [0.0, 1.0, -0.5, f64::NAN] {
+        let err = err(FilterBuilder::new(1000, p));
+        assert!(matches!(err, BuildError::InvalidFalsePositiveProbability(_)), "{p}");
+    }
+    let mut builder = FilterBuilder::new(1000, 0.01);
+    builder.headroom_factor = 0.5;
+    assert_eq!(err(builder), BuildError::InvalidHeadroomFactor(0.5));
+    assert_eq!(err(FilterBuilder::from_size_and_hashes(1024, 0)), BuildError::ZeroHashes);
+
+    let mut builder = FilterBuilder::from_size_and_hashes(64, 3);
+    builder.canaries(64);
+    assert_eq!(err(builder), BuildError::TooManyCanaries);
+    let mut builder = FilterBuilder::from_size_and_hashes(64, 8);
+    builder.canaries(60);
+    builder.partitioned(true);
+    assert_eq!(err(builder.clone()), BuildError::EmptyPartitions);
+    // the non-panicking completion also covers the counting filters
+    let counting = builder.try_build_counting_bloom_filter::<Vec<usize>>(());
+    assert_eq!(counting.err(), Some(BuildError::EmptyPartitions));
+
+    let err = err(FilterBuilder::new(u64::MAX, 0.01));
+    assert!(matches!(err, BuildError::TooLarge(SizeError { requested_size: None, .. })));
+    assert!(err.to_string().contains("elements are supported"));
+    let bloom = FilterBuilder::new(1000, 0.01).try_build_bloom_filter().unwrap();
+    assert_eq!(bloom.config().size, FilterBuilder::new(1000, 0.01).checked_size().unwrap());
+}
+
+#[test]
+fn memory_budget_test() {
+    let builder = FilterBuilder::from_size_and_fpp(1 << 20, 0.01);
+    assert_eq!((builder.size, builder.hashes), (1 << 23, 7));
+    let mut sized = FilterBuilder::new(builder.expected_elements, 0.01);
+    sized.complete();
+    assert!(sized.size <= builder.size && builder.size - sized.size <= 64, "{}", sized.size);
+    assert!(expected_fpp(builder.size, builder.expected_elements, builder.hashes) < 0.0101);
+
+    let builder = FilterBuilder::from_size_and_items(1001, 1000);
+    assert_eq!(builder.size, 8000);
+    assert_eq!(builder.hashes, 6);
+    let p = builder.false_positive_probability;
+    assert!((0.0215..0.0217).contains(&p), "{p}");
+    assert_eq!(FilterBuilder::from_size_and_items(8, 1000).hashes, 1);
+
+    let mut bloom = FilterBuilder::from_size_and_items(1 << 10, 100).build_bloom_filter();
+    bloom.add(b"hello");
+    assert!(bloom.contains(b"hello"));
+    assert_eq!(bloom.get_u8_array().len(), 1 << 10);
+}
+
+#[test]
+fn hash_indices_test() {
+    let mut builder = FilterBuilder::new(10_000, 0.01);
+    builder.seed(5);
+    builder.partitioned(true);
+    builder.index_scheme(IndexScheme::EnhancedDoubleHashing);
+    let mut bloom = builder.build_bloom_filter();
+    for i in 0..100u32 {
+        let key = i.to_le_bytes();
+        assert_eq!(builder.hash_indices(&key).collect::<Vec<_>>(), bloom.get_hash_indices(&key));
+        bloom.add(&key);
+    }
+    let slice = bloom.config().size / bloom.config().hashes as u64;
+    let indices: Vec<u64> = bloom.hash_indices(b"hello").collect();
+    assert!(indices.iter().enumerate().all(|(i, index)| index / slice == i as u64));
+}
+
+#[test]
+fn preset_test() {
+    let mut url = FilterBuilder::preset_url_dedup(1_000_000);
+    url.complete();
+    let mut db = FilterBuilder::preset_db_point_lookup(1_000_000);
+    db.complete();
+    assert!(url.size > db.size);
+    assert!(url.hashes > db.hashes);
+    assert!(FilterBuilder::preset_cache_admission(1_000).enable_conservative_update);
+    assert!(FilterBuilder::preset_cache_admission(1_000).key_len_hashing());
+    assert_eq!(url.index_scheme, IndexScheme::EnhancedDoubleHashing);
+    assert_eq!(db.block_size, BlockSize::CacheLine);
+    let mut blocked = FilterBuilder::preset_db_point_lookup(1_000).build_blocked_bloom_filter();
+    blocked.add(b"key");
+    assert!(blocked.contains(b"key"));
+}
+
+#[test]
+fn headroom_test() {
+    let mut builder = FilterBuilder::new(10_000, 0.01);
+    builder.headroom_factor(1.5);
+    let trajectory = builder.fpp_trajectory(3);
+    assert_eq!(trajectory.iter().map(|(n, _)| *n).collect::<Vec<u64>>(), vec![5_000, 10_000, 15_000]);
+    assert!(trajectory.windows(2).all(|w| w[0].1 < w[1].1));
+    assert!(trajectory[1].1 < 0.01 && (trajectory[2].1 - 0.01).abs() < 0.001);
+
+    let mut bloom = builder.build_bloom_filter();
+    for i in 0..10_000u32 {
+        bloom.add(&i.to_le_bytes());
+    }
+    let remaining = bloom.remaining_capacity();
+    assert!((4_500..5_500).contains(&remaining), "{remaining}");
+}
+
 #[test]
 fn builder_test() {
     let mut bloom = FilterBuilder::new(100_000_000, 0.01)
@@ -208,4 +1635,25 @@ fn builder_test() {
     bloom.add(b"helloworld");
     assert_eq!(bloom.contains(b"helloworld"), true);
     assert_eq!(bloom.contains(b"helloworld!"), false);
-}
\ No newline at end of file
+}
+#[test]
+fn typed_builder_test() {
+    let typed = FilterBuilder::typed().expected_elements(100_000, 0.01).seed(7);
+    let mut untyped = FilterBuilder::new(100_000, 0.01);
+    untyped.seed(7);
+    let bloom = untyped.build_bloom_filter();
+    assert!(typed.config().is_compatible_to(&bloom.config()));
+
+    let mut blocked = typed.clone().blocked(BlockSize::Page).build_blocked_bloom_filter();
+    blocked.add(b"hello");
+    assert!(blocked.contains(b"hello"));
+    assert_eq!(blocked.block_size(), BlockSize::Page);
+
+    let exact = FilterBuilder::typed().size_and_hashes(4096, 3).build_bloom_filter();
+    assert_eq!(exact.config().size, 4096);
+    assert_eq!(exact.config().hashes, 3);
+
+    let cbf = typed.counting().enable_conservative_update(true)
+        .build_counting_bloom_filter::<Vec<usize>>(());
+    assert!(cbf.config().enable_conservative_update);
+}
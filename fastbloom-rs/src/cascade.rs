@@ -0,0 +1,165 @@
+use alloc::vec::Vec;
+
+use crate::bloom::BloomFilter;
+use crate::builder::FilterBuilder;
+use crate::codec::{put_varint, Reader};
+use crate::Membership;
+
+const CASCADE_MAGIC: &[u8; 4] = b"FBFC";
+/// Levels after which the construction gives up, only reached if a key is both included and
+/// excluded or the hash function is of poor quality.
+const MAX_LEVELS: usize = 64;
+/// False positive probability of the levels after the first, which minimizes the total size.
+const LEVEL_FPP: f64 = 0.5;
+
+/// Filter cascade (as in the CRLite certificate revocation sets of Mozilla) of a fixed set of
+/// included keys, with no false positives for the excluded keys of a known universe. The first
+/// level is a Bloom filter of the included keys, the second one of the excluded keys which are
+/// false positives of the first, the third one of the included keys which are false positives of
+/// the second and so on, until a level has no false positives. A key is included if the first
+/// level which misses it has an odd index, or it is in all levels and their number is odd.
+///
+/// Keys outside `included` and `excluded` are answered like with a Bloom filter at
+/// `config.false_positive_probability`. The cascade takes a little more than the first level,
+/// as each further level stores about half the keys of the previous one at 1 bit per key.
+/// Every level uses the hashing of the [FilterBuilder] with the seed increased by its index.
+///
+/// # Examples
+///
+/// ```rust
+/// use fastbloom_rs::{FilterBuilder, FilterCascade};
+///
+/// let revoked: Vec<u32> = (0..100).collect();
+/// let valid: Vec<u32> = (100..10_000).collect();
+/// let cascade = FilterBuilder::new(100, 0.01).build_filter_cascade(
+///     revoked.iter().map(|i| i.to_le_bytes()), valid.iter().map(|i| i.to_le_bytes()));
+/// assert!(revoked.iter().all(|i| cascade.contains(&i.to_le_bytes())));
+/// assert!(valid.iter().all(|i| !cascade.contains(&i.to_le_bytes())));
+///
+/// let restored = FilterCascade::from_bytes(&cascade.to_bytes()).unwrap();
+/// assert_eq!(restored.contains(&42u32.to_le_bytes()), true);
+/// ```
+#[derive(Clone)]
+#[derive(Debug)]
+pub struct FilterCascade {
+    levels: Vec<BloomFilter>,
+}
+
+impl FilterCascade {
+    /// Builds the cascade of the `included` keys, answering `false` for all `excluded` keys.
+    /// The size of `config` is ignored, the levels are sized for their keys. Panics if the
+    /// construction doesn't finish within 64 levels, e.g. because a key is in both sets.
+    pub fn from_keys<K: AsRef<[u8]>, E: AsRef<[u8]>>(config: FilterBuilder,
+                                                     included: impl IntoIterator<Item=K>,
+                                                     excluded: impl IntoIterator<Item=E>)
+                                                     -> Self {
+        let included: Vec<K> = included.into_iter().collect();
+        let excluded: Vec<E> = excluded.into_iter().collect();
+        let mut insert: Vec<&[u8]> = included.iter().map(|key| key.as_ref()).collect();
+        let mut check: Vec<&[u8]> = excluded.iter().map(|key| key.as_ref()).collect();
+        let mut levels = Vec::new();
+        while !insert.is_empty() {
+            assert!(levels.len() < MAX_LEVELS,
+                    "FilterCascade does not converge, are keys both included and excluded?");
+            let fpp = if levels.is_empty() { config.false_positive_probability } else { LEVEL_FPP };
+            let mut builder = level_config(&config, levels.len(), insert.len() as u64, fpp);
+            let mut level = builder.build_bloom_filter();
+            insert.iter().for_each(|key| level.add(key));
+            check.retain(|key| level.contains(key));
+            levels.push(level);
+            // the false positives are stored by the next level, checked against the keys of this
+            core::mem::swap(&mut insert, &mut check);
+        }
+        FilterCascade { levels }
+    }
+
+    /// Returns the number of levels.
+    pub fn levels(&self) -> usize {
+        self.levels.len()
+    }
+
+    /// Returns the total size of the levels in bits.
+    pub fn size(&self) -> u64 {
+        self.levels.iter().map(|level| level.config().size).sum()
+    }
+
+    /// Tests whether an element is one of the included keys, which is exact for the keys of
+    /// the universe the cascade was built from.
+    pub fn contains(&self, element: &[u8]) -> bool {
+        match self.levels.iter().position(|level| !level.contains(element)) {
+            Some(index) => index % 2 == 1,
+            None => self.levels.len() % 2 == 1,
+        }
+    }
+
+    /// Encodes the cascade: the magic `FBFC` and the number of levels (varint), then per level
+    /// its length (varint) and the level in the format of [BloomFilter::to_static_bytes].
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = CASCADE_MAGIC.to_vec();
+        put_varint(&mut out, self.levels.len() as u64);
+        for level in &self.levels {
+            let bytes = level.to_static_bytes();
+            put_varint(&mut out, bytes.len() as u64);
+            out.extend_from_slice(&bytes);
+        }
+        out
+    }
+
+    /// Decodes a cascade written by [FilterCascade::to_bytes]. Returns `None` if `bytes` is not
+    /// a valid cascade.
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        let mut reader = Reader::new(bytes);
+        if reader.take(4)? != CASCADE_MAGIC {
+            return None;
+        }
+        let len = reader.varint()?;
+        if len > MAX_LEVELS as u64 {
+            return None;
+        }
+        let mut levels = Vec::with_capacity(len as usize);
+        for _ in 0..len {
+            let level_len = usize::try_from(reader.varint()?).ok()?;
+            levels.push(BloomFilter::from_static_bytes(reader.take(level_len)?)?);
+        }
+        reader.is_empty().then_some(FilterCascade { levels })
+    }
+}
+
+/// Returns the configuration of level `index` for `elements` keys at `fpp`.
+fn level_config(config: &FilterBuilder, index: usize, elements: u64, fpp: f64) -> FilterBuilder {
+    let mut level = config.clone();
+    level.expected_elements = elements;
+    level.false_positive_probability = fpp;
+    level.headroom_factor = 1.0;
+    level.size = 0;
+    level.hashes = 0;
+    level.done = false;
+    level.seed = config.seed.wrapping_add(index as u64);
+    level
+}
+
+#[test]
+fn filter_cascade_test() {
+    let included: Vec<u32> = (0..2000).collect();
+    let excluded: Vec<u32> = (2000..100_000).collect();
+    let mut config = FilterBuilder::new(2000, 0.01);
+    config.seed(5);
+    let cascade = FilterCascade::from_keys(config, included.iter().map(|i| i.to_le_bytes()),
+                                           excluded.iter().map(|i| i.to_le_bytes()));
+    assert!(cascade.levels() > 1);
+    assert!(included.iter().all(|i| cascade.contains(&i.to_le_bytes())));
+    assert!(excluded.iter().all(|i| !cascade.contains(&i.to_le_bytes())));
+    // the first level takes about 9.6 bits per included key
+    assert!(cascade.size() < 2000 * 16, "{} bits", cascade.size());
+
+    let bytes = cascade.to_bytes();
+    let restored = FilterCascade::from_bytes(&bytes).unwrap();
+    assert_eq!(restored.levels(), cascade.levels());
+    assert!(included.iter().all(|i| restored.contains(&i.to_le_bytes())));
+    assert!(FilterCascade::from_bytes(&bytes[..bytes.len() - 1]).is_none());
+
+    let empty = FilterCascade::from_keys(FilterBuilder::new(1, 0.01), Vec::<&[u8]>::new(),
+                                         [b"hello"]);
+    assert_eq!(empty.levels(), 0);
+    assert!(!empty.contains(b"hello"));
+}
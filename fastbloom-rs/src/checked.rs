@@ -0,0 +1,144 @@
+use core::sync::atomic::{AtomicU64, Ordering};
+
+use crate::Membership;
+
+/// Counters of a [CheckedMembership], see [CheckedMembership::stats].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CheckedStats {
+    /// Number of [CheckedMembership::definitely_contains] calls.
+    pub queries: u64,
+    /// Number of queries answered by the filter alone, without an exact lookup.
+    pub filter_rejections: u64,
+    /// Number of queries passed on to the exact lookup.
+    pub lookups: u64,
+    /// Number of exact lookups which didn't find a key the filter reported as present.
+    pub false_positives: u64,
+}
+
+impl CheckedStats {
+    /// Returns the observed false positive probability: the share of queries for absent keys
+    /// the filter let through, `0.0` before the first one.
+    pub fn false_positive_rate(&self) -> f64 {
+        let absent = self.filter_rejections + self.false_positives;
+        if absent == 0 { 0.0 } else { self.false_positives as f64 / absent as f64 }
+    }
+}
+
+/// The canonical read path of a filter guarding an exact store: the filter (`F`, any
+/// [Membership]) rejects absent keys cheaply, only keys it reports as present are looked up
+/// with `lookup` (e.g. a database or disk index), which settles false positives.
+/// [CheckedMembership::stats] counts both steps, so the observed false positive rate can be
+/// compared to the configured one.
+///
+/// # Examples
+///
+/// ```rust
+/// use std::collections::HashSet;
+///
+/// use fastbloom_rs::{CheckedMembership, FilterBuilder, Membership};
+///
+/// let store: HashSet<&[u8]> = [&b"alice"[..], b"bob"].into_iter().collect();
+/// let mut bloom = FilterBuilder::new(1000, 0.01).build_bloom_filter();
+/// for key in &store {
+///     bloom.add(key);
+/// }
+///
+/// let checked = CheckedMembership::new(bloom, |key: &[u8]| store.contains(key));
+/// assert_eq!(checked.definitely_contains(b"alice"), true);
+/// assert_eq!(checked.definitely_contains(b"carol"), false);
+/// assert_eq!(checked.stats().queries, 2);
+/// ```
+pub struct CheckedMembership<F, L> {
+    filter: F,
+    lookup: L,
+    queries: AtomicU64,
+    lookups: AtomicU64,
+    false_positives: AtomicU64,
+}
+
+impl<F: Membership, L: Fn(&[u8]) -> bool> CheckedMembership<F, L> {
+    pub fn new(filter: F, lookup: L) -> Self {
+        CheckedMembership {
+            filter,
+            lookup,
+            queries: AtomicU64::new(0),
+            lookups: AtomicU64::new(0),
+            false_positives: AtomicU64::new(0),
+        }
+    }
+
+    /// Tests whether `key` is present, exactly: the filter is asked first, the lookup only if
+    /// the filter reports the key as present.
+    pub fn definitely_contains(&self, key: &[u8]) -> bool {
+        self.queries.fetch_add(1, Ordering::Relaxed);
+        if !self.filter.contains(key) {
+            return false;
+        }
+        self.lookups.fetch_add(1, Ordering::Relaxed);
+        let found = (self.lookup)(key);
+        if !found {
+            self.false_positives.fetch_add(1, Ordering::Relaxed);
+        }
+        found
+    }
+
+    /// Adds `key` to the filter, after it was written to the exact store.
+    pub fn add(&mut self, key: &[u8]) {
+        self.filter.add(key);
+    }
+
+    /// Returns the filter.
+    pub fn filter(&self) -> &F {
+        &self.filter
+    }
+
+    /// Returns the counters since creation or the last [CheckedMembership::reset_stats].
+    pub fn stats(&self) -> CheckedStats {
+        let queries = self.queries.load(Ordering::Relaxed);
+        let lookups = self.lookups.load(Ordering::Relaxed);
+        CheckedStats {
+            queries,
+            filter_rejections: queries.saturating_sub(lookups),
+            lookups,
+            false_positives: self.false_positives.load(Ordering::Relaxed),
+        }
+    }
+
+    pub fn reset_stats(&self) {
+        self.queries.store(0, Ordering::Relaxed);
+        self.lookups.store(0, Ordering::Relaxed);
+        self.false_positives.store(0, Ordering::Relaxed);
+    }
+
+    /// Returns the filter and the lookup.
+    pub fn into_inner(self) -> (F, L) {
+        (self.filter, self.lookup)
+    }
+}
+
+#[test]
+fn checked_membership_test() {
+    use std::collections::HashSet;
+
+    use crate::FilterBuilder;
+
+    let store: HashSet<u32> = (0..1000).collect();
+    let mut checked = CheckedMembership::new(
+        FilterBuilder::new(1000, 0.05).build_bloom_filter(),
+        |key: &[u8]| store.contains(&u32::from_le_bytes(key.try_into().unwrap())),
+    );
+    for i in &store {
+        checked.add(&i.to_le_bytes());
+    }
+    assert!((0..1000u32).all(|i| checked.definitely_contains(&i.to_le_bytes())));
+    assert!((1000..11_000u32).all(|i| !checked.definitely_contains(&i.to_le_bytes())));
+
+    let stats = checked.stats();
+    assert_eq!(stats.queries, 11_000);
+    assert_eq!(stats.lookups, 1000 + stats.false_positives);
+    assert_eq!(stats.filter_rejections + stats.false_positives, 10_000);
+    assert!(stats.false_positive_rate() > 0.0 && stats.false_positive_rate() < 0.1);
+
+    checked.reset_stats();
+    assert_eq!(checked.stats(), CheckedStats::default());
+}
@@ -0,0 +1,363 @@
+//! Small helpers shared by the binary encodings of the filters.
+
+use alloc::vec::Vec;
+
+use crate::builder::FilterBuilder;
+use crate::compat::FormatFeatures;
+use crate::hasher::{DEFAULT_HASHER, HasherRef};
+use crate::index::IndexScheme;
+
+/// Flag of [put_hashing]: the key length hint follows.
+const KEY_LEN_FLAG: u8 = 1 << 2;
+/// Flag of [put_hashing]: the hasher id follows.
+const HASHER_FLAG: u8 = 1 << 3;
+/// Flag of [put_hashing]: the [FormatFeatures] follow. Headers written before the features were
+/// introduced don't have it.
+const FEATURES_FLAG: u8 = 1 << 4;
+/// Flag of [put_hashing]: the number of canary bits follows.
+const CANARIES_FLAG: u8 = 1 << 5;
+/// Flag of [put_hashing]: the salts of [FilterBuilder::hash_seeds] follow.
+const SEEDS_FLAG: u8 = 1 << 6;
+
+/// Appends `value` as an unsigned LEB128 varint.
+pub(crate) fn put_varint(out: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        out.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+/// Appends the hashing parameters of `config`: hashes (u32), seed (u64), index scheme (u8) and
+/// flags (u8), followed by the key length hint, hasher id and canaries (varints) if flagged, the
+/// features of the encoding (varint) plus those implied by `config` and, if flagged, the number
+/// (varint) and values (u64) of the hash seeds. The two low bits of `flags` are left to the
+/// encoding.
+pub(crate) fn put_hashing(out: &mut Vec<u8>, config: &FilterBuilder, flags: u8,
+                          features: FormatFeatures) {
+    out.extend_from_slice(&config.hashes.to_le_bytes());
+    out.extend_from_slice(&config.seed.to_le_bytes());
+    out.push(config.index_scheme.id());
+    let key_len = config.key_len_hashing();
+    let hasher = config.hasher_id() != DEFAULT_HASHER;
+    let canaries = config.canaries != 0;
+    let seeds = config.custom_hash_seeds();
+    out.push(flags & 3
+        | if key_len { KEY_LEN_FLAG } else { 0 }
+        | if hasher { HASHER_FLAG } else { 0 }
+        | if canaries { CANARIES_FLAG } else { 0 }
+        | if seeds.is_some() { SEEDS_FLAG } else { 0 }
+        | FEATURES_FLAG);
+    if key_len {
+        put_varint(out, config.expected_key_len as u64);
+    }
+    if hasher {
+        put_varint(out, config.hasher_id() as u64);
+    }
+    if canaries {
+        put_varint(out, config.canaries as u64);
+    }
+    let partitioned = if config.partitioned {
+        FormatFeatures::PARTITIONED
+    } else {
+        FormatFeatures::default()
+    };
+    put_varint(out, (features | partitioned | index_features(config.index_scheme)).bits() as u64);
+    if let Some(seeds) = seeds {
+        put_varint(out, seeds.len() as u64);
+        for seed in seeds {
+            out.extend_from_slice(&seed.to_le_bytes());
+        }
+    }
+}
+
+/// Reads the parameters written by [put_hashing] from `reader` and appends them to `out`
+/// unparsed, for [Reader::hashing] once the header is complete.
+#[cfg(feature = "std")]
+pub(crate) fn read_hashing<R: std::io::Read>(mut reader: R, out: &mut Vec<u8>)
+                                             -> std::io::Result<()> {
+    let start = out.len();
+    out.resize(start + 14, 0);
+    reader.read_exact(&mut out[start..])?;
+    let flags = out[start + 13];
+    let varints = [KEY_LEN_FLAG, HASHER_FLAG, CANARIES_FLAG, FEATURES_FLAG].into_iter()
+        .filter(|flag| flags & flag != 0)
+        .count();
+    for _ in 0..varints {
+        copy_varint(&mut reader, out)?;
+    }
+    if flags & SEEDS_FLAG != 0 {
+        let hashes = u32::from_le_bytes(out[start..start + 4].try_into().unwrap());
+        let invalid = || std::io::Error::from(std::io::ErrorKind::InvalidData);
+        let count = copy_varint(&mut reader, out)?.ok_or_else(invalid)?;
+        if count > hashes as u64 {
+            return Err(invalid());
+        }
+        // appended as they are read, a truncated header allocates no more than it holds
+        let len = count * 8;
+        if std::io::Read::read_to_end(&mut reader.take(len), out)? as u64 != len {
+            return Err(std::io::ErrorKind::UnexpectedEof.into());
+        }
+    }
+    Ok(())
+}
+
+/// Appends a varint of `reader` to `out`, returns its value or `None` if it is malformed.
+#[cfg(feature = "std")]
+pub(crate) fn copy_varint<R: std::io::Read>(reader: &mut R, out: &mut Vec<u8>)
+                                 -> std::io::Result<Option<u64>> {
+    let mut value = 0u64;
+    for shift in (0..64).step_by(7) {
+        let mut byte = [0];
+        reader.read_exact(&mut byte)?;
+        out.push(byte[0]);
+        value |= ((byte[0] & 0x7f) as u64) << shift;
+        if byte[0] & 0x80 == 0 {
+            return Ok(Some(value));
+        }
+    }
+    Ok(None)
+}
+
+/// The features describing the index scheme.
+fn index_features(scheme: IndexScheme) -> FormatFeatures {
+    match scheme {
+        IndexScheme::DoubleHashing => FormatFeatures::default(),
+        IndexScheme::Extended => FormatFeatures::EXTENDED_INDEX,
+        IndexScheme::EnhancedDoubleHashing => FormatFeatures::ENHANCED_INDEX,
+        #[cfg(feature = "std")]
+        IndexScheme::Guava => FormatFeatures::GUAVA_INDEX,
+        IndexScheme::RedisBloom => FormatFeatures::REDIS_INDEX,
+        IndexScheme::BloomfilterCrate => FormatFeatures::BLOOMFILTER_INDEX,
+    }
+}
+
+/// Hashing parameters read by [Reader::hashing].
+pub(crate) struct Hashing {
+    pub(crate) hashes: u32,
+    /// The two low bits of the flags, left to the encoding.
+    pub(crate) flags: u8,
+    /// Features of the encoding, without those describing the hashing.
+    pub(crate) features: FormatFeatures,
+    seed: u64,
+    index_scheme: IndexScheme,
+    expected_key_len: usize,
+    hasher: HasherRef,
+    canaries: u32,
+    partitioned: bool,
+    hash_seeds: Option<Vec<u64>>,
+}
+
+impl Hashing {
+    /// Applies the parameters to `config`, whose size is already set. Returns `None` when the
+    /// canaries don't fit into the size.
+    pub(crate) fn apply(self, config: &mut FilterBuilder) -> Option<()> {
+        if config.size != 0 && self.canaries as u64 >= config.size {
+            return None;
+        }
+        config.hashes = self.hashes;
+        config.seed = self.seed;
+        config.index_scheme = self.index_scheme;
+        config.expected_key_len = self.expected_key_len;
+        config.hasher = self.hasher;
+        config.canaries = self.canaries;
+        config.partitioned = self.partitioned;
+        config.hash_seeds = self.hash_seeds.map(Vec::into);
+        Some(())
+    }
+}
+
+/// Unvalidated parameters read by [Reader::header].
+pub(crate) struct Header {
+    hashes: u32,
+    seed: u64,
+    index_scheme: u8,
+    flags: u8,
+    expected_key_len: usize,
+    hasher: Option<u64>,
+    canaries: u32,
+    pub(crate) features: FormatFeatures,
+    hash_seeds: Option<Vec<u64>>,
+}
+
+/// Reads bytes from the front of a slice, returning `None` once it runs out.
+#[derive(Clone)]
+pub(crate) struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    pub(crate) fn new(bytes: &'a [u8]) -> Self {
+        Reader { bytes }
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    pub(crate) fn take(&mut self, n: usize) -> Option<&'a [u8]> {
+        if self.bytes.len() < n {
+            return None;
+        }
+        let (head, tail) = self.bytes.split_at(n);
+        self.bytes = tail;
+        Some(head)
+    }
+
+    /// Takes all remaining bytes.
+    pub(crate) fn rest(&mut self) -> &'a [u8] {
+        core::mem::take(&mut self.bytes)
+    }
+
+    pub(crate) fn u8(&mut self) -> Option<u8> {
+        self.take(1).map(|b| b[0])
+    }
+
+    pub(crate) fn u32(&mut self) -> Option<u32> {
+        self.take(4).map(|b| u32::from_le_bytes(b.try_into().unwrap()))
+    }
+
+    pub(crate) fn u64(&mut self) -> Option<u64> {
+        self.take(8).map(|b| u64::from_le_bytes(b.try_into().unwrap()))
+    }
+
+    /// Reads the parameters written by [put_hashing]. Returns `None` for zero hashes, unknown
+    /// index schemes, hashers which are not registered and required features this release
+    /// doesn't know.
+    pub(crate) fn hashing(&mut self) -> Option<Hashing> {
+        let header = self.header()?;
+        if header.hashes == 0 || !header.features.unknown_required().is_empty() {
+            return None;
+        }
+        let index_scheme = IndexScheme::from_id(header.index_scheme)?;
+        let scheme_features = FormatFeatures::EXTENDED_INDEX | FormatFeatures::ENHANCED_INDEX
+            | FormatFeatures::GUAVA_INDEX | FormatFeatures::REDIS_INDEX
+            | FormatFeatures::BLOOMFILTER_INDEX;
+        let rest = header.features.without(scheme_features | FormatFeatures::PARTITIONED);
+        let partitioned = header.features.contains(FormatFeatures::PARTITIONED);
+        if header.features.without(rest).without(FormatFeatures::PARTITIONED)
+            != index_features(index_scheme) {
+            return None;
+        }
+        let hasher = match header.hasher {
+            Some(id) => HasherRef::resolve(u32::try_from(id).ok()?)?,
+            None => HasherRef::default(),
+        };
+        if header.hash_seeds.is_some() && index_scheme != IndexScheme::Extended {
+            return None;
+        }
+        Some(Hashing {
+            hashes: header.hashes,
+            flags: header.flags & 3,
+            features: rest,
+            seed: header.seed,
+            index_scheme,
+            expected_key_len: header.expected_key_len,
+            hasher,
+            canaries: header.canaries,
+            partitioned,
+            hash_seeds: header.hash_seeds,
+        })
+    }
+
+    /// Reads the parameters written by [put_hashing] without validating them.
+    pub(crate) fn header(&mut self) -> Option<Header> {
+        let hashes = self.u32()?;
+        let seed = self.u64()?;
+        let index_scheme = self.u8()?;
+        let flags = self.u8()?;
+        // unknown flags can't be skipped, their data would be misread.
+        if flags & !(3 | KEY_LEN_FLAG | HASHER_FLAG | FEATURES_FLAG | CANARIES_FLAG | SEEDS_FLAG)
+            != 0 {
+            return None;
+        }
+        let expected_key_len = if flags & KEY_LEN_FLAG != 0 { self.varint()? as usize } else { 0 };
+        let hasher = if flags & HASHER_FLAG != 0 { Some(self.varint()?) } else { None };
+        let canaries = if flags & CANARIES_FLAG != 0 {
+            u32::try_from(self.varint()?).ok()?
+        } else { 0 };
+        let features = if flags & FEATURES_FLAG != 0 {
+            FormatFeatures::from_bits(u32::try_from(self.varint()?).ok()?)
+        } else {
+            IndexScheme::from_id(index_scheme).map(index_features).unwrap_or_default()
+        };
+        let hash_seeds = if flags & SEEDS_FLAG != 0 {
+            let count = usize::try_from(self.varint()?).ok()?;
+            if count == 0 || count > hashes as usize {
+                return None;
+            }
+            let seeds = self.take(count.checked_mul(8)?)?;
+            Some(seeds.chunks_exact(8).map(|seed| u64::from_le_bytes(seed.try_into().unwrap()))
+                .collect())
+        } else { None };
+        Some(Header {
+            hashes, seed, index_scheme, flags, expected_key_len, hasher, canaries, features,
+            hash_seeds,
+        })
+    }
+
+    pub(crate) fn varint(&mut self) -> Option<u64> {
+        let mut value = 0u64;
+        for shift in (0..64).step_by(7) {
+            let byte = self.u8()?;
+            value |= ((byte & 0x7f) as u64) << shift;
+            if byte & 0x80 == 0 {
+                return Some(value);
+            }
+        }
+        None
+    }
+}
+
+#[test]
+fn header_features_test() {
+    let mut config = FilterBuilder::new(100, 0.01);
+    config.index_scheme(IndexScheme::Extended);
+    config.complete();
+    let mut out = Vec::new();
+    put_hashing(&mut out, &config, 1, FormatFeatures::COUNTING_4BIT);
+    let hashing = Reader::new(&out).hashing().unwrap();
+    assert_eq!(hashing.features, FormatFeatures::COUNTING_4BIT);
+    assert_eq!((hashing.flags, hashing.index_scheme), (1, IndexScheme::Extended));
+
+    // unknown optional features are ignored, unknown required ones are rejected.
+    let flags_at = 13;
+    for (features, accepted) in [(1 << 20, true), (1 << 15, false)] {
+        let mut out = out[..flags_at + 1].to_vec();
+        put_varint(&mut out, (FormatFeatures::EXTENDED_INDEX.bits() | features) as u64);
+        assert_eq!(Reader::new(&out).hashing().is_some(), accepted);
+    }
+    let mut unknown_flag = out.clone();
+    unknown_flag[flags_at] |= 1 << 7;
+    assert!(Reader::new(&unknown_flag).hashing().is_none());
+
+    // headers written before the features are still read.
+    let mut legacy = out[..flags_at + 1].to_vec();
+    legacy[flags_at] &= !FEATURES_FLAG;
+    let hashing = Reader::new(&legacy).hashing().unwrap();
+    assert_eq!(hashing.features, FormatFeatures::default());
+
+    config.index_scheme(IndexScheme::EnhancedDoubleHashing);
+    let mut out = Vec::new();
+    put_hashing(&mut out, &config, 0, FormatFeatures::default());
+    let hashing = Reader::new(&out).hashing().unwrap();
+    assert_eq!(hashing.index_scheme, IndexScheme::EnhancedDoubleHashing);
+    assert!(hashing.features.is_empty());
+    // the scheme and its feature must agree
+    out[12] = IndexScheme::Extended.id();
+    assert!(Reader::new(&out).hashing().is_none());
+}
+
+#[test]
+fn varint_test() {
+    let mut out = Vec::new();
+    for v in [0, 1, 127, 128, 300, u64::MAX] {
+        put_varint(&mut out, v);
+    }
+    let mut reader = Reader::new(&out);
+    for v in [0, 1, 127, 128, 300, u64::MAX] {
+        assert_eq!(reader.varint(), Some(v));
+    }
+    assert!(reader.is_empty());
+    assert_eq!(reader.varint(), None);
+}
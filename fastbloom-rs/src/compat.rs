@@ -0,0 +1,468 @@
+use core::fmt::{Display, Formatter};
+use core::ops::BitOr;
+
+use crate::blocked::BlockSize;
+#[cfg(test)]
+use crate::bloom::BloomFilter;
+use crate::codec::Reader;
+use crate::index::IndexScheme;
+
+/// Serialized formats of the filters, one per release which changed how filters are persisted.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum FormatVersion {
+    /// Raw little-endian bitmap as returned by [get_u8_array](crate::BloomFilter::get_u8_array)
+    /// and loaded by [from_u8_array](crate::BloomFilter::from_u8_array) (fastbloom-rs 0.5). The
+    /// number of hashes is stored out of band.
+    V0_5,
+}
+
+impl FormatVersion {
+    /// The format written by this release.
+    pub const CURRENT: FormatVersion = FormatVersion::V0_5;
+
+    /// All formats this release can load, oldest first.
+    pub fn all() -> &'static [FormatVersion] {
+        &[FormatVersion::V0_5]
+    }
+}
+
+/// Features recorded in the header of the binary formats ([crate::StaticFilterRef],
+/// [crate::CountingBloomFilter::snapshot_compressed], [crate::ReplicationMessage]), so the
+/// formats describe themselves and can evolve: the low 16 bits are required features, a loader
+/// which doesn't know one of them rejects the data instead of misreading it; the high 16 bits
+/// are hints a loader may ignore.
+///
+/// # Examples
+///
+/// ```rust
+/// use fastbloom_rs::{FilterBuilder, FormatFeatures};
+///
+/// let cbf = FilterBuilder::new(1000, 0.01).build_counting_bloom_filter::<Vec<usize>>(());
+/// let features = FormatFeatures::of(&cbf.snapshot_compressed()).unwrap();
+/// assert!(features.contains(FormatFeatures::COUNTING_4BIT));
+/// assert!(features.unknown_required().is_empty());
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct FormatFeatures(u32);
+
+impl FormatFeatures {
+    /// The bits are split into partitions, one per hash function.
+    pub const PARTITIONED: FormatFeatures = FormatFeatures(1 << 0);
+    /// All bits of an element are in one block, see [crate::BlockedBloomFilter].
+    pub const BLOCKED: FormatFeatures = FormatFeatures(1 << 1);
+    /// Positions are 4-bit counters, see [crate::CountingBloomFilter].
+    pub const COUNTING_4BIT: FormatFeatures = FormatFeatures(1 << 2);
+    /// Indices are derived with [IndexScheme::Extended].
+    pub const EXTENDED_INDEX: FormatFeatures = FormatFeatures(1 << 3);
+    /// Indices are derived with [IndexScheme::EnhancedDoubleHashing].
+    pub const ENHANCED_INDEX: FormatFeatures = FormatFeatures(1 << 4);
+    /// Indices are derived with the Guava scheme (`IndexScheme::Guava`, needs `std`).
+    pub const GUAVA_INDEX: FormatFeatures = FormatFeatures(1 << 5);
+    /// Indices are derived with [IndexScheme::RedisBloom].
+    pub const REDIS_INDEX: FormatFeatures = FormatFeatures(1 << 6);
+    /// Indices are derived with [IndexScheme::BloomfilterCrate].
+    pub const BLOOMFILTER_INDEX: FormatFeatures = FormatFeatures(1 << 7);
+
+    /// The required features this release knows, the Guava scheme only with `std`.
+    const KNOWN_REQUIRED: u32 = if cfg!(feature = "std") { 0b1111_1111 } else { 0b1101_1111 };
+    const REQUIRED: u32 = 0xffff;
+
+    /// Returns the raw bits.
+    pub fn bits(&self) -> u32 {
+        self.0
+    }
+
+    pub fn from_bits(bits: u32) -> Self {
+        FormatFeatures(bits)
+    }
+
+    /// Returns [true] if all features of `other` are set.
+    pub fn contains(&self, other: FormatFeatures) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0 == 0
+    }
+
+    /// Returns the features without those of `other`.
+    pub fn without(&self, other: FormatFeatures) -> Self {
+        FormatFeatures(self.0 & !other.0)
+    }
+
+    /// Returns the required features this release doesn't know, which prevent loading.
+    pub fn unknown_required(&self) -> FormatFeatures {
+        FormatFeatures(self.0 & FormatFeatures::REQUIRED & !FormatFeatures::KNOWN_REQUIRED)
+    }
+
+    /// Reads the features from the header of a [crate::StaticFilterRef], a compressed filter or
+    /// counting snapshot or a [crate::GolombCodedSet] without loading it, e.g. to report why it
+    /// can't be loaded. Returns `None` for other data.
+    pub fn of(bytes: &[u8]) -> Option<FormatFeatures> {
+        let mut reader = Reader::new(bytes);
+        match reader.take(4)? {
+            b"FBST" | b"FBCB" | b"FBCS" | b"FBGC" => Some(reader.header()?.features),
+            _ => None,
+        }
+    }
+}
+
+impl BitOr for FormatFeatures {
+    type Output = FormatFeatures;
+
+    fn bitor(self, rhs: FormatFeatures) -> FormatFeatures {
+        FormatFeatures(self.0 | rhs.0)
+    }
+}
+
+/// Memory layout of a filter, part of its [CompatibilityKey].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum FilterLayout {
+    /// One bit per position, [BloomFilter](crate::BloomFilter).
+    Standard,
+    /// One counter of the given number of bits per position,
+    /// [CountingBloomFilter](crate::CountingBloomFilter).
+    Counting(u32),
+    /// All bits of an element in one block,
+    /// [BlockedBloomFilter](crate::BlockedBloomFilter).
+    Blocked(BlockSize),
+}
+
+/// Everything which decides where an element lands in a filter. Filters with equal keys can be
+/// unioned or intersected, so orchestration code can group filters by key (it implements [Hash])
+/// before attempting merges.
+///
+/// # Examples
+///
+/// ```rust
+/// use fastbloom_rs::FilterBuilder;
+///
+/// let mut builder = FilterBuilder::new(10_000, 0.01);
+/// let a = builder.build_bloom_filter();
+/// builder.seed(1);
+/// let b = builder.build_bloom_filter();
+/// assert!(a.compatibility_key().is_mergeable_with(&a.compatibility_key()));
+/// assert!(!a.compatibility_key().is_mergeable_with(&b.compatibility_key()));
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct CompatibilityKey {
+    /// Size of the filter in bits (positions for counting filters).
+    pub size: u64,
+    /// Number of hash functions.
+    pub hashes: u32,
+    /// Seed of the hashing, see [FilterBuilder::seed](crate::FilterBuilder::seed).
+    pub seed: u64,
+    /// Id of the hash function, see [FilterBuilder::hasher](crate::FilterBuilder::hasher).
+    pub hasher: u32,
+    /// Check value of the secret key, see
+    /// [FilterBuilder::secret_key](crate::FilterBuilder::secret_key), `0` without one. It is
+    /// derived from the key with SipHash and does not reveal it.
+    pub key_check: u64,
+    /// Whether the hashing specialized on key length is used, see
+    /// [FilterBuilder::expected_key_len](crate::FilterBuilder::expected_key_len).
+    pub key_len_hashing: bool,
+    /// How the indices are derived from the hashes, see [IndexScheme].
+    pub index_scheme: IndexScheme,
+    /// Digest of the salts of the indices, see
+    /// [FilterBuilder::hash_seeds](crate::FilterBuilder::hash_seeds), `0` for the default ones.
+    pub hash_seeds: u64,
+    /// Number of canary bits, see [FilterBuilder::canaries](crate::FilterBuilder::canaries).
+    pub canaries: u32,
+    /// Whether the bits are partitioned, see
+    /// [FilterBuilder::partitioned](crate::FilterBuilder::partitioned).
+    pub partitioned: bool,
+    /// Memory layout of the filter, see [FilterLayout].
+    pub layout: FilterLayout,
+}
+
+impl CompatibilityKey {
+    /// Returns [true] if filters with these keys can be merged.
+    pub fn is_mergeable_with(&self, other: &CompatibilityKey) -> bool {
+        self == other
+    }
+
+    /// Returns `Ok` if filters with these keys can be merged, or the first parameter in which
+    /// they differ, in the order of the fields. All set operations on filters check their keys
+    /// like this, so filters built differently are never merged into garbage.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use fastbloom_rs::{FilterBuilder, IncompatibilityReason};
+    ///
+    /// let a = FilterBuilder::from_size_and_hashes(8192, 4).build_bloom_filter();
+    /// let b = FilterBuilder::from_size_and_hashes(8192, 7).build_bloom_filter();
+    /// let reason = a.compatibility_key().compatible(&b.compatibility_key()).unwrap_err();
+    /// assert_eq!(reason, IncompatibilityReason::Hashes { left: 4, right: 7 });
+    /// ```
+    pub fn compatible(&self, other: &CompatibilityKey) -> Result<(), IncompatibilityReason> {
+        let (l, r) = (self, other);
+        let reason = if l.size != r.size {
+            IncompatibilityReason::Size { left: l.size, right: r.size }
+        } else if l.hashes != r.hashes {
+            IncompatibilityReason::Hashes { left: l.hashes, right: r.hashes }
+        } else if l.seed != r.seed {
+            IncompatibilityReason::Seed
+        } else if l.hasher != r.hasher || l.key_len_hashing != r.key_len_hashing
+            || l.index_scheme != r.index_scheme || l.hash_seeds != r.hash_seeds {
+            IncompatibilityReason::Hashing
+        } else if l.key_check != r.key_check {
+            IncompatibilityReason::SecretKey
+        } else if l.canaries != r.canaries {
+            IncompatibilityReason::Canaries { left: l.canaries, right: r.canaries }
+        } else if l.partitioned != r.partitioned {
+            IncompatibilityReason::Partitioned
+        } else if l.layout != r.layout {
+            IncompatibilityReason::Layout { left: l.layout, right: r.layout }
+        } else {
+            return Ok(());
+        };
+        Err(reason)
+    }
+}
+
+/// The parameters of a filter which decide whether it can be merged with another one: size,
+/// hashes, seed, hashing and memory layout. Another name of [CompatibilityKey], checked with
+/// [CompatibilityKey::compatible].
+pub type FilterParams = CompatibilityKey;
+
+/// The first parameter in which the [CompatibilityKey]s of two filters differ, see
+/// [CompatibilityKey::compatible] and [MismatchError::reason].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IncompatibilityReason {
+    /// The filters differ in size.
+    Size { left: u64, right: u64 },
+    /// The filters differ in the number of hash functions.
+    Hashes { left: u32, right: u32 },
+    /// The filters hash with different seeds.
+    Seed,
+    /// The filters use different hash functions, index schemes, salts of the indices or hashing
+    /// specialized on the key length.
+    Hashing,
+    /// The filters hash with different secret keys.
+    SecretKey,
+    /// The filters differ in the number of canary bits.
+    Canaries { left: u32, right: u32 },
+    /// One filter is partitioned and the other is not.
+    Partitioned,
+    /// The filters differ in memory layout.
+    Layout { left: FilterLayout, right: FilterLayout },
+}
+
+impl Display for IncompatibilityReason {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            IncompatibilityReason::Size { left, right } => write!(f, "size {left} != {right}"),
+            IncompatibilityReason::Hashes { left, right } => write!(f, "hashes {left} != {right}"),
+            IncompatibilityReason::Seed => write!(f, "seeds differ"),
+            IncompatibilityReason::Hashing => write!(f, "hashing differs"),
+            IncompatibilityReason::SecretKey => write!(f, "secret keys differ"),
+            IncompatibilityReason::Canaries { left, right } => {
+                write!(f, "canaries {left} != {right}")
+            }
+            IncompatibilityReason::Partitioned => write!(f, "partitioning differs"),
+            IncompatibilityReason::Layout { left, right } => {
+                write!(f, "layout {left:?} != {right:?}")
+            }
+        }
+    }
+}
+
+/// Error of a set operation on filters which can't be merged, see
+/// [BloomFilter::unioned](crate::BloomFilter::unioned).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MismatchError {
+    /// Key of the filter the operation was called on.
+    pub left: CompatibilityKey,
+    /// Key of the other filter.
+    pub right: CompatibilityKey,
+}
+
+impl MismatchError {
+    /// Returns `Ok` if filters with the keys `left` and `right` can be merged.
+    pub(crate) fn check(left: CompatibilityKey, right: CompatibilityKey) -> Result<(), Self> {
+        left.compatible(&right).map_err(|_| MismatchError { left, right })
+    }
+
+    /// Returns the first parameter in which the filters differ.
+    pub fn reason(&self) -> IncompatibilityReason {
+        self.left.compatible(&self.right).expect_err("MismatchError of equal keys!")
+    }
+}
+
+impl Display for MismatchError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        let (l, r) = (&self.left, &self.right);
+        write!(f, "filters can't be merged:")?;
+        if l.size != r.size {
+            write!(f, " size {} != {}", l.size, r.size)?;
+        }
+        if l.hashes != r.hashes {
+            write!(f, " hashes {} != {}", l.hashes, r.hashes)?;
+        }
+        if l.seed != r.seed {
+            write!(f, " seed {} != {}", l.seed, r.seed)?;
+        }
+        if l.hasher != r.hasher || l.key_len_hashing != r.key_len_hashing
+            || l.index_scheme != r.index_scheme || l.hash_seeds != r.hash_seeds {
+            write!(f, " hashing differs")?;
+        }
+        if l.key_check != r.key_check {
+            write!(f, " secret keys differ")?;
+        }
+        if l.canaries != r.canaries {
+            write!(f, " canaries {} != {}", l.canaries, r.canaries)?;
+        }
+        if l.partitioned != r.partitioned {
+            write!(f, " partitioned {} != {}", l.partitioned, r.partitioned)?;
+        }
+        if l.layout != r.layout {
+            write!(f, " layout {:?} != {:?}", l.layout, r.layout)?;
+        }
+        Ok(())
+    }
+}
+
+impl core::error::Error for MismatchError {}
+
+/// Loads a serialized Bloom filter stored in the given `version`.
+#[cfg(test)]
+pub(crate) fn load_bloom_filter(version: FormatVersion, bytes: &[u8], hashes: u32) -> BloomFilter {
+    match version {
+        FormatVersion::V0_5 => BloomFilter::from_u8_array(bytes, hashes),
+    }
+}
+
+/// A filter persisted by a prior release, together with the parameters used to produce it. The
+/// bytes were written by that release, so changes to the hashing or the layout fail the tests.
+#[cfg(test)]
+struct Fixture {
+    version: FormatVersion,
+    bytes: &'static [u8],
+    hashes: u32,
+    /// `format!("key-{i}")` for `i` in `0..inserted` was added to the filter.
+    inserted: usize,
+}
+
+#[cfg(test)]
+const FIXTURES: &[Fixture] = &[
+    Fixture {
+        version: FormatVersion::V0_5,
+        bytes: include_bytes!("../fixtures/bloom_v0_5.bin"),
+        hashes: 4,
+        inserted: 100,
+    },
+];
+
+#[test]
+fn fixtures_cover_all_versions() {
+    for version in FormatVersion::all() {
+        assert!(FIXTURES.iter().any(|f| f.version == *version), "no fixture for {:?}", version);
+    }
+}
+
+#[test]
+fn fixtures_query_equivalence() {
+    use crate::Membership;
+
+    for fixture in FIXTURES {
+        let loaded = load_bloom_filter(fixture.version, fixture.bytes, fixture.hashes);
+
+        let mut rebuilt = BloomFilter::from_u8_array(&vec![0u8; fixture.bytes.len()], fixture.hashes);
+        for i in 0..fixture.inserted {
+            rebuilt.add(format!("key-{i}").as_bytes());
+        }
+        assert_eq!(loaded.get_u8_array(), rebuilt.get_u8_array(), "{:?}", fixture.version);
+
+        for i in 0..fixture.inserted {
+            assert!(loaded.contains(format!("key-{i}").as_bytes()), "{:?}", fixture.version);
+        }
+        for i in 0..10_000 {
+            let probe = format!("probe-{i}");
+            assert_eq!(loaded.contains(probe.as_bytes()), rebuilt.contains(probe.as_bytes()));
+        }
+    }
+}
+
+/// The bits of `BloomFilter::from_u8_array(&[0; 64], 3)` after adding `format!("key-{i}")` for
+/// `i` in `0..10`, as written by fastbloom-rs 0.5 on a little-endian 64-bit target.
+#[cfg(test)]
+const V0_5_GOLDEN: [u8; 64] = [
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x10, 0x00, 0x80, 0x00, 0x00, 0x00, 0x02, 0x00, 0x00,
+    0x08, 0x00, 0x10, 0x00, 0x04, 0x00, 0x04, 0x00, 0x04, 0x40, 0x41, 0x00, 0x00, 0x00, 0x00, 0x00,
+    0x00, 0x00, 0x01, 0x00, 0x10, 0x00, 0x40, 0x40, 0x00, 0x01, 0x80, 0x40, 0x00, 0x00, 0x00, 0x01,
+    0x00, 0x40, 0x00, 0x00, 0x40, 0x08, 0x01, 0x20, 0x00, 0x01, 0x00, 0x80, 0x00, 0x40, 0x00, 0x0e,
+];
+
+#[test]
+fn v0_5_golden_bytes_test() {
+    use crate::Membership;
+
+    // built by this release, the bits must be the ones of the previous format
+    let mut rebuilt = BloomFilter::from_u8_array(&[0; 64], 3);
+    for i in 0..10 {
+        rebuilt.add(format!("key-{i}").as_bytes());
+    }
+    assert_eq!(rebuilt.get_u8_array(), V0_5_GOLDEN);
+
+    let loaded = load_bloom_filter(FormatVersion::V0_5, &V0_5_GOLDEN, 3);
+    assert!((0..10).all(|i| loaded.contains(format!("key-{i}").as_bytes())));
+    assert_eq!(loaded.get_u8_array(), V0_5_GOLDEN);
+}
+
+#[test]
+fn compatibility_key_test() {
+    use std::collections::HashMap;
+
+    use crate::FilterBuilder;
+
+    let mut builder = FilterBuilder::new(10_000, 0.01);
+    let bloom = builder.build_bloom_filter();
+    let cbf = builder.build_counting_bloom_filter::<Vec<usize>>(());
+    let blocked = builder.build_blocked_bloom_filter();
+    builder.index_scheme(IndexScheme::Extended);
+    let extended = builder.build_bloom_filter();
+
+    let keys = [bloom.compatibility_key(), cbf.compatibility_key(), blocked.compatibility_key(),
+        extended.compatibility_key(), bloom.clone().compatibility_key()];
+    assert_eq!(keys[1].layout, FilterLayout::Counting(4));
+    assert_eq!(keys[2].layout, FilterLayout::Blocked(BlockSize::CacheLine));
+    let mut groups: HashMap<CompatibilityKey, usize> = HashMap::new();
+    for key in keys {
+        *groups.entry(key).or_default() += 1;
+    }
+    assert_eq!(groups.len(), 4);
+    assert_eq!(groups[&bloom.compatibility_key()], 2);
+}
+
+#[test]
+fn incompatibility_reason_test() {
+    use crate::FilterBuilder;
+
+    let mut builder = FilterBuilder::from_size_and_hashes(8192, 4);
+    let bloom = builder.build_bloom_filter();
+    let key = bloom.compatibility_key();
+    assert_eq!(key.compatible(&key), Ok(()));
+    let reason = |configure: &dyn Fn(&mut FilterBuilder)| {
+        let mut other = FilterBuilder::from_size_and_hashes(8192, 4);
+        configure(&mut other);
+        key.compatible(&other.build_bloom_filter().compatibility_key()).unwrap_err()
+    };
+
+    assert_eq!(reason(&|b| b.size = 4096), IncompatibilityReason::Size { left: 8192, right: 4096 });
+    assert_eq!(reason(&|b| b.hashes = 7), IncompatibilityReason::Hashes { left: 4, right: 7 });
+    assert_eq!(reason(&|b| b.seed(1)), IncompatibilityReason::Seed);
+    assert_eq!(reason(&|b| b.index_scheme(IndexScheme::Extended)), IncompatibilityReason::Hashing);
+    assert_eq!(reason(&|b| b.partitioned(true)), IncompatibilityReason::Partitioned);
+    let cbf = builder.build_counting_bloom_filter::<Vec<usize>>(());
+    assert_eq!(key.compatible(&cbf.compatibility_key()), Err(IncompatibilityReason::Layout {
+        left: FilterLayout::Standard,
+        right: FilterLayout::Counting(4),
+    }));
+
+    // merges report the reason of their mismatch
+    builder.seed(1);
+    let error = bloom.unioned(&builder.build_bloom_filter()).unwrap_err();
+    assert_eq!(error.reason(), IncompatibilityReason::Seed);
+    assert_eq!(error.reason().to_string(), "seeds differ");
+}
@@ -0,0 +1,301 @@
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::builder::FilterBuilder;
+#[cfg(not(feature = "std"))]
+use crate::float::Float;
+use crate::index::{hash_pair, mix64};
+use crate::{Deletable, Membership};
+
+/// Marks an empty slot, fingerprints are never `0`.
+const EMPTY: u16 = 0;
+/// Number of fingerprints in one bucket.
+const BUCKET_SIZE: usize = 4;
+/// Load factor the buckets are sized for, inserts start to fail slightly above it.
+const LOAD_FACTOR: f64 = 0.95;
+/// Number of fingerprints relocated before an insert gives up.
+const MAX_KICKS: usize = 500;
+
+/// Cuckoo filter: a table of small fingerprints in buckets of four, every element has two
+/// candidate buckets and inserts relocate fingerprints between them when both are full. Unlike
+/// a [crate::CountingBloomFilter], removing an element only takes out its own fingerprint, so it
+/// never causes false negatives for other elements.
+///
+/// The filter is sized for [FilterBuilder::capacity] elements, the fingerprints are just wide
+/// enough for the false positive probability of the builder (at most 16 bits, that is about
+/// `2^-13`). Adding more elements eventually fails, [CuckooFilter::try_add] reports it while
+/// [Membership::add] panics. Only remove elements which were added, an element added twice is
+/// stored twice and needs to be removed twice.
+///
+/// # Examples
+///
+/// ```rust
+/// use fastbloom_rs::{Deletable, FilterBuilder, Membership};
+///
+/// let mut cuckoo = FilterBuilder::new(1000, 0.01).build_cuckoo_filter();
+/// cuckoo.add(b"hello");
+/// cuckoo.add(b"world");
+/// assert_eq!(cuckoo.contains(b"hello"), true);
+///
+/// cuckoo.remove(b"hello");
+/// assert_eq!(cuckoo.contains(b"hello"), false);
+/// assert_eq!(cuckoo.contains(b"world"), true);
+/// ```
+#[derive(Clone)]
+#[derive(Debug)]
+pub struct CuckooFilter {
+    config: FilterBuilder,
+    slots: Vec<u16>,
+    fingerprint_bits: u32,
+    len: usize,
+    /// Fingerprint and bucket of the element evicted by the last failed relocation.
+    victim: Option<(usize, u16)>,
+    /// State of the xorshift generator picking the fingerprint to relocate.
+    rng: u64,
+}
+
+impl CuckooFilter {
+    /// Builds an empty filter for `config.capacity()` elements at
+    /// `config.false_positive_probability`.
+    pub fn new(config: FilterBuilder) -> Self {
+        let p = config.false_positive_probability;
+        assert!(p > 0.0 && p < 1.0, "False positive probability must be in (0, 1)!");
+        // a lookup compares 2 * BUCKET_SIZE fingerprints
+        let bits = ((2.0 * BUCKET_SIZE as f64 / p).ln() / 2f64.ln()).ceil() as u32;
+        let buckets = (config.capacity() as f64 / (BUCKET_SIZE as f64 * LOAD_FACTOR)).ceil();
+        let buckets = (buckets as usize).max(1).next_power_of_two();
+        CuckooFilter {
+            rng: mix64(config.seed) | 1,
+            config,
+            slots: vec![EMPTY; buckets * BUCKET_SIZE],
+            fingerprint_bits: bits.clamp(4, 16),
+            len: 0,
+            victim: None,
+        }
+    }
+
+    /// Returns the configuration of the filter.
+    pub fn config(&self) -> FilterBuilder {
+        self.config.clone()
+    }
+
+    /// Returns the number of fingerprints in the filter.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns [true] if the filter holds no fingerprints.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the number of buckets of the filter.
+    pub fn buckets(&self) -> usize {
+        self.slots.len() / BUCKET_SIZE
+    }
+
+    /// Returns the number of bits of a fingerprint.
+    pub fn fingerprint_bits(&self) -> u32 {
+        self.fingerprint_bits
+    }
+
+    /// Returns the share of slots which hold a fingerprint.
+    pub fn load_factor(&self) -> f64 {
+        self.len as f64 / self.slots.len() as f64
+    }
+
+    /// Adds an element, returns [false] and leaves the filter unchanged if it is too full.
+    pub fn try_add(&mut self, element: &[u8]) -> bool {
+        if self.victim.is_some() {
+            return false;
+        }
+        let (bucket, fingerprint) = self.locate(element);
+        self.place(bucket, fingerprint);
+        true
+    }
+
+    /// Stores a fingerprint in one of its buckets, relocating others until one finds a free slot
+    /// in its other bucket. The last evicted fingerprint is kept as victim if that fails.
+    fn place(&mut self, bucket: usize, fingerprint: u16) {
+        self.len += 1;
+        if self.insert(bucket, fingerprint) || self.insert(self.alt(bucket, fingerprint),
+                                                           fingerprint) {
+            return;
+        }
+        let (mut bucket, mut fingerprint) = (bucket, fingerprint);
+        for _ in 0..MAX_KICKS {
+            let slot = bucket * BUCKET_SIZE + self.next_slot();
+            core::mem::swap(&mut self.slots[slot], &mut fingerprint);
+            bucket = self.alt(bucket, fingerprint);
+            if self.insert(bucket, fingerprint) {
+                return;
+            }
+        }
+        self.victim = Some((bucket, fingerprint));
+    }
+
+    /// Returns the first bucket and the fingerprint of an element.
+    #[inline]
+    fn locate(&self, element: &[u8]) -> (usize, u16) {
+        let (hash1, hash2) = hash_pair(element, &self.config);
+        let fingerprint = (hash2 >> (64 - self.fingerprint_bits)) as u16;
+        let bucket = hash1 as usize & (self.buckets() - 1);
+        (bucket, if fingerprint == EMPTY { 1 } else { fingerprint })
+    }
+
+    /// Returns the other bucket of a fingerprint in `bucket`, the mapping is its own inverse.
+    #[inline]
+    fn alt(&self, bucket: usize, fingerprint: u16) -> usize {
+        (bucket ^ mix64(fingerprint as u64) as usize) & (self.buckets() - 1)
+    }
+
+    #[inline]
+    fn bucket(&self, bucket: usize) -> &[u16] {
+        &self.slots[bucket * BUCKET_SIZE..(bucket + 1) * BUCKET_SIZE]
+    }
+
+    /// Tests whether `fingerprint` is in `bucket`, its other bucket or the victim.
+    #[inline]
+    fn contains_fingerprint(&self, bucket: usize, fingerprint: u16) -> bool {
+        let alt = self.alt(bucket, fingerprint);
+        self.bucket(bucket).contains(&fingerprint) || self.bucket(alt).contains(&fingerprint)
+            || self.victim.is_some_and(|(victim, stored)| {
+            stored == fingerprint && (victim == bucket || victim == alt)
+        })
+    }
+
+    /// Stores `fingerprint` in a free slot of `bucket`, returns [false] if it is full.
+    fn insert(&mut self, bucket: usize, fingerprint: u16) -> bool {
+        let range = bucket * BUCKET_SIZE..(bucket + 1) * BUCKET_SIZE;
+        match self.slots[range].iter_mut().find(|slot| **slot == EMPTY) {
+            Some(slot) => {
+                *slot = fingerprint;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Picks the slot of a bucket whose fingerprint is relocated.
+    fn next_slot(&mut self) -> usize {
+        self.rng ^= self.rng << 13;
+        self.rng ^= self.rng >> 7;
+        self.rng ^= self.rng << 17;
+        (self.rng >> 32) as usize % BUCKET_SIZE
+    }
+}
+
+impl Membership for CuckooFilter {
+    /// Adds the fingerprint of the passed value to the filter. Panics if the filter is too full,
+    /// see [CuckooFilter::try_add].
+    fn add(&mut self, element: &[u8]) {
+        assert!(self.try_add(element), "CuckooFilter is full!");
+    }
+
+    /// Tests whether the fingerprint of an element is present in the filter.
+    #[inline]
+    fn contains(&self, element: &[u8]) -> bool {
+        let (bucket, fingerprint) = self.locate(element);
+        self.contains_fingerprint(bucket, fingerprint)
+    }
+
+    /// Get the first bucket and the fingerprint of the element.
+    fn get_hash_indices(&self, element: &[u8]) -> Vec<u64> {
+        let (bucket, fingerprint) = self.locate(element);
+        vec![bucket as u64, fingerprint as u64]
+    }
+
+    /// Tests whether a bucket and fingerprint from `get_hash_indices` are present in the filter,
+    /// returns [false] for indices which no element of this filter has.
+    fn contains_hash_indices(&self, indices: &Vec<u64>) -> bool {
+        let &[bucket, fingerprint] = indices.as_slice() else { return false };
+        if bucket >= self.buckets() as u64 || fingerprint == EMPTY as u64
+            || fingerprint >> self.fingerprint_bits != 0 {
+            return false;
+        }
+        self.contains_fingerprint(bucket as usize, fingerprint as u16)
+    }
+
+    /// Removes all fingerprints from the filter.
+    fn clear(&mut self) {
+        self.slots.fill(EMPTY);
+        self.len = 0;
+        self.victim = None;
+    }
+}
+
+impl Deletable for CuckooFilter {
+    /// Removes one fingerprint of the element, the element must have been added before.
+    fn remove(&mut self, element: &[u8]) {
+        let (bucket, fingerprint) = self.locate(element);
+        let alt = self.alt(bucket, fingerprint);
+        if let Some((victim, stored)) = self.victim {
+            if stored == fingerprint && (victim == bucket || victim == alt) {
+                self.victim = None;
+                self.len -= 1;
+                return;
+            }
+        }
+        let found = [bucket, alt].into_iter()
+            .flat_map(|bucket| bucket * BUCKET_SIZE..(bucket + 1) * BUCKET_SIZE)
+            .find(|&slot| self.slots[slot] == fingerprint);
+        if let Some(slot) = found {
+            self.slots[slot] = EMPTY;
+            self.len -= 1;
+            // the freed slot may take the victim back
+            if let Some((victim, stored)) = self.victim.take() {
+                self.len -= 1;
+                self.place(victim, stored);
+            }
+        }
+    }
+}
+
+#[test]
+fn cuckoo_filter_test() {
+    let mut cuckoo = FilterBuilder::new(1000, 0.01).build_cuckoo_filter();
+    assert_eq!((cuckoo.buckets(), cuckoo.fingerprint_bits()), (512, 10));
+    for i in 0..1000u32 {
+        cuckoo.add(&i.to_le_bytes());
+    }
+    assert_eq!(cuckoo.len(), 1000);
+    assert!((0..1000u32).all(|i| cuckoo.contains(&i.to_le_bytes())));
+    let false_positives = (1000..101_000u32).filter(|i| cuckoo.contains(&i.to_le_bytes())).count();
+    assert!(false_positives < 1000, "{false_positives} false positives");
+
+    for i in (0..1000u32).step_by(2) {
+        cuckoo.remove(&i.to_le_bytes());
+    }
+    assert_eq!(cuckoo.len(), 500);
+    assert!((1..1000u32).step_by(2).all(|i| cuckoo.contains(&i.to_le_bytes())));
+    let removed = (0..1000u32).step_by(2).filter(|i| cuckoo.contains(&i.to_le_bytes())).count();
+    assert!(removed < 10, "{removed} removed elements found");
+    let indices = cuckoo.get_hash_indices(&1u32.to_le_bytes());
+    assert!(cuckoo.contains_hash_indices(&indices));
+    assert!(!cuckoo.contains_hash_indices(&indices[..1].to_vec()));
+    assert!(!cuckoo.contains_hash_indices(&vec![512, indices[1]]));
+    assert!(!cuckoo.contains_hash_indices(&vec![indices[0], 0]));
+    assert!(!cuckoo.contains_hash_indices(&vec![indices[0], 1 << 10]));
+
+    cuckoo.clear();
+    assert!(cuckoo.is_empty());
+    assert!(!cuckoo.contains(&1u32.to_le_bytes()));
+}
+
+#[test]
+fn cuckoo_filter_full_test() {
+    let mut cuckoo = FilterBuilder::new(100, 0.001).build_cuckoo_filter();
+    let slots = cuckoo.buckets() * BUCKET_SIZE;
+    let added = (0..slots as u32 * 2).take_while(|i| cuckoo.try_add(&i.to_le_bytes())).count();
+    assert!(added > slots * 9 / 10 && added <= slots, "{added} of {slots}");
+    assert!(!cuckoo.try_add(b"hello"));
+    assert!((0..added as u32).all(|i| cuckoo.contains(&i.to_le_bytes())));
+
+    // removing frees a slot for the victim again
+    for i in 0..added as u32 / 2 {
+        cuckoo.remove(&i.to_le_bytes());
+    }
+    assert!((added as u32 / 2..added as u32).all(|i| cuckoo.contains(&i.to_le_bytes())));
+    assert!(cuckoo.try_add(b"hello"));
+    assert!(cuckoo.contains(b"hello"));
+}
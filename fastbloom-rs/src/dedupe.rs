@@ -0,0 +1,319 @@
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::aging::AgingBloomFilter;
+use crate::bloom::estimate_elements;
+use crate::builder::FilterBuilder;
+use crate::events::FilterEvents;
+use crate::Membership;
+
+/// Counters of a [DedupCache], see [DedupCache::stats].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct DedupStats {
+    /// Number of `seen` calls which returned `true`.
+    pub duplicates: u64,
+    /// Number of `seen` calls which returned `false`, i.e. keys recorded as new.
+    pub new_keys: u64,
+    /// Number of generations replaced by a new one.
+    pub rotations: u64,
+    /// Number of times the cache was written to its file.
+    pub persists: u64,
+}
+
+#[derive(Debug, Default)]
+struct DedupCounters {
+    duplicates: AtomicU64,
+    new_keys: AtomicU64,
+    rotations: AtomicU64,
+    persists: AtomicU64,
+}
+
+/// The generations and the number of new keys recorded in the newest one.
+struct Generations {
+    filter: AgingBloomFilter,
+    len: u64,
+    /// Number of rotations, the id of the newest generation.
+    id: u64,
+}
+
+struct Persistence {
+    path: PathBuf,
+    every: u64,
+    /// Serializes writers of the file.
+    lock: Mutex<()>,
+}
+
+/// Thread-safe "have I seen this key before?" cache for deduplicating event streams, put
+/// together from an [AgingBloomFilter] of two generations, rotation, persistence and
+/// [FilterEvents].
+///
+/// * **Rotation**: new keys go to the newest generation. Once it holds the
+///   [capacity](FilterBuilder::capacity) of the configuration it becomes the previous
+///   generation and an empty one takes over, so the false positive probability never exceeds
+///   the configured one. A key is remembered for one to two generations after its first
+///   sighting.
+/// * **Persistence**: a cache opened with [DedupCache::open] is restored from its file, and
+///   [DedupCache::persist_if_due] writes it back once `persist_every` new keys were recorded.
+///   The file is the checksummed format of [AgingBloomFilter::save], replaced atomically, so a
+///   crash leaves the previous version. The number of keys in the newest generation is
+///   estimated from its bits when the cache is restored.
+/// * **Metrics**: [DedupCache::stats] and the `on_saturate`/`on_rotate`/`on_persist`
+///   [FilterEvents].
+///
+/// The calls of `seen` are serialized, so a key is reported as new once (until it rotates out).
+///
+/// # Examples
+///
+/// ```rust
+/// use fastbloom_rs::{DedupCache, FilterBuilder};
+///
+/// let path = std::env::temp_dir().join(format!("dedupe-doc-{}.bin", std::process::id()));
+/// let cache = DedupCache::open(&path, FilterBuilder::new(100_000, 0.001), 1000).unwrap();
+/// assert!(!cache.seen(b"event-1"));
+/// assert!(cache.seen(b"event-1"));
+/// cache.persist().unwrap();
+/// drop(cache);
+///
+/// let cache = DedupCache::open(&path, FilterBuilder::new(100_000, 0.001), 1000).unwrap();
+/// assert!(cache.seen(b"event-1"));
+/// std::fs::remove_file(&path).unwrap();
+/// ```
+pub struct DedupCache {
+    config: FilterBuilder,
+    capacity: u64,
+    generations: Mutex<Generations>,
+    persistence: Option<Persistence>,
+    /// New keys since the last persist.
+    dirty: AtomicU64,
+    events: Option<Arc<dyn FilterEvents>>,
+    counters: DedupCounters,
+}
+
+impl DedupCache {
+    /// Builds an empty in-memory cache, each generation a filter configured by `config`.
+    pub fn new(mut config: FilterBuilder) -> Self {
+        config.complete();
+        let capacity = config.capacity().max(1);
+        let filter = AgingBloomFilter::new(config.clone(), 2);
+        DedupCache {
+            config,
+            capacity,
+            generations: Mutex::new(Generations { filter, len: 0, id: 0 }),
+            persistence: None,
+            dirty: AtomicU64::new(0),
+            events: None,
+            counters: DedupCounters::default(),
+        }
+    }
+
+    /// Opens the cache persisted at `path`, or an empty one if there is no file yet, which
+    /// [DedupCache::persist_if_due] writes once `persist_every` new keys were recorded. Fails
+    /// with [io::ErrorKind::InvalidData] when the file is not a cache of this configuration or
+    /// is corrupted.
+    pub fn open(path: impl Into<PathBuf>, config: FilterBuilder, persist_every: u64)
+                -> io::Result<Self> {
+        assert!(persist_every > 0, "persist_every must be positive!");
+        let path = path.into();
+        let mut cache = DedupCache::new(config);
+        match AgingBloomFilter::load(&path) {
+            Ok(filter) => {
+                if filter.generations() != 2 || !filter.config().is_compatible_to(&cache.config) {
+                    return Err(io::Error::new(io::ErrorKind::InvalidData,
+                                              "not a dedup cache of this configuration"));
+                }
+                let newest = filter.by_age().last().flatten().unwrap();
+                let (size, hashes) = (cache.config.size, cache.config.hashes);
+                let len = estimate_elements(newest.count_ones(), size, hashes);
+                let len = if len.is_finite() { len.round() as u64 } else { cache.capacity };
+                cache.generations = Mutex::new(Generations { filter, len, id: 0 });
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e),
+        }
+        cache.persistence = Some(Persistence { path, every: persist_every, lock: Mutex::new(()) });
+        Ok(cache)
+    }
+
+    /// Sets the [FilterEvents] notified of full generations, rotations and persists.
+    pub fn set_events(&mut self, events: Option<Arc<dyn FilterEvents>>) {
+        self.events = events;
+    }
+
+    /// Returns the configuration/builder of the generations.
+    pub fn config(&self) -> FilterBuilder {
+        self.config.clone()
+    }
+
+    /// Returns [true] if `key` was (probably) seen before, otherwise records it and returns
+    /// [false]. Rotates the generations once the newest one is full.
+    pub fn seen(&self, key: &[u8]) -> bool {
+        let saturated = {
+            let mut generations = self.generations.lock().unwrap();
+            if generations.filter.contains(key) {
+                self.counters.duplicates.fetch_add(1, Ordering::Relaxed);
+                return true;
+            }
+            generations.filter.add(key);
+            self.counters.new_keys.fetch_add(1, Ordering::Relaxed);
+            self.dirty.fetch_add(1, Ordering::Relaxed);
+            generations.len += 1;
+            (generations.len >= self.capacity).then(|| {
+                let newest = generations.filter.by_age().last().flatten().unwrap();
+                let fill_ratio = newest.count_ones() as f64 / self.config.size as f64;
+                (fill_ratio, Self::tick(&mut generations))
+            })
+        };
+        if let Some((fill_ratio, id)) = saturated {
+            if let Some(events) = &self.events {
+                events.on_saturate(fill_ratio);
+            }
+            self.rotated(id);
+        }
+        false
+    }
+
+    /// Tests whether `key` was (probably) seen before, without recording it.
+    pub fn contains(&self, key: &[u8]) -> bool {
+        self.generations.lock().unwrap().filter.contains(key)
+    }
+
+    /// Replaces the previous generation by the newest one and starts an empty one, forgetting
+    /// the keys of the previous generation.
+    pub fn rotate(&self) {
+        let id = Self::tick(&mut self.generations.lock().unwrap());
+        self.rotated(id);
+    }
+
+    /// Starts a new generation, returns its id.
+    fn tick(generations: &mut Generations) -> u64 {
+        generations.filter.tick();
+        generations.len = 0;
+        generations.id += 1;
+        generations.id
+    }
+
+    fn rotated(&self, id: u64) {
+        self.counters.rotations.fetch_add(1, Ordering::Relaxed);
+        if let Some(events) = &self.events {
+            events.on_rotate(id);
+        }
+    }
+
+    /// Writes the cache to its file if `persist_every` new keys were recorded since the last
+    /// write, and returns whether it did. Call it regularly, e.g. after every batch of events.
+    pub fn persist_if_due(&self) -> io::Result<bool> {
+        match &self.persistence {
+            Some(persistence) if self.dirty.load(Ordering::Relaxed) >= persistence.every => {
+                self.persist()?;
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    /// Writes the cache to its file with [AgingBloomFilter::save]. Does nothing for a cache
+    /// without file. The generations are copied, so `seen` isn't blocked while writing.
+    pub fn persist(&self) -> io::Result<()> {
+        let Some(persistence) = &self.persistence else { return Ok(()) };
+        let _writer = persistence.lock.lock().unwrap();
+        let (filter, dirty) = {
+            let generations = self.generations.lock().unwrap();
+            (generations.filter.clone(), self.dirty.swap(0, Ordering::Relaxed))
+        };
+        if let Err(e) = filter.save(&persistence.path) {
+            self.dirty.fetch_add(dirty, Ordering::Relaxed);
+            return Err(e);
+        }
+        self.counters.persists.fetch_add(1, Ordering::Relaxed);
+        if let Some(events) = &self.events {
+            events.on_persist(fs::metadata(&persistence.path)?.len());
+        }
+        Ok(())
+    }
+
+    /// Returns a snapshot of the counters.
+    pub fn stats(&self) -> DedupStats {
+        DedupStats {
+            duplicates: self.counters.duplicates.load(Ordering::Relaxed),
+            new_keys: self.counters.new_keys.load(Ordering::Relaxed),
+            rotations: self.counters.rotations.load(Ordering::Relaxed),
+            persists: self.counters.persists.load(Ordering::Relaxed),
+        }
+    }
+}
+
+#[test]
+fn dedup_cache_test() {
+    #[derive(Default)]
+    struct Events(AtomicU64, AtomicU64, Mutex<Vec<f64>>);
+
+    impl FilterEvents for Events {
+        fn on_saturate(&self, fill_ratio: f64) {
+            self.2.lock().unwrap().push(fill_ratio);
+        }
+
+        fn on_rotate(&self, _generation: u64) {
+            self.0.fetch_add(1, Ordering::Relaxed);
+        }
+
+        fn on_persist(&self, _bytes: u64) {
+            self.1.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    let path = std::env::temp_dir().join(format!("fastbloom-dedupe-{}.bin", std::process::id()));
+    let _ = fs::remove_file(&path);
+    let config = FilterBuilder::new(1000, 0.01);
+    let mut cache = DedupCache::open(&path, config.clone(), 500).unwrap();
+    let events = Arc::new(Events::default());
+    cache.set_events(Some(events.clone()));
+
+    std::thread::scope(|scope| {
+        for t in 0..4u32 {
+            let cache = &cache;
+            scope.spawn(move || {
+                for i in (t..1500).step_by(4) {
+                    cache.seen(&i.to_le_bytes());
+                }
+            });
+        }
+    });
+    // the keys of the first generation are still remembered after one rotation
+    let stats = cache.stats();
+    assert!(stats.new_keys > 1450, "{stats:?}");
+    assert_eq!(stats.rotations, 1);
+    assert_eq!(events.0.load(Ordering::Relaxed), 1);
+    let saturated = events.2.lock().unwrap().clone();
+    assert!(saturated.len() == 1 && (0.45..0.55).contains(&saturated[0]), "{saturated:?}");
+    assert!((0..1500u32).all(|i| cache.contains(&i.to_le_bytes())));
+    assert!((0..1500u32).all(|i| cache.seen(&i.to_le_bytes())));
+    assert_eq!(cache.stats().duplicates, stats.duplicates + 1500);
+
+    assert!(cache.persist_if_due().unwrap());
+    assert!(!cache.persist_if_due().unwrap());
+    assert_eq!(events.1.load(Ordering::Relaxed), 1);
+    drop(cache);
+
+    let cache = DedupCache::open(&path, config.clone(), 500).unwrap();
+    assert!((0..1500u32).all(|i| cache.seen(&i.to_le_bytes())));
+    // the restored newest generation rotates after about as many new keys as before
+    let len = cache.generations.lock().unwrap().len;
+    assert!((450..550).contains(&len), "{len} keys estimated");
+    cache.rotate();
+    cache.rotate();
+    assert!(!cache.seen(&0u32.to_le_bytes()));
+
+    let mut other = config.clone();
+    other.seed(3);
+    let err = DedupCache::open(&path, other, 500).err().unwrap();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    let mut corrupted = fs::read(&path).unwrap();
+    *corrupted.last_mut().unwrap() ^= 1;
+    fs::write(&path, corrupted).unwrap();
+    let err = DedupCache::open(&path, config, 500).err().unwrap();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    fs::remove_file(&path).unwrap();
+}
@@ -0,0 +1,164 @@
+use alloc::vec::Vec;
+
+use crate::{Deletable, Membership};
+use crate::bloom::BloomFilter;
+use crate::builder::FilterBuilder;
+
+/// Bloom filter supporting logical deletion with a second filter of tombstones: an element is
+/// present if it is in the main filter and not in the tombstones. Unlike a
+/// [crate::CountingBloomFilter] this keeps one bit per position, but a false positive of the
+/// tombstones hides a live element, and an element added again after its removal stays hidden.
+/// Both degrade as tombstones accumulate, so rebuild the filter from the live keys of the source
+/// of truth with [DeletableBloomFilter::compact] now and then.
+///
+/// # Examples
+///
+/// ```rust
+/// use fastbloom_rs::{Deletable, DeletableBloomFilter, FilterBuilder, Membership};
+///
+/// let mut filter = DeletableBloomFilter::new(FilterBuilder::new(10_000, 0.01));
+/// filter.add(b"hello");
+/// filter.add(b"world");
+/// filter.remove(b"hello");
+/// assert_eq!(filter.contains(b"hello"), false);
+///
+/// let stats = filter.compact([&b"world"[..]].into_iter());
+/// assert_eq!((stats.kept, stats.dropped), (1, 1));
+/// assert_eq!(filter.tombstones(), 0);
+/// assert_eq!(filter.contains(b"world"), true);
+/// ```
+#[derive(Clone)]
+#[derive(Debug)]
+pub struct DeletableBloomFilter {
+    main: BloomFilter,
+    tombstones: BloomFilter,
+    tombstone_count: u64,
+}
+
+/// Result of a [DeletableBloomFilter::compact].
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct CompactionStats {
+    /// Number of live keys added to the rebuilt filter.
+    pub kept: u64,
+    /// Number of removals whose tombstones were dropped.
+    pub dropped: u64,
+    /// Fraction of set bits of the main filter before the compaction.
+    pub density_before: f64,
+    /// Fraction of set bits of the main filter after the compaction.
+    pub density_after: f64,
+}
+
+impl CompactionStats {
+    /// Returns the fraction of bits reclaimed by the compaction.
+    pub fn reclaimed_density(&self) -> f64 {
+        (self.density_before - self.density_after).max(0.0)
+    }
+}
+
+impl DeletableBloomFilter {
+    /// Builds an empty filter from `config`; the tombstones use the same configuration.
+    pub fn new(mut config: FilterBuilder) -> Self {
+        let main = config.build_bloom_filter();
+        let tombstones = BloomFilter::new(config);
+        DeletableBloomFilter { main, tombstones, tombstone_count: 0 }
+    }
+
+    /// Returns the configuration/builder of the filter.
+    pub fn config(&self) -> FilterBuilder {
+        self.main.config()
+    }
+
+    /// Returns the number of removals since the last compaction.
+    pub fn tombstones(&self) -> u64 {
+        self.tombstone_count
+    }
+
+    /// Rebuilds the main filter from `live_keys`, e.g. a scan over the table the filter
+    /// indexes, and clears the tombstones. Every key of the scan is added: the tombstones can't
+    /// tell a removed key from a live one hidden by a false positive, so the source of truth
+    /// decides which keys are live. The keys are streamed, the rebuild only needs the memory of
+    /// one more main filter.
+    pub fn compact<'a>(&mut self, live_keys: impl Iterator<Item=&'a [u8]>) -> CompactionStats {
+        let mut rebuilt = BloomFilter::new(self.main.config());
+        let density_before = density(&self.main);
+        let mut stats = CompactionStats {
+            dropped: self.tombstone_count,
+            density_before,
+            ..CompactionStats::default()
+        };
+        for key in live_keys {
+            rebuilt.add(key);
+            stats.kept += 1;
+        }
+        stats.density_after = density(&rebuilt);
+        self.main = rebuilt;
+        self.tombstones.clear();
+        self.tombstone_count = 0;
+        stats
+    }
+}
+
+fn density(bloom: &BloomFilter) -> f64 {
+    bloom.bit_set.count_ones() as f64 / bloom.config.size as f64
+}
+
+impl Membership for DeletableBloomFilter {
+    fn add(&mut self, element: &[u8]) {
+        self.main.add(element);
+    }
+
+    fn contains(&self, element: &[u8]) -> bool {
+        self.main.contains(element) && !self.tombstones.contains(element)
+    }
+
+    fn get_hash_indices(&self, element: &[u8]) -> Vec<u64> {
+        self.main.get_hash_indices(element)
+    }
+
+    /// Tests the indices against the main filter only, tombstones are not taken into account.
+    fn contains_hash_indices(&self, indices: &Vec<u64>) -> bool {
+        self.main.contains_hash_indices(indices)
+    }
+
+    fn clear(&mut self) {
+        self.main.clear();
+        self.tombstones.clear();
+        self.tombstone_count = 0;
+    }
+}
+
+impl Deletable for DeletableBloomFilter {
+    /// Adds a tombstone for `element`, if it is present.
+    fn remove(&mut self, element: &[u8]) {
+        if self.contains(element) {
+            self.tombstones.add(element);
+            self.tombstone_count += 1;
+        }
+    }
+}
+
+#[test]
+fn deletable_compact_test() {
+    let keys: Vec<Vec<u8>> = (0..2000u32).map(|i| i.to_le_bytes().to_vec()).collect();
+    let mut filter = DeletableBloomFilter::new(FilterBuilder::new(2000, 0.01));
+    for key in keys.iter() {
+        filter.add(key);
+    }
+    for key in keys[..1000].iter() {
+        filter.remove(key);
+    }
+    assert_eq!(filter.tombstones(), 1000);
+    assert!(keys[..1000].iter().all(|key| !filter.contains(key)));
+
+    // tombstone false positives hide a few live keys until the compaction
+    let hidden = keys[1000..].iter().filter(|key| !filter.contains(key)).count();
+    assert!(hidden < 100, "{hidden} live keys hidden");
+
+    let stats = filter.compact(keys[1000..].iter().map(Vec::as_slice));
+    assert_eq!((stats.kept, stats.dropped), (1000, 1000));
+    assert!(stats.reclaimed_density() > 0.1, "{stats:?}");
+    assert_eq!(filter.tombstones(), 0);
+    assert!(keys[1000..].iter().all(|key| filter.contains(key)));
+    let removed = keys[..1000].iter().filter(|key| filter.contains(key)).count();
+    assert!(removed < 30, "{removed} removed keys present");
+}
@@ -0,0 +1,174 @@
+use alloc::vec::Vec;
+
+use crate::bloom::BloomFilter;
+use crate::codec::{put_varint, Reader};
+use crate::compat::MismatchError;
+use crate::vec::{Storage, StorageMut};
+
+const DELTA_MAGIC: &[u8; 4] = b"FBDL";
+
+impl<S: Storage> BloomFilter<S> {
+    /// Returns the bits set since the snapshot `previous` of the filter, for replicas which
+    /// hold `previous` and apply the delta with [BloomFilter::apply_delta]. Adds only set bits,
+    /// so a delta is about as large as the words touched by the adds in between and much
+    /// smaller than the filter. Bits cleared since the snapshot are not part of the delta.
+    /// Returns a [MismatchError] if `previous` is not compatible.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use fastbloom_rs::{FilterBuilder, FilterDelta, Membership};
+    ///
+    /// let mut primary = FilterBuilder::new(100_000, 0.01).build_bloom_filter();
+    /// let mut replica = primary.clone();
+    /// let previous = primary.clone();
+    /// primary.add(b"hello");
+    ///
+    /// let delta = primary.diff(&previous).unwrap();
+    /// let bytes = delta.to_bytes();
+    /// assert!(bytes.len() < 128);
+    /// assert!(replica.apply_delta(&FilterDelta::from_bytes(&bytes).unwrap()));
+    /// assert_eq!(replica.contains(b"hello"), true);
+    /// ```
+    pub fn diff<T: Storage>(&self, previous: &BloomFilter<T>)
+                            -> Result<FilterDelta, MismatchError> {
+        MismatchError::check(self.compatibility_key(), previous.compatibility_key())?;
+        let words = (0..self.bit_set.u64_words())
+            .map(|i| (i, self.bit_set.word_u64(i) & !previous.bit_set.word_u64(i)))
+            .filter(|(_, bits)| *bits != 0)
+            .collect();
+        Ok(FilterDelta {
+            size: self.config.size,
+            hashes: self.config.hashes,
+            seed: self.config.seed,
+            hasher: self.config.hasher_id(),
+            words,
+        })
+    }
+}
+
+impl<S: StorageMut> BloomFilter<S> {
+    /// Applies a delta of [BloomFilter::diff] by setting its bits, which turns the snapshot it
+    /// was computed from into the newer filter. Applying a delta twice or to a replica which
+    /// is ahead of the snapshot is harmless. Returns `false` and leaves the filter unchanged if
+    /// the delta is for a filter of another size, number of hashes, seed or hash function.
+    pub fn apply_delta(&mut self, delta: &FilterDelta) -> bool {
+        if (delta.size, delta.hashes, delta.seed, delta.hasher)
+            != (self.config.size, self.config.hashes, self.config.seed, self.config.hasher_id()) {
+            return false;
+        }
+        for &(i, bits) in &delta.words {
+            let word = self.bit_set.word_u64(i);
+            self.bit_set.set_word_u64(i, word | bits);
+        }
+        true
+    }
+}
+
+/// The bits set between two snapshots of a [BloomFilter], see [BloomFilter::diff].
+#[derive(Clone)]
+#[derive(Debug)]
+#[derive(PartialEq)]
+pub struct FilterDelta {
+    /// Size in bits of the filter.
+    size: u64,
+    hashes: u32,
+    seed: u64,
+    /// Id of the hash function of the filter.
+    hasher: u32,
+    /// The 64-bit words which changed, as `(word, bits set since the snapshot)` in word order.
+    words: Vec<(usize, u64)>,
+}
+
+impl FilterDelta {
+    /// Returns the number of changed 64-bit words.
+    pub fn len(&self) -> usize {
+        self.words.len()
+    }
+
+    /// Returns [true] if no bits were set between the snapshots.
+    pub fn is_empty(&self) -> bool {
+        self.words.is_empty()
+    }
+
+    /// Encodes the delta: the magic `FBDL`, then the filter size, hashes, the seed (u64 LE) and
+    /// the id of the hash function, the number of changed words (varints), then per word its
+    /// distance to the previous one (varint) and its new bits (u64 LE).
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = DELTA_MAGIC.to_vec();
+        put_varint(&mut out, self.size);
+        put_varint(&mut out, self.hashes as u64);
+        out.extend_from_slice(&self.seed.to_le_bytes());
+        put_varint(&mut out, self.hasher as u64);
+        put_varint(&mut out, self.words.len() as u64);
+        let mut next = 0;
+        for &(i, bits) in &self.words {
+            put_varint(&mut out, (i - next) as u64);
+            out.extend_from_slice(&bits.to_le_bytes());
+            next = i + 1;
+        }
+        out
+    }
+
+    /// Decodes a delta written by [FilterDelta::to_bytes]. Returns `None` if `bytes` is not a
+    /// valid delta.
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        let mut reader = Reader::new(bytes);
+        if reader.take(4)? != DELTA_MAGIC {
+            return None;
+        }
+        let size = reader.varint()?;
+        let hashes = u32::try_from(reader.varint()?).ok()?;
+        let seed = reader.u64()?;
+        let hasher = u32::try_from(reader.varint()?).ok()?;
+        let len = usize::try_from(reader.varint()?).ok()?;
+        let mut words = Vec::with_capacity(len.min(bytes.len() / 9));
+        let mut next = 0u64;
+        for _ in 0..len {
+            let i = next.checked_add(reader.varint()?)?;
+            if i >= size.div_ceil(64) {
+                return None;
+            }
+            words.push((usize::try_from(i).ok()?, reader.u64()?));
+            next = i + 1;
+        }
+        reader.is_empty().then_some(FilterDelta { size, hashes, seed, hasher, words })
+    }
+}
+
+#[test]
+fn filter_delta_test() {
+    use crate::builder::FilterBuilder;
+    use crate::Membership;
+
+    let mut primary = FilterBuilder::new(10_000, 0.01).build_bloom_filter();
+    (0..1000u32).for_each(|i| primary.add(&i.to_le_bytes()));
+    let mut replica = primary.clone();
+    let previous = primary.clone();
+    assert!(primary.diff(&previous).unwrap().is_empty());
+
+    (1000..1010u32).for_each(|i| primary.add(&i.to_le_bytes()));
+    let delta = primary.diff(&previous).unwrap();
+    assert!(!delta.is_empty() && delta.len() <= 10 * primary.config().hashes as usize);
+    let decoded = FilterDelta::from_bytes(&delta.to_bytes()).unwrap();
+    assert_eq!(decoded, delta);
+    assert!(replica.apply_delta(&decoded));
+    assert_eq!(replica.get_u8_array(), primary.get_u8_array());
+    // applying it again, or to a replica ahead of the snapshot, leaves the bits set
+    assert!(replica.apply_delta(&decoded));
+    assert_eq!(replica.get_u8_array(), primary.get_u8_array());
+    (1010..1020u32).for_each(|i| primary.add(&i.to_le_bytes()));
+    assert!(replica.apply_delta(&primary.diff(&previous).unwrap()));
+    assert_eq!(replica.get_u8_array(), primary.get_u8_array());
+
+    let bytes = delta.to_bytes();
+    assert!(FilterDelta::from_bytes(&bytes[..bytes.len() - 1]).is_none());
+    let mut other = FilterBuilder::new(1000, 0.01).build_bloom_filter();
+    assert!(!other.apply_delta(&delta));
+    assert!(primary.diff(&other).is_err());
+    let mut seeded = FilterBuilder::new(10_000, 0.01);
+    seeded.seed(1);
+    let mut seeded = seeded.build_bloom_filter();
+    assert!(!seeded.apply_delta(&delta));
+    assert!(seeded.get_u8_array().iter().all(|&byte| byte == 0));
+}
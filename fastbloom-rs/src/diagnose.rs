@@ -0,0 +1,175 @@
+use alloc::collections::BTreeMap;
+use alloc::vec;
+use core::fmt::{self, Display, Formatter};
+
+use smallvec::SmallVec;
+
+use crate::builder::FilterBuilder;
+#[cfg(not(feature = "std"))]
+use crate::float::Float;
+use crate::index::Indices;
+
+/// Skew above which [HashDiagnostics::is_skewed] reports the indices as not uniform, a
+/// probability of about `3e-5` for uniform ones.
+const SKEW_LIMIT: f64 = 4.0;
+
+/// Distribution of the indices a [FilterBuilder] derives for a stream of items, see
+/// [FilterBuilder::diagnose]: how many indices land in each 64-bit word of the filter, and how
+/// far that is from the uniform distribution of a good hash function. Skewed indices crowd some
+/// words and raise the false positive probability above the configured one, e.g. for keys
+/// which differ in bytes the hash function mixes poorly.
+#[derive(Clone, Debug, PartialEq)]
+pub struct HashDiagnostics {
+    /// Number of items.
+    pub items: u64,
+    /// Number of indices of all items.
+    pub indices: u64,
+    /// Number of 64-bit words of the filter.
+    pub words: u64,
+    /// Maps a number of indices to the number of words which that many indices land in, for
+    /// the numbers of some word.
+    pub occupancy: BTreeMap<u64, u64>,
+    /// Pearson's chi-squared statistic of the indices per word against the uniform
+    /// distribution, with `words - 1` degrees of freedom.
+    pub chi_squared: f64,
+    /// Number of items of which two indices are equal, which set fewer bits than the hashes.
+    pub self_collisions: u64,
+}
+
+impl HashDiagnostics {
+    /// Returns the mean number of indices per word.
+    pub fn expected_occupancy(&self) -> f64 {
+        self.indices as f64 / self.words.max(1) as f64
+    }
+
+    /// Returns the largest number of indices of a word.
+    pub fn max_occupancy(&self) -> u64 {
+        self.occupancy.last_key_value().map_or(0, |(&count, _)| count)
+    }
+
+    /// Returns the skew of the indices: the distance of [HashDiagnostics::chi_squared] from its
+    /// mean for uniform indices in standard deviations, about normally distributed around `0`
+    /// for a good hash function. Large values mean that some words get far more indices than
+    /// others.
+    pub fn skew(&self) -> f64 {
+        if self.words < 2 || self.indices == 0 {
+            return 0.0;
+        }
+        let freedom = (self.words - 1) as f64;
+        (self.chi_squared - freedom) / (2.0 * freedom).sqrt()
+    }
+
+    /// Returns [true] if the indices are very unlikely to be uniform, a skew above 4.
+    pub fn is_skewed(&self) -> bool {
+        self.skew() > SKEW_LIMIT
+    }
+}
+
+impl Display for HashDiagnostics {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{} items, {} indices in {} words: chi-squared {:.1}, skew {:.2}",
+               self.items, self.indices, self.words, self.chi_squared, self.skew())?;
+        write!(f, ", occupancy up to {} (mean {:.1})", self.max_occupancy(),
+               self.expected_occupancy())?;
+        if self.self_collisions > 0 {
+            write!(f, ", {} items with equal indices", self.self_collisions)?;
+        }
+        if self.is_skewed() {
+            write!(f, ", skewed")?;
+        }
+        Ok(())
+    }
+}
+
+impl FilterBuilder {
+    /// Hashes the items like a filter built by this builder and records how its indices are
+    /// distributed over the words of the filter, without building it, see [HashDiagnostics]. To
+    /// check a key format against the hashing, pass a sample of real keys: about as many as the
+    /// filter is sized for give one comparable to its occupancy. Takes a 64-bit counter per
+    /// word, as much memory as the filter.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use fastbloom_rs::FilterBuilder;
+    ///
+    /// let keys = (0..10_000u32).map(|i| format!("user:{i:08}"));
+    /// let diagnostics = FilterBuilder::new(10_000, 0.01).diagnose(keys);
+    /// assert_eq!(diagnostics.items, 10_000);
+    /// assert!(!diagnostics.is_skewed(), "{diagnostics}");
+    /// ```
+    pub fn diagnose<K: AsRef<[u8]>>(&self, items: impl IntoIterator<Item=K>) -> HashDiagnostics {
+        let mut config = self.clone();
+        config.complete();
+        let words = config.size.div_ceil(64);
+        let mut counts = vec![0u64; usize::try_from(words).expect("filter is too large!")];
+        let (mut len, mut indices, mut self_collisions) = (0u64, 0u64, 0u64);
+        for item in items {
+            let mut item_indices: SmallVec<[u64; 16]> =
+                Indices::new(item.as_ref(), &config).collect();
+            len += 1;
+            indices += item_indices.len() as u64;
+            for &index in &item_indices {
+                counts[(index / 64) as usize] += 1;
+            }
+            item_indices.sort_unstable();
+            if item_indices.windows(2).any(|pair| pair[0] == pair[1]) {
+                self_collisions += 1;
+            }
+        }
+
+        let expected = indices as f64 / words.max(1) as f64;
+        let mut occupancy = BTreeMap::new();
+        let mut chi_squared = 0.0;
+        for &count in &counts {
+            *occupancy.entry(count).or_insert(0) += 1;
+            if expected > 0.0 {
+                chi_squared += (count as f64 - expected).powi(2) / expected;
+            }
+        }
+        HashDiagnostics { items: len, indices, words, occupancy, chi_squared, self_collisions }
+    }
+}
+
+#[test]
+fn diagnose_test() {
+    use alloc::format;
+    use alloc::string::ToString;
+    use alloc::sync::Arc;
+
+    use crate::{FilterHasher, register_hasher};
+
+    let builder = FilterBuilder::new(100_000, 0.01);
+    let diagnostics = builder.diagnose((0..100_000u32).map(u32::to_le_bytes));
+    let words = builder.clone().build_bloom_filter().config().size.div_ceil(64);
+    assert_eq!((diagnostics.items, diagnostics.words), (100_000, words));
+    assert_eq!(diagnostics.indices, 100_000 * 7);
+    assert_eq!(diagnostics.occupancy.values().sum::<u64>(), words);
+    let weighted: u64 = diagnostics.occupancy.iter().map(|(n, w)| n * w).sum();
+    assert_eq!(weighted, diagnostics.indices);
+    assert!(diagnostics.skew().abs() < SKEW_LIMIT, "{diagnostics}");
+    assert!(!diagnostics.to_string().contains("skewed"));
+
+    /// Hashes only the last byte of a key, like a hash function blind to a key format.
+    struct LastByte;
+
+    impl FilterHasher for LastByte {
+        fn hash_pair(&self, element: &[u8], _seed: u64) -> (u64, u64) {
+            let byte = *element.last().unwrap_or(&0) as u64;
+            (byte.wrapping_mul(0x9e3779b97f4a7c15), byte.wrapping_mul(0xc2b2ae3d27d4eb4f) | 1)
+        }
+    }
+
+    assert!(register_hasher(2010, Arc::new(LastByte)));
+    let mut builder = FilterBuilder::new(100_000, 0.01);
+    builder.hasher(2010);
+    let diagnostics = builder.diagnose((0..100_000u32).map(|i| format!("key:{i:08}")));
+    assert!(diagnostics.is_skewed(), "{diagnostics}");
+    assert!(diagnostics.max_occupancy() as f64 > 100.0 * diagnostics.expected_occupancy());
+    assert!(diagnostics.to_string().ends_with(", skewed"));
+
+    let empty = FilterBuilder::new(100, 0.01).diagnose([b""; 0]);
+    assert_eq!((empty.items, empty.indices, empty.skew()), (0, 0, 0.0));
+    assert_eq!(empty.occupancy, BTreeMap::from([(0, empty.words)]));
+    assert_eq!(empty.max_occupancy(), 0);
+}
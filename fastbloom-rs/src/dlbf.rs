@@ -0,0 +1,184 @@
+use alloc::vec::Vec;
+
+use smallvec::SmallVec;
+
+use crate::{Deletable, Membership};
+use crate::builder::FilterBuilder;
+use crate::index::Indices;
+use crate::vec::BloomBitVec;
+
+/// Deletable Bloom filter (DlBF, Rothenberg et al.): the bits are split into regions, and a
+/// bitmap of one bit per region records whether an add ever set a bit which was already set in
+/// it. Bits in the other, collision-free regions belong to a single element, so a removal resets
+/// the bits of the element in those regions. An element can't be removed if all its bits are in
+/// regions with collisions; [DlBloomFilter::try_remove] then leaves it in the filter. Removals
+/// never cause false negatives.
+///
+/// This sits between [crate::BloomFilter] and [crate::CountingBloomFilter]: it takes one bit per
+/// region on top of the bits, instead of 4-bit counters, but removals only succeed while the
+/// filter is sparse enough to have collision-free regions. More regions make more elements
+/// removable.
+///
+/// # Examples
+///
+/// ```rust
+/// use fastbloom_rs::{FilterBuilder, Membership};
+///
+/// let mut filter = FilterBuilder::new(10_000, 0.01).build_dl_bloom_filter(4096);
+/// filter.add(b"hello");
+/// filter.add(b"world");
+/// assert_eq!(filter.try_remove(b"hello"), true);
+/// assert_eq!(filter.contains(b"hello"), false);
+/// assert_eq!(filter.contains(b"world"), true);
+/// ```
+#[derive(Clone)]
+#[derive(Debug)]
+pub struct DlBloomFilter {
+    config: FilterBuilder,
+    bits: BloomBitVec,
+    /// One bit per region, set once the region had a collision.
+    collisions: BloomBitVec,
+    /// Size of a region in bits, the last one may be smaller.
+    region_size: u64,
+    regions: u64,
+}
+
+impl DlBloomFilter {
+    /// Builds an empty filter configured by `config`, with its bits split into `regions` regions
+    /// (at most one per bit).
+    pub fn new(mut config: FilterBuilder, regions: u64) -> Self {
+        assert!(regions > 0, "DlBloomFilter needs a region!");
+        config.complete();
+        let regions = regions.min(config.size.max(1));
+        let region_size = config.size.div_ceil(regions).max(1);
+        let words = (config.size / usize::BITS as u64) as usize;
+        DlBloomFilter {
+            bits: BloomBitVec::new(words),
+            collisions: BloomBitVec::new(regions.div_ceil(usize::BITS as u64) as usize),
+            config,
+            region_size,
+            regions,
+        }
+    }
+
+    /// Returns the configuration/builder of the filter.
+    pub fn config(&self) -> FilterBuilder {
+        self.config.clone()
+    }
+
+    /// Returns the number of regions.
+    pub fn regions(&self) -> u64 {
+        self.regions
+    }
+
+    /// Returns the number of regions without collisions, whose bits can still be reset.
+    pub fn collision_free_regions(&self) -> u64 {
+        self.regions - self.collisions.count_ones()
+    }
+
+    #[inline]
+    fn region(&self, index: u64) -> usize {
+        (index / self.region_size) as usize
+    }
+
+    /// Returns [true] if `element` is present and [DlBloomFilter::try_remove] would remove it,
+    /// i.e. one of its bits is in a collision-free region.
+    pub fn is_removable(&self, element: &[u8]) -> bool {
+        let indices: SmallVec<[u64; 16]> = Indices::new(element, &self.config).collect();
+        self.contains_indices(&indices)
+            && indices.iter().any(|&index| !self.collisions.get(self.region(index)))
+    }
+
+    /// Removes `element` by resetting its bits in collision-free regions and returns whether it
+    /// did, i.e. the element was present and is absent now. Like with a counting filter, only
+    /// remove elements which were added, removing a false positive resets bits of other
+    /// elements.
+    pub fn try_remove(&mut self, element: &[u8]) -> bool {
+        let indices: SmallVec<[u64; 16]> = Indices::new(element, &self.config).collect();
+        if !self.contains_indices(&indices) {
+            return false;
+        }
+        let mut removed = false;
+        for &index in &indices {
+            if !self.collisions.get(self.region(index)) {
+                self.bits.unset(index as usize);
+                removed = true;
+            }
+        }
+        removed
+    }
+
+    fn contains_indices(&self, indices: &[u64]) -> bool {
+        !indices.is_empty() && indices.iter().all(|&index| self.bits.get(index as usize))
+    }
+}
+
+impl Membership for DlBloomFilter {
+    /// Adds the passed value, marking the regions of its bits which were already set.
+    fn add(&mut self, element: &[u8]) {
+        let indices = Indices::new(element, &self.config);
+        assert!(!indices.is_empty(), "cannot add to a filter without capacity!");
+        for index in indices {
+            if self.bits.get(index as usize) {
+                let region = self.region(index);
+                self.collisions.set(region);
+            } else {
+                self.bits.set(index as usize);
+            }
+        }
+    }
+
+    fn contains(&self, element: &[u8]) -> bool {
+        let indices: SmallVec<[u64; 16]> = Indices::new(element, &self.config).collect();
+        self.contains_indices(&indices)
+    }
+
+    fn get_hash_indices(&self, element: &[u8]) -> Vec<u64> {
+        Indices::new(element, &self.config).collect()
+    }
+
+    fn contains_hash_indices(&self, indices: &Vec<u64>) -> bool {
+        self.contains_indices(indices)
+    }
+
+    /// Removes all elements, which also makes all regions collision-free again.
+    fn clear(&mut self) {
+        self.bits.clear();
+        self.collisions.clear();
+    }
+}
+
+impl Deletable for DlBloomFilter {
+    /// Removes `element` if possible, see [DlBloomFilter::try_remove].
+    fn remove(&mut self, element: &[u8]) {
+        self.try_remove(element);
+    }
+}
+
+#[test]
+fn dl_bloom_filter_test() {
+    let mut filter = FilterBuilder::new(1000, 0.01).build_dl_bloom_filter(1200);
+    assert_eq!(filter.collision_free_regions(), filter.regions());
+    for i in 0..1000u32 {
+        filter.add(&i.to_le_bytes());
+    }
+    assert!(filter.collision_free_regions() < filter.regions());
+
+    let mut removed = 0;
+    for i in 0..500u32 {
+        let removable = filter.is_removable(&i.to_le_bytes());
+        assert_eq!(filter.try_remove(&i.to_le_bytes()), removable);
+        if removable {
+            removed += 1;
+            assert!(!filter.contains(&i.to_le_bytes()));
+        }
+    }
+    // about two thirds of the elements have a bit in a collision-free region at capacity.
+    assert!(removed > 250, "{removed} removed");
+    // no false negatives for the elements which were not removed.
+    assert!((500..1000u32).all(|i| filter.contains(&i.to_le_bytes())));
+
+    filter.clear();
+    assert_eq!(filter.collision_free_regions(), filter.regions());
+    assert!(!filter.try_remove(&999u32.to_le_bytes()));
+}
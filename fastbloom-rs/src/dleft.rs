@@ -0,0 +1,312 @@
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::builder::FilterBuilder;
+#[cfg(not(feature = "std"))]
+use crate::float::Float;
+use crate::index::hash_pair;
+use crate::vec::{Storage, StorageMut};
+use crate::{Deletable, Membership};
+
+/// Number of subtables, an element has one candidate bucket in each.
+const TABLES: usize = 4;
+/// Number of cells in one bucket.
+const CELLS: usize = 8;
+/// Share of the cells the filter is sized to fill, overflowing buckets are very rare below it.
+const LOAD_FACTOR: f64 = 0.75;
+/// Bits of the counter of a cell, a counter of `0` marks an empty cell.
+const COUNTER_BITS: u32 = 4;
+const MAX_COUNT: usize = (1 << COUNTER_BITS) - 1;
+/// Odd multipliers of the permutations of the subtables.
+const MULTIPLIERS: [u64; TABLES] = [
+    0x9e3779b97f4a7c15, 0xc2b2ae3d27d4eb4f, 0x165667b19e3779f9, 0xd6e8feb86659fd93,
+];
+
+/// d-left counting Bloom filter (Bonomi et al.): a fingerprint per element with a small counter,
+/// in four subtables of buckets of eight cells. An element has one candidate bucket per subtable
+/// and goes to the least loaded one (the leftmost on a tie), which keeps the buckets evenly
+/// filled. Each subtable derives bucket and remainder from a permutation of the fingerprint, so
+/// a stored remainder belongs to exactly one fingerprint and removals never take out the entry
+/// of another element. It needs less memory than a [crate::CountingBloomFilter] with 4-bit
+/// counters at the same false positive probability, down to about half when the number of
+/// buckets (a power of two) is not rounded up much.
+///
+/// The filter is sized for [FilterBuilder::capacity] elements, the remainders are just wide
+/// enough for the false positive probability of the builder. Adding an element whose buckets are
+/// all full fails, [DLeftCountingFilter::try_add] reports it while [Membership::add] panics.
+/// Counters saturate at 15 and then stay put like those of a [crate::CountingBloomFilter]. Only
+/// remove elements which were added.
+///
+/// # Examples
+///
+/// ```rust
+/// use fastbloom_rs::{Deletable, FilterBuilder, Membership};
+///
+/// let mut filter = FilterBuilder::new(10_000, 0.01).build_d_left_counting_filter();
+/// filter.add(b"hello");
+/// filter.add(b"hello");
+/// filter.add(b"world");
+/// assert_eq!(filter.estimate_count(b"hello"), 2);
+///
+/// filter.remove(b"hello");
+/// filter.remove(b"hello");
+/// assert_eq!(filter.contains(b"hello"), false);
+/// assert_eq!(filter.contains(b"world"), true);
+/// ```
+#[derive(Clone)]
+#[derive(Debug)]
+pub struct DLeftCountingFilter<S = Vec<usize>> {
+    config: FilterBuilder,
+    storage: S,
+    /// Number of buckets of a subtable, a power of two.
+    buckets: usize,
+    remainder_bits: u32,
+    /// Number of cells packed into a storage word and words of a bucket.
+    cells_per_word: usize,
+    words_per_bucket: usize,
+    len: usize,
+}
+
+impl DLeftCountingFilter {
+    /// Builds an empty filter for `config.capacity()` elements at
+    /// `config.false_positive_probability`.
+    pub fn new(config: FilterBuilder) -> Self {
+        DLeftCountingFilter::new_in(config, ())
+    }
+}
+
+impl<S: Storage> DLeftCountingFilter<S> {
+    /// Like [DLeftCountingFilter::new], on a storage of type `S`.
+    pub fn new_in(config: FilterBuilder, init: S::Init) -> Self {
+        let p = config.false_positive_probability;
+        assert!(p > 0.0 && p < 1.0, "False positive probability must be in (0, 1)!");
+        // a lookup compares the occupied cells of TABLES buckets
+        let compared = TABLES as f64 * CELLS as f64 * LOAD_FACTOR;
+        let remainder_bits = ((compared / p).ln() / 2f64.ln()).ceil() as u32;
+        let remainder_bits = remainder_bits.clamp(4, 32 - COUNTER_BITS);
+        let cells = config.capacity() as f64 / LOAD_FACTOR;
+        let buckets = (cells / (TABLES * CELLS) as f64).ceil() as usize;
+        let buckets = buckets.max(1).next_power_of_two();
+        let cells_per_word = (usize::BITS / (remainder_bits + COUNTER_BITS)) as usize;
+        let words_per_bucket = CELLS.div_ceil(cells_per_word);
+        DLeftCountingFilter {
+            storage: S::new(TABLES * buckets * words_per_bucket, init),
+            config,
+            buckets,
+            remainder_bits,
+            cells_per_word,
+            words_per_bucket,
+            len: 0,
+        }
+    }
+
+    /// Returns the configuration of the filter.
+    pub fn config(&self) -> FilterBuilder {
+        self.config.clone()
+    }
+
+    /// Returns the number of distinct fingerprints in the filter.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns [true] if the filter holds no fingerprints.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the number of bits of a remainder, the part of a fingerprint stored in a cell.
+    pub fn remainder_bits(&self) -> u32 {
+        self.remainder_bits
+    }
+
+    /// Returns the share of cells which hold a fingerprint.
+    pub fn load_factor(&self) -> f64 {
+        self.len as f64 / (TABLES * self.buckets * CELLS) as f64
+    }
+
+    /// Returns the memory taken by the cells in bytes.
+    pub fn memory_usage(&self) -> usize {
+        self.storage.slots() * size_of::<usize>()
+    }
+
+    /// Returns the number of times the element was added, subject to the false positive
+    /// probability, and `15` for saturated counters.
+    pub fn estimate_count(&self, element: &[u8]) -> usize {
+        let fingerprint = self.fingerprint(element);
+        self.find(fingerprint).map_or(0, |cell| self.cell(cell).1)
+    }
+
+    /// Returns the fingerprint of an element, `log2(buckets) + remainder_bits` bits.
+    #[inline]
+    fn fingerprint(&self, element: &[u8]) -> u64 {
+        hash_pair(element, &self.config).0 & self.fingerprint_mask()
+    }
+
+    #[inline]
+    fn fingerprint_mask(&self) -> u64 {
+        let bits = self.buckets.trailing_zeros() + self.remainder_bits;
+        u64::MAX >> (64 - bits)
+    }
+
+    /// Returns the bucket and the remainder of a fingerprint in subtable `table`, a bijection of
+    /// the fingerprints.
+    #[inline]
+    fn locate(&self, table: usize, fingerprint: u64) -> (usize, usize) {
+        let mask = self.fingerprint_mask();
+        let bits = mask.count_ones();
+        let mut x = fingerprint.wrapping_mul(MULTIPLIERS[table]) & mask;
+        x ^= x >> bits.div_ceil(2);
+        let bucket = table * self.buckets + (x >> self.remainder_bits) as usize;
+        (bucket, (x & ((1 << self.remainder_bits) - 1)) as usize)
+    }
+
+    /// Returns the storage word and the shift of a cell.
+    #[inline]
+    fn position(&self, cell: usize) -> (usize, u32) {
+        let (bucket, index) = (cell / CELLS, cell % CELLS);
+        let word = bucket * self.words_per_bucket + index / self.cells_per_word;
+        (word, ((index % self.cells_per_word) as u32) * (self.remainder_bits + COUNTER_BITS))
+    }
+
+    /// Returns the remainder and the counter of a cell.
+    #[inline]
+    fn cell(&self, cell: usize) -> (usize, usize) {
+        let (word, shift) = self.position(cell);
+        let value = self.storage.get(word) >> shift;
+        ((value >> COUNTER_BITS) & ((1 << self.remainder_bits) - 1), value & MAX_COUNT)
+    }
+
+    /// Returns the cell holding `fingerprint`.
+    fn find(&self, fingerprint: u64) -> Option<usize> {
+        (0..TABLES).find_map(|table| {
+            let (bucket, remainder) = self.locate(table, fingerprint);
+            (bucket * CELLS..(bucket + 1) * CELLS).find(|&cell| {
+                let (stored, count) = self.cell(cell);
+                count > 0 && stored == remainder
+            })
+        })
+    }
+
+    /// Returns the number of occupied cells of a bucket.
+    fn load(&self, bucket: usize) -> usize {
+        (bucket * CELLS..(bucket + 1) * CELLS).filter(|&cell| self.cell(cell).1 > 0).count()
+    }
+}
+
+impl<S: StorageMut> DLeftCountingFilter<S> {
+    /// Adds an element, returns [false] and leaves the filter unchanged if all its buckets are
+    /// full.
+    pub fn try_add(&mut self, element: &[u8]) -> bool {
+        let fingerprint = self.fingerprint(element);
+        if let Some(cell) = self.find(fingerprint) {
+            let (remainder, count) = self.cell(cell);
+            self.set_cell(cell, remainder, (count + 1).min(MAX_COUNT));
+            return true;
+        }
+        let target = (0..TABLES)
+            .map(|table| self.locate(table, fingerprint))
+            .map(|(bucket, remainder)| (self.load(bucket), bucket, remainder))
+            .filter(|&(load, _, _)| load < CELLS)
+            .min_by_key(|&(load, _, _)| load);
+        let Some((_, bucket, remainder)) = target else {
+            return false;
+        };
+        let cell = (bucket * CELLS..(bucket + 1) * CELLS)
+            .find(|&cell| self.cell(cell).1 == 0)
+            .unwrap();
+        self.set_cell(cell, remainder, 1);
+        self.len += 1;
+        true
+    }
+
+    #[inline]
+    fn set_cell(&mut self, cell: usize, remainder: usize, count: usize) {
+        let (word, shift) = self.position(cell);
+        let mask = (usize::MAX >> (usize::BITS - self.remainder_bits - COUNTER_BITS)) << shift;
+        let value = ((remainder << COUNTER_BITS) | count) << shift;
+        self.storage.update(word, |slot| Some(slot & !mask | value));
+    }
+}
+
+impl<S: StorageMut> Membership for DLeftCountingFilter<S> {
+    /// Adds the fingerprint of the passed value or increments its counter. Panics if the
+    /// buckets of the element are full, see [DLeftCountingFilter::try_add].
+    fn add(&mut self, element: &[u8]) {
+        assert!(self.try_add(element), "DLeftCountingFilter is full!");
+    }
+
+    /// Tests whether the fingerprint of an element is present in the filter.
+    #[inline]
+    fn contains(&self, element: &[u8]) -> bool {
+        self.find(self.fingerprint(element)).is_some()
+    }
+
+    /// Get the fingerprint of the element.
+    fn get_hash_indices(&self, element: &[u8]) -> Vec<u64> {
+        vec![self.fingerprint(element)]
+    }
+
+    /// Tests whether a fingerprint from `get_hash_indices` is present in the filter.
+    fn contains_hash_indices(&self, indices: &Vec<u64>) -> bool {
+        self.find(indices[0] & self.fingerprint_mask()).is_some()
+    }
+
+    /// Removes all fingerprints from the filter.
+    fn clear(&mut self) {
+        self.storage.clear();
+        self.len = 0;
+    }
+}
+
+impl<S: StorageMut> Deletable for DLeftCountingFilter<S> {
+    /// Decrements the counter of the element and frees its cell at zero. Saturated counters
+    /// are kept.
+    fn remove(&mut self, element: &[u8]) {
+        if let Some(cell) = self.find(self.fingerprint(element)) {
+            match self.cell(cell) {
+                (_, MAX_COUNT) => {}
+                (_, 1) => {
+                    self.set_cell(cell, 0, 0);
+                    self.len -= 1;
+                }
+                (remainder, count) => self.set_cell(cell, remainder, count - 1),
+            }
+        }
+    }
+}
+
+#[test]
+fn d_left_counting_filter_test() {
+    let mut filter = FilterBuilder::new(10_000, 0.01).build_d_left_counting_filter();
+    for i in 0..10_000u32 {
+        filter.add(&i.to_le_bytes());
+    }
+    assert!((0..10_000u32).all(|i| filter.contains(&i.to_le_bytes())));
+    let false_positives = (10_000..110_000u32).filter(|i| filter.contains(&i.to_le_bytes()))
+        .count();
+    assert!(false_positives < 1300, "{false_positives} false positives");
+    // a counting filter takes 4 bits per position
+    let counting_bytes = FilterBuilder::new(10_000, 0.01).checked_size().unwrap() as usize / 2;
+    assert!(filter.memory_usage() < counting_bytes, "{}", filter.memory_usage());
+
+    for i in 0..5000u32 {
+        filter.remove(&i.to_le_bytes());
+    }
+    assert!((5000..10_000u32).all(|i| filter.contains(&i.to_le_bytes())));
+    let left = (0..5000u32).filter(|i| filter.contains(&i.to_le_bytes())).count();
+    assert!(left < 100, "{left} removed elements found");
+
+    filter.add(b"hot");
+    filter.add(b"hot");
+    assert_eq!(filter.estimate_count(b"hot"), 2);
+    for _ in 0..20 {
+        filter.add(b"hot");
+    }
+    assert_eq!(filter.estimate_count(b"hot"), MAX_COUNT);
+    filter.remove(b"hot");
+    assert_eq!(filter.estimate_count(b"hot"), MAX_COUNT);
+
+    filter.clear();
+    assert!(filter.is_empty() && !filter.contains(&9999u32.to_le_bytes()));
+}
@@ -0,0 +1,120 @@
+use core::mem;
+
+use crate::bloom::BloomFilter;
+use crate::builder::FilterBuilder;
+use crate::Membership;
+
+/// Pair of Bloom filters for continuous dedup which never stops deduplicating while it clears:
+/// adds go to an active and a standby filter, queries are answered by the active one, and
+/// [DoubleBufferedBloomFilter::rotate] swaps them and clears the new standby. After a rotation
+/// the active filter holds the adds since the previous rotation, so an element is seen for at
+/// least one and at most two rotation periods after its add, and neither filter holds more
+/// than the adds of two periods. Both filters are sized by the [FilterBuilder].
+///
+/// Unlike an [AgingBloomFilter](crate::AgingBloomFilter) of two generations, a query probes a
+/// single filter, at the cost of setting the bits of an add twice.
+///
+/// # Examples
+///
+/// ```rust
+/// use fastbloom_rs::FilterBuilder;
+///
+/// let mut filter = FilterBuilder::new(10_000, 0.01).build_double_buffered_bloom_filter();
+/// filter.add(b"hello");
+/// filter.rotate();
+/// // still seen for the period after its add
+/// assert_eq!(filter.contains(b"hello"), true);
+/// filter.rotate();
+/// assert_eq!(filter.contains(b"hello"), false);
+/// ```
+#[derive(Clone)]
+#[derive(Debug)]
+pub struct DoubleBufferedBloomFilter {
+    active: BloomFilter,
+    standby: BloomFilter,
+    rotations: u64,
+}
+
+impl DoubleBufferedBloomFilter {
+    /// Builds a pair of empty filters configured by `config`.
+    pub fn new(config: FilterBuilder) -> Self {
+        let active = BloomFilter::new(config);
+        let standby = BloomFilter::new(active.config());
+        DoubleBufferedBloomFilter { active, standby, rotations: 0 }
+    }
+
+    /// Returns the configuration/builder of the filters.
+    pub fn config(&self) -> FilterBuilder {
+        self.active.config()
+    }
+
+    /// Returns the filter answering the queries.
+    pub fn active(&self) -> &BloomFilter {
+        &self.active
+    }
+
+    /// Returns the filter of the adds since the last rotation, which becomes the active one on
+    /// the next rotation.
+    pub fn standby(&self) -> &BloomFilter {
+        &self.standby
+    }
+
+    /// Returns the number of rotations since the filter was built or cleared.
+    pub fn rotations(&self) -> u64 {
+        self.rotations
+    }
+
+    /// Adds the element to both filters, hashing it once.
+    pub fn add(&mut self, element: &[u8]) {
+        let hash = self.active.hash(element);
+        self.active.insert_hash(hash);
+        self.standby.insert_hash(hash);
+    }
+
+    /// Tests whether the element was added within the last one or two rotation periods (subject
+    /// to the false positive probability).
+    pub fn contains(&self, element: &[u8]) -> bool {
+        self.active.contains(element)
+    }
+
+    /// Makes the standby filter the active one and clears the previous active filter, which
+    /// becomes the standby. Queries see the adds since the previous rotation throughout.
+    pub fn rotate(&mut self) {
+        mem::swap(&mut self.active, &mut self.standby);
+        self.standby.clear();
+        self.rotations += 1;
+    }
+
+    /// Removes all elements from both filters.
+    pub fn clear(&mut self) {
+        self.active.clear();
+        self.standby.clear();
+        self.rotations = 0;
+    }
+}
+
+#[test]
+fn double_buffered_bloom_filter_test() {
+    let mut filter = FilterBuilder::new(10_000, 0.01).build_double_buffered_bloom_filter();
+    for i in 0..1000u32 {
+        filter.add(&i.to_le_bytes());
+    }
+    filter.rotate();
+    for i in 1000..2000u32 {
+        filter.add(&i.to_le_bytes());
+    }
+    assert!((0..2000u32).all(|i| filter.contains(&i.to_le_bytes())));
+    assert!((1000..2000u32).all(|i| filter.standby().contains(&i.to_le_bytes())));
+    assert!(filter.active().compatibility_key() == filter.standby().compatibility_key());
+
+    filter.rotate();
+    assert_eq!(filter.rotations(), 2);
+    assert!((1000..2000u32).all(|i| filter.contains(&i.to_le_bytes())));
+    let forgotten = (0..1000u32).filter(|i| filter.contains(&i.to_le_bytes())).count();
+    assert!(forgotten < 30, "{forgotten} false positives");
+    assert_eq!(filter.standby().bits_set(), 0);
+
+    filter.clear();
+    assert_eq!(filter.rotations(), 0);
+    assert!(!filter.contains(&1500u32.to_le_bytes()));
+}
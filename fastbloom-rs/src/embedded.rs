@@ -0,0 +1,181 @@
+use alloc::vec::Vec;
+
+use crate::{Hashes, MembershipDetail};
+use crate::bloom::BloomFilter;
+use crate::builder::FilterBuilder;
+use crate::codec::{put_hashing, Reader};
+use crate::compat::FormatFeatures;
+use crate::index::Indices;
+use crate::vec::{BloomBitVec, le_word, Storage};
+
+const STATIC_MAGIC: &[u8; 4] = b"FBST";
+
+impl<S: Storage> BloomFilter<S> {
+    /// Serializes the filter for [StaticFilterRef::from_static]: a header with the parameters
+    /// needed to query the filter followed by the bit vector as little-endian words.
+    pub fn to_static_bytes(&self) -> Vec<u8> {
+        let storage = &self.bit_set.storage;
+        let mut out = Vec::with_capacity(32 + storage.slots() * size_of::<usize>());
+        put_static_header(&mut out, &self.config);
+        for slot in 0..storage.slots() {
+            out.extend_from_slice(&self.bit_set.word(slot).to_le_bytes());
+        }
+        out
+    }
+}
+
+/// Appends the header of [BloomFilter::to_static_bytes], which the bits follow.
+pub(crate) fn put_static_header(out: &mut Vec<u8>, config: &FilterBuilder) {
+    out.extend_from_slice(STATIC_MAGIC);
+    put_hashing(out, config, 0, FormatFeatures::default());
+    out.extend_from_slice(&config.size.to_le_bytes());
+}
+
+/// Reads the header of [BloomFilter::to_static_bytes], returns the configuration.
+pub(crate) fn parse_static_header(reader: &mut Reader) -> Option<FilterBuilder> {
+    if reader.take(4)? != STATIC_MAGIC {
+        return None;
+    }
+    let hashing = reader.hashing()?;
+    if !hashing.features.is_empty() {
+        return None;
+    }
+    let size = reader.u64()?;
+    if size == 0 || size % 64 != 0 {
+        return None;
+    }
+    let mut config = FilterBuilder::from_size_and_hashes(size, hashing.hashes);
+    hashing.apply(&mut config)?;
+    Some(config)
+}
+
+/// Splits bytes written by [BloomFilter::to_static_bytes] into the configuration and the bits.
+fn parse_static(bytes: &[u8]) -> Option<(FilterBuilder, &[u8])> {
+    let mut reader = Reader::new(bytes);
+    let config = parse_static_header(&mut reader)?;
+    let bits = reader.take(usize::try_from(config.size / 8).ok()?)?;
+    reader.is_empty().then_some((config, bits))
+}
+
+impl BloomFilter {
+    /// Restores an owned filter from bytes written by [BloomFilter::to_static_bytes], e.g. one
+    /// downloaded at runtime, which can't be a [StaticFilterRef]. Returns `None` when `bytes`
+    /// is not such a filter.
+    pub fn from_static_bytes(bytes: &[u8]) -> Option<Self> {
+        let (config, bits) = parse_static(bytes)?;
+        let storage = bits.chunks_exact(size_of::<usize>())
+            .map(|word| le_word(usize::from_le_bytes(word.try_into().unwrap())))
+            .collect();
+        Some(BloomFilter::from_bit_vec(config, BloomBitVec::with_storage(storage)))
+    }
+}
+
+/// Read-only Bloom filter probing directly against a static byte slice written by
+/// [BloomFilter::to_static_bytes], so small filters (e.g. blocklists) can be compiled into a
+/// binary with `include_bytes!` and used with zero startup cost. Only the header is validated
+/// at construction, the bits are never copied.
+///
+/// # Examples
+///
+/// ```rust
+/// use fastbloom_rs::{FilterBuilder, Membership, StaticFilterRef};
+///
+/// let mut bloom = FilterBuilder::new(1000, 0.01).build_bloom_filter();
+/// bloom.add(b"evil.example.com");
+/// // usually `include_bytes!("blocklist.bin")`
+/// let bytes: &'static [u8] = bloom.to_static_bytes().leak();
+///
+/// let blocklist = StaticFilterRef::from_static(bytes).unwrap();
+/// assert_eq!(blocklist.contains(b"evil.example.com"), true);
+/// assert_eq!(blocklist.contains(b"example.com"), false);
+/// ```
+#[derive(Clone)]
+#[derive(Debug)]
+pub struct StaticFilterRef {
+    config: FilterBuilder,
+    bits: &'static [u8],
+}
+
+impl StaticFilterRef {
+    /// Validates the header of `bytes`. Returns `None` when it is not a filter written by
+    /// [BloomFilter::to_static_bytes] or the length doesn't match the size in the header.
+    pub fn from_static(bytes: &'static [u8]) -> Option<Self> {
+        let (config, bits) = parse_static(bytes)?;
+        Some(StaticFilterRef { config, bits })
+    }
+
+    /// Returns the configuration/builder of the filter.
+    pub fn config(&self) -> FilterBuilder {
+        self.config.clone()
+    }
+
+    #[inline]
+    fn get(&self, index: u64) -> bool {
+        self.bits[(index >> 3) as usize] & (1 << (index & 7)) != 0
+    }
+
+    /// Tests whether an element is present in the filter (subject to the specified false
+    /// positive rate).
+    #[inline]
+    pub fn contains(&self, element: &[u8]) -> bool {
+        Indices::new(element, &self.config).all(|index| self.get(index))
+    }
+
+    /// Same as [BloomFilter::contains_detailed].
+    pub fn contains_detailed(&self, element: &[u8]) -> MembershipDetail {
+        let probes_set = Indices::new(element, &self.config).filter(|i| self.get(*i)).count();
+        MembershipDetail::from_probes(probes_set as u32, self.config.hashes)
+    }
+}
+
+impl Hashes for StaticFilterRef {
+    ///  Returns the hash function number of the filter.
+    fn hashes(&self) -> u32 {
+        self.config.hashes
+    }
+}
+
+#[test]
+fn static_filter_test() {
+    use crate::{IndexScheme, Membership};
+
+    let mut builder = FilterBuilder::new(1000, 0.01);
+    builder.seed(9);
+    builder.index_scheme(IndexScheme::Extended);
+    builder.expected_key_len(4);
+    let mut bloom = builder.build_bloom_filter();
+    for i in 0..1000u32 {
+        bloom.add(&i.to_le_bytes());
+    }
+    let bytes: &'static [u8] = bloom.to_static_bytes().leak();
+    let filter = StaticFilterRef::from_static(bytes).unwrap();
+    assert!(filter.config().is_compatible_to(&bloom.config()));
+    for i in 0..2000u32 {
+        assert_eq!(filter.contains(&i.to_le_bytes()), bloom.contains(&i.to_le_bytes()));
+    }
+
+    let owned = BloomFilter::from_static_bytes(bytes).unwrap();
+    assert_eq!(owned.get_u8_array(), bloom.get_u8_array());
+    assert!(owned.config().is_compatible_to(&bloom.config()));
+
+    assert!(StaticFilterRef::from_static(&bytes[..bytes.len() - 1]).is_none());
+    assert!(StaticFilterRef::from_static(&bytes[1..]).is_none());
+}
+
+// the serialized bytes are pinned, so they must not change with the pointer width or the
+// endianness of the target (e.g. x86_64 and wasm32).
+#[cfg(feature = "canonical-u64")]
+#[test]
+fn static_bytes_layout_test() {
+    use xxhash_rust::xxh3::xxh3_64;
+
+    use crate::Membership;
+
+    let mut bloom = FilterBuilder::from_size_and_hashes(1024, 3).build_bloom_filter();
+    for i in 0..50u32 {
+        bloom.add(&i.to_le_bytes());
+    }
+    let bytes = bloom.to_static_bytes();
+    assert_eq!(&bytes[bytes.len() - 1024 / 8..], bloom.get_u8_array());
+    assert_eq!(xxh3_64(&bytes), 0xf6d6_76c6_2c1f_e114);
+}
@@ -0,0 +1,80 @@
+use alloc::sync::Arc;
+use core::fmt;
+
+/// Callbacks for structural events of filters, e.g. to emit logs or metrics, or to trigger a
+/// rebuild workflow: `DedupCache` (feature `dedupe`) reports full generations, rotations and
+/// persists, [BloomFilter::set_insert_hook](crate::BloomFilter::set_insert_hook) inserts and
+/// [CountingBloomFilter::set_events](crate::CountingBloomFilter::set_events) saturations. All
+/// methods do nothing by default, so an implementation only overrides the events it is
+/// interested in; `()` ignores all events.
+///
+/// The callbacks run synchronously on the thread which caused the event and should return
+/// quickly.
+///
+/// # Examples
+///
+/// ```rust
+/// use std::sync::atomic::{AtomicU64, Ordering};
+///
+/// use fastbloom_rs::FilterEvents;
+///
+/// #[derive(Default)]
+/// struct Rotations(AtomicU64);
+///
+/// impl FilterEvents for Rotations {
+///     fn on_rotate(&self, _generation: u64) {
+///         self.0.fetch_add(1, Ordering::Relaxed);
+///     }
+/// }
+///
+/// let events = Rotations::default();
+/// events.on_rotate(1);
+/// events.on_saturate(0.5);
+/// assert_eq!(events.0.load(Ordering::Relaxed), 1);
+/// ```
+pub trait FilterEvents: Send + Sync {
+    /// A new generation `generation` replaced the oldest one.
+    fn on_rotate(&self, _generation: u64) {}
+
+    /// The filter took its capacity of elements, beyond which its false positive probability
+    /// exceeds the configured one, at the fill ratio (share of set bits) `fill_ratio`.
+    fn on_saturate(&self, _fill_ratio: f64) {}
+
+    /// `bytes` bytes of the filter were written to persistent storage.
+    fn on_persist(&self, _bytes: u64) {}
+
+    /// `element` was added to a filter it is registered on with
+    /// [BloomFilter::set_insert_hook](crate::BloomFilter::set_insert_hook), setting the bits at
+    /// `indices`. Lets applications mirror inserts into secondary structures (per-shard filters,
+    /// audit logs) without hashing the element again. `element` is empty for inserts by
+    /// [BloomFilter::insert_hash](crate::BloomFilter::insert_hash).
+    fn on_insert(&self, _element: &[u8], _indices: &[u64]) {}
+
+    /// The counter at `index` of a counting filter it is registered on with
+    /// [CountingBloomFilter::set_events](crate::CountingBloomFilter::set_events) was incremented
+    /// at its maximum, see [CountingBloomFilter::overflows](crate::CountingBloomFilter::overflows).
+    fn on_counter_saturate(&self, _index: u64) {}
+}
+
+impl FilterEvents for () {}
+
+/// The optional [FilterEvents] of a filter, called on every insert or counter saturation.
+#[derive(Clone, Default)]
+pub(crate) struct InsertHook(Option<Arc<dyn FilterEvents>>);
+
+impl InsertHook {
+    pub(crate) const fn new(events: Option<Arc<dyn FilterEvents>>) -> Self {
+        InsertHook(events)
+    }
+
+    #[inline]
+    pub(crate) fn get(&self) -> Option<&dyn FilterEvents> {
+        self.0.as_deref()
+    }
+}
+
+impl fmt::Debug for InsertHook {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("InsertHook").field(&self.0.is_some()).finish()
+    }
+}
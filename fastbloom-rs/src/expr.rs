@@ -0,0 +1,312 @@
+use alloc::boxed::Box;
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt::{Display, Formatter};
+
+use crate::Membership;
+
+/// Membership predicate over named filters, e.g.
+/// `key IN filter('allow') AND key NOT IN filter('deny')`, so operators of the CLI `check`
+/// subcommand or of a server can compose multi-filter checks without writing code.
+///
+/// The grammar, keywords being case-insensitive and names quoted with `'` or `"`:
+///
+/// ```text
+/// expr   := term ("OR" term)*
+/// term   := factor ("AND" factor)*
+/// factor := "NOT" factor | "(" expr ")" | "key" ["NOT"] "IN" "filter" "(" name ")"
+/// ```
+///
+/// Expressions nested deeper than [MembershipExpr::MAX_DEPTH] are rejected, so untrusted input
+/// can't overflow the stack of the parser or of [MembershipExpr::evaluate].
+///
+/// # Examples
+///
+/// ```rust
+/// use fastbloom_rs::{FilterBuilder, Membership, MembershipExpr};
+///
+/// let mut allow = FilterBuilder::new(1000, 0.01).build_bloom_filter();
+/// let mut deny = FilterBuilder::new(1000, 0.01).build_bloom_filter();
+/// allow.add(b"alice");
+/// allow.add(b"bob");
+/// deny.add(b"bob");
+///
+/// let expr = "key IN filter('allow') AND key NOT IN filter('deny')";
+/// let expr = MembershipExpr::parse(expr).unwrap();
+/// assert_eq!(expr.filters(), vec!["allow", "deny"]);
+/// let lookup = |name: &str| -> Option<&dyn Membership> {
+///     match name {
+///         "allow" => Some(&allow),
+///         "deny" => Some(&deny),
+///         _ => None,
+///     }
+/// };
+/// assert_eq!(expr.evaluate(b"alice", lookup), Some(true));
+/// assert_eq!(expr.evaluate(b"bob", lookup), Some(false));
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MembershipExpr {
+    /// The key is (maybe) in the named filter.
+    In(String),
+    Not(Box<MembershipExpr>),
+    And(Box<MembershipExpr>, Box<MembershipExpr>),
+    Or(Box<MembershipExpr>, Box<MembershipExpr>),
+}
+
+/// Error of [MembershipExpr::parse].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ParseError {
+    /// Byte offset of the error in the expression.
+    pub position: usize,
+    pub message: String,
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{} at position {}", self.message, self.position)
+    }
+}
+
+impl core::error::Error for ParseError {}
+
+impl MembershipExpr {
+    /// Maximum depth of an expression: of its parentheses and `NOT`s, and of its tree, where
+    /// each `AND` and `OR` of a chain adds a level.
+    pub const MAX_DEPTH: usize = 64;
+
+    /// Parses an expression, see [MembershipExpr] for the grammar.
+    pub fn parse(text: &str) -> Result<Self, ParseError> {
+        let mut parser = Parser { text, position: 0, nesting: 0 };
+        let (expr, _) = parser.expr()?;
+        parser.skip_whitespace();
+        if parser.position < text.len() {
+            return Err(parser.error("unexpected trailing input"));
+        }
+        Ok(expr)
+    }
+
+    /// Returns the names of the filters referenced by the expression, in order of appearance
+    /// and without duplicates, e.g. to load them before evaluating.
+    pub fn filters(&self) -> Vec<&str> {
+        let mut filters = Vec::new();
+        self.collect_filters(&mut filters);
+        filters
+    }
+
+    fn collect_filters<'a>(&'a self, filters: &mut Vec<&'a str>) {
+        match self {
+            MembershipExpr::In(name) => {
+                if !filters.contains(&name.as_str()) {
+                    filters.push(name);
+                }
+            }
+            MembershipExpr::Not(expr) => expr.collect_filters(filters),
+            MembershipExpr::And(a, b) | MembershipExpr::Or(a, b) => {
+                a.collect_filters(filters);
+                b.collect_filters(filters);
+            }
+        }
+    }
+
+    /// Evaluates the expression for `key`, resolving filter names with `lookup`. Returns `None`
+    /// when a filter needed for the answer can't be resolved. `AND` and `OR` short-circuit.
+    pub fn evaluate<'f, F>(&self, key: &[u8], lookup: F) -> Option<bool>
+        where F: Fn(&str) -> Option<&'f dyn Membership> + Copy {
+        match self {
+            MembershipExpr::In(name) => Some(lookup(name)?.contains(key)),
+            MembershipExpr::Not(expr) => Some(!expr.evaluate(key, lookup)?),
+            MembershipExpr::And(a, b) => {
+                Some(a.evaluate(key, lookup)? && b.evaluate(key, lookup)?)
+            }
+            MembershipExpr::Or(a, b) => {
+                Some(a.evaluate(key, lookup)? || b.evaluate(key, lookup)?)
+            }
+        }
+    }
+}
+
+struct Parser<'a> {
+    text: &'a str,
+    position: usize,
+    /// Number of the parentheses and `NOT`s the parser is in.
+    nesting: usize,
+}
+
+/// Expression parsed by [Parser] with the depth of its tree.
+type Parsed = (MembershipExpr, usize);
+
+impl Parser<'_> {
+    fn error(&self, message: &str) -> ParseError {
+        ParseError { position: self.position, message: message.to_string() }
+    }
+
+    fn rest(&self) -> &str {
+        &self.text[self.position..]
+    }
+
+    fn skip_whitespace(&mut self) {
+        let rest = self.rest();
+        self.position += rest.len() - rest.trim_start().len();
+    }
+
+    /// Consumes `keyword` (case-insensitive, not followed by an identifier character).
+    fn keyword(&mut self, keyword: &str) -> bool {
+        self.skip_whitespace();
+        let rest = self.rest().as_bytes();
+        let matches = rest.len() >= keyword.len()
+            && rest[..keyword.len()].eq_ignore_ascii_case(keyword.as_bytes())
+            && !rest.get(keyword.len()).is_some_and(|c| c.is_ascii_alphanumeric() || *c == b'_');
+        if matches {
+            self.position += keyword.len();
+        }
+        matches
+    }
+
+    fn symbol(&mut self, symbol: char) -> bool {
+        self.skip_whitespace();
+        if self.rest().starts_with(symbol) {
+            self.position += symbol.len_utf8();
+            true
+        } else { false }
+    }
+
+    fn expect_symbol(&mut self, symbol: char) -> Result<(), ParseError> {
+        if self.symbol(symbol) { Ok(()) } else { Err(self.error(&format!("expected `{symbol}`"))) }
+    }
+
+    /// Returns the depth of a node over children of depth `depth`, or an error if it is too deep.
+    fn node_depth(&self, depth: usize) -> Result<usize, ParseError> {
+        if depth < MembershipExpr::MAX_DEPTH {
+            Ok(depth + 1)
+        } else {
+            Err(self.error("expression is nested too deeply"))
+        }
+    }
+
+    /// Enters a parenthesis or a `NOT`, see [Parser::nesting].
+    fn enter(&mut self) -> Result<(), ParseError> {
+        self.nesting += 1;
+        if self.nesting > MembershipExpr::MAX_DEPTH {
+            return Err(self.error("expression is nested too deeply"));
+        }
+        Ok(())
+    }
+
+    fn expr(&mut self) -> Result<Parsed, ParseError> {
+        let (mut expr, mut depth) = self.term()?;
+        while self.keyword("or") {
+            let (other, other_depth) = self.term()?;
+            depth = self.node_depth(depth.max(other_depth))?;
+            expr = MembershipExpr::Or(Box::new(expr), Box::new(other));
+        }
+        Ok((expr, depth))
+    }
+
+    fn term(&mut self) -> Result<Parsed, ParseError> {
+        let (mut expr, mut depth) = self.factor()?;
+        while self.keyword("and") {
+            let (other, other_depth) = self.factor()?;
+            depth = self.node_depth(depth.max(other_depth))?;
+            expr = MembershipExpr::And(Box::new(expr), Box::new(other));
+        }
+        Ok((expr, depth))
+    }
+
+    fn factor(&mut self) -> Result<Parsed, ParseError> {
+        if self.keyword("not") {
+            self.enter()?;
+            let (expr, depth) = self.factor()?;
+            self.nesting -= 1;
+            return Ok((MembershipExpr::Not(Box::new(expr)), self.node_depth(depth)?));
+        }
+        if self.symbol('(') {
+            self.enter()?;
+            let parsed = self.expr()?;
+            self.expect_symbol(')')?;
+            self.nesting -= 1;
+            return Ok(parsed);
+        }
+        if !self.keyword("key") {
+            return Err(self.error("expected `key`, `NOT` or `(`"));
+        }
+        let negated = self.keyword("not");
+        if !self.keyword("in") {
+            return Err(self.error("expected `IN`"));
+        }
+        if !self.keyword("filter") {
+            return Err(self.error("expected `filter`"));
+        }
+        self.expect_symbol('(')?;
+        let name = self.name()?;
+        self.expect_symbol(')')?;
+        let expr = MembershipExpr::In(name);
+        Ok(if negated { (MembershipExpr::Not(Box::new(expr)), 2) } else { (expr, 1) })
+    }
+
+    fn name(&mut self) -> Result<String, ParseError> {
+        self.skip_whitespace();
+        let quote = match self.rest().chars().next() {
+            Some(quote @ ('\'' | '"')) => quote,
+            _ => return Err(self.error("expected a quoted filter name")),
+        };
+        let start = self.position + 1;
+        match self.text[start..].find(quote) {
+            Some(len) => {
+                self.position = start + len + 1;
+                Ok(self.text[start..start + len].to_string())
+            }
+            None => Err(self.error("unterminated filter name")),
+        }
+    }
+}
+
+#[test]
+fn membership_expr_test() {
+    use crate::FilterBuilder;
+
+    let mut a = FilterBuilder::new(100, 0.01).build_bloom_filter();
+    let mut b = FilterBuilder::new(100, 0.01).build_bloom_filter();
+    a.add(b"x");
+    b.add(b"y");
+    let lookup = |name: &str| -> Option<&dyn Membership> {
+        match name {
+            "a" => Some(&a),
+            "path/b.bin" => Some(&b),
+            _ => None,
+        }
+    };
+
+    let expr = MembershipExpr::parse(
+        r#"NOT (key in filter("a") or KEY IN FILTER('path/b.bin')) OR key not in filter('a')"#,
+    ).unwrap();
+    assert_eq!(expr.filters(), vec!["a", "path/b.bin"]);
+    assert_eq!(expr.evaluate(b"x", lookup), Some(false));
+    assert_eq!(expr.evaluate(b"y", lookup), Some(true));
+    assert_eq!(expr.evaluate(b"z", lookup), Some(true));
+
+    // AND binds tighter than OR
+    let expr = MembershipExpr::parse("key IN filter('a') OR key IN filter('a') AND key IN filter('c')");
+    assert_eq!(expr.unwrap().evaluate(b"x", lookup), Some(true));
+    let expr = MembershipExpr::parse("key IN filter('c')").unwrap();
+    assert_eq!(expr.evaluate(b"x", lookup), None);
+
+    assert_eq!(MembershipExpr::parse("key IN filter('a'").unwrap_err().position, 17);
+    assert!(MembershipExpr::parse("key IN filter('a') AND").is_err());
+    assert!(MembershipExpr::parse("keys IN filter('a')").is_err());
+    assert!(MembershipExpr::parse("key IN filter('a') x").is_err());
+
+    let depth = MembershipExpr::MAX_DEPTH;
+    let nested = format!("{}key IN filter('a'){}", "(".repeat(depth), ")".repeat(depth));
+    assert!(MembershipExpr::parse(&nested).is_ok());
+    let nested = format!("{}key IN filter('a'){}", "(".repeat(100_000), ")".repeat(100_000));
+    let error = MembershipExpr::parse(&nested).unwrap_err();
+    assert_eq!(error.position, depth + 1);
+    assert_eq!(error.message, "expression is nested too deeply");
+    let negated = format!("{}key IN filter('a')", "NOT ".repeat(100_000));
+    assert!(MembershipExpr::parse(&negated).is_err());
+    let chain = vec!["key IN filter('a')"; depth].join(" OR ");
+    assert!(MembershipExpr::parse(&chain).is_ok());
+    let chain = vec!["key IN filter('a')"; 100_000].join(" AND ");
+    assert!(MembershipExpr::parse(&chain).is_err());
+}
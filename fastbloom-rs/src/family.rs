@@ -0,0 +1,395 @@
+use alloc::sync::Arc;
+
+use crate::hasher::{DEFAULT_HASHER, FilterHasher, HasherRef};
+#[cfg(any(feature = "wyhash", feature = "siphash"))]
+use crate::index::mix64;
+
+/// Hash functions shipped with the crate, see [FilterBuilder::hash_family]. Each has a fixed id
+/// below [FIRST_CUSTOM_HASHER] which is stored in the serialized filters, so a filter built with
+/// one is restored with the same one (if the crate is built with its feature).
+///
+/// [FilterBuilder::hash_family]: crate::FilterBuilder::hash_family
+/// [FIRST_CUSTOM_HASHER]: crate::FIRST_CUSTOM_HASHER
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum HashFamily {
+    /// xxh3, the default hashing of all prior releases ([DEFAULT_HASHER]). Fast on keys of any
+    /// length, and the only family which [FilterBuilder::expected_key_len] and the
+    /// [IndexScheme](crate::IndexScheme)s other than the default specialize.
+    ///
+    /// [FilterBuilder::expected_key_len]: crate::FilterBuilder::expected_key_len
+    #[default]
+    Xxh3,
+    /// wyhash (final version 4), a little faster than xxh3 on short keys. Needs the `wyhash`
+    /// feature.
+    #[cfg(feature = "wyhash")]
+    WyHash,
+    /// SipHash-1-3 keyed with the seed of the filter, for keys chosen by an attacker: without
+    /// the seed, crafting keys which collide in the filter is as hard as breaking SipHash. Keep
+    /// the seed secret, e.g. with [FilterBuilder::sanitized] when sharing filters. Slower than the
+    /// other families. Needs the `siphash` feature.
+    ///
+    /// [FilterBuilder::sanitized]: crate::FilterBuilder::sanitized
+    #[cfg(feature = "siphash")]
+    SipHash13,
+}
+
+impl HashFamily {
+    /// Returns the hasher id of the family, see [FilterBuilder::hasher_id].
+    ///
+    /// [FilterBuilder::hasher_id]: crate::FilterBuilder::hasher_id
+    pub fn id(&self) -> u32 {
+        match self {
+            HashFamily::Xxh3 => DEFAULT_HASHER,
+            #[cfg(feature = "wyhash")]
+            HashFamily::WyHash => 1,
+            #[cfg(feature = "siphash")]
+            HashFamily::SipHash13 => 2,
+        }
+    }
+
+    /// Returns the family with the hasher id `id`, if it is built in.
+    pub fn from_id(id: u32) -> Option<Self> {
+        match id {
+            DEFAULT_HASHER => Some(HashFamily::Xxh3),
+            #[cfg(feature = "wyhash")]
+            1 => Some(HashFamily::WyHash),
+            #[cfg(feature = "siphash")]
+            2 => Some(HashFamily::SipHash13),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn hasher_ref(&self) -> HasherRef {
+        let hasher: Option<Arc<dyn FilterHasher>> = match self {
+            HashFamily::Xxh3 => None,
+            #[cfg(feature = "wyhash")]
+            HashFamily::WyHash => Some(Arc::new(WyHash)),
+            #[cfg(feature = "siphash")]
+            HashFamily::SipHash13 => Some(Arc::new(SipHash13)),
+        };
+        HasherRef { id: self.id(), hasher, key_check: 0 }
+    }
+}
+
+/// Hasher id of SipHash-1-3 keyed with a secret key, see [FilterBuilder::secret_key]. Not a
+/// [HashFamily], filters with it can't be restored without the key.
+///
+/// [FilterBuilder::secret_key]: crate::FilterBuilder::secret_key
+#[cfg(feature = "siphash")]
+pub(crate) const KEYED_SIPHASH: u32 = 3;
+
+/// Returns the hasher of SipHash-1-3 keyed with `key`, its check value is a SipHash of a
+/// fixed message under the key.
+#[cfg(feature = "siphash")]
+pub(crate) fn keyed_hasher_ref(key: [u8; 16]) -> HasherRef {
+    let k0 = u64::from_le_bytes(key[..8].try_into().unwrap());
+    let k1 = u64::from_le_bytes(key[8..].try_into().unwrap());
+    HasherRef {
+        id: KEYED_SIPHASH,
+        hasher: Some(Arc::new(KeyedSipHash13 { k0, k1 })),
+        key_check: siphash13(k0, k1, b"fastbloom-rs key check") | 1,
+    }
+}
+
+/// Hasher id of the two SipHash-1-3 hashes of the `bloomfilter` crate, see
+/// [FilterBuilder::bloomfilter_sip_keys]. Like [KEYED_SIPHASH] not restorable without the keys.
+///
+/// [FilterBuilder::bloomfilter_sip_keys]: crate::FilterBuilder::bloomfilter_sip_keys
+#[cfg(feature = "siphash")]
+pub(crate) const BLOOMFILTER_SIPHASH: u32 = 4;
+
+/// Returns the hasher of the `bloomfilter` crate with the keys `sip_keys`, its check value is a
+/// SipHash of a fixed message under both keys.
+#[cfg(feature = "siphash")]
+pub(crate) fn bloomfilter_hasher_ref(sip_keys: [(u64, u64); 2]) -> HasherRef {
+    let [(k0, k1), (k2, k3)] = sip_keys;
+    let key_check = siphash13(k0, k1, b"fastbloom-rs key check")
+        ^ siphash13(k2, k3, b"fastbloom-rs key check").rotate_left(32);
+    HasherRef {
+        id: BLOOMFILTER_SIPHASH,
+        hasher: Some(Arc::new(BloomfilterSipHash13 { sip_keys })),
+        key_check: key_check | 1,
+    }
+}
+
+/// Derives the second value of a hasher hashing an element only once.
+#[cfg(any(feature = "wyhash", feature = "siphash"))]
+#[inline]
+fn second_hash(hash1: u64) -> u64 {
+    mix64(hash1.wrapping_add(0x9e3779b97f4a7c15)) | 1
+}
+
+#[cfg(feature = "wyhash")]
+struct WyHash;
+
+#[cfg(feature = "wyhash")]
+impl FilterHasher for WyHash {
+    fn hash_pair(&self, element: &[u8], seed: u64) -> (u64, u64) {
+        let hash1 = wyhash(element, seed);
+        (hash1, second_hash(hash1))
+    }
+}
+
+#[cfg(feature = "wyhash")]
+const WYP: [u64; 4] = [
+    0x2d358dccaa6c78a5, 0x8bb84b93962eacc9, 0x4b33a62ed433d4a3, 0x4d5a2da51de1aa47,
+];
+
+#[cfg(feature = "wyhash")]
+#[inline]
+fn wymum(a: u64, b: u64) -> (u64, u64) {
+    let r = a as u128 * b as u128;
+    (r as u64, (r >> 64) as u64)
+}
+
+#[cfg(feature = "wyhash")]
+#[inline]
+fn wymix(a: u64, b: u64) -> u64 {
+    let (a, b) = wymum(a, b);
+    a ^ b
+}
+
+#[cfg(feature = "wyhash")]
+#[inline]
+fn wyr8(p: &[u8]) -> u64 {
+    u64::from_le_bytes(p[..8].try_into().unwrap())
+}
+
+#[cfg(feature = "wyhash")]
+#[inline]
+fn wyr4(p: &[u8]) -> u64 {
+    u32::from_le_bytes(p[..4].try_into().unwrap()) as u64
+}
+
+/// wyhash final version 4 with the default secret, as `wyhash()` of `wyhash.h`.
+#[cfg(feature = "wyhash")]
+fn wyhash(key: &[u8], seed: u64) -> u64 {
+    let len = key.len();
+    let mut seed = seed ^ wymix(seed ^ WYP[0], WYP[1]);
+    let (a, b) = if len <= 16 {
+        if len >= 4 {
+            let off = (len >> 3) << 2;
+            ((wyr4(key) << 32) | wyr4(&key[off..]),
+             (wyr4(&key[len - 4..]) << 32) | wyr4(&key[len - 4 - off..]))
+        } else if len > 0 {
+            let a = (key[0] as u64) << 16 | (key[len >> 1] as u64) << 8 | key[len - 1] as u64;
+            (a, 0)
+        } else {
+            (0, 0)
+        }
+    } else {
+        let mut p = key;
+        if p.len() >= 48 {
+            let (mut see1, mut see2) = (seed, seed);
+            while p.len() >= 48 {
+                seed = wymix(wyr8(p) ^ WYP[1], wyr8(&p[8..]) ^ seed);
+                see1 = wymix(wyr8(&p[16..]) ^ WYP[2], wyr8(&p[24..]) ^ see1);
+                see2 = wymix(wyr8(&p[32..]) ^ WYP[3], wyr8(&p[40..]) ^ see2);
+                p = &p[48..];
+            }
+            seed ^= see1 ^ see2;
+        }
+        while p.len() > 16 {
+            seed = wymix(wyr8(p) ^ WYP[1], wyr8(&p[8..]) ^ seed);
+            p = &p[16..];
+        }
+        // the last 16 bytes of the key, which may overlap the consumed ones.
+        (wyr8(&key[len - 16..]), wyr8(&key[len - 8..]))
+    };
+    let (a, b) = wymum(a ^ WYP[1], b ^ seed);
+    wymix(a ^ WYP[0] ^ len as u64, b ^ WYP[1])
+}
+
+#[cfg(feature = "siphash")]
+struct SipHash13;
+
+#[cfg(feature = "siphash")]
+impl FilterHasher for SipHash13 {
+    fn hash_pair(&self, element: &[u8], seed: u64) -> (u64, u64) {
+        let hash1 = siphash13(seed, mix64(seed), element);
+        (hash1, second_hash(hash1))
+    }
+}
+
+/// SipHash-1-3 with a 128-bit secret key, the seed of the filter is mixed into the first half.
+#[cfg(feature = "siphash")]
+struct KeyedSipHash13 {
+    k0: u64,
+    k1: u64,
+}
+
+#[cfg(feature = "siphash")]
+impl FilterHasher for KeyedSipHash13 {
+    fn hash_pair(&self, element: &[u8], seed: u64) -> (u64, u64) {
+        let hash1 = siphash13(self.k0 ^ seed, self.k1, element);
+        (hash1, second_hash(hash1))
+    }
+}
+
+#[cfg(feature = "siphash")]
+struct BloomfilterSipHash13 {
+    sip_keys: [(u64, u64); 2],
+}
+
+#[cfg(feature = "siphash")]
+impl FilterHasher for BloomfilterSipHash13 {
+    fn hash_pair(&self, element: &[u8], _seed: u64) -> (u64, u64) {
+        let [(k0, k1), (k2, k3)] = self.sip_keys;
+        (siphash13(k0, k1, element), siphash13(k2, k3, element))
+    }
+}
+
+/// SipHash-1-3 of `data` with the key `(k0, k1)`: one compression and three finalization rounds.
+#[cfg(feature = "siphash")]
+pub(crate) fn siphash13(k0: u64, k1: u64, data: &[u8]) -> u64 {
+    siphash::<1, 3>(k0, k1, data)
+}
+
+/// SipHash-2-4 of `data` with the key `(k0, k1)`, the SipHash of BIP158 block filters.
+#[cfg(feature = "siphash")]
+pub(crate) fn siphash24(k0: u64, k1: u64, data: &[u8]) -> u64 {
+    siphash::<2, 4>(k0, k1, data)
+}
+
+/// SipHash of `C` compression and `D` finalization rounds.
+#[cfg(feature = "siphash")]
+fn siphash<const C: usize, const D: usize>(k0: u64, k1: u64, data: &[u8]) -> u64 {
+    #[inline]
+    fn round(v: &mut [u64; 4]) {
+        v[0] = v[0].wrapping_add(v[1]);
+        v[1] = v[1].rotate_left(13) ^ v[0];
+        v[0] = v[0].rotate_left(32);
+        v[2] = v[2].wrapping_add(v[3]);
+        v[3] = v[3].rotate_left(16) ^ v[2];
+        v[0] = v[0].wrapping_add(v[3]);
+        v[3] = v[3].rotate_left(21) ^ v[0];
+        v[2] = v[2].wrapping_add(v[1]);
+        v[1] = v[1].rotate_left(17) ^ v[2];
+        v[2] = v[2].rotate_left(32);
+    }
+
+    let mut v = [
+        k0 ^ 0x736f6d6570736575,
+        k1 ^ 0x646f72616e646f6d,
+        k0 ^ 0x6c7967656e657261,
+        k1 ^ 0x7465646279746573,
+    ];
+    let chunks = data.chunks_exact(8);
+    let tail = chunks.remainder();
+    for chunk in chunks {
+        let m = u64::from_le_bytes(chunk.try_into().unwrap());
+        v[3] ^= m;
+        (0..C).for_each(|_| round(&mut v));
+        v[0] ^= m;
+    }
+    let mut last = [0u8; 8];
+    last[..tail.len()].copy_from_slice(tail);
+    let m = u64::from_le_bytes(last) | (data.len() as u64) << 56;
+    v[3] ^= m;
+    (0..C).for_each(|_| round(&mut v));
+    v[0] ^= m;
+    v[2] ^= 0xff;
+    (0..D).for_each(|_| round(&mut v));
+    v[0] ^ v[1] ^ v[2] ^ v[3]
+}
+
+#[test]
+fn hash_family_test() {
+    use crate::{BloomFilter, FilterBuilder, Membership};
+
+    let families = [
+        HashFamily::Xxh3,
+        #[cfg(feature = "wyhash")]
+        HashFamily::WyHash,
+        #[cfg(feature = "siphash")]
+        HashFamily::SipHash13,
+    ];
+    for family in families {
+        assert_eq!(HashFamily::from_id(family.id()), Some(family));
+        let mut builder = FilterBuilder::new(10_000, 0.01);
+        builder.hash_family(family);
+        assert_eq!(builder.hasher_id(), family.id());
+        let mut bloom = builder.build_bloom_filter();
+        for i in 0..1000u32 {
+            bloom.add(&i.to_le_bytes());
+        }
+        assert!((0..1000u32).all(|i| bloom.contains(&i.to_le_bytes())));
+        assert!((1000..11_000u32).filter(|i| bloom.contains(&i.to_le_bytes())).count() < 300);
+
+        let restored = BloomFilter::from_compressed_bytes(&bloom.to_compressed_bytes()).unwrap();
+        assert_eq!(restored.config().hasher_id(), family.id());
+        assert!((0..1000u32).all(|i| restored.contains(&i.to_le_bytes())));
+    }
+    assert_eq!(HashFamily::from_id(255), None);
+}
+
+#[cfg(feature = "wyhash")]
+#[test]
+fn wyhash_test() {
+    // test vectors of the reference implementation, seeded with their index.
+    let vectors: [(&[u8], u64); 7] = [
+        (b"", 0x93228a4de0eec5a2),
+        (b"a", 0xc5bac3db178713c4),
+        (b"abc", 0xa97f2f7b1d9b3314),
+        (b"message digest", 0x786d1f1df3801df4),
+        (b"abcdefghijklmnopqrstuvwxyz", 0xdca5a8138ad37c87),
+        (b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789", 0xb9e734f117cfaf70),
+        (b"12345678901234567890123456789012345678901234567890123456789012345678901234567890",
+         0x6cc5eab49a92d617),
+    ];
+    for (seed, (key, hash)) in vectors.into_iter().enumerate() {
+        assert_eq!(wyhash(key, seed as u64), hash);
+    }
+}
+
+#[cfg(feature = "siphash")]
+#[test]
+fn siphash13_test() {
+    use std::collections::hash_map::DefaultHasher;
+    use std::hash::Hasher;
+
+    // the hasher of std's `HashMap` is SipHash-1-3 with zero keys.
+    for key in [&b""[..], b"hello", b"0123456789abcdef", b"the quick brown fox jumps"] {
+        let mut std = DefaultHasher::new();
+        std.write(key);
+        assert_eq!(siphash13(0, 0, key), std.finish());
+    }
+    // the vectors of the SipHash paper: key 0..16, messages 0..n
+    let (k0, k1) = (0x0706050403020100, 0x0f0e0d0c0b0a0908);
+    let message: [u8; 64] = core::array::from_fn(|i| i as u8);
+    assert_eq!(siphash24(k0, k1, &message[..0]), 0x726fdb47dd0e0e31);
+    assert_eq!(siphash24(k0, k1, &message[..1]), 0x74f839c593dc67fd);
+    assert_eq!(siphash24(k0, k1, &message[..63]), 0x958a324ceb064572);
+}
+
+#[cfg(feature = "siphash")]
+#[test]
+fn secret_key_test() {
+    use crate::{BloomFilter, FilterBuilder, Membership};
+
+    let keyed = |key: &[u8; 16]| {
+        let mut builder = FilterBuilder::new(10_000, 0.01);
+        builder.secret_key(*key);
+        builder
+    };
+    let mut a = keyed(b"0123456789abcdef").build_bloom_filter();
+    let mut b = keyed(b"0123456789abcdef").build_bloom_filter();
+    let other_key = keyed(b"fedcba9876543210").build_bloom_filter();
+    let unkeyed = FilterBuilder::new(10_000, 0.01).build_bloom_filter();
+    assert!(a.config().is_keyed() && !unkeyed.config().is_keyed());
+    assert_eq!(a.config().hasher_id(), KEYED_SIPHASH);
+    (0..1000u32).for_each(|i| a.add(&i.to_le_bytes()));
+    (1000..2000u32).for_each(|i| b.add(&i.to_le_bytes()));
+    assert!((1000..11_000u32).filter(|i| a.contains(&i.to_le_bytes())).count() < 300);
+    // another key puts the elements at other bits
+    assert_ne!(a.get_hash_indices(b"hello"), other_key.get_hash_indices(b"hello"));
+
+    let err = a.unioned(&other_key).unwrap_err();
+    assert!(err.to_string().contains("secret keys differ"), "{err}");
+    assert!(a.unioned(&unkeyed).is_err());
+    assert!(a.union(&b));
+    assert!((0..2000u32).all(|i| a.contains(&i.to_le_bytes())));
+
+    // the key is not serialized, so the filter can't be restored
+    assert!(BloomFilter::from_compressed_bytes(&a.to_compressed_bytes()).is_none());
+}
@@ -0,0 +1,200 @@
+use alloc::boxed::Box;
+use core::ptr;
+use core::slice;
+
+use crate::{BloomFilter, FilterBuilder, Membership};
+
+/// Version of the C interface, incremented on incompatible changes of the declarations.
+pub const FASTBLOOM_ABI_VERSION: u32 = 1;
+
+/// Opaque handle of a [BloomFilter] for the C interface declared in `include/fastbloom.h`
+/// (regenerate it with `cbindgen --config cbindgen.toml --output include/fastbloom.h`). Build the
+/// library with `cargo rustc --release --features ffi --crate-type staticlib` (or `cdylib`).
+///
+/// The functions never unwind into C, invalid arguments are reported as `NULL` or `false`. A
+/// filter may be queried from several threads at once, but updates need exclusive access.
+pub struct FastBloom {
+    bloom: BloomFilter,
+}
+
+/// Returns the `len` bytes at `bytes`, which may be `NULL` for `len == 0`.
+unsafe fn bytes_at<'a>(bytes: *const u8, len: usize) -> &'a [u8] {
+    if len == 0 {
+        &[]
+    } else {
+        slice::from_raw_parts(bytes, len)
+    }
+}
+
+/// Returns [FASTBLOOM_ABI_VERSION] of the linked library, to be compared with the version of the
+/// header.
+#[no_mangle]
+pub extern "C" fn fastbloom_abi_version() -> u32 {
+    FASTBLOOM_ABI_VERSION
+}
+
+/// Creates an empty filter for `expected_elements` at `false_positive_probability`. Returns
+/// `NULL` if `expected_elements` is 0, the probability is not in `(0, 1)` or the filter is too
+/// large. Free it with [fastbloom_free].
+#[no_mangle]
+pub extern "C" fn fastbloom_new(expected_elements: u64,
+                                false_positive_probability: f64) -> *mut FastBloom {
+    if expected_elements == 0 || !(false_positive_probability > 0.0
+        && false_positive_probability < 1.0) {
+        return ptr::null_mut();
+    }
+    let mut builder = FilterBuilder::new(expected_elements, false_positive_probability);
+    match builder.try_build_bloom_filter() {
+        Ok(bloom) => Box::into_raw(Box::new(FastBloom { bloom })),
+        Err(_) => ptr::null_mut(),
+    }
+}
+
+/// Frees a filter, `NULL` is ignored.
+///
+/// # Safety
+///
+/// `filter` must be `NULL` or returned by this library and not freed before.
+#[no_mangle]
+pub unsafe extern "C" fn fastbloom_free(filter: *mut FastBloom) {
+    if !filter.is_null() {
+        drop(Box::from_raw(filter));
+    }
+}
+
+/// Adds the `len` bytes at `element` to the filter.
+///
+/// # Safety
+///
+/// `filter` must be a valid filter not accessed by other threads, `element` must point to `len`
+/// readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn fastbloom_add(filter: *mut FastBloom, element: *const u8,
+                                       len: usize) {
+    (*filter).bloom.add(bytes_at(element, len));
+}
+
+/// Tests whether the `len` bytes at `element` are present in the filter (subject to the false
+/// positive probability).
+///
+/// # Safety
+///
+/// `filter` must be a valid filter not updated by other threads, `element` must point to `len`
+/// readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn fastbloom_contains(filter: *const FastBloom, element: *const u8,
+                                            len: usize) -> bool {
+    (*filter).bloom.contains(bytes_at(element, len))
+}
+
+/// Removes all elements from the filter.
+///
+/// # Safety
+///
+/// `filter` must be a valid filter not accessed by other threads.
+#[no_mangle]
+pub unsafe extern "C" fn fastbloom_clear(filter: *mut FastBloom) {
+    (*filter).bloom.clear();
+}
+
+/// Adds all elements of `other` to `filter`. Returns `false` and leaves `filter` unchanged if
+/// the filters were not built with the same parameters.
+///
+/// # Safety
+///
+/// Both must be valid filters, `filter` must not be accessed by other threads and must not be
+/// `other`.
+#[no_mangle]
+pub unsafe extern "C" fn fastbloom_union(filter: *mut FastBloom, other: *const FastBloom) -> bool {
+    (*filter).bloom.union(&(*other).bloom)
+}
+
+/// Returns the number of hash functions of the filter.
+///
+/// # Safety
+///
+/// `filter` must be a valid filter.
+#[no_mangle]
+pub unsafe extern "C" fn fastbloom_hashes(filter: *const FastBloom) -> u32 {
+    (*filter).bloom.config().hashes
+}
+
+/// Returns the size of the filter in bits.
+///
+/// # Safety
+///
+/// `filter` must be a valid filter.
+#[no_mangle]
+pub unsafe extern "C" fn fastbloom_size(filter: *const FastBloom) -> u64 {
+    (*filter).bloom.config().size
+}
+
+/// Serializes the filter like [BloomFilter::to_static_bytes] into `out` and returns the length
+/// of the serialized filter. Nothing is written if it exceeds `capacity`, so call it with a
+/// `capacity` of 0 first to learn the length.
+///
+/// # Safety
+///
+/// `filter` must be a valid filter not updated by other threads, `out` must point to `capacity`
+/// writable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn fastbloom_to_bytes(filter: *const FastBloom, out: *mut u8,
+                                            capacity: usize) -> usize {
+    let bytes = (*filter).bloom.to_static_bytes();
+    if bytes.len() <= capacity {
+        ptr::copy_nonoverlapping(bytes.as_ptr(), out, bytes.len());
+    }
+    bytes.len()
+}
+
+/// Restores a filter written by [fastbloom_to_bytes] (or [BloomFilter::to_static_bytes]).
+/// Returns `NULL` if the bytes are not a valid filter. Free it with [fastbloom_free].
+///
+/// # Safety
+///
+/// `bytes` must point to `len` readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn fastbloom_from_bytes(bytes: *const u8, len: usize) -> *mut FastBloom {
+    match BloomFilter::from_static_bytes(bytes_at(bytes, len)) {
+        Some(bloom) if bloom.config().size > 0 => Box::into_raw(Box::new(FastBloom { bloom })),
+        _ => ptr::null_mut(),
+    }
+}
+
+#[test]
+fn ffi_test() {
+    assert!(fastbloom_new(0, 0.01).is_null());
+    assert!(fastbloom_new(1000, 1.0).is_null());
+    assert!(fastbloom_new(1000, f64::NAN).is_null());
+
+    unsafe {
+        let filter = fastbloom_new(1000, 0.01);
+        fastbloom_add(filter, b"hello".as_ptr(), 5);
+        fastbloom_add(filter, ptr::null(), 0);
+        assert!(fastbloom_contains(filter, b"hello".as_ptr(), 5));
+        assert!(fastbloom_contains(filter, ptr::null(), 0));
+        assert!(!fastbloom_contains(filter, b"world".as_ptr(), 5));
+
+        let len = fastbloom_to_bytes(filter, ptr::null_mut(), 0);
+        let mut bytes = alloc::vec![0u8; len];
+        assert_eq!(fastbloom_to_bytes(filter, bytes.as_mut_ptr(), len), len);
+        let restored = fastbloom_from_bytes(bytes.as_ptr(), len);
+        assert!(fastbloom_contains(restored, b"hello".as_ptr(), 5));
+        assert_eq!(fastbloom_hashes(restored), fastbloom_hashes(filter));
+        assert_eq!(fastbloom_size(restored), fastbloom_size(filter));
+        assert!(fastbloom_from_bytes(bytes.as_ptr(), len - 1).is_null());
+
+        let other = fastbloom_new(1000, 0.01);
+        fastbloom_add(other, b"world".as_ptr(), 5);
+        assert!(fastbloom_union(filter, other));
+        assert!(fastbloom_contains(filter, b"world".as_ptr(), 5));
+        let larger = fastbloom_new(2000, 0.01);
+        assert!(!fastbloom_union(filter, larger));
+
+        fastbloom_clear(filter);
+        assert!(!fastbloom_contains(filter, b"hello".as_ptr(), 5));
+        for filter in [filter, restored, other, larger, ptr::null_mut()] {
+            fastbloom_free(filter);
+        }
+    }
+}
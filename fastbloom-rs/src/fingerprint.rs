@@ -0,0 +1,154 @@
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::builder::FilterBuilder;
+use crate::index::hash_pair;
+use crate::Membership;
+
+/// Marks an empty slot, fingerprints are never `0`.
+const EMPTY: u16 = 0;
+
+/// Tiny open-addressing table of 16-bit fingerprints for short-lived semi-join filtering (e.g.
+/// one per query in a join runtime), where building a full Bloom filter is overkill. It uses the
+/// hashing of the [FilterBuilder] it is built from and answers membership queries with a false
+/// positive probability of about `2^-16` per probed slot.
+///
+/// The table is sized for `expected_elements` at a load factor of one half; adding more slows
+/// down lookups and panics once every slot is taken.
+///
+/// # Examples
+///
+/// ```rust
+/// use fastbloom_rs::{FilterBuilder, Membership};
+///
+/// let mut table = FilterBuilder::new(1000, 0.01).build_fingerprint_table();
+/// table.add(b"hello");
+/// assert_eq!(table.contains(b"hello"), true);
+/// assert_eq!(table.contains(b"world"), false);
+/// ```
+#[derive(Clone)]
+#[derive(Debug)]
+pub struct FingerprintTable {
+    config: FilterBuilder,
+    slots: Vec<u16>,
+    len: usize,
+}
+
+impl FingerprintTable {
+    /// Builds an empty table for `config.expected_elements` elements.
+    pub fn new(config: FilterBuilder) -> Self {
+        let capacity = (config.expected_elements as usize).max(4) * 2;
+        FingerprintTable { config, slots: vec![EMPTY; capacity.next_power_of_two()], len: 0 }
+    }
+
+    /// Returns the number of distinct fingerprints in the table.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns [true] if nothing has been added to the table.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the number of slots of the table.
+    pub fn slots(&self) -> usize {
+        self.slots.len()
+    }
+
+    /// Returns the home slot and the fingerprint of an element.
+    #[inline]
+    fn locate(&self, element: &[u8]) -> (usize, u16) {
+        let (hash1, hash2) = hash_pair(element, &self.config);
+        let fingerprint = (hash2 >> 48) as u16;
+        let slot = hash1 as usize & (self.slots.len() - 1);
+        (slot, if fingerprint == EMPTY { 1 } else { fingerprint })
+    }
+
+    /// Probes from `slot` until `fingerprint` or an empty slot is found, returns its position.
+    #[inline]
+    fn probe(&self, mut slot: usize, fingerprint: u16) -> Option<usize> {
+        let mask = self.slots.len() - 1;
+        for _ in 0..self.slots.len() {
+            if self.slots[slot] == fingerprint || self.slots[slot] == EMPTY {
+                return Some(slot);
+            }
+            slot = (slot + 1) & mask;
+        }
+        None
+    }
+
+    /// Tests whether `fingerprint` is in the probe sequence of `slot`.
+    #[inline]
+    fn contains_fingerprint(&self, slot: usize, fingerprint: u16) -> bool {
+        match self.probe(slot, fingerprint) {
+            Some(slot) => self.slots[slot] == fingerprint,
+            None => false,
+        }
+    }
+}
+
+impl Membership for FingerprintTable {
+    /// Adds the fingerprint of the passed value to the table.
+    fn add(&mut self, element: &[u8]) {
+        let (slot, fingerprint) = self.locate(element);
+        let slot = self.probe(slot, fingerprint).expect("FingerprintTable is full!");
+        if self.slots[slot] == EMPTY {
+            self.slots[slot] = fingerprint;
+            self.len += 1;
+        }
+    }
+
+    /// Tests whether the fingerprint of an element is present in the table.
+    #[inline]
+    fn contains(&self, element: &[u8]) -> bool {
+        let (slot, fingerprint) = self.locate(element);
+        self.contains_fingerprint(slot, fingerprint)
+    }
+
+    /// Get the home slot and the fingerprint of the element.
+    fn get_hash_indices(&self, element: &[u8]) -> Vec<u64> {
+        let (slot, fingerprint) = self.locate(element);
+        vec![slot as u64, fingerprint as u64]
+    }
+
+    /// Tests whether a home slot and fingerprint from `get_hash_indices` are present in the
+    /// table, returns [false] for indices which no element of this table has.
+    fn contains_hash_indices(&self, indices: &Vec<u64>) -> bool {
+        let &[slot, fingerprint] = indices.as_slice() else { return false };
+        if slot >= self.slots.len() as u64 || fingerprint == EMPTY as u64
+            || fingerprint > u16::MAX as u64 {
+            return false;
+        }
+        self.contains_fingerprint(slot as usize, fingerprint as u16)
+    }
+
+    /// Removes all fingerprints from the table.
+    fn clear(&mut self) {
+        self.slots.fill(EMPTY);
+        self.len = 0;
+    }
+}
+
+#[test]
+fn fingerprint_table_test() {
+    let mut table = FilterBuilder::new(1000, 0.01).build_fingerprint_table();
+    assert_eq!(table.slots(), 2048);
+    for i in 0..1000u32 {
+        table.add(&i.to_le_bytes());
+        table.add(&i.to_le_bytes());
+    }
+    assert!(table.len() <= 1000 && table.len() > 990);
+    assert!((0..1000u32).all(|i| table.contains(&i.to_le_bytes())));
+    let false_positives = (1000..101_000u32).filter(|i| table.contains(&i.to_le_bytes())).count();
+    assert!(false_positives < 100, "{false_positives} false positives");
+    let indices = table.get_hash_indices(&1u32.to_le_bytes());
+    assert!(table.contains_hash_indices(&indices));
+    assert!(!table.contains_hash_indices(&indices[..1].to_vec()));
+    assert!(!table.contains_hash_indices(&vec![2048, indices[1]]));
+    assert!(!table.contains_hash_indices(&vec![indices[0], 0]));
+
+    table.clear();
+    assert!(table.is_empty());
+    assert!(!table.contains(&0u32.to_le_bytes()));
+}
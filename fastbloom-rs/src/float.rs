@@ -0,0 +1,48 @@
+/// Float functions of `std` which `core` lacks, implemented with `libm` in `no_std` builds. With
+/// `std` the inherent methods take precedence and this trait is not imported.
+pub(crate) trait Float {
+    fn ln(self) -> Self;
+    fn exp(self) -> Self;
+    fn powi(self, n: i32) -> Self;
+    fn ceil(self) -> Self;
+    fn floor(self) -> Self;
+    fn round(self) -> Self;
+    fn sqrt(self) -> Self;
+}
+
+impl Float for f64 {
+    #[inline]
+    fn ln(self) -> f64 {
+        libm::log(self)
+    }
+
+    #[inline]
+    fn exp(self) -> f64 {
+        libm::exp(self)
+    }
+
+    #[inline]
+    fn powi(self, n: i32) -> f64 {
+        libm::pow(self, n as f64)
+    }
+
+    #[inline]
+    fn ceil(self) -> f64 {
+        libm::ceil(self)
+    }
+
+    #[inline]
+    fn floor(self) -> f64 {
+        libm::floor(self)
+    }
+
+    #[inline]
+    fn round(self) -> f64 {
+        libm::round(self)
+    }
+
+    #[inline]
+    fn sqrt(self) -> f64 {
+        libm::sqrt(self)
+    }
+}
@@ -0,0 +1,111 @@
+use alloc::sync::Arc;
+use alloc::vec;
+
+use crate::{Hashes, MembershipDetail};
+use crate::bloom::BloomFilter;
+use crate::builder::FilterBuilder;
+use crate::index::Indices;
+use crate::vec::Storage;
+
+impl<S: Storage> BloomFilter<S> {
+    /// Returns a read-only copy of the filter for sharing between threads, see
+    /// [FrozenBloomFilter].
+    pub fn freeze(&self) -> FrozenBloomFilter {
+        let mut words = vec![0u64; self.config.size.div_ceil(64) as usize];
+        for w in 0..self.bit_set.storage.slots() {
+            let bit = w * usize::BITS as usize;
+            words[bit / 64] |= (self.bit_set.word(w) as u64) << (bit % 64);
+        }
+        FrozenBloomFilter { config: self.config.clone(), words: words.into() }
+    }
+}
+
+/// Read-only Bloom filter for query-only services: the bits of a finished [BloomFilter] in an
+/// `Arc<[u64]>`, so the filter is [Send] and [Sync], clones share the bits instead of copying
+/// them, and nothing can write to it by accident. Build it with [BloomFilter::freeze].
+///
+/// # Examples
+///
+/// ```rust
+/// use fastbloom_rs::{FilterBuilder, Membership};
+///
+/// let mut bloom = FilterBuilder::new(1000, 0.01).build_bloom_filter();
+/// bloom.add(b"hello");
+/// let frozen = bloom.freeze();
+///
+/// let shared = frozen.clone();
+/// let found = std::thread::spawn(move || shared.contains(b"hello")).join().unwrap();
+/// assert_eq!(found, true);
+/// assert_eq!(frozen.contains(b"world"), false);
+/// ```
+#[derive(Clone)]
+#[derive(Debug)]
+pub struct FrozenBloomFilter {
+    config: FilterBuilder,
+    /// Bit `i` of the filter is bit `i % 64` of word `i / 64`.
+    words: Arc<[u64]>,
+}
+
+impl FrozenBloomFilter {
+    /// Returns the configuration/builder of the filter.
+    pub fn config(&self) -> FilterBuilder {
+        self.config.clone()
+    }
+
+    #[inline]
+    fn get(&self, index: u64) -> bool {
+        self.words[(index >> 6) as usize] & (1 << (index & 63)) != 0
+    }
+
+    /// Tests whether an element is present in the filter (subject to the specified false
+    /// positive rate).
+    #[inline]
+    pub fn contains(&self, element: &[u8]) -> bool {
+        let mut indices = Indices::new(element, &self.config);
+        !indices.is_empty() && indices.all(|index| self.get(index))
+    }
+
+    /// Same as [BloomFilter::contains_detailed].
+    pub fn contains_detailed(&self, element: &[u8]) -> MembershipDetail {
+        let probes_set = Indices::new(element, &self.config).filter(|i| self.get(*i)).count();
+        MembershipDetail::from_probes(probes_set as u32, self.config.hashes)
+    }
+}
+
+impl From<BloomFilter> for FrozenBloomFilter {
+    fn from(bloom: BloomFilter) -> Self {
+        bloom.freeze()
+    }
+}
+
+impl Hashes for FrozenBloomFilter {
+    ///  Returns the hash function number of the filter.
+    fn hashes(&self) -> u32 {
+        self.config.hashes
+    }
+}
+
+#[test]
+fn frozen_bloom_filter_test() {
+    use crate::Membership;
+
+    fn shareable<T: Send + Sync + Clone>(_: &T) {}
+
+    let mut builder = FilterBuilder::new(10_000, 0.01);
+    builder.seed(3);
+    builder.partitioned(true);
+    let mut bloom = builder.build_bloom_filter();
+    for i in 0..10_000u32 {
+        bloom.add(&i.to_le_bytes());
+    }
+    let frozen = FrozenBloomFilter::from(bloom.clone());
+    shareable(&frozen);
+    assert_eq!(frozen.hashes(), bloom.config().hashes);
+    for i in 0..20_000u32 {
+        assert_eq!(frozen.contains(&i.to_le_bytes()), bloom.contains(&i.to_le_bytes()));
+    }
+    assert!(Arc::ptr_eq(&frozen.words, &frozen.clone().words));
+
+    let empty = FilterBuilder::from_size_and_hashes(0, 0).build_bloom_filter().freeze();
+    assert!(!empty.contains(b"hello"));
+}
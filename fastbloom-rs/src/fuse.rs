@@ -0,0 +1,442 @@
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt::Debug;
+use core::ops::BitXor;
+
+use crate::builder::FilterBuilder;
+#[cfg(not(feature = "std"))]
+use crate::float::Float;
+use crate::index::{hash_pair, mix64};
+
+/// Number of seeds tried before the construction gives up.
+const MAX_ITERATIONS: usize = 100;
+/// Largest segment, larger ones do not lower the space overhead any further.
+const MAX_SEGMENT_LENGTH: usize = 1 << 18;
+
+/// Fingerprint stored by a [BinaryFuseFilter], implemented for `u8` and `u16`.
+pub trait FuseFingerprint: Copy + Default + Eq + BitXor<Output=Self> + Debug {
+    /// Derives the fingerprint of a key from its hash.
+    fn from_hash(hash: u64) -> Self;
+}
+
+impl FuseFingerprint for u8 {
+    #[inline]
+    fn from_hash(hash: u64) -> Self {
+        (hash ^ (hash >> 32)) as u8
+    }
+}
+
+impl FuseFingerprint for u16 {
+    #[inline]
+    fn from_hash(hash: u64) -> Self {
+        (hash ^ (hash >> 32)) as u16
+    }
+}
+
+/// [BinaryFuseFilter] with 8-bit fingerprints, about 9 bits per key and a false positive
+/// probability of `2^-8`.
+pub type BinaryFuse8 = BinaryFuseFilter<u8>;
+/// [BinaryFuseFilter] with 16-bit fingerprints, about 18 bits per key and a false positive
+/// probability of `2^-16`.
+pub type BinaryFuse16 = BinaryFuseFilter<u16>;
+
+/// Binary fuse filter of a fixed set of keys (Graf and Lemire, "Binary Fuse Filters: Fast and
+/// Smaller Than Xor Filters"). Every key maps to three slots in neighbouring segments of a table
+/// of fingerprints, which are solved at construction so that the three slots of a key xor to its
+/// fingerprint. A query reads three slots and never has false negatives.
+///
+/// The filter is built once from all keys, nothing can be added or removed afterwards. It takes
+/// about `1.125 * F::BITS` bits per key for large sets (against about 9.6 bits of a Bloom filter
+/// at 1% and 8-bit fingerprints) and uses the hashing of the [FilterBuilder] it is built from;
+/// the size and false positive probability of the builder are ignored. Construction needs about
+/// 30 bytes of scratch memory per key.
+///
+/// # Examples
+///
+/// ```rust
+/// use fastbloom_rs::{BinaryFuse8, FilterBuilder};
+///
+/// let keys = ["hello", "world"];
+/// let filter: BinaryFuse8 = FilterBuilder::new(2, 0.01).build_binary_fuse_filter(keys);
+/// assert_eq!(filter.contains(b"hello"), true);
+/// assert_eq!(filter.contains(b"bloom"), false);
+/// ```
+#[derive(Clone)]
+#[derive(Debug)]
+pub struct BinaryFuseFilter<F: FuseFingerprint = u8> {
+    config: FilterBuilder,
+    seed: u64,
+    len: usize,
+    segment_length: usize,
+    segment_count_length: usize,
+    fingerprints: Vec<F>,
+}
+
+impl<F: FuseFingerprint> BinaryFuseFilter<F> {
+    /// Builds the filter of `keys`, hashed with `config`. Duplicate keys are allowed. Panics if
+    /// no solution is found, which only happens for hash functions of poor quality.
+    pub fn from_keys<K: AsRef<[u8]>>(config: FilterBuilder,
+                                     keys: impl IntoIterator<Item=K>) -> Self {
+        let hashes: Vec<u64> = keys.into_iter()
+            .map(|key| hash_pair(key.as_ref(), &config).0)
+            .collect();
+        let mut filter = Self::empty(config, hashes.len());
+        filter.populate(hashes);
+        filter
+    }
+
+    /// Builds an empty table for `size` keys.
+    fn empty(config: FilterBuilder, size: usize) -> Self {
+        let segment_length = if size == 0 {
+            4
+        } else {
+            // 1 << floor(log_3.33(size) + 2.25)
+            let exponent = ((size as f64).ln() / 3.33f64.ln() + 2.25).floor() as u32;
+            1usize.checked_shl(exponent).unwrap_or(MAX_SEGMENT_LENGTH).min(MAX_SEGMENT_LENGTH)
+        };
+        let size_factor = if size <= 1 {
+            0.0
+        } else {
+            1.125f64.max(0.875 + 0.25 * 1e6f64.ln() / (size as f64).ln())
+        };
+        let capacity = (size as f64 * size_factor).round() as usize;
+        let segments = capacity.div_ceil(segment_length).saturating_sub(2).max(1);
+        BinaryFuseFilter {
+            config,
+            seed: 0,
+            len: size,
+            segment_length,
+            segment_count_length: segments * segment_length,
+            fingerprints: vec![F::default(); (segments + 2) * segment_length],
+        }
+    }
+
+    /// Returns the configuration of the filter.
+    pub fn config(&self) -> FilterBuilder {
+        self.config.clone()
+    }
+
+    /// Returns the number of keys the filter was built from, including duplicates.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns [true] if the filter was built from no keys.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the number of bits of fingerprints per key.
+    pub fn bits_per_key(&self) -> f64 {
+        (self.fingerprints.len() * size_of::<F>() * 8) as f64 / self.len.max(1) as f64
+    }
+
+    /// Tests whether an element is one of the keys (subject to the false positive probability).
+    #[inline]
+    pub fn contains(&self, element: &[u8]) -> bool {
+        let (hash, slots) = self.locate(element);
+        self.matches(hash, slots)
+    }
+
+    /// Returns the seeded hash of an element and its slots.
+    #[inline]
+    fn locate(&self, element: &[u8]) -> (u64, [usize; 3]) {
+        let hash = mix64(hash_pair(element, &self.config).0.wrapping_add(self.seed));
+        (hash, self.slots(hash))
+    }
+
+    /// Tests whether the fingerprints of the slots xor to the fingerprint of the hash.
+    #[inline]
+    fn matches(&self, hash: u64, [h0, h1, h2]: [usize; 3]) -> bool {
+        F::from_hash(hash) ^ self.fingerprints[h0] ^ self.fingerprints[h1]
+            ^ self.fingerprints[h2] == F::default()
+    }
+
+    /// Returns the slots of a key hash in three consecutive segments.
+    #[inline]
+    fn slots(&self, hash: u64) -> [usize; 3] {
+        let h0 = ((hash as u128 * self.segment_count_length as u128) >> 64) as usize;
+        let mask = self.segment_length - 1;
+        let h1 = (h0 + self.segment_length) ^ ((hash >> 18) as usize & mask);
+        let h2 = (h0 + 2 * self.segment_length) ^ (hash as usize & mask);
+        [h0, h1, h2]
+    }
+
+    /// Finds a seed for which the keys peel and assigns the fingerprints.
+    fn populate(&mut self, keys: Vec<u64>) {
+        let (order, found) = self.peel(keys);
+        let mut fingerprints = core::mem::take(&mut self.fingerprints);
+        self.solve(&mut fingerprints, &order, &found, F::from_hash);
+        self.fingerprints = fingerprints;
+    }
+
+    /// Finds a seed for which the keys peel, and returns the seeded hashes of the distinct keys
+    /// in peeling order with the index of the slot each one was peeled from.
+    fn peel(&mut self, mut keys: Vec<u64>) -> (Vec<u64>, Vec<u8>) {
+        let mut size = keys.len();
+        if size == 0 {
+            return (Vec::new(), Vec::new());
+        }
+        let slots = self.fingerprints.len();
+        let segments = self.segment_count_length / self.segment_length;
+        let block_bits = (usize::BITS - (segments - 1).leading_zeros()).max(1);
+        let block = 1usize << block_bits;
+        // hashes sorted by segment, ends with a sentinel so the bucket scan terminates
+        let mut order = vec![0u64; size + 1];
+        let mut found = vec![0u8; size];
+        let mut alone = vec![0usize; slots];
+        // number of keys times 4 | which of the three slots of the key, xored over all keys
+        let mut count = vec![0u8; slots];
+        let mut xors = vec![0u64; slots];
+        let mut start = vec![0usize; block];
+        let mut rng = self.config.seed ^ 0x726b2b9d438b9d4d;
+
+        let stack = 'seed: {
+            for _ in 0..MAX_ITERATIONS {
+                rng = rng.wrapping_add(0x9e3779b97f4a7c15);
+                self.seed = mix64(rng);
+                order[..size].fill(0);
+                order[size] = 1;
+                count.fill(0);
+                xors.fill(0);
+                for (i, start) in start.iter_mut().enumerate() {
+                    *start = ((i as u128 * size as u128) >> block_bits) as usize;
+                }
+                for &key in &keys {
+                    let hash = mix64(key.wrapping_add(self.seed));
+                    let mut segment = (hash >> (64 - block_bits)) as usize;
+                    while order[start[segment]] != 0 {
+                        segment = (segment + 1) & (block - 1);
+                    }
+                    order[start[segment]] = hash;
+                    start[segment] += 1;
+                }
+
+                let (mut error, mut duplicates) = (false, 0);
+                for &hash in &order[..size] {
+                    let slot = self.slots(hash);
+                    for (index, &h) in slot.iter().enumerate() {
+                        count[h] = count[h].wrapping_add(4) ^ index as u8;
+                        xors[h] ^= hash;
+                    }
+                    // a duplicate leaves one of its slots with the same key twice and xor 0
+                    if slot.iter().any(|&h| xors[h] == 0 && count[h] == 8) {
+                        duplicates += 1;
+                        for (index, &h) in slot.iter().enumerate() {
+                            count[h] = count[h].wrapping_sub(4) ^ index as u8;
+                            xors[h] ^= hash;
+                        }
+                    }
+                    error |= slot.iter().any(|&h| count[h] < 4);
+                }
+                if error {
+                    continue;
+                }
+
+                // peel slots with a single key
+                let mut queue = 0;
+                for (i, &keys) in count.iter().enumerate() {
+                    alone[queue] = i;
+                    queue += (keys >> 2 == 1) as usize;
+                }
+                let mut stack = 0;
+                while queue > 0 {
+                    queue -= 1;
+                    let index = alone[queue];
+                    if count[index] >> 2 != 1 {
+                        continue;
+                    }
+                    let hash = xors[index];
+                    let which = count[index] & 3;
+                    found[stack] = which;
+                    order[stack] = hash;
+                    stack += 1;
+                    let slot = self.slots(hash);
+                    for other in [(which + 1) % 3, (which + 2) % 3] {
+                        let h = slot[other as usize];
+                        alone[queue] = h;
+                        queue += (count[h] >> 2 == 2) as usize;
+                        count[h] = count[h].wrapping_sub(4) ^ other;
+                        xors[h] ^= hash;
+                    }
+                }
+                if stack + duplicates == size {
+                    break 'seed stack;
+                }
+                // not every duplicate is noticed while adding them
+                if duplicates > 0 {
+                    keys.sort_unstable();
+                    keys.dedup();
+                    size = keys.len();
+                }
+            }
+            panic!("BinaryFuseFilter construction failed, the keys hash poorly!");
+        };
+
+        order.truncate(stack);
+        found.truncate(stack);
+        (order, found)
+    }
+
+    /// Assigns `cells`, a table with the layout of the fingerprints, in reverse peeling order so
+    /// that the three slots of every key xor to its `target`.
+    fn solve<T: FuseFingerprint>(&self, cells: &mut [T], order: &[u64], found: &[u8],
+                                 target: impl Fn(u64) -> T) {
+        for (&hash, &which) in order.iter().zip(found).rev() {
+            let slot = self.slots(hash);
+            let which = which as usize;
+            cells[slot[which]] = target(hash) ^ cells[slot[(which + 1) % 3]]
+                ^ cells[slot[(which + 2) % 3]];
+        }
+    }
+}
+
+/// Bloomier filter of a fixed map from keys to small values, e.g. a shard id or a routing tag
+/// (Chazelle et al., "The Bloomier Filter"), laid out like a [BinaryFuseFilter]. A second table
+/// of values is solved with the same three slots per key, so that they xor to the value of the
+/// key, and [BloomierFilter::get] returns it for the keys the fingerprints match.
+///
+/// The keys themselves are not stored: a value takes about `1.125 * (V::BITS + F::BITS)` bits
+/// per key, and a non-member gets an arbitrary value with the false positive probability
+/// `2^-F::BITS` of the fingerprints, [None] otherwise. `V` and `F` are `u8` or `u16`.
+///
+/// # Examples
+///
+/// ```rust
+/// use fastbloom_rs::{BloomierFilter, FilterBuilder};
+///
+/// let shards = [("alice", 3u8), ("bob", 7), ("carol", 3)];
+/// let filter: BloomierFilter = FilterBuilder::new(3, 0.01).build_bloomier_filter(shards);
+/// assert_eq!(filter.get(b"bob"), Some(7));
+/// assert_eq!(filter.get(b"carol"), Some(3));
+/// assert_eq!(filter.contains(b"alice"), true);
+/// ```
+#[derive(Clone)]
+#[derive(Debug)]
+pub struct BloomierFilter<V: FuseFingerprint = u8, F: FuseFingerprint = u8> {
+    filter: BinaryFuseFilter<F>,
+    values: Vec<V>,
+}
+
+impl<V: FuseFingerprint, F: FuseFingerprint> BloomierFilter<V, F> {
+    /// Builds the filter of the `(key, value)` pairs, hashed with `config`. If a key occurs more
+    /// than once, its last value is kept. Panics if no solution is found, which only happens for
+    /// hash functions of poor quality.
+    pub fn from_entries<K: AsRef<[u8]>>(config: FilterBuilder,
+                                        entries: impl IntoIterator<Item=(K, V)>) -> Self {
+        let entries: Vec<(u64, V)> = entries.into_iter()
+            .map(|(key, value)| (hash_pair(key.as_ref(), &config).0, value))
+            .collect();
+        let mut filter = BinaryFuseFilter::empty(config, entries.len());
+        let (order, found) = filter.peel(entries.iter().map(|&(hash, _)| hash).collect());
+        let mut fingerprints = core::mem::take(&mut filter.fingerprints);
+        filter.solve(&mut fingerprints, &order, &found, F::from_hash);
+        filter.fingerprints = fingerprints;
+
+        // the values by seeded hash, the last one of a key sorts last
+        let mut seeded: Vec<(u64, V)> = entries.into_iter()
+            .map(|(hash, value)| (mix64(hash.wrapping_add(filter.seed)), value))
+            .collect();
+        seeded.sort_by_key(|&(hash, _)| hash);
+        let mut values = vec![V::default(); filter.fingerprints.len()];
+        filter.solve(&mut values, &order, &found, |hash| {
+            seeded[seeded.partition_point(|&(seeded, _)| seeded <= hash) - 1].1
+        });
+        BloomierFilter { filter, values }
+    }
+
+    /// Returns the configuration of the filter.
+    pub fn config(&self) -> FilterBuilder {
+        self.filter.config()
+    }
+
+    /// Returns the number of entries the filter was built from, including duplicates.
+    pub fn len(&self) -> usize {
+        self.filter.len()
+    }
+
+    /// Returns [true] if the filter was built from no entries.
+    pub fn is_empty(&self) -> bool {
+        self.filter.is_empty()
+    }
+
+    /// Returns the number of bits of fingerprints and values per entry.
+    pub fn bits_per_key(&self) -> f64 {
+        self.filter.bits_per_key() * (size_of::<V>() + size_of::<F>()) as f64
+            / size_of::<F>() as f64
+    }
+
+    /// Tests whether an element is one of the keys (subject to the false positive probability).
+    #[inline]
+    pub fn contains(&self, element: &[u8]) -> bool {
+        self.filter.contains(element)
+    }
+
+    /// Returns the value of a key, or [None] if the element is not one of the keys (subject to
+    /// the false positive probability).
+    #[inline]
+    pub fn get(&self, element: &[u8]) -> Option<V> {
+        let (hash, slots) = self.filter.locate(element);
+        let [h0, h1, h2] = slots;
+        self.filter.matches(hash, slots)
+            .then(|| self.values[h0] ^ self.values[h1] ^ self.values[h2])
+    }
+}
+
+#[test]
+fn binary_fuse_filter_test() {
+    let config = FilterBuilder::new(100_000, 0.01);
+    let filter = BinaryFuse8::from_keys(config.clone(), (0..100_000u32).map(u32::to_le_bytes));
+    assert_eq!(filter.len(), 100_000);
+    assert!((0..100_000u32).all(|i| filter.contains(&i.to_le_bytes())));
+    assert!(filter.bits_per_key() < 10.0, "{} bits per key", filter.bits_per_key());
+    let false_positives = (100_000..1_100_000u32)
+        .filter(|i| filter.contains(&i.to_le_bytes()))
+        .count();
+    assert!(false_positives < 5000, "{false_positives} false positives");
+
+    let filter = BinaryFuse16::from_keys(config, (0..100_000u32).map(u32::to_le_bytes));
+    assert!((0..100_000u32).all(|i| filter.contains(&i.to_le_bytes())));
+    let false_positives = (100_000..1_100_000u32)
+        .filter(|i| filter.contains(&i.to_le_bytes()))
+        .count();
+    assert!(false_positives < 50, "{false_positives} false positives");
+}
+
+#[test]
+fn binary_fuse_filter_small_test() {
+    let config = FilterBuilder::new(10, 0.01);
+    let empty = BinaryFuse8::from_keys(config.clone(), [b""; 0]);
+    assert!(empty.is_empty());
+    assert!(!empty.contains(b"hello"));
+
+    for n in [1u32, 2, 3, 10, 100] {
+        // every key twice
+        let keys = (0..n).chain(0..n).map(u32::to_le_bytes);
+        let filter = BinaryFuse16::from_keys(config.clone(), keys);
+        assert_eq!(filter.len(), 2 * n as usize);
+        assert!((0..n).all(|i| filter.contains(&i.to_le_bytes())), "{n} keys");
+    }
+}
+
+#[test]
+fn bloomier_filter_test() {
+    let config = FilterBuilder::new(100_000, 0.01);
+    let entries = (0..100_000u32).map(|i| (i.to_le_bytes(), (i % 16) as u8));
+    let filter = BloomierFilter::<u8, u16>::from_entries(config.clone(), entries);
+    assert_eq!(filter.len(), 100_000);
+    assert!((0..100_000u32).all(|i| filter.get(&i.to_le_bytes()) == Some((i % 16) as u8)));
+    assert!(filter.bits_per_key() < 29.0, "{} bits per key", filter.bits_per_key());
+    let false_positives = (100_000..1_100_000u32)
+        .filter(|i| filter.get(&i.to_le_bytes()).is_some())
+        .count();
+    assert!(false_positives < 50, "{false_positives} false positives");
+
+    // the last value of a duplicate key wins
+    let entries = [(b"a", 1u16), (b"b", 2), (b"a", 3)];
+    let filter: BloomierFilter<u16> = BloomierFilter::from_entries(config.clone(), entries);
+    assert_eq!((filter.get(b"a"), filter.get(b"b")), (Some(3), Some(2)));
+    let empty: BloomierFilter = BloomierFilter::from_entries(config, [(b"", 0u8); 0]);
+    assert!(empty.is_empty());
+    assert_eq!(empty.get(b"a"), None);
+}
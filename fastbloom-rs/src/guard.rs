@@ -0,0 +1,250 @@
+use alloc::sync::Arc;
+use core::fmt::{self, Display, Formatter};
+use core::mem;
+
+use smallvec::SmallVec;
+
+use crate::bloom::BloomFilter;
+use crate::events::FilterEvents;
+#[cfg(not(feature = "std"))]
+use crate::float::Float;
+use crate::index::Indices;
+use crate::Membership;
+
+/// What a [GuardedBloomFilter] does once its fill ratio reaches the limit.
+#[derive(Clone)]
+pub enum FillPolicy {
+    /// Adds fail with a [SaturatedError] and leave the filter unchanged.
+    Error,
+    /// Calls [FilterEvents::on_saturate] once with the fill ratio and keeps adding, e.g. to
+    /// start a rebuild elsewhere.
+    Callback(Arc<dyn FilterEvents>),
+    /// Keeps the full filter as the previous generation, which queries still probe, and adds to
+    /// a new empty filter. The one before is dropped.
+    Rotate,
+}
+
+impl fmt::Debug for FillPolicy {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            FillPolicy::Error => f.write_str("Error"),
+            FillPolicy::Callback(_) => f.write_str("Callback"),
+            FillPolicy::Rotate => f.write_str("Rotate"),
+        }
+    }
+}
+
+/// Error of an add to a [GuardedBloomFilter] under [FillPolicy::Error] whose fill ratio
+/// reached its limit.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SaturatedError {
+    /// Share of set bits of the filter.
+    pub fill_ratio: f64,
+    /// The limit given to [BloomFilter::on_fill_ratio].
+    pub limit: f64,
+}
+
+impl Display for SaturatedError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "filter is saturated: fill ratio {} >= {}", self.fill_ratio, self.limit)
+    }
+}
+
+impl core::error::Error for SaturatedError {}
+
+/// Bloom filter which keeps track of its set bits on every add, so adds past the point the
+/// filter was designed for fail or rotate it, see [FillPolicy], instead of silently raising the
+/// false positive probability. A filter at its capacity has half of its bits set, so a limit of
+/// `0.5` guards the configured false positive probability. Counting the bits costs a test of
+/// each bit before setting it, the limit is checked against the count.
+///
+/// # Examples
+///
+/// ```rust
+/// use fastbloom_rs::{FillPolicy, FilterBuilder};
+///
+/// let bloom = FilterBuilder::new(100, 0.01).build_bloom_filter();
+/// let mut filter = bloom.on_fill_ratio(0.5, FillPolicy::Error);
+/// let mut added = 0u32;
+/// while filter.add(&added.to_le_bytes()).is_ok() {
+///     added += 1;
+/// }
+/// // about the 100 elements the filter is sized for
+/// assert!(added > 80);
+/// assert!(filter.fill_ratio() >= 0.5);
+/// ```
+#[derive(Clone)]
+#[derive(Debug)]
+pub struct GuardedBloomFilter {
+    bloom: BloomFilter,
+    previous: Option<BloomFilter>,
+    policy: FillPolicy,
+    limit: f64,
+    /// Set bits of `bloom` and the number at the limit.
+    ones: u64,
+    limit_ones: u64,
+    /// Whether [FillPolicy::Callback] was called for `bloom`.
+    notified: bool,
+    rotations: u64,
+}
+
+impl BloomFilter {
+    /// Guards the fill ratio (share of set bits) of this filter: once it reaches `limit`,
+    /// `policy` applies, see [GuardedBloomFilter]. Panics unless `limit` is in `(0, 1]`.
+    pub fn on_fill_ratio(self, limit: f64, policy: FillPolicy) -> GuardedBloomFilter {
+        assert!(limit > 0.0 && limit <= 1.0, "fill ratio limit must be in (0, 1]!");
+        let limit_ones = (limit * self.config.size as f64).ceil() as u64;
+        GuardedBloomFilter {
+            ones: self.bits_set(),
+            bloom: self,
+            previous: None,
+            policy,
+            limit,
+            limit_ones,
+            notified: false,
+            rotations: 0,
+        }
+    }
+}
+
+impl GuardedBloomFilter {
+    /// Adds the element, after applying the policy if the fill ratio reached the limit. Fails
+    /// only under [FillPolicy::Error], without adding the element.
+    pub fn add(&mut self, element: &[u8]) -> Result<(), SaturatedError> {
+        if self.ones >= self.limit_ones {
+            match &self.policy {
+                FillPolicy::Error => {
+                    return Err(SaturatedError { fill_ratio: self.fill_ratio(), limit: self.limit });
+                }
+                FillPolicy::Callback(events) if !self.notified => {
+                    self.notified = true;
+                    events.on_saturate(self.fill_ratio());
+                }
+                FillPolicy::Callback(_) => {}
+                FillPolicy::Rotate => self.rotate(),
+            }
+        }
+        let bloom = &mut self.bloom;
+        let indices: SmallVec<[u64; 16]> = Indices::new(element, &bloom.config).collect();
+        assert!(!indices.is_empty(), "cannot add to a filter without capacity!");
+        bloom.stats.record_add();
+        for &index in &indices {
+            if !bloom.bit_set.get(index as usize) {
+                bloom.bit_set.set(index as usize);
+                self.ones += 1;
+            }
+        }
+        if let Some(events) = bloom.hook.get() {
+            events.on_insert(element, &indices);
+        }
+        Ok(())
+    }
+
+    /// Tests whether an element is present in the filter or, after a rotation, in the previous
+    /// one (subject to the false positive probability).
+    pub fn contains(&self, element: &[u8]) -> bool {
+        self.bloom.contains(element)
+            || self.previous.as_ref().is_some_and(|previous| previous.contains(element))
+    }
+
+    /// Returns the share of set bits of the filter added to, from the maintained count.
+    pub fn fill_ratio(&self) -> f64 {
+        if self.bloom.config.size == 0 {
+            return 0.0;
+        }
+        self.ones as f64 / self.bloom.config.size as f64
+    }
+
+    /// Returns the number of set bits of the filter added to.
+    pub fn bits_set(&self) -> u64 {
+        self.ones
+    }
+
+    /// Returns the filter added to.
+    pub fn filter(&self) -> &BloomFilter {
+        &self.bloom
+    }
+
+    /// Returns the previous filter, replaced by the last rotation of [FillPolicy::Rotate].
+    pub fn previous(&self) -> Option<&BloomFilter> {
+        self.previous.as_ref()
+    }
+
+    /// Returns the number of rotations by [FillPolicy::Rotate].
+    pub fn rotations(&self) -> u64 {
+        self.rotations
+    }
+
+    /// Removes all elements, including those of the previous filter.
+    pub fn clear(&mut self) {
+        self.bloom.clear();
+        self.previous = None;
+        self.ones = 0;
+        self.notified = false;
+    }
+
+    /// Returns the filter added to, dropping the previous one.
+    pub fn into_inner(self) -> BloomFilter {
+        self.bloom
+    }
+
+    fn rotate(&mut self) {
+        let mut fresh = BloomFilter::new(self.bloom.config.clone());
+        fresh.hook = self.bloom.hook.clone();
+        self.previous = Some(mem::replace(&mut self.bloom, fresh));
+        self.ones = 0;
+        self.notified = false;
+        self.rotations += 1;
+    }
+}
+
+#[test]
+fn guarded_bloom_filter_test() {
+    use alloc::vec::Vec;
+    use core::sync::atomic::{AtomicU64, Ordering};
+
+    use crate::FilterBuilder;
+
+    let bloom = FilterBuilder::new(1000, 0.01).build_bloom_filter();
+    let mut filter = bloom.clone().on_fill_ratio(0.5, FillPolicy::Error);
+    let mut added = 0u32;
+    while filter.add(&added.to_le_bytes()).is_ok() {
+        added += 1;
+    }
+    assert!((900..1200).contains(&added), "{added} adds");
+    assert_eq!(filter.bits_set(), filter.filter().bits_set());
+    let error = filter.add(b"hello").unwrap_err();
+    assert!(error.fill_ratio >= 0.5 && error.limit == 0.5);
+    assert!(!filter.contains(b"hello"));
+    filter.clear();
+    assert!(filter.add(b"hello").is_ok());
+
+    #[derive(Default)]
+    struct Saturations(AtomicU64);
+
+    impl FilterEvents for Saturations {
+        fn on_saturate(&self, _fill_ratio: f64) {
+            self.0.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    let events = Arc::new(Saturations::default());
+    let mut filter = bloom.clone().on_fill_ratio(0.5, FillPolicy::Callback(events.clone()));
+    (0..3000u32).for_each(|i| filter.add(&i.to_le_bytes()).unwrap());
+    assert_eq!(events.0.load(Ordering::Relaxed), 1);
+    assert!(filter.fill_ratio() > 0.5);
+
+    let mut filter = bloom.on_fill_ratio(0.5, FillPolicy::Rotate);
+    (0..3000u32).for_each(|i| filter.add(&i.to_le_bytes()).unwrap());
+    assert!(filter.rotations() >= 2, "{} rotations", filter.rotations());
+    assert!(filter.fill_ratio() <= 0.5 + 1e-2);
+    // the adds since the rotation before the last one are still seen
+    let seen: Vec<u32> = (0..3000u32).filter(|i| filter.contains(&i.to_le_bytes())).collect();
+    assert!((2000..3000u32).all(|i| seen.contains(&i)));
+}
+
+#[test]
+#[should_panic(expected = "fill ratio limit must be in (0, 1]")]
+fn guarded_bloom_filter_limit_test() {
+    crate::FilterBuilder::new(100, 0.01).build_bloom_filter().on_fill_ratio(0.0, FillPolicy::Error);
+}
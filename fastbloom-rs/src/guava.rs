@@ -0,0 +1,126 @@
+use crate::bloom::BloomFilter;
+use crate::builder::FilterBuilder;
+use crate::index::IndexScheme;
+use crate::vec::{BloomBitVec, le_word, Storage};
+
+/// Ordinal of `BloomFilterStrategies.MURMUR128_MITZ_64`, the strategy of all Guava releases
+/// since 13.
+const MURMUR128_MITZ_64: u8 = 1;
+
+impl BloomFilter {
+    /// Reads a filter written by Guava's `BloomFilter.writeTo`: the strategy ordinal, the number
+    /// of hashes and the bits as big-endian longs. The filter uses [IndexScheme::Guava], so it
+    /// answers like the Guava filter and elements added on either side are found on the other.
+    /// Keys are the bytes the Guava funnel feeds to the hasher, e.g. the UTF-8 bytes for
+    /// `Funnels.stringFunnel(UTF_8)` or `to_le_bytes` for `Funnels.longFunnel()`.
+    ///
+    /// Returns `None` for truncated data and for filters of the legacy `MURMUR128_MITZ_32`
+    /// strategy.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use fastbloom_rs::{BloomFilter, FilterBuilder, IndexScheme, Membership};
+    ///
+    /// let mut builder = FilterBuilder::new(1000, 0.01);
+    /// builder.index_scheme(IndexScheme::Guava);
+    /// let mut bloom = builder.build_bloom_filter();
+    /// bloom.add("hello".as_bytes());
+    ///
+    /// let bytes = bloom.to_guava_bytes();
+    /// let restored = BloomFilter::from_guava_bytes(&bytes).unwrap();
+    /// assert!(restored.contains("hello".as_bytes()));
+    /// ```
+    pub fn from_guava_bytes(bytes: &[u8]) -> Option<Self> {
+        let (&[strategy, hashes], rest) = bytes.split_first_chunk::<2>()?;
+        let (len, longs) = rest.split_first_chunk::<4>()?;
+        let len = usize::try_from(i32::from_be_bytes(*len)).ok()?;
+        if strategy != MURMUR128_MITZ_64 || hashes == 0 || len == 0
+            || len.checked_mul(8) != Some(longs.len()) {
+            return None;
+        }
+        let mut config = FilterBuilder::from_size_and_hashes(len as u64 * 64, hashes as u32);
+        config.index_scheme(IndexScheme::Guava);
+        config.complete();
+        let le: Vec<u8> = longs.chunks_exact(8)
+            .flat_map(|long| u64::from_be_bytes(long.try_into().unwrap()).to_le_bytes())
+            .collect();
+        let storage = le.chunks_exact(size_of::<usize>())
+            .map(|word| le_word(usize::from_le_bytes(word.try_into().unwrap())))
+            .collect();
+        Some(BloomFilter::from_bit_vec(config, BloomBitVec::with_storage(storage)))
+    }
+}
+
+impl<S: Storage> BloomFilter<S> {
+    /// Serializes the filter in the format of Guava's `BloomFilter.writeTo`, to be read with
+    /// `BloomFilter.readFrom(in, funnel)` on the JVM, see [BloomFilter::from_guava_bytes].
+    /// Panics unless the filter uses [IndexScheme::Guava] with the built-in hasher, a seed of 0,
+    /// no canaries or partitions, at most 255 hashes and a size of whole 64-bit words.
+    pub fn to_guava_bytes(&self) -> Vec<u8> {
+        let config = &self.config;
+        assert!(config.index_scheme == IndexScheme::Guava && config.hasher.hasher.is_none()
+                    && config.seed == 0 && config.canaries == 0 && !config.partitioned,
+                "filter must use IndexScheme::Guava with the default hashing!");
+        assert!(config.hashes <= u8::MAX as u32, "Guava filters have at most 255 hashes!");
+        assert_eq!(config.size % 64, 0, "Guava filters have a size of whole longs!");
+        let longs = i32::try_from(config.size / 64).expect("filter is too large for Guava!");
+        let storage = &self.bit_set.storage;
+        let le: Vec<u8> = (0..storage.slots())
+            .flat_map(|slot| self.bit_set.word(slot).to_le_bytes())
+            .collect();
+        let mut out = Vec::with_capacity(6 + le.len());
+        out.extend_from_slice(&[MURMUR128_MITZ_64, config.hashes as u8]);
+        out.extend_from_slice(&longs.to_be_bytes());
+        for long in le.chunks_exact(8) {
+            out.extend_from_slice(&u64::from_le_bytes(long.try_into().unwrap()).to_be_bytes());
+        }
+        out
+    }
+}
+
+#[test]
+fn guava_indices_test() {
+    use crate::index::Indices;
+
+    // murmur3_x64_128 of Guava's test vector, `Murmur3Hash128Test`.
+    let mut config = FilterBuilder::from_size_and_hashes(64 * 100, 5);
+    config.index_scheme(IndexScheme::Guava);
+    config.complete();
+    let key = b"The quick brown fox jumps over the lazy dog";
+    let (hash1, hash2) = (0xe34bbc7bbc071b6cu64, 0x7a433ca9c49a9347u64);
+    let expected: Vec<u64> = (0..5u64)
+        .map(|i| (hash1.wrapping_add(i.wrapping_mul(hash2)) & i64::MAX as u64) % config.size)
+        .collect();
+    assert_eq!(Indices::new(key, &config).collect::<Vec<_>>(), expected);
+}
+
+#[test]
+fn guava_bytes_test() {
+    use crate::Membership;
+
+    let mut builder = FilterBuilder::new(1000, 0.01);
+    builder.index_scheme(IndexScheme::Guava);
+    let mut bloom = builder.build_bloom_filter();
+    for i in 0..1000u64 {
+        bloom.add(&i.to_le_bytes());
+    }
+    let bytes = bloom.to_guava_bytes();
+    let longs = bloom.config().size as usize / 64;
+    assert_eq!(bytes[..2], [1, bloom.config().hashes as u8]);
+    assert_eq!(bytes[2..6], (longs as i32).to_be_bytes());
+    assert_eq!(bytes.len(), 6 + longs * 8);
+    // bit `i` is bit `i % 64` of long `i / 64`
+    let index = bloom.get_hash_indices(&0u64.to_le_bytes())[0] as usize;
+    assert_ne!(bytes[6 + index / 64 * 8 + 7 - index % 64 / 8] & (1 << (index % 8)), 0);
+
+    let restored = BloomFilter::from_guava_bytes(&bytes).unwrap();
+    assert_eq!(restored.get_u8_array(), bloom.get_u8_array());
+    assert!(restored.config().is_compatible_to(&bloom.config()));
+    assert!((0..1000u64).all(|i| restored.contains(&i.to_le_bytes())));
+
+    assert!(BloomFilter::from_guava_bytes(&bytes[..bytes.len() - 1]).is_none());
+    let mut legacy = bytes.clone();
+    legacy[0] = 0;
+    assert!(BloomFilter::from_guava_bytes(&legacy).is_none());
+}
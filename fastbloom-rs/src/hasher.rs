@@ -0,0 +1,303 @@
+use alloc::collections::BTreeMap;
+use alloc::sync::Arc;
+#[cfg(not(feature = "std"))]
+use core::cell::UnsafeCell;
+use core::fmt::{Debug, Formatter};
+use core::hash::{BuildHasher, Hash, Hasher};
+#[cfg(not(feature = "std"))]
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use smallvec::SmallVec;
+
+use crate::family::HashFamily;
+use crate::index::mix64;
+
+/// Hash function of a filter, see [register_hasher]. The trait is object safe so hashers can be
+/// looked up by the id stored in the serialized header of a filter.
+pub trait FilterHasher: Send + Sync {
+    /// Hashes `element` with `seed` to the two 64-bit values its bit indices are derived from.
+    fn hash_pair(&self, element: &[u8], seed: u64) -> (u64, u64);
+}
+
+/// Id of the built-in hashing (xxh3), used when no other hasher is set, see [HashFamily].
+pub const DEFAULT_HASHER: u32 = 0;
+
+/// Ids below this value are reserved for hashers shipped with the crate.
+pub const FIRST_CUSTOM_HASHER: u32 = 256;
+
+type Registry = BTreeMap<u32, Arc<dyn FilterHasher>>;
+
+/// Runs `f` on the locked registry. Without `std` the lock spins, the registry is only touched
+/// when hashers are registered and resolved.
+fn with_registry<R>(f: impl FnOnce(&mut Registry) -> R) -> R {
+    #[cfg(feature = "std")]
+    {
+        static REGISTRY: std::sync::Mutex<Registry> = std::sync::Mutex::new(BTreeMap::new());
+        f(&mut REGISTRY.lock().unwrap())
+    }
+    #[cfg(not(feature = "std"))]
+    {
+        static REGISTRY: SpinLock = SpinLock {
+            locked: AtomicBool::new(false),
+            registry: UnsafeCell::new(BTreeMap::new()),
+        };
+        while REGISTRY.locked.compare_exchange_weak(false, true, Ordering::Acquire,
+                                                    Ordering::Relaxed).is_err() {
+            core::hint::spin_loop();
+        }
+        // SAFETY: the lock is held.
+        let result = f(unsafe { &mut *REGISTRY.registry.get() });
+        REGISTRY.locked.store(false, Ordering::Release);
+        result
+    }
+}
+
+#[cfg(not(feature = "std"))]
+struct SpinLock {
+    locked: AtomicBool,
+    registry: UnsafeCell<Registry>,
+}
+
+// SAFETY: the registry is only accessed while `locked` is held.
+#[cfg(not(feature = "std"))]
+unsafe impl Sync for SpinLock {}
+
+/// Registers `hasher` under `id`, so filters can be built with [crate::FilterBuilder::hasher]
+/// and filters built with it can be restored. Returns `false` when `id` is reserved (below
+/// [FIRST_CUSTOM_HASHER]) or already taken.
+///
+/// # Examples
+///
+/// ```rust
+/// use std::sync::Arc;
+///
+/// use fastbloom_rs::{CountingBloomFilter, FilterBuilder, FilterHasher, register_hasher};
+///
+/// struct Fnv;
+///
+/// impl FilterHasher for Fnv {
+///     fn hash_pair(&self, element: &[u8], seed: u64) -> (u64, u64) {
+///         let hash = element.iter().fold(0xcbf29ce484222325 ^ seed, |h, b| {
+///             (h ^ *b as u64).wrapping_mul(0x100000001b3)
+///         });
+///         (hash, hash.rotate_left(32) | 1)
+///     }
+/// }
+///
+/// assert!(register_hasher(1000, Arc::new(Fnv)));
+/// let mut builder = FilterBuilder::new(10_000, 0.01);
+/// builder.hasher(1000);
+/// let mut cbf = builder.build_counting_bloom_filter::<Vec<usize>>(());
+/// cbf.add(b"hello");
+/// let restored = CountingBloomFilter::from_snapshot_compressed(&cbf.snapshot_compressed()).unwrap();
+/// assert_eq!(restored.estimate_count(b"hello"), 1);
+/// ```
+pub fn register_hasher(id: u32, hasher: Arc<dyn FilterHasher>) -> bool {
+    if id < FIRST_CUSTOM_HASHER {
+        return false;
+    }
+    with_registry(|registry| {
+        if registry.contains_key(&id) {
+            return false;
+        }
+        registry.insert(id, hasher);
+        true
+    })
+}
+
+/// Adapts a [BuildHasher] (e.g. of the `ahash` or `wyhash` crates) to a [FilterHasher], so any
+/// hash family can be registered with [register_hasher]. The element is hashed once after the
+/// seed, the second value is derived from the first. The [BuildHasher] must be deterministic
+/// (not [RandomState](std::collections::hash_map::RandomState)) for filters which are persisted
+/// or shared between processes. To match filters written by another implementation, e.g. a
+/// Java service, implement [FilterHasher] with its exact derivation of the two values instead.
+///
+/// # Examples
+///
+/// ```rust
+/// use std::hash::BuildHasherDefault;
+/// use std::collections::hash_map::DefaultHasher;
+/// use std::sync::Arc;
+///
+/// use fastbloom_rs::{BuildHasherAdapter, FilterBuilder, Membership, register_hasher};
+///
+/// let sip = BuildHasherAdapter(BuildHasherDefault::<DefaultHasher>::default());
+/// assert!(register_hasher(1001, Arc::new(sip)));
+/// let mut builder = FilterBuilder::new(10_000, 0.01);
+/// builder.hasher(1001);
+/// let mut bloom = builder.build_bloom_filter();
+/// bloom.add(b"hello");
+/// assert!(bloom.contains(b"hello"));
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct BuildHasherAdapter<B>(pub B);
+
+impl<B: BuildHasher + Send + Sync> FilterHasher for BuildHasherAdapter<B> {
+    fn hash_pair(&self, element: &[u8], seed: u64) -> (u64, u64) {
+        let mut hasher = self.0.build_hasher();
+        hasher.write_u64(seed);
+        hasher.write(element);
+        let hash1 = hasher.finish();
+        // mixed, so the second value doesn't share the low bits of the first.
+        (hash1, mix64(hash1.wrapping_add(0x9e3779b97f4a7c15)) | 1)
+    }
+}
+
+/// [Hasher] collecting the bytes a [Hash] implementation writes, as the key of an element added
+/// with [Membership::add_hashable](crate::Membership::add_hashable). Integers are written
+/// little-endian and `usize`/`isize` as 64 bits, so keys are the same on all platforms.
+#[derive(Default)]
+pub(crate) struct KeyBytes(pub(crate) SmallVec<[u8; 64]>);
+
+impl KeyBytes {
+    pub(crate) fn of<T: Hash + ?Sized>(item: &T) -> Self {
+        let mut key = KeyBytes::default();
+        item.hash(&mut key);
+        key
+    }
+}
+
+impl Hasher for KeyBytes {
+    /// Unused, the filter hashes the collected bytes.
+    fn finish(&self) -> u64 {
+        0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        self.0.extend_from_slice(bytes);
+    }
+
+    fn write_u16(&mut self, i: u16) {
+        self.write(&i.to_le_bytes());
+    }
+
+    fn write_u32(&mut self, i: u32) {
+        self.write(&i.to_le_bytes());
+    }
+
+    fn write_u64(&mut self, i: u64) {
+        self.write(&i.to_le_bytes());
+    }
+
+    fn write_u128(&mut self, i: u128) {
+        self.write(&i.to_le_bytes());
+    }
+
+    fn write_usize(&mut self, i: usize) {
+        self.write_u64(i as u64);
+    }
+
+    fn write_i16(&mut self, i: i16) {
+        self.write_u16(i as u16);
+    }
+
+    fn write_i32(&mut self, i: i32) {
+        self.write_u32(i as u32);
+    }
+
+    fn write_i64(&mut self, i: i64) {
+        self.write_u64(i as u64);
+    }
+
+    fn write_i128(&mut self, i: i128) {
+        self.write_u128(i as u128);
+    }
+
+    fn write_isize(&mut self, i: isize) {
+        self.write_u64(i as u64);
+    }
+}
+
+/// A hasher id together with the hasher it was resolved to, so hashing doesn't go through the
+/// registry.
+#[derive(Clone, Default)]
+pub(crate) struct HasherRef {
+    pub(crate) id: u32,
+    pub(crate) hasher: Option<Arc<dyn FilterHasher>>,
+    /// Check value of the secret key of a keyed hasher, `0` for the others.
+    pub(crate) key_check: u64,
+}
+
+impl HasherRef {
+    /// The built-in hasher, a const version of [Default::default].
+    pub(crate) const fn builtin() -> Self {
+        HasherRef { id: DEFAULT_HASHER, hasher: None, key_check: 0 }
+    }
+
+    /// Resolves `id`, returns `None` when no hasher is registered for it.
+    pub(crate) fn resolve(id: u32) -> Option<Self> {
+        if id < FIRST_CUSTOM_HASHER {
+            return HashFamily::from_id(id).map(|family| family.hasher_ref());
+        }
+        let hasher = with_registry(|registry| registry.get(&id).cloned())?;
+        Some(HasherRef { id, hasher: Some(hasher), key_check: 0 })
+    }
+}
+
+impl Debug for HasherRef {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        write!(f, "HasherRef({})", self.id)
+    }
+}
+
+impl PartialEq for HasherRef {
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id && self.key_check == other.key_check
+    }
+}
+
+#[test]
+fn hasher_registry_test() {
+    use crate::{FilterBuilder, Membership};
+
+    struct Constant;
+    impl FilterHasher for Constant {
+        fn hash_pair(&self, _element: &[u8], seed: u64) -> (u64, u64) {
+            (seed, 1)
+        }
+    }
+
+    assert!(!register_hasher(1, Arc::new(Constant)));
+    assert!(register_hasher(2000, Arc::new(Constant)));
+    assert!(!register_hasher(2000, Arc::new(Constant)));
+    assert!(HasherRef::resolve(2001).is_none());
+
+    let mut builder = FilterBuilder::new(10_000, 0.01);
+    builder.hasher(2000);
+    let mut bloom = builder.build_bloom_filter();
+    bloom.add(b"hello");
+    // every element hashes to the same indices
+    assert!(bloom.contains(b"world"));
+    assert!(!bloom.config().is_compatible_to(&FilterBuilder::new(10_000, 0.01).build_bloom_filter().config()));
+}
+
+#[test]
+fn build_hasher_adapter_test() {
+    use std::collections::hash_map::DefaultHasher;
+    use std::hash::BuildHasherDefault;
+
+    use crate::{FilterBuilder, Membership};
+
+    let adapter = BuildHasherAdapter(BuildHasherDefault::<DefaultHasher>::default());
+    assert_eq!(adapter.hash_pair(b"hello", 1), adapter.hash_pair(b"hello", 1));
+    assert_ne!(adapter.hash_pair(b"hello", 1), adapter.hash_pair(b"hello", 2));
+    assert_eq!(adapter.hash_pair(b"hello", 1).1 & 1, 1);
+
+    assert!(register_hasher(2002, Arc::new(adapter)));
+    let mut builder = FilterBuilder::new(10_000, 0.01);
+    builder.hasher(2002);
+    let mut bloom = builder.build_bloom_filter();
+    for i in 0..1000u32 {
+        bloom.add(&i.to_le_bytes());
+    }
+    assert!((0..1000u32).all(|i| bloom.contains(&i.to_le_bytes())));
+    assert!((1000..11_000u32).filter(|i| bloom.contains(&i.to_le_bytes())).count() < 300);
+}
+
+#[test]
+fn key_bytes_test() {
+    assert_eq!(KeyBytes::of(&0x0102u16).0.as_slice(), &[2, 1]);
+    assert_eq!(KeyBytes::of(&7usize).0.as_slice(), &7u64.to_le_bytes());
+    // str writes a terminator, so ("ab", "c") and ("a", "bc") differ
+    assert_ne!(KeyBytes::of(&("ab", "c")).0, KeyBytes::of(&("a", "bc")).0);
+}
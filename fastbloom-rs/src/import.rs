@@ -0,0 +1,131 @@
+use std::fs::File;
+use std::io;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+use std::thread;
+
+use crate::bloom::BloomFilter;
+use crate::builder::FilterBuilder;
+use crate::index::Indices;
+
+/// Number of keys read from a key file before their indices are computed in parallel.
+const IMPORT_BATCH: usize = 64 * 1024;
+
+/// Iterates over the distinct keys of a sorted key file, one key per line.
+struct SortedKeys<R> {
+    lines: io::Split<R>,
+    last: Option<Vec<u8>>,
+}
+
+impl<R: BufRead> SortedKeys<R> {
+    fn new(reader: R) -> Self {
+        SortedKeys { lines: reader.split(b'\n'), last: None }
+    }
+
+    /// Returns the next distinct key, fails with [io::ErrorKind::InvalidData] when the keys are
+    /// not sorted.
+    fn next_key(&mut self) -> io::Result<Option<Vec<u8>>> {
+        for line in self.lines.by_ref() {
+            let mut key = line?;
+            if key.last() == Some(&b'\r') {
+                key.pop();
+            }
+            match self.last.as_ref().map(|last| last.as_slice().cmp(&key)) {
+                Some(std::cmp::Ordering::Equal) => continue,
+                Some(std::cmp::Ordering::Greater) => {
+                    return Err(io::Error::new(io::ErrorKind::InvalidData, "keys are not sorted"));
+                }
+                _ => {}
+            }
+            self.last = Some(key.clone());
+            return Ok(Some(key));
+        }
+        Ok(None)
+    }
+}
+
+impl BloomFilter {
+    /// Builds a Bloom filter of the keys of the file at `path`, one key per line (a trailing
+    /// `\r` is dropped), sorted bytewise e.g. by `LC_ALL=C sort`. Adjacent duplicates are
+    /// skipped, so the filter is sized for the exact number of distinct keys, which is returned
+    /// with the filter. The file is read twice, the second time hashing batches of keys on all
+    /// cores. Fails with [io::ErrorKind::InvalidData] when the keys are not sorted.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use fastbloom_rs::{BloomFilter, Membership};
+    ///
+    /// let path = std::env::temp_dir().join("fastbloom-doc-keys.txt");
+    /// std::fs::write(&path, "apple\napple\nbanana\ncherry\n").unwrap();
+    /// let (bloom, distinct) = BloomFilter::from_sorted_key_file(&path, 0.01).unwrap();
+    /// assert_eq!(distinct, 3);
+    /// assert!(bloom.contains(b"banana"));
+    /// # std::fs::remove_file(&path).unwrap();
+    /// ```
+    pub fn from_sorted_key_file(path: impl AsRef<Path>, false_positive_probability: f64)
+                                -> io::Result<(Self, u64)> {
+        let path = path.as_ref();
+        let mut keys = SortedKeys::new(BufReader::new(File::open(path)?));
+        let mut distinct = 0u64;
+        while keys.next_key()?.is_some() {
+            distinct += 1;
+        }
+
+        let config = FilterBuilder::new(distinct.max(1), false_positive_probability);
+        let mut bloom = BloomFilter::new(config);
+        let threads = thread::available_parallelism().map_or(1, |threads| threads.get());
+        let mut keys = SortedKeys::new(BufReader::new(File::open(path)?));
+        let mut batch = Vec::with_capacity(IMPORT_BATCH);
+        let mut inserted = 0u64;
+        loop {
+            batch.clear();
+            while batch.len() < IMPORT_BATCH {
+                match keys.next_key()? {
+                    Some(key) => batch.push(key),
+                    None => break,
+                }
+            }
+            if batch.is_empty() {
+                break;
+            }
+            let chunk_len = batch.len().div_ceil(threads);
+            let config = &bloom.config;
+            let indices: Vec<Vec<u64>> = thread::scope(|scope| {
+                let workers: Vec<_> = batch.chunks(chunk_len)
+                    .map(|chunk| scope.spawn(move || {
+                        chunk.iter().flat_map(|key| Indices::new(key, config)).collect()
+                    }))
+                    .collect();
+                workers.into_iter().map(|worker| worker.join().unwrap()).collect()
+            });
+            for index in indices.into_iter().flatten() {
+                bloom.bit_set.set(index as usize);
+            }
+            inserted += batch.len() as u64;
+        }
+        if inserted != distinct {
+            let message = "key file changed while reading";
+            return Err(io::Error::new(io::ErrorKind::InvalidData, message));
+        }
+        Ok((bloom, distinct))
+    }
+}
+
+#[test]
+fn sorted_key_file_test() {
+    let path = std::env::temp_dir().join(format!("fastbloom-keys-{}.txt", std::process::id()));
+    let mut keys: Vec<String> = (0..100_000).map(|i| format!("key-{i}")).collect();
+    keys.sort();
+    let text: String = keys.iter().flat_map(|key| [key, "\n", key, "\r\n"]).collect();
+    std::fs::write(&path, text).unwrap();
+    let (bloom, distinct) = BloomFilter::from_sorted_key_file(&path, 0.01).unwrap();
+    assert_eq!(distinct, 100_000);
+    assert_eq!(bloom.config().expected_elements, 100_000);
+    assert!(keys.iter().all(|key| bloom.contains(key.as_bytes())));
+
+    std::fs::write(&path, "b\na\n").unwrap();
+    let error = BloomFilter::from_sorted_key_file(&path, 0.01).unwrap_err();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+}
@@ -0,0 +1,518 @@
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+
+use xxhash_rust::xxh3::{xxh3_128_with_seed, xxh3_64, xxh3_64_with_seed};
+
+use crate::builder::FilterBuilder;
+
+/// How the `k` bit indices of an element are derived from its hash.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum IndexScheme {
+    /// The `i`-th index is `(h1 + i * h2) % m` for two 64-bit hashes `h1` and `h2`. Cheap, and
+    /// the layout of all prior releases, but for large `k` (roughly above 16) the indices get
+    /// correlated and the false positive probability drifts above the theoretical one.
+    #[default]
+    DoubleHashing,
+    /// Every index is taken from its own 64-bit value, produced by mixing a 128-bit hash of the
+    /// element with the index number (an extendable-output construction). Costs one mixing step
+    /// per index and keeps the false positive probability close to the theory for any `k`, which
+    /// is the better choice for very low false positive probabilities (`k` in the 20-30 range).
+    Extended,
+    /// Enhanced double hashing (Dillinger & Manolios): `h1` and `h2` come from one 128-bit hash
+    /// of the element instead of two 64-bit ones, and `h2` grows by `i` after the `i`-th index,
+    /// which breaks up the correlation of [IndexScheme::DoubleHashing] for large `k`. Halves the
+    /// hashing cost of long keys.
+    EnhancedDoubleHashing,
+    /// The `MURMUR128_MITZ_64` strategy of Guava's `BloomFilter`: `h1` and `h2` are the halves of
+    /// murmur3_x64_128 of the element (salted with the seed, 0 in Guava) and the `i`-th index is
+    /// `((h1 + i * h2) & i64::MAX) % m`. Filters with this scheme read and answer like the Guava
+    /// filter, see [crate::BloomFilter::from_guava_bytes].
+    #[cfg(feature = "std")]
+    Guava,
+    /// The 64-bit hashing of RedisBloom: `h1` is MurmurHash64A of the element (salted with the
+    /// seed, 0 in RedisBloom), `h2` is MurmurHash64A seeded with `h1`, and the `i`-th index is
+    /// `(h1 + i * h2) % m` without reducing the hashes first. Filters with this scheme answer like
+    /// the RedisBloom filter, see [crate::BloomFilter::from_redis_chunks].
+    RedisBloom,
+    /// The indices of the `bloomfilter` crate: the first two are `h1 % m` and `h2 % m`, the
+    /// `i`-th of the others is `((h1 + i * h2) % 0xffffffffffffffc5) % m`. Its hashes are
+    /// SipHash-1-3 under the two keys of the filter, set together with the scheme by
+    /// [FilterBuilder::bloomfilter_sip_keys](crate::FilterBuilder::bloomfilter_sip_keys), see
+    /// [crate::BloomFilter::from_bloomfilter].
+    BloomfilterCrate,
+}
+
+impl IndexScheme {
+    /// Stable identifier used in the binary encodings.
+    pub(crate) fn id(&self) -> u8 {
+        match self {
+            IndexScheme::DoubleHashing => 0,
+            IndexScheme::Extended => 1,
+            IndexScheme::EnhancedDoubleHashing => 2,
+            #[cfg(feature = "std")]
+            IndexScheme::Guava => 3,
+            IndexScheme::RedisBloom => 4,
+            IndexScheme::BloomfilterCrate => 5,
+        }
+    }
+
+    pub(crate) fn from_id(id: u8) -> Option<Self> {
+        match id {
+            0 => Some(IndexScheme::DoubleHashing),
+            1 => Some(IndexScheme::Extended),
+            2 => Some(IndexScheme::EnhancedDoubleHashing),
+            #[cfg(feature = "std")]
+            3 => Some(IndexScheme::Guava),
+            4 => Some(IndexScheme::RedisBloom),
+            5 => Some(IndexScheme::BloomfilterCrate),
+            _ => None,
+        }
+    }
+}
+
+/// Largest prime below 2^64, the combined hashes of [IndexScheme::BloomfilterCrate] are reduced
+/// modulo it.
+const BLOOMFILTER_PRIME: u64 = 0xffff_ffff_ffff_ffc5;
+
+/// Iterator over the `k` bit indices of an element. Cloning it does not hash again.
+#[derive(Clone, Debug)]
+pub(crate) struct Indices {
+    scheme: IndexScheme,
+    hash1: u64,
+    hash2: u64,
+    m: u64,
+    k: u64,
+    i: u64,
+    /// Size of a partition, `0` unless the filter is partitioned.
+    slice: u64,
+    /// Salts of [IndexScheme::Extended] given to [FilterBuilder::hash_seeds].
+    seeds: Option<Arc<[u64]>>,
+}
+
+/// The two base hashes of an element, which its indices in any filter with the same hashing are
+/// derived from, see [crate::BloomFilter::hash]. It carries a tag of the hashing, so filters
+/// with another hashing reject it instead of missing the element later.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ElementHash {
+    hash1: u64,
+    hash2: u64,
+    /// [hashing_tag] of the filter which hashed the element.
+    hashing: u64,
+}
+
+impl ElementHash {
+    #[inline]
+    pub(crate) fn new(element: &[u8], config: &FilterBuilder) -> Self {
+        let (hash1, hash2) = hash_pair(element, config);
+        ElementHash { hash1, hash2, hashing: hashing_tag(config) }
+    }
+
+    /// Returns [true] if the filter configured by `config` hashes elements to the same base
+    /// hashes.
+    #[inline]
+    pub(crate) fn is_for(&self, config: &FilterBuilder) -> bool {
+        self.hashing == hashing_tag(config)
+    }
+
+    /// Returns a well mixed 64-bit hash of the element, also for hash functions of poor quality.
+    #[inline]
+    pub(crate) fn mixed(&self) -> u64 {
+        mix64(self.hash1)
+    }
+}
+
+impl Indices {
+    #[inline]
+    pub(crate) fn new(element: &[u8], config: &FilterBuilder) -> Self {
+        Indices::from_hash(ElementHash::new(element, config), config)
+    }
+
+    /// Derives the indices in the filter configured by `config` from the base hashes.
+    #[inline]
+    pub(crate) fn from_hash(hash: ElementHash, config: &FilterBuilder) -> Self {
+        // a filter without capacity has no indices at all, canaries are out of range.
+        let (mut m, k) = if config.size == 0 {
+            (1, 0)
+        } else {
+            (config.size - config.canaries as u64, config.hashes as u64)
+        };
+        let slice = if config.partitioned && k > 0 { m / k } else { 0 };
+        if slice > 0 {
+            m = slice;
+        }
+        let ElementHash { hash1, hash2, .. } = hash;
+        let (hash1, hash2) = match config.index_scheme {
+            IndexScheme::DoubleHashing | IndexScheme::EnhancedDoubleHashing => {
+                (hash1 % m, hash2 % m)
+            }
+            IndexScheme::Extended => (hash1, hash2),
+            #[cfg(feature = "std")]
+            IndexScheme::Guava => (hash1, hash2),
+            IndexScheme::RedisBloom | IndexScheme::BloomfilterCrate => (hash1, hash2),
+        };
+        let seeds = config.custom_hash_seeds().and_then(|_| config.hash_seeds.clone());
+        Indices { scheme: config.index_scheme, hash1, hash2, m, k, i: 0, slice, seeds }
+    }
+
+    /// Returns [true] if there are no indices at all, i.e. the filter has no capacity.
+    #[inline]
+    pub(crate) fn is_empty(&self) -> bool {
+        self.k == 0
+    }
+
+    /// Keeps only the first `k` indices, which are the same for any number of hashes.
+    #[inline]
+    pub(crate) fn truncate(mut self, k: u32) -> Self {
+        self.k = self.k.min(k as u64);
+        self
+    }
+}
+
+/// Hashes an element to the two 64-bit values its indices are derived from.
+#[inline]
+pub(crate) fn hash_pair(element: &[u8], config: &FilterBuilder) -> (u64, u64) {
+    if let Some(hasher) = &config.hasher.hasher {
+        return hasher.hash_pair(element, config.seed);
+    }
+    match config.index_scheme {
+        #[cfg(feature = "std")]
+        IndexScheme::Guava => {
+            // the halves as Guava reads them, `h1` is the low one.
+            let hash = fastmurmur3::murmur3_x64_128(element, config.seed as u32);
+            (hash as u64, (hash >> 64) as u64)
+        }
+        IndexScheme::RedisBloom => {
+            let hash1 = murmur64a(element, REDIS_SEED ^ config.seed);
+            (hash1, murmur64a(element, hash1))
+        }
+        _ if config.key_len_hashing() => hash_by_len(element, config.seed),
+        IndexScheme::DoubleHashing | IndexScheme::BloomfilterCrate => (
+            xxh3_64_with_seed(element, config.seed),
+            xxh3_64_with_seed(element, config.seed.wrapping_add(32)),
+        ),
+        IndexScheme::Extended | IndexScheme::EnhancedDoubleHashing => {
+            let hash = xxh3_128_with_seed(element, config.seed);
+            (hash as u64, (hash >> 64) as u64)
+        }
+    }
+}
+
+/// Identifies all that [hash_pair] depends on: the hasher and its key, the seed and the hash
+/// functions picked by the index scheme and the key length hint.
+#[inline]
+fn hashing_tag(config: &FilterBuilder) -> u64 {
+    let functions = match config.index_scheme {
+        _ if config.hasher.hasher.is_some() => 0,
+        #[cfg(feature = "std")]
+        IndexScheme::Guava => 1,
+        IndexScheme::RedisBloom => 2,
+        _ if config.key_len_hashing() => 3,
+        IndexScheme::DoubleHashing | IndexScheme::BloomfilterCrate => 4,
+        IndexScheme::Extended | IndexScheme::EnhancedDoubleHashing => 5,
+    };
+    let hasher = (config.hasher.id as u64) << 8 | functions;
+    mix64(config.seed ^ mix64(hasher ^ config.hasher.key_check))
+}
+
+/// Whether `element` falls into the deterministic sample of a `rate` of all elements.
+#[inline]
+pub(crate) fn is_sampled(element: &[u8], config: &FilterBuilder, rate: f64) -> bool {
+    assert!((0.0..=1.0).contains(&rate), "rate must between [0.0, 1.0]!");
+    let (hash1, hash2) = hash_pair(element, config);
+    let sample = mix64(hash1 ^ hash2.rotate_left(32));
+    rate >= 1.0 || (sample as f64) < rate * u64::MAX as f64
+}
+
+/// Hashing specialized on the key length, see [FilterBuilder::expected_key_len]: keys of up to
+/// 16 bytes are mixed as one or two words without calling into xxh3, keys up to 64 bytes are
+/// hashed once instead of twice.
+#[inline]
+fn hash_by_len(element: &[u8], seed: u64) -> (u64, u64) {
+    let len = element.len();
+    let key = mix64(seed ^ (len as u64).wrapping_mul(0x9e3779b97f4a7c15));
+    let hash = match len {
+        0..=8 => {
+            let mut word = [0u8; 8];
+            word[..len].copy_from_slice(element);
+            mix64(u64::from_le_bytes(word) ^ key)
+        }
+        9..=16 => {
+            let first = u64::from_le_bytes(element[..8].try_into().unwrap());
+            let last = u64::from_le_bytes(element[len - 8..].try_into().unwrap());
+            mix64(mix64(first ^ key) ^ last)
+        }
+        17..=64 => xxh3_64_with_seed(element, seed),
+        _ => {
+            let hash = xxh3_128_with_seed(element, seed);
+            return (hash as u64, (hash >> 64) as u64);
+        }
+    };
+    (hash, mix64(hash ^ 0xd6e8feb86659fd93))
+}
+
+/// Salt of the `i`-th index of [IndexScheme::Extended] unless given by
+/// [FilterBuilder::hash_seeds].
+#[inline]
+pub(crate) fn default_hash_seed(i: u64) -> u64 {
+    i.wrapping_mul(0x9e3779b97f4a7c15)
+}
+
+/// Whether `seeds` are the default salts of their indices.
+pub(crate) fn is_default_hash_seeds(seeds: &[u64]) -> bool {
+    seeds.iter().enumerate().all(|(i, &seed)| seed == default_hash_seed(i as u64))
+}
+
+/// Digest of the salts of the indices, for [crate::CompatibilityKey::hash_seeds].
+pub(crate) fn hash_seeds_digest(seeds: &[u64]) -> u64 {
+    let bytes: Vec<u8> = seeds.iter().flat_map(|seed| seed.to_le_bytes()).collect();
+    xxh3_64(&bytes) | 1
+}
+
+/// Seed of the first hash of RedisBloom, its multiplier.
+const REDIS_SEED: u64 = 0xc6a4a7935bd1e995;
+
+/// MurmurHash64A as copied into RedisBloom, words are read little-endian.
+fn murmur64a(element: &[u8], seed: u64) -> u64 {
+    const M: u64 = 0xc6a4a7935bd1e995;
+    let mut h = seed ^ (element.len() as u64).wrapping_mul(M);
+    let chunks = element.chunks_exact(8);
+    let tail = chunks.remainder();
+    for chunk in chunks {
+        let mut k = u64::from_le_bytes(chunk.try_into().unwrap()).wrapping_mul(M);
+        k = (k ^ (k >> 47)).wrapping_mul(M);
+        h = (h ^ k).wrapping_mul(M);
+    }
+    if !tail.is_empty() {
+        let mut word = [0u8; 8];
+        word[..tail.len()].copy_from_slice(tail);
+        h = (h ^ u64::from_le_bytes(word)).wrapping_mul(M);
+    }
+    h = (h ^ (h >> 47)).wrapping_mul(M);
+    h ^ (h >> 47)
+}
+
+/// splitmix64 finalizer.
+#[inline]
+pub fn mix64(mut z: u64) -> u64 {
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    z ^ (z >> 31)
+}
+
+impl Iterator for Indices {
+    type Item = u64;
+
+    #[inline]
+    fn next(&mut self) -> Option<u64> {
+        if self.i == self.k {
+            return None;
+        }
+        let i = self.i;
+        self.i += 1;
+        let index = match self.scheme {
+            IndexScheme::DoubleHashing => (self.hash1 + i * self.hash2) % self.m,
+            IndexScheme::Extended => {
+                let seed = match &self.seeds {
+                    Some(seeds) => seeds.get(i as usize).copied()
+                        .unwrap_or_else(|| default_hash_seed(i)),
+                    None => default_hash_seed(i),
+                };
+                let stream = self.hash1.wrapping_add(seed);
+                (mix64(stream) ^ self.hash2) % self.m
+            }
+            IndexScheme::EnhancedDoubleHashing => {
+                let index = self.hash1;
+                self.hash1 = (self.hash1 + self.hash2) % self.m;
+                self.hash2 = (self.hash2 + i + 1) % self.m;
+                index
+            }
+            #[cfg(feature = "std")]
+            IndexScheme::Guava => {
+                (self.hash1.wrapping_add(i.wrapping_mul(self.hash2)) & i64::MAX as u64) % self.m
+            }
+            IndexScheme::RedisBloom => self.hash1.wrapping_add(i.wrapping_mul(self.hash2)) % self.m,
+            IndexScheme::BloomfilterCrate => match i {
+                0 => self.hash1 % self.m,
+                1 => self.hash2 % self.m,
+                _ => {
+                    let hash = self.hash1.wrapping_add(i.wrapping_mul(self.hash2));
+                    hash % BLOOMFILTER_PRIME % self.m
+                }
+            },
+        };
+        Some(i * self.slice + index)
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = (self.k - self.i) as usize;
+        (remaining, Some(remaining))
+    }
+}
+
+impl ExactSizeIterator for Indices {}
+
+#[test]
+fn extended_scheme_fpp_test() {
+    use crate::Membership;
+
+    // k = 23 for p = 1e-7
+    let n = 20_000u64;
+    let mut builder = FilterBuilder::new(n, 1e-7);
+    builder.index_scheme(IndexScheme::Extended);
+    let mut bloom = builder.build_bloom_filter();
+    assert!(bloom.config().hashes > 16);
+    for i in 0..n {
+        bloom.add(&i.to_le_bytes());
+    }
+    for i in 0..n {
+        assert!(bloom.contains(&i.to_le_bytes()));
+    }
+    let false_positives = (n..n + 2_000_000).filter(|i| bloom.contains(&i.to_le_bytes())).count();
+    // 0.2 expected false positives
+    assert!(false_positives <= 3, "{false_positives} false positives");
+}
+
+#[test]
+fn indices_test() {
+    let mut builder = FilterBuilder::new(10_000, 0.01);
+    builder.complete();
+    let indices: Vec<u64> = Indices::new(b"hello", &builder).collect();
+    assert_eq!(indices.len(), builder.hashes as usize);
+    assert!(indices.iter().all(|i| *i < builder.size));
+
+    builder.index_scheme(IndexScheme::Extended);
+    let extended: Vec<u64> = Indices::new(b"hello", &builder).collect();
+    assert_eq!(extended.len(), builder.hashes as usize);
+    assert_ne!(indices, extended);
+
+    builder.index_scheme(IndexScheme::EnhancedDoubleHashing);
+    let enhanced: Vec<u64> = Indices::new(b"hello", &builder).collect();
+    assert_eq!(enhanced.len(), builder.hashes as usize);
+    assert!(enhanced.iter().all(|i| *i < builder.size));
+    assert_ne!(indices, enhanced);
+    // the first indices don't depend on the number of hashes
+    let prefix: Vec<u64> = Indices::new(b"hello", &builder).truncate(3).collect();
+    assert_eq!(prefix, enhanced[..3]);
+}
+
+#[test]
+fn enhanced_double_hashing_fpp_test() {
+    use crate::Membership;
+
+    let n = 20_000u64;
+    let mut builder = FilterBuilder::new(n, 1e-5);
+    builder.index_scheme(IndexScheme::EnhancedDoubleHashing);
+    let mut bloom = builder.build_bloom_filter();
+    for i in 0..n {
+        bloom.add(&i.to_le_bytes());
+    }
+    assert!((0..n).all(|i| bloom.contains(&i.to_le_bytes())));
+    let false_positives = (n..n + 1_000_000).filter(|i| bloom.contains(&i.to_le_bytes())).count();
+    // 10 expected false positives
+    assert!(false_positives <= 30, "{false_positives} false positives");
+}
+
+#[test]
+fn key_len_hashing_test() {
+    use crate::Membership;
+
+    let mut builder = FilterBuilder::new(10_000, 0.01);
+    builder.complete();
+    let legacy: Vec<u64> = Indices::new(b"hello", &builder).collect();
+    builder.expected_key_len(8);
+    assert_ne!(legacy, Indices::new(b"hello", &builder).collect::<Vec<u64>>());
+    assert!(!builder.is_compatible_to(&FilterBuilder::from_size_and_hashes(builder.size, builder.hashes)));
+
+    // one key of every length class
+    let mut bloom = builder.build_bloom_filter();
+    let keys: Vec<Vec<u8>> = (0..100u8).map(|len| vec![len; len as usize]).collect();
+    for key in keys.iter() {
+        bloom.add(key);
+    }
+    assert!(keys.iter().all(|key| bloom.contains(key)));
+
+    let mut bloom = builder.build_bloom_filter();
+    for i in 0..10_000u64 {
+        bloom.add(&i.to_le_bytes());
+    }
+    let false_positives = (10_000..110_000u64).filter(|i| bloom.contains(&i.to_le_bytes())).count();
+    assert!(false_positives < 1_500, "{false_positives} false positives");
+}
+
+#[test]
+fn murmur64a_test() {
+    // RedisBloom's MurmurHash64A_Bloom built from its C source.
+    let vectors: [(&[u8], u64, u64); 3] = [
+        (b"", 0x1ab11ea5a7b2c56e, 0xbbddcb5ab56dd547),
+        (b"hello", 0x5ba5b8a59803e699, 0xa7d451d588a0c2a4),
+        (b"The quick brown fox jumps over the lazy dog", 0xc7a616a28f4a74d6, 0xfab0774384f74e2a),
+    ];
+    for (element, hash1, hash2) in vectors {
+        assert_eq!(murmur64a(element, REDIS_SEED), hash1);
+        assert_eq!(murmur64a(element, hash1), hash2);
+    }
+}
+
+#[test]
+fn hash_seeds_test() {
+    use crate::{BloomFilter, Membership};
+
+    let mut builder = FilterBuilder::new(10_000, 0.01);
+    builder.index_scheme(IndexScheme::Extended);
+    builder.complete();
+    let defaults = builder.hash_seeds_in_use().unwrap();
+    assert_eq!(defaults.len(), builder.hashes as usize);
+    let extended: Vec<u64> = Indices::new(b"hello", &builder).collect();
+
+    // the default seeds reproduce the scheme
+    let mut seeded = builder.clone();
+    seeded.hash_seeds(&defaults);
+    assert_eq!(Indices::new(b"hello", &seeded).collect::<Vec<u64>>(), extended);
+    assert!(seeded.is_compatible_to(&builder));
+
+    // the documented derivation
+    let seeds = [1, 2, 3, 0xdead_beef];
+    seeded.hash_seeds(&seeds);
+    let hash = xxh3_128_with_seed(b"hello", 0);
+    let (h1, h2) = (hash as u64, (hash >> 64) as u64);
+    let expected: Vec<u64> = seeds.iter()
+        .map(|&seed| (mix64(h1.wrapping_add(seed)) ^ h2) % seeded.size)
+        .collect();
+    assert_eq!(Indices::new(b"hello", &seeded).collect::<Vec<u64>>(), expected);
+    assert_eq!(seeded.hash_seeds_in_use(), Some(seeds.to_vec()));
+    assert!(!seeded.is_compatible_to(&builder));
+
+    let mut bloom = seeded.build_bloom_filter();
+    (0..100u32).for_each(|i| bloom.add(&i.to_le_bytes()));
+    assert!(!bloom.union(&builder.build_bloom_filter()));
+    let restored = BloomFilter::from_static_bytes(&bloom.to_static_bytes()).unwrap();
+    assert_eq!(restored.config().hash_seeds_in_use(), Some(seeds.to_vec()));
+    assert!(restored.config().is_compatible_to(&bloom.config()));
+    assert!((0..100u32).all(|i| restored.contains(&i.to_le_bytes())));
+
+    // seeds missing for some hashes are the defaults
+    let mut short = FilterBuilder::from_size_and_hashes(1024, 3);
+    short.hash_seeds(&[1, 2]);
+    short.hashes = 3;
+    let mut padded = FilterBuilder::from_size_and_hashes(1024, 3);
+    padded.hash_seeds(&[1, 2, default_hash_seed(2)]);
+    assert_eq!(Indices::new(b"hello", &short).collect::<Vec<u64>>(),
+               Indices::new(b"hello", &padded).collect::<Vec<u64>>());
+    assert!(short.is_compatible_to(&padded));
+    let mut partial = builder.clone();
+    partial.hash_seeds(&defaults[..2]);
+    partial.hashes = builder.hashes;
+    assert!(partial.is_compatible_to(&builder));
+
+    // sized by the number of seeds
+    let mut sized = FilterBuilder::new(10_000, 0.01);
+    sized.hash_seeds(&seeds);
+    assert_eq!(sized.build_bloom_filter().config().hashes, 4);
+}
+
+#[test]
+#[should_panic(expected = "hash_seeds needs at least one seed")]
+fn hash_seeds_empty_test() {
+    FilterBuilder::new(100, 0.01).hash_seeds(&[]);
+}
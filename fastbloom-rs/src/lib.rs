@@ -1,16 +1,214 @@
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+
+extern crate alloc;
 extern crate core;
 
-pub use bloom::{BloomFilter, CountingBloomFilter};
-pub use builder::FilterBuilder;
-pub use vec::{Storage, StorageMut};
+use alloc::vec::Vec;
+use core::hash::Hash;
+
+use crate::hasher::KeyBytes;
+
+pub use aging::AgingBloomFilter;
+pub use atomic::{AtomicBloomFilter, ConcurrentCountingBloomFilter};
+#[cfg(feature = "siphash")]
+pub use bip158::Bip158Filter;
+pub use blocked::{BlockedBloomFilter, BlockSize, ShardFill};
+pub use bloom::{ArrayBloomFilter, BloomFilter, CountingBloomFilter};
+pub use builder::{BuildError, FilterBuilder, SizeError, state, TypedBuilder};
+pub use cascade::FilterCascade;
+#[cfg(target_has_atomic = "64")]
+pub use checked::{CheckedMembership, CheckedStats};
+#[cfg(feature = "dedupe")]
+pub use dedupe::{DedupCache, DedupStats};
+pub use compat::{
+    CompatibilityKey, FilterLayout, FilterParams, FormatFeatures, FormatVersion,
+    IncompatibilityReason, MismatchError,
+};
+pub use cuckoo::CuckooFilter;
+pub use deletable::{CompactionStats, DeletableBloomFilter};
+pub use delta::FilterDelta;
+pub use diagnose::HashDiagnostics;
+pub use dleft::DLeftCountingFilter;
+pub use dlbf::DlBloomFilter;
+pub use double::DoubleBufferedBloomFilter;
+pub use embedded::StaticFilterRef;
+pub use events::FilterEvents;
+pub use expr::{MembershipExpr, ParseError};
+pub use family::HashFamily;
+pub use fingerprint::FingerprintTable;
+pub use frozen::FrozenBloomFilter;
+pub use fuse::{BinaryFuse8, BinaryFuse16, BinaryFuseFilter, BloomierFilter, FuseFingerprint};
+pub use golomb::GolombCodedSet;
+pub use guard::{FillPolicy, GuardedBloomFilter, SaturatedError};
+pub use hasher::{
+    BuildHasherAdapter, DEFAULT_HASHER, FilterHasher, FIRST_CUSTOM_HASHER, register_hasher,
+};
+pub use index::{ElementHash, IndexScheme};
+/// The mixing of the filters, for the keys of the fastbloom-soak binary.
+#[cfg(feature = "soak")]
+#[doc(hidden)]
+pub use index::mix64;
+pub use manifest::BuildManifest;
+#[cfg(feature = "mmap")]
+pub use mmap::MmapStorage;
+pub use namespace::NamespacedFilter;
+pub use pair::PairBloomFilter;
+#[cfg(feature = "std")]
+pub use pushdown::ListingFilter;
+pub use quarantine::{Presence, QuarantineFilter};
+pub use quotient::{CountingQuotientFilter, QuotientFilter};
+#[cfg(feature = "async")]
+pub use remote::{AsyncBloomFilter, AsyncStorage};
+pub use region::{RegionAllocator, RegionId};
+pub use replication::{
+    ReplicationMessage, ReplicationPrimary, ReplicationStandby, ReplicationTransport,
+};
+pub use ribbon::RibbonFilter;
+pub use sbbf::SplitBlockBloomFilter;
+pub use sharded::ShardedBloomFilter;
+pub use shm::SharedMemStorage;
+pub use sketch::HyperLogLog;
+#[cfg(feature = "std")]
+pub use sliding::SlidingBloomFilter;
+pub use stats::{FilterMetrics, OpStats};
+pub use tiered::TieredBloomFilter;
+#[cfg(feature = "witness")]
+pub use witness::{FilterCommitment, MembershipWitness};
+#[cfg(feature = "std")]
+pub use wal::WalBloomFilter;
+#[cfg(feature = "wasm")]
+pub use wasm::WasmBloomFilter;
+pub use vec::{
+    AccessSampling, Advice, AllocStorage, AtomicStorage, SparseStorage, Storage, StorageMut,
+    WriteStats, WriteTracking,
+};
 
+mod aging;
+mod atomic;
+#[cfg(feature = "siphash")]
+mod bip158;
+mod blocked;
 mod builder;
 mod bloom;
-mod vec;
+#[cfg(feature = "siphash")]
+mod bloomfilter;
+mod cascade;
+#[cfg(target_has_atomic = "64")]
+mod checked;
+mod codec;
+mod compat;
 mod cuckoo;
+#[cfg(feature = "dedupe")]
+mod dedupe;
+mod deletable;
+mod delta;
+mod diagnose;
+mod dleft;
+mod dlbf;
+mod double;
+mod embedded;
+mod events;
+mod expr;
+mod family;
+#[cfg(feature = "ffi")]
+mod ffi;
+mod fingerprint;
+mod frozen;
+mod fuse;
+mod golomb;
+mod guard;
+#[cfg(not(feature = "std"))]
+mod float;
+#[cfg(feature = "std")]
+mod guava;
+mod hasher;
+#[cfg(feature = "std")]
+mod import;
+mod index;
+mod manifest;
+#[cfg(feature = "mmap")]
+mod mmap;
+mod namespace;
+mod pair;
+#[cfg(feature = "rayon")]
+mod parallel;
+pub mod params;
+#[cfg(feature = "std")]
+mod persist;
+#[cfg(feature = "std")]
+mod pushdown;
+mod quarantine;
+mod quotient;
+mod raw;
+mod redis;
+mod region;
+#[cfg(feature = "async")]
+mod remote;
+mod replication;
+mod ribbon;
+#[cfg(feature = "serde")]
+mod serialize;
+#[cfg(feature = "simd")]
+mod simd;
+mod sbbf;
+mod sharded;
+mod shm;
+#[cfg(feature = "std")]
+mod sliding;
+mod vec;
 mod sketch;
+#[cfg(feature = "stream")]
+mod stream;
+mod stats;
+mod tiered;
+#[cfg(feature = "wasm")]
+mod wasm;
+#[cfg(feature = "std")]
+mod wal;
+#[cfg(feature = "witness")]
+mod witness;
+#[cfg(feature = "roaring")]
+mod roaring;
+
+/// Embeds a filter written to `OUT_DIR` by the `fastbloom-build` crate in a build script and
+/// opens it as a [StaticFilterRef]. Panics if the embedded bytes are not a valid filter.
+#[macro_export]
+macro_rules! include_filter {
+    ($name:literal) => {
+        $crate::StaticFilterRef::from_static(include_bytes!(concat!(env!("OUT_DIR"), "/", $name)))
+            .expect("embedded filter is invalid")
+    };
+}
 
 /// filter for check whether membership.
+///
+/// Implemented by all filters which take elements one at a time ([BloomFilter],
+/// [CountingBloomFilter], [BlockedBloomFilter], [CuckooFilter], [QuotientFilter] and so on), and
+/// object safe, so applications can pick the filter at runtime behind a `Box<dyn Membership>`
+/// without changing the call sites. Filters which can remove elements also implement
+/// [Deletable].
+///
+/// # Examples
+///
+/// ```rust
+/// use fastbloom_rs::{FilterBuilder, Membership};
+///
+/// fn filter(kind: &str) -> Box<dyn Membership> {
+///     let mut builder = FilterBuilder::new(10_000, 0.01);
+///     match kind {
+///         "counting" => Box::new(builder.build_counting_bloom_filter::<Vec<usize>>(())),
+///         "cuckoo" => Box::new(builder.build_cuckoo_filter()),
+///         _ => Box::new(builder.build_bloom_filter()),
+///     }
+/// }
+///
+/// for kind in ["bloom", "counting", "cuckoo"] {
+///     let mut filter = filter(kind);
+///     filter.add(b"hello");
+///     assert_eq!(filter.contains(b"hello"), true);
+///     assert_eq!(filter.contains(b"world"), false);
+/// }
+/// ```
 pub trait Membership {
     fn add(&mut self, element: &[u8]);
 
@@ -21,6 +219,89 @@ pub trait Membership {
     fn contains_hash_indices(&self, indices: &Vec<u64>) -> bool;
 
     fn clear(&mut self);
+
+    /// Adds any [Hash] value, e.g. a tuple or a struct deriving [Hash], without converting it to
+    /// bytes by hand. The key is the byte stream the [Hash] implementation writes, with the
+    /// integers little-endian, hashed like any other key with the seed and hasher of the filter;
+    /// a value added this way is only found with [Membership::contains_hashable]. Keys change
+    /// with the [Hash] implementation, so only share filters between builds with the same one.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use fastbloom_rs::{FilterBuilder, Membership};
+    ///
+    /// #[derive(Hash)]
+    /// struct Visit<'a> {
+    ///     user: u64,
+    ///     page: &'a str,
+    /// }
+    ///
+    /// let mut bloom = FilterBuilder::new(100_000, 0.01).build_bloom_filter();
+    /// bloom.add_hashable(&Visit { user: 7, page: "/home" });
+    /// bloom.add_hashable(&(8u64, "/about"));
+    /// assert_eq!(bloom.contains_hashable(&Visit { user: 7, page: "/home" }), true);
+    /// assert_eq!(bloom.contains_hashable(&(8u64, "/about")), true);
+    /// assert_eq!(bloom.contains_hashable(&(7u64, "/about")), false);
+    /// ```
+    fn add_hashable<T: Hash + ?Sized>(&mut self, item: &T) where Self: Sized {
+        self.add(&KeyBytes::of(item).0);
+    }
+
+    /// Tests whether a value added with [Membership::add_hashable] is present.
+    fn contains_hashable<T: Hash + ?Sized>(&self, item: &T) -> bool where Self: Sized {
+        self.contains(&KeyBytes::of(item).0)
+    }
+
+    /// Returns a view for queries which prefixes every key with the namespace `ns`, see
+    /// [NamespacedFilter].
+    fn namespaced(&self, ns: &str) -> NamespacedFilter<&Self> where Self: Sized {
+        NamespacedFilter::new(self, ns)
+    }
+
+    /// Returns a view for updates which prefixes every key with the namespace `ns`, see
+    /// [NamespacedFilter].
+    fn namespaced_mut(&mut self, ns: &str) -> NamespacedFilter<&mut Self> where Self: Sized {
+        NamespacedFilter::new(self, ns)
+    }
+}
+
+/// Detailed answer of a membership query, see `contains_detailed` on the filter types.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MembershipDetail {
+    /// At least one probed position is unset, the element is definitely not present.
+    Absent {
+        /// Number of probed positions which are set.
+        probes_set: u32,
+    },
+    /// All probed positions are set, the element is present or a false positive.
+    MaybePresent {
+        /// Number of probed positions which are set.
+        probes_set: u32,
+    },
+}
+
+impl MembershipDetail {
+    /// Returns [true] for [MembershipDetail::MaybePresent].
+    pub fn is_present(&self) -> bool {
+        matches!(self, MembershipDetail::MaybePresent { .. })
+    }
+
+    /// Returns the number of probed positions which are set.
+    pub fn probes_set(&self) -> u32 {
+        match *self {
+            MembershipDetail::Absent { probes_set } => probes_set,
+            MembershipDetail::MaybePresent { probes_set } => probes_set,
+        }
+    }
+
+    pub(crate) fn from_probes(probes_set: u32, probes: u32) -> Self {
+        if probes_set == probes && probes != 0 {
+            MembershipDetail::MaybePresent { probes_set }
+        } else {
+            MembershipDetail::Absent { probes_set }
+        }
+    }
 }
 
 pub trait Hashes {
@@ -33,4 +314,15 @@ pub trait Deletable {
     fn remove(&mut self, element: &[u8]);
 }
 
+/// Filter with time-based behavior, e.g. rotations every interval, which it otherwise performs
+/// on the next add or query. The crate starts no threads or timers: call [Maintain::maintain]
+/// from the timer of your runtime (a tokio interval, a thread sleeping in a loop, ...) to
+/// perform the work which is due at `now` without waiting for the next add, e.g. to drop
+/// expired generations of an idle filter. Calling it early or twice does nothing.
+#[cfg(feature = "std")]
+pub trait Maintain {
+    /// Performs the time-based work which is due at `now`.
+    fn maintain(&mut self, now: std::time::Instant);
+}
+
 
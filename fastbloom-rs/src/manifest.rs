@@ -0,0 +1,217 @@
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt::{Display, Formatter};
+
+use xxhash_rust::xxh3::xxh3_64;
+
+use crate::bloom::BloomFilter;
+use crate::builder::FilterBuilder;
+use crate::hasher::HasherRef;
+use crate::index::IndexScheme;
+use crate::vec::Storage;
+
+/// Version of the built-in hashing, bumped whenever a release changes where elements land.
+pub(crate) const HASHING_VERSION: u32 = 1;
+
+const MANIFEST_HEADER: &str = "fastbloom-manifest 1";
+
+/// Describes how a serialized filter was produced: its parameters, the hashing and crate
+/// version, the number of inputs and a checksum of the serialized bytes. Store its text form
+/// ([ToString::to_string]) next to the filter artifact, so incidents can be traced back to
+/// the build, and load the filter with [BloomFilter::from_u8_array_verified].
+///
+/// # Examples
+///
+/// ```rust
+/// use fastbloom_rs::{BloomFilter, BuildManifest, FilterBuilder, Membership};
+///
+/// let mut bloom = FilterBuilder::new(1000, 0.01).build_bloom_filter();
+/// bloom.add(b"hello");
+/// let manifest = bloom.build_manifest(1).to_string();
+///
+/// let manifest = BuildManifest::from_text(&manifest).unwrap();
+/// let restored = BloomFilter::from_u8_array_verified(bloom.get_u8_array(), &manifest).unwrap();
+/// assert_eq!(restored.contains(b"hello"), true);
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BuildManifest {
+    /// Version of the crate which built the filter.
+    pub crate_version: String,
+    /// Size of the filter in bits.
+    pub size: u64,
+    /// Number of hash functions.
+    pub hashes: u32,
+    /// See [FilterBuilder::seed].
+    pub seed: u64,
+    /// See [FilterBuilder::index_scheme].
+    pub index_scheme: IndexScheme,
+    /// Id of the hash function, see [FilterBuilder::hasher].
+    pub hasher: u32,
+    /// See [FilterBuilder::expected_key_len].
+    pub expected_key_len: usize,
+    /// See [FilterBuilder::canaries].
+    pub canaries: u32,
+    /// See [FilterBuilder::partitioned].
+    pub partitioned: bool,
+    /// See [FilterBuilder::hash_seeds], `None` for the default ones.
+    pub hash_seeds: Option<Vec<u64>>,
+    /// Version of the built-in hashing.
+    pub hashing_version: u32,
+    /// Number of elements added to the filter, as reported by the producer.
+    pub inputs: u64,
+    /// xxh3 checksum of the serialized filter.
+    pub checksum: u64,
+}
+
+impl BuildManifest {
+    pub(crate) fn new(config: &FilterBuilder, inputs: u64, bytes: &[u8]) -> Self {
+        BuildManifest {
+            crate_version: env!("CARGO_PKG_VERSION").to_string(),
+            size: config.size,
+            hashes: config.hashes,
+            seed: config.seed,
+            index_scheme: config.index_scheme,
+            hasher: config.hasher_id(),
+            expected_key_len: config.expected_key_len,
+            canaries: config.canaries,
+            partitioned: config.partitioned,
+            hash_seeds: config.custom_hash_seeds().map(<[u64]>::to_vec),
+            hashing_version: HASHING_VERSION,
+            inputs,
+            checksum: xxh3_64(bytes),
+        }
+    }
+
+    /// Parses the text form written by [ToString::to_string]. Returns `None` when a line is
+    /// malformed or a field is missing; unknown fields are ignored, and `canaries`,
+    /// `partitioned` and `hash_seeds` default to `0`, `false` and none for manifests written
+    /// before they were introduced.
+    pub fn from_text(text: &str) -> Option<Self> {
+        let mut lines = text.lines();
+        if lines.next()? != MANIFEST_HEADER {
+            return None;
+        }
+        let mut fields = alloc::collections::BTreeMap::new();
+        for line in lines.filter(|line| !line.is_empty()) {
+            let (key, value) = line.split_once('=')?;
+            fields.insert(key, value);
+        }
+        Some(BuildManifest {
+            crate_version: fields.get("crate_version")?.to_string(),
+            size: fields.get("size")?.parse().ok()?,
+            hashes: fields.get("hashes")?.parse().ok()?,
+            seed: fields.get("seed")?.parse().ok()?,
+            index_scheme: IndexScheme::from_id(fields.get("index_scheme")?.parse().ok()?)?,
+            hasher: fields.get("hasher")?.parse().ok()?,
+            expected_key_len: fields.get("expected_key_len")?.parse().ok()?,
+            canaries: fields.get("canaries").map_or(Some(0), |canaries| canaries.parse().ok())?,
+            partitioned: fields.get("partitioned")
+                .map_or(Some(false), |partitioned| partitioned.parse().ok())?,
+            hash_seeds: match fields.get("hash_seeds") {
+                Some(seeds) => Some(seeds.split(',').map(|seed| seed.parse().ok())
+                    .collect::<Option<Vec<u64>>>()?),
+                None => None,
+            },
+            hashing_version: fields.get("hashing_version")?.parse().ok()?,
+            inputs: fields.get("inputs")?.parse().ok()?,
+            checksum: u64::from_str_radix(fields.get("checksum")?, 16).ok()?,
+        })
+    }
+
+    /// Checks that `bytes` is the serialized filter this manifest was built for and that this
+    /// release hashes elements the same way.
+    pub fn verify(&self, bytes: &[u8]) -> bool {
+        self.hashing_version == HASHING_VERSION
+            && self.size == bytes.len() as u64 * 8
+            && self.checksum == xxh3_64(bytes)
+    }
+}
+
+impl Display for BuildManifest {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        writeln!(f, "{MANIFEST_HEADER}")?;
+        writeln!(f, "crate_version={}", self.crate_version)?;
+        writeln!(f, "size={}", self.size)?;
+        writeln!(f, "hashes={}", self.hashes)?;
+        writeln!(f, "seed={}", self.seed)?;
+        writeln!(f, "index_scheme={}", self.index_scheme.id())?;
+        writeln!(f, "hasher={}", self.hasher)?;
+        writeln!(f, "expected_key_len={}", self.expected_key_len)?;
+        writeln!(f, "canaries={}", self.canaries)?;
+        writeln!(f, "partitioned={}", self.partitioned)?;
+        if let Some(seeds) = &self.hash_seeds {
+            let seeds: Vec<String> = seeds.iter().map(u64::to_string).collect();
+            writeln!(f, "hash_seeds={}", seeds.join(","))?;
+        }
+        writeln!(f, "hashing_version={}", self.hashing_version)?;
+        writeln!(f, "inputs={}", self.inputs)?;
+        writeln!(f, "checksum={:016x}", self.checksum)
+    }
+}
+
+impl<S: Storage + AsRef<[usize]>> BloomFilter<S> {
+    /// Returns the [BuildManifest] of the bytes returned by [BloomFilter::get_u8_array], for a
+    /// filter built from `inputs` elements.
+    pub fn build_manifest(&self, inputs: u64) -> BuildManifest {
+        BuildManifest::new(&self.config, inputs, self.get_u8_array())
+    }
+}
+
+impl BloomFilter {
+    /// Build a Bloom filter form `&[u8]` like [BloomFilter::from_u8_array], restoring all
+    /// parameters from `manifest`. Returns `None` when [BuildManifest::verify] fails or the
+    /// hasher of the manifest is not registered.
+    pub fn from_u8_array_verified(array: &[u8], manifest: &BuildManifest) -> Option<Self> {
+        if !manifest.verify(array) || manifest.hashes == 0
+            || manifest.canaries as u64 >= manifest.size
+            || manifest.partitioned
+                && manifest.size - (manifest.canaries as u64) < manifest.hashes as u64
+            || manifest.hash_seeds.as_ref().is_some_and(|seeds| {
+                seeds.is_empty() || seeds.len() > manifest.hashes as usize
+                    || manifest.index_scheme != IndexScheme::Extended
+            }) {
+            return None;
+        }
+        let hasher = HasherRef::resolve(manifest.hasher)?;
+        let mut bloom = BloomFilter::from_u8_array(array, manifest.hashes);
+        bloom.config.seed = manifest.seed;
+        bloom.config.index_scheme = manifest.index_scheme;
+        bloom.config.hasher = hasher;
+        bloom.config.expected_key_len = manifest.expected_key_len;
+        bloom.config.canaries = manifest.canaries;
+        bloom.config.partitioned = manifest.partitioned;
+        bloom.config.hash_seeds = manifest.hash_seeds.as_deref().map(Into::into);
+        Some(bloom)
+    }
+}
+
+#[test]
+fn build_manifest_test() {
+    use crate::Membership;
+
+    let mut builder = FilterBuilder::new(1000, 0.01);
+    builder.seed(3);
+    builder.index_scheme(IndexScheme::Extended);
+    let mut bloom = builder.build_bloom_filter();
+    for i in 0..100u32 {
+        bloom.add(&i.to_le_bytes());
+    }
+    let manifest = bloom.build_manifest(100);
+    assert_eq!(BuildManifest::from_text(&manifest.to_string()), Some(manifest.clone()));
+
+    let restored = BloomFilter::from_u8_array_verified(bloom.get_u8_array(), &manifest).unwrap();
+    assert!(restored.config().is_compatible_to(&bloom.config()));
+    assert!((0..100u32).all(|i| restored.contains(&i.to_le_bytes())));
+
+    let mut corrupted = bloom.get_u8_array().to_vec();
+    corrupted[0] ^= 1;
+    assert!(BloomFilter::from_u8_array_verified(&corrupted, &manifest).is_none());
+    assert!(BuildManifest::from_text("fastbloom-manifest 1\nsize=64\n").is_none());
+
+    builder.hash_seeds(&[1, 2, 3]);
+    let seeded = builder.build_bloom_filter();
+    let manifest = BuildManifest::from_text(&seeded.build_manifest(0).to_string()).unwrap();
+    assert_eq!(manifest.hash_seeds, Some(vec![1, 2, 3]));
+    let restored = BloomFilter::from_u8_array_verified(seeded.get_u8_array(), &manifest).unwrap();
+    assert!(restored.config().is_compatible_to(&seeded.config()));
+}
@@ -0,0 +1,240 @@
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::mem::size_of;
+use std::path::Path;
+
+use memmap2::MmapMut;
+
+use crate::bloom::BloomFilter;
+use crate::builder::FilterBuilder;
+use crate::codec::{put_hashing, Reader};
+use crate::compat::FormatFeatures;
+use crate::vec::{BloomBitVec, Storage, StorageMut};
+
+const MMAP_MAGIC: &[u8; 4] = b"FBMM";
+
+/// Bytes reserved for the header in front of the words, one page so the words are aligned.
+const HEADER_LEN: usize = 4096;
+
+/// Storage in a memory-mapped file, for filters larger than RAM or which must survive restarts.
+/// The file starts with a one page header holding the filter parameters, written by
+/// [BloomFilter::create_mmap] and read by [BloomFilter::open_mmap], followed by the words in
+/// memory layout, so a file can only be reopened on a platform with the same word size and byte
+/// order (any platform with the `canonical-u64` feature). The file must not be truncated or
+/// written by anyone else while it is mapped.
+///
+/// [Storage::new] maps the file given as `Init`, resized to the slots, without writing a header.
+///
+/// # Examples
+///
+/// ```rust
+/// use fastbloom_rs::{BloomFilter, FilterBuilder, Membership, MmapStorage};
+///
+/// let path = std::env::temp_dir().join("fastbloom-doc.mmap");
+/// let mut bloom = BloomFilter::create_mmap(&path, FilterBuilder::new(100_000, 0.01)).unwrap();
+/// bloom.add(b"hello");
+/// bloom.flush().unwrap();
+/// drop(bloom);
+///
+/// let bloom = BloomFilter::open_mmap(&path).unwrap();
+/// assert_eq!(bloom.contains(b"hello"), true);
+/// # std::fs::remove_file(&path).unwrap();
+/// ```
+#[derive(Debug)]
+pub struct MmapStorage {
+    map: MmapMut,
+    slots: usize,
+}
+
+impl MmapStorage {
+    fn map(file: &File, slots: usize) -> io::Result<Self> {
+        file.set_len((HEADER_LEN + slots * size_of::<usize>()) as u64)?;
+        // Safety: the file is sized to the words and must not be truncated while mapped.
+        let map = unsafe { MmapMut::map_mut(file)? };
+        Ok(MmapStorage { map, slots })
+    }
+
+    /// Writes the modified pages back to the file.
+    pub fn flush(&self) -> io::Result<()> {
+        self.map.flush()
+    }
+
+    fn header_mut(&mut self) -> &mut [u8] {
+        &mut self.map[..HEADER_LEN]
+    }
+}
+
+impl AsRef<[usize]> for MmapStorage {
+    fn as_ref(&self) -> &[usize] {
+        // Safety: the map is page aligned and holds `slots` words after the header.
+        unsafe {
+            std::slice::from_raw_parts(self.map.as_ptr().add(HEADER_LEN) as *const usize,
+                                       self.slots)
+        }
+    }
+}
+
+impl AsMut<[usize]> for MmapStorage {
+    fn as_mut(&mut self) -> &mut [usize] {
+        // Safety: see `as_ref`.
+        unsafe {
+            std::slice::from_raw_parts_mut(self.map.as_mut_ptr().add(HEADER_LEN) as *mut usize,
+                                           self.slots)
+        }
+    }
+}
+
+impl Storage for MmapStorage {
+    type Init = File;
+    fn new(slots: usize, file: File) -> Self {
+        MmapStorage::map(&file, slots).expect("failed to map the file!")
+    }
+    #[inline]
+    fn get(&self, slot: usize) -> usize {
+        self.as_ref()[slot]
+    }
+    #[inline]
+    fn slots(&self) -> usize {
+        self.slots
+    }
+    #[cfg(feature = "simd")]
+    #[inline]
+    fn prefetch(&self, slot: usize) {
+        crate::simd::prefetch(self.as_ref().as_ptr().wrapping_add(slot))
+    }
+    #[cfg(unix)]
+    fn advise(&self, advice: crate::vec::Advice) {
+        use crate::vec::Advice;
+        let advice = match advice {
+            Advice::Sequential => memmap2::Advice::Sequential,
+            Advice::Random => memmap2::Advice::Random,
+            Advice::WillNeed => memmap2::Advice::WillNeed,
+        };
+        // advice is only a hint.
+        let _ = self.map.advise(advice);
+    }
+    #[cfg(unix)]
+    fn pin(&self, slots: std::ops::Range<usize>) {
+        let words = &self.as_ref()[slots];
+        // Safety: sysconf has no preconditions.
+        let page = unsafe { libc::sysconf(libc::_SC_PAGESIZE) } as usize;
+        let start = words.as_ptr() as usize & !(page - 1);
+        let len = words.as_ptr() as usize + size_of_val(words) - start;
+        // Safety: the pages belong to the map and stay locked until it is unmapped. Pinning is
+        // best effort, e.g. RLIMIT_MEMLOCK may not allow it.
+        let _ = unsafe { libc::mlock(start as *const libc::c_void, len) };
+    }
+}
+
+impl StorageMut for MmapStorage {
+    #[inline]
+    fn update(&mut self, slot: usize, op: impl FnOnce(usize) -> Option<usize>) {
+        let words = self.as_mut();
+        if let Some(v) = op(words[slot]) {
+            words[slot] = v;
+        }
+    }
+    fn clear(&mut self) {
+        self.as_mut().fill(0);
+    }
+}
+
+impl BloomFilter<MmapStorage> {
+    /// Creates (or truncates) the file at `path` and builds an empty filter from `config` in it.
+    pub fn create_mmap(path: impl AsRef<Path>, mut config: FilterBuilder) -> io::Result<Self> {
+        config.complete();
+        let file = OpenOptions::new().read(true).write(true).create(true).truncate(true)
+            .open(path)?;
+        let mut storage = MmapStorage::map(&file, (config.size / usize::BITS as u64) as usize)?;
+        let mut header = Vec::with_capacity(64);
+        header.extend_from_slice(MMAP_MAGIC);
+        header.push(usize::BITS as u8);
+        put_hashing(&mut header, &config, 0, FormatFeatures::default());
+        header.extend_from_slice(&config.size.to_le_bytes());
+        storage.header_mut()[..header.len()].copy_from_slice(&header);
+        Ok(BloomFilter::from_bit_vec(config, BloomBitVec::with_storage(storage)))
+    }
+
+    /// Maps the file at `path` written by [BloomFilter::create_mmap], restoring the parameters
+    /// of the filter from its header. Fails with [io::ErrorKind::InvalidData] when the file is
+    /// not such a filter or was written on a platform with another word size.
+    pub fn open_mmap(path: impl AsRef<Path>) -> io::Result<Self> {
+        let invalid = || io::Error::new(io::ErrorKind::InvalidData, "not a mapped filter file");
+        let file = OpenOptions::new().read(true).write(true).open(path)?;
+        let mut header = vec![0; HEADER_LEN];
+        io::Read::read_exact(&mut &file, &mut header).map_err(|_| invalid())?;
+        let mut reader = Reader::new(&header);
+        if reader.take(4) != Some(MMAP_MAGIC) || reader.u8() != Some(usize::BITS as u8) {
+            return Err(invalid());
+        }
+        let hashing = reader.hashing().filter(|hashing| hashing.features.is_empty());
+        let (hashing, size) = hashing.zip(reader.u64()).ok_or_else(invalid)?;
+        let slots = usize::try_from(size / usize::BITS as u64).map_err(|_| invalid())?;
+        let expected = slots.checked_mul(size_of::<usize>())
+            .and_then(|bytes| bytes.checked_add(HEADER_LEN))
+            .ok_or_else(invalid)?;
+        let len = file.metadata()?.len();
+        if size == 0 || size % usize::BITS as u64 != 0 || len != expected as u64 {
+            return Err(invalid());
+        }
+        let mut config = FilterBuilder::from_size_and_hashes(size, hashing.hashes);
+        hashing.apply(&mut config).ok_or_else(invalid)?;
+        let storage = MmapStorage::map(&file, slots)?;
+        Ok(BloomFilter::from_bit_vec(config, BloomBitVec::with_storage(storage)))
+    }
+
+    /// Writes the modified pages of the filter back to its file.
+    pub fn flush(&self) -> io::Result<()> {
+        self.storage().flush()
+    }
+}
+
+#[test]
+fn mmap_storage_test() {
+    use crate::Membership;
+
+    let path = std::env::temp_dir().join(format!("fastbloom-{}.mmap", std::process::id()));
+    let mut builder = FilterBuilder::new(100_000, 0.01);
+    builder.seed(9);
+    let mut bloom = BloomFilter::create_mmap(&path, builder).unwrap();
+    for i in 0..10_000u32 {
+        bloom.add(&i.to_le_bytes());
+    }
+    bloom.flush().unwrap();
+    let (config, expected) = (bloom.config(), bloom.get_u8_array().to_vec());
+    drop(bloom);
+
+    let mut bloom = BloomFilter::open_mmap(&path).unwrap();
+    assert!(bloom.config().is_compatible_to(&config));
+    assert_eq!(bloom.get_u8_array(), &expected[..]);
+    assert!((0..10_000u32).all(|i| bloom.contains(&i.to_le_bytes())));
+    bloom.clear();
+    assert!(!bloom.contains(&0u32.to_le_bytes()));
+    drop(bloom);
+
+    #[cfg(target_os = "linux")]
+    {
+        let locked = || {
+            let status = std::fs::read_to_string("/proc/self/status").unwrap();
+            status.lines().find(|line| line.starts_with("VmLck:")).unwrap().to_owned()
+        };
+        let bloom = BloomFilter::open_mmap(&path).unwrap();
+        let before = locked();
+        bloom.storage().pin(0..1);
+        assert_ne!(locked(), before);
+    }
+
+    // a size of more bytes than a 32-bit usize, and not the length of the file
+    let mut bytes = std::fs::read(&path).unwrap();
+    let at = bytes.windows(8).position(|window| window == config.size.to_le_bytes()).unwrap();
+    let forged = u32::MAX as u64 * usize::BITS as u64;
+    bytes[at..at + 8].copy_from_slice(&forged.to_le_bytes());
+    std::fs::write(&path, &bytes).unwrap();
+    assert_eq!(BloomFilter::open_mmap(&path).unwrap_err().kind(), io::ErrorKind::InvalidData);
+
+    let file = std::fs::OpenOptions::new().write(true).open(&path).unwrap();
+    file.set_len(HEADER_LEN as u64).unwrap();
+    let error = BloomFilter::open_mmap(&path).unwrap_err();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+}
@@ -0,0 +1,98 @@
+use alloc::vec::Vec;
+
+use smallvec::SmallVec;
+
+use crate::Membership;
+use crate::codec::put_varint;
+
+/// View of a filter which prefixes every key with a namespace before hashing, so several
+/// logical sets can share one physical filter. The prefix is the length of the namespace
+/// followed by its bytes, thus keys of different namespaces never collide (`"a"` + `"bc"` is
+/// not `"ab"` + `"c"`) and the prefixed keys of one namespace sort like the keys themselves.
+///
+/// Created by [Membership::namespaced] for queries and [Membership::namespaced_mut] for
+/// updates. The namespaces share the false positive budget of the filter, which has to be sized
+/// for the elements of all of them.
+///
+/// # Examples
+///
+/// ```rust
+/// use fastbloom_rs::{FilterBuilder, Membership};
+///
+/// let mut bloom = FilterBuilder::new(1000, 0.01).build_bloom_filter();
+/// bloom.namespaced_mut("users").add(b"alice");
+/// assert_eq!(bloom.namespaced("users").contains(b"alice"), true);
+/// assert_eq!(bloom.namespaced("groups").contains(b"alice"), false);
+/// ```
+#[derive(Clone)]
+#[derive(Debug)]
+pub struct NamespacedFilter<F> {
+    filter: F,
+    prefix: SmallVec<[u8; 32]>,
+    namespace_len: usize,
+}
+
+impl<F> NamespacedFilter<F> {
+    pub(crate) fn new(filter: F, namespace: &str) -> Self {
+        let mut prefix = Vec::with_capacity(namespace.len() + 2);
+        put_varint(&mut prefix, namespace.len() as u64);
+        prefix.extend_from_slice(namespace.as_bytes());
+        let namespace_len = namespace.len();
+        NamespacedFilter { filter, prefix: SmallVec::from_vec(prefix), namespace_len }
+    }
+
+    /// Returns the namespace of the view.
+    pub fn namespace(&self) -> &str {
+        let start = self.prefix.len() - self.namespace_len;
+        core::str::from_utf8(&self.prefix[start..]).unwrap()
+    }
+
+    /// Returns `element` prefixed with the namespace, the key actually hashed.
+    pub fn key(&self, element: &[u8]) -> SmallVec<[u8; 64]> {
+        let mut key = SmallVec::with_capacity(self.prefix.len() + element.len());
+        key.extend_from_slice(&self.prefix);
+        key.extend_from_slice(element);
+        key
+    }
+}
+
+impl<M: Membership> NamespacedFilter<&M> {
+    /// Tests whether an element is present in the namespace.
+    pub fn contains(&self, element: &[u8]) -> bool {
+        self.filter.contains(&self.key(element))
+    }
+}
+
+impl<M: Membership> NamespacedFilter<&mut M> {
+    /// Adds an element to the namespace.
+    pub fn add(&mut self, element: &[u8]) {
+        let key = self.key(element);
+        self.filter.add(&key);
+    }
+
+    /// Tests whether an element is present in the namespace.
+    pub fn contains(&self, element: &[u8]) -> bool {
+        self.filter.contains(&self.key(element))
+    }
+}
+
+#[test]
+fn namespaced_filter_test() {
+    use crate::FilterBuilder;
+
+    let mut bloom = FilterBuilder::new(10_000, 0.001).build_bloom_filter();
+    let mut users = bloom.namespaced_mut("users");
+    users.add(b"alice");
+    assert!(users.contains(b"alice"));
+    bloom.namespaced_mut("a").add(b"bc");
+
+    assert!(bloom.namespaced("users").contains(b"alice"));
+    assert!(!bloom.contains(b"alice"));
+    assert!(bloom.namespaced("a").contains(b"bc"));
+    assert!(!bloom.namespaced("ab").contains(b"c"));
+    assert_eq!(bloom.namespaced("users").namespace(), "users");
+
+    let view = bloom.namespaced("n");
+    assert!(view.key(b"a") < view.key(b"b"));
+    assert!(view.key(b"a") < view.key(b"ab"));
+}
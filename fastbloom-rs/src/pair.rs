@@ -0,0 +1,123 @@
+use crate::Membership;
+use crate::bloom::BloomFilter;
+use crate::builder::FilterBuilder;
+use crate::index::{hash_pair, mix64};
+
+/// Bloom filter of `(key, label)` pairs, as found in ACL (user, permission) and tagging
+/// (document, tag) systems. Key and label are hashed separately and combined with a mixing
+/// function, so callers don't concatenate them (and ambiguities like `("ab", "c")` vs
+/// `("a", "bc")` can't occur). An auxiliary filter of the keys answers
+/// [PairBloomFilter::contains_any_label] and lets [PairBloomFilter::contains_pair] reject pairs
+/// of unknown keys early, which also lowers its false positive probability.
+///
+/// # Examples
+///
+/// ```rust
+/// use fastbloom_rs::{FilterBuilder, PairBloomFilter};
+///
+/// let mut acl = PairBloomFilter::new(FilterBuilder::new(10_000, 0.01), 1_000);
+/// acl.add_pair(b"alice", b"read");
+/// assert_eq!(acl.contains_pair(b"alice", b"read"), true);
+/// assert_eq!(acl.contains_pair(b"alice", b"write"), false);
+/// assert_eq!(acl.contains_any_label(b"alice"), true);
+/// assert_eq!(acl.contains_any_label(b"bob"), false);
+/// ```
+#[derive(Clone)]
+#[derive(Debug)]
+pub struct PairBloomFilter {
+    pairs: BloomFilter,
+    keys: BloomFilter,
+}
+
+impl PairBloomFilter {
+    /// Builds a filter for the pairs configured in `config`, with an auxiliary key filter for
+    /// `expected_keys` distinct keys at the same false positive probability and hashing.
+    pub fn new(config: FilterBuilder, expected_keys: u64) -> Self {
+        let mut keys = config.clone();
+        keys.expected_elements = expected_keys;
+        keys.size = 0;
+        keys.hashes = 0;
+        keys.done = false;
+        PairBloomFilter { pairs: BloomFilter::new(config), keys: BloomFilter::new(keys) }
+    }
+
+    /// Returns the filter of the pairs.
+    pub fn pairs(&self) -> &BloomFilter {
+        &self.pairs
+    }
+
+    /// Returns the auxiliary filter of the keys.
+    pub fn keys(&self) -> &BloomFilter {
+        &self.keys
+    }
+
+    /// Combines the hashes of key and label into the element added to the pair filter.
+    #[inline]
+    fn pair(&self, key: &[u8], label: &[u8]) -> [u8; 16] {
+        let config = &self.pairs.config;
+        let (k1, k2) = hash_pair(key, config);
+        let (l1, l2) = hash_pair(label, config);
+        // asymmetric in key and label, so (a, b) and (b, a) are different pairs.
+        let a = mix64(k1 ^ mix64(l1));
+        let b = mix64(k2.wrapping_add(l2.rotate_left(29)));
+        let mut pair = [0; 16];
+        pair[..8].copy_from_slice(&a.to_le_bytes());
+        pair[8..].copy_from_slice(&b.to_le_bytes());
+        pair
+    }
+
+    /// Adds the pair `(key, label)`.
+    pub fn add_pair(&mut self, key: &[u8], label: &[u8]) {
+        let pair = self.pair(key, label);
+        self.pairs.add(&pair);
+        self.keys.add(key);
+    }
+
+    /// Tests whether the pair `(key, label)` is present (subject to the false positive rate).
+    pub fn contains_pair(&self, key: &[u8], label: &[u8]) -> bool {
+        self.keys.contains(key) && self.pairs.contains(&self.pair(key, label))
+    }
+
+    /// Tests whether `key` was added with any label.
+    pub fn contains_any_label(&self, key: &[u8]) -> bool {
+        self.keys.contains(key)
+    }
+
+    /// Removes all pairs.
+    pub fn clear(&mut self) {
+        self.pairs.clear();
+        self.keys.clear();
+    }
+}
+
+#[test]
+fn pair_bloom_filter_test() {
+    let mut filter = PairBloomFilter::new(FilterBuilder::new(10_000, 0.01), 100);
+    for user in 0..100u32 {
+        for permission in 0..10u32 {
+            if (user + permission) % 3 == 0 {
+                filter.add_pair(&user.to_le_bytes(), &permission.to_le_bytes());
+            }
+        }
+    }
+    assert!(filter.keys().config().size < filter.pairs().config().size);
+
+    let mut false_positives = 0;
+    for user in 0..100u32 {
+        assert!(filter.contains_any_label(&user.to_le_bytes()));
+        for permission in 0..10u32 {
+            let present = filter.contains_pair(&user.to_le_bytes(), &permission.to_le_bytes());
+            if (user + permission) % 3 == 0 {
+                assert!(present);
+            } else if present {
+                false_positives += 1;
+            }
+        }
+    }
+    assert!(false_positives < 20, "{false_positives}");
+    assert!(!filter.contains_any_label(b"unknown"));
+
+    filter.add_pair(b"a", b"b");
+    filter.clear();
+    assert!(!filter.contains_pair(b"a", b"b"));
+}
@@ -0,0 +1,111 @@
+use alloc::vec::Vec;
+
+use rayon::prelude::*;
+use smallvec::SmallVec;
+
+use crate::bloom::{bit_set, BloomFilter, union_block, union_many_target, UNION_BLOCK_WORDS};
+use crate::compat::MismatchError;
+use crate::index::Indices;
+use crate::vec::{BloomBitVec, Storage, StorageMut};
+
+impl BloomFilter {
+    /// [BloomFilter::union_many] on the rayon thread pool: the blocks of the result are split
+    /// between the threads, every thread merges the words of all filters into its blocks. The
+    /// result is the same, without a bit vector per thread.
+    pub fn par_union_many<'a, T: Storage + Sync + 'a>(
+        filters: impl IntoIterator<Item=&'a BloomFilter<T>>)
+        -> Result<Option<BloomFilter>, MismatchError> {
+        let filters: Vec<&BloomFilter<T>> = filters.into_iter().collect();
+        let Some(mut res) = union_many_target(&filters)? else {
+            return Ok(None);
+        };
+        let blocks = res.bit_set.storage.par_chunks_mut(UNION_BLOCK_WORDS).enumerate();
+        blocks.for_each(|(block, words)| union_block(words, block * UNION_BLOCK_WORDS, &filters));
+        Ok(Some(res))
+    }
+}
+
+impl<S: StorageMut> BloomFilter<S> {
+    /// Adds all `items` on the rayon thread pool: the items are split into one chunk per
+    /// thread, every thread sets the bits of its chunk in a bit vector of its own and the bit
+    /// vectors are OR-ed into the filter at the end. The result is the same as adding the items
+    /// one by one, at the cost of one extra bit vector of the filter size per thread. Insert
+    /// hooks (see [BloomFilter::set_insert_hook]) are called from the worker threads.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use fastbloom_rs::FilterBuilder;
+    ///
+    /// let keys: Vec<String> = (0..100_000).map(|i| format!("key-{i}")).collect();
+    /// let mut bloom = FilterBuilder::new(100_000, 0.01).build_bloom_filter();
+    /// bloom.par_add_all(&keys);
+    /// assert!(bloom.contains_all(&keys));
+    /// ```
+    pub fn par_add_all<T: AsRef<[u8]> + Sync>(&mut self, items: &[T]) {
+        if items.is_empty() {
+            return;
+        }
+        let chunk = items.len().div_ceil(rayon::current_num_threads().max(1));
+        let slots = self.bit_set.storage.slots();
+        let (config, hook) = (&self.config, &self.hook);
+        let merged = items.par_chunks(chunk).map(|chunk| {
+            let mut bits = BloomBitVec::new(slots);
+            for item in chunk {
+                let indices = Indices::new(item.as_ref(), config);
+                match hook.get() {
+                    None => bit_set(&mut bits, indices),
+                    Some(events) => {
+                        let indices: SmallVec<[u64; 16]> = indices.collect();
+                        assert!(!indices.is_empty(), "cannot add to a filter without capacity!");
+                        indices.iter().for_each(|&index| bits.set(index as usize));
+                        events.on_insert(item.as_ref(), &indices);
+                    }
+                }
+            }
+            bits
+        }).reduce_with(|mut bits, other| {
+            bits.or(&other);
+            bits
+        });
+        if let Some(bits) = merged {
+            self.bit_set.or(&bits);
+        }
+        self.stats.record_adds(items.len() as u64);
+    }
+}
+
+#[test]
+fn par_add_all_test() {
+    use crate::{FilterBuilder, Membership};
+
+    let keys: Vec<[u8; 4]> = (0..200_000u32).map(u32::to_le_bytes).collect();
+    let mut bloom = FilterBuilder::new(200_000, 0.01).build_bloom_filter();
+    bloom.add(b"before");
+    bloom.par_add_all(&keys);
+
+    let mut single = FilterBuilder::new(200_000, 0.01).build_bloom_filter();
+    single.add(b"before");
+    single.add_all(&keys);
+    assert_eq!(bloom.get_u8_array(), single.get_u8_array());
+
+    bloom.par_add_all::<&[u8]>(&[]);
+    assert_eq!(bloom.get_u8_array(), single.get_u8_array());
+}
+
+#[test]
+fn par_union_many_test() {
+    use crate::{FilterBuilder, Membership};
+
+    let mut builder = FilterBuilder::new(1_000_000, 0.01);
+    let shards: Vec<BloomFilter> = (0..16u32).map(|shard| {
+        let mut bloom = builder.build_bloom_filter();
+        (shard * 1000..shard * 1000 + 1000).for_each(|i| bloom.add(&i.to_le_bytes()));
+        bloom
+    }).collect();
+    let all = BloomFilter::par_union_many(&shards).unwrap().unwrap();
+    let serial = BloomFilter::union_many(&shards).unwrap().unwrap();
+    assert_eq!(all.get_u8_array(), serial.get_u8_array());
+    assert!((0..16_000u32).all(|i| all.contains(&i.to_le_bytes())));
+    assert!(BloomFilter::par_union_many(&shards[..0]).unwrap().is_none());
+}
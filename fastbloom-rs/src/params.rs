@@ -0,0 +1,90 @@
+//! Formulas relating the size `m` in bits, the number of elements `n`, the number of hashes `k`
+//! and the false positive probability `p` of a Bloom filter, for capacity planning without
+//! building one. They assume a uniform hash function, which the hashers of this crate are close
+//! to.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use fastbloom_rs::params::{expected_fpp, optimal_bits, optimal_hashes};
+//!
+//! let m = optimal_bits(1_000_000, 0.01);
+//! let k = optimal_hashes(m, 1_000_000);
+//! assert_eq!((m, k), (9_585_059, 7));
+//! assert!(expected_fpp(m, 2_000_000, k) > 0.1);
+//! ```
+
+#[cfg(not(feature = "std"))]
+use crate::float::Float;
+
+/// Returns the size in bits for `n` elements at the false positive probability `p`,
+/// `-n ln(p) / ln(2)^2`, saturating at [u64::MAX]. The builders round it up to whole words.
+#[inline]
+pub fn optimal_bits(n: u64, p: f64) -> u64 {
+    (-(n as f64) * p.ln() / 2f64.ln().powi(2)).ceil() as u64
+}
+
+/// Returns the number of hashes with the lowest false positive probability for `m` bits and `n`
+/// elements, one of the integers around `m / n * ln(2)`, or 0 for no elements, which any number
+/// of hashes keeps without false positives. [FilterBuilder::new] rounds up instead, which is the
+/// same for most sizes.
+///
+/// [FilterBuilder::new]: crate::FilterBuilder::new
+pub fn optimal_hashes(m: u64, n: u64) -> u32 {
+    if n == 0 {
+        return 0;
+    }
+    let k = (m as f64 * 2f64.ln() / n as f64).floor().clamp(1.0, u32::MAX as f64 - 1.0) as u32;
+    if expected_fpp(m, n, k) <= expected_fpp(m, n, k + 1) { k } else { k + 1 }
+}
+
+/// Returns the false positive probability of a filter of `m` bits with `k` hashes holding `n`
+/// elements, `(1 - e^(-kn/m))^k`.
+#[inline]
+pub fn expected_fpp(m: u64, n: u64, k: u32) -> f64 {
+    (1.0 - (-(k as f64) * n as f64 / m as f64).exp()).powi(k as i32)
+}
+
+/// Const version of [expected_fpp] for loads `k n / m` below 2, e.g. the optimal one.
+pub(crate) const fn expected_fpp_const(m: u64, n: u64, k: u32) -> f64 {
+    let x = k as f64 * n as f64 / m as f64;
+    // e^-x by its series, exact to the last bits for x below 2
+    let (mut e, mut term, mut i) = (1.0, 1.0, 1);
+    while i < 40 {
+        term *= -x / i as f64;
+        e += term;
+        i += 1;
+    }
+    let (mut p, mut i) = (1.0, 0);
+    while i < k {
+        p *= 1.0 - e;
+        i += 1;
+    }
+    p
+}
+
+/// Returns the largest number of elements `m` bits hold at the false positive probability `p`
+/// with the optimal number of hashes, the inverse of [optimal_bits].
+#[inline]
+pub fn max_elements(m: u64, p: f64) -> u64 {
+    (m as f64 * 2f64.ln().powi(2) / -p.ln()).floor() as u64
+}
+
+#[test]
+fn params_test() {
+    let m = optimal_bits(100_000_000, 0.01);
+    assert_eq!(m, 958_505_838);
+    assert_eq!(optimal_hashes(m, 100_000_000), 7);
+    assert!((expected_fpp(m, 100_000_000, 7) - 0.01).abs() < 1e-4);
+    assert!(max_elements(m, 0.01).abs_diff(100_000_000) <= 1);
+
+    assert_eq!(optimal_hashes(8000, 1000), 6);
+    assert_eq!(optimal_hashes(64, 1000), 1);
+    assert_eq!(optimal_hashes(8000, 0), 0);
+    assert_eq!(optimal_bits(u64::MAX, 1e-9), u64::MAX);
+    assert_eq!(expected_fpp(1 << 20, 0, 7), 0.0);
+    for (m, n, k) in [(256, 36, 5), (64, 45, 1), (8000, 693, 8), (1 << 20, 0, 7)] {
+        let p = expected_fpp(m, n, k);
+        assert!((expected_fpp_const(m, n, k) - p).abs() <= p * 1e-12, "{m} {n} {k}");
+    }
+}
@@ -0,0 +1,355 @@
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+use xxhash_rust::xxh64::Xxh64;
+
+use crate::aging::AgingBloomFilter;
+use crate::bloom::BloomFilter;
+use crate::builder::FilterBuilder;
+use crate::codec::{put_hashing, read_hashing, Reader};
+use crate::compat::FormatFeatures;
+use crate::embedded::{parse_static_header, put_static_header};
+use crate::vec::{BloomBitVec, le_word, Storage};
+
+/// Words per chunk of [BloomFilter::write_to] and [BloomFilter::read_from], 64 KB.
+const CHUNK_WORDS: usize = 65536 / size_of::<usize>();
+
+const SAVE_MAGIC: &[u8; 4] = b"FBSV";
+const AGING_SAVE_MAGIC: &[u8; 4] = b"FBSA";
+/// Version of the format of [BloomFilter::save] and [AgingBloomFilter::save].
+const SAVE_VERSION: u8 = 1;
+
+impl<S: Storage> BloomFilter<S> {
+    /// Writes the filter in the format of [BloomFilter::to_static_bytes] to `writer`, a chunk
+    /// at a time, without building the serialized filter in memory. Wrap unbuffered writers
+    /// in a [BufWriter](std::io::BufWriter) for small chunks.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use fastbloom_rs::{BloomFilter, FilterBuilder, Membership};
+    ///
+    /// let mut bloom = FilterBuilder::new(100_000, 0.01).build_bloom_filter();
+    /// bloom.add(b"hello");
+    /// let mut file = Vec::new();
+    /// bloom.write_to(&mut file).unwrap();
+    /// assert_eq!(file, bloom.to_static_bytes());
+    ///
+    /// let restored = BloomFilter::read_from(&file[..]).unwrap();
+    /// assert_eq!(restored.contains(b"hello"), true);
+    /// ```
+    pub fn write_to<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        let mut header = Vec::new();
+        put_static_header(&mut header, &self.config);
+        writer.write_all(&header)?;
+        let slots = self.bit_set.storage.slots();
+        let mut chunk = Vec::with_capacity(CHUNK_WORDS.min(slots) * size_of::<usize>());
+        for start in (0..slots).step_by(CHUNK_WORDS) {
+            chunk.clear();
+            for slot in start..(start + CHUNK_WORDS).min(slots) {
+                chunk.extend_from_slice(&self.bit_set.word(slot).to_le_bytes());
+            }
+            writer.write_all(&chunk)?;
+        }
+        Ok(())
+    }
+}
+
+impl BloomFilter {
+    /// Reads a filter written by [BloomFilter::write_to] or [BloomFilter::to_static_bytes] from
+    /// `reader`, straight into the bit vector of the filter. Reads exactly the filter, so more
+    /// data may follow it. Fails with [io::ErrorKind::InvalidData] when the header is not valid
+    /// and with [io::ErrorKind::UnexpectedEof] when the bits are truncated, before allocating
+    /// more than the bits read for a header claiming a large size.
+    pub fn read_from<R: Read>(mut reader: R) -> io::Result<Self> {
+        let invalid = || io::Error::new(io::ErrorKind::InvalidData, "not a serialized filter");
+        let mut header = vec![0; 4];
+        reader.read_exact(&mut header)?;
+        read_hashing(&mut reader, &mut header)?;
+        let hashing_end = header.len();
+        header.resize(hashing_end + 8, 0);
+        reader.read_exact(&mut header[hashing_end..])?;
+        let config = parse_static_header(&mut Reader::new(&header)).ok_or_else(invalid)?;
+
+        let slots = usize::try_from(config.size / usize::BITS as u64).map_err(|_| invalid())?;
+        // grown a chunk at a time, so a forged size can't allocate more than the bits read
+        let mut storage = Vec::with_capacity(CHUNK_WORDS.min(slots));
+        let mut chunk = vec![0u8; CHUNK_WORDS.min(slots) * size_of::<usize>()];
+        while storage.len() < slots {
+            let bytes = &mut chunk[..CHUNK_WORDS.min(slots - storage.len()) * size_of::<usize>()];
+            reader.read_exact(bytes)?;
+            storage.extend(bytes.chunks_exact(size_of::<usize>())
+                .map(|bytes| le_word(usize::from_le_bytes(bytes.try_into().unwrap()))));
+        }
+        Ok(BloomFilter::from_bit_vec(config, BloomBitVec::with_storage(storage)))
+    }
+}
+
+impl<S: Storage> BloomFilter<S> {
+    /// Saves the filter to the file at `path`, replacing it: a header with the format version,
+    /// the size `m`, the hashes `k`, the seed and the hash function of the filter and an xxh64
+    /// checksum of the header and the bits, followed by the bits as little-endian u64 words.
+    /// The file is written next to `path` first, synced and renamed over it, so a crash leaves
+    /// the old file in place. Restore it with [BloomFilter::load].
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use fastbloom_rs::{BloomFilter, FilterBuilder, Membership};
+    ///
+    /// let path = std::env::temp_dir().join(format!("fastbloom-save-{}.bin", std::process::id()));
+    /// let mut bloom = FilterBuilder::new(100_000, 0.01).build_bloom_filter();
+    /// bloom.add(b"hello");
+    /// bloom.save(&path).unwrap();
+    /// let restored = BloomFilter::load(&path).unwrap();
+    /// assert_eq!(restored.contains(b"hello"), true);
+    /// # std::fs::remove_file(&path).unwrap();
+    /// ```
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let words = self.bit_set.u64_words();
+        save_file(path.as_ref(), SAVE_MAGIC, &self.config, &[], words, |i| self.bit_set.word_u64(i))
+    }
+}
+
+impl BloomFilter {
+    /// Loads a filter saved with [BloomFilter::save]. Fails with [io::ErrorKind::InvalidData]
+    /// when the file is not such a filter, has another format version, is truncated or longer,
+    /// or its header or bits don't match the checksum. The length of the file is checked
+    /// against the size in the header before the bits are allocated.
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        let (config, mut bit_sets) = load_file(path.as_ref(), SAVE_MAGIC, 0, |_| Some(1))?;
+        Ok(BloomFilter::from_bit_vec(config, bit_sets.pop().unwrap()))
+    }
+}
+
+impl AgingBloomFilter {
+    /// Saves the filter to the file at `path` like [BloomFilter::save], with the number of
+    /// generations in the header and the generations from the oldest to the newest. Generations
+    /// which expired since the last add are saved empty; the interval of
+    /// [AgingBloomFilter::rotate_every] is not saved. Restore it with [AgingBloomFilter::load].
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use fastbloom_rs::{AgingBloomFilter, FilterBuilder, Membership};
+    ///
+    /// let path = std::env::temp_dir().join(format!("fastbloom-aging-{}.bin", std::process::id()));
+    /// let mut filter = FilterBuilder::new(1000, 0.01).build_aging_bloom_filter(2);
+    /// filter.add(b"hello");
+    /// filter.tick();
+    /// filter.save(&path).unwrap();
+    /// let mut restored = AgingBloomFilter::load(&path).unwrap();
+    /// assert_eq!(restored.contains(b"hello"), true);
+    /// restored.tick();
+    /// assert_eq!(restored.contains(b"hello"), false);
+    /// # std::fs::remove_file(&path).unwrap();
+    /// ```
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let generations: Vec<_> = self.by_age().collect();
+        let meta = (generations.len() as u32).to_le_bytes();
+        let (config, words) = (self.config(), (self.config().size / 64) as usize);
+        save_file(path.as_ref(), AGING_SAVE_MAGIC, &config, &meta, words * generations.len(), |i| {
+            generations[i / words].map_or(0, |bits| bits.word_u64(i % words))
+        })
+    }
+
+    /// Loads a filter saved with [AgingBloomFilter::save], failing like [BloomFilter::load].
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        let (config, generations) = load_file(path.as_ref(), AGING_SAVE_MAGIC, 4, |meta| {
+            let generations = u32::from_le_bytes(meta.try_into().unwrap());
+            usize::try_from(generations).ok().filter(|&generations| generations > 0)
+        })?;
+        Ok(AgingBloomFilter::from_generations(config, generations))
+    }
+}
+
+/// Writes the format of [BloomFilter::save] with the magic `magic` to `path`: `meta` follows
+/// the size in the header, and `word(i)` gives the u64 words `0..words` of all bit vectors.
+fn save_file(path: &Path, magic: &[u8; 4], config: &FilterBuilder, meta: &[u8], words: usize,
+             word: impl Fn(usize) -> u64) -> io::Result<()> {
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    let written = (|| {
+        let mut header = magic.to_vec();
+        header.push(SAVE_VERSION);
+        put_hashing(&mut header, config, 0, FormatFeatures::default());
+        header.extend_from_slice(&config.size.to_le_bytes());
+        header.extend_from_slice(meta);
+        let checksum_at = header.len() as u64;
+        header.extend_from_slice(&[0; 8]);
+
+        let mut file = BufWriter::with_capacity(65536, File::create(&tmp)?);
+        file.write_all(&header)?;
+        let mut checksum = Xxh64::new(0);
+        checksum.update(&header[..checksum_at as usize]);
+        for i in 0..words {
+            let word = word(i).to_le_bytes();
+            checksum.update(&word);
+            file.write_all(&word)?;
+        }
+        let mut file = file.into_inner().map_err(|err| err.into_error())?;
+        file.seek(SeekFrom::Start(checksum_at))?;
+        file.write_all(&checksum.digest().to_le_bytes())?;
+        file.sync_all()
+    })().and_then(|_| fs::rename(&tmp, path));
+    if written.is_err() {
+        let _ = fs::remove_file(&tmp);
+    }
+    written
+}
+
+/// Reads a file of [save_file] with the magic `magic` and `meta_len` bytes of `meta`, of as
+/// many bit vectors as `vectors` returns for the meta.
+fn load_file(path: &Path, magic: &[u8; 4], meta_len: usize,
+             vectors: impl FnOnce(&[u8]) -> Option<usize>)
+             -> io::Result<(FilterBuilder, Vec<BloomBitVec>)> {
+    let invalid = |reason| io::Error::new(io::ErrorKind::InvalidData, reason);
+    let mut file = BufReader::with_capacity(65536, File::open(path)?);
+    let mut header = vec![0; 5];
+    file.read_exact(&mut header).map_err(|_| invalid("not a saved filter"))?;
+    if &header[..4] != magic {
+        return Err(invalid("not a saved filter"));
+    }
+    if header[4] != SAVE_VERSION {
+        return Err(invalid("unsupported version of a saved filter"));
+    }
+    read_hashing(&mut file, &mut header).map_err(|_| invalid("truncated filter header"))?;
+    let hashing_end = header.len();
+    header.resize(hashing_end + 8 + meta_len + 8, 0);
+    file.read_exact(&mut header[hashing_end..])
+        .map_err(|_| invalid("truncated filter header"))?;
+
+    let mut reader = Reader::new(&header[5..]);
+    let (config, count) = (|| {
+        let hashing = reader.hashing()?;
+        if !hashing.features.is_empty() {
+            return None;
+        }
+        let size = reader.u64()?;
+        if size == 0 || size % 64 != 0 {
+            return None;
+        }
+        let mut config = FilterBuilder::from_size_and_hashes(size, hashing.hashes);
+        hashing.apply(&mut config)?;
+        Some((config, vectors(reader.take(meta_len)?)?))
+    })().ok_or_else(|| invalid("invalid filter header"))?;
+    let expected = reader.u64().unwrap();
+    let len = file.get_ref().metadata()?.len();
+    let bits = (config.size / 8).checked_mul(count as u64);
+    if len.checked_sub(header.len() as u64) != bits {
+        return Err(invalid("file length doesn't match the filter size"));
+    }
+
+    let slots = usize::try_from(config.size / usize::BITS as u64)
+        .map_err(|_| invalid("filter too large for this platform"))?;
+    let mut checksum = Xxh64::new(0);
+    checksum.update(&header[..header.len() - 8]);
+    let mut chunk = vec![0u8; 65536];
+    let mut words = Vec::with_capacity(chunk.len() / 8);
+    let mut bit_sets = Vec::with_capacity(count);
+    for _ in 0..count {
+        let mut bit_set = BloomBitVec::new(slots);
+        for start in (0..bit_set.u64_words()).step_by(chunk.len() / 8) {
+            let end = (start + chunk.len() / 8).min(bit_set.u64_words());
+            let bytes = &mut chunk[..(end - start) * 8];
+            file.read_exact(bytes).map_err(|_| invalid("truncated filter"))?;
+            checksum.update(bytes);
+            words.clear();
+            words.extend(bytes.chunks_exact(8).map(|w| u64::from_le_bytes(w.try_into().unwrap())));
+            bit_set.set_words_u64(start, &words);
+        }
+        bit_sets.push(bit_set);
+    }
+    if file.read(&mut [0])? != 0 {
+        return Err(invalid("trailing data after the filter"));
+    }
+    if checksum.digest() != expected {
+        return Err(invalid("checksum mismatch, the filter is corrupted"));
+    }
+    Ok((config, bit_sets))
+}
+
+#[test]
+fn stream_io_test() {
+    use crate::builder::FilterBuilder;
+    use crate::Membership;
+
+    let mut builder = FilterBuilder::new(100_000, 0.01);
+    builder.seed(9);
+    builder.canaries(4);
+    let mut bloom = builder.build_bloom_filter();
+    (0..10_000u32).for_each(|i| bloom.add(&i.to_le_bytes()));
+    let mut out = Vec::new();
+    bloom.write_to(&mut out).unwrap();
+    assert_eq!(out, bloom.to_static_bytes());
+
+    out.extend_from_slice(b"trailer");
+    let mut reader = &out[..];
+    let restored = BloomFilter::read_from(&mut reader).unwrap();
+    assert_eq!(restored.get_u8_array(), bloom.get_u8_array());
+    assert_eq!(restored.config().seed, 9);
+    assert_eq!(reader, b"trailer");
+
+    let truncated = BloomFilter::read_from(&out[..out.len() - 100]).unwrap_err();
+    assert_eq!(truncated.kind(), io::ErrorKind::UnexpectedEof);
+    let mut corrupt = out.clone();
+    corrupt[0] = b'X';
+    let corrupt = BloomFilter::read_from(&corrupt[..]).unwrap_err();
+    assert_eq!(corrupt.kind(), io::ErrorKind::InvalidData);
+
+    // a header claiming 2^60 bits fails on the missing bits, not on the allocation
+    let mut forged = Vec::new();
+    put_static_header(&mut forged, &FilterBuilder::from_size_and_hashes(1 << 60, 7));
+    forged.extend_from_slice(&[0; 64]);
+    let forged = BloomFilter::read_from(&forged[..]).unwrap_err();
+    assert!(matches!(forged.kind(), io::ErrorKind::UnexpectedEof | io::ErrorKind::InvalidData));
+
+    // the hash seeds follow the hashing parameters
+    builder.hash_seeds(&[1, 2, 3]);
+    let seeded = builder.build_bloom_filter();
+    let restored = BloomFilter::read_from(&seeded.to_static_bytes()[..]).unwrap();
+    assert_eq!(restored.config().hash_seeds_in_use(), Some(vec![1, 2, 3]));
+}
+
+#[test]
+fn save_load_test() {
+    use crate::builder::FilterBuilder;
+    use crate::Membership;
+
+    let dir = std::env::temp_dir().join(format!("fastbloom-save-test-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("filter.bin");
+    let mut builder = FilterBuilder::new(10_000, 0.01);
+    builder.seed(11);
+    let mut bloom = builder.build_bloom_filter();
+    (0..1000u32).for_each(|i| bloom.add(&i.to_le_bytes()));
+    bloom.save(&path).unwrap();
+    let restored = BloomFilter::load(&path).unwrap();
+    assert_eq!(restored.get_u8_array(), bloom.get_u8_array());
+    assert_eq!(restored.config().seed, 11);
+    assert!(!dir.join("filter.bin.tmp").exists());
+
+    let bytes = fs::read(&path).unwrap();
+    let corrupted = |bytes: &[u8]| {
+        fs::write(&path, bytes).unwrap();
+        BloomFilter::load(&path).unwrap_err().kind()
+    };
+    let mut flipped = bytes.clone();
+    *flipped.last_mut().unwrap() ^= 1;
+    assert_eq!(corrupted(&flipped), io::ErrorKind::InvalidData);
+    assert_eq!(corrupted(&bytes[..bytes.len() - 8]), io::ErrorKind::InvalidData);
+    assert_eq!(corrupted(&[&bytes[..], b"x"].concat()), io::ErrorKind::InvalidData);
+    let mut version = bytes.clone();
+    version[4] = 2;
+    assert_eq!(corrupted(&version), io::ErrorKind::InvalidData);
+    assert_eq!(corrupted(&bytes[..10]), io::ErrorKind::InvalidData);
+    // the checksum covers the header: the bits and checksum under another seed are rejected
+    builder.seed(12);
+    builder.build_bloom_filter().save(&path).unwrap();
+    let reseeded = fs::read(&path).unwrap();
+    let checksum_at = bytes.len() - bloom.get_u8_array().len() - 8;
+    assert_eq!(corrupted(&[&reseeded[..checksum_at], &bytes[checksum_at..]].concat()),
+               io::ErrorKind::InvalidData);
+    fs::remove_dir_all(&dir).unwrap();
+}
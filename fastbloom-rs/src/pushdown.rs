@@ -0,0 +1,137 @@
+use std::collections::VecDeque;
+
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+
+use crate::Membership;
+use crate::bloom::BloomFilter;
+
+/// Filters a stream of candidate keys, e.g. an object store listing, down to those which may be
+/// in a filter. The keys are checked in batches, with the `rayon` feature several at once on the
+/// rayon thread pool, so the memory stays bounded by `batch_size * threads` keys however long the
+/// listing is, and the order of the keys is kept.
+///
+/// # Examples
+///
+/// ```rust
+/// use fastbloom_rs::{FilterBuilder, ListingFilter, Membership};
+///
+/// let mut bloom = FilterBuilder::new(1000, 0.01).build_bloom_filter();
+/// bloom.add(b"logs/2024/01/02.gz");
+/// let bytes = bloom.to_static_bytes();
+///
+/// let listing = ["logs/2024/01/01.gz", "logs/2024/01/02.gz", "logs/2024/01/03.gz"];
+/// let filter = ListingFilter::from_bytes(&bytes).unwrap().threads(2);
+/// let keys: Vec<&str> = filter.filter(listing.into_iter()).collect();
+/// assert_eq!(keys, vec!["logs/2024/01/02.gz"]);
+/// ```
+#[derive(Clone)]
+#[derive(Debug)]
+pub struct ListingFilter<F = BloomFilter> {
+    filter: F,
+    batch_size: usize,
+    threads: usize,
+}
+
+impl ListingFilter {
+    /// Creates a listing filter from a filter serialized with [BloomFilter::to_static_bytes].
+    /// Returns `None` when `bytes` is not such a filter.
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        Some(ListingFilter::new(BloomFilter::from_static_bytes(bytes)?))
+    }
+}
+
+impl<F: Membership + Sync> ListingFilter<F> {
+    /// Creates a listing filter checking keys against `filter`, in batches of 1024 keys on the
+    /// calling thread.
+    pub fn new(filter: F) -> Self {
+        ListingFilter { filter, batch_size: 1024, threads: 1 }
+    }
+
+    /// set the number of keys each thread checks at a time.
+    pub fn batch_size(mut self, batch_size: usize) -> Self {
+        assert!(batch_size > 0, "batch_size must be positive!");
+        self.batch_size = batch_size;
+        self
+    }
+
+    /// set the number of batches checked at once on the rayon thread pool, `1` checks on the
+    /// calling thread. Without the `rayon` feature all batches are checked on the calling
+    /// thread.
+    pub fn threads(mut self, threads: usize) -> Self {
+        assert!(threads > 0, "threads must be positive!");
+        self.threads = threads;
+        self
+    }
+
+    /// Returns the filter the keys are checked against.
+    pub fn inner(&self) -> &F {
+        &self.filter
+    }
+
+    /// Lazily yields the keys of `keys` which may be in the filter, in their original order.
+    pub fn filter<'a, K, I>(&'a self, keys: I) -> impl Iterator<Item=K> + 'a
+        where K: AsRef<[u8]> + Sync + 'a, I: Iterator<Item=K> + 'a {
+        Listing { filter: self, keys, ready: VecDeque::new() }
+    }
+
+    /// Moves the keys of `batch` which may be in the filter to `ready`.
+    fn check<K: AsRef<[u8]> + Sync>(&self, batch: Vec<K>, ready: &mut VecDeque<K>) {
+        let contains = |chunk: &[K]| -> Vec<bool> {
+            chunk.iter().map(|key| self.filter.contains(key.as_ref())).collect()
+        };
+        #[cfg(feature = "rayon")]
+        let present: Vec<bool> = if self.threads == 1 || batch.len() <= self.batch_size {
+            contains(&batch)
+        } else {
+            let chunks: Vec<Vec<bool>> = batch.par_chunks(self.batch_size).map(contains).collect();
+            chunks.concat()
+        };
+        #[cfg(not(feature = "rayon"))]
+        let present = contains(&batch);
+        let kept = batch.into_iter().zip(present).filter(|(_, present)| *present);
+        ready.extend(kept.map(|(key, _)| key));
+    }
+}
+
+struct Listing<'a, F, K, I> {
+    filter: &'a ListingFilter<F>,
+    keys: I,
+    ready: VecDeque<K>,
+}
+
+impl<F, K, I> Iterator for Listing<'_, F, K, I>
+    where F: Membership + Sync, K: AsRef<[u8]> + Sync, I: Iterator<Item=K> {
+    type Item = K;
+
+    fn next(&mut self) -> Option<K> {
+        while self.ready.is_empty() {
+            let batch: Vec<K> =
+                self.keys.by_ref().take(self.filter.batch_size * self.filter.threads).collect();
+            if batch.is_empty() {
+                return None;
+            }
+            self.filter.check(batch, &mut self.ready);
+        }
+        self.ready.pop_front()
+    }
+}
+
+#[test]
+fn listing_filter_test() {
+    use crate::FilterBuilder;
+
+    let mut bloom = FilterBuilder::new(10_000, 0.01).build_bloom_filter();
+    for i in (0..10_000).step_by(7) {
+        bloom.add(format!("objects/{i}").as_bytes());
+    }
+    let keys = || (0..10_000).map(|i| format!("objects/{i}"));
+    let expected: Vec<String> = keys().filter(|key| bloom.contains(key.as_bytes())).collect();
+
+    let filter = ListingFilter::from_bytes(&bloom.to_static_bytes()).unwrap();
+    assert_eq!(filter.filter(keys()).collect::<Vec<_>>(), expected);
+    let filter = filter.batch_size(100).threads(4);
+    assert_eq!(filter.filter(keys()).collect::<Vec<_>>(), expected);
+    assert!(expected.len() >= 10_000 / 7);
+    assert!(ListingFilter::from_bytes(b"FBST").is_none());
+}
@@ -0,0 +1,191 @@
+use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
+
+use crate::bloom::BloomFilter;
+use crate::builder::FilterBuilder;
+use crate::Membership;
+
+/// Answer of [QuarantineFilter::contains].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Presence {
+    /// The element is in the exact set of quarantined keys, present without doubt.
+    Definitely,
+    /// The element is in the Bloom filter: present or a false positive.
+    Probably,
+    /// The element is not present.
+    No,
+}
+
+impl Presence {
+    /// Returns [true] for [Presence::Definitely] and [Presence::Probably].
+    pub fn is_present(&self) -> bool {
+        *self != Presence::No
+    }
+}
+
+/// Bloom filter behind an exact set of the recently added keys, for flows which need exact
+/// answers about fresh keys until they are confirmed, e.g. fraud checks which must not take a
+/// new key for a false positive. Adds go to the exact set (the quarantine), and
+/// [QuarantineFilter::confirm] moves a key into the filter. Once the quarantine holds more than
+/// its capacity of keys, the oldest are moved into the filter on their own. [Presence] tells
+/// the quarantined keys from the probable ones.
+///
+/// The quarantine stores the keys themselves, twice (by key and by age), so keep its capacity
+/// small. The filter is sized by the [FilterBuilder].
+///
+/// # Examples
+///
+/// ```rust
+/// use fastbloom_rs::{FilterBuilder, Presence};
+///
+/// let mut filter = FilterBuilder::new(10_000, 0.01).build_quarantine_filter(100);
+/// filter.add(b"hello");
+/// assert_eq!(filter.contains(b"hello"), Presence::Definitely);
+/// filter.confirm(b"hello");
+/// assert_eq!(filter.contains(b"hello"), Presence::Probably);
+/// assert_eq!(filter.contains(b"world"), Presence::No);
+/// ```
+#[derive(Clone)]
+#[derive(Debug)]
+pub struct QuarantineFilter {
+    bloom: BloomFilter,
+    /// The quarantined keys with the number of their add, and the reverse for the age order.
+    keys: BTreeMap<Box<[u8]>, u64>,
+    ages: BTreeMap<u64, Box<[u8]>>,
+    adds: u64,
+    capacity: usize,
+}
+
+impl QuarantineFilter {
+    /// Builds an empty filter configured by `config` with a quarantine of `capacity` keys.
+    /// Panics if `capacity` is 0.
+    pub fn new(config: FilterBuilder, capacity: usize) -> Self {
+        assert!(capacity > 0, "QuarantineFilter needs a quarantine!");
+        QuarantineFilter {
+            bloom: BloomFilter::new(config),
+            keys: BTreeMap::new(),
+            ages: BTreeMap::new(),
+            adds: 0,
+            capacity,
+        }
+    }
+
+    /// Returns the configuration/builder of the filter.
+    pub fn config(&self) -> FilterBuilder {
+        self.bloom.config()
+    }
+
+    /// Returns the filter of the confirmed keys.
+    pub fn filter(&self) -> &BloomFilter {
+        &self.bloom
+    }
+
+    /// Returns the number of keys in quarantine.
+    pub fn quarantined(&self) -> usize {
+        self.keys.len()
+    }
+
+    /// Returns the most keys in quarantine.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Puts the element in quarantine, unless it is already. If the quarantine is over its
+    /// capacity then, its oldest key is moved into the filter.
+    pub fn add(&mut self, element: &[u8]) {
+        if self.keys.contains_key(element) {
+            return;
+        }
+        self.adds += 1;
+        self.keys.insert(element.into(), self.adds);
+        self.ages.insert(self.adds, element.into());
+        if self.keys.len() > self.capacity {
+            let (_, oldest) = self.ages.pop_first().unwrap();
+            self.keys.remove(&oldest);
+            self.bloom.add(&oldest);
+        }
+    }
+
+    /// Moves the element from the quarantine into the filter. Returns [false] if it was not in
+    /// quarantine, the filter is left unchanged then.
+    pub fn confirm(&mut self, element: &[u8]) -> bool {
+        let Some(age) = self.keys.remove(element) else {
+            return false;
+        };
+        self.ages.remove(&age);
+        self.bloom.add(element);
+        true
+    }
+
+    /// Moves all keys in quarantine into the filter.
+    pub fn flush(&mut self) {
+        for key in self.ages.values() {
+            self.bloom.add(key);
+        }
+        self.keys.clear();
+        self.ages.clear();
+    }
+
+    /// Tests whether the element is in quarantine or (subject to the false positive
+    /// probability) in the filter.
+    pub fn contains(&self, element: &[u8]) -> Presence {
+        if self.keys.contains_key(element) {
+            Presence::Definitely
+        } else if self.bloom.contains(element) {
+            Presence::Probably
+        } else {
+            Presence::No
+        }
+    }
+
+    /// Removes all elements from the quarantine and the filter.
+    pub fn clear(&mut self) {
+        self.bloom.clear();
+        self.keys.clear();
+        self.ages.clear();
+    }
+
+    /// Returns the filter with all keys in quarantine moved into it.
+    pub fn into_inner(mut self) -> BloomFilter {
+        self.flush();
+        self.bloom
+    }
+}
+
+#[test]
+fn quarantine_filter_test() {
+    let mut filter = FilterBuilder::new(10_000, 0.01).build_quarantine_filter(100);
+    (0..100u32).for_each(|i| filter.add(&i.to_le_bytes()));
+    filter.add(&5u32.to_le_bytes());
+    assert_eq!(filter.quarantined(), 100);
+    assert!((0..100u32).all(|i| filter.contains(&i.to_le_bytes()) == Presence::Definitely));
+    assert_eq!(filter.filter().bits_set(), 0);
+    // the quarantine is exact
+    assert!((100..10_000u32).all(|i| filter.contains(&i.to_le_bytes()) == Presence::No));
+
+    assert!(filter.confirm(&7u32.to_le_bytes()));
+    assert!(!filter.confirm(&7u32.to_le_bytes()));
+    assert!(!filter.confirm(&1000u32.to_le_bytes()));
+    assert_eq!(filter.contains(&7u32.to_le_bytes()), Presence::Probably);
+    assert_eq!(filter.quarantined(), 99);
+
+    // the oldest keys leave the quarantine first
+    (100..150u32).for_each(|i| filter.add(&i.to_le_bytes()));
+    assert_eq!(filter.quarantined(), 100);
+    assert!((0..50u32).all(|i| filter.contains(&i.to_le_bytes()) == Presence::Probably));
+    assert!((50..150u32).all(|i| filter.contains(&i.to_le_bytes()) == Presence::Definitely));
+
+    let bloom = filter.clone().into_inner();
+    assert!((0..150u32).all(|i| bloom.contains(&i.to_le_bytes())));
+    filter.flush();
+    assert_eq!(filter.quarantined(), 0);
+    assert!((0..150u32).all(|i| filter.contains(&i.to_le_bytes()).is_present()));
+    filter.clear();
+    assert_eq!(filter.contains(&1u32.to_le_bytes()), Presence::No);
+}
+
+#[test]
+#[should_panic(expected = "QuarantineFilter needs a quarantine")]
+fn quarantine_filter_capacity_test() {
+    FilterBuilder::new(100, 0.01).build_quarantine_filter(0);
+}
@@ -0,0 +1,929 @@
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::builder::FilterBuilder;
+use crate::index::hash_pair;
+use crate::vec::CountingVec;
+use crate::{Deletable, Membership};
+
+/// The slot is the canonical slot of a stored fingerprint.
+const OCCUPIED: usize = 1;
+/// The slot holds a remainder of the same run as the slot before.
+const CONTINUATION: usize = 2;
+/// The slot holds a remainder which is not in its canonical slot.
+const SHIFTED: usize = 4;
+/// Number of metadata bits in front of the remainder of a slot.
+const METADATA_BITS: usize = 3;
+/// Share of the slots in use at which the filter grows.
+const MAX_LOAD: f64 = 0.9;
+
+/// Quotient filter (Bender et al., "Don't Thrash: How to Cache Your Hash on Flash"): the
+/// fingerprint of an element is split into a quotient, the index of its canonical slot, and a
+/// remainder stored in or shortly after that slot, in runs sorted by quotient and remainder. A
+/// query scans a few neighbouring slots only, which stay in one or two cache lines.
+///
+/// Every slot of `W` bits (8 or 16) holds three metadata bits and a remainder of up to `W - 3`
+/// bits, packed like the counters of a [crate::CountingBloomFilter]. The false positive
+/// probability is about the load factor times `2^-remainder_bits`, the false positive
+/// probability of the [FilterBuilder] is not used. The filter is sized for
+/// [FilterBuilder::capacity] elements and doubles its slots when it gets too full, moving a bit
+/// of each remainder to the quotient (so every growth doubles the false positive probability).
+///
+/// Elements with the same fingerprint are stored once, so removing an element also removes
+/// others which collide with it. Filters with the same fingerprint length and hashing can be
+/// merged, see [QuotientFilter::merge].
+///
+/// # Examples
+///
+/// ```rust
+/// use fastbloom_rs::{Deletable, FilterBuilder, Membership};
+///
+/// let mut filter = FilterBuilder::new(1000, 0.01).build_quotient_filter();
+/// filter.add(b"hello");
+/// filter.add(b"world");
+/// assert_eq!(filter.contains(b"hello"), true);
+///
+/// filter.remove(b"hello");
+/// assert_eq!(filter.contains(b"hello"), false);
+/// assert_eq!(filter.contains(b"world"), true);
+/// ```
+#[derive(Clone)]
+#[derive(Debug)]
+pub struct QuotientFilter<const W: usize = 16> {
+    config: FilterBuilder,
+    slots: CountingVec<Vec<usize>, W>,
+    quotient_bits: u32,
+    remainder_bits: u32,
+    len: usize,
+}
+
+impl<const W: usize> QuotientFilter<W> {
+    /// Builds an empty filter for `config.capacity()` elements.
+    pub fn new(config: FilterBuilder) -> Self {
+        assert!(W == 8 || W == 16, "slot width must be 8 or 16 bits!");
+        let slots = ((config.capacity() as f64 / MAX_LOAD) as usize + 1).next_power_of_two();
+        let quotient_bits = slots.trailing_zeros().max(3);
+        Self::with_bits(config, quotient_bits, (W - METADATA_BITS) as u32)
+    }
+
+    fn with_bits(config: FilterBuilder, quotient_bits: u32, remainder_bits: u32) -> Self {
+        assert!(quotient_bits + remainder_bits <= 64, "QuotientFilter is too large!");
+        let words = (1usize << quotient_bits).div_ceil(CountingVec::<Vec<usize>, W>::PER_SLOT);
+        QuotientFilter {
+            config,
+            slots: CountingVec::new(vec![0; words]),
+            quotient_bits,
+            remainder_bits,
+            len: 0,
+        }
+    }
+
+    /// Returns the configuration of the filter.
+    pub fn config(&self) -> FilterBuilder {
+        self.config.clone()
+    }
+
+    /// Returns the number of distinct fingerprints in the filter.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns [true] if the filter holds no fingerprints.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the number of slots of the filter.
+    pub fn slots(&self) -> usize {
+        1 << self.quotient_bits
+    }
+
+    /// Returns the number of bits of a remainder, which decreases when the filter grows.
+    pub fn remainder_bits(&self) -> u32 {
+        self.remainder_bits
+    }
+
+    /// Returns the share of slots which hold a remainder.
+    pub fn load_factor(&self) -> f64 {
+        self.len as f64 / self.slots() as f64
+    }
+
+    /// Doubles the number of slots, using one bit of the remainders as additional quotient bit.
+    /// Returns [false] if the remainders have only one bit left.
+    pub fn grow(&mut self) -> bool {
+        if self.remainder_bits <= 1 {
+            return false;
+        }
+        let grown = Self::with_bits(self.config.clone(), self.quotient_bits + 1,
+                                    self.remainder_bits - 1);
+        let old = core::mem::replace(self, grown);
+        for fingerprint in old.fingerprints() {
+            self.insert(fingerprint);
+        }
+        true
+    }
+
+    /// Adds all elements of `other` to this filter, growing it if needed. Returns [false] and
+    /// leaves the filter unchanged if the filters hash differently or have fingerprints of a
+    /// different length.
+    pub fn merge<const V: usize>(&mut self, other: &QuotientFilter<V>) -> bool {
+        if !self.is_compatible(other) {
+            return false;
+        }
+        for fingerprint in other.fingerprints() {
+            self.reserve();
+            self.insert(fingerprint);
+        }
+        true
+    }
+
+    /// Tests whether both filters compute the same fingerprints.
+    fn is_compatible<const V: usize>(&self, other: &QuotientFilter<V>) -> bool {
+        let (ours, theirs) = (&self.config, &other.config);
+        self.fingerprint_bits() == other.fingerprint_bits() && ours.seed == theirs.seed
+            && ours.hasher == theirs.hasher
+            && ours.key_len_hashing() == theirs.key_len_hashing()
+            && ours.index_scheme == theirs.index_scheme
+    }
+
+    /// Returns the number of bits of the fingerprints, which does not change when growing.
+    fn fingerprint_bits(&self) -> u32 {
+        self.quotient_bits + self.remainder_bits
+    }
+
+    #[inline]
+    fn fingerprint(&self, element: &[u8]) -> u64 {
+        hash_pair(element, &self.config).0 >> (64 - self.fingerprint_bits())
+    }
+
+    /// Grows the filter if one more fingerprint would exceed the maximal load factor, panics if
+    /// it can neither grow nor take another fingerprint.
+    fn reserve(&mut self) {
+        if (self.len + 1) as f64 > self.slots() as f64 * MAX_LOAD && !self.grow() {
+            assert!(self.len + 1 < self.slots(), "QuotientFilter is full!");
+        }
+    }
+
+    #[inline]
+    fn get(&self, slot: usize) -> usize {
+        self.slots.get(slot)
+    }
+
+    #[inline]
+    fn set(&mut self, slot: usize, value: usize) {
+        self.slots.set(slot, value);
+    }
+
+    #[inline]
+    fn next(&self, slot: usize) -> usize {
+        (slot + 1) & (self.slots() - 1)
+    }
+
+    #[inline]
+    fn prev(&self, slot: usize) -> usize {
+        slot.wrapping_sub(1) & (self.slots() - 1)
+    }
+
+    #[inline]
+    fn split(&self, fingerprint: u64) -> (usize, usize) {
+        let quotient = (fingerprint >> self.remainder_bits) as usize & (self.slots() - 1);
+        (quotient, (fingerprint & ((1 << self.remainder_bits) - 1)) as usize)
+    }
+
+    /// Returns the slot where the run of `quotient` starts, or would be inserted.
+    fn find_run(&self, quotient: usize) -> usize {
+        let mut canonical = quotient;
+        while self.get(canonical) & SHIFTED != 0 {
+            canonical = self.prev(canonical);
+        }
+        // walk the runs of the cluster in step with the occupied slots
+        let mut run = canonical;
+        while canonical != quotient {
+            loop {
+                run = self.next(run);
+                if self.get(run) & CONTINUATION == 0 {
+                    break;
+                }
+            }
+            loop {
+                canonical = self.next(canonical);
+                if self.get(canonical) & OCCUPIED != 0 {
+                    break;
+                }
+            }
+        }
+        run
+    }
+
+    /// Returns the slot of `remainder` in the run starting at `start`, or the slot it belongs
+    /// to. Skips the counter slots of a [CountingQuotientFilter], whose payload is above any
+    /// remainder.
+    fn find_remainder(&self, start: usize, remainder: usize) -> (usize, bool) {
+        let mut slot = start;
+        loop {
+            let stored = self.get(slot) >> METADATA_BITS;
+            if stored >> self.remainder_bits == 0 && stored >= remainder {
+                return (slot, stored == remainder);
+            }
+            slot = self.next(slot);
+            if self.get(slot) & CONTINUATION == 0 {
+                return (slot, false);
+            }
+        }
+    }
+
+    /// Contains check of a fingerprint.
+    fn contains_fingerprint(&self, fingerprint: u64) -> bool {
+        let (quotient, remainder) = self.split(fingerprint);
+        self.get(quotient) & OCCUPIED != 0
+            && self.find_remainder(self.find_run(quotient), remainder).1
+    }
+
+    /// Inserts a fingerprint, which needs a free slot.
+    fn insert(&mut self, fingerprint: u64) {
+        let (quotient, remainder) = self.split(fingerprint);
+        if self.get(quotient) & OCCUPIED == 0 {
+            self.insert_run(quotient, remainder);
+            return;
+        }
+        let start = self.find_run(quotient);
+        let (slot, found) = self.find_remainder(start, remainder);
+        if !found {
+            self.insert_at(quotient, slot, slot == start, remainder);
+        }
+    }
+
+    /// Stores `payload` as the only entry of the run of `quotient`, which has none yet, and
+    /// returns its slot.
+    fn insert_run(&mut self, quotient: usize, payload: usize) -> usize {
+        let canonical = self.get(quotient);
+        let entry = payload << METADATA_BITS;
+        self.len += 1;
+        if canonical & (OCCUPIED | CONTINUATION | SHIFTED) == 0 {
+            self.set(quotient, entry | OCCUPIED);
+            return quotient;
+        }
+        self.set(quotient, canonical | OCCUPIED);
+        let slot = self.find_run(quotient);
+        self.shift_in(slot, if slot != quotient { entry | SHIFTED } else { entry });
+        slot
+    }
+
+    /// Stores `payload` at `slot` of the run of `quotient`, in front of the entry there, as new
+    /// start of the run if `starts_run`.
+    fn insert_at(&mut self, quotient: usize, slot: usize, starts_run: bool, payload: usize) {
+        let mut entry = payload << METADATA_BITS;
+        if starts_run {
+            // the old start of the run becomes a continuation
+            self.set(slot, self.get(slot) | CONTINUATION);
+        } else {
+            entry |= CONTINUATION;
+        }
+        if slot != quotient {
+            entry |= SHIFTED;
+        }
+        self.shift_in(slot, entry);
+        self.len += 1;
+    }
+
+    /// Stores `entry` at `slot` and shifts the following entries of the cluster to the right,
+    /// the occupied bits stay with their slots.
+    fn shift_in(&mut self, mut slot: usize, mut entry: usize) {
+        loop {
+            let previous = self.get(slot);
+            let empty = previous & (OCCUPIED | CONTINUATION | SHIFTED) == 0;
+            let mut moved = previous;
+            if !empty {
+                moved |= SHIFTED;
+                if moved & OCCUPIED != 0 {
+                    entry |= OCCUPIED;
+                    moved &= !OCCUPIED;
+                }
+            }
+            self.set(slot, entry);
+            if empty {
+                return;
+            }
+            entry = moved;
+            slot = self.next(slot);
+        }
+    }
+
+    /// Removes a fingerprint, returns [false] if it is not present.
+    fn delete(&mut self, fingerprint: u64) -> bool {
+        let (quotient, remainder) = self.split(fingerprint);
+        if self.get(quotient) & OCCUPIED == 0 {
+            return false;
+        }
+        let (slot, found) = self.find_remainder(self.find_run(quotient), remainder);
+        if found {
+            self.remove_at(quotient, slot);
+        }
+        found
+    }
+
+    /// Removes the entry at `slot` of the run of `quotient`.
+    fn remove_at(&mut self, quotient: usize, slot: usize) {
+        let run_start = self.get(slot) & CONTINUATION == 0;
+        if run_start && self.get(self.next(slot)) & CONTINUATION == 0 {
+            // the last remainder of the run
+            self.set(quotient, self.get(quotient) & !OCCUPIED);
+        }
+        self.shift_out(slot, quotient);
+        if run_start {
+            let next = self.get(slot);
+            let mut updated = next;
+            if next & CONTINUATION != 0 {
+                // the next remainder of the run starts it now
+                updated &= !CONTINUATION;
+            }
+            if slot == quotient && is_run_start(updated) {
+                updated &= !SHIFTED;
+            }
+            if updated != next {
+                self.set(slot, updated);
+            }
+        }
+        self.len -= 1;
+    }
+
+    /// Removes the entry at `slot` and shifts the following entries of the cluster to the left.
+    /// `quotient` is the canonical slot of the run of the entry.
+    fn shift_out(&mut self, mut slot: usize, mut quotient: usize) {
+        let first = slot;
+        let mut current = self.get(slot);
+        loop {
+            let following = self.next(slot);
+            let next = self.get(following);
+            if next & (OCCUPIED | CONTINUATION | SHIFTED) == 0 || is_cluster_start(next)
+                || following == first {
+                self.set(slot, 0);
+                return;
+            }
+            let mut updated = next;
+            if is_run_start(next) {
+                // the next run moves left, towards its canonical slot
+                loop {
+                    quotient = self.next(quotient);
+                    if self.get(quotient) & OCCUPIED != 0 {
+                        break;
+                    }
+                }
+                if current & OCCUPIED != 0 && quotient == slot {
+                    updated &= !SHIFTED;
+                }
+            }
+            self.set(slot, updated & !OCCUPIED | current & OCCUPIED);
+            slot = following;
+            current = next;
+        }
+    }
+
+    /// Iterates over the fingerprints, run by run, starting with the first cluster.
+    fn fingerprints(&self) -> impl Iterator<Item=u64> + '_ {
+        self.entries().map(|(quotient, payload)| (quotient as u64) << self.remainder_bits
+            | payload as u64)
+    }
+
+    /// Iterates over the quotients and payloads of the entries, run by run, starting with the
+    /// first cluster.
+    fn entries(&self) -> impl Iterator<Item=(usize, usize)> + '_ {
+        let start = (0..self.slots()).find(|&slot| is_cluster_start(self.get(slot)))
+            .unwrap_or(0);
+        let (mut slot, mut quotient, mut visited) = (start, start, 0);
+        core::iter::from_fn(move || {
+            while visited < self.len {
+                let entry = self.get(slot);
+                if is_cluster_start(entry) {
+                    quotient = slot;
+                } else if is_run_start(entry) {
+                    loop {
+                        quotient = self.next(quotient);
+                        if self.get(quotient) & OCCUPIED != 0 {
+                            break;
+                        }
+                    }
+                }
+                slot = self.next(slot);
+                if entry & (OCCUPIED | CONTINUATION | SHIFTED) != 0 {
+                    visited += 1;
+                    return Some((quotient, entry >> METADATA_BITS));
+                }
+            }
+            None
+        })
+    }
+}
+
+/// The entry is in its canonical slot and starts a cluster.
+#[inline]
+fn is_cluster_start(entry: usize) -> bool {
+    entry & (OCCUPIED | CONTINUATION | SHIFTED) == OCCUPIED
+}
+
+/// The entry is the first remainder of a run.
+#[inline]
+fn is_run_start(entry: usize) -> bool {
+    entry & CONTINUATION == 0 && entry & (OCCUPIED | SHIFTED) != 0
+}
+
+impl<const W: usize> Membership for QuotientFilter<W> {
+    /// Adds the fingerprint of the passed value to the filter, growing it if it is too full.
+    /// Panics if it can not grow any further and is full.
+    fn add(&mut self, element: &[u8]) {
+        let fingerprint = self.fingerprint(element);
+        if !self.contains_fingerprint(fingerprint) {
+            self.reserve();
+            // growing keeps the fingerprint
+            self.insert(fingerprint);
+        }
+    }
+
+    /// Tests whether the fingerprint of an element is present in the filter.
+    #[inline]
+    fn contains(&self, element: &[u8]) -> bool {
+        self.contains_fingerprint(self.fingerprint(element))
+    }
+
+    /// Get the fingerprint of the element, the quotient followed by the remainder.
+    fn get_hash_indices(&self, element: &[u8]) -> Vec<u64> {
+        vec![self.fingerprint(element)]
+    }
+
+    /// Tests whether a fingerprint from `get_hash_indices` is present in the filter.
+    fn contains_hash_indices(&self, indices: &Vec<u64>) -> bool {
+        self.contains_fingerprint(indices[0])
+    }
+
+    /// Removes all fingerprints from the filter.
+    fn clear(&mut self) {
+        self.slots.clear();
+        self.len = 0;
+    }
+}
+
+impl<const W: usize> Deletable for QuotientFilter<W> {
+    /// Removes the fingerprint of the element, if present.
+    fn remove(&mut self, element: &[u8]) {
+        let fingerprint = self.fingerprint(element);
+        self.delete(fingerprint);
+    }
+}
+
+/// Counting quotient filter (Pandey et al., "A General-Purpose Counting Filter: Making Every Bit
+/// Count"): a [QuotientFilter] which stores how often each fingerprint was added, as a
+/// variable-length counter in the slots following its remainder. A fingerprint added once takes
+/// one slot, more frequent ones one more slot per `remainder_bits` bits of their count, so the
+/// filter stays as small as a quotient filter for sets and much smaller than a
+/// [crate::CountingBloomFilter] for skewed multisets.
+///
+/// Slots of `W` bits (8 or 16) hold the three metadata bits, one bit marking the counter slots
+/// and a remainder of `W - 4` bits. Counts are exact unless fingerprints collide, so
+/// [CountingQuotientFilter::estimate_count] never underestimates, like a count-min sketch
+/// which supports deletes. The filter is sized for [FilterBuilder::capacity] distinct elements
+/// and grows like a [QuotientFilter], re-inserting its fingerprints in sorted order. Filters
+/// with the same fingerprint length and hashing can be merged, adding their counts.
+///
+/// # Examples
+///
+/// ```rust
+/// use fastbloom_rs::{Deletable, FilterBuilder, Membership};
+///
+/// let mut filter = FilterBuilder::new(1000, 0.01).build_counting_quotient_filter();
+/// filter.add(b"hello");
+/// filter.add_count(b"world", 1000);
+/// assert_eq!(filter.estimate_count(b"hello"), 1);
+/// assert_eq!(filter.estimate_count(b"world"), 1000);
+///
+/// filter.remove(b"world");
+/// assert_eq!(filter.estimate_count(b"world"), 999);
+/// filter.remove(b"hello");
+/// assert_eq!(filter.contains(b"hello"), false);
+/// assert_eq!(filter.len(), 1);
+/// ```
+#[derive(Clone)]
+#[derive(Debug)]
+pub struct CountingQuotientFilter<const W: usize = 16> {
+    /// The slots, with a remainder bit less than a [QuotientFilter] of the same width. Its
+    /// length counts the slots in use.
+    filter: QuotientFilter<W>,
+    distinct: usize,
+}
+
+impl<const W: usize> CountingQuotientFilter<W> {
+    /// Builds an empty filter for `config.capacity()` distinct elements.
+    pub fn new(config: FilterBuilder) -> Self {
+        assert!(W == 8 || W == 16, "slot width must be 8 or 16 bits!");
+        let slots = ((config.capacity() as f64 / MAX_LOAD) as usize + 1).next_power_of_two();
+        let quotient_bits = slots.trailing_zeros().max(3);
+        Self::with_bits(config, quotient_bits, (W - METADATA_BITS - 1) as u32)
+    }
+
+    fn with_bits(config: FilterBuilder, quotient_bits: u32, remainder_bits: u32) -> Self {
+        CountingQuotientFilter {
+            filter: QuotientFilter::with_bits(config, quotient_bits, remainder_bits),
+            distinct: 0,
+        }
+    }
+
+    /// Returns the configuration of the filter.
+    pub fn config(&self) -> FilterBuilder {
+        self.filter.config()
+    }
+
+    /// Returns the number of distinct fingerprints in the filter.
+    pub fn len(&self) -> usize {
+        self.distinct
+    }
+
+    /// Returns [true] if the filter holds no fingerprints.
+    pub fn is_empty(&self) -> bool {
+        self.distinct == 0
+    }
+
+    /// Returns the number of slots of the filter.
+    pub fn slots(&self) -> usize {
+        self.filter.slots()
+    }
+
+    /// Returns the number of bits of a remainder, which decreases when the filter grows.
+    pub fn remainder_bits(&self) -> u32 {
+        self.filter.remainder_bits()
+    }
+
+    /// Returns the share of slots which hold a remainder or a counter.
+    pub fn load_factor(&self) -> f64 {
+        self.filter.load_factor()
+    }
+
+    /// Returns how often the element was added and not removed, or more if its fingerprint
+    /// collides with others.
+    pub fn estimate_count(&self, element: &[u8]) -> usize {
+        self.count_fingerprint(self.filter.fingerprint(element))
+    }
+
+    /// Adds the element `count` times, growing the filter if it is too full. The count
+    /// saturates at [usize::MAX]. Panics if it can not grow any further and is full.
+    pub fn add_count(&mut self, element: &[u8], count: usize) {
+        let fingerprint = self.filter.fingerprint(element);
+        self.add_fingerprint(fingerprint, count);
+    }
+
+    /// Removes the element up to `count` times and returns how often it was removed.
+    pub fn remove_count(&mut self, element: &[u8], count: usize) -> usize {
+        let fingerprint = self.filter.fingerprint(element);
+        let Some((quotient, slot)) = self.locate(fingerprint) else {
+            return 0;
+        };
+        let (stored, digits) = self.counter(slot);
+        let removed = count.min(stored);
+        self.set_counter(quotient, slot, digits, stored - removed);
+        removed
+    }
+
+    /// Iterates over the stored fingerprints, the quotient followed by the remainder like in
+    /// [Membership::get_hash_indices], with their counts.
+    pub fn fingerprints(&self) -> impl Iterator<Item=(u64, usize)> + '_ {
+        let remainder_bits = self.filter.remainder_bits;
+        let mut entries = self.filter.entries().peekable();
+        core::iter::from_fn(move || {
+            let (quotient, remainder) = entries.next()?;
+            let mut count = 0;
+            let mut shift = 0;
+            while let Some((_, digit)) = entries.next_if(|&(_, p)| p >> remainder_bits != 0) {
+                count |= (digit & ((1 << remainder_bits) - 1)) << shift;
+                shift += remainder_bits;
+            }
+            Some(((quotient as u64) << remainder_bits | remainder as u64, count + 1))
+        })
+    }
+
+    /// Doubles the number of slots, using one bit of the remainders as additional quotient bit.
+    /// Returns [false] if the remainders have only one bit left.
+    pub fn grow(&mut self) -> bool {
+        let (quotient_bits, remainder_bits) = (self.filter.quotient_bits, self.remainder_bits());
+        if remainder_bits <= 1 {
+            return false;
+        }
+        let grown = Self::with_bits(self.config(), quotient_bits + 1, remainder_bits - 1);
+        let old = core::mem::replace(self, grown);
+        for (fingerprint, count) in old.fingerprints() {
+            self.insert_fingerprint(fingerprint, count);
+        }
+        true
+    }
+
+    /// Adds the counts of `other` to this filter, growing it if needed. Returns [false] and
+    /// leaves the filter unchanged if the filters hash differently or have fingerprints of a
+    /// different length.
+    pub fn merge<const V: usize>(&mut self, other: &CountingQuotientFilter<V>) -> bool {
+        if !self.filter.is_compatible(&other.filter) {
+            return false;
+        }
+        for (fingerprint, count) in other.fingerprints() {
+            self.add_fingerprint(fingerprint, count);
+        }
+        true
+    }
+
+    /// Returns the quotient and the slot of the remainder of a stored fingerprint.
+    fn locate(&self, fingerprint: u64) -> Option<(usize, usize)> {
+        let (quotient, remainder) = self.filter.split(fingerprint);
+        if self.filter.get(quotient) & OCCUPIED == 0 {
+            return None;
+        }
+        let (slot, found) = self.filter.find_remainder(self.filter.find_run(quotient), remainder);
+        found.then_some((quotient, slot))
+    }
+
+    fn count_fingerprint(&self, fingerprint: u64) -> usize {
+        self.locate(fingerprint).map_or(0, |(_, slot)| self.counter(slot).0)
+    }
+
+    /// Returns the count of the remainder at `slot` and the number of its counter slots.
+    fn counter(&self, slot: usize) -> (usize, usize) {
+        let remainder_bits = self.filter.remainder_bits;
+        let (mut count, mut digits, mut next) = (0, 0, slot);
+        loop {
+            next = self.filter.next(next);
+            let entry = self.filter.get(next);
+            let payload = entry >> METADATA_BITS;
+            if entry & CONTINUATION == 0 || payload >> remainder_bits == 0 {
+                return (count + 1, digits);
+            }
+            count |= (payload & ((1 << remainder_bits) - 1)) << (digits as u32 * remainder_bits);
+            digits += 1;
+        }
+    }
+
+    /// Returns the number of counter slots of `count`, which is `count - 1` in base
+    /// `2^remainder_bits`.
+    fn counter_slots(&self, count: usize) -> usize {
+        let bits = usize::BITS - count.saturating_sub(1).leading_zeros();
+        bits.div_ceil(self.filter.remainder_bits) as usize
+    }
+
+    /// Adds a fingerprint `count` times, growing the filter if it is too full.
+    fn add_fingerprint(&mut self, fingerprint: u64, count: usize) {
+        if count == 0 {
+            return;
+        }
+        let stored = self.count_fingerprint(fingerprint);
+        let needed = self.counter_slots(stored.saturating_add(count)) + (stored == 0) as usize
+            - self.counter_slots(stored);
+        while (self.filter.len + needed) as f64 > self.slots() as f64 * MAX_LOAD {
+            if !self.grow() {
+                assert!(self.filter.len + needed < self.slots(),
+                        "CountingQuotientFilter is full!");
+                break;
+            }
+        }
+        // growing keeps the fingerprint
+        self.insert_fingerprint(fingerprint, count);
+    }
+
+    /// Adds a fingerprint `count` times, which needs enough free slots.
+    fn insert_fingerprint(&mut self, fingerprint: u64, count: usize) {
+        let (quotient, remainder) = self.filter.split(fingerprint);
+        let slot = if self.filter.get(quotient) & OCCUPIED == 0 {
+            self.filter.insert_run(quotient, remainder)
+        } else {
+            let start = self.filter.find_run(quotient);
+            let (slot, found) = self.filter.find_remainder(start, remainder);
+            if found {
+                let (stored, digits) = self.counter(slot);
+                self.set_counter(quotient, slot, digits, stored.saturating_add(count));
+                return;
+            }
+            self.filter.insert_at(quotient, slot, slot == start, remainder);
+            slot
+        };
+        self.distinct += 1;
+        self.set_counter(quotient, slot, 0, count);
+    }
+
+    /// Replaces the `digits` counter slots of the remainder at `slot` of the run of `quotient`
+    /// with those of `count`, removes the remainder if `count` is zero.
+    fn set_counter(&mut self, quotient: usize, slot: usize, digits: usize, count: usize) {
+        let remainder_bits = self.filter.remainder_bits;
+        let (mut rest, mut kept, mut at) = (count.saturating_sub(1), 0, slot);
+        while rest != 0 {
+            at = self.filter.next(at);
+            let payload = 1 << remainder_bits | rest & ((1 << remainder_bits) - 1);
+            if kept < digits {
+                let metadata = self.filter.get(at) & ((1 << METADATA_BITS) - 1);
+                self.filter.set(at, payload << METADATA_BITS | metadata);
+                kept += 1;
+            } else {
+                self.filter.insert_at(quotient, at, false, payload);
+            }
+            rest >>= remainder_bits;
+        }
+        for _ in kept..digits {
+            self.filter.remove_at(quotient, self.filter.next(at));
+        }
+        if count == 0 {
+            self.filter.remove_at(quotient, slot);
+            self.distinct -= 1;
+        }
+    }
+}
+
+impl<const W: usize> Membership for CountingQuotientFilter<W> {
+    /// Adds the element once, growing the filter if it is too full. Panics if it can not grow
+    /// any further and is full.
+    fn add(&mut self, element: &[u8]) {
+        self.add_count(element, 1);
+    }
+
+    /// Tests whether the fingerprint of an element is present in the filter.
+    #[inline]
+    fn contains(&self, element: &[u8]) -> bool {
+        self.locate(self.filter.fingerprint(element)).is_some()
+    }
+
+    /// Get the fingerprint of the element, the quotient followed by the remainder.
+    fn get_hash_indices(&self, element: &[u8]) -> Vec<u64> {
+        self.filter.get_hash_indices(element)
+    }
+
+    /// Tests whether a fingerprint from `get_hash_indices` is present in the filter.
+    fn contains_hash_indices(&self, indices: &Vec<u64>) -> bool {
+        self.locate(indices[0]).is_some()
+    }
+
+    /// Removes all fingerprints from the filter.
+    fn clear(&mut self) {
+        self.filter.clear();
+        self.distinct = 0;
+    }
+}
+
+impl<const W: usize> Deletable for CountingQuotientFilter<W> {
+    /// Removes the element once, if present.
+    fn remove(&mut self, element: &[u8]) {
+        self.remove_count(element, 1);
+    }
+}
+
+#[test]
+fn quotient_filter_test() {
+    let mut filter = FilterBuilder::new(1000, 0.01).build_quotient_filter();
+    assert_eq!((filter.slots(), filter.remainder_bits()), (2048, 13));
+    for i in 0..1000u32 {
+        filter.add(&i.to_le_bytes());
+        filter.add(&i.to_le_bytes());
+    }
+    assert_eq!(filter.len(), 1000);
+    assert!((0..1000u32).all(|i| filter.contains(&i.to_le_bytes())));
+    let false_positives = (1000..101_000u32).filter(|i| filter.contains(&i.to_le_bytes())).count();
+    assert!(false_positives < 20, "{false_positives} false positives");
+
+    for i in (0..1000u32).step_by(2) {
+        filter.remove(&i.to_le_bytes());
+    }
+    assert_eq!(filter.len(), 500);
+    assert!((1..1000u32).step_by(2).all(|i| filter.contains(&i.to_le_bytes())));
+    assert!((0..1000u32).step_by(2).all(|i| !filter.contains(&i.to_le_bytes())));
+
+    filter.clear();
+    assert!(filter.is_empty());
+    assert!(!filter.contains(&1u32.to_le_bytes()));
+}
+
+#[test]
+fn quotient_filter_runs_test() {
+    use alloc::collections::BTreeSet;
+
+    // a tiny filter with long clusters and wrap-around, checked against a set of fingerprints
+    let mut filter = QuotientFilter::<8>::with_bits(FilterBuilder::new(8, 0.01), 4, 5);
+    let mut expected = BTreeSet::new();
+    let mut state = 0x2545f4914f6cdd1du64;
+    for round in 0..20_000 {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        let fingerprint = state >> 55;
+        if expected.len() < 14 && (round % 3 != 0 || expected.is_empty()) {
+            filter.insert(fingerprint);
+            expected.insert(fingerprint);
+        } else {
+            assert_eq!(filter.delete(fingerprint), expected.remove(&fingerprint));
+        }
+        assert_eq!(filter.len(), expected.len());
+        assert!(expected.iter().all(|&f| filter.contains_fingerprint(f)), "round {round}");
+        let stored: BTreeSet<u64> = filter.fingerprints().collect();
+        assert_eq!(stored, expected, "round {round}");
+    }
+}
+
+#[test]
+fn quotient_filter_grow_merge_test() {
+    let mut filter = FilterBuilder::new(100, 0.01).build_quotient_filter();
+    for i in 0..1000u32 {
+        filter.add(&i.to_le_bytes());
+    }
+    assert_eq!((filter.slots(), filter.remainder_bits()), (2048, 9));
+    assert!((0..1000u32).all(|i| filter.contains(&i.to_le_bytes())));
+
+    let mut other = FilterBuilder::new(100, 0.01).build_quotient_filter();
+    for i in 1000..1100u32 {
+        other.add(&i.to_le_bytes());
+    }
+    assert!(other.merge(&filter));
+    assert_eq!(other.len(), 1100);
+    assert!((0..1100u32).all(|i| other.contains(&i.to_le_bytes())));
+    let narrow = QuotientFilter::<8>::new(FilterBuilder::new(100, 0.01));
+    assert!(!other.merge(&narrow));
+    let mut seeded = FilterBuilder::new(100, 0.01);
+    seeded.seed(7);
+    assert!(!other.merge(&seeded.build_quotient_filter()));
+
+    let mut small = QuotientFilter::<8>::new(FilterBuilder::new(4, 0.01));
+    while small.grow() {}
+    assert_eq!(small.remainder_bits(), 1);
+    for i in 0..small.slots() as u32 * 8 / 10 {
+        small.add(&i.to_le_bytes());
+    }
+    assert!((0..small.slots() as u32 * 8 / 10).all(|i| small.contains(&i.to_le_bytes())));
+}
+
+#[test]
+fn counting_quotient_filter_test() {
+    // a counter of three slots, which also fits a 32-bit usize
+    const HEAVY: usize = 1 << 30;
+    let mut filter = FilterBuilder::new(1000, 0.01).build_counting_quotient_filter();
+    assert_eq!((filter.slots(), filter.remainder_bits()), (2048, 12));
+    for i in 0..1000u32 {
+        filter.add_count(&i.to_le_bytes(), i as usize % 10 + 1);
+    }
+    filter.add_count(&0u32.to_le_bytes(), HEAVY);
+    assert_eq!(filter.len(), 1000);
+    assert_eq!(filter.estimate_count(&0u32.to_le_bytes()), HEAVY + 1);
+    assert!((1..1000u32).all(|i| filter.estimate_count(&i.to_le_bytes()) > i as usize % 10));
+    let exact = (1..1000u32)
+        .filter(|&i| filter.estimate_count(&i.to_le_bytes()) == i as usize % 10 + 1).count();
+    assert!(exact > 990, "{exact} exact counts");
+    let false_positives = (1000..101_000u32).filter(|i| filter.contains(&i.to_le_bytes())).count();
+    assert!(false_positives < 50, "{false_positives} false positives");
+
+    assert_eq!(filter.remove_count(&5u32.to_le_bytes(), 100), 6);
+    assert!(!filter.contains(&5u32.to_le_bytes()));
+    filter.remove(&0u32.to_le_bytes());
+    assert_eq!(filter.estimate_count(&0u32.to_le_bytes()), HEAVY);
+    assert_eq!(filter.len(), 999);
+
+    let mut other = FilterBuilder::new(1000, 0.01).build_counting_quotient_filter();
+    other.add_count(&5u32.to_le_bytes(), 3);
+    other.add(&0u32.to_le_bytes());
+    for i in 1000..1500u32 {
+        other.add(&i.to_le_bytes());
+    }
+    assert!(filter.merge(&other));
+    assert_eq!(filter.len(), 1500);
+    assert_eq!(filter.estimate_count(&5u32.to_le_bytes()), 3);
+    assert_eq!(filter.estimate_count(&0u32.to_le_bytes()), HEAVY + 1);
+    let narrow = CountingQuotientFilter::<8>::new(FilterBuilder::new(10, 0.01));
+    assert!(!filter.merge(&narrow));
+
+    filter.clear();
+    assert!(filter.is_empty());
+    assert_eq!(filter.fingerprints().count(), 0);
+}
+
+#[test]
+fn counting_quotient_filter_counters_test() {
+    use alloc::collections::BTreeMap;
+
+    // a tiny filter with long counters, growth and wrap-around, checked against a map of counts
+    let mut filter = CountingQuotientFilter::<16>::with_bits(FilterBuilder::new(8, 0.01), 3, 9);
+    let mut expected = BTreeMap::new();
+    let mut state = 0x2545f4914f6cdd1du64;
+    for round in 0..20_000 {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        // 32 fingerprints spread over the 12-bit fingerprints, with counts of up to 3 slots
+        let fingerprint = (state >> 59).wrapping_mul(0x9e5) & 0xfff;
+        let count = (state >> 40 & 0x3ff) as usize + 1;
+        if round % 3 != 0 {
+            filter.add_fingerprint(fingerprint, count);
+            *expected.entry(fingerprint).or_insert(0) += count;
+        } else if let Some(stored) = expected.get_mut(&fingerprint) {
+            let (quotient, slot) = filter.locate(fingerprint).unwrap();
+            let (_, digits) = filter.counter(slot);
+            *stored = stored.saturating_sub(count * 2);
+            filter.set_counter(quotient, slot, digits, *stored);
+            if *stored == 0 {
+                expected.remove(&fingerprint);
+            }
+        } else {
+            assert!(filter.locate(fingerprint).is_none(), "round {round}");
+        }
+        assert_eq!(filter.len(), expected.len());
+        let stored: BTreeMap<u64, usize> = filter.fingerprints().collect();
+        assert_eq!(stored, expected, "round {round}");
+        assert!(expected.iter().all(|(&f, &c)| filter.count_fingerprint(f) == c), "round {round}");
+    }
+    assert!(filter.remainder_bits() < 9);
+}
@@ -0,0 +1,135 @@
+use alloc::borrow::Cow;
+use alloc::vec::Vec;
+
+use crate::bloom::BloomFilter;
+use crate::builder::FilterBuilder;
+use crate::hasher::DEFAULT_HASHER;
+use crate::index::IndexScheme;
+use crate::vec::{BloomBitVec, le_word, Storage};
+
+impl BloomFilter {
+    /// Builds a filter of `nbits` bits from its 64-bit words, e.g. a bitmap computed by another
+    /// system: bit `i` of the filter is bit `i % 64` (least significant first) of `bits[i / 64]`
+    /// on every host. The filter hashes with `hashes` hash functions seeded with `seed`, the
+    /// defaults of [FilterBuilder]. On 64-bit hosts the words are taken over without copying.
+    ///
+    /// Returns `None` when there are no bits or hashes, `bits` is not `nbits.div_ceil(64)`
+    /// words long or a bit past `nbits` is set.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use fastbloom_rs::{BloomFilter, FilterBuilder, Membership};
+    ///
+    /// let mut bloom = FilterBuilder::from_size_and_hashes(1024, 5).build_bloom_filter();
+    /// bloom.add(b"hello");
+    /// let (bits, nbits, hashes, seed) = bloom.into_raw_parts();
+    /// assert_eq!((bits.len(), nbits, hashes, seed), (16, 1024, 5, 0));
+    ///
+    /// let restored = BloomFilter::from_raw_parts(bits, nbits, hashes, seed).unwrap();
+    /// assert!(restored.contains(b"hello"));
+    /// ```
+    pub fn from_raw_parts(bits: Vec<u64>, nbits: u64, hashes: u32, seed: u64) -> Option<Self> {
+        if nbits == 0 || hashes == 0 || bits.len() as u64 != nbits.div_ceil(64) {
+            return None;
+        }
+        if !nbits.is_multiple_of(64) && bits.last()? >> (nbits % 64) != 0 {
+            return None;
+        }
+        let mut config = FilterBuilder::from_size_and_hashes(nbits, hashes);
+        config.seed = seed;
+        #[cfg(target_pointer_width = "64")]
+        let storage: Vec<usize> = bits.into_iter().map(|word| le_word(word as usize)).collect();
+        #[cfg(target_pointer_width = "32")]
+        let storage: Vec<usize> = bits.into_iter()
+            .flat_map(|word| [word as usize, (word >> 32) as usize])
+            .map(le_word)
+            .collect();
+        Some(BloomFilter::from_bit_vec(config, BloomBitVec::with_storage(storage)))
+    }
+
+    /// Returns the words, size, hashes and seed of the filter, the parts of
+    /// [BloomFilter::from_raw_parts]. Panics unless the filter hashes like the filters built
+    /// from raw parts: the default hasher and index scheme, without canaries, partitions, hash
+    /// seeds or hashing specialized on the key length.
+    pub fn into_raw_parts(self) -> (Vec<u64>, u64, u32, u64) {
+        let config = &self.config;
+        assert!(config.index_scheme == IndexScheme::DoubleHashing
+                    && config.hasher_id() == DEFAULT_HASHER && config.hasher.key_check == 0
+                    && !config.key_len_hashing() && config.canaries == 0 && !config.partitioned,
+                "filter must use the default hashing to export raw parts!");
+        let words = usize::try_from(config.size.div_ceil(64)).unwrap();
+        let bits = (0..words).map(|i| self.bit_set.word_u64(i)).collect();
+        (bits, config.size, config.hashes, config.seed)
+    }
+}
+
+impl<S: Storage + AsRef<[usize]>> BloomFilter<S> {
+    /// Returns the bits of the filter as bytes in little-endian order on every host: bit `i` is
+    /// bit `i % 8` of byte `i / 8`, the bytes of the words of [BloomFilter::into_raw_parts] one
+    /// after another. Borrows the bit vector on little-endian hosts and with the
+    /// `canonical-u64` feature, copies it on big-endian ones. The bytes past the size are zero.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use fastbloom_rs::BloomFilter;
+    ///
+    /// let bloom = BloomFilter::from_raw_parts(vec![0b1010, 1 << 63], 128, 3, 0).unwrap();
+    /// let bytes = bloom.as_bytes();
+    /// assert_eq!((bytes[0], bytes[15]), (0b1010, 0x80));
+    /// ```
+    pub fn as_bytes(&self) -> Cow<'_, [u8]> {
+        if cfg!(any(target_endian = "little", feature = "canonical-u64")) {
+            Cow::Borrowed(self.get_u8_array())
+        } else {
+            Cow::Owned((0..self.bit_set.storage.slots())
+                .flat_map(|slot| self.bit_set.word(slot).to_le_bytes())
+                .collect())
+        }
+    }
+}
+
+#[test]
+fn raw_parts_test() {
+    use crate::Membership;
+
+    let mut builder = FilterBuilder::from_size_and_hashes(1024, 4);
+    builder.seed(42);
+    let mut bloom = builder.build_bloom_filter();
+    (0..50u32).for_each(|i| bloom.add(&i.to_le_bytes()));
+    let indices = bloom.get_hash_indices(&7u32.to_le_bytes());
+    let bytes = bloom.as_bytes().into_owned();
+    let (bits, nbits, hashes, seed) = bloom.clone().into_raw_parts();
+    assert_eq!((bits.len(), nbits, hashes, seed), (16, 1024, 4, 42));
+    assert!(indices.iter().all(|&i| bits[i as usize / 64] >> (i % 64) & 1 == 1));
+    assert!(indices.iter().all(|&i| bytes[i as usize / 8] >> (i % 8) & 1 == 1));
+    let le: Vec<u8> = bits.iter().flat_map(|word| word.to_le_bytes()).collect();
+    assert_eq!(bytes[..], le[..bytes.len()]);
+
+    let restored = BloomFilter::from_raw_parts(bits.clone(), nbits, hashes, seed).unwrap();
+    assert!(restored.config().is_compatible_to(&bloom.config()));
+    assert!((0..50u32).all(|i| restored.contains(&i.to_le_bytes())));
+    assert_eq!(restored.bits_set(), bloom.bits_set());
+
+    assert!(BloomFilter::from_raw_parts(bits[1..].to_vec(), nbits, hashes, seed).is_none());
+    assert!(BloomFilter::from_raw_parts(bits.clone(), nbits, 0, seed).is_none());
+    assert!(BloomFilter::from_raw_parts(Vec::new(), 0, hashes, seed).is_none());
+    // sizes which are not whole words, as in bitmaps built elsewhere
+    let mut odd = BloomFilter::from_raw_parts(vec![0; 16], 1000, hashes, seed).unwrap();
+    (0..50u32).for_each(|i| odd.add(&i.to_le_bytes()));
+    assert!(odd.get_hash_indices(&7u32.to_le_bytes()).iter().all(|&i| i < 1000));
+    let (bits, nbits, ..) = odd.into_raw_parts();
+    assert_eq!((bits.len(), nbits, bits[15] >> 40), (16, 1000, 0));
+    let mut stray = bits;
+    stray[15] |= 1 << 63;
+    assert!(BloomFilter::from_raw_parts(stray, nbits, hashes, seed).is_none());
+}
+
+#[test]
+#[should_panic(expected = "default hashing to export raw parts")]
+fn raw_parts_hashing_test() {
+    let mut builder = FilterBuilder::new(1000, 0.01);
+    builder.index_scheme(IndexScheme::Extended);
+    builder.build_bloom_filter().into_raw_parts();
+}
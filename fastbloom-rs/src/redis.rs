@@ -0,0 +1,217 @@
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::bloom::BloomFilter;
+use crate::builder::FilterBuilder;
+#[cfg(not(feature = "std"))]
+use crate::float::Float;
+use crate::index::IndexScheme;
+use crate::vec::{BloomBitVec, le_word, Storage};
+
+/// `BLOOM_OPT_NOROUND`: the size is not rounded up to a power of two.
+const OPT_NOROUND: u32 = 1;
+/// `BLOOM_OPT_FORCE64`: elements are hashed to 64-bit values.
+const OPT_FORCE64: u32 = 4;
+/// Length of `dumpedChainHeader` without its links.
+const CHAIN_HEADER_LEN: usize = 20;
+/// Length of one packed `dumpedChainLink`.
+const LINK_HEADER_LEN: usize = 53;
+/// Largest chunk of bits `BF.SCANDUMP` returns.
+const MAX_CHUNK_LEN: usize = 16 * 1024 * 1024;
+/// Growth factor of a scalable RedisBloom filter, the default of `BF.RESERVE`.
+const GROWTH: u32 = 2;
+
+impl BloomFilter {
+    /// Restores a filter from the `(iterator, data)` replies of RedisBloom's `BF.SCANDUMP`, in
+    /// the order they were returned: the header followed by the chunks of the bits. The final
+    /// `(0, "")` reply may be included. The filter uses [IndexScheme::RedisBloom], so it
+    /// answers like the Redis filter.
+    ///
+    /// Returns `None` for invalid or incomplete dumps, for filters which scaled to more than one
+    /// sub-filter (reserve with `NONSCALING` or a large enough capacity) and for filters of
+    /// RedisBloom releases hashing to 32-bit values.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use fastbloom_rs::{BloomFilter, FilterBuilder, IndexScheme, Membership};
+    ///
+    /// let mut builder = FilterBuilder::new(1000, 0.01);
+    /// builder.index_scheme(IndexScheme::RedisBloom);
+    /// let mut bloom = builder.build_bloom_filter();
+    /// bloom.add(b"hello");
+    ///
+    /// // BF.LOADCHUNK key <iterator> <data> for every chunk, BF.SCANDUMP to get them back
+    /// let chunks = bloom.to_redis_chunks();
+    /// let restored = BloomFilter::from_redis_chunks(
+    ///     chunks.iter().map(|(iterator, data)| (*iterator, data.as_slice())),
+    /// ).unwrap();
+    /// assert!(restored.contains(b"hello"));
+    /// ```
+    pub fn from_redis_chunks<'a>(chunks: impl IntoIterator<Item=(i64, &'a [u8])>) -> Option<Self> {
+        let mut chunks = chunks.into_iter();
+        let (1, header) = chunks.next()? else {
+            return None;
+        };
+        let config = parse_header(header)?;
+        let len = usize::try_from(config.size / 8).ok()?;
+        // grown with the chunks received, the size in the header is not trusted for allocations
+        let mut bits = Vec::new();
+        for (iterator, data) in chunks {
+            if iterator == 0 && data.is_empty() {
+                break;
+            }
+            // the iterator points behind the chunk, one-based.
+            let offset = usize::try_from(iterator).ok()?.checked_sub(data.len() + 1)?;
+            if offset != bits.len() || len - bits.len() < data.len() {
+                return None;
+            }
+            bits.extend_from_slice(data);
+        }
+        if bits.len() != len {
+            return None;
+        }
+        let storage = bits.chunks_exact(size_of::<usize>())
+            .map(|word| le_word(usize::from_le_bytes(word.try_into().unwrap())))
+            .collect();
+        Some(BloomFilter::from_bit_vec(config, BloomBitVec::with_storage(storage)))
+    }
+}
+
+/// Reads the configuration of a single-filter chain from a `BF.SCANDUMP` header.
+fn parse_header(header: &[u8]) -> Option<FilterBuilder> {
+    let u32_at = |at: usize| Some(u32::from_le_bytes(header.get(at..at + 4)?.try_into().ok()?));
+    let u64_at = |at: usize| Some(u64::from_le_bytes(header.get(at..at + 8)?.try_into().ok()?));
+    let (filters, options) = (u32_at(8)?, u32_at(12)?);
+    if filters != 1 || header.len() != CHAIN_HEADER_LEN + LINK_HEADER_LEN
+        || options & OPT_FORCE64 == 0 {
+        return None;
+    }
+    let link = CHAIN_HEADER_LEN;
+    let (bytes, bits) = (u64_at(link)?, u64_at(link + 8)?);
+    let error = f64::from_bits(u64_at(link + 24)?);
+    let (hashes, entries) = (u32_at(link + 40)?, u64_at(link + 44)?);
+    let n2 = header[link + 52];
+    // indices are taken modulo 2^n2 for power of two sizes, below 64 bits that is not the size.
+    let modulus = if n2 > 0 { 1u64.checked_shl(n2 as u32)? } else { bits };
+    if bytes == 0 || bytes % 8 != 0 || bits != bytes * 8 || modulus != bits || hashes == 0 {
+        return None;
+    }
+    let mut config = FilterBuilder::from_size_and_hashes(bits, hashes);
+    config.index_scheme(IndexScheme::RedisBloom);
+    if entries > 0 && error > 0.0 && error < 1.0 {
+        config.expected_elements = entries;
+        config.false_positive_probability = error;
+    }
+    config.complete();
+    Some(config)
+}
+
+impl<S: Storage> BloomFilter<S> {
+    /// Encodes the filter as the `(iterator, data)` arguments of RedisBloom's `BF.LOADCHUNK`,
+    /// to be sent in order, see [BloomFilter::from_redis_chunks]. The Redis filter is a default
+    /// scalable one: it grows by a new sub-filter once about as many elements as
+    /// [FilterBuilder::expected_elements] were added, the elements of this filter are counted
+    /// with [BloomFilter::estimate_len]. Panics unless the filter uses [IndexScheme::RedisBloom]
+    /// with the built-in hasher, a seed of 0, no canaries or partitions and a size of whole
+    /// 64-bit words.
+    pub fn to_redis_chunks(&self) -> Vec<(i64, Vec<u8>)> {
+        let config = &self.config;
+        assert!(config.index_scheme == IndexScheme::RedisBloom && config.hasher.hasher.is_none()
+                    && config.seed == 0 && config.canaries == 0 && !config.partitioned,
+                "filter must use IndexScheme::RedisBloom with the default hashing!");
+        assert_eq!(config.size % 64, 0, "RedisBloom filters have a size of whole words!");
+        let len = self.estimate_len() as u64;
+        let error = config.false_positive_probability;
+        let bpe = -error.ln() / (2f64.ln() * 2f64.ln());
+
+        let mut header = Vec::with_capacity(CHAIN_HEADER_LEN + LINK_HEADER_LEN);
+        header.extend_from_slice(&len.to_le_bytes());
+        header.extend_from_slice(&1u32.to_le_bytes());
+        header.extend_from_slice(&(OPT_NOROUND | OPT_FORCE64).to_le_bytes());
+        header.extend_from_slice(&GROWTH.to_le_bytes());
+        header.extend_from_slice(&(config.size / 8).to_le_bytes());
+        header.extend_from_slice(&config.size.to_le_bytes());
+        header.extend_from_slice(&len.to_le_bytes());
+        header.extend_from_slice(&error.to_le_bytes());
+        header.extend_from_slice(&bpe.to_le_bytes());
+        header.extend_from_slice(&config.hashes.to_le_bytes());
+        header.extend_from_slice(&config.expected_elements.to_le_bytes());
+        header.push(0);
+
+        let storage = &self.bit_set.storage;
+        let bits: Vec<u8> = (0..storage.slots())
+            .flat_map(|slot| self.bit_set.word(slot).to_le_bytes())
+            .collect();
+        let mut chunks = vec![(1, header)];
+        let mut iterator = 1;
+        for chunk in bits.chunks(MAX_CHUNK_LEN) {
+            iterator += chunk.len() as i64;
+            chunks.push((iterator, chunk.to_vec()));
+        }
+        chunks
+    }
+}
+
+#[test]
+fn redis_chunks_test() {
+    use crate::Membership;
+
+    let mut builder = FilterBuilder::new(1000, 0.01);
+    builder.index_scheme(IndexScheme::RedisBloom);
+    let mut bloom = builder.build_bloom_filter();
+    for i in 0..500u32 {
+        bloom.add(&i.to_le_bytes());
+    }
+    let chunks = bloom.to_redis_chunks();
+    assert_eq!(chunks.len(), 2);
+    assert_eq!(chunks[0].1.len(), CHAIN_HEADER_LEN + LINK_HEADER_LEN);
+    assert_eq!(chunks[1], (1 + bloom.get_u8_array().len() as i64, bloom.get_u8_array().to_vec()));
+
+    let replies = chunks.iter().map(|(iterator, data)| (*iterator, data.as_slice()));
+    let restored = BloomFilter::from_redis_chunks(replies.chain([(0, &[][..])])).unwrap();
+    assert_eq!(restored.get_u8_array(), bloom.get_u8_array());
+    assert!(restored.config().is_compatible_to(&bloom.config()));
+    assert_eq!(restored.config().expected_elements, 1000);
+    assert!((0..500u32).all(|i| restored.contains(&i.to_le_bytes())));
+
+    // the bits split into two chunks like a large filter
+    let (header, bits) = (chunks[0].1.as_slice(), chunks[1].1.as_slice());
+    let split = [(1, header), (11, &bits[..10]), (1 + bits.len() as i64, &bits[10..])];
+    assert!(BloomFilter::from_redis_chunks(split).is_some());
+    assert!(BloomFilter::from_redis_chunks([(1, header), (11, &bits[..10])]).is_none());
+    assert!(BloomFilter::from_redis_chunks([(1, header)]).is_none());
+
+    let mut scaled = header.to_vec();
+    scaled[8] = 2;
+    assert!(BloomFilter::from_redis_chunks([(1, &scaled[..]), (chunks[1].0, bits)]).is_none());
+    let mut hash32 = header.to_vec();
+    hash32[12] &= !(OPT_FORCE64 as u8);
+    assert!(BloomFilter::from_redis_chunks([(1, &hash32[..]), (chunks[1].0, bits)]).is_none());
+}
+
+#[test]
+fn redis_power_of_two_test() {
+    // filters of releases before NOROUND have 2^n2 bits.
+    let mut header = [0u8; CHAIN_HEADER_LEN + LINK_HEADER_LEN];
+    header[8] = 1;
+    header[12] = OPT_FORCE64 as u8;
+    let link = CHAIN_HEADER_LEN;
+    header[link..link + 8].copy_from_slice(&128u64.to_le_bytes());
+    header[link + 8..link + 16].copy_from_slice(&1024u64.to_le_bytes());
+    header[link + 40..link + 44].copy_from_slice(&7u32.to_le_bytes());
+    header[link + 52] = 10;
+    let bits = [0xffu8; 128];
+    let bloom = BloomFilter::from_redis_chunks([(1, &header[..]), (129, &bits[..])]).unwrap();
+    assert_eq!((bloom.config().size, bloom.config().hashes), (1024, 7));
+    assert!(bloom.contains(b"hello"));
+
+    header[link + 52] = 11;
+    assert!(BloomFilter::from_redis_chunks([(1, &header[..]), (129, &bits[..])]).is_none());
+
+    // a header claiming 2^60 bits without the chunks for them
+    header[link..link + 8].copy_from_slice(&(1u64 << 57).to_le_bytes());
+    header[link + 8..link + 16].copy_from_slice(&(1u64 << 60).to_le_bytes());
+    header[link + 52] = 60;
+    assert!(BloomFilter::from_redis_chunks([(1, &header[..]), (129, &bits[..])]).is_none());
+}
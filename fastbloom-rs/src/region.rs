@@ -0,0 +1,212 @@
+use alloc::vec;
+use alloc::vec::Vec;
+use core::ops::Range;
+
+use crate::bloom::BloomFilter;
+use crate::builder::FilterBuilder;
+use crate::index::Indices;
+use crate::vec::{BloomBitVec, le_word};
+
+/// Handle of a region of a [RegionAllocator]. Handles of freed regions are never valid again,
+/// even once their slot is reused.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct RegionId {
+    slot: u32,
+    /// Generation of the slot the region was allocated in.
+    generation: u32,
+}
+
+#[derive(Clone, Debug)]
+struct Region {
+    config: FilterBuilder,
+    words: Range<usize>,
+}
+
+/// A slot of [RegionAllocator::regions], its generation counts the regions freed in it.
+#[derive(Clone, Debug, Default)]
+struct Slot {
+    generation: u32,
+    region: Option<Region>,
+}
+
+/// Carves one pre-allocated bit array into regions, each a logical Bloom filter of its own size
+/// and seed which is cleared and freed independently. Services managing thousands of small
+/// filters (per tenant, per user) allocate once instead of once per filter, which keeps the
+/// allocator pressure and the fragmentation of the heap low.
+///
+/// Regions are placed first fit, freed space is merged with its free neighbours. Give every
+/// region its own [seed](FilterBuilder::seed), so keys of one region don't set the same bit
+/// patterns in another.
+///
+/// # Examples
+///
+/// ```rust
+/// use fastbloom_rs::{FilterBuilder, Membership, RegionAllocator};
+///
+/// let mut regions = RegionAllocator::new(1 << 20);
+/// let mut config = FilterBuilder::new(1000, 0.01);
+/// config.seed(1);
+/// let alice = regions.alloc(config.clone()).unwrap();
+/// config.seed(2);
+/// let bob = regions.alloc(config).unwrap();
+///
+/// regions.filter_mut(alice).add(b"hello");
+/// assert!(regions.contains(alice, b"hello"));
+/// assert!(!regions.contains(bob, b"hello"));
+/// regions.clear(alice);
+/// assert!(!regions.contains(alice, b"hello"));
+/// ```
+#[derive(Clone, Debug)]
+pub struct RegionAllocator {
+    words: Vec<usize>,
+    regions: Vec<Slot>,
+    /// Free word ranges, sorted and not adjacent.
+    free: Vec<Range<usize>>,
+}
+
+impl RegionAllocator {
+    /// Allocates a bit array of `bits` bits (rounded up to whole words) for the regions.
+    pub fn new(bits: u64) -> Self {
+        let slots = bits.div_ceil(usize::BITS as u64) as usize;
+        let free = core::iter::once(0..slots).collect();
+        RegionAllocator { words: vec![0; slots], regions: Vec::new(), free }
+    }
+
+    /// Returns the number of free bits, maybe split into several ranges.
+    pub fn free_bits(&self) -> u64 {
+        self.free.iter().map(|range| range.len() as u64 * usize::BITS as u64).sum()
+    }
+
+    /// Allocates an empty region for a filter configured by `config`, or returns `None` if no
+    /// free range is large enough.
+    pub fn alloc(&mut self, mut config: FilterBuilder) -> Option<RegionId> {
+        config.complete();
+        let slots = (config.size / usize::BITS as u64) as usize;
+        let i = self.free.iter().position(|range| range.len() >= slots)?;
+        let start = self.free[i].start;
+        self.free[i].start += slots;
+        if self.free[i].is_empty() {
+            self.free.remove(i);
+        }
+        let words = start..start + slots;
+        self.words[words.clone()].fill(0);
+        let slot = match self.regions.iter().position(|slot| slot.region.is_none()) {
+            Some(slot) => slot,
+            None => {
+                self.regions.push(Slot::default());
+                self.regions.len() - 1
+            }
+        };
+        self.regions[slot].region = Some(Region { config, words });
+        Some(RegionId { slot: slot as u32, generation: self.regions[slot].generation })
+    }
+
+    /// Frees the region `id`. Panics if it is not allocated; using its handle afterwards
+    /// panics as well.
+    pub fn free(&mut self, id: RegionId) {
+        self.region(id);
+        let slot = &mut self.regions[id.slot as usize];
+        slot.generation = slot.generation.wrapping_add(1);
+        let words = slot.region.take().unwrap().words;
+        let i = self.free.partition_point(|range| range.start < words.start);
+        let merge_prev = i > 0 && self.free[i - 1].end == words.start;
+        let merge_next = i < self.free.len() && self.free[i].start == words.end;
+        match (merge_prev, merge_next) {
+            (true, true) => {
+                self.free[i - 1].end = self.free[i].end;
+                self.free.remove(i);
+            }
+            (true, false) => self.free[i - 1].end = words.end,
+            (false, true) => self.free[i].start = words.start,
+            (false, false) => self.free.insert(i, words),
+        }
+    }
+
+    /// Returns the region `id`, panics if its handle is stale.
+    fn region(&self, id: RegionId) -> &Region {
+        let slot = &self.regions[id.slot as usize];
+        assert_eq!(slot.generation, id.generation, "region was freed!");
+        slot.region.as_ref().expect("region is not allocated!")
+    }
+
+    /// Returns the configuration/builder of the region `id`.
+    pub fn config(&self, id: RegionId) -> FilterBuilder {
+        self.region(id).config.clone()
+    }
+
+    /// Removes all elements from the region `id`.
+    pub fn clear(&mut self, id: RegionId) {
+        let words = self.region(id).words.clone();
+        self.words[words].fill(0);
+    }
+
+    /// Tests whether an element is present in the region `id`.
+    pub fn contains(&self, id: RegionId, element: &[u8]) -> bool {
+        let region = self.region(id);
+        let words = &self.words[region.words.clone()];
+        let mut indices = Indices::new(element, &region.config);
+        !indices.is_empty() && indices.all(|index| {
+            let (w, b) = (index / usize::BITS as u64, index % usize::BITS as u64);
+            words[w as usize] & le_word(1 << b) != 0
+        })
+    }
+
+    /// Returns the region `id` as a filter borrowing its words, e.g. to add elements.
+    pub fn filter_mut(&mut self, id: RegionId) -> BloomFilter<&mut [usize]> {
+        let region = self.region(id).clone();
+        let words = &mut self.words[region.words.clone()];
+        BloomFilter::from_bit_vec(region.config.clone(), BloomBitVec::with_storage(words))
+    }
+}
+
+#[test]
+fn region_allocator_test() {
+    use crate::Membership;
+
+    let mut regions = RegionAllocator::new(64 * 1000);
+    let config = |seed| {
+        let mut config = FilterBuilder::from_size_and_hashes(64 * 100, 4);
+        config.seed(seed);
+        config
+    };
+    let ids: Vec<RegionId> = (0..10).map(|seed| regions.alloc(config(seed)).unwrap()).collect();
+    assert_eq!(regions.free_bits(), 0);
+    assert!(regions.alloc(config(10)).is_none());
+
+    for (i, id) in ids.iter().enumerate() {
+        regions.filter_mut(*id).add(&(i as u32).to_le_bytes());
+    }
+    for (i, id) in ids.iter().enumerate() {
+        assert!(regions.contains(*id, &(i as u32).to_le_bytes()));
+        assert_eq!(regions.config(*id).seed, i as u64);
+    }
+    assert!(!regions.contains(ids[1], &0u32.to_le_bytes()));
+
+    // freeing neighbours merges their space
+    regions.free(ids[3]);
+    regions.free(ids[5]);
+    regions.free(ids[4]);
+    assert_eq!(regions.free_bits(), 3 * 64 * 100);
+    let mut large = FilterBuilder::from_size_and_hashes(3 * 64 * 100, 4);
+    large.seed(11);
+    let large = regions.alloc(large).unwrap();
+    assert_ne!(large, ids[3]);
+    assert_eq!(regions.config(large).seed, 11);
+    assert!(!regions.contains(large, &3u32.to_le_bytes()));
+    assert!(regions.contains(ids[6], &6u32.to_le_bytes()));
+
+    regions.clear(ids[6]);
+    assert!(!regions.contains(ids[6], &6u32.to_le_bytes()));
+    assert!(regions.contains(ids[7], &7u32.to_le_bytes()));
+}
+
+#[test]
+#[should_panic(expected = "region was freed")]
+fn region_allocator_stale_id_test() {
+    let mut regions = RegionAllocator::new(64 * 1000);
+    let first = regions.alloc(FilterBuilder::from_size_and_hashes(64 * 100, 4)).unwrap();
+    regions.free(first);
+    // the slot is reused, the handle of the freed region stays invalid
+    regions.alloc(FilterBuilder::from_size_and_hashes(64 * 100, 4)).unwrap();
+    regions.contains(first, b"hello");
+}
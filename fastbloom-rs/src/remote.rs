@@ -0,0 +1,221 @@
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::future::{poll_fn, Future};
+use core::task::Poll;
+
+use smallvec::SmallVec;
+
+use crate::builder::FilterBuilder;
+use crate::index::Indices;
+
+/// Asynchronous storage of the 64-bit words of a bit vector, for filters whose bits live
+/// elsewhere, e.g. in Redis or on another node, see [AsyncBloomFilter]. Bit `i` of the filter is
+/// bit `i % 64` of word `i / 64` on every platform, the layout of
+/// [BloomFilter::bits_in_range](crate::BloomFilter::bits_in_range), which copies the words of a
+/// local filter for upload. Implementations may write the methods as `async fn`. The storage is
+/// [Sync] and its futures are [Send], so the queries of an [AsyncBloomFilter] can be spawned on
+/// multi-threaded runtimes such as `tokio::spawn`.
+pub trait AsyncStorage: Sync {
+    /// Error of a failed read.
+    type Error;
+
+    /// Returns the number of words.
+    fn slots(&self) -> usize;
+
+    /// Reads the word at `slot`.
+    fn get_word(&self, slot: usize) -> impl Future<Output=Result<u64, Self::Error>> + Send;
+
+    /// Reads the words at `slots`, returned in the same order. By default all
+    /// [AsyncStorage::get_word] requests are issued at once and awaited together; override it
+    /// to fetch them in one round trip, e.g. with an `MGET` or a pipeline. Fails with the first
+    /// error, dropping the other requests.
+    fn get_words(&self, slots: &[usize])
+                 -> impl Future<Output=Result<Vec<u64>, Self::Error>> + Send {
+        async move {
+            let mut requests: Vec<_> = slots.iter().map(|&slot| Box::pin(self.get_word(slot)))
+                .collect();
+            let mut words: Vec<Option<u64>> = alloc::vec![None; slots.len()];
+            poll_fn(|cx| {
+                let mut pending = false;
+                for (request, word) in requests.iter_mut().zip(&mut words) {
+                    if word.is_some() {
+                        continue;
+                    }
+                    match request.as_mut().poll(cx) {
+                        Poll::Ready(Ok(value)) => *word = Some(value),
+                        Poll::Ready(Err(error)) => return Poll::Ready(Err(error)),
+                        Poll::Pending => pending = true,
+                    }
+                }
+                if pending {
+                    Poll::Pending
+                } else {
+                    Poll::Ready(Ok(words.iter().map(|word| word.unwrap()).collect()))
+                }
+            }).await
+        }
+    }
+}
+
+/// Bloom filter probing the bits of an [AsyncStorage], so the bitmap can live in Redis or on
+/// another node while the hashing stays in this crate (feature `async`). A query reads the
+/// distinct words of its `k` indices with a single [AsyncStorage::get_words], so they are
+/// fetched concurrently or in one batch. The filter is read-only: build it locally and upload
+/// its words. No runtime is needed, the futures run on any executor.
+///
+/// # Examples
+///
+/// ```rust
+/// use std::future::Future;
+/// use std::pin::pin;
+/// use std::task::{Context, Poll, Waker};
+///
+/// use fastbloom_rs::{AsyncStorage, FilterBuilder, Membership};
+///
+/// /// The words of a filter "on another node".
+/// struct Remote(Vec<u64>);
+///
+/// impl AsyncStorage for Remote {
+///     type Error = ();
+///
+///     fn slots(&self) -> usize {
+///         self.0.len()
+///     }
+///
+///     async fn get_word(&self, slot: usize) -> Result<u64, ()> {
+///         self.0.get(slot).copied().ok_or(())
+///     }
+/// }
+///
+/// let mut builder = FilterBuilder::new(10_000, 0.01);
+/// let mut bloom = builder.build_bloom_filter();
+/// bloom.add(b"hello");
+/// let remote = Remote(bloom.bits_in_range(0..bloom.word_len()));
+/// let filter = builder.build_async_bloom_filter(remote);
+///
+/// let mut query = pin!(filter.contains(b"hello"));
+/// let Poll::Ready(found) = query.as_mut().poll(&mut Context::from_waker(Waker::noop())) else {
+///     unreachable!()
+/// };
+/// assert_eq!(found, Ok(true));
+/// ```
+#[derive(Clone)]
+#[derive(Debug)]
+pub struct AsyncBloomFilter<S> {
+    config: FilterBuilder,
+    storage: S,
+}
+
+impl<S: AsyncStorage> AsyncBloomFilter<S> {
+    /// Builds a filter configured by `config` over the words of `storage`. Panics unless the
+    /// storage holds the words of the size of the filter.
+    pub fn new(mut config: FilterBuilder, storage: S) -> Self {
+        config.complete();
+        assert_eq!(storage.slots() as u64, config.size.div_ceil(64),
+                   "storage must hold the words of the filter!");
+        AsyncBloomFilter { config, storage }
+    }
+
+    /// Returns the configuration/builder of the filter.
+    pub fn config(&self) -> FilterBuilder {
+        self.config.clone()
+    }
+
+    /// Returns the storage of the bits.
+    pub fn storage(&self) -> &S {
+        &self.storage
+    }
+
+    /// Tests whether an element is present in the filter (subject to the false positive
+    /// probability), reading the words of its indices at once. Fails with the error of the
+    /// storage.
+    pub async fn contains(&self, element: &[u8]) -> Result<bool, S::Error> {
+        let indices: SmallVec<[u64; 16]> = Indices::new(element, &self.config).collect();
+        if indices.is_empty() {
+            return Ok(false);
+        }
+        let mut slots: SmallVec<[usize; 16]> = indices.iter()
+            .map(|&index| (index / 64) as usize)
+            .collect();
+        slots.sort_unstable();
+        slots.dedup();
+        let words = self.storage.get_words(&slots).await?;
+        Ok(indices.iter().all(|&index| {
+            let at = slots.binary_search(&((index / 64) as usize)).unwrap();
+            words[at] >> (index % 64) & 1 == 1
+        }))
+    }
+}
+
+#[test]
+fn async_bloom_filter_test() {
+    use core::pin::pin;
+    use core::sync::atomic::{AtomicUsize, Ordering};
+    use core::task::{Context, Waker};
+
+    use crate::Membership;
+
+    /// Words behind a "network": every read is pending once, in-flight reads are tracked.
+    struct Remote {
+        words: Vec<u64>,
+        in_flight: AtomicUsize,
+        max_in_flight: AtomicUsize,
+    }
+
+    impl AsyncStorage for Remote {
+        type Error = usize;
+
+        fn slots(&self) -> usize {
+            self.words.len()
+        }
+
+        async fn get_word(&self, slot: usize) -> Result<u64, usize> {
+            let in_flight = self.in_flight.fetch_add(1, Ordering::Relaxed) + 1;
+            self.max_in_flight.fetch_max(in_flight, Ordering::Relaxed);
+            let mut waited = false;
+            poll_fn(|cx| {
+                if waited {
+                    return Poll::Ready(());
+                }
+                waited = true;
+                cx.waker().wake_by_ref();
+                Poll::Pending
+            }).await;
+            self.in_flight.fetch_sub(1, Ordering::Relaxed);
+            self.words.get(slot).copied().ok_or(slot)
+        }
+    }
+
+    fn block_on<F: Future>(future: F) -> F::Output {
+        let mut future = pin!(future);
+        let mut cx = Context::from_waker(Waker::noop());
+        loop {
+            if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+                return output;
+            }
+        }
+    }
+
+    let mut builder = FilterBuilder::new(10_000, 0.01);
+    let mut bloom = builder.build_bloom_filter();
+    (0..1000u32).for_each(|i| bloom.add(&i.to_le_bytes()));
+    let words = bloom.bits_in_range(0..bloom.word_len());
+    let remote = Remote { words, in_flight: AtomicUsize::new(0),
+                          max_in_flight: AtomicUsize::new(0) };
+    let filter = builder.build_async_bloom_filter(remote);
+    // queries can be spawned on multi-threaded runtimes
+    fn assert_send<T: Send>(_: &T) {}
+    assert_send(&filter.contains(b"hello"));
+    for i in 0..2000u32 {
+        let element = i.to_le_bytes();
+        assert_eq!(block_on(filter.contains(&element)), Ok(bloom.contains(&element)));
+    }
+    // the words of a query are read concurrently
+    assert!(filter.storage().max_in_flight.load(Ordering::Relaxed) > 1);
+    assert_eq!(filter.storage().in_flight.load(Ordering::Relaxed), 0);
+
+    let short = Remote { words: alloc::vec![1, 2], in_flight: AtomicUsize::new(0),
+                         max_in_flight: AtomicUsize::new(0) };
+    assert_eq!(block_on(short.get_words(&[1, 0])), Ok(alloc::vec![2, 1]));
+    assert_eq!(block_on(short.get_words(&[0, 3])), Err(3));
+}
@@ -0,0 +1,447 @@
+use alloc::collections::VecDeque;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::convert::Infallible;
+#[cfg(feature = "std")]
+use std::hash::BuildHasher;
+#[cfg(feature = "std")]
+use std::sync::mpsc::{SendError, Sender};
+
+use crate::bloom::BloomFilter;
+use crate::builder::FilterBuilder;
+use crate::codec::{put_hashing, put_varint, Reader};
+use crate::compat::FormatFeatures;
+use crate::vec::{BloomBitVec, Storage, WriteTracking};
+
+const REPLICATION_MAGIC: &[u8; 4] = b"FBRP";
+
+/// Granularity of the write tracking of a [ReplicationPrimary], in bytes.
+const TRACKING_PAGE_SIZE: usize = 4096;
+
+/// Message streamed from a [ReplicationPrimary] to its [ReplicationStandby]s: a snapshot of the
+/// complete filter or a delta of the words changed by one update, numbered by the epoch of the
+/// primary and a sequence number.
+#[derive(Clone, Debug)]
+pub struct ReplicationMessage {
+    epoch: u64,
+    sequence: u64,
+    body: MessageBody,
+}
+
+#[derive(Clone, Debug)]
+enum MessageBody {
+    /// The complete filter as of the sequence number.
+    Snapshot { config: FilterBuilder, words: Vec<usize> },
+    /// The words changed by the update, as `(slot, new value)`.
+    Delta { words: Vec<(usize, usize)> },
+}
+
+impl ReplicationMessage {
+    /// Returns the epoch of the primary which sent the message, see
+    /// [ReplicationPrimary::with_epoch].
+    pub fn epoch(&self) -> u64 {
+        self.epoch
+    }
+
+    /// Returns the sequence number of the message.
+    pub fn sequence(&self) -> u64 {
+        self.sequence
+    }
+
+    /// Returns [true] for a snapshot of the complete filter, [false] for a delta.
+    pub fn is_snapshot(&self) -> bool {
+        matches!(self.body, MessageBody::Snapshot { .. })
+    }
+
+    /// Encodes the message for transports which move bytes, see
+    /// [ReplicationMessage::from_bytes].
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = REPLICATION_MAGIC.to_vec();
+        out.push(usize::BITS as u8);
+        out.push(if self.is_snapshot() { 0 } else { 1 });
+        out.extend_from_slice(&self.epoch.to_le_bytes());
+        out.extend_from_slice(&self.sequence.to_le_bytes());
+        match &self.body {
+            MessageBody::Snapshot { config, words } => {
+                put_hashing(&mut out, config, 0, FormatFeatures::default());
+                put_varint(&mut out, words.len() as u64);
+                for word in words {
+                    out.extend_from_slice(&(*word as u64).to_le_bytes());
+                }
+            }
+            MessageBody::Delta { words } => {
+                put_varint(&mut out, words.len() as u64);
+                for (slot, word) in words {
+                    put_varint(&mut out, *slot as u64);
+                    out.extend_from_slice(&(*word as u64).to_le_bytes());
+                }
+            }
+        }
+        out
+    }
+
+    /// Decodes a message written by [ReplicationMessage::to_bytes]. Returns `None` if `bytes` is
+    /// not a valid message or was written on a platform with a different word size.
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        let mut reader = Reader::new(bytes);
+        if reader.take(4)? != REPLICATION_MAGIC || reader.u8()? != usize::BITS as u8 {
+            return None;
+        }
+        let kind = reader.u8()?;
+        let epoch = reader.u64()?;
+        let sequence = reader.u64()?;
+        let body = match kind {
+            0 => {
+                let hashing = reader.hashing()?;
+                if !hashing.features.is_empty() {
+                    return None;
+                }
+                let slots = usize::try_from(reader.varint()?).ok()?;
+                let words = reader.take(slots.checked_mul(8)?)?
+                    .chunks_exact(8)
+                    .map(|word| usize::try_from(u64::from_le_bytes(word.try_into().unwrap())).ok())
+                    .collect::<Option<Vec<_>>>()?;
+                if slots == 0 {
+                    return None;
+                }
+                let mut config = FilterBuilder::from_size_and_hashes(
+                    slots as u64 * usize::BITS as u64, hashing.hashes);
+                hashing.apply(&mut config)?;
+                config.complete();
+                MessageBody::Snapshot { config, words }
+            }
+            1 => {
+                let len = usize::try_from(reader.varint()?).ok()?;
+                let mut words = Vec::with_capacity(len.min(bytes.len()));
+                for _ in 0..len {
+                    let slot = usize::try_from(reader.varint()?).ok()?;
+                    words.push((slot, usize::try_from(reader.u64()?).ok()?));
+                }
+                MessageBody::Delta { words }
+            }
+            _ => return None,
+        };
+        reader.is_empty().then_some(ReplicationMessage { epoch, sequence, body })
+    }
+}
+
+/// Carries [ReplicationMessage]s from a [ReplicationPrimary] to its standbys, e.g. over a
+/// socket or a message queue.
+pub trait ReplicationTransport {
+    type Error;
+
+    /// Sends `message` to the standbys.
+    fn send(&mut self, message: &ReplicationMessage) -> Result<(), Self::Error>;
+}
+
+/// Collects the messages, e.g. to hand them over in batches.
+impl ReplicationTransport for Vec<ReplicationMessage> {
+    type Error = Infallible;
+
+    fn send(&mut self, message: &ReplicationMessage) -> Result<(), Infallible> {
+        self.push(message.clone());
+        Ok(())
+    }
+}
+
+#[cfg(feature = "std")]
+impl ReplicationTransport for Sender<ReplicationMessage> {
+    type Error = SendError<ReplicationMessage>;
+
+    fn send(&mut self, message: &ReplicationMessage) -> Result<(), Self::Error> {
+        Sender::send(self, message.clone())
+    }
+}
+
+/// A mutable Bloom filter which streams its changes to warm standbys: every
+/// [ReplicationPrimary::publish] sends the words changed since the previous one as a
+/// sequence-numbered delta. A standby which joins late or misses a delta asks for
+/// [ReplicationPrimary::catch_up], which replays the deltas still kept in the backlog or falls
+/// back to a snapshot.
+///
+/// Every primary has an epoch, which tells a restarted primary, whose sequence numbers start
+/// over, from the one a standby followed: standbys reject the deltas of another epoch and catch
+/// up with a snapshot.
+///
+/// # Examples
+///
+/// ```rust
+/// use fastbloom_rs::{FilterBuilder, Membership, ReplicationPrimary, ReplicationStandby};
+///
+/// let mut primary = ReplicationPrimary::new(FilterBuilder::new(10_000, 0.01), 16);
+/// let mut standby = ReplicationStandby::new();
+/// for message in primary.catch_up(standby.epoch(), standby.next_sequence()) {
+///     assert!(standby.apply(&message));
+/// }
+///
+/// primary.filter_mut().add(b"hello");
+/// let mut sent = Vec::new();
+/// primary.publish(&mut sent).unwrap();
+/// assert!(sent.iter().all(|message| standby.apply(message)));
+/// assert_eq!(standby.filter().unwrap().contains(b"hello"), true);
+/// ```
+#[derive(Clone)]
+#[derive(Debug)]
+pub struct ReplicationPrimary {
+    filter: BloomFilter<WriteTracking<Vec<usize>>>,
+    epoch: u64,
+    sequence: u64,
+    backlog: VecDeque<ReplicationMessage>,
+    backlog_len: usize,
+}
+
+impl ReplicationPrimary {
+    /// Builds an empty filter from `config`, keeping the last `backlog` deltas for
+    /// [ReplicationPrimary::catch_up], in a random epoch.
+    #[cfg(feature = "std")]
+    pub fn new(config: FilterBuilder, backlog: usize) -> Self {
+        let epoch = std::collections::hash_map::RandomState::new().hash_one(std::process::id());
+        ReplicationPrimary::with_epoch(config, backlog, epoch)
+    }
+
+    /// Like [ReplicationPrimary::new] in the epoch `epoch`, which must differ from the epochs of
+    /// the previous primaries of the standbys, e.g. a counter persisted across restarts.
+    pub fn with_epoch(mut config: FilterBuilder, backlog: usize, epoch: u64) -> Self {
+        let filter = config.build_bloom_filter_in(((), TRACKING_PAGE_SIZE));
+        ReplicationPrimary {
+            filter,
+            epoch,
+            sequence: 0,
+            backlog: VecDeque::new(),
+            backlog_len: backlog,
+        }
+    }
+
+    /// Returns the epoch of the primary.
+    pub fn epoch(&self) -> u64 {
+        self.epoch
+    }
+
+    /// Returns the replicated filter.
+    pub fn filter(&self) -> &BloomFilter<WriteTracking<Vec<usize>>> {
+        &self.filter
+    }
+
+    /// Returns the replicated filter for updates, which are sent with the next
+    /// [ReplicationPrimary::publish].
+    pub fn filter_mut(&mut self) -> &mut BloomFilter<WriteTracking<Vec<usize>>> {
+        &mut self.filter
+    }
+
+    /// Returns the sequence number of the last published delta.
+    pub fn sequence(&self) -> u64 {
+        self.sequence
+    }
+
+    /// Sends the words changed since the previous call as the next delta and returns its
+    /// sequence number. Nothing is sent if nothing changed. A delta which fails to send is still
+    /// kept in the backlog, standbys notice the gap and catch up.
+    pub fn publish<T: ReplicationTransport>(&mut self, transport: &mut T) -> Result<u64, T::Error> {
+        let storage = self.filter.storage();
+        let words: Vec<(usize, usize)> =
+            storage.dirty_slots().map(|slot| (slot, storage.get(slot))).collect();
+        if words.is_empty() {
+            return Ok(self.sequence);
+        }
+        self.filter.bit_set.storage.take_stats();
+        self.sequence += 1;
+        let body = MessageBody::Delta { words };
+        let delta = ReplicationMessage { epoch: self.epoch, sequence: self.sequence, body };
+        let sent = transport.send(&delta);
+        if self.backlog_len > 0 {
+            if self.backlog.len() == self.backlog_len {
+                self.backlog.pop_front();
+            }
+            self.backlog.push_back(delta);
+        }
+        sent.map(|_| self.sequence)
+    }
+
+    /// Returns a snapshot of the filter as of the last published delta. Unpublished changes
+    /// are included, they are sent again with the next delta.
+    pub fn snapshot(&self) -> ReplicationMessage {
+        let storage = self.filter.storage();
+        ReplicationMessage {
+            epoch: self.epoch,
+            sequence: self.sequence,
+            body: MessageBody::Snapshot {
+                config: self.filter.config(),
+                words: (0..storage.slots()).map(|slot| storage.get(slot)).collect(),
+            },
+        }
+    }
+
+    /// Returns the messages a standby in `epoch` expecting `next_sequence` needs to be up to
+    /// date: the missing deltas if the backlog still has them, a snapshot otherwise or if the
+    /// standby followed another epoch.
+    pub fn catch_up(&self, epoch: u64, next_sequence: u64) -> Vec<ReplicationMessage> {
+        if epoch != self.epoch {
+            return vec![self.snapshot()];
+        }
+        if next_sequence > self.sequence {
+            return Vec::new();
+        }
+        match self.backlog.front() {
+            Some(first) if next_sequence > 0 && first.sequence() <= next_sequence => {
+                let skip = (next_sequence - first.sequence()) as usize;
+                self.backlog.iter().skip(skip).cloned().collect()
+            }
+            _ => vec![self.snapshot()],
+        }
+    }
+}
+
+/// Read-only replica of a [ReplicationPrimary], kept up to date by applying its messages in
+/// order.
+#[derive(Clone)]
+#[derive(Debug)]
+#[derive(Default)]
+pub struct ReplicationStandby {
+    filter: Option<BloomFilter>,
+    epoch: u64,
+    sequence: u64,
+}
+
+impl ReplicationStandby {
+    /// Creates a standby without state, which needs a snapshot first.
+    pub fn new() -> Self {
+        ReplicationStandby::default()
+    }
+
+    /// Returns the replica, `None` until the first snapshot was applied.
+    pub fn filter(&self) -> Option<&BloomFilter> {
+        self.filter.as_ref()
+    }
+
+    /// Returns the epoch of the primary of the last applied message.
+    pub fn epoch(&self) -> u64 {
+        self.epoch
+    }
+
+    /// Returns the sequence number of the last applied message.
+    pub fn sequence(&self) -> u64 {
+        self.sequence
+    }
+
+    /// Returns the sequence number to pass to [ReplicationPrimary::catch_up] with the epoch.
+    pub fn next_sequence(&self) -> u64 {
+        if self.filter.is_some() { self.sequence + 1 } else { 0 }
+    }
+
+    /// Applies `message`. Returns `false` if it can't be applied because earlier messages are
+    /// missing or it is a delta of another epoch, the standby should then catch up from
+    /// [ReplicationStandby::next_sequence]. Messages which were applied already are ignored, a
+    /// snapshot of another epoch replaces the replica.
+    pub fn apply(&mut self, message: &ReplicationMessage) -> bool {
+        let (epoch, sequence) = (message.epoch, message.sequence);
+        let current = self.filter.is_some() && epoch == self.epoch;
+        match &message.body {
+            MessageBody::Snapshot { config, words } => {
+                if !current || sequence > self.sequence {
+                    let bit_set = BloomBitVec::with_storage(words.clone());
+                    self.filter = Some(BloomFilter::from_bit_vec(config.clone(), bit_set));
+                    (self.epoch, self.sequence) = (epoch, sequence);
+                }
+                true
+            }
+            MessageBody::Delta { words } => {
+                if !current {
+                    return false;
+                }
+                if sequence <= self.sequence {
+                    return true;
+                }
+                if sequence != self.sequence + 1 {
+                    return false;
+                }
+                let filter = self.filter.as_mut().unwrap();
+                let storage = &mut filter.bit_set.storage;
+                if words.iter().any(|(slot, _)| *slot >= storage.len()) {
+                    return false;
+                }
+                for (slot, word) in words {
+                    storage[*slot] = *word;
+                }
+                self.sequence = sequence;
+                true
+            }
+        }
+    }
+}
+
+#[test]
+fn replication_test() {
+    use crate::Membership;
+
+    let mut primary = ReplicationPrimary::new(FilterBuilder::new(10_000, 0.01), 3);
+    let mut early = ReplicationStandby::new();
+    for message in primary.catch_up(early.epoch(), early.next_sequence()) {
+        assert!(early.apply(&message));
+    }
+
+    let mut sent = Vec::new();
+    for i in 0..4u32 {
+        primary.filter_mut().add(&i.to_le_bytes());
+        assert_eq!(primary.publish(&mut sent).unwrap(), i as u64 + 1);
+    }
+    assert_eq!(primary.publish(&mut sent).unwrap(), 4);
+    assert_eq!(sent.len(), 4);
+
+    // a lost delta is detected and recovered from the backlog.
+    assert!(early.apply(&sent[0]));
+    assert!(!early.apply(&sent[2]));
+    let missing = primary.catch_up(early.epoch(), early.next_sequence());
+    assert_eq!(missing.iter().map(ReplicationMessage::sequence).collect::<Vec<_>>(), vec![2, 3, 4]);
+    assert!(missing.iter().all(|message| early.apply(message)));
+    assert!(sent.iter().all(|message| early.apply(message)));
+
+    // a late standby, whose deltas left the backlog, starts from a snapshot.
+    let mut late = ReplicationStandby::new();
+    let missing = primary.catch_up(primary.epoch(), 1);
+    assert!(missing.len() == 1 && missing[0].is_snapshot() && missing[0].sequence() == 4);
+    let bytes = missing[0].to_bytes();
+    assert!(late.apply(&ReplicationMessage::from_bytes(&bytes).unwrap()));
+    assert!(ReplicationMessage::from_bytes(&bytes[..bytes.len() - 1]).is_none());
+
+    for standby in [early, late] {
+        assert_eq!(standby.sequence(), 4);
+        let filter = standby.filter().unwrap();
+        assert_eq!(filter.storage(), primary.filter().storage().inner());
+        assert!((0..4u32).all(|i| filter.contains(&i.to_le_bytes())));
+        assert!(primary.catch_up(standby.epoch(), standby.next_sequence()).is_empty());
+    }
+}
+
+#[test]
+fn replication_restart_test() {
+    use crate::Membership;
+
+    let config = FilterBuilder::new(10_000, 0.01);
+    let mut primary = ReplicationPrimary::with_epoch(config.clone(), 8, 1);
+    let mut standby = ReplicationStandby::new();
+    let mut sent = primary.catch_up(standby.epoch(), standby.next_sequence());
+    for i in 0..3u32 {
+        primary.filter_mut().add(&i.to_le_bytes());
+        primary.publish(&mut sent).unwrap();
+    }
+    assert!(sent.iter().all(|message| standby.apply(message)));
+    assert_eq!((standby.epoch(), standby.sequence()), (1, 3));
+
+    // the restarted primary numbers its deltas from 1 again, in a new epoch
+    let mut primary = ReplicationPrimary::with_epoch(config, 8, 2);
+    primary.filter_mut().add(b"restarted");
+    let mut sent = Vec::new();
+    primary.publish(&mut sent).unwrap();
+    assert!(!standby.apply(&sent[0]));
+    let missing = primary.catch_up(standby.epoch(), standby.next_sequence());
+    assert!(missing.len() == 1 && missing[0].is_snapshot());
+    assert!(standby.apply(&missing[0]));
+    assert_eq!((standby.epoch(), standby.sequence()), (2, 1));
+    let filter = standby.filter().unwrap();
+    assert!(filter.contains(b"restarted") && !filter.contains(&0u32.to_le_bytes()));
+    let bytes = missing[0].to_bytes();
+    assert_eq!(ReplicationMessage::from_bytes(&bytes).unwrap().epoch(), 2);
+
+    let first = ReplicationPrimary::new(FilterBuilder::new(100, 0.01), 0);
+    assert_ne!(first.epoch(), ReplicationPrimary::new(FilterBuilder::new(100, 0.01), 0).epoch());
+}
@@ -0,0 +1,67 @@
+use roaring::RoaringTreemap;
+
+use crate::bloom::BloomFilter;
+use crate::builder::FilterBuilder;
+use crate::vec::{BloomBitVec, Storage};
+
+impl<S: Storage> BloomFilter<S> {
+    /// Returns the indices of the set bits as a [RoaringTreemap], so roaring's compression and
+    /// set algebra can be used on the bit array.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use fastbloom_rs::{FilterBuilder, Membership};
+    ///
+    /// let mut bloom = FilterBuilder::new(100_000, 0.01).build_bloom_filter();
+    /// bloom.add(b"hello");
+    /// let bitmap = bloom.to_roaring();
+    /// assert_eq!(bitmap.len(), bloom.get_hash_indices(b"hello").len() as u64);
+    /// ```
+    pub fn to_roaring(&self) -> RoaringTreemap {
+        RoaringTreemap::from_sorted_iter(self.bit_set.ones()).unwrap()
+    }
+}
+
+impl BloomFilter {
+    /// Build a Bloom filter of `size` bits and `hashes` hash functions with the bits set at the
+    /// indices contained in `bitmap`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use fastbloom_rs::{BloomFilter, FilterBuilder, Hashes, Membership};
+    ///
+    /// let mut bloom = FilterBuilder::new(100_000, 0.01).build_bloom_filter();
+    /// bloom.add(b"hello");
+    /// let copy = BloomFilter::from_roaring(&bloom.to_roaring(), bloom.config().size, bloom.hashes());
+    /// assert_eq!(copy.contains(b"hello"), true);
+    /// ```
+    pub fn from_roaring(bitmap: &RoaringTreemap, size: u64, hashes: u32) -> Self {
+        assert_eq!(size % usize::BITS as u64, 0, "size must be a multiple of {}!", usize::BITS);
+        if let Some(max) = bitmap.max() {
+            assert!(max < size, "bit index {} out of range {}!", max, size);
+        }
+        let mut config = FilterBuilder::from_size_and_hashes(size, hashes);
+        config.complete();
+        let mut bit_vec = BloomBitVec::new((size / usize::BITS as u64) as usize);
+        for index in bitmap.iter() {
+            bit_vec.set(index as usize);
+        }
+        BloomFilter::from_bit_vec(config, bit_vec)
+    }
+}
+
+#[test]
+fn roaring_round_trip_test() {
+    use crate::Membership;
+
+    let mut bloom = FilterBuilder::new(10_000, 0.01).build_bloom_filter();
+    for i in 0..1000u64 {
+        bloom.add(&i.to_le_bytes());
+    }
+    let bitmap = bloom.to_roaring();
+    assert!(bitmap.iter().all(|i| bloom.contains_hash_indices(&vec![i])));
+    let copy = BloomFilter::from_roaring(&bitmap, bloom.config.size, bloom.config.hashes);
+    assert_eq!(copy.get_u8_array(), bloom.get_u8_array());
+}
@@ -0,0 +1,234 @@
+use alloc::vec;
+use alloc::vec::Vec;
+
+use xxhash_rust::xxh64::xxh64;
+
+use crate::builder::FilterBuilder;
+use crate::codec::{put_varint, Reader};
+#[cfg(not(feature = "std"))]
+use crate::float::Float;
+use crate::Membership;
+
+/// Salts of the eight words of a block, from the Parquet specification.
+const SALT: [u32; 8] = [
+    0x47b6137b, 0x44974d91, 0x8824ad5b, 0xa2b7289d, 0x705495c7, 0x2df1424b, 0x9efc4947, 0x5c6bfb31,
+];
+/// Bytes of a block, eight 32-bit words.
+const BLOCK_BYTES: usize = 32;
+/// Largest filter the Parquet writers produce, 128 MB.
+const MAX_BYTES: usize = 128 << 20;
+
+type Block = [u32; 8];
+
+/// Split-block Bloom filter (SBBF) as specified for the column chunks of Apache Parquet: blocks
+/// of eight 32-bit words, an element sets one bit in each word of the block chosen by its hash.
+/// Elements are hashed with xxHash64 (seed 0) of their plain encoding, e.g. the little-endian
+/// bytes of an `INT64` or the bytes of a `BYTE_ARRAY` without length, so filters written by
+/// other Parquet implementations answer the queries of this one and vice versa. The seed and
+/// hasher of the [FilterBuilder] are not used.
+///
+/// [SplitBlockBloomFilter::to_parquet_bytes] returns the filter as stored at the
+/// `bloom_filter_offset` of a column chunk, the Thrift `BloomFilterHeader` followed by the
+/// bitset, which [SplitBlockBloomFilter::from_parquet_bytes] reads.
+///
+/// # Examples
+///
+/// ```rust
+/// use fastbloom_rs::{FilterBuilder, Membership, SplitBlockBloomFilter};
+///
+/// let mut sbbf = SplitBlockBloomFilter::new(FilterBuilder::new(10_000, 0.01));
+/// sbbf.add(b"hello");
+/// sbbf.add(&42i64.to_le_bytes());
+///
+/// let restored = SplitBlockBloomFilter::from_parquet_bytes(&sbbf.to_parquet_bytes()).unwrap();
+/// assert_eq!(restored.contains(b"hello"), true);
+/// assert_eq!(restored.contains(&42i64.to_le_bytes()), true);
+/// assert_eq!(restored.contains(b"world"), false);
+/// ```
+#[derive(Clone)]
+#[derive(Debug)]
+pub struct SplitBlockBloomFilter {
+    blocks: Vec<Block>,
+}
+
+impl SplitBlockBloomFilter {
+    /// Builds an empty filter for [FilterBuilder::capacity] distinct values at
+    /// `config.false_positive_probability`, sized like the Parquet writers do: rounded up to a
+    /// power of two between 32 bytes and 128 MB.
+    pub fn new(config: FilterBuilder) -> Self {
+        let p = config.false_positive_probability;
+        assert!(p > 0.0 && p < 1.0, "False positive probability must be in (0, 1)!");
+        // p^(1/8) is the false positive probability of each of the eight words
+        let bits = -8.0 * config.capacity() as f64 / (1.0 - (p.ln() / 8.0).exp()).ln();
+        let bytes = ((bits / 8.0) as usize).clamp(BLOCK_BYTES, MAX_BYTES).next_power_of_two();
+        SplitBlockBloomFilter::with_num_bytes(bytes)
+    }
+
+    /// Builds an empty filter of `bytes` bytes, rounded up to whole blocks of 32 bytes.
+    pub fn with_num_bytes(bytes: usize) -> Self {
+        let blocks = bytes.div_ceil(BLOCK_BYTES).max(1);
+        SplitBlockBloomFilter { blocks: vec![[0; 8]; blocks] }
+    }
+
+    /// Returns the size of the bitset in bytes.
+    pub fn num_bytes(&self) -> usize {
+        self.blocks.len() * BLOCK_BYTES
+    }
+
+    /// Returns the hash of an element as defined by Parquet, xxHash64 with seed 0.
+    #[inline]
+    pub fn hash(element: &[u8]) -> u64 {
+        xxh64(element, 0)
+    }
+
+    /// Adds the element with the Parquet hash `hash`, see [SplitBlockBloomFilter::hash].
+    pub fn add_hash(&mut self, hash: u64) {
+        let index = self.block_index(hash);
+        let mask = mask(hash as u32);
+        for (word, bit) in self.blocks[index].iter_mut().zip(mask) {
+            *word |= bit;
+        }
+    }
+
+    /// Tests whether the element with the Parquet hash `hash` is present.
+    #[inline]
+    pub fn contains_hash(&self, hash: u64) -> bool {
+        let block = &self.blocks[self.block_index(hash)];
+        block.iter().zip(mask(hash as u32)).all(|(word, bit)| word & bit != 0)
+    }
+
+    #[inline]
+    fn block_index(&self, hash: u64) -> usize {
+        (((hash >> 32) * self.blocks.len() as u64) >> 32) as usize
+    }
+
+    /// Encodes the filter as Parquet stores it: the `BloomFilterHeader` (Thrift compact protocol)
+    /// with the size and the `BLOCK`, `XXHASH` and `UNCOMPRESSED` variants, then the bitset as
+    /// little-endian 32-bit words.
+    pub fn to_parquet_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(self.num_bytes() + 16);
+        // field 1, i32 numBytes (zigzag)
+        out.push(0x15);
+        put_varint(&mut out, (self.num_bytes() as u64) << 1);
+        // fields 2 to 4: a union whose field 1 is an empty struct
+        for _ in 0..3 {
+            out.extend_from_slice(&[0x1c, 0x1c, 0x00, 0x00]);
+        }
+        out.push(0x00);
+        for word in self.blocks.iter().flatten() {
+            out.extend_from_slice(&word.to_le_bytes());
+        }
+        out
+    }
+
+    /// Decodes a filter written by [SplitBlockBloomFilter::to_parquet_bytes] or a Parquet writer,
+    /// e.g. read from the `bloom_filter_offset` of a column chunk. Returns `None` if `bytes`
+    /// is not such a filter or uses another algorithm, hash or compression; bytes after the
+    /// bitset are ignored, as the `bloom_filter_length` of older files is unreliable.
+    pub fn from_parquet_bytes(bytes: &[u8]) -> Option<Self> {
+        let mut reader = Reader::new(bytes);
+        let mut num_bytes = None;
+        let mut field = 0;
+        loop {
+            let header = reader.u8()?;
+            if header == 0 {
+                break;
+            }
+            field = match header >> 4 {
+                0 => return None,
+                delta => field + delta as i16,
+            };
+            match (field, header & 0x0f) {
+                (1, 5) => {
+                    let zigzag = reader.varint()?;
+                    num_bytes = Some(((zigzag >> 1) as i64 ^ -((zigzag & 1) as i64)) as usize);
+                }
+                // algorithm, hash and compression: only the first variant of each is defined
+                (2..=4, 12) => {
+                    if reader.take(3)? != [0x1c, 0x00, 0x00] {
+                        return None;
+                    }
+                }
+                _ => return None,
+            }
+        }
+        let num_bytes = num_bytes?;
+        if num_bytes == 0 || num_bytes % BLOCK_BYTES != 0 || num_bytes > MAX_BYTES {
+            return None;
+        }
+        let bitset = reader.take(num_bytes)?;
+        let blocks = bitset.chunks_exact(BLOCK_BYTES).map(|block| {
+            let mut words = [0; 8];
+            for (word, bytes) in words.iter_mut().zip(block.chunks_exact(4)) {
+                *word = u32::from_le_bytes(bytes.try_into().unwrap());
+            }
+            words
+        }).collect();
+        Some(SplitBlockBloomFilter { blocks })
+    }
+}
+
+/// Returns the bits an element with the low hash bits `key` sets in the words of its block.
+#[inline]
+fn mask(key: u32) -> Block {
+    let mut mask = [0; 8];
+    for (bit, salt) in mask.iter_mut().zip(SALT) {
+        *bit = 1 << (key.wrapping_mul(salt) >> 27);
+    }
+    mask
+}
+
+impl Membership for SplitBlockBloomFilter {
+    fn add(&mut self, element: &[u8]) {
+        self.add_hash(SplitBlockBloomFilter::hash(element));
+    }
+
+    fn contains(&self, element: &[u8]) -> bool {
+        self.contains_hash(SplitBlockBloomFilter::hash(element))
+    }
+
+    /// Get the Parquet hash of the element.
+    fn get_hash_indices(&self, element: &[u8]) -> Vec<u64> {
+        vec![SplitBlockBloomFilter::hash(element)]
+    }
+
+    /// Tests whether the hash from `get_hash_indices` is present.
+    fn contains_hash_indices(&self, indices: &Vec<u64>) -> bool {
+        self.contains_hash(indices[0])
+    }
+
+    fn clear(&mut self) {
+        self.blocks.fill([0; 8]);
+    }
+}
+
+#[test]
+fn split_block_bloom_filter_test() {
+    let mut sbbf = SplitBlockBloomFilter::new(FilterBuilder::new(10_000, 0.01));
+    assert!(sbbf.num_bytes().is_power_of_two());
+    for i in 0..10_000i64 {
+        sbbf.add(&i.to_le_bytes());
+    }
+    assert!((0..10_000i64).all(|i| sbbf.contains(&i.to_le_bytes())));
+    let false_positives = (10_000..110_000i64).filter(|i| sbbf.contains(&i.to_le_bytes()))
+        .count();
+    assert!(false_positives < 1300, "{false_positives} false positives");
+
+    let bytes = sbbf.to_parquet_bytes();
+    let header = bytes.len() - sbbf.num_bytes();
+    assert!(bytes[0] == 0x15 && header <= 20, "{header}");
+    let restored = SplitBlockBloomFilter::from_parquet_bytes(&bytes).unwrap();
+    assert_eq!(restored.blocks, sbbf.blocks);
+    assert!(SplitBlockBloomFilter::from_parquet_bytes(&bytes[..bytes.len() - 1]).is_none());
+    let mut other_hash = bytes.clone();
+    // the variant of the hash union
+    other_hash[header - 8] = 0x2c;
+    assert!(SplitBlockBloomFilter::from_parquet_bytes(&other_hash).is_none());
+
+    // every word of the block gets exactly one bit
+    let mut single = SplitBlockBloomFilter::with_num_bytes(32);
+    single.add(b"hello");
+    assert!(single.blocks[0].iter().all(|word| word.count_ones() == 1));
+    single.clear();
+    assert!(!single.contains(b"hello"));
+}
@@ -0,0 +1,288 @@
+//! `Serialize`/`Deserialize` of the filters with the `serde` feature. Bit vectors are written as
+//! u64 words with bit `i` standing for index `i`, and counter vectors with their counter width
+//! `W` as u64 words of `64 / W` counters starting at the most significant bits, so the form
+//! doesn't depend on the pointer width or endianness of the writer.
+
+use alloc::format;
+use alloc::vec;
+use alloc::vec::Vec;
+
+use serde::de::Error;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::bloom::{BloomFilter, CountingBloomFilter};
+use crate::builder::FilterBuilder;
+use crate::hasher::HasherRef;
+use crate::index::IndexScheme;
+use crate::vec::{le_word, BloomBitVec, CountingVec, Storage};
+
+#[derive(Serialize, Deserialize)]
+struct BitVecRepr {
+    nbits: u64,
+    words: Vec<u64>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct CountingVecRepr {
+    counters: u64,
+    /// Bits per counter, `4` for vectors written before it was recorded.
+    #[serde(default = "default_counter_width")]
+    width: u32,
+    words: Vec<u64>,
+}
+
+fn default_counter_width() -> u32 {
+    4
+}
+
+#[derive(Serialize, Deserialize)]
+struct ConfigRepr {
+    expected_elements: u64,
+    false_positive_probability: f64,
+    headroom_factor: f64,
+    size: u64,
+    hashes: u32,
+    seed: u64,
+    index_scheme: u8,
+    hasher: u32,
+    expected_key_len: u64,
+    enable_repeat_insert: bool,
+    enable_conservative_update: bool,
+    enable_constant_time: bool,
+    #[serde(default)]
+    canaries: u32,
+    #[serde(default)]
+    partitioned: bool,
+    #[serde(default)]
+    hash_seeds: Option<Vec<u64>>,
+}
+
+impl ConfigRepr {
+    fn new(config: &FilterBuilder) -> Self {
+        ConfigRepr {
+            expected_elements: config.expected_elements,
+            false_positive_probability: config.false_positive_probability,
+            headroom_factor: config.headroom_factor,
+            size: config.size,
+            hashes: config.hashes,
+            seed: config.seed,
+            index_scheme: config.index_scheme.id(),
+            hasher: config.hasher_id(),
+            expected_key_len: config.expected_key_len as u64,
+            enable_repeat_insert: config.enable_repeat_insert,
+            enable_conservative_update: config.enable_conservative_update,
+            enable_constant_time: config.enable_constant_time,
+            canaries: config.canaries,
+            partitioned: config.partitioned,
+            hash_seeds: config.custom_hash_seeds().map(<[u64]>::to_vec),
+        }
+    }
+
+    fn config<E: Error>(self) -> Result<FilterBuilder, E> {
+        let mut config = FilterBuilder::from_size_and_hashes(self.size, self.hashes);
+        config.expected_elements = self.expected_elements;
+        config.false_positive_probability = self.false_positive_probability;
+        config.headroom_factor = self.headroom_factor;
+        config.seed = self.seed;
+        config.index_scheme = IndexScheme::from_id(self.index_scheme)
+            .ok_or_else(|| E::custom(format!("unknown index scheme {}", self.index_scheme)))?;
+        config.hasher = HasherRef::resolve(self.hasher)
+            .ok_or_else(|| E::custom(format!("hasher {} is not registered", self.hasher)))?;
+        config.expected_key_len = usize::try_from(self.expected_key_len).map_err(E::custom)?;
+        config.enable_repeat_insert = self.enable_repeat_insert;
+        config.enable_conservative_update = self.enable_conservative_update;
+        config.enable_constant_time = self.enable_constant_time;
+        if self.size != 0 && self.canaries as u64 >= self.size {
+            return Err(E::custom("canaries must be less than the size"));
+        }
+        config.canaries = self.canaries;
+        if self.partitioned && self.size.saturating_sub(self.canaries as u64) < self.hashes as u64 {
+            return Err(E::custom("a partitioned filter needs a bit per hash"));
+        }
+        config.partitioned = self.partitioned;
+        if let Some(seeds) = self.hash_seeds {
+            if seeds.is_empty() || seeds.len() > self.hashes as usize
+                || config.index_scheme != IndexScheme::Extended {
+                return Err(E::custom("hash seeds must be one per hash of the Extended scheme"));
+            }
+            config.hash_seeds = Some(seeds.into());
+        }
+        config.done = true;
+        Ok(config)
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct FilterRepr<V> {
+    config: ConfigRepr,
+    data: V,
+}
+
+impl<S: Storage> Serialize for BloomBitVec<S> {
+    fn serialize<Se: Serializer>(&self, serializer: Se) -> Result<Se::Ok, Se::Error> {
+        let slots = self.storage.slots();
+        let mut words = vec![0u64; (slots * usize::BITS as usize).div_ceil(64)];
+        for slot in 0..slots {
+            let bit = slot * usize::BITS as usize;
+            words[bit / 64] |= (self.word(slot) as u64) << (bit % 64);
+        }
+        BitVecRepr { nbits: self.nbits, words }.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for BloomBitVec {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let repr = BitVecRepr::deserialize(deserializer)?;
+        if repr.words.len() as u64 != repr.nbits.div_ceil(64) {
+            return Err(D::Error::invalid_length(repr.words.len(), &"nbits / 64 words"));
+        }
+        let slots = usize::try_from(repr.nbits.div_ceil(usize::BITS as u64))
+            .map_err(D::Error::custom)?;
+        let storage = (0..slots).map(|slot| {
+            let bit = slot * usize::BITS as usize;
+            le_word((repr.words[bit / 64] >> (bit % 64)) as usize)
+        }).collect();
+        Ok(BloomBitVec { storage, nbits: repr.nbits })
+    }
+}
+
+impl<S: Storage, const W: usize> Serialize for CountingVec<S, W> {
+    fn serialize<Se: Serializer>(&self, serializer: Se) -> Result<Se::Ok, Se::Error> {
+        let mut counters = self.iter();
+        let words = (0..self.counters().div_ceil(64 / W)).map(|_| {
+            counters.by_ref().take(64 / W).fold(0u64, |word, counter| {
+                word.checked_shl(W as u32).unwrap_or(0) | counter as u64
+            })
+        }).collect();
+        let (counters, width) = (self.counters() as u64, W as u32);
+        CountingVecRepr { counters, width, words }.serialize(serializer)
+    }
+}
+
+impl<'de, const W: usize> Deserialize<'de> for CountingVec<Vec<usize>, W> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let repr = CountingVecRepr::deserialize(deserializer)?;
+        if repr.width != W as u32 {
+            return Err(D::Error::custom(format!("counters of {} bits, not {W}", repr.width)));
+        }
+        if repr.words.len() as u64 * (64 / W) as u64 != repr.counters {
+            return Err(D::Error::invalid_length(repr.words.len(), &"counters / (64 / W) words"));
+        }
+        let slots = usize::try_from(repr.counters).map_err(D::Error::custom)?
+            .div_ceil(CountingVec::<Vec<usize>, W>::PER_SLOT);
+        let mut counting_vec = CountingVec::<_, W>::new(vec![0; slots]);
+        let max = u64::MAX >> (64 - W);
+        let counters = repr.words.iter().flat_map(|word| {
+            (0..64 / W).rev().map(move |c| ((word >> (c * W)) & max) as usize)
+        });
+        for (index, counter) in counters.enumerate().filter(|(_, counter)| *counter != 0) {
+            counting_vec.set(index, counter);
+        }
+        Ok(counting_vec)
+    }
+}
+
+impl<S: Storage> Serialize for BloomFilter<S> {
+    fn serialize<Se: Serializer>(&self, serializer: Se) -> Result<Se::Ok, Se::Error> {
+        FilterRepr { config: ConfigRepr::new(&self.config), data: &self.bit_set }
+            .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for BloomFilter {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let repr = FilterRepr::<BloomBitVec>::deserialize(deserializer)?;
+        let config = repr.config.config()?;
+        if repr.data.nbits < config.size {
+            return Err(D::Error::custom("bit vector is shorter than the filter size"));
+        }
+        Ok(BloomFilter::from_bit_vec(config, repr.data))
+    }
+}
+
+impl<S: Storage, const W: usize> Serialize for CountingBloomFilter<S, W> {
+    fn serialize<Se: Serializer>(&self, serializer: Se) -> Result<Se::Ok, Se::Error> {
+        FilterRepr { config: ConfigRepr::new(&self.config), data: &self.counting_vec }
+            .serialize(serializer)
+    }
+}
+
+impl<'de, const W: usize> Deserialize<'de> for CountingBloomFilter<Vec<usize>, W> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let repr = FilterRepr::<CountingVec<Vec<usize>, W>>::deserialize(deserializer)?;
+        let config = repr.config.config()?;
+        if repr.data.counters() as u64 != config.size {
+            return Err(D::Error::custom("counter vector doesn't match the filter size"));
+        }
+        Ok(CountingBloomFilter::from_counting_vec(config, repr.data))
+    }
+}
+
+#[test]
+fn serde_round_trip_test() {
+    use crate::Membership;
+
+    let mut builder = FilterBuilder::new(1000, 0.01);
+    builder.seed(7);
+    builder.index_scheme(IndexScheme::Extended);
+    let mut bloom = builder.build_bloom_filter();
+    let mut counting: CountingBloomFilter = builder.build_counting_bloom_filter(());
+    for i in 0..100u32 {
+        bloom.add(&i.to_le_bytes());
+        counting.add(&i.to_le_bytes());
+    }
+    counting.add(&0u32.to_le_bytes());
+
+    let json = serde_json::to_string(&bloom).unwrap();
+    let restored: BloomFilter = serde_json::from_str(&json).unwrap();
+    assert_eq!(restored.config().seed, 7);
+    assert!(restored.config().is_compatible_to(&bloom.config()));
+    assert_eq!(restored.get_u64_array(), bloom.get_u64_array());
+
+    let json = serde_json::to_string(&counting).unwrap();
+    let restored: CountingBloomFilter = serde_json::from_str(&json).unwrap();
+    assert_eq!(restored.estimate_count(&0u32.to_le_bytes()), 2);
+    assert!((0..100u32).all(|i| restored.estimate_count(&i.to_le_bytes()) > 0));
+
+    builder.partitioned(true);
+    let mut partitioned = builder.build_bloom_filter();
+    (0..100u32).for_each(|i| partitioned.add(&i.to_le_bytes()));
+    let restored: BloomFilter =
+        serde_json::from_str(&serde_json::to_string(&partitioned).unwrap()).unwrap();
+    assert!(restored.config().partitioned);
+    assert!((0..100u32).all(|i| restored.contains(&i.to_le_bytes())));
+
+    let mut seeded = FilterBuilder::new(1000, 0.01);
+    seeded.hash_seeds(&[3, 5, 7]);
+    let seeded = seeded.build_bloom_filter();
+    let json = serde_json::to_string(&seeded).unwrap();
+    let restored: BloomFilter = serde_json::from_str(&json).unwrap();
+    assert_eq!(restored.config().hash_seeds_in_use(), Some(vec![3, 5, 7]));
+    assert!(serde_json::from_str::<BloomFilter>(&json.replace("[3,5,7]", "[]")).is_err());
+
+    // the bit vector form is the same on 32-bit targets
+    let bits = BloomBitVec { storage: vec![le_word(0b1011); 2], nbits: 128 };
+    let json = serde_json::to_string(&bits).unwrap();
+    assert_eq!(json, r#"{"nbits":128,"words":[11,11]}"#);
+    assert!(serde_json::from_str::<BloomBitVec>(r#"{"nbits":128,"words":[11]}"#).is_err());
+    assert!(serde_json::from_str::<BloomFilter>(&json.replace("nbits", "x")).is_err());
+
+    // so is the counter vector form, for any counter width
+    let mut counters = CountingVec::<_, 4>::new(vec![0usize; 64 / usize::BITS as usize]);
+    counters.set(0, 1);
+    counters.set(15, 2);
+    let json = serde_json::to_string(&counters).unwrap();
+    assert_eq!(json, format!(r#"{{"counters":16,"width":4,"words":[{}]}}"#, 1u64 << 60 | 2));
+    let restored: CountingVec<Vec<usize>> =
+        serde_json::from_str(r#"{"counters":16,"words":[1152921504606846978]}"#).unwrap();
+    assert_eq!((restored.get(0), restored.get(15)), (1, 2));
+    assert!(serde_json::from_str::<CountingVec<Vec<usize>, 8>>(&json).is_err());
+
+    let mut wide: CountingBloomFilter<Vec<usize>, 16> =
+        builder.build_counting_bloom_filter_with_width(());
+    wide.add(b"hello");
+    wide.add(b"hello");
+    let restored: CountingBloomFilter<Vec<usize>, 16> =
+        serde_json::from_str(&serde_json::to_string(&wide).unwrap()).unwrap();
+    assert_eq!(restored.estimate_count(b"hello"), 2);
+}
@@ -0,0 +1,174 @@
+use alloc::vec::Vec;
+
+use crate::atomic::AtomicBloomFilter;
+use crate::blocked::ShardFill;
+use crate::bloom::BloomFilter;
+use crate::builder::FilterBuilder;
+use crate::index::{hash_pair, mix64};
+use crate::params::expected_fpp;
+
+/// Bloom filter for write-heavy workloads on many threads: it keeps `N` independent
+/// [AtomicBloomFilter]s and every element goes to the shard selected by its hash, so threads
+/// adding different elements mostly write to different allocations instead of contending for
+/// the cache lines of one bit vector.
+///
+/// Each shard is sized for `1 / N` of the elements of the [FilterBuilder] at its false positive
+/// probability, so the shards take about the memory of one filter for all elements. They share
+/// the size and hashing, which makes their bits line up:
+/// [ShardedBloomFilter::merge_into_single] ORs them into one [BloomFilter] of a shard's size
+/// equal to the one an insert of all elements would have built, with the higher false positive
+/// probability of all elements in fewer bits.
+///
+/// # Examples
+///
+/// ```rust
+/// use std::thread;
+///
+/// use fastbloom_rs::FilterBuilder;
+///
+/// let sharded = FilterBuilder::new(100_000, 0.01).build_sharded_bloom_filter(4);
+/// thread::scope(|scope| {
+///     for t in 0..4u32 {
+///         let sharded = &sharded;
+///         scope.spawn(move || {
+///             (0..1000u32).for_each(|i| sharded.add(&(t * 1000 + i).to_le_bytes()))
+///         });
+///     }
+/// });
+/// assert!(sharded.contains(&3999u32.to_le_bytes()));
+///
+/// let bloom = sharded.merge_into_single();
+/// assert!((0..4000u32).all(|i| bloom.contains(&i.to_le_bytes())));
+/// ```
+#[derive(Debug)]
+pub struct ShardedBloomFilter {
+    config: FilterBuilder,
+    shards: Vec<AtomicBloomFilter>,
+}
+
+impl ShardedBloomFilter {
+    /// Builds an empty filter of `shards` shards, each configured by `config` for
+    /// `1 / shards` of its elements, or of its size if that is set.
+    pub fn new(mut config: FilterBuilder, shards: usize) -> Self {
+        assert!(shards > 0, "ShardedBloomFilter needs a shard!");
+        config.expected_elements = config.expected_elements.div_ceil(shards as u64);
+        if config.done && config.size != 0 {
+            config.size = config.size.div_ceil(shards as u64);
+        } else {
+            config.size = 0;
+            config.hashes = 0;
+            config.done = false;
+        }
+        config.complete();
+        ShardedBloomFilter {
+            shards: (0..shards).map(|_| AtomicBloomFilter::new(config.clone())).collect(),
+            config,
+        }
+    }
+
+    /// Returns the configuration/builder of a shard.
+    pub fn config(&self) -> FilterBuilder {
+        self.config.clone()
+    }
+
+    /// Returns the number of shards.
+    pub fn shards(&self) -> usize {
+        self.shards.len()
+    }
+
+    #[inline]
+    fn shard(&self, element: &[u8]) -> &AtomicBloomFilter {
+        let (hash1, hash2) = hash_pair(element, &self.config);
+        let hash = mix64(hash2 ^ hash1.rotate_left(17));
+        &self.shards[((hash as u128 * self.shards.len() as u128) >> 64) as usize]
+    }
+
+    /// Adds the passed value to its shard.
+    pub fn add(&self, element: &[u8]) {
+        self.shard(element).add(element);
+    }
+
+    /// Tests whether an element is present in its shard (subject to the specified false
+    /// positive rate).
+    #[inline]
+    pub fn contains(&self, element: &[u8]) -> bool {
+        self.shard(element).contains(element)
+    }
+
+    /// Reports the fill and estimated cardinality of each shard, like
+    /// [BlockedBloomFilter::shard_fill_report](crate::BlockedBloomFilter::shard_fill_report).
+    /// Shards take the elements by their hash, so a shard much fuller than the others points
+    /// to keys the hash function doesn't spread evenly, e.g. a custom [crate::FilterHasher]
+    /// which ignores part of the key.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use fastbloom_rs::FilterBuilder;
+    ///
+    /// let sharded = FilterBuilder::new(10_000, 0.01).build_sharded_bloom_filter(4);
+    /// (0..10_000u32).for_each(|i| sharded.add(&i.to_le_bytes()));
+    /// let report = sharded.shard_fill_report();
+    /// assert_eq!(report[3].blocks, 3..4);
+    /// let total: f64 = report.iter().map(|shard| shard.estimated_elements).sum();
+    /// assert!((total - 10_000.0).abs() < 500.0);
+    /// ```
+    pub fn shard_fill_report(&self) -> Vec<ShardFill> {
+        self.shards.iter().zip(0..).map(|(shard, i)| ShardFill {
+            blocks: i..i + 1,
+            fill_ratio: shard.fill_ratio(),
+            estimated_elements: shard.estimate_len(),
+        }).collect()
+    }
+
+    /// ORs the shards into one [BloomFilter] of a shard's size, e.g. to serialize it once all
+    /// threads are done. Its configuration expects the elements of all shards.
+    pub fn merge_into_single(self) -> BloomFilter {
+        let elements = self.config.expected_elements * self.shards.len() as u64;
+        let mut shards = self.shards.into_iter().map(AtomicBloomFilter::into_inner);
+        let mut bloom = shards.next().unwrap();
+        for shard in shards {
+            bloom.union(&shard);
+        }
+        let config = &mut bloom.config;
+        config.expected_elements = elements;
+        config.false_positive_probability = expected_fpp(config.size, elements, config.hashes);
+        bloom
+    }
+}
+
+#[test]
+fn sharded_bloom_filter_test() {
+    use crate::Membership;
+
+    let mut builder = FilterBuilder::new(10_000, 0.01);
+    builder.seed(5);
+    let sharded = builder.build_sharded_bloom_filter(8);
+    assert_eq!(sharded.shards(), 8);
+    let mut shard = FilterBuilder::new(1250, 0.01);
+    shard.complete();
+    assert_eq!((sharded.config().size, sharded.config().hashes), (shard.size, shard.hashes));
+    let sized = FilterBuilder::from_size_and_hashes(64 * 800, 7).build_sharded_bloom_filter(8);
+    assert_eq!((sized.config().size, sized.config().hashes), (64 * 100, 7));
+    std::thread::scope(|scope| {
+        for t in 0..4u32 {
+            let sharded = &sharded;
+            scope.spawn(move || {
+                for i in (t..10_000).step_by(4) {
+                    sharded.add(&i.to_le_bytes());
+                }
+            });
+        }
+    });
+    assert!((0..10_000u32).all(|i| sharded.contains(&i.to_le_bytes())));
+    let report = sharded.shard_fill_report();
+    assert_eq!(report.len(), 8);
+    for shard in report.iter() {
+        assert!((shard.estimated_elements - 1250.0).abs() < 200.0, "{shard:?}");
+        assert!(shard.fill_ratio > 0.3 && shard.fill_ratio < 0.7, "{shard:?}");
+    }
+
+    let mut expected = sharded.config().build_bloom_filter();
+    (0..10_000u32).for_each(|i| expected.add(&i.to_le_bytes()));
+    assert_eq!(sharded.merge_into_single().get_u64_array(), expected.get_u64_array());
+}
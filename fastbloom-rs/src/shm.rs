@@ -0,0 +1,183 @@
+use core::ptr::NonNull;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::vec::{AtomicStorage, Storage, StorageMut};
+
+/// Storage over a memory region mapped by the caller, e.g. a POSIX shared-memory object or a
+/// file in `/dev/shm` which every worker process maps, so the processes probe one filter instead
+/// of each holding a private copy. The words are read and written as atomics with relaxed
+/// ordering, so processes may keep adding while the others query. [StorageMut::update] sets and
+/// clears the bits it changes with `fetch_or` and `fetch_and`, so the adds of several writers to
+/// a Bloom filter never drop each other's bits; updates which read their word, e.g. of counters,
+/// need [AtomicStorage] with several writers.
+///
+/// The region only holds the words, every process builds its filter from the same
+/// [FilterBuilder](crate::FilterBuilder) with
+/// [build_bloom_filter_in](crate::FilterBuilder::build_bloom_filter_in), whose `Init` is the
+/// storage, or with [BloomFilter::from_storage](crate::BloomFilter::from_storage) and the same
+/// number of hashes. [Storage::new] keeps the content of the region, so processes attaching
+/// later see the elements added so far, and panics when the region is too small.
+///
+/// # Examples
+///
+/// ```rust
+/// use fastbloom_rs::{FilterBuilder, Membership, SharedMemStorage};
+///
+/// let mut builder = FilterBuilder::new(10_000, 0.01);
+/// // stands in for a region mapped by all processes, e.g. with `mmap(MAP_SHARED)`
+/// let mut region = vec![0usize; 2048];
+/// let (ptr, slots) = (region.as_mut_ptr(), region.len());
+///
+/// let writer_storage = unsafe { SharedMemStorage::from_raw_parts(ptr, slots) };
+/// let mut writer = builder.build_bloom_filter_in::<SharedMemStorage>(writer_storage);
+/// writer.add(b"hello");
+///
+/// let reader_storage = unsafe { SharedMemStorage::from_raw_parts(ptr, slots) };
+/// let reader = builder.build_bloom_filter_in::<SharedMemStorage>(reader_storage);
+/// assert_eq!(reader.contains(b"hello"), true);
+/// assert_eq!(reader.contains(b"world"), false);
+/// ```
+#[derive(Debug)]
+pub struct SharedMemStorage {
+    words: NonNull<AtomicUsize>,
+    slots: usize,
+}
+
+// Safety: all accesses to the words are atomic, see `from_raw_parts`.
+unsafe impl Send for SharedMemStorage {}
+unsafe impl Sync for SharedMemStorage {}
+
+impl SharedMemStorage {
+    /// Wraps the `slots` words at `ptr`, keeping their content.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must be non-null, aligned for `usize` and valid for reads and writes of `slots`
+    /// words for the lifetime of the storage and the filters built from it, i.e. the region must
+    /// stay mapped. Meanwhile the words may only be accessed atomically, by this and other
+    /// processes, or with ordinary reads and writes while no filter uses them.
+    pub unsafe fn from_raw_parts(ptr: *mut usize, slots: usize) -> Self {
+        let words = NonNull::new(ptr.cast::<AtomicUsize>()).expect("null shared memory pointer!");
+        assert!(ptr.is_aligned(), "shared memory is not aligned for usize!");
+        SharedMemStorage { words, slots }
+    }
+
+    /// Returns the pointer to the first word.
+    pub fn as_ptr(&self) -> *mut usize {
+        self.words.as_ptr().cast()
+    }
+
+    #[inline]
+    fn word(&self, slot: usize) -> &AtomicUsize {
+        assert!(slot < self.slots, "slot {slot} out of range of {} slots!", self.slots);
+        // Safety: the region holds `slots` words, which are only accessed atomically.
+        unsafe { &*self.words.as_ptr().add(slot) }
+    }
+}
+
+impl Storage for SharedMemStorage {
+    type Init = SharedMemStorage;
+    #[inline]
+    fn new(slots: usize, init: SharedMemStorage) -> Self {
+        assert!(init.slots >= slots, "region of {} slots is too small, {} slots required!",
+                init.slots, slots);
+        SharedMemStorage { slots, ..init }
+    }
+    #[inline]
+    fn get(&self, slot: usize) -> usize {
+        self.word(slot).load(Ordering::Relaxed)
+    }
+    #[inline]
+    fn slots(&self) -> usize {
+        self.slots
+    }
+    #[cfg(feature = "simd")]
+    #[inline]
+    fn prefetch(&self, slot: usize) {
+        crate::simd::prefetch(self.as_ptr().cast_const().wrapping_add(slot))
+    }
+}
+
+impl StorageMut for SharedMemStorage {
+    #[inline]
+    fn update(&mut self, slot: usize, op: impl FnOnce(usize) -> Option<usize>) {
+        let word = self.word(slot);
+        let old = word.load(Ordering::Relaxed);
+        if let Some(new) = op(old) {
+            // only the changed bits, keeping the ones other writers changed since the load
+            if new & !old != 0 {
+                word.fetch_or(new & !old, Ordering::Relaxed);
+            }
+            if old & !new != 0 {
+                word.fetch_and(!(old & !new), Ordering::Relaxed);
+            }
+        }
+    }
+    fn clear(&mut self) {
+        (0..self.slots).for_each(|slot| self.word(slot).store(0, Ordering::Relaxed));
+    }
+}
+
+impl AtomicStorage for SharedMemStorage {
+    #[inline]
+    fn fetch_update(&self, slot: usize, op: impl FnMut(usize) -> Option<usize>)
+                    -> Result<usize, usize> {
+        self.word(slot).fetch_update(Ordering::Relaxed, Ordering::Relaxed, op)
+    }
+}
+
+#[test]
+fn shared_mem_storage_test() {
+    use crate::builder::FilterBuilder;
+    use crate::Membership;
+
+    let mut builder = FilterBuilder::new(10_000, 0.01);
+    builder.complete();
+    let slots = (builder.size / usize::BITS as u64) as usize;
+    let mut region = vec![0usize; slots + 8];
+    let ptr = region.as_mut_ptr();
+    let mut writer = builder.build_bloom_filter_in::<SharedMemStorage>(unsafe {
+        SharedMemStorage::from_raw_parts(ptr, slots + 8)
+    });
+    assert_eq!(writer.storage().slots(), slots);
+    (0..1000u32).for_each(|i| writer.add(&i.to_le_bytes()));
+
+    let reader = builder.build_bloom_filter_in::<SharedMemStorage>(unsafe {
+        SharedMemStorage::from_raw_parts(ptr, slots)
+    });
+    assert!((0..1000u32).all(|i| reader.contains(&i.to_le_bytes())));
+    let words: Vec<usize> = (0..slots).map(|slot| reader.storage().get(slot)).collect();
+    let expected = writer.to_dense();
+    assert_eq!(words, expected.storage().as_slice());
+
+    writer.clear();
+    assert!(!reader.contains(&0u32.to_le_bytes()));
+    drop((writer, reader));
+    assert!(region.iter().all(|word| *word == 0));
+}
+
+#[test]
+fn shared_mem_storage_writers_test() {
+    use crate::builder::FilterBuilder;
+    use crate::Membership;
+
+    let mut builder = FilterBuilder::new(100_000, 0.01);
+    builder.complete();
+    let slots = (builder.size / usize::BITS as u64) as usize;
+    let mut region = vec![0usize; slots];
+    let ptr = region.as_mut_ptr();
+    let writers: Vec<_> = (0..4).map(|_| builder.build_bloom_filter_in::<SharedMemStorage>(
+        unsafe { SharedMemStorage::from_raw_parts(ptr, slots) }
+    )).collect();
+    std::thread::scope(|scope| {
+        for (n, mut writer) in writers.into_iter().enumerate() {
+            scope.spawn(move || {
+                (0..25_000u32).for_each(|i| writer.add(&(i * 4 + n as u32).to_le_bytes()))
+            });
+        }
+    });
+    let reader = builder.build_bloom_filter_in::<SharedMemStorage>(unsafe {
+        SharedMemStorage::from_raw_parts(ptr, slots)
+    });
+    assert!((0..100_000u32).all(|i| reader.contains(&i.to_le_bytes())));
+}
@@ -0,0 +1,89 @@
+//! Helpers of the `simd` feature: grouped hashing and prefetching for the batch operations and
+//! the AVX2 build of the word loops of unions and intersections. The probes stay scalar, the
+//! gain comes from overlapping cache misses rather than from vector instructions.
+
+use smallvec::SmallVec;
+
+use crate::builder::FilterBuilder;
+use crate::index::Indices;
+#[cfg(all(feature = "std", target_arch = "x86_64"))]
+use crate::vec::combine_words_unrolled;
+use crate::vec::{le_word, Storage};
+
+/// Number of elements hashed and probed together, see [LaneProbes].
+pub(crate) const LANES: usize = 8;
+
+/// Hints the CPU to load the cache line at `ptr`, see [Storage::prefetch]. A hint only: it
+/// never faults, so any address is fine.
+#[inline(always)]
+pub(crate) fn prefetch<T>(ptr: *const T) {
+    #[cfg(target_arch = "x86_64")]
+    // SAFETY: prefetch doesn't access memory; SSE is part of the x86_64 baseline.
+    unsafe {
+        use core::arch::x86_64::{_mm_prefetch, _MM_HINT_T0};
+        _mm_prefetch::<_MM_HINT_T0>(ptr as *const i8);
+    }
+    #[cfg(target_arch = "aarch64")]
+    // SAFETY: prfm doesn't access memory.
+    unsafe {
+        core::arch::asm!("prfm pldl1keep, [{0}]", in(reg) ptr,
+                         options(nostack, readonly, preserves_flags));
+    }
+    #[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+    let _ = ptr;
+}
+
+/// [combine_words_unrolled] compiled for AVX2, which combines four 64-bit words per
+/// instruction instead of two.
+///
+/// # Safety
+///
+/// The CPU must support AVX2.
+#[cfg(all(feature = "std", target_arch = "x86_64"))]
+#[target_feature(enable = "avx2")]
+pub(crate) unsafe fn combine_words_avx2(words: &mut [usize], others: &[usize],
+                                        op: impl Fn(usize, usize) -> usize + Copy) {
+    combine_words_unrolled(words, others, op);
+}
+
+/// Probe of an element in storage representation: a word and the probed bit of it.
+pub(crate) type Probe = (usize, usize);
+
+/// Probes of a group of up to [LANES] elements, hashed together. A scalar loop waits for one
+/// cache miss per probe; prefetching the words of a whole group first overlaps the misses, and
+/// as the probes are (word, mask) pairs no gather is needed to test or set them.
+#[derive(Default)]
+pub(crate) struct LaneProbes {
+    probes: SmallVec<[Probe; 16 * LANES]>,
+    ends: [usize; LANES],
+}
+
+impl LaneProbes {
+    /// Hashes the elements of `group`, at most [LANES].
+    #[inline]
+    pub(crate) fn load(&mut self, group: &[&[u8]], config: &FilterBuilder) {
+        self.probes.clear();
+        for (lane, element) in group.iter().enumerate() {
+            for index in Indices::new(element, config) {
+                let (w, b) = (index / usize::BITS as u64, index % usize::BITS as u64);
+                self.probes.push((w as usize, le_word(1 << b)));
+            }
+            self.ends[lane] = self.probes.len();
+        }
+    }
+
+    /// Prefetches the words of all probes of the group.
+    #[inline]
+    pub(crate) fn prefetch<S: Storage>(&self, storage: &S) {
+        for &(w, _) in &self.probes {
+            storage.prefetch(w);
+        }
+    }
+
+    /// Returns the probes of element `lane` of the group.
+    #[inline]
+    pub(crate) fn lane(&self, lane: usize) -> &[Probe] {
+        let start = if lane == 0 { 0 } else { self.ends[lane - 1] };
+        &self.probes[start..self.ends[lane]]
+    }
+}
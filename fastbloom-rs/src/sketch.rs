@@ -0,0 +1,131 @@
+use alloc::vec;
+use alloc::vec::Vec;
+
+use xxhash_rust::xxh3::xxh3_64;
+
+#[cfg(not(feature = "std"))]
+use crate::float::Float;
+use crate::index::ElementHash;
+
+/// Number of index bits of [HyperLogLog], `2^14` registers give a standard error of about 0.8%.
+const PRECISION: u32 = 14;
+
+/// HyperLogLog distinct counter, used to size filters from a sample of the stream and to count
+/// the distinct elements added to a filter alongside it, see [crate::BloomFilter::add_and_sketch].
+/// It takes 16 KiB for a standard error of about 0.8%.
+///
+/// A sketch counts either elements added with [HyperLogLog::add] or element hashes added with
+/// [HyperLogLog::add_hash], the hashes of one hashing configuration. Sketches of the same kind
+/// can be merged.
+///
+/// **Reference**: Flajolet, P., Fusy, É., Gandouet, O., & Meunier, F. (2007). HyperLogLog: the
+/// analysis of a near-optimal cardinality estimation algorithm. In AofA: Analysis of Algorithms
+/// (pp. 137-156).
+///
+/// # Examples
+///
+/// ```rust
+/// use fastbloom_rs::{FilterBuilder, HyperLogLog};
+///
+/// let mut bloom = FilterBuilder::new(100_000, 0.01).build_bloom_filter();
+/// let mut hll = HyperLogLog::new();
+/// for i in 0..10_000u32 {
+///     bloom.add_and_sketch(&(i % 1000).to_le_bytes(), &mut hll);
+/// }
+/// assert!((hll.estimate() - 1000.0).abs() < 30.0);
+/// ```
+#[derive(Clone)]
+#[derive(Debug)]
+pub struct HyperLogLog {
+    registers: Vec<u8>,
+}
+
+impl Default for HyperLogLog {
+    fn default() -> Self {
+        HyperLogLog::new()
+    }
+}
+
+impl HyperLogLog {
+    /// Builds an empty sketch.
+    pub fn new() -> Self {
+        HyperLogLog { registers: vec![0; 1 << PRECISION] }
+    }
+
+    /// Adds an element, hashed with xxh3.
+    pub fn add(&mut self, element: &[u8]) {
+        self.add_mixed(xxh3_64(element));
+    }
+
+    /// Adds an element by its base hashes of [BloomFilter::hash](crate::BloomFilter::hash),
+    /// without hashing it again.
+    pub fn add_hash(&mut self, hash: ElementHash) {
+        self.add_mixed(hash.mixed());
+    }
+
+    #[inline]
+    fn add_mixed(&mut self, hash: u64) {
+        let register = (hash >> (64 - PRECISION)) as usize;
+        // position of the first set bit of the remaining bits, the sentinel bounds it.
+        let rank = ((hash << PRECISION) | (1 << (PRECISION - 1))).leading_zeros() + 1;
+        self.registers[register] = self.registers[register].max(rank as u8);
+    }
+
+    /// Adds the elements of `other`, so the sketch counts the distinct elements of both.
+    pub fn merge(&mut self, other: &HyperLogLog) {
+        for (register, &theirs) in self.registers.iter_mut().zip(&other.registers) {
+            *register = (*register).max(theirs);
+        }
+    }
+
+    /// Removes all elements.
+    pub fn clear(&mut self) {
+        self.registers.fill(0);
+    }
+
+    /// Estimates the number of distinct elements added.
+    pub fn estimate(&self) -> f64 {
+        let m = self.registers.len() as f64;
+        let alpha = 0.7213 / (1.0 + 1.079 / m);
+        let sum: f64 = self.registers.iter().map(|r| 2f64.powi(-(*r as i32))).sum();
+        let estimate = alpha * m * m / sum;
+        let zeros = self.registers.iter().filter(|r| **r == 0).count();
+        if estimate <= 2.5 * m && zeros > 0 {
+            // linear counting is more accurate for small cardinalities.
+            m * (m / zeros as f64).ln()
+        } else {
+            estimate
+        }
+    }
+}
+
+#[test]
+fn hyper_log_log_test() {
+    for n in [0u32, 100, 10_000, 1_000_000] {
+        let mut hll = HyperLogLog::new();
+        for i in 0..n {
+            hll.add(&i.to_le_bytes());
+            hll.add(&i.to_le_bytes());
+        }
+        let error = (hll.estimate() - n as f64).abs() / (n as f64).max(1.0);
+        assert!(error < 0.03, "{n}: {}", hll.estimate());
+    }
+}
+
+#[test]
+fn hyper_log_log_hash_test() {
+    use crate::builder::FilterBuilder;
+
+    let mut bloom = FilterBuilder::new(100_000, 0.01).build_bloom_filter();
+    let (mut even, mut odd) = (HyperLogLog::new(), HyperLogLog::default());
+    for i in 0..100_000u32 {
+        let sketch = if i % 2 == 0 { &mut even } else { &mut odd };
+        bloom.add_and_sketch(&(i % 50_000).to_le_bytes(), sketch);
+    }
+    assert!((0..50_000u32).all(|i| bloom.contains(&i.to_le_bytes())));
+    assert!((even.estimate() - 25_000.0).abs() < 750.0, "{}", even.estimate());
+    even.merge(&odd);
+    assert!((even.estimate() - 50_000.0).abs() < 1500.0, "{}", even.estimate());
+    even.clear();
+    assert_eq!(even.estimate(), 0.0);
+}
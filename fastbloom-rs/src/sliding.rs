@@ -0,0 +1,138 @@
+use std::time::{Duration, Instant};
+
+use crate::aging::AgingBloomFilter;
+use crate::builder::FilterBuilder;
+use crate::{Maintain, Membership};
+
+/// Bloom filter of the items inserted within a sliding time window, e.g. to drop duplicate
+/// events of the last ten minutes. The window is split into `slices` slices, each a generation
+/// of an [AgingBloomFilter] rotating on its own every `window / slices`, so the rotation is
+/// driven by the clock and needs no calls besides inserts and queries.
+///
+/// An item is seen for at least `window` and at most `window + window / slices` after its
+/// insert, more slices make the window sharper at the cost of one bit vector each. The
+/// [FilterBuilder] sizes one slice, i.e. the items inserted within `window / slices`; a query
+/// checks `slices + 1` bit vectors, so their false positive probabilities add up.
+///
+/// # Examples
+///
+/// ```rust
+/// use std::time::Duration;
+///
+/// use fastbloom_rs::{FilterBuilder, SlidingBloomFilter};
+///
+/// let mut filter = SlidingBloomFilter::new(Duration::from_secs(600), 10,
+///                                          FilterBuilder::new(10_000, 0.001));
+/// filter.insert_now(b"event-1");
+/// assert_eq!(filter.seen_within_window(b"event-1"), true);
+/// assert_eq!(filter.seen_within_window(b"event-2"), false);
+/// ```
+#[derive(Clone)]
+#[derive(Debug)]
+pub struct SlidingBloomFilter {
+    filter: AgingBloomFilter,
+    window: Duration,
+}
+
+impl SlidingBloomFilter {
+    /// Builds an empty filter for a window of `window` in `slices` slices, each sized by
+    /// `config`. Panics if there are no slices, more than `u32::MAX` or a slice is shorter than a
+    /// nanosecond.
+    pub fn new(window: Duration, slices: usize, config: FilterBuilder) -> Self {
+        assert!(slices > 0, "SlidingBloomFilter needs a slice!");
+        let Ok(divisor) = u32::try_from(slices) else {
+            panic!("SlidingBloomFilter takes at most u32::MAX slices!");
+        };
+        let interval = window / divisor;
+        // one more generation, which stays live while the oldest slice of the window expires
+        let mut filter = AgingBloomFilter::new(config, slices + 1);
+        filter.rotate_every(interval);
+        SlidingBloomFilter { filter, window }
+    }
+
+    /// Returns the configuration/builder of a slice.
+    pub fn config(&self) -> FilterBuilder {
+        self.filter.config()
+    }
+
+    /// Returns the length of the window.
+    pub fn window(&self) -> Duration {
+        self.window
+    }
+
+    /// Returns the number of slices of the window.
+    pub fn slices(&self) -> usize {
+        self.filter.generations() - 1
+    }
+
+    /// Inserts the item at the current time, dropping the slices which slid out of the window.
+    pub fn insert_now(&mut self, item: &[u8]) {
+        self.filter.add(item);
+    }
+
+    /// Tests whether the item was inserted within the window (subject to the false positive
+    /// probability).
+    pub fn seen_within_window(&self, item: &[u8]) -> bool {
+        self.filter.contains(item)
+    }
+
+    /// Forgets all items and restarts the rotation.
+    pub fn clear(&mut self) {
+        self.filter.clear();
+    }
+}
+
+/// Drops the slices which slid out of the window at `now`, freeing them for inserts.
+impl Maintain for SlidingBloomFilter {
+    fn maintain(&mut self, now: Instant) {
+        self.filter.maintain(now);
+    }
+}
+
+#[test]
+fn sliding_bloom_filter_test() {
+    let window = Duration::from_millis(400);
+    let mut filter = FilterBuilder::new(1000, 0.01).build_sliding_bloom_filter(window, 4);
+    assert_eq!((filter.window(), filter.slices()), (window, 4));
+    // the sleeps may overrun on a loaded machine, so the items are only asserted to be seen
+    // while they are certainly younger than the window
+    let hello = Instant::now();
+    filter.insert_now(b"hello");
+    let hello_inserted = Instant::now();
+    std::thread::sleep(Duration::from_millis(200));
+    let world = Instant::now();
+    filter.insert_now(b"world");
+    if hello.elapsed() < window {
+        assert!(filter.seen_within_window(b"hello"));
+    }
+    // forgotten at most 500ms after its insert, with or without another insert
+    std::thread::sleep(Duration::from_millis(550).saturating_sub(hello_inserted.elapsed()));
+    assert!(!filter.seen_within_window(b"hello"));
+    if world.elapsed() < window {
+        assert!(filter.seen_within_window(b"world"));
+    }
+    filter.insert_now(b"again");
+    assert!(!filter.seen_within_window(b"hello"));
+
+    filter.clear();
+    assert!(!filter.seen_within_window(b"world"));
+}
+
+#[test]
+fn sliding_bloom_filter_maintain_test() {
+    let window = Duration::from_secs(600);
+    let mut filter = FilterBuilder::new(1000, 0.01).build_sliding_bloom_filter(window, 4);
+    let start = Instant::now();
+    filter.insert_now(b"hello");
+    filter.maintain(start + window / 2);
+    assert!(filter.seen_within_window(b"hello"));
+    filter.maintain(start + 2 * window);
+    assert!(!filter.seen_within_window(b"hello"));
+}
+
+#[test]
+#[cfg(target_pointer_width = "64")]
+#[should_panic(expected = "at most u32::MAX slices")]
+fn sliding_bloom_filter_slices_test() {
+    SlidingBloomFilter::new(Duration::from_secs(1), 1 << 32, FilterBuilder::new(1000, 0.01));
+}
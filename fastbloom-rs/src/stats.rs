@@ -0,0 +1,149 @@
+#[cfg(feature = "stats")]
+use core::sync::atomic::{AtomicU64, Ordering};
+
+/// Snapshot of the per-operation counters of a filter, see `stats()` on the filter types.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct OpStats {
+    /// Number of `add` calls.
+    pub adds: u64,
+    /// Number of `contains` calls.
+    pub contains: u64,
+    /// Number of `contains` calls which returned `true`.
+    pub hits: u64,
+    /// Number of `contains` calls which returned `false`.
+    pub misses: u64,
+    /// Number of union/intersection merges with another filter.
+    pub merges: u64,
+}
+
+/// Snapshot of the metrics of a long-lived filter, to be exported periodically to dashboards and
+/// alerts, see `metrics()` on the filter types (feature `metrics`).
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct FilterMetrics {
+    /// The per-operation counters: inserts, queries and their hits.
+    pub ops: OpStats,
+    /// Share of set bits, or of non-zero counters of counting filters.
+    pub fill_ratio: f64,
+    /// False positive probability estimated from the fill ratio.
+    pub estimated_fpp: f64,
+    /// Number of increments of counters at their maximum, `0` for filters without counters.
+    pub saturation_events: u64,
+}
+
+/// Per-operation counters using relaxed atomics, so they can be bumped from `&self` methods.
+/// Without the `stats` feature this is a zero-sized type and all recording is a no-op.
+#[derive(Debug, Default)]
+pub(crate) struct OpCounters {
+    #[cfg(feature = "stats")]
+    adds: AtomicU64,
+    #[cfg(feature = "stats")]
+    contains: AtomicU64,
+    #[cfg(feature = "stats")]
+    hits: AtomicU64,
+    #[cfg(feature = "stats")]
+    merges: AtomicU64,
+}
+
+impl OpCounters {
+    /// Const version of [Default::default].
+    pub(crate) const fn new() -> Self {
+        OpCounters {
+            #[cfg(feature = "stats")]
+            adds: AtomicU64::new(0),
+            #[cfg(feature = "stats")]
+            contains: AtomicU64::new(0),
+            #[cfg(feature = "stats")]
+            hits: AtomicU64::new(0),
+            #[cfg(feature = "stats")]
+            merges: AtomicU64::new(0),
+        }
+    }
+
+    #[inline]
+    pub(crate) fn record_add(&self) {
+        #[cfg(feature = "stats")]
+        self.adds.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Records `n` adds at once, e.g. of a parallel bulk add.
+    #[cfg_attr(not(feature = "rayon"), allow(dead_code))]
+    #[inline]
+    pub(crate) fn record_adds(&self, n: u64) {
+        #[cfg(feature = "stats")]
+        self.adds.fetch_add(n, Ordering::Relaxed);
+        #[cfg(not(feature = "stats"))]
+        let _ = n;
+    }
+
+    #[inline]
+    pub(crate) fn record_contains(&self, hit: bool) {
+        #[cfg(feature = "stats")]
+        {
+            self.contains.fetch_add(1, Ordering::Relaxed);
+            if hit {
+                self.hits.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        #[cfg(not(feature = "stats"))]
+        let _ = hit;
+    }
+
+    #[inline]
+    pub(crate) fn record_merge(&self) {
+        #[cfg(feature = "stats")]
+        self.merges.fetch_add(1, Ordering::Relaxed);
+    }
+
+    #[cfg(feature = "stats")]
+    pub(crate) fn snapshot(&self) -> OpStats {
+        let contains = self.contains.load(Ordering::Relaxed);
+        let hits = self.hits.load(Ordering::Relaxed);
+        OpStats {
+            adds: self.adds.load(Ordering::Relaxed),
+            contains,
+            hits,
+            misses: contains.saturating_sub(hits),
+            merges: self.merges.load(Ordering::Relaxed),
+        }
+    }
+
+    #[cfg(feature = "stats")]
+    pub(crate) fn reset(&self) {
+        self.adds.store(0, Ordering::Relaxed);
+        self.contains.store(0, Ordering::Relaxed);
+        self.hits.store(0, Ordering::Relaxed);
+        self.merges.store(0, Ordering::Relaxed);
+    }
+}
+
+impl Clone for OpCounters {
+    fn clone(&self) -> Self {
+        #[cfg(feature = "stats")]
+        {
+            let stats = self.snapshot();
+            OpCounters {
+                adds: AtomicU64::new(stats.adds),
+                contains: AtomicU64::new(stats.contains),
+                hits: AtomicU64::new(stats.hits),
+                merges: AtomicU64::new(stats.merges),
+            }
+        }
+        #[cfg(not(feature = "stats"))]
+        OpCounters {}
+    }
+}
+
+#[cfg(feature = "stats")]
+#[test]
+fn op_counters_test() {
+    let counters = OpCounters::default();
+    counters.record_add();
+    counters.record_contains(true);
+    counters.record_contains(false);
+    counters.record_contains(false);
+    counters.record_merge();
+    assert_eq!(counters.clone().snapshot(),
+               OpStats { adds: 1, contains: 3, hits: 1, misses: 2, merges: 1 });
+    counters.reset();
+    assert_eq!(counters.snapshot(), OpStats::default());
+}
@@ -0,0 +1,106 @@
+use alloc::vec::Vec;
+use core::future::poll_fn;
+use core::pin::pin;
+use core::task::Poll;
+
+use futures_core::Stream;
+
+use crate::bloom::BloomFilter;
+use crate::vec::StorageMut;
+
+/// Maximum number of stream items buffered by [BloomFilter::extend_from_stream].
+const STREAM_BATCH: usize = 1024;
+
+enum Next<T> {
+    Item(T),
+    /// The stream is pending and items are buffered.
+    Flush,
+    End,
+}
+
+impl<S: StorageMut> BloomFilter<S> {
+    /// Adds all elements of `stream`, e.g. messages consumed from Kafka or the lines of an HTTP
+    /// body, and returns their number. Elements which are ready are buffered and added in
+    /// batches of up to 1024; the buffer is flushed whenever the stream has to wait, so added
+    /// elements never lag behind the stream.
+    pub async fn extend_from_stream<T, St>(&mut self, stream: St) -> u64
+        where T: AsRef<[u8]>, St: Stream<Item=T> {
+        let mut stream = pin!(stream);
+        let mut batch = Vec::with_capacity(STREAM_BATCH);
+        let mut added = 0;
+        loop {
+            let buffered = !batch.is_empty();
+            let next = poll_fn(|cx| match stream.as_mut().poll_next(cx) {
+                Poll::Ready(Some(item)) => Poll::Ready(Next::Item(item)),
+                Poll::Ready(None) => Poll::Ready(Next::End),
+                Poll::Pending if buffered => Poll::Ready(Next::Flush),
+                Poll::Pending => Poll::Pending,
+            }).await;
+            match next {
+                Next::Item(item) => {
+                    batch.push(item);
+                    if batch.len() < STREAM_BATCH {
+                        continue;
+                    }
+                }
+                Next::Flush => {}
+                Next::End => break,
+            }
+            added += self.add_buffered(&mut batch);
+        }
+        added + self.add_buffered(&mut batch)
+    }
+
+    fn add_buffered<T: AsRef<[u8]>>(&mut self, batch: &mut Vec<T>) -> u64 {
+        let added = batch.len() as u64;
+        self.add_all(batch.drain(..));
+        added
+    }
+}
+
+#[test]
+fn extend_from_stream_test() {
+    use core::future::Future;
+    use core::pin::Pin;
+    use core::task::{Context, Waker};
+
+    use crate::FilterBuilder;
+
+    /// Yields `0..len`, pending before every 100th element.
+    struct Numbers {
+        next: u32,
+        len: u32,
+        pending: bool,
+    }
+
+    impl Stream for Numbers {
+        type Item = [u8; 4];
+
+        fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<[u8; 4]>> {
+            if self.next == self.len {
+                return Poll::Ready(None);
+            }
+            self.pending = !self.pending && self.next.is_multiple_of(100);
+            if self.pending {
+                cx.waker().wake_by_ref();
+                return Poll::Pending;
+            }
+            self.next += 1;
+            Poll::Ready(Some((self.next - 1).to_le_bytes()))
+        }
+    }
+
+    let mut bloom = FilterBuilder::new(10_000, 0.01).build_bloom_filter();
+    let numbers = Numbers { next: 0, len: 5000, pending: false };
+    let added = {
+        let mut future = pin!(bloom.extend_from_stream(numbers));
+        let mut cx = Context::from_waker(Waker::noop());
+        loop {
+            if let Poll::Ready(added) = future.as_mut().poll(&mut cx) {
+                break added;
+            }
+        }
+    };
+    assert_eq!(added, 5000);
+    assert!((0..5000u32).all(|i| bloom.contains(&i.to_le_bytes())));
+}
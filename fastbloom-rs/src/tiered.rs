@@ -0,0 +1,275 @@
+use alloc::vec::Vec;
+#[cfg(feature = "std")]
+use std::time::{Duration, Instant};
+
+use crate::bloom::BloomFilter;
+use crate::builder::FilterBuilder;
+use crate::index::ElementHash;
+#[cfg(feature = "std")]
+use crate::Maintain;
+use crate::Membership;
+
+/// Two-level Bloom filter for skewed access patterns: a small front filter, sized to stay in
+/// the L1 or L2 cache, holds the recently added and the hot elements, and a large back filter
+/// holds all others. Most probes of a skewed workload are answered by the front filter, the
+/// others cost the cache misses of the back filter. An element is hashed once for both.
+///
+/// * **Adds** go to the front filter only. Their hashes are kept until
+///   [TieredBloomFilter::fold] inserts them into the back filter and empties the front one,
+///   which happens on its own once the front filter took its capacity of elements, and
+///   periodically after [TieredBloomFilter::fold_every].
+/// * **Promotion**: [TieredBloomFilter::lookup] inserts elements found in the back filter into
+///   the front one, every one or every n-th, see [TieredBloomFilter::promote_every].
+///   [TieredBloomFilter::contains] doesn't promote.
+///
+/// There are no false negatives, the false positive probability is about the sum of the ones
+/// of both filters. The back filter is sized by the [FilterBuilder], the front filter for
+/// `front_elements` with the same false positive probability and hashing.
+///
+/// # Examples
+///
+/// ```rust
+/// use fastbloom_rs::FilterBuilder;
+///
+/// let mut filter = FilterBuilder::new(1_000_000, 0.01).build_tiered_bloom_filter(1000);
+/// filter.add(b"hello");
+/// assert_eq!(filter.contains(b"hello"), true);
+///
+/// filter.fold();
+/// assert_eq!(filter.front().contains(b"hello"), false);
+/// // found in the back filter and promoted to the front one
+/// assert_eq!(filter.lookup(b"hello"), true);
+/// assert_eq!(filter.front().contains(b"hello"), true);
+/// ```
+#[derive(Clone)]
+#[derive(Debug)]
+pub struct TieredBloomFilter {
+    front: BloomFilter,
+    back: BloomFilter,
+    /// Hashes of the adds which are in the front filter only.
+    pending: Vec<ElementHash>,
+    /// Number of elements inserted into the front filter since the last fold.
+    front_len: u64,
+    promote_every: u32,
+    /// Back filter hits since the last promotion.
+    hits: u32,
+    /// Interval of the periodic folds and the time of the last fold.
+    #[cfg(feature = "std")]
+    fold_interval: Option<(Duration, Instant)>,
+}
+
+impl TieredBloomFilter {
+    /// Builds an empty filter with a back filter configured by `config` and a front filter for
+    /// `front_elements` elements.
+    pub fn new(config: FilterBuilder, front_elements: u64) -> Self {
+        assert!(front_elements > 0, "TieredBloomFilter needs a front filter!");
+        let mut front = config.clone();
+        front.expected_elements = front_elements;
+        front.headroom_factor = 1.0;
+        front.size = 0;
+        front.hashes = 0;
+        front.done = false;
+        TieredBloomFilter {
+            front: BloomFilter::new(front),
+            back: BloomFilter::new(config),
+            pending: Vec::new(),
+            front_len: 0,
+            promote_every: 1,
+            hits: 0,
+            #[cfg(feature = "std")]
+            fold_interval: None,
+        }
+    }
+
+    /// Folds every `interval` from now on, besides the folds of a full front filter, so the
+    /// back filter gets the adds within `interval` also when there are few. The folds happen in
+    /// [TieredBloomFilter::add] and [TieredBloomFilter::lookup].
+    #[cfg(feature = "std")]
+    pub fn fold_every(&mut self, interval: Duration) {
+        assert!(!interval.is_zero(), "interval must not be zero!");
+        self.fold_interval = Some((interval, Instant::now()));
+    }
+
+    /// Promotes every `n`-th element [TieredBloomFilter::lookup] finds in the back filter, or
+    /// none for `0`. Sampling the promotions keeps elements probed only now and then out of the
+    /// front filter, the default of `1` promotes all of them.
+    pub fn promote_every(&mut self, n: u32) {
+        self.promote_every = n;
+        self.hits = 0;
+    }
+
+    /// Returns the front filter.
+    pub fn front(&self) -> &BloomFilter {
+        &self.front
+    }
+
+    /// Returns the back filter, which lacks the adds since the last fold.
+    pub fn back(&self) -> &BloomFilter {
+        &self.back
+    }
+
+    /// Returns the number of adds which are not in the back filter yet.
+    pub fn pending(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Adds the element to the front filter, folding it first if it is full.
+    pub fn add(&mut self, element: &[u8]) {
+        #[cfg(feature = "std")]
+        self.fold_if_due();
+        let hash = self.back.hash(element);
+        self.insert_front(hash);
+        self.pending.push(hash);
+    }
+
+    /// Tests whether an element is present in one of the filters (subject to the false
+    /// positive probability), without promoting it.
+    pub fn contains(&self, element: &[u8]) -> bool {
+        let hash = self.back.hash(element);
+        self.front.contains_hash(hash) || self.back.contains_hash(hash)
+    }
+
+    /// Tests whether an element is present like [TieredBloomFilter::contains], and promotes it
+    /// to the front filter if it was found in the back filter only, see
+    /// [TieredBloomFilter::promote_every].
+    pub fn lookup(&mut self, element: &[u8]) -> bool {
+        #[cfg(feature = "std")]
+        self.fold_if_due();
+        let hash = self.back.hash(element);
+        if self.front.contains_hash(hash) {
+            return true;
+        }
+        if !self.back.contains_hash(hash) {
+            return false;
+        }
+        if self.promote_every != 0 {
+            self.hits += 1;
+            if self.hits >= self.promote_every {
+                self.hits = 0;
+                self.insert_front(hash);
+            }
+        }
+        true
+    }
+
+    /// Inserts the adds of the front filter into the back filter and empties the front filter.
+    pub fn fold(&mut self) {
+        for hash in self.pending.drain(..) {
+            self.back.insert_hash(hash);
+        }
+        self.front.clear();
+        self.front_len = 0;
+        #[cfg(feature = "std")]
+        if let Some((interval, _)) = self.fold_interval {
+            self.fold_interval = Some((interval, Instant::now()));
+        }
+    }
+
+    /// Folds if the interval of [TieredBloomFilter::fold_every] passed since the last fold.
+    #[cfg(feature = "std")]
+    fn fold_if_due(&mut self) {
+        self.fold_if_due_at(Instant::now());
+    }
+
+    /// Folds if the interval passed between the last fold and `now`.
+    #[cfg(feature = "std")]
+    fn fold_if_due_at(&mut self, now: Instant) {
+        if let Some((interval, folded)) = self.fold_interval {
+            if now.saturating_duration_since(folded) >= interval {
+                self.fold();
+            }
+        }
+    }
+
+    /// Removes all elements from both filters.
+    pub fn clear(&mut self) {
+        self.front.clear();
+        self.back.clear();
+        self.pending.clear();
+        self.front_len = 0;
+        self.hits = 0;
+        #[cfg(feature = "std")]
+        if let Some((interval, _)) = self.fold_interval {
+            self.fold_interval = Some((interval, Instant::now()));
+        }
+    }
+
+    fn insert_front(&mut self, hash: ElementHash) {
+        if self.front_len >= self.front.config.capacity() {
+            self.fold();
+        }
+        self.front.insert_hash(hash);
+        self.front_len += 1;
+    }
+}
+
+/// Folds if the interval of [TieredBloomFilter::fold_every] passed, so the back filter gets the
+/// adds of an idle filter as well.
+#[cfg(feature = "std")]
+impl Maintain for TieredBloomFilter {
+    fn maintain(&mut self, now: Instant) {
+        self.fold_if_due_at(now);
+    }
+}
+
+#[test]
+fn tiered_bloom_filter_test() {
+    let mut filter = FilterBuilder::new(100_000, 0.01).build_tiered_bloom_filter(1000);
+    for i in 0..10_500u32 {
+        filter.add(&i.to_le_bytes());
+    }
+    // folded on its own every 1000 adds
+    assert_eq!(filter.pending(), 500);
+    assert!((0..10_500u32).all(|i| filter.contains(&i.to_le_bytes())));
+    assert!((0..10_000u32).all(|i| filter.back().contains(&i.to_le_bytes())));
+    assert!((10_000..10_500u32).all(|i| filter.front().contains(&i.to_le_bytes())));
+    let false_positives = (10_500..110_500u32)
+        .filter(|i| filter.contains(&i.to_le_bytes()))
+        .count();
+    assert!(false_positives < 2 * 1300, "{false_positives} false positives");
+
+    filter.fold();
+    assert_eq!(filter.pending(), 0);
+    assert!(!filter.front().contains(&10_499u32.to_le_bytes()));
+    filter.promote_every(2);
+    assert!((0..100u32).all(|i| filter.lookup(&i.to_le_bytes())));
+    let promoted = (0..100u32).filter(|i| filter.front().contains(&i.to_le_bytes())).count();
+    assert!((50..55).contains(&promoted), "{promoted} promoted");
+    filter.promote_every(0);
+    assert!(filter.lookup(&200u32.to_le_bytes()));
+    assert!(!filter.front().contains(&200u32.to_le_bytes()));
+
+    filter.clear();
+    assert!(!filter.contains(&1u32.to_le_bytes()));
+}
+
+#[test]
+#[cfg(feature = "std")]
+fn tiered_bloom_filter_fold_every_test() {
+    let mut filter = FilterBuilder::new(100_000, 0.01).build_tiered_bloom_filter(1000);
+    filter.fold_every(Duration::from_millis(50));
+    filter.add(b"hello");
+    assert_eq!(filter.pending(), 1);
+    std::thread::sleep(Duration::from_millis(60));
+    filter.add(b"world");
+    assert_eq!(filter.pending(), 1);
+    assert!(filter.back().contains(b"hello"));
+    assert!(!filter.back().contains(b"world"));
+}
+
+#[test]
+#[cfg(feature = "std")]
+fn tiered_bloom_filter_maintain_test() {
+    let mut filter = FilterBuilder::new(100_000, 0.01).build_tiered_bloom_filter(1000);
+    filter.add(b"hello");
+    filter.maintain(Instant::now() + Duration::from_secs(3600));
+    assert_eq!(filter.pending(), 1);
+    filter.fold_every(Duration::from_secs(60));
+    let start = Instant::now();
+    filter.maintain(start);
+    assert_eq!(filter.pending(), 1);
+    // an idle filter folds on the tick
+    filter.maintain(start + Duration::from_secs(61));
+    assert_eq!(filter.pending(), 0);
+    assert!(filter.back().contains(b"hello"));
+}
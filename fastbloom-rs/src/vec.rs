@@ -1,106 +1,258 @@
+use alloc::alloc::{handle_alloc_error, GlobalAlloc, Layout};
+use alloc::boxed::Box;
+use alloc::vec;
+use alloc::vec::Vec;
 use core::mem::size_of;
+use core::ops::Range;
+use core::ptr::NonNull;
+use core::sync::atomic::{AtomicU32, AtomicUsize, Ordering};
+use smallvec::SmallVec;
+
+#[cfg(not(feature = "std"))]
+use crate::float::Float;
 
-use crate::builder::SUFFIX;
 
 const USIZE_LEN: usize = 64;
+#[cfg(test)]
 const COUNTER_PER_SLOT: usize = USIZE_LEN >> 2;
 
+/// log2 of the bits per storage word.
+const WORD_SHIFT: u32 = usize::BITS.trailing_zeros();
+/// Mask of the bit offset inside a storage word.
+const WORD_MASK: usize = usize::BITS as usize - 1;
+
+/// Converts a bit vector word from and to its in-memory representation: little-endian with the
+/// `canonical-u64` feature, native otherwise. Bitwise operations commute with the conversion.
+#[inline]
+pub(crate) fn le_word(word: usize) -> usize {
+    if cfg!(feature = "canonical-u64") { usize::from_le(word) } else { word }
+}
+
 /// bitmap only for bloom filter.
 #[derive(Debug)]
 #[derive(Clone)]
-pub(crate) struct BloomBitVec {
+pub(crate) struct BloomBitVec<S = Vec<usize>> {
     /// Internal representation of the bit vector
-    pub(crate) storage: Vec<usize>,
+    pub(crate) storage: S,
     /// The number of valid bits in the internal representation
     pub(crate) nbits: u64,
 }
 
 impl BloomBitVec {
     pub fn new(slots: usize) -> Self {
-        BloomBitVec {
-            storage: vec![0; slots],
-            nbits: (slots * COUNTER_PER_SLOT) as u64,
-        }
+        BloomBitVec::with_storage(vec![0; slots])
     }
     pub fn from_elem(slots: usize, bit: bool) -> Self {
-        BloomBitVec {
-            storage: vec![if bit { !0 } else { 0 }; slots],
-            nbits: (slots * COUNTER_PER_SLOT) as u64,
-        }
+        BloomBitVec::with_storage(vec![if bit { !0 } else { 0 }; slots])
+    }
+}
+
+impl<const N: usize> BloomBitVec<[u64; N]> {
+    /// Const version of [BloomBitVec::with_storage] for empty array storage.
+    pub(crate) const fn zeroed_array() -> Self {
+        BloomBitVec { storage: [0; N], nbits: N as u64 * u64::BITS as u64 }
+    }
+}
+
+impl<S: Storage> BloomBitVec<S> {
+    pub fn with_storage(storage: S) -> Self {
+        let nbits = storage.slots() as u64 * usize::BITS as u64;
+        BloomBitVec { storage, nbits }
     }
 
+    #[inline]
+    pub fn get(&self, index: usize) -> bool {
+        let (w, b) = (index >> WORD_SHIFT, index & WORD_MASK);
+        let flag = le_word(1 << b);
+        (self.storage.get(w) & flag) != 0
+    }
+
+    /// Returns the bit at `index` as `0` or `1`, without branching.
+    #[inline]
+    pub fn get_bit(&self, index: usize) -> usize {
+        let (w, b) = (index >> WORD_SHIFT, index & WORD_MASK);
+        (le_word(self.storage.get(w)) >> b) & 1
+    }
+
+    /// Returns word `w` with bit `b` standing for index `w * usize::BITS + b`, whatever the
+    /// in-memory representation.
+    #[inline]
+    pub fn word(&self, w: usize) -> usize {
+        le_word(self.storage.get(w))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.storage.slots() == 0
+    }
+
+    /// Returns the number of set bits.
+    pub fn count_ones(&self) -> u64 {
+        (0..self.storage.slots()).map(|slot| self.storage.get(slot).count_ones() as u64).sum()
+    }
+
+    /// Iterates over the indices of the set bits in ascending order.
+    pub fn ones(&self) -> impl Iterator<Item=u64> + '_ {
+        (0..self.storage.slots()).flat_map(|w| {
+            let mut word = self.word(w);
+            core::iter::from_fn(move || {
+                if word == 0 {
+                    return None;
+                }
+                let b = word.trailing_zeros() as u64;
+                word &= word - 1;
+                Some(w as u64 * usize::BITS as u64 + b)
+            })
+        })
+    }
+
+    /// Returns the number of 64-bit words, the last one padded with zeros if the storage has an
+    /// odd number of 32-bit words.
+    pub fn u64_words(&self) -> usize {
+        (self.storage.slots() * usize::BITS as usize).div_ceil(64)
+    }
+
+    /// Returns the 64-bit word `i`, bit `b` standing for index `64 * i + b` on all platforms.
+    #[inline]
+    pub fn word_u64(&self, i: usize) -> u64 {
+        let per_word = 64 / usize::BITS as usize;
+        let slots = self.storage.slots();
+        (0..per_word).filter(|j| i * per_word + j < slots).fold(0, |word, j| {
+            word | (self.word(i * per_word + j) as u64) << (j * usize::BITS as usize)
+        })
+    }
+
+    /// Returns the 64-bit words in `range`, see [BloomBitVec::word_u64].
+    pub fn words_u64(&self, range: Range<usize>) -> Vec<u64> {
+        assert!(range.end <= self.u64_words(), "word range out of bounds!");
+        range.map(|i| self.word_u64(i)).collect()
+    }
+}
+
+impl<S: StorageMut> BloomBitVec<S> {
     #[inline]
     pub fn set(&mut self, index: usize) {
-        #[cfg(target_pointer_width = "64")]
-            let w = index >> 6;
-        #[cfg(target_pointer_width = "32")]
-            let w = index >> 5;
-        let b = index & SUFFIX;
-        let flag = 1usize << b;
-        self.storage[w] = self.storage[w] | flag;
+        let (w, b) = (index >> WORD_SHIFT, index & WORD_MASK);
+        let flag = le_word(1 << b);
+        self.storage.update(w, |slot| Some(slot | flag));
     }
 
     #[inline]
-    pub fn get(&self, index: usize) -> bool {
-        #[cfg(target_pointer_width = "64")]
-            let w = index >> 6;
-        #[cfg(target_pointer_width = "32")]
-            let w = index >> 5;
-        let b = index & SUFFIX;
-        let flag = 1usize << b;
-        (self.storage[w] & flag) != 0
+    pub fn unset(&mut self, index: usize) {
+        let (w, b) = (index >> WORD_SHIFT, index & WORD_MASK);
+        let flag = le_word(1 << b);
+        self.storage.update(w, |slot| Some(slot & !flag));
     }
 
-    pub fn or(&mut self, other: &BloomBitVec) {
-        for (m, o) in self.storage.iter_mut().zip(&other.storage) {
-            *m |= *o;
+    #[inline]
+    fn combine<T: Storage>(&mut self, other: &BloomBitVec<T>,
+                           op: impl Fn(usize, usize) -> usize + Copy) {
+        if let (Some(words), Some(others)) = (self.storage.words_mut(), other.storage.words()) {
+            combine_words(words, others, op);
+            return;
+        }
+        let slots = self.storage.slots().min(other.storage.slots());
+        for w in 0..slots {
+            let o = other.storage.get(w);
+            self.storage.update(w, |m| Some(op(m, o)));
         }
     }
 
-    pub fn xor(&mut self, other: &BloomBitVec) {
-        for (m, o) in self.storage.iter_mut().zip(&other.storage) {
-            *m ^= *o;
+    /// Overwrites the 64-bit word `i`, see [BloomBitVec::word_u64].
+    #[inline]
+    pub fn set_word_u64(&mut self, i: usize, word: u64) {
+        let per_word = 64 / usize::BITS as usize;
+        let slots = self.storage.slots();
+        for j in (0..per_word).filter(|j| i * per_word + j < slots) {
+            let value = le_word((word >> (j * usize::BITS as usize)) as usize);
+            self.storage.update(i * per_word + j, |slot| (slot != value).then_some(value));
         }
     }
 
-    pub fn nor(&mut self, other: &Self) {
-        for (m, o) in self.storage.iter_mut().zip(&other.storage) {
-            *m = !(*m | *o);
+    /// Overwrites the 64-bit words from `offset` on with `words`, see [BloomBitVec::word_u64].
+    pub fn set_words_u64(&mut self, offset: usize, words: &[u64]) {
+        assert!(offset + words.len() <= self.u64_words(), "word range out of bounds!");
+        for (i, word) in words.iter().enumerate() {
+            self.set_word_u64(offset + i, *word);
         }
     }
 
-    pub fn xnor(&mut self, other: &Self) {
-        for (m, o) in self.storage.iter_mut().zip(&other.storage) {
-            *m = !(*m ^ *o);
-        }
+    pub fn or<T: Storage>(&mut self, other: &BloomBitVec<T>) {
+        self.combine(other, |m, o| m | o);
     }
 
-    pub fn and(&mut self, other: &BloomBitVec) {
-        for (m, o) in self.storage.iter_mut().zip(&other.storage) {
-            *m &= *o;
-        }
+    pub fn xor<T: Storage>(&mut self, other: &BloomBitVec<T>) {
+        self.combine(other, |m, o| m ^ o);
     }
 
-    pub fn nand(&mut self, other: &Self) {
-        for (m, o) in self.storage.iter_mut().zip(&other.storage) {
-            *m = !(*m & *o);
-        }
+    pub fn nor<T: Storage>(&mut self, other: &BloomBitVec<T>) {
+        self.combine(other, |m, o| !(m | o));
     }
 
-    pub fn difference(&mut self, other: &Self) {
-        for (m, o) in self.storage.iter_mut().zip(&other.storage) {
-            *m &= !*o;
-        }
+    pub fn xnor<T: Storage>(&mut self, other: &BloomBitVec<T>) {
+        self.combine(other, |m, o| !(m ^ o));
     }
 
+    pub fn and<T: Storage>(&mut self, other: &BloomBitVec<T>) {
+        self.combine(other, |m, o| m & o);
+    }
+
+    pub fn nand<T: Storage>(&mut self, other: &BloomBitVec<T>) {
+        self.combine(other, |m, o| !(m & o));
+    }
+
+    pub fn difference<T: Storage>(&mut self, other: &BloomBitVec<T>) {
+        self.combine(other, |m, o| m & !o);
+    }
 
     pub fn clear(&mut self) {
-        self.storage.fill(0);
+        self.storage.clear();
     }
+}
 
-    pub fn is_empty(&self) -> bool {
-        self.storage.is_empty()
+/// Replaces the words of `words` with `op` of them and the words of `others`, up to the shorter
+/// of both. With the `simd` feature on x86_64 the loop is compiled for AVX2 as well and picked
+/// at runtime if the CPU has it.
+#[inline]
+pub(crate) fn combine_words(words: &mut [usize], others: &[usize],
+                            op: impl Fn(usize, usize) -> usize + Copy) {
+    #[cfg(all(feature = "simd", feature = "std", target_arch = "x86_64"))]
+    if std::is_x86_feature_detected!("avx2") {
+        // SAFETY: the CPU supports AVX2.
+        unsafe { crate::simd::combine_words_avx2(words, others, op) };
+        return;
     }
+    combine_words_unrolled(words, others, op);
+}
+
+/// The loop of [combine_words], unrolled by [UNROLL] words which the compiler vectorizes.
+#[inline(always)]
+pub(crate) fn combine_words_unrolled(words: &mut [usize], others: &[usize],
+                                     op: impl Fn(usize, usize) -> usize + Copy) {
+    let len = words.len().min(others.len());
+    let mut chunks = words[..len].chunks_exact_mut(UNROLL);
+    let mut other_chunks = others[..len].chunks_exact(UNROLL);
+    for (chunk, other) in (&mut chunks).zip(&mut other_chunks) {
+        for i in 0..UNROLL {
+            chunk[i] = op(chunk[i], other[i]);
+        }
+    }
+    for (word, &other) in chunks.into_remainder().iter_mut().zip(other_chunks.remainder()) {
+        *word = op(*word, other);
+    }
+}
+
+/// Words combined per iteration of [combine_words_unrolled], 64 bytes on 64-bit targets.
+const UNROLL: usize = 8;
+
+/// Expected access pattern of a storage, passed on to the OS for disk-backed storage.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Advice {
+    /// The storage is read front to back, e.g. while merging or copying a filter.
+    Sequential,
+    /// The storage is read at random, the access pattern of queries.
+    Random,
+    /// The storage is needed soon and should be read ahead.
+    WillNeed,
 }
 
 pub trait Storage {
@@ -108,10 +260,48 @@ pub trait Storage {
     fn new(slots: usize, init: Self::Init) -> Self;
     fn get(&self, slot: usize) -> usize;
     fn slots(&self) -> usize;
+    /// Passes the expected access pattern on to the OS. Does nothing for in-memory storage.
+    fn advise(&self, _advice: Advice) {}
+    /// Reads one word of every page so that the whole storage is resident before the first
+    /// queries.
+    fn prefault(&self) {
+        let mut acc = 0;
+        for slot in (0..self.slots()).step_by(4096 / size_of::<usize>()) {
+            acc ^= self.get(slot);
+        }
+        core::hint::black_box(acc);
+    }
+    /// Locks the words in `slots` in RAM (`mlock`) for disk-backed storage, as far as the limits
+    /// of the process allow. Does nothing for in-memory storage.
+    fn pin(&self, _slots: Range<usize>) {}
+    /// Hints the CPU to load the word at `slot` into the cache ahead of its use, see the batch
+    /// operations of [BloomFilter](crate::BloomFilter) with the `simd` feature. Does nothing by
+    /// default.
+    #[inline]
+    fn prefetch(&self, _slot: usize) {}
+    /// Returns the words if they are a slice in memory, which lets unions and intersections
+    /// combine them in bulk instead of one [Storage::get] at a time. `None` by default.
+    #[inline]
+    fn words(&self) -> Option<&[usize]> {
+        None
+    }
 }
 pub trait StorageMut: Storage {
     fn update(&mut self, slot: usize, op: impl FnOnce(usize) -> Option<usize>);
     fn clear(&mut self);
+    /// Mutable version of [Storage::words], for storage which needs no bookkeeping on updates.
+    #[inline]
+    fn words_mut(&mut self) -> Option<&mut [usize]> {
+        None
+    }
+}
+/// Storage whose words can be updated through a shared reference, e.g. by many threads at once.
+pub trait AtomicStorage: Storage {
+    /// Replaces the word at `slot` with `op` of it atomically, calling `op` again if another
+    /// thread changed the word in the meantime. Returns the previous word, or `Err` with the
+    /// current one if `op` returned `None`.
+    fn fetch_update(&self, slot: usize, op: impl FnMut(usize) -> Option<usize>)
+                    -> Result<usize, usize>;
 }
 
 impl Storage for Vec<usize> {
@@ -128,6 +318,15 @@ impl Storage for Vec<usize> {
     fn slots(&self) -> usize {
         self.len()
     }
+    #[cfg(feature = "simd")]
+    #[inline]
+    fn prefetch(&self, slot: usize) {
+        crate::simd::prefetch(self[..].as_ptr().wrapping_add(slot))
+    }
+    #[inline]
+    fn words(&self) -> Option<&[usize]> {
+        Some(self)
+    }
 }
 impl StorageMut for Vec<usize> {
     #[inline]
@@ -141,16 +340,789 @@ impl StorageMut for Vec<usize> {
     fn clear(&mut self) {
         self.fill(0);
     }
+    #[inline]
+    fn words_mut(&mut self) -> Option<&mut [usize]> {
+        Some(self)
+    }
+}
+
+impl Storage for Box<[usize]> {
+    type Init = ();
+    #[inline]
+    fn new(slots: usize, _: ()) -> Self {
+        vec![0; slots].into_boxed_slice()
+    }
+    #[inline]
+    fn get(&self, slot: usize) -> usize {
+        self[slot]
+    }
+    #[inline]
+    fn slots(&self) -> usize {
+        self.len()
+    }
+    #[cfg(feature = "simd")]
+    #[inline]
+    fn prefetch(&self, slot: usize) {
+        crate::simd::prefetch(self[..].as_ptr().wrapping_add(slot))
+    }
+    #[inline]
+    fn words(&self) -> Option<&[usize]> {
+        Some(self)
+    }
+}
+impl StorageMut for Box<[usize]> {
+    #[inline]
+    fn update(&mut self, slot: usize, op: impl FnOnce(usize) -> Option<usize>) {
+        let v = self[slot];
+        if let Some(v) = op(v) {
+            self[slot] = v;
+        }
+    }
+    #[inline]
+    fn clear(&mut self) {
+        self.fill(0);
+    }
+    #[inline]
+    fn words_mut(&mut self) -> Option<&mut [usize]> {
+        Some(self)
+    }
+}
+
+/// Number of slots of a `u64` word.
+pub(crate) const SLOTS_PER_U64: usize = (u64::BITS / usize::BITS) as usize;
+
+/// Shift of a slot within its `u64` word.
+#[allow(clippy::modulo_one)] // a word holds a single slot on 64-bit targets
+#[inline]
+const fn u64_shift(slot: usize) -> usize {
+    slot % SLOTS_PER_U64 * usize::BITS as usize
+}
+
+/// Inline storage of `N` 64-bit words without heap allocation, see
+/// [ArrayBloomFilter](crate::ArrayBloomFilter), so the size of a filter doesn't depend on the
+/// target. A word holds two slots on 32-bit targets, the low half first. [Storage::new] panics
+/// unless `slots` is the number of slots of `N` words.
+impl<const N: usize> Storage for [u64; N] {
+    type Init = ();
+    #[inline]
+    fn new(slots: usize, _: ()) -> Self {
+        assert_eq!(slots, N * SLOTS_PER_U64,
+                   "array storage of {N} words can't hold {slots} slots!");
+        [0; N]
+    }
+    #[inline]
+    fn get(&self, slot: usize) -> usize {
+        let shift = u64_shift(slot);
+        (self[slot / SLOTS_PER_U64] >> shift) as usize
+    }
+    #[inline]
+    fn slots(&self) -> usize {
+        N * SLOTS_PER_U64
+    }
+    #[cfg(target_pointer_width = "64")]
+    #[inline]
+    fn words(&self) -> Option<&[usize]> {
+        // Safety: u64 and usize have the same layout on 64-bit targets.
+        Some(unsafe { core::slice::from_raw_parts(self.as_ptr().cast(), N) })
+    }
+}
+impl<const N: usize> StorageMut for [u64; N] {
+    #[inline]
+    fn update(&mut self, slot: usize, op: impl FnOnce(usize) -> Option<usize>) {
+        if let Some(v) = op(self.get(slot)) {
+            let shift = u64_shift(slot);
+            let word = &mut self[slot / SLOTS_PER_U64];
+            *word = *word & !((usize::MAX as u64) << shift) | (v as u64) << shift;
+        }
+    }
+    #[inline]
+    fn clear(&mut self) {
+        self.fill(0);
+    }
+    #[cfg(target_pointer_width = "64")]
+    #[inline]
+    fn words_mut(&mut self) -> Option<&mut [usize]> {
+        // Safety: see `words`.
+        Some(unsafe { core::slice::from_raw_parts_mut(self.as_mut_ptr().cast(), N) })
+    }
+}
+
+/// Storage of words allocated with a [GlobalAlloc] other than the global allocator, e.g. one
+/// backed by huge pages, which cuts the TLB misses of filters of many gigabytes, or an arena.
+/// The `Init` is the allocator; [Storage::new] allocates zeroed words with it, which are
+/// returned to it on drop. `Vec<usize, A>` of the unstable allocator API is not available on
+/// stable Rust, any `GlobalAlloc` works here. The words are aligned to 64 bytes, so each
+/// [BlockSize::CacheLine](crate::BlockSize::CacheLine) block of a
+/// [BlockedBloomFilter](crate::BlockedBloomFilter) is one cache line.
+///
+/// # Examples
+///
+/// ```rust
+/// use std::alloc::System;
+///
+/// use fastbloom_rs::{AllocStorage, FilterBuilder, Membership};
+///
+/// let mut builder = FilterBuilder::new(100_000, 0.01);
+/// let mut bloom = builder.build_bloom_filter_in::<AllocStorage<System>>(System);
+/// bloom.add(b"hello");
+/// assert_eq!(bloom.contains(b"hello"), true);
+/// let mut cbf = builder.build_counting_bloom_filter::<AllocStorage<System>>(System);
+/// cbf.add(b"hello");
+/// assert_eq!(cbf.estimate_count(b"hello"), 1);
+/// ```
+pub struct AllocStorage<A: GlobalAlloc> {
+    words: NonNull<usize>,
+    slots: usize,
+    alloc: A,
+}
+
+// Safety: the storage owns its words like a `Box<[usize]>`.
+unsafe impl<A: GlobalAlloc + Send> Send for AllocStorage<A> {}
+unsafe impl<A: GlobalAlloc + Sync> Sync for AllocStorage<A> {}
+
+impl<A: GlobalAlloc> AllocStorage<A> {
+    /// Allocates `slots` zeroed words with `alloc`. Panics if the allocation fails.
+    pub fn new_in(slots: usize, alloc: A) -> Self {
+        let words = if slots == 0 {
+            NonNull::dangling()
+        } else {
+            let layout = Self::layout(slots);
+            // Safety: the layout is not empty.
+            let ptr = unsafe { alloc.alloc_zeroed(layout) };
+            NonNull::new(ptr.cast()).unwrap_or_else(|| handle_alloc_error(layout))
+        };
+        AllocStorage { words, slots, alloc }
+    }
+
+    /// Returns the allocator of the words.
+    pub fn allocator(&self) -> &A {
+        &self.alloc
+    }
+
+    /// Returns the words.
+    pub fn as_slice(&self) -> &[usize] {
+        // Safety: the storage owns `slots` initialized words.
+        unsafe { core::slice::from_raw_parts(self.words.as_ptr(), self.slots) }
+    }
+
+    /// Returns the words mutably.
+    pub fn as_mut_slice(&mut self) -> &mut [usize] {
+        // Safety: see `as_slice`.
+        unsafe { core::slice::from_raw_parts_mut(self.words.as_ptr(), self.slots) }
+    }
+
+    fn layout(slots: usize) -> Layout {
+        Layout::array::<usize>(slots)
+            .and_then(|layout| layout.align_to(64))
+            .expect("storage is too large!")
+    }
+}
+
+impl<A: GlobalAlloc> Drop for AllocStorage<A> {
+    fn drop(&mut self) {
+        if self.slots > 0 {
+            // Safety: the words were allocated by `alloc` with this layout.
+            unsafe { self.alloc.dealloc(self.words.as_ptr().cast(), Self::layout(self.slots)) }
+        }
+    }
+}
+
+impl<A: GlobalAlloc + Clone> Clone for AllocStorage<A> {
+    fn clone(&self) -> Self {
+        let mut clone = AllocStorage::new_in(self.slots, self.alloc.clone());
+        clone.as_mut_slice().copy_from_slice(self.as_slice());
+        clone
+    }
+}
+
+impl<A: GlobalAlloc> core::fmt::Debug for AllocStorage<A> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("AllocStorage").field("slots", &self.slots).finish_non_exhaustive()
+    }
+}
+
+impl<A: GlobalAlloc> Storage for AllocStorage<A> {
+    type Init = A;
+    #[inline]
+    fn new(slots: usize, alloc: A) -> Self {
+        AllocStorage::new_in(slots, alloc)
+    }
+    #[inline]
+    fn get(&self, slot: usize) -> usize {
+        self.as_slice()[slot]
+    }
+    #[inline]
+    fn slots(&self) -> usize {
+        self.slots
+    }
+    #[cfg(feature = "simd")]
+    #[inline]
+    fn prefetch(&self, slot: usize) {
+        crate::simd::prefetch(self.as_slice().as_ptr().wrapping_add(slot))
+    }
+    #[inline]
+    fn words(&self) -> Option<&[usize]> {
+        Some(self.as_slice())
+    }
+}
+impl<A: GlobalAlloc> StorageMut for AllocStorage<A> {
+    #[inline]
+    fn update(&mut self, slot: usize, op: impl FnOnce(usize) -> Option<usize>) {
+        let words = self.as_mut_slice();
+        if let Some(v) = op(words[slot]) {
+            words[slot] = v;
+        }
+    }
+    #[inline]
+    fn clear(&mut self) {
+        self.as_mut_slice().fill(0);
+    }
+    #[inline]
+    fn words_mut(&mut self) -> Option<&mut [usize]> {
+        Some(self.as_mut_slice())
+    }
+}
+
+/// Borrowed storage, e.g. a pre-allocated buffer, an arena or a shared-memory segment. The
+/// `Init` is the buffer itself; [Storage::new] zeroes its first `slots` words and panics when the
+/// buffer is too small.
+impl<'a> Storage for &'a mut [usize] {
+    type Init = &'a mut [usize];
+    #[inline]
+    fn new(slots: usize, init: &'a mut [usize]) -> Self {
+        assert!(init.len() >= slots, "buffer of {} slots is too small, {} slots required!",
+                init.len(), slots);
+        let buf = &mut init[..slots];
+        buf.fill(0);
+        buf
+    }
+    #[inline]
+    fn get(&self, slot: usize) -> usize {
+        self[slot]
+    }
+    #[inline]
+    fn slots(&self) -> usize {
+        self.len()
+    }
+    #[cfg(feature = "simd")]
+    #[inline]
+    fn prefetch(&self, slot: usize) {
+        crate::simd::prefetch(self[..].as_ptr().wrapping_add(slot))
+    }
+    #[inline]
+    fn words(&self) -> Option<&[usize]> {
+        Some(self)
+    }
+}
+impl StorageMut for &mut [usize] {
+    #[inline]
+    fn update(&mut self, slot: usize, op: impl FnOnce(usize) -> Option<usize>) {
+        let v = self[slot];
+        if let Some(v) = op(v) {
+            self[slot] = v;
+        }
+    }
+    #[inline]
+    fn clear(&mut self) {
+        self.fill(0);
+    }
+    #[inline]
+    fn words_mut(&mut self) -> Option<&mut [usize]> {
+        Some(self)
+    }
+}
+
+/// Storage of atomic words, shared by the threads adding to an
+/// [AtomicBloomFilter](crate::AtomicBloomFilter). Words are loaded with relaxed ordering.
+impl Storage for Vec<AtomicUsize> {
+    type Init = ();
+    #[inline]
+    fn new(slots: usize, _: ()) -> Self {
+        (0..slots).map(|_| AtomicUsize::new(0)).collect()
+    }
+    #[inline]
+    fn get(&self, slot: usize) -> usize {
+        self[slot].load(Ordering::Relaxed)
+    }
+    #[inline]
+    fn slots(&self) -> usize {
+        self.len()
+    }
+    #[cfg(feature = "simd")]
+    #[inline]
+    fn prefetch(&self, slot: usize) {
+        crate::simd::prefetch(self[..].as_ptr().wrapping_add(slot))
+    }
+}
+impl StorageMut for Vec<AtomicUsize> {
+    #[inline]
+    fn update(&mut self, slot: usize, op: impl FnOnce(usize) -> Option<usize>) {
+        let v = self[slot].get_mut();
+        if let Some(new) = op(*v) {
+            *v = new;
+        }
+    }
+    #[inline]
+    fn clear(&mut self) {
+        self.iter_mut().for_each(|word| *word.get_mut() = 0);
+    }
+}
+impl AtomicStorage for Vec<AtomicUsize> {
+    #[inline]
+    fn fetch_update(&self, slot: usize, op: impl FnMut(usize) -> Option<usize>)
+                    -> Result<usize, usize> {
+        self[slot].fetch_update(Ordering::Relaxed, Ordering::Relaxed, op)
+    }
 }
 
-/// counter vector for counting bloom filter.
+/// Read-only borrowed storage of the words in native byte order, e.g. a memory-mapped file; the
+/// bytes need not be aligned. The `Init` is the buffer itself; [Storage::new] keeps its content
+/// and panics when the buffer is too small.
+impl<'a> Storage for &'a [u8] {
+    type Init = &'a [u8];
+    #[inline]
+    fn new(slots: usize, init: &'a [u8]) -> Self {
+        let len = slots * size_of::<usize>();
+        assert!(init.len() >= len, "buffer of {} bytes is too small, {} bytes required!",
+                init.len(), len);
+        &init[..len]
+    }
+    #[inline]
+    fn get(&self, slot: usize) -> usize {
+        let start = slot * size_of::<usize>();
+        usize::from_ne_bytes(self[start..start + size_of::<usize>()].try_into().unwrap())
+    }
+    #[inline]
+    fn slots(&self) -> usize {
+        self.len() / size_of::<usize>()
+    }
+    #[cfg(feature = "simd")]
+    #[inline]
+    fn prefetch(&self, slot: usize) {
+        crate::simd::prefetch(self.as_ptr().wrapping_add(slot * size_of::<usize>()))
+    }
+}
+
+/// Sparse storage keeping only the non-zero words as `(slot, word)` pairs sorted by slot. For huge
+/// but barely filled filters this takes a fraction of the memory of a dense `Vec<usize>` and is
+/// fast to serialize; lookups are a binary search over the non-zero words.
+///
+/// # Examples
+///
+/// ```rust
+/// use fastbloom_rs::{FilterBuilder, Membership};
+///
+/// let mut bloom = FilterBuilder::new(10_000_000, 0.01).build_bloom_filter();
+/// bloom.add(b"hello");
+/// let sparse = bloom.to_sparse();
+/// assert!(sparse.storage().is_smaller_than_dense());
+/// assert_eq!(sparse.contains(b"hello"), true);
+/// assert_eq!(sparse.contains(b"world"), false);
+/// ```
+#[derive(Debug)]
+#[derive(Clone)]
+pub struct SparseStorage {
+    slots: usize,
+    words: Vec<(usize, usize)>,
+}
+
+impl SparseStorage {
+    /// Copies the non-zero words of `dense`.
+    pub fn from_dense<S: Storage>(dense: &S) -> Self {
+        let words = (0..dense.slots())
+            .map(|slot| (slot, dense.get(slot)))
+            .filter(|(_, word)| *word != 0)
+            .collect();
+        SparseStorage { slots: dense.slots(), words }
+    }
+
+    /// Expands to a dense vector of words.
+    pub fn to_dense(&self) -> Vec<usize> {
+        let mut dense = vec![0; self.slots];
+        for (slot, word) in &self.words {
+            dense[*slot] = *word;
+        }
+        dense
+    }
+
+    /// Returns the number of non-zero words.
+    pub fn nonzero_words(&self) -> usize {
+        self.words.len()
+    }
+
+    /// Returns the non-zero `(slot, word)` pairs in slot order.
+    pub fn entries(&self) -> impl Iterator<Item=(usize, usize)> + '_ {
+        self.words.iter().copied()
+    }
+
+    /// Returns [true] while this representation takes less memory than the dense one, i.e. while
+    /// less than half of the words are non-zero.
+    pub fn is_smaller_than_dense(&self) -> bool {
+        self.words.len() * 2 < self.slots
+    }
+}
+
+impl Storage for SparseStorage {
+    type Init = ();
+    #[inline]
+    fn new(slots: usize, _: ()) -> Self {
+        SparseStorage { slots, words: Vec::new() }
+    }
+    #[inline]
+    fn get(&self, slot: usize) -> usize {
+        assert!(slot < self.slots, "slot {} out of range {}", slot, self.slots);
+        match self.words.binary_search_by_key(&slot, |(s, _)| *s) {
+            Ok(i) => self.words[i].1,
+            Err(_) => 0,
+        }
+    }
+    #[inline]
+    fn slots(&self) -> usize {
+        self.slots
+    }
+}
+impl StorageMut for SparseStorage {
+    #[inline]
+    fn update(&mut self, slot: usize, op: impl FnOnce(usize) -> Option<usize>) {
+        assert!(slot < self.slots, "slot {} out of range {}", slot, self.slots);
+        match self.words.binary_search_by_key(&slot, |(s, _)| *s) {
+            Ok(i) => match op(self.words[i].1) {
+                Some(0) => { self.words.remove(i); }
+                Some(v) => self.words[i].1 = v,
+                None => {}
+            },
+            Err(i) => match op(0) {
+                Some(0) | None => {}
+                Some(v) => self.words.insert(i, (slot, v)),
+            },
+        }
+    }
+    #[inline]
+    fn clear(&mut self) {
+        self.words.clear();
+    }
+}
+
+/// Write statistics of a [WriteTracking] storage since its last flush.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct WriteStats {
+    /// Number of updates which changed a word.
+    pub writes: u64,
+    /// Number of distinct words which were changed.
+    pub dirty_words: u64,
+    /// Number of distinct pages which were changed.
+    pub dirty_pages: u64,
+    /// Size of a page in bytes.
+    pub page_size: usize,
+}
+
+impl WriteStats {
+    /// Bytes written back when flushing the dirty pages divided by the bytes actually changed,
+    /// `1.0` when nothing was changed.
+    pub fn write_amplification(&self) -> f64 {
+        if self.dirty_words == 0 {
+            return 1.0;
+        }
+        let changed = self.dirty_words * core::mem::size_of::<usize>() as u64;
+        (self.dirty_pages * self.page_size as u64) as f64 / changed as f64
+    }
+}
+
+/// Storage wrapper recording which words and pages of the inner storage were dirtied, so the
+/// write amplification of file-backed storage can be measured per flush.
+///
+/// # Examples
+///
+/// ```rust
+/// use fastbloom_rs::{BloomFilter, Membership, WriteTracking};
+///
+/// let storage = WriteTracking::wrap(vec![0usize; 1024], 4096);
+/// let mut bloom = BloomFilter::from_storage(storage, 4);
+/// bloom.add(b"hello");
+/// let stats = bloom.storage().stats();
+/// assert!(stats.dirty_words >= 1 && stats.dirty_pages >= 1);
+/// ```
+#[derive(Debug)]
+#[derive(Clone)]
+pub struct WriteTracking<S> {
+    inner: S,
+    page_words: usize,
+    dirty_words: Vec<u64>,
+    dirty_pages: Vec<u64>,
+    stats: WriteStats,
+}
+
+impl<S: Storage> WriteTracking<S> {
+    /// Wraps `inner`, grouping its words in pages of `page_size` bytes.
+    pub fn wrap(inner: S, page_size: usize) -> Self {
+        let page_words = (page_size / core::mem::size_of::<usize>()).max(1);
+        let slots = inner.slots();
+        let pages = slots.div_ceil(page_words);
+        WriteTracking {
+            inner,
+            page_words,
+            dirty_words: vec![0; slots.div_ceil(64)],
+            dirty_pages: vec![0; pages.div_ceil(64)],
+            stats: WriteStats { page_size, ..WriteStats::default() },
+        }
+    }
+
+    /// Returns the statistics since the last flush.
+    pub fn stats(&self) -> WriteStats {
+        self.stats
+    }
+
+    /// Returns the statistics since the last flush and starts a new flush interval. Call it when
+    /// the dirty pages are written back.
+    pub fn take_stats(&mut self) -> WriteStats {
+        self.dirty_words.fill(0);
+        self.dirty_pages.fill(0);
+        let page_size = self.stats.page_size;
+        core::mem::replace(&mut self.stats, WriteStats { page_size, ..WriteStats::default() })
+    }
+
+    /// Returns the wrapped storage.
+    pub fn inner(&self) -> &S {
+        &self.inner
+    }
+
+    /// Unwraps the inner storage.
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+
+    /// Iterates over the words changed since the last [WriteTracking::take_stats].
+    pub(crate) fn dirty_slots(&self) -> impl Iterator<Item=usize> + '_ {
+        self.dirty_words.iter().enumerate().flat_map(|(i, bits)| {
+            (0..64).filter(move |b| bits & (1 << b) != 0).map(move |b| i * 64 + b)
+        })
+    }
+
+    #[inline]
+    fn mark(&mut self, slot: usize) {
+        self.stats.writes += 1;
+        if mark_bit(&mut self.dirty_words, slot) {
+            self.stats.dirty_words += 1;
+            if mark_bit(&mut self.dirty_pages, slot / self.page_words) {
+                self.stats.dirty_pages += 1;
+            }
+        }
+    }
+}
+
+/// Sets bit `index` of `bits`, returns whether it was unset before.
+#[inline]
+fn mark_bit(bits: &mut [u64], index: usize) -> bool {
+    let flag = 1u64 << (index & 63);
+    let unset = bits[index >> 6] & flag == 0;
+    bits[index >> 6] |= flag;
+    unset
+}
+
+impl<S: Storage> Storage for WriteTracking<S> {
+    type Init = (S::Init, usize);
+    #[inline]
+    fn new(slots: usize, (init, page_size): (S::Init, usize)) -> Self {
+        WriteTracking::wrap(S::new(slots, init), page_size)
+    }
+    #[inline]
+    fn get(&self, slot: usize) -> usize {
+        self.inner.get(slot)
+    }
+    #[inline]
+    fn slots(&self) -> usize {
+        self.inner.slots()
+    }
+    fn advise(&self, advice: Advice) {
+        self.inner.advise(advice)
+    }
+    fn prefault(&self) {
+        self.inner.prefault()
+    }
+    fn pin(&self, slots: Range<usize>) {
+        self.inner.pin(slots)
+    }
+    #[inline]
+    fn prefetch(&self, slot: usize) {
+        self.inner.prefetch(slot)
+    }
+}
+impl<S: StorageMut> StorageMut for WriteTracking<S> {
+    #[inline]
+    fn update(&mut self, slot: usize, op: impl FnOnce(usize) -> Option<usize>) {
+        let mut changed = false;
+        self.inner.update(slot, |v| {
+            let new = op(v);
+            changed = matches!(new, Some(n) if n != v);
+            new
+        });
+        if changed {
+            self.mark(slot);
+        }
+    }
+    #[inline]
+    fn clear(&mut self) {
+        self.inner.clear();
+        for slot in 0..self.inner.slots() {
+            self.mark(slot);
+        }
+    }
+}
+
+/// One in this many words is sampled by [AccessSampling].
+const ACCESS_SAMPLE_WORDS: usize = 64;
+
+/// Storage wrapper sampling which segments of the inner storage are probed, so the hottest ones
+/// of a disk-backed filter larger than RAM can be pinned with
+/// [AccessSampling::pin_hot_segments], bounding the tail latency. Only reads of one in 64 words,
+/// or of the first word of smaller segments, are counted, the same number in every segment,
+/// which keeps the sampling cheap.
+///
+/// # Examples
+///
+/// ```rust
+/// use fastbloom_rs::{AccessSampling, BloomFilter, Membership};
+///
+/// let storage = AccessSampling::wrap(vec![0usize; 1 << 16], 4096);
+/// let mut bloom = BloomFilter::from_storage(storage, 4);
+/// for i in 0..1000u32 {
+///     bloom.contains(&i.to_le_bytes());
+/// }
+/// let segments = bloom.storage().segments();
+/// let hot = (segments as f64 * 0.1).ceil() as usize;
+/// assert_eq!(bloom.storage().pin_hot_segments(0.1), hot);
+/// ```
+#[derive(Debug)]
+pub struct AccessSampling<S> {
+    inner: S,
+    /// Log2 of the words of a segment.
+    segment_shift: u32,
+    /// Mask of the word offsets which are not sampled.
+    sample_mask: usize,
+    probes: Vec<AtomicU32>,
+}
+
+impl<S: Storage> AccessSampling<S> {
+    /// Wraps `inner`, grouping its words in segments of `segment_size` bytes, rounded down to a
+    /// power of two words.
+    pub fn wrap(inner: S, segment_size: usize) -> Self {
+        let segment_shift = (segment_size / size_of::<usize>()).max(1).ilog2();
+        let sample_mask = (1 << segment_shift).min(ACCESS_SAMPLE_WORDS) - 1;
+        let segments = inner.slots().div_ceil(1 << segment_shift);
+        let probes = (0..segments).map(|_| AtomicU32::new(0)).collect();
+        AccessSampling { inner, segment_shift, sample_mask, probes }
+    }
+
+    /// Returns the number of segments.
+    pub fn segments(&self) -> usize {
+        self.probes.len()
+    }
+
+    /// Returns the word ranges of the `fraction` (between `0.0` and `1.0`) of the segments which
+    /// were probed the most, hottest first.
+    pub fn hot_segments(&self, fraction: f64) -> Vec<Range<usize>> {
+        assert!((0.0..=1.0).contains(&fraction), "fraction must between [0.0, 1.0]!");
+        let mut segments: Vec<(u32, usize)> = self.probes.iter().enumerate()
+            .map(|(segment, probes)| (probes.load(Ordering::Relaxed), segment))
+            .collect();
+        segments.sort_unstable_by(|a, b| b.cmp(a));
+        let hot = (segments.len() as f64 * fraction).ceil() as usize;
+        segments.iter().take(hot).map(|(_, segment)| {
+            let start = segment << self.segment_shift;
+            start..(start + (1 << self.segment_shift)).min(self.inner.slots())
+        }).collect()
+    }
+
+    /// Pins the `fraction` of the segments which were probed the most, see [Storage::pin].
+    /// Returns the number of pinned segments.
+    pub fn pin_hot_segments(&self, fraction: f64) -> usize {
+        let hot = self.hot_segments(fraction);
+        for slots in hot.iter() {
+            self.inner.pin(slots.clone());
+        }
+        hot.len()
+    }
+
+    /// Resets the sampled probe counts.
+    pub fn reset(&self) {
+        for probes in self.probes.iter() {
+            probes.store(0, Ordering::Relaxed);
+        }
+    }
+
+    /// Returns the wrapped storage.
+    pub fn inner(&self) -> &S {
+        &self.inner
+    }
+}
+
+impl<S: Storage> Storage for AccessSampling<S> {
+    type Init = (S::Init, usize);
+    #[inline]
+    fn new(slots: usize, (init, segment_size): (S::Init, usize)) -> Self {
+        AccessSampling::wrap(S::new(slots, init), segment_size)
+    }
+    #[inline]
+    fn get(&self, slot: usize) -> usize {
+        if slot & self.sample_mask == 0 {
+            self.probes[slot >> self.segment_shift].fetch_add(1, Ordering::Relaxed);
+        }
+        self.inner.get(slot)
+    }
+    #[inline]
+    fn slots(&self) -> usize {
+        self.inner.slots()
+    }
+    fn advise(&self, advice: Advice) {
+        self.inner.advise(advice)
+    }
+    fn prefault(&self) {
+        self.inner.prefault()
+    }
+    fn pin(&self, slots: Range<usize>) {
+        self.inner.pin(slots)
+    }
+    #[inline]
+    fn prefetch(&self, slot: usize) {
+        self.inner.prefetch(slot)
+    }
+}
+impl<S: StorageMut> StorageMut for AccessSampling<S> {
+    #[inline]
+    fn update(&mut self, slot: usize, op: impl FnOnce(usize) -> Option<usize>) {
+        self.inner.update(slot, op)
+    }
+    #[inline]
+    fn clear(&mut self) {
+        self.inner.clear()
+    }
+}
+
+/// counter vector for counting bloom filter, with counters of `W` bits (2, 4, 8 or 16) packed
+/// into 64-bit words starting at the most significant bits.
 #[derive(Debug)]
 #[derive(Clone)]
-pub(crate) struct CountingVec<S> {
+pub(crate) struct CountingVec<S, const W: usize = 4> {
     /// Internal representation of the vector
     pub(crate) storage: S,
 }
-impl<S: Storage> CountingVec<S> {
+impl<S, const W: usize> CountingVec<S, W> {
+    /// Number of counters per word.
+    pub(crate) const PER_SLOT: usize = {
+        assert!(matches!(W, 2 | 4 | 8 | 16 | 32 | 64) && W <= usize::BITS as usize,
+                "counter width must be 2, 4, 8, 16, 32 or 64 bits (at most a word)!");
+        USIZE_LEN / W
+    };
+    /// Largest value of a counter, at which it saturates.
+    pub(crate) const MAX: usize = usize::MAX >> (usize::BITS as usize - W);
+
+    /// Returns the word of the counter at `index` and the shift of the counter in the word.
+    #[inline]
+    fn position(index: usize) -> (usize, usize) {
+        (index / Self::PER_SLOT, (Self::PER_SLOT - 1 - index % Self::PER_SLOT) * W)
+    }
+}
+impl<S: Storage, const W: usize> CountingVec<S, W> {
     /// create a CountingVec
     pub fn new(storage: S) -> Self {
         CountingVec {
@@ -160,54 +1132,191 @@ impl<S: Storage> CountingVec<S> {
 
     #[inline]
     pub fn get(&self, index: usize) -> usize {
-        let w = index >> 4;
-        let b = index & 0b1111;
-        let slot = self.storage.get(w);
-        (slot >> ((15 - b) * 4)) & 0b1111
+        let (w, shift) = Self::position(index);
+        (self.storage.get(w) >> shift) & Self::MAX
     }
 
     pub fn counters(&self) -> usize {
-        self.storage.slots() * COUNTER_PER_SLOT
+        self.storage.slots() * Self::PER_SLOT
+    }
+
+    /// Gathers the counters at `indices`, loading each word only once for consecutive indices
+    /// in the same word.
+    #[inline]
+    pub fn get_many(&self, indices: impl IntoIterator<Item=u64>) -> SmallVec<[usize; 16]> {
+        let mut counters = SmallVec::new();
+        let (mut loaded, mut slot) = (usize::MAX, 0);
+        for index in indices {
+            let (w, shift) = Self::position(index as usize);
+            if w != loaded {
+                slot = self.storage.get(w);
+                loaded = w;
+            }
+            counters.push((slot >> shift) & Self::MAX);
+        }
+        counters
+    }
+
+    /// Iterates over all counters in index order, loading every word once.
+    pub fn iter(&self) -> impl Iterator<Item=usize> + '_ {
+        (0..self.storage.slots()).flat_map(move |w| {
+            let slot = self.storage.get(w);
+            (0..Self::PER_SLOT).rev().map(move |b| (slot >> (b * W)) & Self::MAX)
+        })
     }
 }
-impl<S: StorageMut> CountingVec<S> {
+impl<S: StorageMut, const W: usize> CountingVec<S, W> {
+    /// Increments the counter at `index`, returns [false] if it is saturated at
+    /// [CountingVec::MAX] and stays there.
     #[inline]
-    pub fn increment(&mut self, index: usize) {
-        let w = index >> 4;
-        let b = index & 0b1111;
+    pub fn increment(&mut self, index: usize) -> bool {
+        let (w, shift) = Self::position(index);
+        let mut incremented = false;
         self.storage.update(w, |slot| {
-            let current = (slot >> ((15 - b) * 4)) & 0b1111;
-            if current != 0b1111 {
-                let current = current + 1;
-                let move_bits = (15 - b) * 4;
-                Some((slot & !(0b1111 << move_bits)) | (current << move_bits))
-            } else {
-                None
-            }
+            let current = (slot >> shift) & Self::MAX;
+            incremented = current != Self::MAX;
+            incremented.then(|| slot + (1 << shift))
         });
+        incremented
     }
 
     #[inline]
     pub fn decrement(&mut self, index: usize) {
-        let w = index >> 4;
-        let b = index & 0b1111;
+        let (w, shift) = Self::position(index);
         self.storage.update(w, |slot| {
-            let current = (slot >> ((15 - b) * 4)) & 0b1111;
+            let current = (slot >> shift) & Self::MAX;
             if current > 0 {
-                let current = current - 1;
-                let w = index >> 4;
-                let b = index & 0b1111;
-                let move_bits = (15 - b) * 4;
-                Some((slot & !(0b1111 << move_bits)) | (current << move_bits))
+                Some(slot - (1 << shift))
             } else {
                 None
             }
         });
     }
 
+    /// Sets the counter at `index` to `value`, which must not exceed [CountingVec::MAX].
+    #[inline]
+    pub fn set(&mut self, index: usize, value: usize) {
+        let (w, shift) = Self::position(index);
+        self.storage.update(w, |slot| {
+            let updated = slot & !(Self::MAX << shift) | value << shift;
+            (updated != slot).then_some(updated)
+        });
+    }
+
     pub fn clear(&mut self) {
         self.storage.clear();
     }
+
+    /// Adds the counters of `other`, which has as many words, saturating each counter.
+    pub fn add<T: Storage>(&mut self, other: &CountingVec<T, W>) {
+        for w in 0..self.storage.slots() {
+            let theirs = other.storage.get(w);
+            self.storage.update(w, |ours| {
+                let sum = (0..Self::PER_SLOT).map(|c| c * W).fold(0, |sum, shift| {
+                    let counter = ((ours >> shift) & Self::MAX)
+                        .saturating_add((theirs >> shift) & Self::MAX);
+                    sum | counter.min(Self::MAX) << shift
+                });
+                (sum != ours).then_some(sum)
+            });
+        }
+    }
+
+    /// Subtracts the counters of `other`, which has as many words, stopping at zero. Saturated
+    /// counters stay saturated, their count is unknown.
+    pub fn sub<T: Storage>(&mut self, other: &CountingVec<T, W>) {
+        for w in 0..self.storage.slots() {
+            let theirs = other.storage.get(w);
+            self.storage.update(w, |ours| {
+                let diff = (0..Self::PER_SLOT).map(|c| c * W).fold(0, |diff, shift| {
+                    let counter = (ours >> shift) & Self::MAX;
+                    let counter = if counter == Self::MAX {
+                        counter
+                    } else {
+                        counter.saturating_sub((theirs >> shift) & Self::MAX)
+                    };
+                    diff | counter << shift
+                });
+                (diff != ours).then_some(diff)
+            });
+        }
+    }
+}
+
+impl<S: AtomicStorage, const W: usize> CountingVec<S, W> {
+    /// Like [CountingVec::increment] through a shared reference.
+    #[inline]
+    pub fn increment_shared(&self, index: usize) -> bool {
+        let (w, shift) = Self::position(index);
+        self.storage.fetch_update(w, |slot| {
+            ((slot >> shift) & Self::MAX != Self::MAX).then(|| slot + (1 << shift))
+        }).is_ok()
+    }
+
+    /// Like [CountingVec::decrement] through a shared reference, returns whether the counter
+    /// was non-zero.
+    #[inline]
+    pub fn decrement_shared(&self, index: usize) -> bool {
+        let (w, shift) = Self::position(index);
+        self.storage.fetch_update(w, |slot| {
+            ((slot >> shift) & Self::MAX != 0).then(|| slot - (1 << shift))
+        }).is_ok()
+    }
+}
+
+#[test]
+fn test_combine_words() {
+    let mut state = 0x9e3779b97f4a7c15u64;
+    let mut random = || {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        state as usize
+    };
+    // not a multiple of the unrolling, and the other vector shorter
+    let words: Vec<usize> = (0..67).map(|_| random()).collect();
+    let others: Vec<usize> = (0..61).map(|_| random()).collect();
+    type Combine = fn(&mut BloomBitVec, &BloomBitVec);
+    type WordOp = fn(usize, usize) -> usize;
+    let ops: [(Combine, WordOp); 7] = [
+        (BloomBitVec::or, |m, o| m | o),
+        (BloomBitVec::and, |m, o| m & o),
+        (BloomBitVec::xor, |m, o| m ^ o),
+        (BloomBitVec::nor, |m, o| !(m | o)),
+        (BloomBitVec::xnor, |m, o| !(m ^ o)),
+        (BloomBitVec::nand, |m, o| !(m & o)),
+        (BloomBitVec::difference, |m, o| m & !o),
+    ];
+    for (combine, op) in ops {
+        let mut vec = BloomBitVec::with_storage(words.clone());
+        combine(&mut vec, &BloomBitVec::with_storage(others.clone()));
+        let expected: Vec<usize> = words.iter().enumerate()
+            .map(|(i, &m)| others.as_slice().get(i).map_or(m, |&o| op(m, o)))
+            .collect();
+        assert_eq!(vec.storage, expected);
+
+        // storage without a word slice takes the scalar path
+        let mut sparse = BloomBitVec::with_storage(SparseStorage::from_dense(&words));
+        let others = BloomBitVec::with_storage(SparseStorage::from_dense(&others));
+        sparse.combine(&others, op);
+        assert_eq!(sparse.storage.to_dense(), expected);
+    }
+}
+
+#[test]
+fn test_words_u64() {
+    let mut vec = BloomBitVec::new(8);
+    vec.set(3);
+    vec.set(64 + 5);
+    vec.set(511);
+    assert_eq!(vec.u64_words(), 8);
+    assert_eq!(vec.words_u64(0..2), vec![1 << 3, 1 << 5]);
+    assert_eq!(vec.words_u64(7..8), vec![1 << 63]);
+
+    let mut copy = BloomBitVec::new(8);
+    copy.set(100);
+    copy.set_words_u64(1, &vec.words_u64(1..8));
+    assert_eq!(copy.ones().collect::<Vec<_>>(), vec![64 + 5, 511]);
 }
 
 #[test]
@@ -220,6 +1329,140 @@ fn test_vec() {
     assert_eq!(vec.get(38), true);
 }
 
+#[test]
+fn test_ones() {
+    let mut vec = BloomBitVec::new(4);
+    for index in [0, 63, 64, 200, 255] {
+        vec.set(index);
+    }
+    assert_eq!(vec.ones().collect::<Vec<_>>(), vec![0, 63, 64, 200, 255]);
+    assert_eq!(vec.count_ones(), 5);
+    assert_eq!(BloomBitVec::new(4).ones().count(), 0);
+}
+
+#[test]
+fn test_borrowed_storage() {
+    let mut buf = vec![!0usize; 8];
+    {
+        let mut vec = CountingVec::<_>::new(<&mut [usize]>::new(4, &mut buf));
+        vec.increment(3);
+        assert_eq!(vec.get(3), 1);
+        assert_eq!(vec.counters(), 64);
+    }
+    assert_ne!(buf[0], 0);
+    assert_eq!(buf[1..4], [0, 0, 0]);
+    assert_eq!(buf[4], !0);
+
+    let mut vec = BloomBitVec::with_storage(Box::<[usize]>::new(2, ()));
+    vec.set(7);
+    assert_eq!(vec.get(7), true);
+    assert_eq!(vec.get(8), false);
+}
+
+#[test]
+fn test_alloc_storage() {
+    use std::alloc::System;
+
+    /// Counts the bytes allocated and not yet freed.
+    #[derive(Clone, Default)]
+    struct Tracking(std::sync::Arc<AtomicUsize>);
+
+    unsafe impl GlobalAlloc for Tracking {
+        unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+            self.0.fetch_add(layout.size(), Ordering::Relaxed);
+            unsafe { System.alloc(layout) }
+        }
+        unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+            self.0.fetch_sub(layout.size(), Ordering::Relaxed);
+            unsafe { System.dealloc(ptr, layout) }
+        }
+    }
+
+    let alloc = Tracking::default();
+    let mut vec = BloomBitVec::with_storage(AllocStorage::new(16, alloc.clone()));
+    assert_eq!(alloc.0.load(Ordering::Relaxed), 16 * size_of::<usize>());
+    vec.set(7);
+    let copy = vec.clone();
+    assert_eq!(alloc.0.load(Ordering::Relaxed), 32 * size_of::<usize>());
+    assert!(copy.get(7) && !copy.get(8));
+    vec.clear();
+    assert!(!vec.get(7) && copy.get(7));
+    drop((vec, copy));
+    assert_eq!(alloc.0.load(Ordering::Relaxed), 0);
+
+    let empty = AllocStorage::new(0, alloc.clone());
+    assert!(empty.as_slice().is_empty());
+}
+
+#[test]
+fn test_sparse_storage() {
+    let mut sparse = SparseStorage::new(100, ());
+    sparse.update(42, |v| Some(v | 4));
+    sparse.update(7, |v| Some(v | 1));
+    sparse.update(7, |v| Some(v + 1));
+    assert_eq!((sparse.get(7), sparse.get(42), sparse.get(8)), (2, 4, 0));
+    assert_eq!(sparse.entries().collect::<Vec<_>>(), vec![(7, 2), (42, 4)]);
+    sparse.update(42, |_| Some(0));
+    assert_eq!(sparse.nonzero_words(), 1);
+
+    let dense = sparse.to_dense();
+    assert_eq!(dense[7], 2);
+    assert_eq!(SparseStorage::from_dense(&dense).entries().collect::<Vec<_>>(), vec![(7, 2)]);
+}
+
+#[test]
+fn test_write_tracking() {
+    let mut storage = WriteTracking::wrap(vec![0usize; 1024], 4096);
+    storage.update(0, |v| Some(v | 1));
+    storage.update(0, |v| Some(v | 2));
+    storage.update(1, |v| Some(v | 1));
+    storage.update(1, |v| Some(v | 1));
+    storage.update(600, |_| None);
+    let stats = storage.stats();
+    assert_eq!((stats.writes, stats.dirty_words, stats.dirty_pages), (3, 2, 1));
+    assert_eq!(stats.write_amplification(), 4096.0 / 16.0);
+
+    assert_eq!(storage.take_stats(), stats);
+    storage.update(600, |v| Some(v | 1));
+    assert_eq!(storage.stats().dirty_pages, 1);
+    storage.clear();
+    assert_eq!(storage.stats().dirty_pages, 2);
+    assert_eq!(storage.stats().dirty_words, 1024);
+}
+
+#[test]
+fn test_prefault() {
+    let mut storage = WriteTracking::wrap(vec![0usize; 10_000], 4096);
+    storage.update(42, |_| Some(1));
+    storage.advise(Advice::WillNeed);
+    storage.prefault();
+    assert_eq!(storage.stats().writes, 1);
+    assert_eq!(storage.get(42), 1);
+}
+
+#[test]
+fn test_access_sampling() {
+    let storage = AccessSampling::wrap(vec![0usize; 4096], 4096);
+    for _ in 0..10 {
+        storage.get(3 * 512);
+        storage.get(3 * 512 + 1);
+    }
+    storage.get(7 * 512);
+    assert_eq!(storage.hot_segments(0.2), vec![1536..2048, 3584..4096]);
+    assert_eq!(storage.pin_hot_segments(0.0), 0);
+    storage.reset();
+    assert_eq!(storage.hot_segments(1.0).len(), 8);
+
+    // segments of fewer words than the sampling interval are sampled too
+    let storage = AccessSampling::wrap(vec![0usize; 64], size_of::<[usize; 8]>());
+    assert_eq!(storage.segments(), 8);
+    storage.get(8);
+    storage.get(16);
+    storage.get(16);
+    storage.get(17);
+    assert_eq!(storage.hot_segments(0.25), vec![16..24, 8..16]);
+}
+
 #[test]
 fn test_size() {
     println!("{}", COUNTER_PER_SLOT);
@@ -229,10 +1472,76 @@ fn test_size() {
     assert_eq!(COUNTER_PER_SLOT, 32);
 }
 
+#[test]
+fn test_word_layout() {
+    let mut vec = BloomBitVec::new(8);
+    for index in [0, 31, 32, 63, 64, 200, 511] {
+        vec.set(index);
+    }
+    let bytes: Vec<u8> = (0..vec.storage.slots()).flat_map(|w| vec.word(w).to_le_bytes()).collect();
+    for index in 0..512 {
+        assert_eq!((bytes[index / 8] >> (index % 8)) & 1, vec.get_bit(index) as u8);
+    }
+    vec.unset(200);
+    assert!(!vec.get(200) && vec.get(511));
+}
+
+#[test]
+fn test_count_vec_gather() {
+    let mut vec = CountingVec::<_>::new(vec![0; 4]);
+    for (index, count) in [(3, 1), (17, 2), (18, 3), (63, 15)] {
+        for _ in 0..count {
+            vec.increment(index);
+        }
+    }
+    assert_eq!(vec.get_many([3, 17, 18, 63, 0]).as_slice(), &[1, 2, 3, 15, 0]);
+    let counters: Vec<usize> = vec.iter().collect();
+    assert_eq!(counters.len(), vec.counters());
+    assert!((0..vec.counters()).all(|index| counters[index] == vec.get(index)));
+}
+
 #[test]
 fn test_count_vec() {
-    let mut vec = CountingVec::new(vec![0; 10]);
+    let mut vec = CountingVec::<_>::new(vec![0; 10]);
     vec.increment(7);
 
     assert_eq!(1, vec.get(7))
-}
\ No newline at end of file
+}
+#[test]
+fn test_count_vec_width() {
+    fn saturate<const W: usize>() {
+        let mut vec = CountingVec::<_, W>::new(vec![0usize; 2]);
+        for _ in 0..70_000 {
+            vec.increment(5);
+        }
+        vec.increment(6);
+        vec.decrement(6);
+        vec.decrement(5);
+        assert_eq!(vec.counters(), 128 / W);
+        assert_eq!((vec.get(4), vec.get(5), vec.get(6)), (0, (1 << W) - 2, 0));
+        assert_eq!(vec.iter().sum::<usize>(), (1 << W) - 2);
+    }
+    saturate::<2>();
+    saturate::<4>();
+    saturate::<8>();
+    saturate::<16>();
+
+    fn wide<const W: usize>() {
+        let max = CountingVec::<Vec<usize>, W>::MAX;
+        let mut vec = CountingVec::<_, W>::new(vec![0usize; 2]);
+        assert_eq!(vec.counters(), 2 * usize::BITS as usize / W);
+        for _ in 0..70_000 {
+            vec.increment(1);
+        }
+        assert_eq!((vec.get(0), vec.get(1)), (0, 70_000));
+        vec.set(0, max - 1);
+        assert!(vec.increment(0) && !vec.increment(0));
+        assert_eq!((vec.get(0), vec.get(1)), (max, 70_000));
+        let other = vec.clone();
+        vec.add(&other);
+        assert_eq!((vec.get(0), vec.get(1)), (max, 140_000));
+    }
+    wide::<32>();
+    #[cfg(target_pointer_width = "64")]
+    wide::<64>();
+}
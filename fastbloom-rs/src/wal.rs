@@ -0,0 +1,236 @@
+use std::io::{self, BufRead, BufReader, Read, Write};
+
+use smallvec::SmallVec;
+use xxhash_rust::xxh64::xxh64;
+
+use crate::bloom::BloomFilter;
+use crate::codec::{copy_varint, put_varint, read_hashing, Reader};
+use crate::embedded::{parse_static_header, put_static_header};
+use crate::index::Indices;
+use crate::vec::StorageMut;
+
+const WAL_MAGIC: &[u8; 4] = b"FBWL";
+/// Most bytes of the index of a new bit in a record.
+const MAX_INDEX_LEN: usize = 10;
+
+/// Bloom filter which appends the bits newly set by each add to a write-ahead log, so a large
+/// filter is recovered after a crash from a rare snapshot plus a log of the adds since, see
+/// [BloomFilter::replay]. An add which sets no new bits writes nothing, a log is about as large
+/// as the bits set since the snapshot.
+///
+/// The log starts with the parameters of the filter and their checksum, and holds a record per
+/// add: its length, the indices of the new bits (varints) and a checksum of both. A record torn
+/// by a crash is dropped on replay, so only the last adds are lost. Records are written straight to the writer: wrap
+/// files in a [BufWriter](std::io::BufWriter) and [flush](WalBloomFilter::flush) it as often as
+/// adds may be lost, or sync the file for durability against power loss.
+///
+/// # Examples
+///
+/// ```rust
+/// use fastbloom_rs::{FilterBuilder, Membership};
+///
+/// let snapshot = FilterBuilder::new(100_000, 0.01).build_bloom_filter();
+/// let mut log = Vec::new();
+/// let mut filter = snapshot.clone().with_wal(&mut log).unwrap();
+/// filter.add(b"hello").unwrap();
+/// filter.add(b"world").unwrap();
+/// assert!(filter.contains(b"hello"));
+/// drop(filter);
+///
+/// // after a crash: the snapshot plus the log
+/// let mut recovered = snapshot;
+/// assert_eq!(recovered.replay(&log[..]).unwrap(), 2);
+/// assert!(recovered.contains(b"hello") && recovered.contains(b"world"));
+/// ```
+#[derive(Clone)]
+#[derive(Debug)]
+pub struct WalBloomFilter<W> {
+    bloom: BloomFilter,
+    writer: W,
+    /// Buffers of the indices of the new bits and of the record of an add.
+    bits: Vec<u8>,
+    record: Vec<u8>,
+}
+
+impl BloomFilter {
+    /// Logs the adds to this filter to `writer` from now on, see [WalBloomFilter]. Writes the
+    /// parameters of the filter first, so start a new log after each snapshot of the filter;
+    /// [BloomFilter::replay] rejects the log of another filter.
+    pub fn with_wal<W: Write>(self, mut writer: W) -> io::Result<WalBloomFilter<W>> {
+        let mut header = WAL_MAGIC.to_vec();
+        put_static_header(&mut header, &self.config);
+        let checksum = xxh64(&header, 0) as u32;
+        header.extend_from_slice(&checksum.to_le_bytes());
+        writer.write_all(&header)?;
+        Ok(WalBloomFilter { bloom: self, writer, bits: Vec::new(), record: Vec::new() })
+    }
+}
+
+impl<W: Write> WalBloomFilter<W> {
+    /// Adds the element to the filter and appends the bits it set to the log. The filter has
+    /// the element also when writing the record fails.
+    pub fn add(&mut self, element: &[u8]) -> io::Result<()> {
+        let bloom = &mut self.bloom;
+        let indices: SmallVec<[u64; 16]> = Indices::new(element, &bloom.config).collect();
+        assert!(!indices.is_empty(), "cannot add to a filter without capacity!");
+        bloom.stats.record_add();
+        self.bits.clear();
+        for &index in &indices {
+            if !bloom.bit_set.get(index as usize) {
+                bloom.bit_set.set(index as usize);
+                put_varint(&mut self.bits, index);
+            }
+        }
+        if let Some(events) = bloom.hook.get() {
+            events.on_insert(element, &indices);
+        }
+        if self.bits.is_empty() {
+            return Ok(());
+        }
+        self.record.clear();
+        put_varint(&mut self.record, self.bits.len() as u64);
+        self.record.extend_from_slice(&self.bits);
+        let checksum = xxh64(&self.record, 0) as u32;
+        self.record.extend_from_slice(&checksum.to_le_bytes());
+        self.writer.write_all(&self.record)
+    }
+
+    /// Tests whether an element is present in the filter (subject to the false positive
+    /// probability).
+    pub fn contains(&self, element: &[u8]) -> bool {
+        self.bloom.contains(element)
+    }
+
+    /// Flushes the writer of the log.
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+
+    /// Returns the filter.
+    pub fn filter(&self) -> &BloomFilter {
+        &self.bloom
+    }
+
+    /// Returns the writer of the log.
+    pub fn writer(&self) -> &W {
+        &self.writer
+    }
+
+    /// Stops logging and returns the filter and the writer of the log.
+    pub fn into_parts(self) -> (BloomFilter, W) {
+        (self.bloom, self.writer)
+    }
+}
+
+impl<S: StorageMut> BloomFilter<S> {
+    /// Sets the bits logged by a [WalBloomFilter] until its end, to recover the filter after a
+    /// crash from the snapshot the log was started on, and returns the number of records. The
+    /// log is read record by record. A record torn by the crash at the end is ignored; the next
+    /// log should start on a new snapshot, since appending behind a torn record corrupts the
+    /// log. Fails with [io::ErrorKind::InvalidData] when the log is not one of this filter or a
+    /// record doesn't match its checksum, after setting the bits of the records before it.
+    pub fn replay<R: Read>(&mut self, reader: R) -> io::Result<u64> {
+        let invalid = |error| io::Error::new(io::ErrorKind::InvalidData, error);
+        let mut reader = BufReader::new(reader);
+        let mut header = [0u8; 8].to_vec();
+        reader.read_exact(&mut header).map_err(|_| invalid("not a filter log"))?;
+        if header[..4] != WAL_MAGIC[..] {
+            return Err(invalid("not a filter log"));
+        }
+        read_hashing(&mut reader, &mut header).map_err(|_| invalid("truncated log header"))?;
+        let hashing_end = header.len();
+        header.resize(hashing_end + 12, 0);
+        reader.read_exact(&mut header[hashing_end..])
+            .map_err(|_| invalid("truncated log header"))?;
+        let checksum = u32::from_le_bytes(header[hashing_end + 8..].try_into().unwrap());
+        if checksum != xxh64(&header[..hashing_end + 8], 0) as u32 {
+            return Err(invalid("log header doesn't match its checksum"));
+        }
+        match parse_static_header(&mut Reader::new(&header[4..])) {
+            Some(config) if config.is_compatible_to(&self.config) => {}
+            _ => return Err(invalid("log of another filter")),
+        }
+
+        // a record sets at most a bit per hash
+        let max_len = self.config.hashes as usize * MAX_INDEX_LEN;
+        let (mut record, mut records) = (Vec::new(), 0);
+        while !reader.fill_buf()?.is_empty() {
+            // the read of a record fails at the end of the log only, when it is torn by a crash
+            record.clear();
+            let len = match copy_varint(&mut reader, &mut record) {
+                Ok(len) => len.and_then(|len| usize::try_from(len).ok())
+                    .filter(|&len| len <= max_len)
+                    .ok_or_else(|| invalid("record of a corrupt length"))?,
+                Err(error) if error.kind() == io::ErrorKind::UnexpectedEof => break,
+                Err(error) => return Err(error),
+            };
+            let start = record.len();
+            record.resize(start + len + 4, 0);
+            match reader.read_exact(&mut record[start..]) {
+                Ok(()) => {}
+                Err(error) if error.kind() == io::ErrorKind::UnexpectedEof => break,
+                Err(error) => return Err(error),
+            }
+            let checksum = u32::from_le_bytes(record[start + len..].try_into().unwrap());
+            if checksum != xxh64(&record[..start + len], 0) as u32 {
+                return Err(invalid("record doesn't match its checksum"));
+            }
+            let mut indices = Reader::new(&record[start..start + len]);
+            while !indices.is_empty() {
+                match indices.varint() {
+                    Some(index) if index < self.config.size => self.bit_set.set(index as usize),
+                    _ => return Err(invalid("record of bits outside the filter")),
+                }
+            }
+            records += 1;
+        }
+        Ok(records)
+    }
+}
+
+#[test]
+fn wal_replay_test() {
+    use crate::FilterBuilder;
+
+    let snapshot = FilterBuilder::new(10_000, 0.01).build_bloom_filter();
+    let mut filter = snapshot.clone().with_wal(Vec::new()).unwrap();
+    for i in 0..1000u32 {
+        filter.add(&i.to_le_bytes()).unwrap();
+    }
+    let len = filter.writer().len();
+    // adds which set no new bits are not logged
+    filter.add(&0u32.to_le_bytes()).unwrap();
+    assert_eq!(filter.writer().len(), len);
+    let (bloom, log) = filter.into_parts();
+
+    let mut recovered = snapshot.clone();
+    assert!(recovered.replay(&log[..]).unwrap() <= 1000);
+    assert_eq!(recovered.get_u8_array(), bloom.get_u8_array());
+
+    // a torn record at the end is dropped
+    let mut torn = snapshot.clone();
+    torn.replay(&log[..log.len() - 2]).unwrap();
+    assert!((0..998u32).all(|i| torn.contains(&i.to_le_bytes())));
+
+    let mut corrupt = log.clone();
+    *corrupt.last_mut().unwrap() ^= 1;
+    let error = snapshot.clone().replay(&corrupt[..]).unwrap_err();
+    assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    // a corrupt length in the middle of the log fails instead of ending it
+    let header_len = snapshot.clone().with_wal(Vec::new()).unwrap().writer().len();
+    for len in [0x7f, log[header_len] - 1] {
+        let mut corrupt = log.clone();
+        corrupt[header_len] = len;
+        let error = snapshot.clone().replay(&corrupt[..]).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+    let mut corrupt = log.clone();
+    corrupt[10] ^= 1;
+    let error = snapshot.clone().replay(&corrupt[..]).unwrap_err();
+    assert!(error.to_string().contains("header"), "{error}");
+    assert_eq!(snapshot.clone().replay(&log[..header_len]).unwrap(), 0);
+    let mut other = FilterBuilder::new(20_000, 0.01).build_bloom_filter();
+    assert_eq!(other.replay(&log[..]).unwrap_err().kind(), io::ErrorKind::InvalidData);
+    assert_eq!(snapshot.clone().replay(&b"FBST"[..]).unwrap_err().kind(),
+               io::ErrorKind::InvalidData);
+}
@@ -0,0 +1,81 @@
+use wasm_bindgen::prelude::wasm_bindgen;
+
+use crate::BloomFilter;
+
+/// Read-only Bloom filter for browsers and other WebAssembly hosts, e.g. to probe a
+/// server-generated filter for client-side cache hints. The server writes the filter with
+/// [BloomFilter::to_static_bytes], which is laid out the same on 32- and 64-bit targets, and the
+/// client loads and probes it through the `wasm-bindgen` bindings of this type (feature
+/// `wasm`). Build the module with `cargo rustc --release --target wasm32-unknown-unknown
+/// --no-default-features --features wasm --crate-type cdylib` and generate the JavaScript
+/// bindings with `wasm-bindgen --target web --out-dir pkg
+/// target/wasm32-unknown-unknown/release/fastbloom_rs.wasm`:
+///
+/// ```js
+/// import init, { WasmBloomFilter } from "./pkg/fastbloom_rs.js";
+///
+/// await init();
+/// const bytes = new Uint8Array(await (await fetch("/filter.bin")).arrayBuffer());
+/// // undefined when the bytes are not a filter
+/// const filter = WasmBloomFilter.fromBytes(bytes);
+/// const cached = filter.contains(new TextEncoder().encode("/assets/app.js"));
+/// filter.free();
+/// ```
+///
+/// # Examples
+///
+/// ```rust
+/// use fastbloom_rs::{FilterBuilder, Membership, WasmBloomFilter};
+///
+/// let mut bloom = FilterBuilder::new(1000, 0.01).build_bloom_filter();
+/// bloom.add(b"/assets/app.js");
+///
+/// let filter = WasmBloomFilter::from_bytes(&bloom.to_static_bytes()).unwrap();
+/// assert_eq!(filter.contains(b"/assets/app.js"), true);
+/// assert_eq!(filter.contains(b"/assets/missing.js"), false);
+/// ```
+#[wasm_bindgen]
+#[derive(Clone)]
+#[derive(Debug)]
+pub struct WasmBloomFilter {
+    bloom: BloomFilter,
+}
+
+#[wasm_bindgen]
+impl WasmBloomFilter {
+    /// Reads a filter written by [BloomFilter::to_static_bytes]. Returns `None` (`undefined` in
+    /// JavaScript) when `bytes` is not such a filter.
+    #[wasm_bindgen(js_name = fromBytes)]
+    pub fn from_bytes(bytes: &[u8]) -> Option<WasmBloomFilter> {
+        BloomFilter::from_static_bytes(bytes).map(|bloom| WasmBloomFilter { bloom })
+    }
+
+    /// Tests whether an element is present in the filter (subject to the false positive
+    /// probability).
+    pub fn contains(&self, element: &[u8]) -> bool {
+        self.bloom.contains(element)
+    }
+}
+
+impl WasmBloomFilter {
+    /// Returns the filter.
+    pub fn bloom_filter(&self) -> &BloomFilter {
+        &self.bloom
+    }
+}
+
+#[test]
+fn wasm_bloom_filter_test() {
+    use crate::{FilterBuilder, Membership};
+
+    let mut bloom = FilterBuilder::new(1000, 0.01).build_bloom_filter();
+    bloom.add(b"hello");
+    let bytes = bloom.to_static_bytes();
+    let filter = WasmBloomFilter::from_bytes(&bytes).unwrap();
+    assert!(filter.contains(b"hello"));
+    assert!(!filter.contains(b"world"));
+    assert_eq!(filter.bloom_filter().get_u8_array(), bloom.get_u8_array());
+
+    assert!(WasmBloomFilter::from_bytes(&bytes[..bytes.len() - 1]).is_none());
+    assert!(WasmBloomFilter::from_bytes(&[]).is_none());
+}
@@ -0,0 +1,371 @@
+use alloc::vec::Vec;
+
+use sha2::{Digest, Sha256};
+
+use crate::blocked::{block_indices, BlockedBloomFilter, BlockSize};
+use crate::builder::FilterBuilder;
+use crate::codec::{put_hashing, Reader};
+use crate::compat::FormatFeatures;
+use crate::vec::Storage;
+
+const COMMITMENT_MAGIC: &[u8; 4] = b"FBWC";
+const WITNESS_MAGIC: &[u8; 4] = b"FBMW";
+
+/// Size of the digests of the commitment tree.
+const DIGEST_LEN: usize = 32;
+
+type Digest32 = [u8; DIGEST_LEN];
+
+/// The header of a [BlockedBloomFilter] a client verifies [MembershipWitness]es against: the
+/// parameters which decide where a key lands, and the root of a SHA-256 Merkle tree over the
+/// blocks of the filter. The server signs [FilterCommitment::to_bytes] with a key of its
+/// choice; a client holding the signed commitment checks the membership of single keys without
+/// downloading the bit vector.
+///
+/// Only blocked filters are supported: their witness is one block and its Merkle path, while
+/// the `k` bits of a key of a [BloomFilter](crate::BloomFilter) are spread over the whole filter
+/// and would need `k` of them.
+///
+/// # Examples
+///
+/// ```rust
+/// use fastbloom_rs::{FilterBuilder, FilterCommitment, Membership, MembershipWitness};
+///
+/// let mut bloom = FilterBuilder::new(10_000, 0.01).build_blocked_bloom_filter();
+/// bloom.add(b"hello");
+///
+/// // server: publish (and sign) the commitment, answer witness requests
+/// let header = bloom.commitment().to_bytes();
+/// let witness = bloom.witness(b"hello").to_bytes();
+///
+/// // client: only holds the header
+/// let header = FilterCommitment::from_bytes(&header).unwrap();
+/// let witness = MembershipWitness::from_bytes(&witness).unwrap();
+/// assert!(header.verify_witness(b"hello", &witness));
+/// assert!(!header.verify_witness(b"world", &witness));
+/// ```
+#[derive(Clone, Debug)]
+pub struct FilterCommitment {
+    config: FilterBuilder,
+    blocks: u64,
+    root: Digest32,
+}
+
+/// Proof that the bits of a key are set in a [BlockedBloomFilter], see
+/// [BlockedBloomFilter::witness] and [FilterCommitment::verify_witness]. Sent to the client with
+/// [MembershipWitness::to_bytes].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MembershipWitness {
+    /// The block of the key.
+    block: u64,
+    /// The words of the block as 64-bit words, bit `b` of word `w` standing for bit index
+    /// `w * 64 + b` of the block.
+    words: Vec<u64>,
+    /// Sibling digests from the leaf up to the root, levels without a sibling are skipped.
+    path: Vec<Digest32>,
+}
+
+impl MembershipWitness {
+    /// Returns the probes of the key as `(word index, bit mask)` pairs, the word index counting
+    /// 64-bit words from the start of the filter. Only masks of keys present in the filter are
+    /// all set in their words.
+    pub fn probes(&self, key: &[u8], commitment: &FilterCommitment) -> Vec<(u64, u64)> {
+        probes(key, &commitment.config, commitment.blocks)
+    }
+
+    /// Returns the words of the block `(first word index, words)`, see
+    /// [MembershipWitness::probes].
+    pub fn block_words(&self) -> (u64, &[u64]) {
+        (self.block * self.words.len() as u64, &self.words)
+    }
+
+    /// Serializes the witness: magic, block, number of words and of path digests, then the
+    /// words (little-endian) and the digests.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(20 + self.words.len() * 8 + self.path.len() * DIGEST_LEN);
+        out.extend_from_slice(WITNESS_MAGIC);
+        out.extend_from_slice(&self.block.to_le_bytes());
+        out.extend_from_slice(&(self.words.len() as u32).to_le_bytes());
+        out.extend_from_slice(&(self.path.len() as u32).to_le_bytes());
+        for word in &self.words {
+            out.extend_from_slice(&word.to_le_bytes());
+        }
+        for digest in &self.path {
+            out.extend_from_slice(digest);
+        }
+        out
+    }
+
+    /// Restores a witness written by [MembershipWitness::to_bytes]. Returns `None` when `bytes`
+    /// is not such a witness. Whether it proves anything is up to
+    /// [FilterCommitment::verify_witness].
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        let mut reader = Reader::new(bytes);
+        if reader.take(4)? != WITNESS_MAGIC {
+            return None;
+        }
+        let block = reader.u64()?;
+        let words = reader.u32()? as u64;
+        let path = reader.u32()? as u64;
+        // the counts must match the data before anything is allocated for them
+        let rest = reader.rest();
+        if words.checked_mul(8)?.checked_add(path.checked_mul(DIGEST_LEN as u64)?)?
+            != rest.len() as u64 {
+            return None;
+        }
+        let (words, path) = rest.split_at(words as usize * 8);
+        let words = words.chunks_exact(8).map(|w| u64::from_le_bytes(w.try_into().unwrap()));
+        let path = path.chunks_exact(DIGEST_LEN).map(|d| d.try_into().unwrap());
+        Some(MembershipWitness { block, words: words.collect(), path: path.collect() })
+    }
+}
+
+/// Probes of `key` as `(word index, bit mask)` pairs of 64-bit words, one per probed word.
+fn probes(key: &[u8], config: &FilterBuilder, blocks: u64) -> Vec<(u64, u64)> {
+    let mut probes: Vec<(u64, u64)> = Vec::new();
+    for index in block_indices(key, config, blocks) {
+        let (w, mask) = (index / 64, 1u64 << (index % 64));
+        match probes.iter_mut().find(|(word, _)| *word == w) {
+            Some((_, bits)) => *bits |= mask,
+            None => probes.push((w, mask)),
+        }
+    }
+    probes
+}
+
+/// Returns the block `key` lands in.
+fn block_of(key: &[u8], config: &FilterBuilder, blocks: u64) -> u64 {
+    block_indices(key, config, blocks).base / config.block_size.bits()
+}
+
+fn block_size_id(block_size: BlockSize) -> u8 {
+    match block_size {
+        BlockSize::CacheLine => 0,
+        BlockSize::Sector => 1,
+        BlockSize::Page => 2,
+    }
+}
+
+fn block_size_from_id(id: u8) -> Option<BlockSize> {
+    match id {
+        0 => Some(BlockSize::CacheLine),
+        1 => Some(BlockSize::Sector),
+        2 => Some(BlockSize::Page),
+        _ => None,
+    }
+}
+
+/// Leaf digest of block `block`, domain separated from the inner nodes.
+fn leaf(block: u64, words: &[u64]) -> Digest32 {
+    let mut hasher = Sha256::new();
+    hasher.update([0u8]);
+    hasher.update(block.to_le_bytes());
+    for word in words {
+        hasher.update(word.to_le_bytes());
+    }
+    hasher.finalize().into()
+}
+
+fn node(left: &Digest32, right: &Digest32) -> Digest32 {
+    let mut hasher = Sha256::new();
+    hasher.update([1u8]);
+    hasher.update(left);
+    hasher.update(right);
+    hasher.finalize().into()
+}
+
+/// Returns the next level of the tree; the last node of an odd level moves up as is.
+fn parents(level: &[Digest32]) -> Vec<Digest32> {
+    level.chunks(2)
+        .map(|pair| if pair.len() == 2 { node(&pair[0], &pair[1]) } else { pair[0] })
+        .collect()
+}
+
+impl FilterCommitment {
+    /// Serializes the commitment, the bytes to sign.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(64);
+        out.extend_from_slice(COMMITMENT_MAGIC);
+        put_hashing(&mut out, &self.config, block_size_id(self.config.block_size),
+                    FormatFeatures::BLOCKED);
+        out.extend_from_slice(&self.blocks.to_le_bytes());
+        out.extend_from_slice(&self.root);
+        out
+    }
+
+    /// Restores a commitment written by [FilterCommitment::to_bytes]. Returns `None` when
+    /// `bytes` is not such a commitment.
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        let mut reader = Reader::new(bytes);
+        if reader.take(4)? != COMMITMENT_MAGIC {
+            return None;
+        }
+        let hashing = reader.hashing()?;
+        if hashing.features != FormatFeatures::BLOCKED {
+            return None;
+        }
+        let block_size = block_size_from_id(hashing.flags)?;
+        let blocks = reader.u64()?;
+        let root = reader.take(DIGEST_LEN)?.try_into().unwrap();
+        if blocks == 0 || !reader.is_empty() {
+            return None;
+        }
+        let size = blocks.checked_mul(block_size.bits())?;
+        let mut config = FilterBuilder::from_size_and_hashes(size, hashing.hashes);
+        config.block_size = block_size;
+        hashing.apply(&mut config)?;
+        Some(FilterCommitment { config, blocks, root })
+    }
+
+    /// Returns the configuration/builder of the committed filter.
+    pub fn config(&self) -> FilterBuilder {
+        self.config.clone()
+    }
+
+    /// Returns [true] if `witness` proves that all bits of `key` are set in the committed
+    /// filter, i.e. the filter (probably) contains `key`. The block of the key is derived from
+    /// the commitment, not taken from the witness.
+    pub fn verify_witness(&self, key: &[u8], witness: &MembershipWitness) -> bool {
+        let words_per_block = self.config.block_size.bits() / 64;
+        let block = block_of(key, &self.config, self.blocks);
+        if witness.block != block || witness.words.len() as u64 != words_per_block {
+            return false;
+        }
+        let first = block * words_per_block;
+        let present = probes(key, &self.config, self.blocks).into_iter()
+            .all(|(w, mask)| witness.words[(w - first) as usize] & mask == mask);
+        if !present {
+            return false;
+        }
+
+        let mut digest = leaf(block, &witness.words);
+        let mut path = witness.path.iter();
+        let (mut index, mut len) = (block, self.blocks);
+        while len > 1 {
+            let sibling = index ^ 1;
+            if sibling < len {
+                let Some(sibling_digest) = path.next() else { return false };
+                digest = if index & 1 == 0 {
+                    node(&digest, sibling_digest)
+                } else {
+                    node(sibling_digest, &digest)
+                };
+            }
+            index /= 2;
+            len = len.div_ceil(2);
+        }
+        path.next().is_none() && digest == self.root
+    }
+}
+
+impl<S: Storage> BlockedBloomFilter<S> {
+    /// Returns the words of block `block` as 64-bit words.
+    fn block_words(&self, block: u64) -> Vec<u64> {
+        let bits = self.config.block_size.bits();
+        let per_word = 64 / usize::BITS as usize;
+        let first = (block * bits / usize::BITS as u64) as usize;
+        (0..(bits / 64) as usize).map(|w| {
+            (0..per_word).fold(0u64, |acc, i| {
+                let word = self.bit_set.word(first + w * per_word + i) as u64;
+                acc | word << (i * usize::BITS as usize)
+            })
+        }).collect()
+    }
+
+    fn leaves(&self) -> Vec<Digest32> {
+        (0..self.blocks).map(|block| leaf(block, &self.block_words(block))).collect()
+    }
+
+    /// Returns the [FilterCommitment] of the filter, to be signed and handed to clients which
+    /// verify [MembershipWitness]es. Hashes the whole filter.
+    pub fn commitment(&self) -> FilterCommitment {
+        let mut level = self.leaves();
+        while level.len() > 1 {
+            level = parents(&level);
+        }
+        FilterCommitment { config: self.config.clone(), blocks: self.blocks, root: level[0] }
+    }
+
+    /// Returns the witness of `key`: the block of the key and the Merkle path of the block, so a
+    /// client verifies with [FilterCommitment::verify_witness] that the bits of the key are set.
+    /// The witness of an absent key doesn't verify. Hashes the whole filter, a server answering
+    /// many requests keeps the filter immutable and caches the witnesses.
+    pub fn witness(&self, key: &[u8]) -> MembershipWitness {
+        let block = block_of(key, &self.config, self.blocks);
+        let mut path = Vec::new();
+        let mut level = self.leaves();
+        let mut index = block as usize;
+        while level.len() > 1 {
+            if let Some(sibling) = level.get(index ^ 1) {
+                path.push(*sibling);
+            }
+            level = parents(&level);
+            index /= 2;
+        }
+        MembershipWitness { block, words: self.block_words(block), path }
+    }
+}
+
+#[test]
+fn witness_test() {
+    use crate::Membership;
+
+    for block_size in [BlockSize::CacheLine, BlockSize::Sector, BlockSize::Page] {
+        let mut builder = FilterBuilder::new(5_000, 0.01);
+        builder.block_size(block_size);
+        let mut bloom = builder.build_blocked_bloom_filter();
+        for i in 0..1000u32 {
+            bloom.add(&i.to_le_bytes());
+        }
+        let commitment = FilterCommitment::from_bytes(&bloom.commitment().to_bytes()).unwrap();
+        assert_eq!(commitment.config().block_size, block_size);
+        for i in (0..1000u32).step_by(37) {
+            let key = i.to_le_bytes();
+            let witness = bloom.witness(&key);
+            assert_eq!(MembershipWitness::from_bytes(&witness.to_bytes()).as_ref(), Some(&witness));
+            assert!(commitment.verify_witness(&key, &witness), "{block_size:?} {i}");
+            let (first, words) = witness.block_words();
+            assert!(witness.probes(&key, &commitment).iter()
+                .all(|&(w, mask)| words[(w - first) as usize] & mask == mask));
+        }
+
+        let absent = (1000..2000u32)
+            .filter(|i| !bloom.contains(&i.to_le_bytes()))
+            .map(u32::to_le_bytes)
+            .next().unwrap();
+        assert!(!commitment.verify_witness(&absent, &bloom.witness(&absent)));
+
+        // a tampered block doesn't match the root
+        let key = 7u32.to_le_bytes();
+        let mut forged = bloom.witness(&absent);
+        forged.words.iter_mut().for_each(|word| *word = u64::MAX);
+        assert!(!commitment.verify_witness(&absent, &forged));
+        let mut truncated = bloom.witness(&key);
+        truncated.path.pop();
+        assert!(!commitment.verify_witness(&key, &truncated));
+    }
+    assert!(FilterCommitment::from_bytes(b"FBWC").is_none());
+}
+
+#[test]
+fn witness_bytes_test() {
+    use crate::Membership;
+
+    let mut bloom = FilterBuilder::new(1000, 0.01).build_blocked_bloom_filter();
+    bloom.add(b"hello");
+    let bytes = bloom.witness(b"hello").to_bytes();
+    assert_eq!(bytes.len(), 20 + 8 * 8 + bloom.witness(b"hello").path.len() * DIGEST_LEN);
+    for len in 0..bytes.len() {
+        assert!(MembershipWitness::from_bytes(&bytes[..len]).is_none(), "{len}");
+    }
+    let mut longer = bytes.clone();
+    longer.push(0);
+    assert!(MembershipWitness::from_bytes(&longer).is_none());
+    // counts far beyond the data are rejected without allocating
+    let mut huge = bytes.clone();
+    huge[12..16].copy_from_slice(&u32::MAX.to_le_bytes());
+    assert!(MembershipWitness::from_bytes(&huge).is_none());
+    let mut magic = bytes;
+    magic[0] = b'X';
+    assert!(MembershipWitness::from_bytes(&magic).is_none());
+}
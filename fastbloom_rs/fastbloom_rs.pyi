@@ -108,6 +108,13 @@ class PyBloomFilter(object):
     def intersect(self, other: PyBloomFilter) -> bool:
         ...
 
+    def serialize(self) -> bytes:
+        ...
+
+    @staticmethod
+    def deserialize(data: bytes) -> PyBloomFilter:
+        ...
+
     @staticmethod
     def from_bytes(array: bytes, hashes: int) -> PyBloomFilter:
         ...
@@ -208,6 +215,16 @@ class PyCountingBloomFilter(object):
     def counter_at(self, index: int) -> int:
         ...
 
+    def union(self, other: PyCountingBloomFilter) -> bool:
+        ...
+
+    def serialize(self) -> bytes:
+        ...
+
+    @staticmethod
+    def deserialize(data: bytes) -> PyCountingBloomFilter:
+        ...
+
     @staticmethod
     def from_bytes(array: bytes, hashes: int, enable_repeat_insert: bool) -> PyCountingBloomFilter:
         ...
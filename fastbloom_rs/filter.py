@@ -335,6 +335,26 @@ class BloomFilter(object):
         """
         return self._py_bloom.intersect(other._py_bloom)
 
+    def serialize(self) -> bytes:
+        """
+        Serialize the filter with its parameters, in the format `BloomFilter::to_static_bytes`
+        writes in Rust.
+
+        :return: bytes
+        """
+        return self._py_bloom.serialize()
+
+    @staticmethod
+    def deserialize(data: bytes) -> "BloomFilter":
+        """
+        Load a filter written by `serialize` or by `BloomFilter::to_static_bytes` in Rust.
+        Raises ValueError for invalid data.
+
+        :param data: serialized filter
+        :return:
+        """
+        return BloomFilter(PyBloomFilter.deserialize(data))
+
     def __contains__(self, item: Union[str, int, bytes]):
         return self.contains(item)
 
@@ -656,6 +676,36 @@ class CountingBloomFilter(object):
         """
         self._py_counting_bloom.clear()
 
+    def union(self, other: "CountingBloomFilter") -> bool:
+        """
+        Adds the counters of a compatible counting bloom filter, so this filter counts the
+        elements of both. Returns False if the filters are not compatible.
+
+        :param other:
+        :return:
+        """
+        return self._py_counting_bloom.union(other._py_counting_bloom)
+
+    def serialize(self) -> bytes:
+        """
+        Serialize the filter with its parameters, in the format
+        `CountingBloomFilter::snapshot_compressed` writes in Rust.
+
+        :return: bytes
+        """
+        return self._py_counting_bloom.serialize()
+
+    @staticmethod
+    def deserialize(data: bytes) -> "CountingBloomFilter":
+        """
+        Load a filter written by `serialize` or by `CountingBloomFilter::snapshot_compressed` in
+        Rust. Raises ValueError for invalid data.
+
+        :param data: serialized filter
+        :return:
+        """
+        return CountingBloomFilter(PyCountingBloomFilter.deserialize(data))
+
     def __contains__(self, item: Union[str, int, bytes]):
         return self.contains(item)
 
@@ -140,3 +140,21 @@ def test_batch_check():
 
     bloom.add_bytes_batch(list(map(lambda x: bytes(x), inserts)))
     assert bloom.contains_bytes_batch(list(map(lambda x: bytes(x), checks))) == results
+
+
+def test_serialize():
+    bloom = BloomFilter(100_000, 0.01)
+    bloom.add('hello')
+    bloom.add(87)
+
+    restored = BloomFilter.deserialize(bloom.serialize())
+    assert 'hello' in restored
+    assert 87 in restored
+    assert 'world' not in restored
+    assert restored.get_bytes() == bloom.get_bytes()
+
+    try:
+        BloomFilter.deserialize(b'not a filter')
+        assert False
+    except ValueError:
+        pass
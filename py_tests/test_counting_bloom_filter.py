@@ -133,3 +133,21 @@ def test_batch():
 
     bloom.add_bytes_batch(list(map(lambda x: bytes(x), inserts)))
     assert bloom.contains_bytes_batch(list(map(lambda x: bytes(x), checks))) == results
+
+
+def test_union_and_serialize():
+    builder = FilterBuilder(100_000, 0.01)
+    cbf = builder.build_counting_bloom_filter()  # type: CountingBloomFilter
+    other = builder.build_counting_bloom_filter()  # type: CountingBloomFilter
+    cbf.add('hello')
+    other.add('hello')
+    other.add('world')
+
+    assert cbf.union(other)
+    assert cbf.estimate_count('hello') == 2
+    assert 'world' in cbf
+
+    restored = CountingBloomFilter.deserialize(cbf.serialize())
+    assert restored.estimate_count('hello') == 2
+    assert restored.estimate_count('world') == 1
+    assert 'python' not in restored
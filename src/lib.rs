@@ -1,9 +1,13 @@
+#[cfg(feature = "pyo3")]
 use pyo3::prelude::*;
 
+#[cfg(feature = "pyo3")]
 use crate::pybloom::{PyBloomFilter, PyFilterBuilder, PyCountingBloomFilter};
 
+#[cfg(feature = "pyo3")]
 pub mod pybloom;
 
+#[cfg(feature = "pyo3")]
 #[pymodule]
 fn fastbloom_rs(_py: Python<'_>, m: &PyModule) -> PyResult<()> {
     m.add_class::<PyBloomFilter>().unwrap();
@@ -1,7 +1,8 @@
+use pyo3::exceptions::PyValueError;
 use pyo3::prelude::*;
 use pyo3::types::PyBytes;
 
-use fastbloom_rs::{BloomFilter, CountingBloomFilter, Deletable, FilterBuilder, Hashes, Membership};
+use fastbloom_rs::{BloomFilter, CountingBloomFilter, FilterBuilder, Hashes, Membership};
 
 #[pyclass]
 pub struct PyFilterBuilder {
@@ -25,7 +26,7 @@ impl PyFilterBuilder {
     }
 
     pub fn build_counting_bloom_filter(&mut self) -> PyResult<PyCountingBloomFilter> {
-        let filter = self.filter_builder.build_counting_bloom_filter();
+        let filter = self.filter_builder.build_counting_bloom_filter(());
         Ok(PyCountingBloomFilter { counting_bloom_filter: filter })
     }
 
@@ -163,6 +164,19 @@ impl PyBloomFilter {
         Ok(self.bloomfilter.intersect(&other.bloomfilter))
     }
 
+    /// The filter with its parameters, as `BloomFilter::to_static_bytes` writes it in Rust.
+    pub fn serialize<'py>(&self, py: Python<'py>) -> &'py PyBytes {
+        PyBytes::new(py, &self.bloomfilter.to_static_bytes())
+    }
+
+    #[staticmethod]
+    pub fn deserialize(bts: &[u8]) -> PyResult<Self> {
+        match BloomFilter::from_static_bytes(bts) {
+            Some(bloomfilter) => Ok(PyBloomFilter { bloomfilter }),
+            None => Err(PyValueError::new_err("invalid bloom filter bytes")),
+        }
+    }
+
     pub fn get_hash_indices_int(&self, element: i64) -> PyResult<Vec<u64>> {
         Ok(self.bloomfilter.get_hash_indices(&i64::to_le_bytes(element)))
     }
@@ -284,18 +298,37 @@ impl PyCountingBloomFilter {
         Ok(self.counting_bloom_filter.hashes())
     }
 
-    pub fn get_bytes(&self) -> PyResult<&[u8]> {
-        Ok(self.counting_bloom_filter.get_u8_array())
+    pub fn get_bytes<'py>(&self, py: Python<'py>) -> PyResult<&'py PyBytes> {
+        Ok(PyBytes::new(py, &words_to_bytes(self.counting_bloom_filter.storage())))
     }
 
     pub fn get_int_array(&self) -> PyResult<Vec<u32>> {
-        Ok(Vec::from(self.counting_bloom_filter.get_u32_array()))
+        let bytes = words_to_bytes(self.counting_bloom_filter.storage());
+        Ok(bytes.chunks_exact(4).map(|int| u32::from_ne_bytes(int.try_into().unwrap())).collect())
     }
 
     pub fn clear(&mut self) {
         self.counting_bloom_filter.clear()
     }
 
+    pub fn union(&mut self, other: &PyCountingBloomFilter) -> PyResult<bool> {
+        Ok(self.counting_bloom_filter.union(&other.counting_bloom_filter))
+    }
+
+    /// The filter with its parameters, as `CountingBloomFilter::snapshot_compressed` writes it
+    /// in Rust.
+    pub fn serialize<'py>(&self, py: Python<'py>) -> &'py PyBytes {
+        PyBytes::new(py, &self.counting_bloom_filter.snapshot_compressed())
+    }
+
+    #[staticmethod]
+    pub fn deserialize(bts: &[u8]) -> PyResult<Self> {
+        match CountingBloomFilter::from_snapshot_compressed(bts) {
+            Some(counting_bloom_filter) => Ok(PyCountingBloomFilter { counting_bloom_filter }),
+            None => Err(PyValueError::new_err("invalid counting bloom filter bytes")),
+        }
+    }
+
     pub fn get_hash_indices_int(&self, element: i64) -> PyResult<Vec<u64>> {
         Ok(self.counting_bloom_filter.get_hash_indices(&i64::to_le_bytes(element)))
     }
@@ -327,17 +360,30 @@ impl PyCountingBloomFilter {
     #[staticmethod]
     pub fn from_bytes(array: &[u8], hashes: u32, enable_repeat_insert: bool) -> PyResult<Self> {
         Ok(PyCountingBloomFilter {
-            counting_bloom_filter: CountingBloomFilter::from_u8_array(array, hashes, enable_repeat_insert)
+            counting_bloom_filter:
+            CountingBloomFilter::from_storage(bytes_to_words(array), hashes, enable_repeat_insert)
         })
     }
 
     #[staticmethod]
     pub fn from_int_array(array: Vec<u32>, hashes: u32, enable_repeat_insert: bool) -> PyResult<Self> {
+        let bytes: Vec<u8> = array.iter().flat_map(|int| int.to_ne_bytes()).collect();
         Ok(PyCountingBloomFilter {
             counting_bloom_filter:
-            CountingBloomFilter::from_u32_array(array.as_slice(), hashes, enable_repeat_insert)
+            CountingBloomFilter::from_storage(bytes_to_words(&bytes), hashes, enable_repeat_insert)
         })
     }
 }
 
+/// The words of a counting filter in memory order.
+fn words_to_bytes(words: &[usize]) -> Vec<u8> {
+    words.iter().flat_map(|word| word.to_ne_bytes()).collect()
+}
+
+fn bytes_to_words(bytes: &[u8]) -> Vec<usize> {
+    bytes.chunks_exact(std::mem::size_of::<usize>())
+        .map(|word| usize::from_ne_bytes(word.try_into().unwrap()))
+        .collect()
+}
+
 